@@ -1,10 +1,69 @@
 // SPDX-License-Identifier: MIT
 // Copyright 2025. Triad National Security, LLC.
 
+use std::process::Command;
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("cargo::rerun-if-changed=halo.capnp");
 
     capnpc::CompilerCommand::new().file("halo.capnp").run()?;
 
+    build_info();
+
     Ok(())
 }
+
+/// Record details of the build for `halo version`. None of them are essential, so any which can
+/// not be found (e.g., when building from a source tarball without git) are reported as "unknown".
+fn build_info() {
+    // Rebuild when the checked out commit changes, rather than on every change to the source:
+    println!("cargo::rerun-if-changed=.git/HEAD");
+    println!("cargo::rerun-if-changed=.git/refs/heads");
+    println!("cargo::rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    let commit = command_output("git", &["rev-parse", "--short=12", "HEAD"]);
+    println!("cargo::rustc-env=HALO_BUILD_COMMIT={commit}");
+
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc = command_output(&rustc, &["--version"]);
+    println!("cargo::rustc-env=HALO_BUILD_RUSTC={rustc}");
+
+    // Honor SOURCE_DATE_EPOCH so that reproducible builds get the same timestamp:
+    let time = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|since| since.as_secs())
+                .unwrap_or(0)
+        });
+    println!("cargo::rustc-env=HALO_BUILD_TIME={time}");
+
+    let mut features: Vec<String> = std::env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(str::to_string))
+        .map(|feature| feature.to_lowercase().replace('_', "-"))
+        .collect();
+    features.sort();
+    println!(
+        "cargo::rustc-env=HALO_BUILD_FEATURES={}",
+        features.join(",")
+    );
+
+    let profile = std::env::var("PROFILE").unwrap_or_else(|_| "unknown".to_string());
+    println!("cargo::rustc-env=HALO_BUILD_PROFILE={profile}");
+}
+
+/// The trimmed stdout of running `program` with `args`, or "unknown" if it could not be run or
+/// failed.
+fn command_output(program: &str, args: &[&str]) -> String {
+    Command::new(program)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|stdout| stdout.trim().to_string())
+        .filter(|stdout| !stdout.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
@@ -92,6 +92,28 @@ halo_manager --config cluster.yaml
 or, if it is not specified on the command line, a default location
 of `/etc/halo/halo.conf` is used.
 
+A large cluster's config can be split across several files, e.g. a base config shared by every site
+and overrides for one site, by giving `--config` more than once, or by giving a directory,
+which stands for the `.yaml`, `.yml`, and `.conf` files in it, in order of name:
+```bash
+halo_manager --config base.yaml --config site.yaml
+halo_manager --config /etc/halo/conf.d
+```
+The files are merged in order, so that a later file takes precedence over an earlier one:
+
+- A host is merged with the host of the same hostname in earlier files:
+  its resources are added to theirs, and its other fields, such as `fence_agent`, override theirs.
+- `failover_pairs` are added to those of earlier files.
+- Mappings, such as `tls`, `quorum`, and `groups`, are merged key by key,
+  so a later file can override a single group, or a single certificate path.
+- Any other setting, such as `poll_interval`, replaces the earlier one.
+
+A resource may only be defined in one file; defining it in a second file is an error,
+even under another host.
+`halo validate` and `halo config dump` work on the merged config;
+`halo validate -v` prints the file that each resource is defined in,
+and `halo config dump` shows it as the `source` of each resource.
+
 == Automatically generating a config file
 
 The `halo discover` command is available to generate a configuration file
@@ -100,6 +122,9 @@ as long as certain assumptions hold true.
 
 This command works by connecting to cluster nodes using `ssh` and running
 `zpool list` and `mount -t lustre`.
+Each kind of resource is found by its own discovery agent: `zpool` for zpools, and `lustre` for Lustre targets.
+All of them are run by default; `--agent-type` runs only some of them, e.g. `--agent-type zpool`.
+The JSON and CSV outputs say which agent found each resource.
 
 Thus, for this command to generate a useful configuration,
 the following conditions must be met:
@@ -141,12 +166,60 @@ The command writes out a YAML file to stdout.
 The generated YAML file will typically require some manual editing to add fencing information,
 since the `discover` command is not designed to detect that information.
 
+To start a new config file instead, pass `--emit-config` with the path to write it to:
+```bash
+$ halo discover --emit-config /etc/halo/halo.conf lu-mds[00-01],lu-oss[00-05]
+```
+In the starter config, each resource group has `managed: false`, so that the management daemon does not act on it until it is managed with `halo manage`,
+and its `home_node` is the host that it was found on.
+An existing file is only replaced with `--force`.
+Failover pairs and fencing information still have to be added by hand;
+the command lists the problems that the config has, as `halo validate` would.
+
+Later, `--diff` checks that the config still matches the cluster,
+listing the resources which were found but are not in the config, those in the config which were not found,
+and those whose kind, required resource, or parameters differ:
+```bash
+$ halo --config /etc/halo/halo.conf discover --diff lu-mds[00-01],lu-oss[00-05]
++ oss05e2 (heartbeat/ZFS on lu-oss05)
+~ oss00e0/ost0 parameters.mountpoint: "/mnt/ost0" -> "/mnt/ost1"
+```
+The exit status is 1 if anything differs, and `--output json` prints the differences as JSON, for use in CI.
+Only what discovery can see is compared: parameters are matched by key, in any order,
+and parameters which are only in the config are not reported.
+
+Discovery also works as a quick inventory of the cluster.
+`--where` keeps only the hosts which match a query on their attributes:
+`hostname`, `reachable`, `resources`, `zpools`, `targets`, and `error`.
+`--select` prints just the attributes asked for, as a table, or as CSV or JSON with `--output`:
+```bash
+$ halo discover --where 'zpools<2 || reachable==false' --select hostname,zpools,targets lu-oss[00-05]
+hostname  zpools  targets
+lu-oss03  1       1
+```
+`--output wide` prints a table of every host instead, with the number of each kind of resource found on it, and why it could not be reached, fitted to the width of the terminal.
+
 == File Format
 
 The YAML file consists of a list of hosts.
 Each host consists of a list of resources, along with the resources' parameters,
 and fencing information for the host.
 
+A `#` starts a comment, which runs to the end of the line, so the config can be annotated anywhere:
+```yaml
+hosts:
+- hostname: oss00  # the primary server of the pair
+  resources:
+    # ost0 lives on this pool:
+    zpool0: {kind: heartbeat/ZFS, parameters: {pool: pool0}}
+```
+Since YAML also accepts JSON, a config may be written in JSON instead, with `#` comments,
+trailing commas within braces and brackets, and keys without quotes.
+Comments must start with `#` there too: the `//` and `/* */` comments of JSON5 are not accepted,
+and a config which has them fails to load with an error saying so.
+HALO only reads the config files and never writes them, so the comments are kept when the config is reloaded.
+`halo discover` writes a new config without comments.
+
 === Fencing information
 
 Fencing information for hosts is specified in the `fence_agent` and `fence_parameters` fields.
@@ -170,6 +243,130 @@ Resources are managed using OCF Resource Agent scripts.
 Those scripts expect parameters that describe the resource to be managed,
 and those parameters are specified in the `parameters` field.
 
+For the kinds of resources that HALO knows about, `heartbeat/ZFS` and `lustre/Lustre`,
+the parameters are checked against a schema when the config is loaded, by `halo validate` and `halo_manager --config-check`, and by `halo set-param`:
+a `heartbeat/ZFS` resource must set `pool`, and a `lustre/Lustre` resource must set `target` and `mountpoint`, which must be an absolute path.
+A required parameter which is missing, or a parameter whose value is of the wrong kind, is reported with the field it is in, e.g.
+`hosts[0].resources.ost0.parameters.mountpoint: expected an absolute path, but got "mnt/ost0"`, and the manager refuses to start with it.
+Parameters which the schema does not list are passed to the resource agent as they are, and resources of other kinds are not checked.
+`halo describe --schema lustre/Lustre` prints the parameters that a kind takes.
+
+=== Dependencies
+
+A resource can also depend on resources outside of its own tree, such as a Lustre OST on the MGS,
+by listing them in the optional `depends_on` field:
+```yaml
+    ost0:
+      kind: lustre/Lustre
+      requires: zpool_oss00
+      depends_on:
+      - mgs
+```
+In Manage mode, a resource is not started until every resource that it depends on is running.
+Until then, its status is `Blocked`, and `status` shows the resource that it is waiting for.
+This is also the case when a dependency fails to start, in which case it needs admin intervention before the resources that depend on it are started.
+Stopping a resource with `halo stop` first stops the running resources that depend on it, in reverse dependency order,
+and `halo start` refuses to start a resource whose dependencies are not running.
+Dependencies do not affect failover: a resource group is failed over or migrated without stopping the resources in other groups that depend on it.
+
+A dependency that does not exist, or a cycle of dependencies, is reported by `halo validate`,
+and the management daemon refuses to start with one.
+
+=== Groups
+
+Resources which are administered together, such as all of the OSTs of a filesystem,
+can be given a name in the optional `groups` section:
+```yaml
+groups:
+  osts:
+  - ost0
+  - ost1
+```
+A group is referred to as `@name` on the command line.
+`halo manage @osts` and `halo unmanage @osts` update every resource group with a member in the group,
+and `halo migrate @osts --to lu-oss01` moves them all to the same host.
+The migration is all or nothing: every resource group is checked before any is moved,
+and if one fails to move, the ones that were already moved are moved back.
+`halo status` lists the members of each group under a header line of its own.
+
+=== Placement
+
+By default, a resource group's home node is the host that it is listed under,
+and it may be failed over to that host's partner.
+The root resource of a resource group can change this with two optional fields:
+```yaml
+    zpool_oss00:
+      kind: heartbeat/ZFS
+      parameters:
+        pool: oss00e0
+      home_node: lu-oss00
+      allowed_nodes:
+      - lu-oss00
+```
+- `home_node` is the host that the resource group prefers to run on:
+  either the host that it is listed under, or that host's failover partner.
+  When it is set, a resource group that was failed over is moved back to its home node,
+  in Manage mode, once the home node is healthy again.
+- `allowed_nodes` lists the hosts that the resource group may be started on,
+  which must include its home node.
+  A resource group that is not allowed on the failover partner is never failed over;
+  it is started again once its home node is back.
+
+The `status` command shows the home node and allowed nodes of each resource with `-v`.
+
+The root resource may also set `managed: false`, for a resource group which the management daemon should leave unmanaged when it starts,
+until it is managed with `halo manage`.
+
+A resource group which is not running on either host is started on its home node.
+To start it on the better host of the pair instead, give the hosts `attributes`, and the config `placement_weights`:
+```yaml
+hosts:
+- hostname: lu-oss00
+  attributes:
+    memory_gb: 256
+  ...
+placement_weights:
+  memory_gb: 1
+  running_groups: -50
+```
+The score of a host is the sum of each of its attributes times the weight of that attribute, where an attribute that a host does not have counts as 0.
+`running_groups` is kept by the management daemon: it is the number of resource groups running on the host when the score is taken.
+In Manage mode, a resource group that is running on neither host is started on the one with the higher score of those it may start on,
+and a tie goes to the host whose name comes first.
+`allowed_nodes` and standby still decide which hosts a group may start on, and `home_node` still moves it back home after it is started elsewhere.
+The daemon logs the host that it chose, with its score, and records the choice in `halo history`.
+A failover always goes to the partner, since it is the only other host that the group may run on.
+Changes to the weights or attributes take effect once the daemon is restarted.
+
+=== Colocation and Anti-Affinity
+
+Some resource groups must run on the same host, and some must not.
+Each entry of `colocation` is a set of resources whose resource groups must run together,
+and each entry of `anti_affinity` is a set whose resource groups must run apart:
+```yaml
+colocation:
+- [ost0, ost0_exporter]
+anti_affinity:
+- [ost0, ost1]
+```
+A resource group which is not running is started on the host of a group that it must run with, if one is running.
+If none of them is running, each is started on a host that they may all run on, preferring the home node of the one whose ID comes first.
+A host which is running a group that it must not run with is avoided when there is another host that it may start on.
+Migrating a resource group with `halo migrate` moves the groups which must run with it along with it,
+and a migration which would put a group on the same host as one that it must not run with is refused.
+
+A failover or standby can leave two groups without anywhere else to run, so they are put together anyway.
+While a resource group breaks one of its constraints, `halo status` and `halo describe` show a warning for each of its resources,
+and the daemon logs it once.
+Once both hosts can run resources again, the daemon moves one of the groups to keep to the constraint:
+the one which is failed over, or if neither or both are, the one whose ID comes last.
+A group which prefers its home node is only moved back there if that keeps to its constraints.
+
+The management daemon refuses to start with constraints which can not be kept,
+such as two resources in the same resource group which must run apart,
+or resources which must run together but have no host that they may all run on.
+Changes to the constraints take effect once the daemon is restarted.
+
 === Failover Pairs
 
 If HALO is being used to manage a cluster in which nodes are arranged in failover pairs,
@@ -185,6 +382,25 @@ failover_pairs:
 ...
 ```
 
+=== Environment Variables
+
+Secrets and site-specific paths can be kept out of the config file by referring to environment variables,
+which are substituted when the config is loaded, before it is validated.
+Substitution applies to the values of resource `parameters` and `fence_parameters`, and to `fence_agent`; no other field is substituted.
+```yaml
+  fence_agent: redfish
+  fence_parameters:
+    username: admin
+    password: ${REDFISH_PASSWORD}
+```
+- `${VAR}` is replaced with the value of `VAR`. It is an error for `VAR` not to be set:
+  the config fails to load, and `halo validate` reports every field that refers to an unset variable.
+- `${VAR:-default}` is replaced with `default` when `VAR` is not set or is empty.
+- `$$` stands for a literal `$`. A `$` which is not followed by `{` is left as it is.
+
+The variables are read from the environment of the program that loads the config:
+the management daemon, when it starts or reloads its config, or the CLI, for commands like `halo validate`.
+
 = Remote Agent
 
 The HALO remote agent runs the `halo_remote` program.
@@ -209,6 +425,50 @@ MTLS is available for the management daemon and remote agent to mutually authent
 It is not enabled by default because HALO is designed to be run on a secure management network.
 Normally, the remote agent only listens on a private management IP address.
 However, if additional security is desired, the `--mtls` option can be passed.
+It must be passed to both the remote agent and the management daemon.
+
+With `--mtls`, both daemons load their certificates at startup, and refuse to start if any of them are missing.
+The certificate and key paths can be set in a `tls` section of the config file:
+```yaml
+tls:
+  ca_cert: /etc/halo/ca.crt
+  client_cert: /etc/halo/client.crt
+  client_key: /etc/halo/client.key
+```
+Any path that is not set in the config file is taken from its environment variable
+(`HALO_CA_CERT`, `HALO_SERVER_CERT`, `HALO_SERVER_KEY`, `HALO_CLIENT_CERT`, or `HALO_CLIENT_KEY`),
+or else from its default location in `/etc/halo/`.
+The remote agent does not read the config file, so its paths can only be set with the environment variables.
+
+mTLS does not apply to the unix domain socket that the CLI utility uses to talk to the management daemon;
+access to that socket is controlled by filesystem permissions.
+Passing `--mtls` to a `halo` subcommand that talks to the management daemon over its unix socket is rejected with an error.
+When the management daemon listens on a TCP address (see @tcp-listen) and `--mtls` is given,
+connections on that address must use mTLS, and the CLI utility must also be passed `--mtls`.
+The CLI utility loads its client certificate from the environment variables or default locations listed above.
+
+mTLS authenticates the hosts at either end of a connection, not the user making requests.
+For that, the management daemon can also require an auth token, given with its `--auth-token` option,
+or better, in the `auth_token` field of the config file, since command line options are visible to every user of the host:
+```yaml
+auth_token: 4c0b16c7e2a5d83f
+```
+With a token, the daemon only serves requests which carry it in an `Authorization: Bearer` header,
+on the unix domain socket and the TCP address alike, and replies to others with 401.
+The health checks at `/` and `/ready` are served without it.
+The CLI utility sends the token from the `HALO_AUTH_TOKEN` environment variable, or else from the `auth_token` field of its config file,
+so the config file should only be readable by the users who may manage the cluster.
+
+Monitoring, such as scraping `/metrics`, only needs to read from the daemon,
+so it can be given a read-only token instead, with `--read-only-token` or the `read_only_token` field of the config file:
+```yaml
+auth_token: 4c0b16c7e2a5d83f
+read_only_token: 91e3a07c5d2b6f48
+```
+The daemon serves GET requests which carry the read-only token, and replies with 403 to any other request with it,
+since every request which changes something, such as a failover or `halo manage`, is a POST, PATCH, or DELETE.
+A read-only token can only be given along with an auth token.
+If the two are the same, the token gives full access, and the daemon logs a warning at startup.
 
 == OCF Resource Agents
 
@@ -228,14 +488,217 @@ The management daemon can run in two modes:
 and *observe* mode in which it monitors the state of the cluster but never takes action.
 
 Currently, observe mode is the default. Manage mode must be requested using the `--manage-resources` option.
+The daemon logs which mode it is running in when it starts.
+In a cluster without failover pairs, there is nowhere to fail resources over to, so manage mode only starts stopped resources on their home node.
 A future version of HALO will make manage mode the default.
 
+Before switching a cluster to manage mode, the daemon can be run with `--dry-run` to preview what it would do.
+It then observes the cluster, and logs the actions that manage mode would take, such as
+"Dry run: would start resource group ost0 on oss01.", without taking any of them.
+Requests from the CLI utility are also treated as dry runs while the daemon runs this way.
+A single command can be previewed the same way with `halo --dry-run`, e.g. `halo --dry-run migrate ost0 --to oss01`.
+
+During an incident, a daemon in manage mode can be switched to observe mode without restarting it, with `halo mode observe`,
+and back again with `halo mode manage`; `halo mode` prints the mode that it is in.
+From its next pass on, the daemon only monitors the resources: it does not start, move, or fail them over, nor fence hosts,
+though commands such as `halo start` and `halo migrate` still act on the resources that they are given.
+Each switch is logged, and the daemon goes back to the mode on its command line when it restarts.
+A daemon that was started in observe mode can not be switched to manage mode, since it does not run the loops that manage the resources.
+
+For a longer maintenance window, `halo maintenance on` freezes the automatic management of the whole cluster, and `halo maintenance off` lifts the freeze.
+While it is on, the daemon keeps monitoring the resources, but starts, stops, moves, and fails over none of them, nor fences hosts,
+whatever its mode and whether or not each resource is managed; as with observe mode, `halo start` and `halo migrate` still act on the resources that they are given.
+Unlike the mode, maintenance mode is recorded in a file, `/var/lib/halo/maintenance` by default, so that it stays on when the daemon restarts.
+The file can be moved with the `--maintenance-file` option, or the `maintenance_file` field of the config file.
+`halo status` and `halo top` print a line at the top while maintenance mode is on, and `halo maintenance` prints whether it is on, and since when.
+
+For checks from cron or another scheduler, `halo_manager --once` checks on every resource group once, in observe mode or with `--manage-resources`, and exits instead of running as a daemon.
+It prints the status of each resource, and how a pass in manage mode changed it, followed by any problems:
+resources which failed, resource groups which could not be checked within ten poll intervals, e.g. because their hosts are unreachable,
+and resource groups which manage mode would still start or move.
+It exits with status 0 if there are none, and 3 otherwise. It does not open the socket, so the CLI can not reach it while it runs.
+
+== Polling
+
+The daemon checks on each resource group in passes, waiting 5 seconds between them by default.
+The wait can be changed with the `--poll-interval` option, in milliseconds, or the `poll_interval` field of the config file:
+a shorter interval notices failures sooner, but sends more requests to the remote agents.
+When many daemons are restarted together, `--poll-jitter` (or `poll_jitter`) adds up to that many more milliseconds, chosen at random, to each wait,
+so that they do not poll their agents in lockstep.
+The options take precedence over the config file, and an interval of 0 is rejected at startup.
+
+To see where the time goes on a large cluster, the daemon can be run with `--profile`.
+It then times each pass over a resource group, how much of it was spent monitoring the resources and how much acting on them,
+and how long the remote agent took to check on each resource.
+A summary of each pass is logged, naming the slowest resources, e.g.
+"Profile: resource group ost0, pass 12: 35.2ms (monitor 30.1ms, actions 5.1ms); slowest: ost0 30.1ms, zpool0 12.0ms",
+and `--profile-slowest` sets how many are named, 3 by default.
+The timings are also added to histograms at `/metrics`: `halo_pass_duration_seconds`, with a `phase` label of `total`, `monitor`, or `actions`,
+and `halo_resource_monitor_duration_seconds`.
+Without `--profile`, nothing is timed, and these metrics are left out.
+
+== Retrying Failed Actions
+
+When a resource fails to start or stop, the daemon marks it as being in error and tries again later,
+waiting 1 second after the first failure and twice as long after each one after that, up to a minute.
+After 5 failures in a row, it gives up: the resource is reported as `FailedStart` or `FailedStop`, and is left alone,
+so that a resource which can not start does not keep the daemon busy, or fill its log.
+`halo status` prints how many times a resource has failed, and when it will next be tried.
+Once the problem is fixed, `halo start --clear-failed` (or `halo stop --clear-failed`) clears the failure and acts on the resource again.
+
+The attempts and the waits can be changed with the `--retry-attempts`, `--retry-backoff`, and `--retry-backoff-max` options, in milliseconds,
+or the `retry_attempts`, `retry_backoff`, and `retry_backoff_max` fields of the config file.
+
+A resource agent which hangs would otherwise hold up its resource group for good,
+so the daemon gives up on a start or stop which takes longer than 5 minutes.
+It logs how long the agent ran, and reports the resource as `FailedStart` or `FailedStop` right away, without retrying it,
+since an agent that hung once is likely to hang again; the other resources are managed as usual.
+The limits can be set for each resource, in seconds, with its `start_timeout` and `stop_timeout` fields:
+```yaml
+    ost0:
+      kind: lustre/Lustre
+      start_timeout: 600
+      stop_timeout: 120
+```
+
+When many resources need to be started at once, such as when a whole cluster is recovered, running all of their resource agents together can overwhelm the hosts.
+So the daemon runs at most 8 starts and stops at a time, across the cluster, and the rest wait for their turn;
+a resource is still only started after the resources that it requires.
+The limit can be changed with the `--action-concurrency` option, or the `action_concurrency` field of the config file.
+
+== Fencing Before Failover
+
+When a host stops responding, the daemon fences it by powering it off with its fence agent,
+then checks with the fence agent that it really is off.
+Only then are the host's resources started on its partner, so that they never run in two places at once.
+If fencing fails, or the host still reports that it is on, the daemon logs a critical error and leaves the resources stopped.
+It retries fencing on every pass until it succeeds, or until the host's remote agent is reachable again, in which case the resources are managed on the host as before.
+
+So that a broken fence agent, such as an unreachable IPMI endpoint, is not run forever,
+the daemon stops trying once fencing a host has failed 10 times in a row, or as many times as `--fence-attempts` sets.
+The host is then listed under "Hosts whose fencing failed" by `halo status`, and `halo_host_fence_failed` is 1 for it at `/metrics`.
+Its resources stay stopped, as they would while fencing kept failing: they are still never started elsewhere without a successful fence.
+Once the fence agent is fixed, `halo power --reset-breaker <host>` lets the daemon try fencing the host again, with a fresh count of failures.
+
+The `--no-fence` option skips fencing and fails resources over right away.
+This is unsafe: a host that is not really down can end up running the same resources as its partner.
+It is only meant for clusters without working fencing, such as test setups.
+
+== Quorum
+
+A daemon which has lost contact with most of the hosts may be the one which is cut off from them,
+so it can be told to act only while it reaches enough of them, with a `quorum` section in the config file:
+```yaml
+quorum:
+  expected_hosts: 4
+  tiebreak: first_host
+```
+`expected_hosts` defaults to the number of hosts in the config.
+The cluster has quorum while the daemon is connected to the remote agents of more than half of the expected hosts.
+Without quorum, the daemon keeps observing resources, but does not start, move, or fail any over,
+and `halo migrate` and `halo failback` are refused; it logs when quorum is lost and when it is regained.
+
+When exactly half of the hosts are reachable, `tiebreak` decides:
+`deny`, the default, means there is no quorum;
+`allow` means there is;
+and `first_host` means there is only if the first host listed in the config is one of the reachable ones.
+Note that in a cluster of one failover pair, losing either host leaves exactly half,
+so with `deny` such a cluster never fails over.
+
+`halo status` prints whether the cluster has quorum, and how many hosts are reachable, on its last line.
+
+== Stopping the Daemon
+
+On SIGTERM or SIGINT (for example, from `systemctl stop halo`), the management daemon shuts down gracefully.
+It stops accepting new commands from the CLI utility, finishes any commands that are in progress,
+and lets any resource operation that it is in the middle of complete before it exits.
+The unix domain socket is removed on exit.
+
+Otherwise, the daemon starts from what the config file says each time: which resource groups are managed, which hosts are on standby, and whether each resource is enabled.
+To keep what admins changed at runtime, with `halo unmanage`, `halo standby`, `halo disable`, and the like, pass `--state-file` with a path, e.g. `/var/lib/halo/state.json`.
+The daemon then writes those settings to the file as JSON whenever they change, checking once per poll interval and once more when it shuts down, and restores them when it starts, over the defaults of the config file.
+The file is written to a temporary file next to it first and renamed over it, so that a crash never leaves it half-written.
+If the file can not be read, or was written in another version of its format, the daemon logs a warning and starts with the config defaults instead.
+Resource groups, resources, and hosts in the file which are no longer in the config are skipped.
+Maintenance mode is recorded in the state file too, but it is restored from the maintenance file, which stays authoritative.
+
+== Reloading the Config
+
+To change the config file without restarting the management daemon, check it first with `halo_manager --config-check`,
+and then send the daemon SIGHUP (for example, with `systemctl reload halo`):
+```bash
+$ halo_manager --config /etc/halo/halo.conf --config-check
+$ systemctl reload halo
+```
+The daemon applies the changes to the `parameters`, `depends_on`, `start_timeout`, and `stop_timeout` of existing resources, and to `groups`, right away,
+without dropping its socket or interrupting the management loops.
+A running resource keeps running with its old parameters until it is restarted, e.g. with `halo set-param --restart`.
+A resource group which is added as a whole (a new root resource, with the resources that require it) is checked and managed right away, like the others at startup.
+A resource group which is removed as a whole is no longer managed, once each of its resources is stopped, e.g. with `halo disable`, and nothing else depends on it;
+a group which may still be running is kept until the next reload after it is stopped.
+A removed resource group can be added back as it was, with the same resources on the same hosts, once the daemon is done with it, which takes up to a management loop iteration;
+until the daemon is restarted, its IDs can not be used for a different resource group, and the daemon logs why a group was not added.
+Adding or removing resources in an existing resource group, changing the hosts, or changing where a resource may run, only takes effect once the daemon is restarted;
+the daemon logs a warning for each such change.
+If the new config can not be loaded or has problems, the daemon logs them and keeps using the config that it has.
+
+Each successful reload increases the config generation by one, starting from 1 for the config that the daemon started with.
+The daemon logs the generation that is in effect, and `halo status --json` reports it as `config_generation`.
+
 == Unix Domain Socket
 
 The management daemon listens for commands from the CLI utility on a unix domain socket.
 The default path to the socket is `/var/run/halo.socket`,
 but a custom path can be specified with the `--socket` option.
 
+On a machine which administers several clusters, it is convenient to set the path once for each cluster instead.
+The CLI utility and the daemon look for the socket in this order:
+the `--socket` option, the `HALO_SOCKET` environment variable,
+the `socket` field of the config file, and then the default path.
+`halo -v` prints which of these was used.
+
+Only one management daemon can run on a given socket.
+While it runs, the daemon holds a lock on a file next to the socket, named after it with `.lock` appended,
+which records its process ID.
+A second daemon started with the same socket exits with an error such as
+"another halo manager is already running (pid 1234)", rather than cutting the first one off from the CLI utility.
+
+A socket file left behind by a daemon which did not exit cleanly is removed and replaced at startup.
+If some other process is still listening on the socket, though, the daemon exits with an error instead,
+unless it is started with `--force`.
+Where a supervisor creates and removes the socket file itself,
+start the daemon with `--no-cleanup-socket` so that it never removes the file.
+
+Anyone who can connect to the socket can send the management daemon commands,
+so its permissions are what control who can manage the cluster from the CLI utility.
+To restrict it to the members of a group, give the socket a mode and a group,
+with the `--socket-mode` and `--socket-group` options or the `socket_mode` and `socket_group` fields of the config file:
+```yaml
+socket_mode: "0660"
+socket_group: halo-admins
+```
+The daemon sets these as soon as it creates the socket, and refuses to start if the mode is not an octal mode of at most `0777`,
+or if there is no such group.
+They do not apply to the TCP address, whose access is controlled by mTLS instead.
+
+== TCP Address <tcp-listen>
+
+For setups where the CLI utility runs on a different host than the management daemon,
+the daemon can also listen on a TCP address, given with the `--listen` option:
+```bash
+$ halo_manager --listen 10.0.0.1:8080
+```
+An IPv6 address must be put in brackets, as in `[fd00::1]:8080`,
+since otherwise the port could not be told apart from the address.
+To listen on every address, give `0.0.0.0:8080` for IPv4 or `[::]:8080` for IPv6.
+The host can also be a hostname, such as `mgmt0:8080`;
+it is resolved once at startup, and the daemon listens on the first address it resolves to.
+The unix domain socket remains available alongside the TCP address.
+The CLI utility connects over TCP when its `--socket` option is a `host:port` address:
+```bash
+$ halo status --socket 10.0.0.1:8080
+```
+
 = CLI Utility
 
 The CLI utility uses the `halo` binary, followed by a subcommand. For example:
@@ -246,7 +709,8 @@ $ halo status --socket halo.socket
 ```
 
 The `--socket` option allows specifying the unix socket path opened by the
-management daemon, in case the default location is not used.
+management daemon, in case the default location is not used,
+or the `host:port` address that the daemon listens on.
 
 == HTTP API
 
@@ -254,6 +718,125 @@ The CLI utility and the management daemon communicate with each other using an H
 In principal, this means that a tool like `curl` can be used and the CLI utility is not strictly necessary.
 However, the utility is more convenient that manually making HTTP requests using curl.
 
+The JSON replies to requests which change something also say why a request failed, in a `code` field,
+so that scripts do not have to match on the `message`, which is meant for humans:
+`not_found` if there is no such resource, resource group, group, or host;
+`already_in_state` if a resource group is already managed or unmanaged;
+`validation_error` if the request is invalid, e.g. a parameter which the resource's agent does not accept;
+`conflict` if it can not be done in the current state of the cluster, e.g. a start of a disabled resource;
+`no_quorum` if the cluster does not have quorum;
+`agent_failure` if a remote agent failed to do what it was asked;
+`timeout` and `canceled` if the action did not finish in time, or was canceled with `halo cancel`;
+and `internal` if the manager itself failed.
+The field is left out when the request succeeded.
+A request for several resource groups at once reports each one the same way, in its `results`.
+
+To guard against a runaway script, `halo_manager --rate-limit N` limits each endpoint which changes something to `N` requests per second,
+with bursts of up to `--rate-limit-burst` requests, 5 by default.
+Requests over the limit get a `429 Too Many Requests` reply with a `Retry-After` header, in seconds;
+`halo --retry` waits that long and tries again instead of failing.
+Requests which only read, such as `/status` and `/metrics`, are never limited.
+
+The management daemon also exports metrics in the Prometheus text format at `/metrics`,
+on the unix domain socket and on the TCP address if one is configured:
+```bash
+$ curl --unix-socket /var/run/halo.socket http://localhost/metrics
+```
+The metrics are the number of resources in each status (`halo_resources`),
+the number of managed and unmanaged resources (`halo_resources_managed`),
+whether each host is on standby (`halo_host_standby`),
+whether the daemon is connected to each host (`halo_host_reachable`),
+how many times in a row fencing each host has failed (`halo_host_fence_failures`), and whether the daemon has stopped trying (`halo_host_fence_failed`),
+whether the cluster has quorum (`halo_quorum`, always 1 without a quorum policy),
+the number of passes the management loops have made over resource groups (`halo_main_loop_iterations_total`),
+and the number of starts and stops of resources that are running right now (`halo_actions_in_flight`).
+With `--profile`, the timings of the passes are also exported; see Polling.
+
+The status of every resource is at `/status`, and the status of a single resource is at `/resources/<id>`,
+in the same form as each resource in `/status`, or a 404 if there is no such resource;
+`halo describe <id>` prints it:
+```bash
+$ curl --unix-socket /var/run/halo.socket http://localhost/resources/ost0
+```
+On a large cluster, `/status` can be narrowed down with query parameters:
+`status` (a kind of status such as `RunningOnAway`, in any case), `managed` (`true` or `false`),
+and `prefix` (the start of the resources' IDs) select resources,
+and `limit` and `offset` select a page of them.
+The reply's `total` field is how many resources matched, across every page.
+The `halo status` options `--status`, `--managed`, `--unmanaged`, `--prefix`, `--limit`, and `--offset` set these:
+```bash
+$ curl --unix-socket /var/run/halo.socket 'http://localhost/status?status=RunningOnAway&limit=50'
+```
+
+A summary of the whole cluster is at `/summary`, which `halo top` shows:
+the number of resources in each status, the number of managed and unmanaged resources,
+the number of hosts and how many are reachable, the hosts on standby, the quorum state,
+and the number of passes the management loops have made over resource groups:
+```bash
+$ curl --unix-socket /var/run/halo.socket http://localhost/summary
+```
+
+The mode of the daemon is at `/mode`, as `{"mode": "manage"}` or `{"mode": "observe"}`, and `halo mode` switches it by posting the same form there:
+```bash
+$ curl --unix-socket /var/run/halo.socket http://localhost/mode -H 'Content-Type: application/json' -d '{"mode": "observe"}'
+```
+
+The settings that the daemon is running with are at `/config`, which `halo config dump` prints.
+These are what its command line, environment, and config file came to once combined,
+such as its socket, mode, poll interval, and retry policy, along with its hosts and resources,
+so they answer which of several places a setting came from without having to check each of them.
+The auth token is shown as `<redacted>`, and the parameters of fence agents are left out:
+```bash
+$ curl --unix-socket /var/run/halo.socket http://localhost/config
+```
+
+For health checks, `/` replies with 200 as long as the management daemon is alive and answering requests.
+`/ready` replies with 200 only once the management loops have finished a pass over the resource groups,
+and with 503 and the reason otherwise, e.g. while the daemon is starting up,
+or if no pass has finished in ten poll intervals because the loops are stuck:
+```bash
+$ curl --unix-socket /var/run/halo.socket http://localhost/ready
+```
+
+Changes in the state of the cluster are streamed as server-sent events at `/events`,
+which `halo events` prints as they arrive.
+An event is sent whenever a resource changes status, a resource group is managed or unmanaged,
+or a host is put on standby or taken off of it.
+A client which falls behind misses the oldest events, and is sent a `lagged` event saying how many.
+
+A client can also hold a session open as a WebSocket at `/ws`, and send requests over it, one JSON text frame each,
+rather than make a new HTTP request for each one; `halo shell` is such a client, which reads commands from stdin.
+Each request has a `type`, and an `id` chosen by the client, which the reply to it carries as well:
+`manage` sets the managed state of resource groups as `PATCH /resources` does, with the same fields in its `args`,
+and is answered with the same reply, along with the status that `PATCH /resources` would have replied with;
+`status` lists the resources as `/status` does, with the same parameters in its `query`;
+and `subscribe` and `unsubscribe` start and stop the events that `/events` streams, which arrive as `event` messages.
+A request which fails is answered with an `error` message, with the status and message that the same HTTP request would have got,
+so a client with the read-only token can not send `manage`, and `manage` counts against the rate limit of `PATCH /resources`.
+Replies are sent in order, and the events for a change are sent before the reply to any later request.
+A client which falls behind on events misses the oldest ones, as on `/events`,
+and one which stops reading altogether is disconnected after 30 seconds:
+```bash
+$ halo shell <<EOF
+subscribe
+unmanage ost0
+status --prefix ost
+EOF
+```
+
+The most recent actions requested on resources are listed at `/history`, oldest first.
+With `?since=`, only those from a given time on are listed,
+either an RFC 3339 timestamp or a duration before now such as `10m` or `2h`.
+The times are taken from the management daemon's clock, which a relative `since` is also measured by,
+so a client whose clock is off still gets the window it asked for;
+compare absolute timestamps with the daemon host's clock, not the client's:
+```bash
+$ curl --unix-socket /var/run/halo.socket 'http://localhost/history?since=2h'
+```
+
+`halo simulate` asks `/simulate` where the resource groups would go in a hypothetical state,
+given as comma-separated lists in `?fail=`, `?standby=`, and `?add=`; see @simulate.
+
 == Man pages
 
 Detailed documentation of the specific commands exists
@@ -278,3 +861,40 @@ While the management daemon is running in Manage mode,
 it may be desirable to prevent it from managing some specific resources.
 When a resource is unmanaged using `halo unmanage <resource_id>`,
 HALO will still attempt to monitor the resource status but will not take any actions on that resource.
+
+=== enable, disable
+
+Whether a resource is managed, and whether it should be running, are separate:
+`halo disable <resource_id>` tells HALO to keep a resource stopped, while still monitoring it.
+While the resource is managed, HALO stops it, after the resources which depend on it, and does not start it again until `halo enable <resource_id>`.
+A disabled resource which is unmanaged is left alone, like any other unmanaged resource.
+`halo status` shows "(Disabled)" after a disabled resource, and "(Unmanaged)" after an unmanaged one,
+so an unmanaged resource that is running can be told apart from a managed one that is meant to be stopped.
+A resource can start out disabled by setting `target_state: stopped` on it in the config file.
+
+=== standby, unstandby
+
+The `standby` command is used to drain a node before maintenance.
+While a node is on standby, HALO will not start resources on it.
+In Manage mode, resource groups that are running on a standby node
+are moved to its failover partner, as with `halo migrate`.
+In Observe mode, the standby flag is only recorded and reported.
+The `unstandby` command makes the node eligible to run resources again;
+resources that were moved off of it are not moved back automatically.
+Both commands take node names in nodeset syntax, e.g., `halo standby oss[01-02]`.
+The standby flag is kept by the management daemon, and is cleared when the daemon restarts.
+
+=== simulate <simulate>
+
+The `simulate` command shows what HALO would do with the resource groups if the cluster were in a different state,
+without changing anything, e.g., before taking a node down for maintenance:
+```bash
+$ halo simulate --fail-node oss01
+$ halo simulate --standby oss[01-02] --add-resource ost9@oss03
+```
+With `--fail-node`, the nodes are treated as down, with `--standby` as on standby,
+and with `--add-resource`, each `ID@HOST` is treated as a new, stopped resource group whose home node is `HOST`.
+HALO makes the same placement decisions as in Manage mode, one at a time, until there is nothing left to do,
+and prints each action it would take and where each resource group would end up,
+along with the constraints that it would break there.
+Fencing and quorum are not simulated: a node which is down is assumed to have been fenced, and every action is assumed to succeed.
@@ -0,0 +1,226 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+//! The interface through which the management loops act on resources.
+//!
+//! The loops in resource.rs check on, start, and stop resources only through the ResourceAgent
+//! trait, so they do not depend on how an agent is reached. In the manager, the agent for a host is
+//! the capnp client of the remote agent running there, which runs the OCF resource agent for each
+//! kind of resource; tests can drive the loops with an agent of their own.
+//!
+//! The OCF resource agents that halo knows about also have a Schema, which says what parameters
+//! they take, so that a resource with missing or malformed parameters is caught when the config is
+//! loaded, or when its parameters are set, rather than when the agent fails on it.
+
+use std::{collections::HashMap, future::Future, pin::Pin};
+
+use serde::Serialize;
+
+use crate::{
+    halo_capnp::{ocf_resource_agent, remote_ocf_operation_given_client, AgentReply},
+    resource::Resource,
+};
+
+/// The outcome of an operation on a resource. An Err means that the agent could not be reached,
+/// so it is unknown whether the operation was attempted; see remote_ocf_operation().
+pub type AgentResult<'a> = Pin<Box<dyn Future<Output = Result<AgentReply, capnp::Error>> + 'a>>;
+
+/// Something that can check on, start, and stop the resources on one host.
+///
+/// The management loops turn each reply into the status of the resource: a resource which is
+/// reported running is RunningOnHome or RunningOnAway, depending on where the agent is, one which
+/// is reported not running is Stopped, and any other error is an Error. A start or stop which keeps
+/// failing becomes FailedStart or FailedStop once the manager gives up on it. If the agent can not
+/// be reached, the status is Unknown, and a disconnected agent is treated as a failed host.
+pub trait ResourceAgent {
+    /// Check whether `res` is running.
+    fn monitor<'a>(&'a self, res: &'a Resource) -> AgentResult<'a>;
+
+    fn start<'a>(&'a self, res: &'a Resource) -> AgentResult<'a>;
+
+    fn stop<'a>(&'a self, res: &'a Resource) -> AgentResult<'a>;
+}
+
+impl ResourceAgent for ocf_resource_agent::Client {
+    fn monitor<'a>(&'a self, res: &'a Resource) -> AgentResult<'a> {
+        Box::pin(remote_ocf_operation_given_client(
+            res,
+            self,
+            ocf_resource_agent::Operation::Monitor,
+        ))
+    }
+
+    fn start<'a>(&'a self, res: &'a Resource) -> AgentResult<'a> {
+        Box::pin(remote_ocf_operation_given_client(
+            res,
+            self,
+            ocf_resource_agent::Operation::Start,
+        ))
+    }
+
+    fn stop<'a>(&'a self, res: &'a Resource) -> AgentResult<'a> {
+        Box::pin(remote_ocf_operation_given_client(
+            res,
+            self,
+            ocf_resource_agent::Operation::Stop,
+        ))
+    }
+}
+
+/// The kind of value that a resource parameter takes.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamType {
+    /// Any value which is not empty.
+    String,
+
+    /// An absolute path, e.g. a mountpoint.
+    Path,
+
+    /// A whole number.
+    Integer,
+
+    /// "true" or "false", or "yes" or "no", or "1" or "0", as OCF agents take them.
+    Boolean,
+
+    /// One of the given values.
+    OneOf(&'static [&'static str]),
+}
+
+impl ParamType {
+    /// What is wrong with `value` as a value of this type, if anything.
+    fn problem(&self, value: &str) -> Option<String> {
+        let ok = match self {
+            ParamType::String => !value.is_empty(),
+            ParamType::Path => value.starts_with('/'),
+            ParamType::Integer => value.parse::<i64>().is_ok(),
+            ParamType::Boolean => {
+                ["true", "false", "yes", "no", "1", "0"].contains(&value.to_lowercase().as_str())
+            }
+            ParamType::OneOf(values) => values.contains(&value),
+        };
+        (!ok).then(|| format!("expected {self}, but got \"{value}\""))
+    }
+}
+
+/// ParamTypes are displayed as what a value of them must be, e.g. "an absolute path".
+impl std::fmt::Display for ParamType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParamType::String => write!(f, "a value which is not empty"),
+            ParamType::Path => write!(f, "an absolute path"),
+            ParamType::Integer => write!(f, "a whole number"),
+            ParamType::Boolean => write!(f, "true or false"),
+            ParamType::OneOf(values) => write!(f, "one of {}", values.join(", ")),
+        }
+    }
+}
+
+/// A parameter that an OCF resource agent takes.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: &'static str,
+
+    #[serde(rename = "type")]
+    pub kind: ParamType,
+
+    /// Whether a resource of this kind must set the parameter.
+    pub required: bool,
+
+    pub description: &'static str,
+}
+
+/// The parameters that the OCF resource agent for one kind of resource takes. Parameters which are
+/// not in the schema are passed to the agent as they are, since agents take many optional ones.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Schema {
+    /// The kind of resource, e.g. "heartbeat/ZFS".
+    pub kind: &'static str,
+
+    pub params: &'static [Param],
+}
+
+/// The schemas of the kinds of resources that halo knows about.
+pub const SCHEMAS: &[Schema] = &[
+    Schema {
+        kind: "heartbeat/ZFS",
+        params: &[
+            Param {
+                name: "pool",
+                kind: ParamType::String,
+                required: true,
+                description: "The name of the zpool to import.",
+            },
+            Param {
+                name: "importargs",
+                kind: ParamType::String,
+                required: false,
+                description: "Extra arguments to pass to `zpool import`.",
+            },
+            Param {
+                name: "importforce",
+                kind: ParamType::Boolean,
+                required: false,
+                description: "Whether to force the import of a pool which another host imported.",
+            },
+        ],
+    },
+    Schema {
+        kind: "lustre/Lustre",
+        params: &[
+            Param {
+                name: "target",
+                kind: ParamType::String,
+                required: true,
+                description: "The device or dataset of the Lustre target, e.g. pool0/ost0.",
+            },
+            Param {
+                name: "mountpoint",
+                kind: ParamType::Path,
+                required: true,
+                description: "Where to mount the target.",
+            },
+            Param {
+                name: "mountoptions",
+                kind: ParamType::String,
+                required: false,
+                description: "Extra options to pass to `mount`.",
+            },
+            Param {
+                name: "kind",
+                kind: ParamType::OneOf(&["mgs", "mdt", "ost"]),
+                required: false,
+                description: "Which kind of Lustre target this is.",
+            },
+        ],
+    },
+];
+
+impl Schema {
+    /// The schema for resources of the given kind, if halo knows about it.
+    pub fn of(kind: &str) -> Option<&'static Schema> {
+        SCHEMAS.iter().find(|schema| schema.kind == kind)
+    }
+
+    /// Check `params` against this schema. Returns a problem, prefixed with the parameter's name,
+    /// for each required parameter which is missing and each parameter whose value is not of its
+    /// type, in the order of the schema.
+    pub fn check(&self, params: &HashMap<String, String>) -> Vec<String> {
+        let mut problems = Vec::new();
+        for param in self.params.iter() {
+            match params.get(param.name) {
+                Some(value) => {
+                    if let Some(problem) = param.kind.problem(value) {
+                        problems.push(format!("{}: {problem}", param.name));
+                    }
+                }
+                None if param.required => problems.push(format!(
+                    "{}: a {} resource must set this parameter",
+                    param.name, self.kind
+                )),
+                None => {}
+            }
+        }
+        problems
+    }
+}
@@ -14,7 +14,7 @@ fn main() {
 
     let args = Cli::parse();
 
-    if commands::main(&args).is_err() {
-        std::process::exit(1);
+    if let Err(e) = commands::main(&args) {
+        std::process::exit(e.report().code() as i32);
     }
 }
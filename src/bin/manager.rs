@@ -9,15 +9,28 @@ use halo_lib::{self, cluster, manager};
 fn main() {
     let args = manager::Cli::parse();
 
-    let default_log_level = if args.verbose { "debug" } else { "warn" };
-    env_logger::Builder::from_env(
-        env_logger::Env::default().filter_or("HALO_LOG", default_log_level),
-    )
-    .init();
+    if halo_lib::logging::init(args.verbose, args.log_format, args.log_file.as_deref()).is_err() {
+        std::process::exit(1);
+    }
+    if args.config_check {
+        let code = match manager::check_config(args) {
+            Ok(()) => 0,
+            Err(_) => 2,
+        };
+        std::process::exit(code);
+    }
     let Ok(cluster) = cluster::Cluster::new(args) else {
         std::process::exit(1);
     };
 
+    if cluster.args.once {
+        let code = match manager::once::main(cluster) {
+            Ok(true) => 0,
+            Ok(false) => manager::once::UNHEALTHY,
+            Err(_) => 1,
+        };
+        std::process::exit(code);
+    }
     if manager::main(cluster).is_err() {
         std::process::exit(1);
     }
@@ -8,11 +8,9 @@ use halo_lib::remote::{self, Cli};
 fn main() {
     let args = Cli::parse();
 
-    let default_log_level = if args.verbose { "debug" } else { "warn" };
-    env_logger::Builder::from_env(
-        env_logger::Env::default().filter_or("HALO_LOG", default_log_level),
-    )
-    .init();
+    if halo_lib::logging::init(args.verbose, args.log_format, args.log_file.as_deref()).is_err() {
+        std::process::exit(1);
+    }
 
     if remote::agent_main(args).is_err() {
         std::process::exit(1);
@@ -1,17 +1,47 @@
 // SPDX-License-Identifier: MIT
 // Copyright 2025. Triad National Security, LLC.
 
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+};
 
-use futures::future;
+use {
+    futures::future,
+    log::{debug, error, warn},
+    tokio_util::sync::CancellationToken,
+    tracing::Instrument,
+};
 
 use crate::{
     commands::{Handle, HandledResult},
     host::*,
-    manager,
+    manager::{
+        self,
+        maintenance::Maintenance,
+        placement::{Action, GroupState, HostState, PlacementReason, Snapshot},
+        profile::Profiler,
+        quorum::Quorum,
+    },
     resource::*,
 };
 
+/// What reloading the config file changed, from Cluster::reload().
+#[derive(Debug, Default)]
+pub struct ReloadedConfig {
+    /// The config generation which is now in effect.
+    pub generation: u64,
+
+    /// The changes which were applied, e.g. "parameters of ost0".
+    pub applied: Vec<String>,
+
+    /// The changes which were not applied, since they only take effect when the manager restarts.
+    pub needs_restart: Vec<String>,
+}
+
 /// Cluster is the model used to represent the dynamic state of a cluster in memory.
 /// Unlike the persistent model which views a cluster as made up of nodes, which own services,
 /// the in-memory model views a cluster as made up of services (storage devices and Lustre
@@ -20,9 +50,17 @@ use crate::{
 /// This model is slightly more convenient for performing cluster operations.
 #[derive(Debug)]
 pub struct Cluster {
-    resource_groups: Vec<ResourceGroup>,
-    num_zpools: u32,
-    num_targets: u32,
+    resource_groups: ResourceGroups,
+    num_zpools: AtomicU32,
+    num_targets: AtomicU32,
+
+    /// The total number of resources in all of the resource groups, so that it does not need to
+    /// be counted each time it is needed.
+    num_resources: AtomicUsize,
+
+    /// Held while a resource group is added or removed, so that one change is checked and made
+    /// before the next.
+    changes: Mutex<()>,
 
     /// The hosts in the Cluster are mapped by their ID, a unique identifier which is the hostname
     /// normally. However, in the test environment, it is a test-defined identifier since the
@@ -33,19 +71,103 @@ pub struct Cluster {
 
     /// True if this is a failover cluster (hosts are in high-availability pairs)
     failover: bool,
+
+    /// Every host in the cluster publishes its changes here.
+    events: crate::manager::events::Publisher,
+
+    /// The actions that admins have requested on resources.
+    history: crate::manager::history::History,
+
+    /// The IDs of the resources which are being stopped at the request of an admin, so that the
+    /// resources which depend on them are not started again in the meantime.
+    stopping: Mutex<HashSet<String>>,
+
+    /// The actions that admins have requested which are still in progress, by a number of their
+    /// own, with the IDs of the resources that each acts on, so that they can be canceled.
+    running_actions: Mutex<HashMap<u64, (Vec<String>, CancellationToken)>>,
+    next_action: AtomicU64,
+
+    /// The named groups of resources from the config, by name. These can be changed by reloading
+    /// the config, so they are behind a lock.
+    groups: Mutex<BTreeMap<String, Vec<String>>>,
+
+    /// The files that the config in effect was read from, and which of them each resource is
+    /// defined in. These can change when the config is reloaded, so they are behind a lock.
+    sources: Mutex<crate::config::Sources>,
+
+    /// Which version of the config file is in effect: 1 for the one that the manager started with,
+    /// and one more each time that it is reloaded.
+    config_generation: AtomicU64,
+
+    /// The quorum policy from the config, if it sets one.
+    quorum: Option<Quorum>,
+
+    /// How much each attribute of a host counts towards its score, from `placement_weights` in
+    /// the config; see Cluster::place().
+    placement_weights: BTreeMap<String, f64>,
+
+    /// The sets of resources from `colocation` and `anti_affinity` in the config, whose resource
+    /// groups must run on the same host, or must not; see Cluster::place().
+    colocation: Vec<Vec<String>>,
+    anti_affinity: Vec<Vec<String>>,
+
+    /// Whether the cluster had quorum when the management loops last checked, so that changes can
+    /// be logged.
+    quorate: Mutex<Option<bool>>,
+
+    /// A permit for each start or stop of a resource which may run at once.
+    actions: tokio::sync::Semaphore,
+
+    /// Whether the management loops act on the resources, or only observe them. This starts out
+    /// as `--manage-resources`, and can be switched off and on again while the manager runs.
+    managing: AtomicBool,
+
+    /// While maintenance mode is on, the management loops do not act on any resource, whatever
+    /// the mode; unlike the mode, it stays on when the manager restarts.
+    maintenance: Maintenance,
+
+    /// The timings of the management loops, with `--profile`.
+    profiler: Option<Profiler>,
 }
 
 impl Cluster {
+    /// Monitor the cluster forever. In Manage mode, resources are started, failed over, and fenced
+    /// as needed; in Observe mode, their statuses are only checked and reported. A dry run is
+    /// observed the same way, and also logs the actions that Manage mode would take.
     pub async fn main_loop(&self) {
-        if self.args.manage_resources {
-            if self.failover {
-                let futures: Vec<_> = self.hosts.values().map(|h| h.manage_ha(self)).collect();
+        let mode = if self.args.dry_run {
+            "Dry run"
+        } else if self.args.manage_resources {
+            "Manage"
+        } else {
+            "Observe"
+        };
+        warn!("Manager starting in {mode} mode.");
+
+        let span = tracing::trace_span!("main_loop", mode);
+        async {
+            if self.args.dry_run {
+                future::join(self.observe(), self.report_intended_actions()).await;
+            } else if self.args.manage_resources {
+                if self.failover {
+                    let futures: Vec<_> = self.hosts.values().map(|h| h.manage_ha(self)).collect();
 
-                let _ = future::join_all(futures).await;
+                    let _ = future::join_all(futures).await;
+                } else {
+                    let futures: Vec<_> = self.hosts.values().map(|h| h.manage(self)).collect();
+
+                    let _ = future::join_all(futures).await;
+                }
             } else {
-                todo!("Implement management loop for non-HA cluster.");
-            }
-        } else if self.failover {
+                self.observe().await;
+            };
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn observe(&self) {
+        if self.failover {
             let futures: Vec<_> = self.hosts.values().map(|h| h.observe_ha(self)).collect();
 
             let _ = future::join_all(futures).await;
@@ -53,15 +175,383 @@ impl Cluster {
             let futures: Vec<_> = self.hosts.values().map(|h| h.observe(self)).collect();
 
             let _ = future::join_all(futures).await;
-        };
+        }
+    }
+
+    /// Log the actions that Manage mode would take, as the observed state of the cluster changes.
+    /// Each action is logged once, when it first becomes needed, rather than on every pass.
+    async fn report_intended_actions(&self) {
+        let mut reported = Vec::new();
+        loop {
+            let actions = self.intended_actions();
+            for action in actions.iter().filter(|action| !reported.contains(*action)) {
+                warn!("Dry run: would {action}.");
+            }
+            reported = actions;
+
+            tokio::time::sleep(self.args.poll_delay()).await;
+        }
+    }
+
+    /// The actions that Manage mode would take on the managed resource groups, given their
+    /// current statuses, e.g. "start resource group ost0 on oss01".
+    pub fn intended_actions(&self) -> Vec<String> {
+        let mut actions = Vec::new();
+        if self.maintenance.is_on() {
+            return actions;
+        }
+        let plan = self.snapshot().plan();
+        for action in plan.iter() {
+            let wait = match action {
+                Action::Start { group, .. } => {
+                    match self.unmet_dependency(&self.get_resource_group(group).root) {
+                        Some(dep) => format!(" once {dep} is running"),
+                        None => String::new(),
+                    }
+                }
+                _ => String::new(),
+            };
+            actions.push(format!("{action}{wait}"));
+        }
+
+        // The members of a running group which stopped are started again where it is running:
+        for rg in self.resource_groups.iter().filter(|rg| rg.get_managed()) {
+            if plan.iter().any(|action| action.group() == rg.id()) {
+                continue;
+            }
+            let Some(here) = rg.running_on() else {
+                continue;
+            };
+            for res in rg
+                .resources()
+                .filter(|res| res.get_status() == ResourceStatus::Stopped)
+            {
+                actions.push(format!("start resource {} on {}", res.id, here.id()));
+            }
+        }
+        actions
     }
 
     pub fn num_zpools(&self) -> u32 {
-        self.num_zpools
+        self.num_zpools.load(Ordering::Relaxed)
     }
 
     pub fn num_targets(&self) -> u32 {
-        self.num_targets
+        self.num_targets.load(Ordering::Relaxed)
+    }
+
+    /// The total number of resources in the cluster.
+    pub fn resource_count(&self) -> usize {
+        self.num_resources.load(Ordering::Relaxed)
+    }
+
+    /// Add `groups` to the cluster as it is built, before the management loops start. Resource
+    /// groups must only be added this way, or by add_resource_group(), so that the resource counts
+    /// stay up to date.
+    fn add_resource_groups(&self, groups: Vec<ResourceGroup>) {
+        for rg in groups {
+            self.count_resources(&rg, true);
+            self.resource_groups.push(rg);
+        }
+    }
+
+    /// Add the resources of `rg` to the resource counts, or take them away if `!add`.
+    fn count_resources(&self, rg: &ResourceGroup, add: bool) {
+        let of_kind = |kind| rg.resources().filter(|res| res.kind == kind).count() as u32;
+        let (resources, zpools, targets) = (
+            rg.resources().count(),
+            of_kind("heartbeat/ZFS"),
+            of_kind("lustre/Lustre"),
+        );
+        if add {
+            self.num_resources.fetch_add(resources, Ordering::Relaxed);
+            self.num_zpools.fetch_add(zpools, Ordering::Relaxed);
+            self.num_targets.fetch_add(targets, Ordering::Relaxed);
+        } else {
+            self.num_resources.fetch_sub(resources, Ordering::Relaxed);
+            self.num_zpools.fetch_sub(zpools, Ordering::Relaxed);
+            self.num_targets.fetch_sub(targets, Ordering::Relaxed);
+        }
+    }
+
+    /// Add a resource group to the cluster while the manager runs, and have its home host start
+    /// managing (or observing) it. Returns why not, leaving the cluster as it was, if any of its
+    /// resources has the same ID as one which is in the cluster, or was removed from it since the
+    /// manager started, or if it would run on a host which is not in the cluster.
+    ///
+    /// A group which was removed can be added back, though, as long as it has the same resources
+    /// running on the same hosts as before, and the management task of the removed group has
+    /// exited: the removed group then takes the settings of `rg`, and is managed again.
+    ///
+    /// This is safe to call while the management loops run: the groups which are already in the
+    /// cluster are never moved, so the loops' references to them stay valid, and a loop which is
+    /// iterating over the groups may or may not see the new one. Its home host picks it up once
+    /// the host is done with whatever it is doing at the moment.
+    pub fn add_resource_group(&self, rg: ResourceGroup) -> Result<(), String> {
+        let _change = self.changes.lock().unwrap();
+        let id = rg.id().to_string();
+        let result = self.check_added_resource_group(&rg);
+        if let Err(reason) = &result {
+            warn!("Resource group {id} was not added: {reason}.");
+            return result;
+        }
+
+        let home = Arc::clone(rg.home_node());
+        let removed = self
+            .resource_groups
+            .iter_all()
+            .find(|old| old.is_removed() && old.id() == id);
+        if let Some(old) = removed {
+            old.revive(&rg);
+            self.count_resources(old, true);
+            warn!("Resource group {id} was added back.");
+        } else {
+            self.count_resources(&rg, true);
+            self.resource_groups.push(rg);
+            warn!("Resource group {id} was added.");
+        }
+        home.resource_group_added(id);
+        Ok(())
+    }
+
+    /// Check that `rg` can be added to the cluster, either as a new group, or in place of a removed
+    /// group with the same ID.
+    fn check_added_resource_group(&self, rg: &ResourceGroup) -> Result<(), String> {
+        let removed = self
+            .resource_groups
+            .iter_all()
+            .find(|old| old.is_removed() && old.id() == rg.id());
+        let taken = |id: &str| {
+            self.resource_groups
+                .iter_all()
+                .filter(|other| removed.is_none_or(|old| !std::ptr::eq(*other, old)))
+                .any(|other| other.resources().any(|res| res.id == id))
+        };
+        if let Some(res) = rg.resources().find(|res| taken(&res.id)) {
+            return Err(format!("resource {} is already in the cluster", res.id));
+        }
+        let known = |host: &Arc<Host>| self.hosts.values().any(|h| Arc::ptr_eq(h, host));
+        if ![Location::Home, Location::Away]
+            .into_iter()
+            .filter_map(|loc| rg.root.node(loc))
+            .all(known)
+        {
+            return Err("it would run on a host which is not in the cluster".to_string());
+        }
+
+        let Some(old) = removed else {
+            return Ok(());
+        };
+        if !old.same_layout(rg) {
+            return Err(
+                "it was removed with different resources or hosts, and its IDs can not be used \
+                 for a different group until the manager restarts"
+                    .to_string(),
+            );
+        }
+        if old.is_held() {
+            return Err(
+                "it was removed, and its management task has not exited yet; try again shortly"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Remove the resource group whose root resource has the ID `id` from the cluster while the
+    /// manager runs. Returns false, leaving the cluster as it was, if there is no such group, if a
+    /// resource outside of the group depends on one in it, or, unless `force` is given, if any of
+    /// its resources may be running: each must be known to be Stopped, or Blocked. A group which
+    /// is removed while running by force is left running, but is no longer managed.
+    ///
+    /// This is safe to call while the management loops run. The group stops being listed right
+    /// away, but a pass of the management loop which is already under way over it finishes first;
+    /// the loop exits before its next pass. Since the loops may still refer to it, the group is
+    /// kept until the manager exits, so its resource IDs can not be used by a different group
+    /// until then; the same group can be added back, though, by add_resource_group().
+    pub fn remove_resource_group(&self, id: &str, force: bool) -> bool {
+        let _change = self.changes.lock().unwrap();
+
+        let Some(rg) = self.find_resource_group(id) else {
+            return false;
+        };
+        let members: Vec<&str> = rg.resources().map(|res| res.id.as_str()).collect();
+        let dependent = self
+            .resources()
+            .filter(|res| !members.contains(&res.id.as_str()))
+            .find(|res| {
+                res.depends_on()
+                    .iter()
+                    .any(|dep| members.contains(&dep.as_str()))
+            });
+        if let Some(dependent) = dependent {
+            warn!(
+                "Resource group {id} was not removed: resource {} depends on it.",
+                dependent.id
+            );
+            return false;
+        }
+        let stopped = |res: &Resource| {
+            matches!(
+                res.get_status(),
+                ResourceStatus::Stopped | ResourceStatus::Blocked(_)
+            )
+        };
+        if !force && !rg.resources().all(stopped) {
+            warn!("Resource group {id} was not removed, since it may be running.");
+            return false;
+        }
+
+        rg.set_removed();
+        self.count_resources(rg, false);
+        warn!("Resource group {id} was removed.");
+        true
+    }
+
+    /// The number of hosts whose remote agents the manager is currently connected to.
+    pub fn reachable_hosts(&self) -> usize {
+        self.hosts
+            .values()
+            .filter(|host| host.is_reachable())
+            .count()
+    }
+
+    pub fn quorum(&self) -> Option<&Quorum> {
+        self.quorum.as_ref()
+    }
+
+    /// Whether the manager is in Manage mode: the management loops only start, move, and fail over
+    /// resources while it is, and otherwise just observe them.
+    pub fn is_managing(&self) -> bool {
+        self.managing.load(Ordering::Relaxed)
+    }
+
+    /// Switch between Manage and Observe mode. This only has an effect if the manager was started
+    /// in Manage mode, since otherwise there are no management loops to act on the resources.
+    /// Returns false if the manager was already in that mode.
+    pub fn set_managing(&self, managing: bool) -> bool {
+        let mode = |managing| if managing { "Manage" } else { "Observe" };
+        let changed = self.managing.swap(managing, Ordering::Relaxed) != managing;
+        if changed {
+            warn!(
+                "Manager switching from {} to {} mode.",
+                mode(!managing),
+                mode(managing)
+            );
+        }
+        changed
+    }
+
+    /// Whether the management loops may act on the resources: the manager must be in Manage mode,
+    /// and not in maintenance mode.
+    pub fn may_act(&self) -> bool {
+        self.is_managing() && !self.maintenance.is_on()
+    }
+
+    pub fn maintenance(&self) -> &Maintenance {
+        &self.maintenance
+    }
+
+    /// The timings of the management loops, if the manager was run with `--profile`.
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Turn maintenance mode on or off, recording it in the maintenance file. Returns false if it
+    /// was already on or off.
+    pub fn set_maintenance(&self, on: bool) -> std::io::Result<bool> {
+        let changed = self.maintenance.set(on)?;
+        if changed && on {
+            warn!(
+                "Maintenance mode switched on: no resource will be started, stopped, moved, or \
+                 failed over until it is switched off."
+            );
+        } else if changed {
+            warn!("Maintenance mode switched off.");
+        }
+        Ok(changed)
+    }
+
+    /// Whether the manager may start, move, and fail over resources: if the config sets a quorum
+    /// policy, enough hosts must be reachable to meet it. This only reads the state of the hosts,
+    /// so it is safe to call from anywhere, e.g. to report the quorum in the status.
+    pub fn has_quorum(&self) -> bool {
+        let Some(quorum) = &self.quorum else {
+            return true;
+        };
+        quorum.is_met(self.reachable_hosts(), self.first_host_reachable(quorum))
+    }
+
+    /// Whether the cluster has quorum, as has_quorum(), logging when it is lost or regained. Only
+    /// the management loops should call this, as they decide whether to act on the resources, so
+    /// that each change is logged once, when it takes effect.
+    pub fn update_quorum(&self) -> bool {
+        let Some(quorum) = &self.quorum else {
+            return true;
+        };
+
+        let reachable = self.reachable_hosts();
+        let has_quorum = quorum.is_met(reachable, self.first_host_reachable(quorum));
+
+        let previous = self.quorate.lock().unwrap().replace(has_quorum);
+        match previous {
+            Some(false) if has_quorum => warn!(
+                "Quorum regained: {reachable} of {} hosts are reachable.",
+                quorum.expected
+            ),
+            Some(true) | None if !has_quorum => error!(
+                "Quorum lost: only {reachable} of {} hosts are reachable, so resources will not be \
+                 started, moved, or failed over until it is regained.",
+                quorum.expected
+            ),
+            _ => {}
+        }
+
+        has_quorum
+    }
+
+    /// Whether the host which breaks a tie under `quorum` is reachable.
+    fn first_host_reachable(&self, quorum: &Quorum) -> bool {
+        quorum
+            .first_host
+            .as_ref()
+            .and_then(|id| self.hosts.get(id))
+            .is_some_and(|host| host.is_reachable())
+    }
+
+    /// The total number of passes that the management loops have made over resource groups.
+    pub fn main_loop_iterations(&self) -> u64 {
+        self.resource_groups
+            .iter_all()
+            .map(|rg| rg.iterations())
+            .sum()
+    }
+
+    /// Whether the management loops are running: some resource group must have finished a pass,
+    /// and recently enough that the loops do not seem to be stuck. Otherwise, the reason that they
+    /// are not is returned. A cluster with no resource groups is always ready.
+    pub fn readiness(&self) -> Result<(), String> {
+        if self.resource_groups.iter().next().is_none() {
+            return Ok(());
+        }
+
+        let Some(last_pass) = self
+            .resource_groups
+            .iter()
+            .filter_map(|rg| rg.last_pass())
+            .max()
+        else {
+            return Err("the management loops have not finished a pass yet".to_string());
+        };
+        let since = last_pass.elapsed();
+        let timeout = self.args.stall_timeout();
+        if since > timeout {
+            return Err(format!(
+                "the management loops have not finished a pass in {}s",
+                since.as_secs()
+            ));
+        }
+        Ok(())
     }
 
     pub fn resource_groups(&self) -> impl Iterator<Item = &ResourceGroup> {
@@ -74,6 +564,55 @@ impl Cluster {
             .flat_map(|group| group.resources())
     }
 
+    /// The resources whose status is the same kind as `status`. The reason given by an Unknown or
+    /// Error status is not compared.
+    pub fn resources_with_status(
+        &self,
+        status: &ResourceStatus,
+    ) -> impl Iterator<Item = &Resource> {
+        let want = std::mem::discriminant(status);
+        self.resources()
+            .filter(move |res| std::mem::discriminant(&res.get_status()) == want)
+    }
+
+    /// The resources which match every one of the filters that is given, along with their resource
+    /// groups: those whose reported status is of the kind named `status` (see
+    /// ResourceStatus::name(), compared without regard to case), those in managed or unmanaged
+    /// resource groups, and those whose IDs start with `prefix`.
+    pub fn filter_resources<'a>(
+        &'a self,
+        status: Option<&'a str>,
+        managed: Option<bool>,
+        prefix: Option<&'a str>,
+    ) -> impl Iterator<Item = (&'a ResourceGroup, &'a Resource)> {
+        self.resource_groups
+            .iter()
+            .filter(move |rg| managed.is_none_or(|managed| rg.get_managed() == managed))
+            .flat_map(|rg| rg.resources().map(move |res| (rg, res)))
+            .filter(move |(_, res)| prefix.is_none_or(|prefix| res.id.starts_with(prefix)))
+            .filter(move |(rg, res)| {
+                status.is_none_or(|status| {
+                    rg.reported_status(res).name().eq_ignore_ascii_case(status)
+                })
+            })
+    }
+
+    /// The resources in managed resource groups.
+    pub fn managed_resources(&self) -> impl Iterator<Item = &Resource> {
+        self.resource_groups
+            .iter()
+            .filter(|rg| rg.get_managed())
+            .flat_map(|rg| rg.resources())
+    }
+
+    /// The resources in unmanaged resource groups.
+    pub fn unmanaged_resources(&self) -> impl Iterator<Item = &Resource> {
+        self.resource_groups
+            .iter()
+            .filter(|rg| !rg.get_managed())
+            .flat_map(|rg| rg.resources())
+    }
+
     pub fn zpool_resources(&self) -> impl Iterator<Item = &Resource> {
         self.resources().filter(|res| res.kind == "heartbeat/ZFS")
     }
@@ -84,7 +623,7 @@ impl Cluster {
 
     pub fn lustre_resources_no_mgs(&self) -> impl Iterator<Item = &Resource> {
         self.lustre_resources()
-            .filter(|res| res.parameters.get("kind").unwrap() != "mgs")
+            .filter(|res| res.parameter("kind").unwrap() != "mgs")
     }
 
     pub fn host_home_resource_groups<'a>(
@@ -96,28 +635,265 @@ impl Cluster {
             .filter(|rg| std::ptr::eq(Arc::as_ptr(rg.home_node()), host))
     }
 
+    /// The resource groups which can run on `host`, along with the location that `host` is for
+    /// each of them.
+    pub fn host_resource_groups<'a>(
+        &'a self,
+        host: &'a Host,
+    ) -> impl Iterator<Item = (&'a ResourceGroup, Location)> {
+        self.resource_groups.iter().filter_map(|rg| {
+            [Location::Home, Location::Away]
+                .into_iter()
+                .find(|loc| {
+                    rg.root
+                        .node(*loc)
+                        .is_some_and(|h| std::ptr::eq(Arc::as_ptr(h), host))
+                })
+                .map(|loc| (rg, loc))
+        })
+    }
+
+    /// The resource group whose root resource has the given ID. Unlike find_resource_group(), this
+    /// also finds a group which was removed, for the management tasks which still refer to it.
     pub fn get_resource_group(&self, id: &str) -> &ResourceGroup {
         self.resource_groups
-            .iter()
+            .iter_all()
             .find(|rg| rg.id() == id)
             .unwrap()
     }
 
+    /// Find the resource group whose root resource has the given ID.
+    pub fn find_resource_group(&self, id: &str) -> Option<&ResourceGroup> {
+        self.resource_groups.iter().find(|rg| rg.id() == id)
+    }
+
+    /// Find the resource with the given ID, along with the resource group that it belongs to.
+    pub fn find_resource(&self, id: &str) -> Option<(&ResourceGroup, &Resource)> {
+        self.resource_groups
+            .iter()
+            .find_map(|rg| rg.resources().find(|res| res.id == id).map(|res| (rg, res)))
+    }
+
+    /// The first of the resources that `res` depends on which is not running, if any. A resource
+    /// which is being stopped by an admin does not count as running.
+    pub fn unmet_dependency(&self, res: &Resource) -> Option<String> {
+        let stopping = self.stopping.lock().unwrap();
+        res.depends_on().into_iter().find(|dep| {
+            stopping.contains(dep)
+                || !self
+                    .find_resource(dep)
+                    .is_some_and(|(_, dep)| dep.is_running())
+        })
+    }
+
+    /// The resources which depend on `res`, directly or through other dependents, in the order in
+    /// which they should be stopped before it: each one comes before everything that it depends
+    /// on.
+    pub fn dependents_of(&self, res: &Resource) -> Vec<&Resource> {
+        fn visit<'a>(cluster: &'a Cluster, id: &str, order: &mut Vec<&'a Resource>) {
+            for dependent in cluster
+                .resources()
+                .filter(|other| other.depends_on().iter().any(|dep| dep == id))
+            {
+                if !order.iter().any(|seen| seen.id == dependent.id) {
+                    visit(cluster, &dependent.id, order);
+                    order.push(dependent);
+                }
+            }
+        }
+
+        let mut order = Vec::new();
+        visit(self, &res.id, &mut order);
+        order
+    }
+
+    /// The state of the cluster which decides where its resource groups should run, for the
+    /// placement decisions in manager::placement. A group which is not running is stopped in the
+    /// snapshot only if every one of its resources is known to be stopped. The groups and hosts
+    /// are in the order of their IDs.
+    pub fn snapshot(&self) -> Snapshot {
+        let mut hosts: Vec<HostState> = self
+            .hosts()
+            .map(|host| HostState {
+                id: host.id(),
+                standby: host.is_standby(),
+                down: false,
+                attributes: host.attributes().clone(),
+            })
+            .collect();
+        hosts.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut groups: Vec<GroupState> = self
+            .resource_groups()
+            .map(|rg| GroupState {
+                id: rg.id().to_string(),
+                members: rg.resources().map(|res| res.id.clone()).collect(),
+                home: rg.home_node().id(),
+                away: rg.root.node(Location::Away).map(|host| host.id()),
+                placement: rg.placement(),
+                managed: rg.get_managed(),
+                migrating: rg.is_migrating(),
+                running_on: rg.running_on().map(|host| host.id()),
+                stopped: rg
+                    .resources()
+                    .all(|res| res.get_status() == ResourceStatus::Stopped),
+            })
+            .collect();
+        groups.sort_by(|a, b| a.id.cmp(&b.id));
+
+        Snapshot {
+            hosts,
+            groups,
+            placement_weights: self.placement_weights.clone(),
+            colocation: self.colocation.clone(),
+            anti_affinity: self.anti_affinity.clone(),
+        }
+    }
+
+    /// Where to start `rg`, which is not running anywhere, and why, when that is decided by more
+    /// than which host is its home node; see Snapshot::place().
+    pub fn place(&self, rg: &ResourceGroup) -> Option<(Location, PlacementReason)> {
+        let snapshot = self.snapshot();
+        let (host, reason) = snapshot.place(snapshot.group(rg.id())?)?;
+        let loc = if host == rg.home_node().id() {
+            Location::Home
+        } else {
+            Location::Away
+        };
+        Some((loc, reason))
+    }
+
+    /// The resource groups which `rg` must not run on the same host as, by the anti-affinity
+    /// constraints.
+    pub fn anti_affine_with(&self, rg: &ResourceGroup) -> Vec<&ResourceGroup> {
+        let snapshot = self.snapshot();
+        let Some(group) = snapshot.group(rg.id()) else {
+            return Vec::new();
+        };
+        snapshot
+            .anti_affine_with(group)
+            .into_iter()
+            .filter_map(|other| self.find_resource_group(&other.id))
+            .collect()
+    }
+
+    /// The resource groups which must run with `rg`, and are running on the same host as it, so
+    /// that they are moved along with it.
+    pub fn moves_with(&self, rg: &ResourceGroup) -> Vec<&ResourceGroup> {
+        let snapshot = self.snapshot();
+        let Some(group) = snapshot.group(rg.id()) else {
+            return Vec::new();
+        };
+        snapshot
+            .moves_with(group)
+            .into_iter()
+            .filter_map(|other| self.find_resource_group(&other.id))
+            .collect()
+    }
+
+    /// The constraints which `rg` breaks where it is running now, e.g. "must not run on the same
+    /// host as ost1"; see Snapshot::violations().
+    pub fn constraint_violations(&self, rg: &ResourceGroup) -> Vec<String> {
+        let snapshot = self.snapshot();
+        snapshot
+            .group(rg.id())
+            .map(|group| snapshot.violations(group))
+            .unwrap_or_default()
+    }
+
+    /// Treat the resources in `ids` as stopped, for the purposes of starting the resources which
+    /// depend on them, until the returned guard is dropped.
+    pub fn begin_stopping(&self, ids: Vec<String>) -> StoppingGuard<'_> {
+        self.stopping.lock().unwrap().extend(ids.iter().cloned());
+        StoppingGuard { cluster: self, ids }
+    }
+
+    /// Record that an action is running on the resources in `ids`, until the returned guard is
+    /// dropped: either one requested by an admin, or a start or stop by the management loops. The
+    /// action should give up once the guard's token is canceled.
+    pub fn begin_action(&self, ids: Vec<String>) -> ActionGuard<'_> {
+        let number = self.next_action.fetch_add(1, Ordering::Relaxed);
+        let token = CancellationToken::new();
+        self.running_actions
+            .lock()
+            .unwrap()
+            .insert(number, (ids, token.clone()));
+        ActionGuard {
+            cluster: self,
+            number,
+            token,
+        }
+    }
+
+    /// The tokens of the actions which are running on the resource `id`; canceling them makes
+    /// the actions give up.
+    pub fn running_actions(&self, id: &str) -> Vec<CancellationToken> {
+        self.running_actions
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|(ids, _)| ids.iter().any(|other| other == id))
+            .map(|(_, token)| token.clone())
+            .collect()
+    }
+
+    /// The named groups of resources, and their members, in order of name.
+    pub fn groups(&self) -> Vec<(String, Vec<String>)> {
+        self.groups
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, members)| (name.clone(), members.clone()))
+            .collect()
+    }
+
+    /// The names of the groups that the resource `id` is a member of.
+    pub fn groups_of(&self, id: &str) -> Vec<String> {
+        self.groups()
+            .into_iter()
+            .filter(|(_, members)| members.iter().any(|member| member == id))
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    /// The resource groups that the members of the group `name` belong to, each listed once, or
+    /// None if there is no such group.
+    pub fn group_resource_groups(&self, name: &str) -> Option<Vec<&ResourceGroup>> {
+        let mut groups: Vec<&ResourceGroup> = Vec::new();
+        for member in self.groups.lock().unwrap().get(name)? {
+            if let Some((rg, _)) = self.find_resource(member) {
+                if !groups.iter().any(|other| other.id() == rg.id()) {
+                    groups.push(rg);
+                }
+            }
+        }
+        Some(groups)
+    }
+
     pub fn get_mgs(&self) -> Option<&Resource> {
         self.lustre_resources()
-            .find(|res| res.parameters.get("kind").unwrap() == "mgs")
+            .find(|res| res.parameter("kind").unwrap() == "mgs")
     }
 
     pub fn hosts(&self) -> impl Iterator<Item = &Arc<Host>> {
         self.hosts.values()
     }
 
+    pub fn history(&self) -> &crate::manager::history::History {
+        &self.history
+    }
+
+    /// Subscribe to the changes in the state of the cluster from now on.
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<crate::manager::events::Event> {
+        self.events.subscribe()
+    }
+
     pub fn get_host(&self, name: &str) -> Option<&Arc<Host>> {
         self.hosts.get(name)
     }
 
-    /// Create a Cluster given a path to a config file.
-    pub fn from_config(config: Option<String>) -> HandledResult<Self> {
+    /// Create a Cluster given the paths to its config files.
+    pub fn from_config(config: Vec<String>) -> HandledResult<Self> {
         let args = crate::manager::Cli {
             config,
             ..Default::default()
@@ -127,34 +903,166 @@ impl Cluster {
 
     /// Create a Cluster given a context. The context contains the arguments, which holds the
     /// (optional) path to the config file.
-    pub fn new(args: manager::Cli) -> HandledResult<Self> {
-        let path = match &args.config {
-            Some(path) => path,
-            None => &crate::default_config_path(),
-        };
-        let config = std::fs::read_to_string(path).handle_err(|e| {
-            eprintln!("Could not open config file \"{path}\": {e}");
-        })?;
+    pub fn new(mut args: manager::Cli) -> HandledResult<Self> {
+        let config = crate::config::Config::load(&args.config)?;
 
-        let config: crate::config::Config = serde_yaml::from_str(&config).handle_err(|e| {
-            eprintln!("Could not parse config file \"{path}\": {e}");
-        })?;
+        // Listen where the CLI looks for the manager: the socket in the config file is only used
+        // when neither --socket nor HALO_SOCKET is given.
+        if args.socket.is_none() && std::env::var_os("HALO_SOCKET").is_none() {
+            args.socket = config.socket.clone();
+        }
+        if args.socket_mode.is_none() {
+            if let Some(mode) = &config.socket_mode {
+                let mode = crate::manager::parse_socket_mode(mode)
+                    .handle_err(|e| eprintln!("Invalid config: socket_mode: {e}"))?;
+                args.socket_mode = Some(mode);
+            }
+        }
+        args.socket_group = args.socket_group.or(config.socket_group.clone());
+        if let Some(group) = &args.socket_group {
+            crate::manager::socket_group_id(group).handle_err(|e| eprintln!("Error: {e}."))?;
+        }
+        args.auth_token = args.auth_token.or(config.auth_token.clone());
+        if args.auth_token.as_deref() == Some("") {
+            eprintln!("Invalid auth token: it must not be empty.");
+            return Err(crate::commands::HandledError::default());
+        }
+        args.read_only_token = args.read_only_token.or(config.read_only_token.clone());
+        match (&args.auth_token, args.read_only_token.as_deref()) {
+            (_, Some("")) => {
+                eprintln!("Invalid read-only token: it must not be empty.");
+                return Err(crate::commands::HandledError::default());
+            }
+            (None, Some(_)) => {
+                eprintln!(
+                    "Invalid read-only token: it needs an auth token for full access, which is \
+                     set with --auth-token or auth_token in the config file."
+                );
+                return Err(crate::commands::HandledError::default());
+            }
+            (Some(token), Some(read_only)) if token == read_only => {
+                warn!("The read-only token is the same as the auth token, so it gives full access.")
+            }
+            _ => {}
+        }
+        args.poll_interval = args.poll_interval.or(config.poll_interval);
+        args.poll_jitter = args.poll_jitter.or(config.poll_jitter);
+        if args.poll_interval == Some(0) {
+            eprintln!("Invalid poll interval: it must be at least 1 ms.");
+            return Err(crate::commands::HandledError::default());
+        }
+        args.retry_attempts = args.retry_attempts.or(config.retry_attempts);
+        args.retry_backoff = args.retry_backoff.or(config.retry_backoff);
+        args.retry_backoff_max = args.retry_backoff_max.or(config.retry_backoff_max);
+        if args.retry_attempts == Some(0) {
+            eprintln!("Invalid retry attempts: there must be at least 1.");
+            return Err(crate::commands::HandledError::default());
+        }
+        args.action_concurrency = args.action_concurrency.or(config.action_concurrency);
+        args.maintenance_file = args.maintenance_file.or(config.maintenance_file.clone());
+        if args.action_concurrency == Some(0) {
+            eprintln!("Invalid action concurrency: at least 1 action must be allowed at once.");
+            return Err(crate::commands::HandledError::default());
+        }
 
+        // When the config is merged from several files, say where each resource came from, so
+        // that it is clear which file to change:
+        if config.sources.files.len() > 1 {
+            for (id, file) in &config.sources.resources {
+                debug!("Resource {id} is defined in config file \"{file}\".");
+            }
+        }
+
+        // A mistake in where resources may run could lead to them running in the wrong place,
+        // constraints which contradict each other could never be kept, a dependency cycle would
+        // keep resources from ever starting, a group with an unknown member would not act on all
+        // of its members, and a resource with missing or malformed parameters would only fail
+        // once its agent is run, so refuse to start with any of these:
+        let mut problems = config.placement_problems();
+        problems.extend(config.constraint_problems());
+        problems.extend(config.dependency_problems());
+        problems.extend(config.group_problems());
+        problems.extend(config.quorum_problems());
+        problems.extend(config.parameter_problems());
+        if !problems.is_empty() {
+            for problem in problems.iter() {
+                eprintln!("Invalid config: {problem}");
+            }
+            return Err(crate::commands::HandledError::default());
+        }
+
+        // Load the certificates now, so that a problem with them is reported at startup instead of
+        // on the first connection to a remote agent:
+        if args.mtls {
+            crate::tls::init_connector(&crate::tls::TlsPaths::new(config.tls.as_ref()))
+                .handle_err(|e| {
+                    eprintln!("--mtls is set, but the TLS certificates could not be loaded: {e}")
+                })?;
+            if args.listen.is_some() {
+                crate::tls::init_acceptor(&crate::tls::TlsPaths::new(config.tls.as_ref()))
+                    .handle_err(|e| {
+                        eprintln!(
+                            "--mtls is set, but the TLS certificates could not be loaded: {e}"
+                        )
+                    })?;
+            }
+        }
+
+        Self::build(args, config)
+    }
+
+    /// Create a Cluster from a config which was already loaded and checked for problems.
+    fn build(args: manager::Cli, config: crate::config::Config) -> HandledResult<Self> {
         let mut new = Cluster {
-            resource_groups: Vec::new(),
+            resource_groups: ResourceGroups::default(),
             hosts: HashMap::new(),
-            num_zpools: 0,
-            num_targets: 0,
+            num_zpools: AtomicU32::new(0),
+            num_targets: AtomicU32::new(0),
+            num_resources: AtomicUsize::new(0),
+            changes: Mutex::new(()),
             args: args.clone(),
             failover: false,
+            events: crate::manager::events::Publisher::new(),
+            history: crate::manager::history::History::new(args.history_size),
+            stopping: Mutex::new(HashSet::new()),
+            running_actions: Mutex::new(HashMap::new()),
+            next_action: AtomicU64::new(0),
+            groups: Mutex::new(BTreeMap::new()),
+            sources: Mutex::new(crate::config::Sources::default()),
+            config_generation: AtomicU64::new(1),
+            quorum: None,
+            placement_weights: config.placement_weights.clone(),
+            colocation: config.colocation.clone(),
+            anti_affinity: config.anti_affinity.clone(),
+            quorate: Mutex::new(None),
+            actions: tokio::sync::Semaphore::new(args.action_concurrency()),
+            managing: AtomicBool::new(args.manage_resources),
+            maintenance: Maintenance::new(args.maintenance_file()),
+            profiler: args.profile.then(|| Profiler::new(args.profile_slowest)),
         };
+        let events = new.events.clone();
 
         let hosts: HashMap<String, Arc<Host>> = config
             .hosts
             .iter()
-            .map(|host| (host.hostname.clone(), Arc::new(Host::from_config(host))))
+            .map(|host| {
+                (
+                    host.hostname.clone(),
+                    Arc::new(Host::from_config(host, events.clone())),
+                )
+            })
             .collect();
 
+        new.quorum = config.quorum.as_ref().map(|quorum| Quorum {
+            expected: quorum.expected_hosts.unwrap_or(config.hosts.len()),
+            tiebreak: quorum.tiebreak,
+            first_host: config
+                .hosts
+                .first()
+                .and_then(|host| hosts.get(&host.hostname))
+                .map(|host| host.id()),
+        });
+
         for config_host in config.hosts.into_iter() {
             let host = hosts.get(&config_host.hostname).ok_or(()).handle_err(|_| {
                 eprintln!(
@@ -197,9 +1105,17 @@ impl Cluster {
                     )
                 })?;
 
-            let mut rg =
-                Self::one_host_resource_groups(config_host, host, failover_host, args.clone());
-            new.resource_groups.append(&mut rg);
+            let failover_hostname = failover_host
+                .as_ref()
+                .map(|_| failover_hostname.to_string());
+            let rg = Self::one_host_resource_groups(
+                config_host,
+                host,
+                failover_host,
+                failover_hostname.as_deref(),
+                args.clone(),
+            );
+            new.add_resource_groups(rg);
         }
 
         // In the Cluster object, hosts should be mapped by their "unique" ID, which is different
@@ -208,16 +1124,259 @@ impl Cluster {
         let hosts = hosts.into_values().map(|host| (host.id(), host)).collect();
 
         new.hosts = hosts;
+        new.groups = Mutex::new(config.groups);
+        new.sources = Mutex::new(config.sources);
 
         Ok(new)
     }
 
+    /// The files that the config in effect was read from, and which of them each resource is
+    /// defined in.
+    pub fn sources(&self) -> crate::config::Sources {
+        self.sources.lock().unwrap().clone()
+    }
+
+    /// Which version of the config file is in effect: 1 until the config is first reloaded.
+    pub fn config_generation(&self) -> u64 {
+        self.config_generation.load(Ordering::Relaxed)
+    }
+
+    /// Wait for a turn to start or stop a resource, so that no more than `--action-concurrency` of
+    /// them run at once. The turn lasts until the returned permit is dropped.
+    pub async fn action_permit(&self) -> tokio::sync::SemaphorePermit<'_> {
+        self.actions
+            .acquire()
+            .await
+            .expect("the action semaphore is never closed")
+    }
+
+    /// How many starts and stops of resources are running right now.
+    pub fn actions_in_flight(&self) -> usize {
+        self.args.action_concurrency() - self.actions.available_permits()
+    }
+
+    /// Read the config file again and apply the changes to it which can be made while the manager
+    /// is running: the parameters, `depends_on`, and timeouts of existing resources, the named
+    /// groups, and whole resource groups which were added, or removed once they are stopped.
+    /// Changes which can only take effect when the manager is restarted, such as adding or removing
+    /// resources in an existing resource group, changing the hosts, or changing where a resource
+    /// runs, are not applied.
+    ///
+    /// Returns the changes which were applied, and those which need a restart; or, if the new
+    /// config can not be loaded or has problems, every problem that was found, in which case the
+    /// config in effect is left as it was.
+    pub fn reload(&self) -> Result<ReloadedConfig, Vec<String>> {
+        let config = crate::config::Config::try_load(&self.args.config).map_err(|e| vec![e])?;
+        let problems = config.validate();
+        if !problems.is_empty() {
+            return Err(problems);
+        }
+        let new = Self::build(self.args.clone(), config.clone())
+            .map_err(|_| vec!["invalid config".to_string()])?;
+
+        let mut reloaded = ReloadedConfig::default();
+
+        let old_hosts: BTreeMap<String, Option<String>> = self
+            .hosts()
+            .map(|host| (host.id(), host.failover_partner().map(|p| p.id())))
+            .collect();
+        let new_hosts: BTreeMap<String, Option<String>> = new
+            .hosts()
+            .map(|host| (host.id(), host.failover_partner().map(|p| p.id())))
+            .collect();
+        if old_hosts != new_hosts {
+            reloaded
+                .needs_restart
+                .push("the hosts or failover pairs changed".to_string());
+        }
+        let attributes = |cluster: &Cluster| -> BTreeMap<String, BTreeMap<String, f64>> {
+            cluster
+                .hosts()
+                .map(|host| (host.id(), host.attributes().clone()))
+                .collect()
+        };
+        if self.placement_weights != new.placement_weights || attributes(self) != attributes(&new) {
+            reloaded
+                .needs_restart
+                .push("the placement weights or host attributes changed".to_string());
+        }
+        if self.colocation != new.colocation || self.anti_affinity != new.anti_affinity {
+            reloaded
+                .needs_restart
+                .push("the colocation or anti-affinity constraints changed".to_string());
+        }
+
+        // Whole resource groups can be added and removed while the manager runs, as long as the
+        // hosts that they run on stay the same:
+        let (mut added_groups, mut removed_groups) = (Vec::new(), Vec::new());
+        if old_hosts == new_hosts {
+            added_groups = new
+                .resource_groups()
+                .filter(|rg| {
+                    rg.resources()
+                        .all(|res| self.find_resource(&res.id).is_none())
+                })
+                .map(|rg| rg.id().to_string())
+                .collect();
+            removed_groups = self
+                .resource_groups()
+                .filter(|rg| {
+                    rg.resources()
+                        .all(|res| new.find_resource(&res.id).is_none())
+                })
+                .map(|rg| rg.id().to_string())
+                .collect();
+        }
+        for id in added_groups.iter() {
+            let added = match self.resource_group_from_config(&config, id) {
+                Some(rg) => self.add_resource_group(rg),
+                None => Err("it would run on a host which is not in the cluster".to_string()),
+            };
+            match added {
+                Ok(()) => reloaded
+                    .applied
+                    .push(format!("resource group {id} (added)")),
+                Err(reason) => reloaded
+                    .needs_restart
+                    .push(format!("resource group {id} was added, but {reason}")),
+            }
+        }
+        // A group can only be removed once nothing depends on it, so the groups which depend on
+        // others in the list need to be removed first:
+        let mut pending = removed_groups.clone();
+        while !pending.is_empty() {
+            let before = pending.len();
+            pending.retain(|id| !self.remove_resource_group(id, false));
+            if pending.len() == before {
+                break;
+            }
+        }
+        for id in removed_groups.iter() {
+            if pending.contains(id) {
+                reloaded.needs_restart.push(format!(
+                    "resource group {id} was removed while it may still be running"
+                ));
+            } else {
+                reloaded
+                    .applied
+                    .push(format!("resource group {id} (removed)"));
+            }
+        }
+
+        for (new_rg, new_res) in new
+            .resource_groups()
+            .filter(|rg| !added_groups.iter().any(|id| id == rg.id()))
+            .flat_map(|rg| rg.resources().map(move |res| (rg, res)))
+        {
+            let id = &new_res.id;
+            let Some((old_rg, old_res)) = self.find_resource(id) else {
+                reloaded
+                    .needs_restart
+                    .push(format!("resource {id} was added"));
+                continue;
+            };
+
+            let dependents = |res: &Resource| {
+                res.dependents
+                    .iter()
+                    .map(|d| d.id.clone())
+                    .collect::<Vec<_>>()
+            };
+            if old_res.kind != new_res.kind
+                || old_rg.id() != new_rg.id()
+                || old_rg.placement() != new_rg.placement()
+                || old_res.home_node.id() != new_res.home_node.id()
+                || old_res.failover_node.as_ref().map(|host| host.id())
+                    != new_res.failover_node.as_ref().map(|host| host.id())
+                || dependents(old_res) != dependents(new_res)
+            {
+                reloaded
+                    .needs_restart
+                    .push(format!("where or how resource {id} runs changed"));
+                continue;
+            }
+
+            let parameters = new_res.parameters();
+            if old_res.parameters() != parameters {
+                old_res.set_parameters(parameters);
+                reloaded.applied.push(format!("parameters of {id}"));
+            }
+            let depends_on = new_res.depends_on();
+            if old_res.depends_on() != depends_on {
+                old_res.set_depends_on(depends_on);
+                reloaded.applied.push(format!("dependencies of {id}"));
+            }
+            let timeouts = new_res.timeouts();
+            if old_res.timeouts() != timeouts {
+                old_res.set_timeouts(timeouts);
+                reloaded.applied.push(format!("timeouts of {id}"));
+            }
+        }
+        for old_res in self
+            .resource_groups()
+            .filter(|rg| !removed_groups.iter().any(|id| id == rg.id()))
+            .flat_map(|rg| rg.resources())
+        {
+            if new.find_resource(&old_res.id).is_none() {
+                reloaded
+                    .needs_restart
+                    .push(format!("resource {} was removed", old_res.id));
+            }
+        }
+
+        let groups = new.groups.into_inner().unwrap();
+        let mut old_groups = self.groups.lock().unwrap();
+        if *old_groups != groups {
+            *old_groups = groups;
+            reloaded.applied.push("groups".to_string());
+        }
+        *self.sources.lock().unwrap() = new.sources.into_inner().unwrap();
+
+        reloaded.generation = self.config_generation.fetch_add(1, Ordering::Relaxed) + 1;
+        Ok(reloaded)
+    }
+
+    /// Build the resource group whose root resource is `id` from `config`, on this cluster's
+    /// hosts, so that it can be added to the cluster.
+    fn resource_group_from_config(
+        &self,
+        config: &crate::config::Config,
+        id: &str,
+    ) -> Option<ResourceGroup> {
+        let config_host = config
+            .hosts
+            .iter()
+            .find(|host| host.resources.contains_key(id))?;
+        let host = self
+            .hosts
+            .get(&Host::from_config(config_host, self.events.clone()).id())?;
+        let failover_host = host.failover_partner().cloned();
+        let failover_hostname = match (&config.failover_pairs, &failover_host) {
+            (Some(pairs), Some(_)) => get_failover_partner(pairs, &config_host.hostname),
+            _ => None,
+        };
+
+        Self::one_host_resource_groups(
+            config_host.clone(),
+            Arc::clone(host),
+            failover_host,
+            failover_hostname,
+            self.args.clone(),
+        )
+        .into_iter()
+        .find(|rg| rg.id() == id)
+    }
+
     /// Given a config::Host object, convert it into a vector of ResourceGroups where each
     /// ResourceGroup represents a complete dependency tree of resources on the Host.
+    ///
+    /// A resource group is homed on the Host, unless its root resource names the failover host
+    /// (`failover_hostname`) as its home node.
     fn one_host_resource_groups(
         config_host: crate::config::Host,
         host: Arc<Host>,
         failover_host: Option<Arc<Host>>,
+        failover_hostname: Option<&str>,
         args: manager::Cli,
     ) -> Vec<ResourceGroup> {
         use std::cell::RefCell;
@@ -310,12 +1469,34 @@ impl Cluster {
         roots
             .into_iter()
             .map(|root| {
-                let root = Rc::into_inner(root).unwrap().into_resource(
-                    Arc::clone(&host),
-                    failover_host.clone(),
-                    args.clone(),
-                );
-                ResourceGroup::new(root, args.clone())
+                let root = Rc::into_inner(root).unwrap();
+
+                let swapped = failover_hostname.is_some()
+                    && root.me.home_node.as_deref() == failover_hostname;
+                let (home, away) = match (&failover_host, swapped) {
+                    (Some(failover_host), true) => {
+                        (Arc::clone(failover_host), Some(Arc::clone(&host)))
+                    }
+                    _ => (Arc::clone(&host), failover_host.clone()),
+                };
+                let away_hostname = if swapped {
+                    Some(config_host.hostname.as_str())
+                } else {
+                    failover_hostname
+                };
+                let placement = Placement {
+                    prefer_home: root.me.home_node.is_some(),
+                    allow_away: match &root.me.allowed_nodes {
+                        Some(allowed) => allowed.iter().any(|n| Some(n.as_str()) == away_hostname),
+                        None => true,
+                    },
+                };
+
+                let managed = root.me.managed.unwrap_or(true);
+                let root = root.into_resource(home, away, args.clone());
+                let rg = ResourceGroup::new(root, args.clone(), placement);
+                rg.set_managed(managed);
+                rg
             })
             .collect()
     }
@@ -323,7 +1504,7 @@ impl Cluster {
     /// Print out a summary of the cluster to stdout. Mainly intended for debugging purposes.
     pub fn print_summary(&self) {
         println!("=== Resource Groups ===");
-        for rg in &self.resource_groups {
+        for rg in self.resource_groups.iter() {
             for res in rg.resources() {
                 print!("{}: ", res.id);
                 println!("{}", res.params_string());
@@ -344,6 +1525,101 @@ impl Cluster {
     }
 }
 
+/// How many chunks ResourceGroups has room for, which is enough for 2^32 - 1 resource groups.
+const MAX_CHUNKS: usize = 32;
+
+/// The resource groups of a cluster. Groups can be added while the management loops hold
+/// references to the ones that are already there, so a group is never moved or dropped once it
+/// is added: they are kept in chunks which are never reallocated, each holding twice as many
+/// groups as the one before it. A group which is removed stays, marked as removed, and the same
+/// group may later be marked as in the cluster again.
+#[derive(Debug, Default)]
+struct ResourceGroups {
+    chunks: [OnceLock<Box<[OnceLock<ResourceGroup>]>>; MAX_CHUNKS],
+
+    /// How many groups have been added, including the ones which were removed since.
+    len: AtomicUsize,
+
+    /// Held while a group is added, so that no two are added in the same place.
+    push: Mutex<()>,
+}
+
+impl ResourceGroups {
+    /// The chunk which holds the group at `index`, and where it is in that chunk.
+    fn locate(index: usize) -> (usize, usize) {
+        let n = index + 1;
+        let chunk = (usize::BITS - 1 - n.leading_zeros()) as usize;
+        (chunk, n - (1 << chunk))
+    }
+
+    fn push(&self, rg: ResourceGroup) {
+        let _push = self.push.lock().unwrap();
+        let index = self.len.load(Ordering::Acquire);
+        let (chunk, offset) = Self::locate(index);
+        let slots = self
+            .chunks
+            .get(chunk)
+            .expect("too many resource groups")
+            .get_or_init(|| (0..1 << chunk).map(|_| OnceLock::new()).collect());
+        if slots[offset].set(rg).is_err() {
+            unreachable!("resource group {index} was added twice");
+        }
+        self.len.store(index + 1, Ordering::Release);
+    }
+
+    /// Every group which was added, including the ones which were removed since, in the order
+    /// that they were added.
+    fn iter_all(&self) -> impl Iterator<Item = &ResourceGroup> {
+        (0..self.len.load(Ordering::Acquire)).filter_map(|index| {
+            let (chunk, offset) = Self::locate(index);
+            self.chunks[chunk].get()?[offset].get()
+        })
+    }
+
+    /// The groups which are in the cluster, in the order that they were added.
+    fn iter(&self) -> impl Iterator<Item = &ResourceGroup> {
+        self.iter_all().filter(|rg| !rg.is_removed())
+    }
+}
+
+/// While a StoppingGuard exists, its resources are being stopped by an admin.
+pub struct StoppingGuard<'a> {
+    cluster: &'a Cluster,
+    ids: Vec<String>,
+}
+
+impl Drop for StoppingGuard<'_> {
+    fn drop(&mut self) {
+        let mut stopping = self.cluster.stopping.lock().unwrap();
+        for id in self.ids.iter() {
+            stopping.remove(id);
+        }
+    }
+}
+
+/// While an ActionGuard exists, its action can be canceled with Cluster::running_actions().
+pub struct ActionGuard<'a> {
+    cluster: &'a Cluster,
+    number: u64,
+    token: CancellationToken,
+}
+
+impl ActionGuard<'_> {
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+impl Drop for ActionGuard<'_> {
+    fn drop(&mut self) {
+        self.cluster
+            .running_actions
+            .lock()
+            .unwrap()
+            .remove(&self.number);
+    }
+}
+
 /// Given a list `pairs` of failover pairs, and a hostname `name`, return its partner, if one
 /// exists.
 pub fn get_failover_partner<'pairs>(
@@ -360,3 +1636,188 @@ pub fn get_failover_partner<'pairs>(
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate() {
+        assert_eq!(ResourceGroups::locate(0), (0, 0));
+        assert_eq!(ResourceGroups::locate(1), (1, 0));
+        assert_eq!(ResourceGroups::locate(2), (1, 1));
+        assert_eq!(ResourceGroups::locate(3), (2, 0));
+        assert_eq!(ResourceGroups::locate(6), (2, 3));
+        assert_eq!(ResourceGroups::locate(7), (3, 0));
+    }
+
+    /// A resource group made up of a single zpool with the given ID, on the cluster's only host.
+    fn zpool_group(cluster: &Cluster, id: &str) -> ResourceGroup {
+        let config = crate::config::Resource {
+            kind: "heartbeat/ZFS".to_string(),
+            parameters: HashMap::from([("pool".to_string(), id.to_string())]),
+            ..Default::default()
+        };
+        let host = Arc::clone(cluster.hosts().next().unwrap());
+        let root = Resource::from_config(
+            config,
+            Vec::new(),
+            host,
+            None,
+            id.to_string(),
+            cluster.args.clone(),
+        );
+        ResourceGroup::new(root, cluster.args.clone(), Placement::default())
+    }
+
+    fn set_status(rg: &ResourceGroup, status: ResourceStatus) {
+        for res in rg.resources() {
+            *res.status.lock().unwrap() = status.clone();
+        }
+    }
+
+    #[test]
+    fn test_quorum_reads_have_no_effect() {
+        let mut cluster =
+            Cluster::from_config(vec![crate::test_env::test_path("simple.yaml")]).unwrap();
+        cluster.quorum = Some(Quorum {
+            expected: 1,
+            tiebreak: crate::manager::quorum::Tiebreak::Deny,
+            first_host: None,
+        });
+
+        // Reading the quorum, as the status and metrics do, does not record a change:
+        assert!(!cluster.has_quorum());
+        assert_eq!(*cluster.quorate.lock().unwrap(), None);
+
+        // Only the management loops do:
+        assert!(!cluster.update_quorum());
+        assert_eq!(*cluster.quorate.lock().unwrap(), Some(false));
+        cluster.hosts().next().unwrap().set_reachable(true);
+        assert!(cluster.has_quorum());
+        assert_eq!(*cluster.quorate.lock().unwrap(), Some(false));
+        assert!(cluster.update_quorum());
+        assert_eq!(*cluster.quorate.lock().unwrap(), Some(true));
+    }
+
+    #[test]
+    fn test_add_and_remove_during_loop() {
+        let cluster =
+            Cluster::from_config(vec![crate::test_env::test_path("simple.yaml")]).unwrap();
+        assert_eq!(cluster.resource_count(), 4);
+        let held = cluster.get_resource_group("test_zpool");
+
+        // Add and remove groups while iterating over them, as the management loops do. The groups
+        // which are added are not seen by an iteration which is already under way:
+        let mut seen = Vec::new();
+        for rg in cluster.resource_groups() {
+            seen.push(rg.id());
+            for i in 0..10 {
+                assert!(cluster
+                    .add_resource_group(zpool_group(&cluster, &format!("zpool_{i}")))
+                    .is_ok());
+            }
+            // A group whose status is not known yet may be running:
+            assert!(!cluster.remove_resource_group("test_zpool", false));
+            set_status(rg, ResourceStatus::Stopped);
+            assert!(cluster.remove_resource_group("test_zpool", false));
+        }
+        assert_eq!(seen, vec!["test_zpool"]);
+
+        // The removed group is still there for the tasks which refer to it, but not listed:
+        assert_eq!(held.id(), "test_zpool");
+        assert!(held.is_removed());
+        assert!(cluster.get_resource_group("test_zpool").is_removed());
+        assert!(cluster.find_resource("test_mgt").is_none());
+        let ids: Vec<&str> = cluster.resource_groups().map(|rg| rg.id()).collect();
+        let expected: Vec<String> = (0..10).map(|i| format!("zpool_{i}")).collect();
+        assert_eq!(ids, expected);
+        assert_eq!(cluster.resource_count(), 10);
+        assert_eq!(cluster.num_zpools(), 10);
+        assert_eq!(cluster.num_targets(), 0);
+
+        // IDs which are in use, or were removed from a different group, are not added again:
+        assert!(cluster
+            .add_resource_group(zpool_group(&cluster, "zpool_3"))
+            .is_err());
+        assert!(cluster
+            .add_resource_group(zpool_group(&cluster, "test_zpool"))
+            .is_err());
+        assert!(!cluster.remove_resource_group("test_zpool", true));
+        assert!(!cluster.remove_resource_group("no_such_group", true));
+
+        // A running group is only removed by force:
+        let zpool_0 = cluster.get_resource_group("zpool_0");
+        set_status(zpool_0, ResourceStatus::RunningOnHome);
+        assert!(!cluster.remove_resource_group("zpool_0", false));
+        assert!(cluster.remove_resource_group("zpool_0", true));
+
+        // A group which others depend on is not removed, even by force:
+        let (_, zpool_2) = cluster.find_resource("zpool_2").unwrap();
+        zpool_2.set_depends_on(vec!["zpool_1".to_string()]);
+        set_status(
+            cluster.get_resource_group("zpool_1"),
+            ResourceStatus::Stopped,
+        );
+        assert!(!cluster.remove_resource_group("zpool_1", true));
+
+        assert_eq!(cluster.resource_count(), 9);
+        assert_eq!(cluster.resource_groups().count(), 9);
+    }
+
+    #[test]
+    fn test_remove_and_add_back() {
+        let cluster =
+            Cluster::from_config(vec![crate::test_env::test_path("simple.yaml")]).unwrap();
+        assert!(cluster
+            .add_resource_group(zpool_group(&cluster, "zpool_a"))
+            .is_ok());
+        let old = cluster.get_resource_group("zpool_a");
+        set_status(old, ResourceStatus::Stopped);
+        old.set_managed(false);
+
+        // The management task of the group still holds it for a while after it is removed, and it
+        // is not added back until the task lets go of it:
+        assert!(old.hold());
+        assert!(cluster.remove_resource_group("zpool_a", false));
+        assert_eq!(cluster.resource_count(), 4);
+        let err = cluster
+            .add_resource_group(zpool_group(&cluster, "zpool_a"))
+            .unwrap_err();
+        assert!(err.contains("try again"), "{err}");
+        assert!(old.is_removed());
+        old.release();
+
+        // A group with the same ID, but other resources or placement, is not added either:
+        let other = ResourceGroup::new(
+            zpool_group(&cluster, "zpool_a").root,
+            cluster.args.clone(),
+            Placement {
+                allow_away: false,
+                ..Default::default()
+            },
+        );
+        let err = cluster.add_resource_group(other).unwrap_err();
+        assert!(err.contains("until the manager restarts"), "{err}");
+        assert!(old.is_removed());
+
+        // The same group is added back in its old place, with its new settings:
+        let groups = cluster.resource_groups.iter_all().count();
+        assert!(cluster
+            .add_resource_group(zpool_group(&cluster, "zpool_a"))
+            .is_ok());
+        assert_eq!(cluster.resource_groups.iter_all().count(), groups);
+        assert!(std::ptr::eq(cluster.get_resource_group("zpool_a"), old));
+        assert!(!old.is_removed());
+        assert!(old.get_managed());
+        assert_eq!(cluster.resource_count(), 5);
+        assert_eq!(cluster.num_zpools(), 2);
+
+        // And it can be removed and added back again:
+        assert!(cluster.remove_resource_group("zpool_a", false));
+        assert!(cluster
+            .add_resource_group(zpool_group(&cluster, "zpool_a"))
+            .is_ok());
+        assert_eq!(cluster.resource_count(), 5);
+    }
+}
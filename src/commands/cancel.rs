@@ -0,0 +1,48 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+use {clap::Args, reqwest::StatusCode};
+
+use crate::{commands::*, manager::http};
+
+#[derive(Args, Debug, Clone)]
+pub struct CancelArgs {
+    /// The resource whose start, stop, restart, or migration should be canceled.
+    #[arg(value_parser = parse_resource_id)]
+    resource: String,
+}
+
+/// Ask the manager to give up on the actions on a resource which are still in progress, whether
+/// admins requested them or the management loops started them. The commands which requested them
+/// fail, saying that they were canceled.
+pub fn cancel(cli: &Cli, args: &CancelArgs) -> CommandResult<()> {
+    let resource = &args.resource;
+    let params = http::CancelArgs {
+        dry_run: cli.dry_run,
+    };
+
+    let client = HaloClient::from_cli(cli)?;
+    let response = client
+        .post(&format!("/cancel/{resource}"))
+        .json(&params)
+        .send()
+        .handle_request_err(client.addr())?;
+
+    let done = format!("could not cancel actions on '{resource}'");
+    match response.status() {
+        StatusCode::OK => {}
+        StatusCode::NOT_FOUND => return Err(resource_not_found(cli, &done, resource)),
+        _ => return Err(reply_error(response, &done)),
+    };
+
+    let Some(reply): Option<http::CancelReply> = read_reply(client.addr(), response)? else {
+        return Ok(());
+    };
+    match reply.canceled {
+        0 => println!("{}: no action in progress", reply.id),
+        1 => println!("{}: canceled 1 action", reply.id),
+        n => println!("{}: canceled {n} actions", reply.id),
+    }
+
+    Ok(())
+}
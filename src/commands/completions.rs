@@ -0,0 +1,21 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+use clap::{Args, CommandFactory};
+
+use crate::commands::{Cli, CommandResult};
+
+#[derive(Args, Debug, Clone)]
+pub struct CompletionsArgs {
+    /// The shell to generate the completion script for.
+    shell: clap_complete::Shell,
+}
+
+/// Print a completion script for `shell` to stdout. The script is generated from the definition of
+/// the command line itself, so it does not need a config file or a running manager.
+pub fn completions(args: &CompletionsArgs) -> CommandResult<()> {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(args.shell, &mut command, name, &mut std::io::stdout());
+    Ok(())
+}
@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+use clap::{Args, Subcommand};
+
+use crate::{commands::*, manager::http};
+
+#[derive(Args, Debug, Clone)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    command: ConfigCommand,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum ConfigCommand {
+    /// Print the settings that the manager is running with, once its command line, environment,
+    /// and config file are combined. Secrets such as the auth token are redacted.
+    Dump(DumpArgs),
+}
+
+#[derive(Args, Debug, Clone)]
+struct DumpArgs {
+    /// Print the settings as a JSON object, for consumption by other tools. Otherwise, they are
+    /// printed as YAML.
+    #[arg(long)]
+    json: bool,
+}
+
+pub fn config(cli: &Cli, args: &ConfigArgs) -> CommandResult<()> {
+    match &args.command {
+        ConfigCommand::Dump(args) => dump(cli, args),
+    }
+}
+
+fn dump(cli: &Cli, args: &DumpArgs) -> CommandResult<()> {
+    let client = HaloClient::from_cli(cli)?;
+    let config: http::ConfigJson = client
+        .get("/config")
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.json())
+        .handle_request_err(client.addr())?;
+
+    let out = if args.json {
+        serde_json::to_string_pretty(&config).map_err(|e| e.to_string())
+    } else {
+        serde_yaml::to_string(&config).map_err(|e| e.to_string())
+    };
+    let out = out.map_err(|e| std::io::Error::other(format!("could not print config: {e}")))?;
+    println!("{}", out.trim_end());
+
+    Ok(())
+}
@@ -0,0 +1,229 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+use {clap::Args, reqwest::StatusCode};
+
+use crate::{
+    agent::{Schema, SCHEMAS},
+    commands::{status::StatusKind, *},
+    manager::http,
+};
+
+#[derive(Args, Debug, Clone)]
+pub struct DescribeArgs {
+    /// The resource to describe.
+    #[arg(value_parser = parse_resource_id, required_unless_present = "schema")]
+    resource: Option<String>,
+
+    /// Instead of a resource, describe the parameters that resources of this kind take, e.g.
+    /// 'lustre/Lustre', as they are checked when the config is loaded and by 'set-param'. This
+    /// does not need the manager.
+    #[arg(long, value_name = "KIND", conflicts_with = "resource")]
+    schema: Option<String>,
+
+    /// Print the resource as a JSON object, in the same form as each entry of 'halo status
+    /// --json'.
+    #[arg(long)]
+    json: bool,
+}
+
+/// Print everything that the manager knows about a single resource.
+pub fn describe(cli: &Cli, args: &DescribeArgs) -> CommandResult<()> {
+    let resource = match (&args.resource, &args.schema) {
+        (Some(resource), _) => resource,
+        (None, Some(kind)) => return describe_schema(kind, args.json),
+        // clap requires one or the other:
+        (None, None) => unreachable!(),
+    };
+    let client = HaloClient::from_cli(cli)?;
+    let response = client
+        .get(&format!("/resources/{resource}"))
+        .send()
+        .handle_request_err(client.addr())?;
+
+    match response.status() {
+        StatusCode::OK => {}
+        StatusCode::NOT_FOUND => {
+            let done = format!("could not describe '{resource}'");
+            return Err(resource_not_found(cli, &done, resource));
+        }
+        status => {
+            return Err(CommandError::with_code(
+                status_exit_code(status),
+                format!("could not describe '{resource}': unexpected error: {status}"),
+            ));
+        }
+    }
+
+    let res: http::ResourceJson = response.json().handle_request_err(client.addr())?;
+    if args.json {
+        let out = serde_json::to_string_pretty(&res).map_err(std::io::Error::from)?;
+        println!("{out}");
+        return Ok(());
+    }
+
+    print!("{}", format_resource(&res, cli.use_color()));
+    Ok(())
+}
+
+/// Print the parameters that resources of `kind` take.
+fn describe_schema(kind: &str, json: bool) -> CommandResult<()> {
+    let Some(schema) = Schema::of(kind) else {
+        let kinds: Vec<&str> = SCHEMAS.iter().map(|schema| schema.kind).collect();
+        return Err(CommandError::NotFound(format!(
+            "no schema is known for resources of kind '{kind}', so their parameters are not \
+             checked. Known kinds: {}.",
+            kinds.join(", ")
+        )));
+    };
+    if json {
+        let out = serde_json::to_string_pretty(schema).map_err(std::io::Error::from)?;
+        println!("{out}");
+        return Ok(());
+    }
+
+    print!("{}", format_schema(schema));
+    Ok(())
+}
+
+/// Format `schema` as its kind, followed by a line for each of its parameters, in order.
+fn format_schema(schema: &Schema) -> String {
+    let mut out = format!("kind: {}\nparameters:\n", schema.kind);
+    for param in schema.params.iter() {
+        let required = if param.required { " (required)" } else { "" };
+        out += &format!(
+            "  {}{required}: {}. {}\n",
+            param.name, param.kind, param.description
+        );
+    }
+    out
+}
+
+/// Format `res` as one "field: value" line for each of its fields, leaving out the ones which are
+/// empty. With `color`, the status is colored by its kind.
+fn format_resource(res: &http::ResourceJson, color: bool) -> String {
+    let mut lines = vec![
+        ("id", res.id.clone()),
+        ("kind", res.kind.clone()),
+        (
+            "status",
+            StatusKind::from(res.status.as_str()).paint(&res.status, color),
+        ),
+    ];
+    if let Some(comment) = &res.comment {
+        lines.push(("comment", comment.clone()));
+    }
+    for warning in res.warnings.iter() {
+        lines.push(("warning", StatusKind::Abnormal.paint(warning, color)));
+    }
+    lines.push((
+        "managed",
+        if res.managed { "yes" } else { "no" }.to_string(),
+    ));
+    lines.push(("target state", res.target_state.to_string()));
+    lines.push((
+        "location",
+        res.location
+            .clone()
+            .unwrap_or_else(|| "not running".to_string()),
+    ));
+    lines.push(("home node", res.home_node.clone()));
+    lines.push(("allowed nodes", res.allowed_nodes.join(", ")));
+    if !res.depends_on.is_empty() {
+        lines.push(("depends on", res.depends_on.join(", ")));
+    }
+    if !res.groups.is_empty() {
+        lines.push(("groups", res.groups.join(", ")));
+    }
+    lines.push(("start timeout", format!("{}s", res.start_timeout)));
+    lines.push(("stop timeout", format!("{}s", res.stop_timeout)));
+    if res.retries > 0 {
+        lines.push(("failures", res.retries.to_string()));
+        lines.push((
+            "next attempt",
+            res.next_retry
+                .map(history::format_time)
+                .unwrap_or_else(|| "none; run with --clear-failed to try again".to_string()),
+        ));
+    }
+
+    let mut out = String::new();
+    for (field, value) in lines {
+        out += &format!("{field}: {value}\n");
+    }
+
+    // Sort the parameters so that the output is stable between invocations:
+    let mut params: Vec<_> = res.parameters.iter().collect();
+    params.sort();
+    out += "parameters:\n";
+    for (key, val) in params {
+        out += &format!("  {key}: {val}\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_format_resource() {
+        let res = http::ResourceJson {
+            id: "mdt_0".to_string(),
+            kind: "lustre/Lustre".to_string(),
+            parameters: HashMap::from([
+                ("target".to_string(), "mdt0".to_string()),
+                ("mountpoint".to_string(), "/mnt/mdt0".to_string()),
+            ]),
+            status: "Stopped".to_string(),
+            comment: None,
+            managed: true,
+            target_state: crate::resource::TargetState::Started,
+            home_node: "mds00".to_string(),
+            location: None,
+            allowed_nodes: vec!["mds00".to_string(), "mds01".to_string()],
+            depends_on: vec![],
+            groups: vec!["mdts".to_string()],
+            retries: 5,
+            next_retry: None,
+            start_timeout: 300,
+            stop_timeout: 60,
+            warnings: vec![],
+        };
+        assert!(format_resource(&res, true).contains("status: \x1b[33mStopped\x1b[0m\n"));
+        assert_eq!(
+            format_resource(&res, false),
+            "id: mdt_0\n\
+             kind: lustre/Lustre\n\
+             status: Stopped\n\
+             managed: yes\n\
+             target state: started\n\
+             location: not running\n\
+             home node: mds00\n\
+             allowed nodes: mds00, mds01\n\
+             groups: mdts\n\
+             start timeout: 300s\n\
+             stop timeout: 60s\n\
+             failures: 5\n\
+             next attempt: none; run with --clear-failed to try again\n\
+             parameters:\n  \
+             mountpoint: /mnt/mdt0\n  \
+             target: mdt0\n"
+        );
+    }
+
+    #[test]
+    fn test_format_schema() {
+        assert_eq!(
+            format_schema(Schema::of("heartbeat/ZFS").unwrap()),
+            "kind: heartbeat/ZFS\n\
+             parameters:\n  \
+             pool (required): a value which is not empty. The name of the zpool to import.\n  \
+             importargs: a value which is not empty. Extra arguments to pass to `zpool import`.\n  \
+             importforce: true or false. Whether to force the import of a pool which another host \
+             imported.\n"
+        );
+    }
+}
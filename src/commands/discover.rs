@@ -1,12 +1,21 @@
 // SPDX-License-Identifier: MIT
 // Copyright 2025. Triad National Security, LLC.
 
-use std::{collections::HashMap, io, process::Command};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, io,
+    sync::Arc,
+};
 
-use clap::Args;
+use {clap::Args, serde::Serialize};
 
 use crate::{
-    commands::{Handle, HandledResult},
+    commands::{
+        discovery::{self, DiscoveryAgent, RemoteHost},
+        merge_nodesets,
+        query::{self, Attribute, Query},
+        runtime_error, Cli, CommandError, CommandResult, ExitCode, NodesetError,
+    },
     config,
 };
 
@@ -17,166 +26,1015 @@ pub struct DiscoverArgs {
 
     #[arg()]
     hostnames: Vec<String>,
+
+    /// Hosts to leave out of the discovery, such as ones that are down for maintenance, in
+    /// nodeset syntax. This may be given more than once.
+    #[arg(long, value_name = "NODESET")]
+    exclude: Vec<String>,
+
+    /// How to print what was found. Progress and errors are always printed on stderr, so that
+    /// stdout only has the output in this format.
+    #[arg(long, alias = "format", value_enum, default_value_t)]
+    output: OutputFormat,
+
+    /// How many hosts to discover at the same time.
+    #[arg(long, default_value_t = 32, value_parser = clap::value_parser!(u32).range(1..))]
+    concurrency: u32,
+
+    /// Write a starter config file with the hosts that were reached to this path, instead of
+    /// printing what was found. Each resource group in it starts out unmanaged, with its home node
+    /// set to the host that it was found on.
+    #[arg(long, value_name = "PATH", conflicts_with = "output")]
+    emit_config: Option<String>,
+
+    /// Replace the file given to --emit-config if it already exists.
+    #[arg(long, requires = "emit_config")]
+    force: bool,
+
+    /// Compare what was found with the resources in the config file, and print the resources
+    /// which were added, removed, or changed, instead of what was found. The exit status is 1 if
+    /// there are any.
+    #[arg(long, conflicts_with = "emit_config")]
+    diff: bool,
+
+    /// Only keep the hosts for which this query holds, e.g. 'resources>=2 && hostname!=oss00'.
+    /// The attributes of a host are hostname, reachable, resources, zpools, targets, and error;
+    /// comparisons are joined with && and ||, and may be grouped with parentheses.
+    #[arg(long = "where", value_name = "QUERY", value_parser = Query::parse)]
+    filter: Option<Query>,
+
+    /// Print only these attributes of each host, separated by commas, such as
+    /// 'hostname,resources', in the format chosen with --output.
+    #[arg(
+        long,
+        value_name = "ATTRIBUTES",
+        value_delimiter = ',',
+        value_parser = query::parse_attribute,
+        conflicts_with_all = ["emit_config", "diff"],
+    )]
+    select: Vec<Attribute>,
+
+    /// Only look for the resources found by these agents, separated by commas, such as 'zpool'.
+    /// The agents are zpool and lustre; by default, all of them are run.
+    #[arg(
+        long,
+        value_name = "AGENTS",
+        value_delimiter = ',',
+        value_parser = discovery::parse_agent_type,
+    )]
+    agent_type: Vec<String>,
 }
 
-pub fn discover(args: &DiscoverArgs) -> HandledResult<()> {
-    let mut config = config::Config {
-        hosts: Vec::new(),
-        failover_pairs: None,
+/// What was found on a host that was reached: the host, with its resources as they would be in a
+/// config file, and the name of the agent that found each resource.
+#[derive(Debug)]
+struct Found {
+    host: config::Host,
+    agents: HashMap<String, &'static str>,
+}
+
+/// The result of discovering one host, by its hostname.
+type HostResult = (String, io::Result<Found>);
+
+pub fn discover(cli: &Cli, args: &DiscoverArgs) -> CommandResult<()> {
+    let targets = discovery_targets(&args.hostnames, &args.exclude)?;
+    if targets.is_empty() && !args.hostnames.is_empty() {
+        return Err(CommandError::Validation(
+            "no hosts remain after exclusions.".to_string(),
+        ));
+    }
+    if let Some(path) = &args.emit_config {
+        if !args.force && std::path::Path::new(path).exists() {
+            return Err(already_exists(path));
+        }
+    }
+    let configured = match args.diff {
+        true if matches!(args.output, OutputFormat::Csv | OutputFormat::Wide) => {
+            return Err(CommandError::Validation(
+                "--diff can only be printed as text or json.".to_string(),
+            ));
+        }
+        true => Some(config::Config::try_load(&cli.config).map_err(CommandError::Validation)?),
+        false => None,
     };
-    let hostnames = crate::commands::nodesets2hostnames(&args.hostnames)
-        .handle_err(|e| eprintln!("nodeset syntax error: {e}"))?;
+    if args.verbose {
+        eprintln!("Discovering {} host(s).", targets.len());
+    }
+    let hostnames: Vec<String> = targets.iter().collect();
+    let agents = discovery::agents(&args.agent_type);
 
-    for hostname in hostnames {
-        let host = discover_one_host(&hostname, args.verbose).unwrap();
-        config.hosts.push(host);
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(runtime_error)?;
+    let results = runtime.block_on(discover_hosts(hostnames, &agents, args));
+
+    // The hosts are discovered in whatever order they respond, so sort them to keep the output
+    // the same from one run to the next:
+    let mut results = results;
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+    let failed: Vec<(String, String)> = results
+        .iter()
+        .filter_map(|(hostname, result)| {
+            result
+                .as_ref()
+                .err()
+                .map(|e| (hostname.clone(), e.to_string()))
+        })
+        .collect();
+
+    // Hosts which could not be discovered are reported even if the query leaves them out, since
+    // discovery still failed on them:
+    if let Some(filter) = &args.filter {
+        results.retain(|(hostname, result)| {
+            filter.matches(&|attr| attr.of(hostname, result.as_ref().map(|found| &found.host)))
+        });
+    }
+
+    let reached = |results: Vec<HostResult>| -> Vec<config::Host> {
+        results
+            .into_iter()
+            .filter_map(|(_, result)| result.ok().map(|found| found.host))
+            .collect()
+    };
+    let mut drifted = false;
+    if let Some(path) = &args.emit_config {
+        emit_config(path, &starter_config(reached(results)), args.force)?;
+    } else if let Some(configured) = &configured {
+        let drift = Drift::between(configured, &reached(results));
+        if args.output == OutputFormat::Json {
+            let out = serde_json::to_string_pretty(&drift)
+                .map_err(|e| io::Error::other(format!("could not serialize differences: {e}")))?;
+            println!("{out}");
+        } else {
+            print!("{drift}");
+        }
+        drifted = !drift.is_empty();
+    } else if !args.select.is_empty() {
+        let out = select(&results, &args.select, args.output)
+            .map_err(|e| io::Error::other(format!("could not serialize discovery results: {e}")))?;
+        println!("{out}");
+    } else {
+        let out = match args.output {
+            OutputFormat::Text => {
+                let config = config::Config {
+                    hosts: reached(results),
+                    ..Default::default()
+                };
+                serde_yaml::to_string(&config)
+                    .map_err(|e| io::Error::other(format!("could not serialize config: {e}")))?
+            }
+            OutputFormat::Json => {
+                let reports: Vec<HostReport> = results.iter().map(HostReport::from).collect();
+                serde_json::to_string_pretty(&reports).map_err(|e| {
+                    io::Error::other(format!("could not serialize discovery results: {e}"))
+                })?
+            }
+            OutputFormat::Csv => to_csv(&results),
+            OutputFormat::Wide => select(&results, &WIDE_ATTRIBUTES, args.output).map_err(|e| {
+                io::Error::other(format!("could not format discovery results: {e}"))
+            })?,
+        };
+        println!("{out}");
+    }
+
+    // The differences, or the hosts which could not be discovered, were already printed:
+    if failed.is_empty() {
+        return match drifted {
+            true => Err(CommandError::Reported(ExitCode::Failure)),
+            false => Ok(()),
+        };
+    }
+    eprintln!("Could not discover {} host(s):", failed.len());
+    for (hostname, e) in failed.iter() {
+        eprintln!("  {hostname}: {e}");
+    }
+    Err(CommandError::Reported(ExitCode::Failure))
+}
+
+/// A config for a new cluster made of `hosts`, in which every resource group is unmanaged, so that
+/// the manager does not act on any of them until told to, and prefers to run on the host where it
+/// was found.
+fn starter_config(mut hosts: Vec<config::Host>) -> config::Config {
+    for host in hosts.iter_mut() {
+        for resource in host.resources.values_mut() {
+            if resource.requires.is_none() {
+                resource.managed = Some(false);
+                resource.home_node = Some(host.hostname.clone());
+            }
+        }
+    }
+    config::Config {
+        hosts,
+        ..Default::default()
+    }
+}
+
+/// Write `config` to a new file at `path`, or replace the file there with `force`. Since discovery
+/// can not tell which hosts are failover partners, the problems which the config still has are
+/// printed, along with a reminder to add the pairs.
+fn emit_config(path: &str, config: &config::Config, force: bool) -> CommandResult<()> {
+    use std::io::Write;
+
+    let contents = serde_yaml::to_string(config)
+        .map_err(|e| io::Error::other(format!("could not serialize config: {e}")))?;
+
+    let mut options = std::fs::OpenOptions::new();
+    if force {
+        options.write(true).create(true).truncate(true);
+    } else {
+        options.write(true).create_new(true);
+    }
+    let mut file = match options.open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Err(already_exists(path)),
+        Err(e) => {
+            let message = format!("could not create \"{path}\": {e}");
+            return Err(io::Error::new(e.kind(), message).into());
+        }
+    };
+    file.write_all(contents.as_bytes())
+        .map_err(|e| io::Error::new(e.kind(), format!("could not write \"{path}\": {e}")))?;
+
+    let resources: usize = config.hosts.iter().map(|host| host.resources.len()).sum();
+    eprintln!(
+        "Wrote a config with {} host(s) and {resources} resource(s) to \"{path}\".",
+        config.hosts.len()
+    );
+    if config.failover_pairs.is_none() {
+        eprintln!("Add failover_pairs to it, so that resources can fail over.");
+    }
+    let problems = config.validate();
+    if !problems.is_empty() {
+        eprintln!("Fix these problems before using it:");
+        for problem in problems.iter() {
+            eprintln!("  {problem}");
+        }
     }
-    println!("{}", serde_yaml::to_string(&config).unwrap());
     Ok(())
 }
 
-/// Attempt to discover all of the resources (zpools and lustre targerts) running on `hostname`,
-/// and construct them into a config::Host object that owns those resources.
-fn discover_one_host(hostname: &str, verbose: bool) -> io::Result<config::Host> {
-    let zpool_output = get_zpool_output(hostname, verbose)?;
+/// The error for a file at `path` which --emit-config would replace without --force.
+fn already_exists(path: &str) -> CommandError {
+    CommandError::Validation(format!(
+        "\"{path}\" already exists; pass --force to replace it."
+    ))
+}
 
-    let mut resources = parse_zpool_output(zpool_output);
+/// How the resources found by discovery differ from those in a config file, for `--diff`.
+///
+/// Resources are matched by ID, wherever they were found, since a resource that is running on its
+/// host's failover partner has not changed. Only what discovery can find out about a resource is
+/// compared: its kind, what it requires, and the parameters that discovery reports, by key and
+/// value. Parameters are compared as maps, so the order in which they are listed never counts as
+/// a change, and parameters which are only in the config, which discovery can not see, are
+/// ignored.
+#[derive(Serialize, Debug, Default, PartialEq)]
+struct Drift {
+    /// Resources which were found, but are not in the config.
+    added: Vec<DriftResource>,
 
-    let lustre_output = get_lustre_output(hostname, verbose)?;
+    /// Resources in the config, listed under a host which was discovered, which were not found on
+    /// any host.
+    removed: Vec<DriftResource>,
 
-    let lustre_resources = parse_lustre_output(lustre_output);
+    changed: Vec<DriftChange>,
+}
 
-    resources.extend(lustre_resources);
+#[derive(Serialize, Debug, PartialEq)]
+struct DriftResource {
+    id: String,
+    host: String,
+    kind: String,
+}
 
-    Ok(config::Host {
-        hostname: hostname.to_string(),
-        resources,
-        fence_agent: None,
-        fence_parameters: None,
-    })
+#[derive(Serialize, Debug, PartialEq)]
+struct DriftChange {
+    id: String,
+
+    /// What changed: "kind", "requires", or "parameters.<key>".
+    field: String,
+
+    /// The value in the config, if it has one.
+    configured: Option<String>,
+
+    /// The value that was found.
+    discovered: Option<String>,
 }
 
-fn parse_lustre_output(output: String) -> HashMap<String, config::Resource> {
-    let mut resources = HashMap::new();
+impl Drift {
+    /// Compare the resources in `configured` with those on the `discovered` hosts. Each list is in
+    /// order of resource ID, and the changes to a resource in order of field.
+    fn between(configured: &config::Config, discovered: &[config::Host]) -> Self {
+        let resources = |hosts: &[config::Host]| -> BTreeMap<String, (String, config::Resource)> {
+            hosts
+                .iter()
+                .flat_map(|host| {
+                    host.resources
+                        .iter()
+                        .map(|(id, res)| (id.clone(), (host.hostname.clone(), res.clone())))
+                })
+                .collect()
+        };
+        let old = resources(&configured.hosts);
+        let new = resources(discovered);
+        let entry = |id: &str, (host, res): &(String, config::Resource)| DriftResource {
+            id: id.to_string(),
+            host: host.clone(),
+            kind: res.kind.clone(),
+        };
 
-    for line in output.lines() {
-        let res = config::Resource::new_lustre(line);
+        let mut drift = Drift::default();
+        for (id, found) in new.iter() {
+            let Some((_, res)) = old.get(id) else {
+                drift.added.push(entry(id, found));
+                continue;
+            };
+            let (_, found_res) = found;
+            let mut change =
+                |field: String, configured: Option<&String>, discovered: Option<&String>| {
+                    if configured != discovered {
+                        drift.changed.push(DriftChange {
+                            id: id.clone(),
+                            field,
+                            configured: configured.cloned(),
+                            discovered: discovered.cloned(),
+                        });
+                    }
+                };
+            change("kind".to_string(), Some(&res.kind), Some(&found_res.kind));
+            change(
+                "requires".to_string(),
+                res.requires.as_ref(),
+                found_res.requires.as_ref(),
+            );
+            let params: BTreeMap<_, _> = found_res.parameters.iter().collect();
+            for (key, value) in params {
+                change(
+                    format!("parameters.{key}"),
+                    res.parameters.get(key),
+                    Some(value),
+                );
+            }
+        }
 
-        let target = res.parameters.get("target").unwrap();
+        let discovered_hosts: Vec<&str> = discovered
+            .iter()
+            .map(|host| host.hostname.as_str())
+            .collect();
+        for (id, listed) in old.iter() {
+            if !new.contains_key(id) && discovered_hosts.contains(&listed.0.as_str()) {
+                drift.removed.push(entry(id, listed));
+            }
+        }
+        drift
+    }
 
-        resources.insert(target.to_string(), res);
+    fn is_empty(&self) -> bool {
+        self == &Drift::default()
+    }
+}
+
+/// One line for each resource which was added or removed, and each change to a resource, like a
+/// diff from the config to what was found.
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "The resources found match the config.");
+        }
+        for res in self.added.iter() {
+            writeln!(f, "+ {} ({} on {})", res.id, res.kind, res.host)?;
+        }
+        for res in self.removed.iter() {
+            writeln!(f, "- {} ({} on {})", res.id, res.kind, res.host)?;
+        }
+        let show = |value: &Option<String>| match value {
+            Some(value) => format!("\"{value}\""),
+            None => "(none)".to_string(),
+        };
+        for change in self.changed.iter() {
+            writeln!(
+                f,
+                "~ {} {}: {} -> {}",
+                change.id,
+                change.field,
+                show(&change.configured),
+                show(&change.discovered)
+            )?;
+        }
+        Ok(())
     }
+}
+
+/// The ways that `discover` can print what it found.
+#[derive(clap::ValueEnum, Debug, Default, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// A YAML config file with the hosts that were reached.
+    #[default]
+    Text,
+
+    /// A JSON array with an object for each host, including the ones that were not reached.
+    Json,
 
-    resources
+    /// CSV with a header row, and a row for each resource, or for each host that was not reached.
+    Csv,
+
+    /// An aligned table with a row for each host, including the ones that were not reached, which
+    /// fits the width of the terminal.
+    Wide,
 }
 
-fn get_lustre_output(hostname: &str, verbose: bool) -> io::Result<String> {
-    // Get Targets and parse both Zpools and Lustre targets
-    if verbose {
-        eprintln!("Discovering lustre targets for host={hostname}");
-        eprintln!("Running command on hosggt: 'mount -t lustre'");
+/// The columns of `--output wide`, unless others are chosen with `--select`.
+const WIDE_ATTRIBUTES: [Attribute; 6] = [
+    Attribute::Hostname,
+    Attribute::Reachable,
+    Attribute::Resources,
+    Attribute::Zpools,
+    Attribute::Targets,
+    Attribute::Error,
+];
+
+/// How wide `--output wide` may be when stdout is not a terminal, so that it stays the same from
+/// one run to the next, whoever runs it.
+const PIPED_WIDTH: usize = 132;
+
+/// How wide `--output wide` may be: the width of the terminal, as given by $COLUMNS, or 80 if that
+/// is not set; or PIPED_WIDTH when stdout is not a terminal.
+fn output_width() -> usize {
+    use std::io::IsTerminal;
+
+    if !io::stdout().is_terminal() {
+        return PIPED_WIDTH;
     }
-    let output = Command::new("ssh")
-        .args([hostname, "mount", "-t", "lustre"])
-        .output()?;
-    if verbose {
-        eprintln!(
-            "stdout: {}",
-            String::from_utf8(output.stdout.clone()).unwrap()
-        );
-        eprintln!("stderr: {}", String::from_utf8(output.stderr).unwrap());
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .unwrap_or(80)
+}
+
+/// What was found on a host, for `--output json`.
+#[derive(Serialize, Debug)]
+struct HostReport<'a> {
+    hostname: &'a str,
+    reachable: bool,
+
+    /// Why the host could not be discovered, if it was not reached.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+
+    resources: BTreeMap<&'a str, ResourceReport<'a>>,
+}
+
+/// A resource as it would be in a config file, tagged with the agent that found it.
+#[derive(Serialize, Debug)]
+struct ResourceReport<'a> {
+    #[serde(flatten)]
+    resource: &'a config::Resource,
+
+    agent: &'a str,
+}
+
+impl<'a> From<&'a HostResult> for HostReport<'a> {
+    fn from((hostname, result): &'a HostResult) -> Self {
+        match result {
+            Ok(found) => Self {
+                hostname,
+                reachable: true,
+                error: None,
+                resources: found
+                    .host
+                    .resources
+                    .iter()
+                    .map(|(id, resource)| {
+                        let agent = found.agents.get(id).copied().unwrap_or_default();
+                        (id.as_str(), ResourceReport { resource, agent })
+                    })
+                    .collect(),
+            },
+            Err(e) => Self {
+                hostname,
+                reachable: false,
+                error: Some(e.to_string()),
+                resources: BTreeMap::new(),
+            },
+        }
     }
+}
 
-    Ok(String::from_utf8(output.stdout).unwrap())
+/// The columns of `--output csv`. These are part of the format, so a new column should only ever
+/// be added at the end.
+const CSV_HEADER: &str = "hostname,reachable,resource,kind,requires,parameters,error,agent";
+
+/// Format the results of discovery as CSV, with a row for each resource in the order of its ID.
+/// The parameters of a resource are a single field of `key=value` pairs separated by `;`.
+fn to_csv(results: &[HostResult]) -> String {
+    let mut rows = vec![CSV_HEADER.to_string()];
+    for (hostname, result) in results {
+        let found = match result {
+            Ok(found) => found,
+            Err(e) => {
+                let fields = [hostname, "false", "", "", "", "", &e.to_string(), ""];
+                rows.push(csv_row(&fields));
+                continue;
+            }
+        };
+
+        let resources: BTreeMap<_, _> = found.host.resources.iter().collect();
+        for (id, res) in resources {
+            let params: BTreeMap<_, _> = res.parameters.iter().collect();
+            let params: Vec<String> = params
+                .into_iter()
+                .map(|(key, val)| format!("{key}={val}"))
+                .collect();
+            let fields = [
+                hostname,
+                "true",
+                id,
+                &res.kind,
+                res.requires.as_deref().unwrap_or(""),
+                &params.join(";"),
+                "",
+                found.agents.get(id).copied().unwrap_or_default(),
+            ];
+            rows.push(csv_row(&fields));
+        }
+    }
+    rows.join("\n")
 }
 
-fn parse_zpool_output(output: String) -> HashMap<String, config::Resource> {
-    HashMap::from_iter(output.lines().map(|line| {
-        (
-            line.to_string(),
-            config::Resource::new_zpool(line.to_string()),
-        )
-    }))
+/// Format the `attributes` of each host in `results`, in the order given: as aligned columns under
+/// a header for text, a row for each host under a header for CSV, or an array with an object for
+/// each host for JSON. The wide format is also aligned, but fits the width of the terminal, and
+/// shows a dash for the attributes which do not apply to a host, such as the number of resources
+/// on one that was not reached.
+fn select(
+    results: &[HostResult],
+    attributes: &[Attribute],
+    format: OutputFormat,
+) -> serde_json::Result<String> {
+    let rows: Vec<Vec<query::Value>> = results
+        .iter()
+        .map(|(hostname, result)| {
+            attributes
+                .iter()
+                .map(|attr| attr.of(hostname, result.as_ref().map(|found| &found.host)))
+                .collect()
+        })
+        .collect();
+    let header: Vec<&str> = attributes.iter().map(|attr| attr.name()).collect();
+
+    match format {
+        OutputFormat::Json => {
+            let hosts: Vec<serde_json::Map<String, serde_json::Value>> = rows
+                .into_iter()
+                .map(|row| {
+                    header
+                        .iter()
+                        .zip(row)
+                        .map(|(name, value)| Ok((name.to_string(), serde_json::to_value(value)?)))
+                        .collect()
+                })
+                .collect::<serde_json::Result<_>>()?;
+            serde_json::to_string_pretty(&hosts)
+        }
+        OutputFormat::Csv => {
+            let mut lines = vec![csv_row(&header)];
+            for row in rows {
+                let fields: Vec<String> = row.iter().map(|value| value.to_string()).collect();
+                let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+                lines.push(csv_row(&fields));
+            }
+            Ok(lines.join("\n"))
+        }
+        OutputFormat::Text => {
+            let rows: Vec<Vec<String>> = rows
+                .iter()
+                .map(|row| row.iter().map(|value| value.to_string()).collect())
+                .collect();
+            Ok(table(&header, &rows, None))
+        }
+        OutputFormat::Wide => {
+            let rows: Vec<Vec<String>> = results
+                .iter()
+                .zip(rows)
+                .map(|((_, result), row)| {
+                    attributes
+                        .iter()
+                        .zip(row)
+                        .map(|(attr, value)| match (attr, result.is_ok(), value) {
+                            (Attribute::Reachable, _, query::Value::Bool(true)) => "yes".into(),
+                            (Attribute::Reachable, _, query::Value::Bool(false)) => "no".into(),
+                            (Attribute::Error, true, _) => "-".to_string(),
+                            (_, false, query::Value::Number(_)) => "-".to_string(),
+                            (_, _, value) => value.to_string(),
+                        })
+                        .collect()
+                })
+                .collect();
+            Ok(table(&header, &rows, Some(output_width())))
+        }
+    }
 }
 
-fn get_zpool_output(hostname: &str, verbose: bool) -> io::Result<String> {
-    // Get Zpools
-    if verbose {
-        eprintln!("\nDiscovering zpools for host={hostname}");
-        eprintln!("Running command on host: 'zpool list -H -o name'");
+/// Lay out `rows` in columns under `header`, each as wide as its widest value. Given a `width`,
+/// the widest columns are narrowed until the lines fit in it, though never to less than its header
+/// or MIN_WIDTH, and the values which no longer fit are cut short with an ellipsis.
+fn table(header: &[&str], rows: &[Vec<String>], width: Option<usize>) -> String {
+    const MIN_WIDTH: usize = 8;
+    const GAP: &str = "  ";
+
+    let mut widths: Vec<usize> = header
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([name.len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    if let Some(width) = width {
+        let gaps = GAP.len() * header.len().saturating_sub(1);
+        while widths.iter().sum::<usize>() + gaps > width {
+            let Some((widest, _)) = widths
+                .iter()
+                .enumerate()
+                .filter(|(i, w)| **w > MIN_WIDTH.max(header[*i].len()))
+                .max_by_key(|(_, w)| **w)
+            else {
+                break;
+            };
+            widths[widest] -= 1;
+        }
     }
-    let output = Command::new("ssh")
-        .args([hostname, "zpool", "list", "-H", "-o", "name"])
-        .output()?;
-    if verbose {
-        eprintln!(
-            "stdout: {}",
-            String::from_utf8(output.stdout.clone()).unwrap()
-        );
-        eprintln!("stderr: {}", String::from_utf8(output.stderr).unwrap());
+
+    let line = |fields: &[&str]| -> String {
+        let padded: Vec<String> = fields
+            .iter()
+            .zip(widths.iter())
+            .map(|(field, width)| {
+                let field = if field.chars().count() > *width {
+                    let kept: String = field.chars().take(width - 1).collect();
+                    format!("{kept}…")
+                } else {
+                    field.to_string()
+                };
+                format!("{field:<width$}")
+            })
+            .collect();
+        padded.join(GAP).trim_end().to_string()
+    };
+    let mut lines = vec![line(header)];
+    for row in rows.iter() {
+        let fields: Vec<&str> = row.iter().map(String::as_str).collect();
+        lines.push(line(&fields));
+    }
+    lines.join("\n")
+}
+
+/// Join `fields` into a CSV row, quoting the ones which need it.
+fn csv_row(fields: &[&str]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect();
+    fields.join(",")
+}
+
+/// Discover every host in `hostnames` with `agents`, with at most `args.concurrency` of them at a
+/// time, and return the result for each one in the order that they finished.
+async fn discover_hosts(
+    hostnames: Vec<String>,
+    agents: &[&'static dyn DiscoveryAgent],
+    args: &DiscoverArgs,
+) -> Vec<HostResult> {
+    let total = hostnames.len();
+    let permits = Arc::new(tokio::sync::Semaphore::new(args.concurrency as usize));
+    let mut tasks = tokio::task::JoinSet::new();
+    for hostname in hostnames {
+        let permits = Arc::clone(&permits);
+        let agents = agents.to_vec();
+        let host = RemoteHost {
+            hostname,
+            verbose: args.verbose,
+        };
+        tasks.spawn(async move {
+            // The semaphore is never closed, so acquiring a permit can not fail:
+            let _permit = permits.acquire().await.unwrap();
+            let result = discover_one_host(&host, &agents).await;
+            (host.hostname, result)
+        });
     }
 
-    Ok(String::from_utf8(output.stdout).unwrap())
+    let mut results = Vec::with_capacity(total);
+    while let Some(joined) = tasks.join_next().await {
+        let (hostname, result) = match joined {
+            Ok(done) => done,
+            // A task only fails if it panicked, and every hostname is moved into its task, so
+            // there is nothing to attribute the failure to:
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        };
+        if args.verbose {
+            let outcome = if result.is_ok() { "done" } else { "failed" };
+            eprintln!("[{}/{total}] {hostname}: {outcome}", results.len() + 1);
+        }
+        results.push((hostname, result));
+    }
+    results
+}
+
+/// The hosts in the nodesets `hostnames`, less those in the nodesets `exclude`. Excluding a host
+/// which is not one of the targets is not an error.
+fn discovery_targets(
+    hostnames: &[String],
+    exclude: &[String],
+) -> Result<nodeset::NodeSet, NodesetError> {
+    let (targets, _) = merge_nodesets(hostnames)?;
+    let (excluded, _) = merge_nodesets(exclude)?;
+    Ok(targets.difference(&excluded))
+}
+
+/// Discover all of the resources on `host` with each of `agents` in turn, and construct them into
+/// a config::Host object that owns those resources. If any agent fails, so does the host.
+async fn discover_one_host(host: &RemoteHost, agents: &[&dyn DiscoveryAgent]) -> io::Result<Found> {
+    let mut resources = HashMap::new();
+    let mut found_by = HashMap::new();
+    for agent in agents {
+        for found in agent.discover(host).await? {
+            found_by.insert(found.id.clone(), found.agent);
+            resources.insert(found.id, found.resource);
+        }
+    }
+
+    Ok(Found {
+        host: config::Host {
+            hostname: host.hostname.clone(),
+            resources,
+            fence_agent: None,
+            fence_parameters: None,
+            attributes: BTreeMap::new(),
+        },
+        agents: found_by,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
 
-    use super::{parse_lustre_output, parse_zpool_output};
-    use crate::config::*;
+    use super::{csv_row, discover_one_host, discovery_targets, starter_config, table, Drift};
+    use crate::{
+        commands::discovery::{
+            parse_lustre_output, parse_zpool_output, DiscoveredResource, Discovery, DiscoveryAgent,
+            RemoteHost,
+        },
+        config::*,
+    };
+
+    /// An agent which finds the same zpools on every host, or fails.
+    struct MockAgent {
+        name: &'static str,
+        pools: Result<&'static [&'static str], &'static str>,
+    }
+
+    impl DiscoveryAgent for MockAgent {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn discover<'a>(&'a self, _host: &'a RemoteHost) -> Discovery<'a> {
+            Box::pin(async move {
+                let pools = self.pools.map_err(std::io::Error::other)?;
+                Ok(pools
+                    .iter()
+                    .map(|pool| DiscoveredResource {
+                        id: pool.to_string(),
+                        agent: self.name,
+                        resource: Resource::new_zpool(pool.to_string()),
+                    })
+                    .collect())
+            })
+        }
+    }
 
     #[test]
-    fn parse_zpools() {
-        let output = String::from("zpool_1\nzpool_2");
-        let resources = parse_zpool_output(output);
-        assert_eq!(resources.len(), 2);
-
-        let goal = HashMap::from([
-            (
-                "zpool_1".to_string(),
-                Resource::new_zpool("zpool_1".to_string()),
-            ),
-            (
-                "zpool_2".to_string(),
-                Resource::new_zpool("zpool_2".to_string()),
-            ),
-        ]);
+    fn discover_with_agents() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let host = RemoteHost {
+            hostname: "oss00".to_string(),
+            verbose: false,
+        };
+        let first = MockAgent {
+            name: "first",
+            pools: Ok(&["oss00e0", "oss00e1"]),
+        };
+        let second = MockAgent {
+            name: "second",
+            pools: Ok(&["oss00e2"]),
+        };
+        let failing = MockAgent {
+            name: "failing",
+            pools: Err("no such command"),
+        };
+
+        let found = runtime
+            .block_on(discover_one_host(&host, &[&first, &second]))
+            .unwrap();
+        assert_eq!(found.host.hostname, "oss00");
+        assert_eq!(found.host.resources.len(), 3);
+        assert_eq!(found.agents["oss00e1"], "first");
+        assert_eq!(found.agents["oss00e2"], "second");
 
-        assert_eq!(resources, goal);
+        let found = runtime
+            .block_on(discover_one_host(&host, &[&second]))
+            .unwrap();
+        assert_eq!(found.host.resources.len(), 1);
+
+        let e = runtime
+            .block_on(discover_one_host(&host, &[&first, &failing]))
+            .unwrap_err();
+        assert_eq!(e.to_string(), "no such command");
+        assert!(runtime
+            .block_on(discover_one_host(&host, &[]))
+            .unwrap()
+            .host
+            .resources
+            .is_empty());
+    }
+
+    #[test]
+    fn csv_quoting() {
+        assert_eq!(csv_row(&["a", "", "b c"]), "a,,b c");
+        assert_eq!(
+            csv_row(&["a,b", "say \"hi\"", "x"]),
+            "\"a,b\",\"say \"\"hi\"\"\",x"
+        );
     }
 
     #[test]
-    fn parse_lustre() {
-        let output = concat!("oss01e0/ost2 on /mnt/ost2 type lustre (ro,svname=test-OST0002,mgsnode=10.0.0.1@tcp:10.0.0.2@tcp,osd=osd-zfs)\n",
-                             "oss01e1/ost3 on /mnt/ost3 type lustre (ro,svname=test-OST0003,mgsnode=10.0.0.1@tcp:10.0.0.2@tcp,osd=osd-zfs)");
+    fn fit_table() {
+        let header = ["hostname", "resources", "error"];
+        let rows = vec![
+            vec!["oss00".to_string(), "2".to_string(), "-".to_string()],
+            vec![
+                "oss01".to_string(),
+                "-".to_string(),
+                "ssh: connect to host oss01 port 22: Connection refused".to_string(),
+            ],
+        ];
+        assert_eq!(
+            table(&header, &rows, None),
+            "hostname  resources  error
+oss00     2          -
+oss01     -          ssh: connect to host oss01 port 22: Connection refused"
+        );
 
-        let resources = parse_lustre_output(output.to_string());
-        assert_eq!(resources.len(), 2);
+        // Only the widest column is narrowed to fit, and the value which no longer fits is cut:
+        assert_eq!(
+            table(&header, &rows, Some(40)),
+            "hostname  resources  error
+oss00     2          -
+oss01     -          ssh: connect to ho…"
+        );
 
-        let goal_1 = Resource {
-            kind: "lustre/Lustre".to_string(),
-            parameters: HashMap::from([
-                ("mountpoint".to_string(), "/mnt/ost2".to_string()),
-                ("target".to_string(), "oss01e0/ost2".to_string()),
-                ("kind".to_string(), "ost".to_string()),
-            ]),
-            requires: Some("oss01e0".to_string()),
+        // A column is never narrower than its header, even if the lines can not fit:
+        let narrowest = table(&header, &rows, Some(10));
+        assert_eq!(
+            narrowest.lines().next().unwrap(),
+            "hostname  resources  error"
+        );
+        assert!(narrowest.ends_with("oss01     -          ssh: co…"));
+    }
+
+    #[test]
+    fn exclude_hosts() {
+        let strings =
+            |list: &[&str]| -> Vec<String> { list.iter().map(|s| s.to_string()).collect() };
+        let targets = |hostnames: &[&str], exclude: &[&str]| -> Vec<String> {
+            discovery_targets(&strings(hostnames), &strings(exclude))
+                .unwrap()
+                .iter()
+                .collect()
+        };
+
+        assert_eq!(
+            targets(&["oss[00-03]"], &["oss01", "oss03"]),
+            vec!["oss00", "oss02"]
+        );
+        assert_eq!(targets(&["oss[00-01]"], &["mds00"]), vec!["oss00", "oss01"]);
+        assert!(targets(&["oss[00-01]"], &["oss[00-05]"]).is_empty());
+        assert!(discovery_targets(&strings(&["oss00"]), &strings(&["oss["])).is_err());
+    }
+
+    #[test]
+    fn starter_config_is_unmanaged() {
+        let host = Host {
+            hostname: "oss00".to_string(),
+            resources: parse_zpool_output("oss00e0".to_string())
+                .into_iter()
+                .chain(parse_lustre_output(
+                    "oss00e0/ost0 on /mnt/ost0 type lustre (ro,svname=test-OST0000,osd=osd-zfs)"
+                        .to_string(),
+                ))
+                .collect(),
+            fence_agent: None,
+            fence_parameters: None,
+            attributes: Default::default(),
+        };
+
+        let config = starter_config(vec![host]);
+        let pool = &config.hosts[0].resources["oss00e0"];
+        assert_eq!(pool.managed, Some(false));
+        assert_eq!(pool.home_node.as_deref(), Some("oss00"));
+        let target = &config.hosts[0].resources["oss00e0/ost0"];
+        assert_eq!(target.managed, None);
+        assert_eq!(target.home_node, None);
+        assert!(config.validate().is_empty(), "{:?}", config.validate());
+    }
+
+    #[test]
+    fn drift() {
+        let host = |hostname: &str, resources: &[(&str, Resource)]| Host {
+            hostname: hostname.to_string(),
+            resources: resources
+                .iter()
+                .map(|(id, res)| (id.to_string(), res.clone()))
+                .collect(),
+            fence_agent: None,
+            fence_parameters: None,
+            attributes: Default::default(),
         };
-        let goal_2 = Resource {
+        let zpool = |pool: &str| Resource::new_zpool(pool.to_string());
+        let mut target = Resource {
             kind: "lustre/Lustre".to_string(),
             parameters: HashMap::from([
-                ("mountpoint".to_string(), "/mnt/ost3".to_string()),
-                ("target".to_string(), "oss01e1/ost3".to_string()),
-                ("kind".to_string(), "ost".to_string()),
+                ("target".to_string(), "oss00e0/ost0".to_string()),
+                ("mountpoint".to_string(), "/mnt/ost0".to_string()),
             ]),
-            requires: Some("oss01e1".to_string()),
+            requires: Some("oss00e0".to_string()),
+            ..Default::default()
+        };
+
+        // The config has a parameter that discovery does not find, and is placed differently:
+        let mut configured_target = target.clone();
+        configured_target
+            .parameters
+            .insert("extra".to_string(), "yes".to_string());
+        let configured = Config {
+            hosts: vec![
+                host(
+                    "oss00",
+                    &[
+                        ("oss00e0", zpool("oss00e0")),
+                        ("oss00e0/ost0", configured_target),
+                    ],
+                ),
+                host("oss01", &[("oss01e0", zpool("oss01e0"))]),
+                host("oss02", &[("oss02e0", zpool("oss02e0"))]),
+            ],
+            ..Default::default()
         };
-        let goal = HashMap::from([
-            ("oss01e0/ost2".to_string(), goal_1),
-            ("oss01e1/ost3".to_string(), goal_2),
-        ]);
 
-        assert_eq!(resources, goal);
+        // oss00e0 has failed over to oss01, and oss02 was not discovered:
+        let discovered = vec![
+            host("oss00", &[("oss00e0/ost0", target.clone())]),
+            host("oss01", &[("oss00e0", zpool("oss00e0"))]),
+        ];
+        let drift = Drift::between(&configured, &discovered);
+        assert!(drift.added.is_empty());
+        let removed: Vec<&str> = drift.removed.iter().map(|res| res.id.as_str()).collect();
+        assert_eq!(removed, vec!["oss01e0"]);
+        assert!(drift.changed.is_empty());
+
+        target
+            .parameters
+            .insert("mountpoint".to_string(), "/mnt/ost1".to_string());
+        let discovered = vec![
+            host(
+                "oss00",
+                &[("oss00e0", zpool("oss00e0")), ("oss00e0/ost0", target)],
+            ),
+            host(
+                "oss01",
+                &[("oss01e0", zpool("oss01e0")), ("oss01e1", zpool("oss01e1"))],
+            ),
+        ];
+        let drift = Drift::between(&configured, &discovered);
+        assert_eq!(
+            drift.to_string(),
+            "+ oss01e1 (heartbeat/ZFS on oss01)
+~ oss00e0/ost0 parameters.mountpoint: \"/mnt/ost0\" -> \"/mnt/ost1\"
+"
+        );
+        assert!(Drift::between(&configured, &[]).is_empty());
     }
 }
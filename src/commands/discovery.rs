@@ -0,0 +1,267 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+//! The agents which `halo discover` uses to find resources on a host.
+//!
+//! Each kind of resource is found by its own agent, which runs whatever it needs to on the host
+//! and reports the resources that it found. Discovery runs every agent that was asked for on each
+//! host, and tags each resource with the agent that found it. A new kind of resource is discovered
+//! by adding an agent to [`AGENTS`].
+//!
+//! These are not to be confused with the OCF resource agents that the manager uses to start and
+//! stop resources: a discovery agent only looks for resources, and never changes anything.
+
+use std::{collections::HashMap, future::Future, io, pin::Pin};
+
+use tokio::process::Command;
+
+use crate::config;
+
+/// A resource found on a host, along with the agent that found it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveredResource {
+    /// The ID of the resource, as it would be in a config file.
+    pub id: String,
+
+    /// The name of the agent that found it.
+    pub agent: &'static str,
+
+    pub resource: config::Resource,
+}
+
+/// The resources found by one agent on one host.
+pub type Discovery<'a> =
+    Pin<Box<dyn Future<Output = io::Result<Vec<DiscoveredResource>>> + Send + 'a>>;
+
+/// A way of finding one kind of resource on a host.
+///
+/// An agent should fail if it can not tell which resources are on the host, for example because
+/// the host can not be reached, rather than report that there are none; the host is then reported
+/// as not discovered.
+pub trait DiscoveryAgent: Send + Sync {
+    /// The name of the agent, which is given to `--agent-type` to choose it.
+    fn name(&self) -> &'static str;
+
+    /// Find the resources of this agent's kind on `host`.
+    fn discover<'a>(&'a self, host: &'a RemoteHost) -> Discovery<'a>;
+}
+
+/// The built-in agents, in the order that they are run on each host.
+pub const AGENTS: &[&dyn DiscoveryAgent] = &[&Zpools, &LustreTargets];
+
+/// The agents with the given names, in the order of [`AGENTS`], or all of them if no names are
+/// given.
+pub fn agents(names: &[String]) -> Vec<&'static dyn DiscoveryAgent> {
+    AGENTS
+        .iter()
+        .filter(|agent| names.is_empty() || names.iter().any(|name| name == agent.name()))
+        .copied()
+        .collect()
+}
+
+/// Parse the name of a built-in agent, e.g. for `--agent-type`.
+pub fn parse_agent_type(name: &str) -> Result<String, String> {
+    if AGENTS.iter().any(|agent| agent.name() == name) {
+        return Ok(name.to_string());
+    }
+    let names: Vec<&str> = AGENTS.iter().map(|agent| agent.name()).collect();
+    Err(format!(
+        "unknown agent type '{name}': expected one of {}",
+        names.join(", ")
+    ))
+}
+
+/// A host being discovered, on which agents can run commands.
+pub struct RemoteHost {
+    pub hostname: String,
+
+    /// Print each command that is run, and what it wrote.
+    pub verbose: bool,
+}
+
+impl RemoteHost {
+    /// Run `command` on the host over ssh, and return what it wrote to stdout. It is an error for
+    /// the command to fail, including when the host can not be reached.
+    ///
+    /// Several hosts are discovered at once, so verbose output is prefixed with the hostname.
+    pub async fn run(&self, command: &[&str]) -> io::Result<String> {
+        let hostname = &self.hostname;
+        if self.verbose {
+            eprintln!("{hostname}: running '{}'", command.join(" "));
+        }
+        let output = Command::new("ssh")
+            .arg(hostname)
+            .args(command)
+            .output()
+            .await?;
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if self.verbose {
+            eprintln!("{hostname}: stdout: {stdout}");
+            eprintln!("{hostname}: stderr: {stderr}");
+        }
+
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "'{}' failed ({}): {}",
+                command.join(" "),
+                output.status,
+                stderr.trim()
+            )));
+        }
+        Ok(stdout)
+    }
+}
+
+/// Tag each of `resources` with the agent named `agent`.
+fn tagged(
+    agent: &'static str,
+    resources: HashMap<String, config::Resource>,
+) -> Vec<DiscoveredResource> {
+    resources
+        .into_iter()
+        .map(|(id, resource)| DiscoveredResource {
+            id,
+            agent,
+            resource,
+        })
+        .collect()
+}
+
+/// Finds the zpools which are imported on a host.
+pub struct Zpools;
+
+impl DiscoveryAgent for Zpools {
+    fn name(&self) -> &'static str {
+        "zpool"
+    }
+
+    fn discover<'a>(&'a self, host: &'a RemoteHost) -> Discovery<'a> {
+        Box::pin(async move {
+            let output = host.run(&["zpool", "list", "-H", "-o", "name"]).await?;
+            Ok(tagged(self.name(), parse_zpool_output(output)))
+        })
+    }
+}
+
+/// Finds the Lustre targets which are mounted on a host.
+pub struct LustreTargets;
+
+impl DiscoveryAgent for LustreTargets {
+    fn name(&self) -> &'static str {
+        "lustre"
+    }
+
+    fn discover<'a>(&'a self, host: &'a RemoteHost) -> Discovery<'a> {
+        Box::pin(async move {
+            let output = host.run(&["mount", "-t", "lustre"]).await?;
+            Ok(tagged(self.name(), parse_lustre_output(output)))
+        })
+    }
+}
+
+pub fn parse_lustre_output(output: String) -> HashMap<String, config::Resource> {
+    let mut resources = HashMap::new();
+
+    for line in output.lines() {
+        let res = config::Resource::new_lustre(line);
+
+        let target = res.parameters.get("target").unwrap();
+
+        resources.insert(target.to_string(), res);
+    }
+
+    resources
+}
+
+pub fn parse_zpool_output(output: String) -> HashMap<String, config::Resource> {
+    HashMap::from_iter(output.lines().map(|line| {
+        (
+            line.to_string(),
+            config::Resource::new_zpool(line.to_string()),
+        )
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::config::*;
+
+    #[test]
+    fn choose_agents() {
+        let names = |agents: Vec<&dyn DiscoveryAgent>| -> Vec<&str> {
+            agents.iter().map(|agent| agent.name()).collect()
+        };
+        assert_eq!(names(agents(&[])), vec!["zpool", "lustre"]);
+        assert_eq!(
+            names(agents(&["lustre".to_string(), "zpool".to_string()])),
+            vec!["zpool", "lustre"]
+        );
+        assert_eq!(names(agents(&["lustre".to_string()])), vec!["lustre"]);
+
+        assert_eq!(parse_agent_type("zpool").unwrap(), "zpool");
+        assert_eq!(
+            parse_agent_type("systemd").unwrap_err(),
+            "unknown agent type 'systemd': expected one of zpool, lustre"
+        );
+    }
+
+    #[test]
+    fn parse_zpools() {
+        let output = String::from("zpool_1\nzpool_2");
+        let resources = parse_zpool_output(output);
+        assert_eq!(resources.len(), 2);
+
+        let goal = HashMap::from([
+            (
+                "zpool_1".to_string(),
+                Resource::new_zpool("zpool_1".to_string()),
+            ),
+            (
+                "zpool_2".to_string(),
+                Resource::new_zpool("zpool_2".to_string()),
+            ),
+        ]);
+
+        assert_eq!(resources, goal);
+    }
+
+    #[test]
+    fn parse_lustre() {
+        let output = concat!("oss01e0/ost2 on /mnt/ost2 type lustre (ro,svname=test-OST0002,mgsnode=10.0.0.1@tcp:10.0.0.2@tcp,osd=osd-zfs)\n",
+                             "oss01e1/ost3 on /mnt/ost3 type lustre (ro,svname=test-OST0003,mgsnode=10.0.0.1@tcp:10.0.0.2@tcp,osd=osd-zfs)");
+
+        let resources = parse_lustre_output(output.to_string());
+        assert_eq!(resources.len(), 2);
+
+        let goal_1 = Resource {
+            kind: "lustre/Lustre".to_string(),
+            parameters: HashMap::from([
+                ("mountpoint".to_string(), "/mnt/ost2".to_string()),
+                ("target".to_string(), "oss01e0/ost2".to_string()),
+                ("kind".to_string(), "ost".to_string()),
+            ]),
+            requires: Some("oss01e0".to_string()),
+            ..Default::default()
+        };
+        let goal_2 = Resource {
+            kind: "lustre/Lustre".to_string(),
+            parameters: HashMap::from([
+                ("mountpoint".to_string(), "/mnt/ost3".to_string()),
+                ("target".to_string(), "oss01e1/ost3".to_string()),
+                ("kind".to_string(), "ost".to_string()),
+            ]),
+            requires: Some("oss01e1".to_string()),
+            ..Default::default()
+        };
+        let goal = HashMap::from([
+            ("oss01e0/ost2".to_string(), goal_1),
+            ("oss01e1/ost3".to_string(), goal_2),
+        ]);
+
+        assert_eq!(resources, goal);
+    }
+}
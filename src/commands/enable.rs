@@ -0,0 +1,67 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+use {clap::Args, reqwest::StatusCode};
+
+use crate::{commands::*, manager::http, resource::TargetState};
+
+#[derive(Args, Debug, Clone)]
+pub struct EnableArgs {
+    /// The resource which the manager should keep running again.
+    #[arg(value_parser = parse_resource_id)]
+    resource: String,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct DisableArgs {
+    /// The resource which the manager should keep stopped, while still monitoring it.
+    #[arg(value_parser = parse_resource_id)]
+    resource: String,
+}
+
+pub fn enable(cli: &Cli, args: &EnableArgs) -> CommandResult<()> {
+    send_target_state(cli, &args.resource, TargetState::Started)
+}
+
+pub fn disable(cli: &Cli, args: &DisableArgs) -> CommandResult<()> {
+    send_target_state(cli, &args.resource, TargetState::Stopped)
+}
+
+/// Set the target state of `resource`. The manager starts or stops the resource on its next pass,
+/// so this does not wait for that to happen.
+fn send_target_state(cli: &Cli, resource: &str, target_state: TargetState) -> CommandResult<()> {
+    let (command, state) = match target_state {
+        TargetState::Started => ("enable", "enabled"),
+        TargetState::Stopped => ("disable", "disabled"),
+    };
+    let params = http::SetTargetStateArgs {
+        target_state,
+        dry_run: cli.dry_run,
+    };
+
+    let client = HaloClient::from_cli(cli)?;
+    let response = client
+        .post(&format!("/target/{resource}"))
+        .json(&params)
+        .send()
+        .handle_request_err(client.addr())?;
+
+    let done = format!("could not {command} '{resource}'");
+    match response.status() {
+        StatusCode::OK => {}
+        StatusCode::NOT_FOUND => return Err(resource_not_found(cli, &done, resource)),
+        _ => return Err(reply_error(response, &done)),
+    };
+
+    let Some(reply): Option<http::SetTargetStateReply> = read_reply(client.addr(), response)?
+    else {
+        return Ok(());
+    };
+    if reply.changed {
+        println!("{}: {state}", reply.id);
+    } else {
+        println!("{}: already {state}", reply.id);
+    }
+
+    Ok(())
+}
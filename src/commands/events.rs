@@ -0,0 +1,87 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+use std::io::BufRead;
+
+use clap::Args;
+
+use crate::{commands::*, manager::http};
+
+#[derive(Args, Debug, Clone)]
+pub struct EventsArgs {
+    /// Print each event as a JSON object on its own line, for consumption by other tools.
+    #[arg(long)]
+    json: bool,
+}
+
+/// Print changes in the state of the cluster as they happen, until the manager shuts down or the
+/// command is interrupted.
+pub fn events(cli: &Cli, args: &EventsArgs) -> CommandResult<()> {
+    let addr = ManagerAddress::from_cli(cli);
+    let client = addr.streaming_client()?;
+    let response = client
+        .get(addr.url("/events"))
+        .send()
+        .and_then(|response| response.error_for_status())
+        .handle_request_err(&addr)?;
+
+    // The stream is a series of events separated by blank lines. Only the data of each event is
+    // needed, since it also holds the event's name; comments and other fields are skipped:
+    let mut data = String::new();
+    for line in std::io::BufReader::new(response).lines() {
+        let line = line.map_err(|e| {
+            CommandError::Connection(format!("lost connection to manager at {addr}: {e}"))
+        })?;
+        if let Some(chunk) = line.strip_prefix("data:") {
+            data.push_str(chunk.trim_start());
+            continue;
+        }
+        if !line.is_empty() || data.is_empty() {
+            continue;
+        }
+
+        let event: http::EventJson = serde_json::from_str(&data)
+            .map_err(|e| CommandError::Agent(format!("could not parse event '{data}': {e}")))?;
+        if args.json {
+            println!("{data}");
+        } else {
+            print_event(event);
+        }
+        data.clear();
+    }
+
+    Ok(())
+}
+
+pub fn print_event(event: http::EventJson) {
+    match event {
+        http::EventJson::ResourceStatus {
+            id,
+            status,
+            comment: Some(comment),
+        } => println!("{id}: {status} ({comment})"),
+        http::EventJson::ResourceStatus { id, status, .. } => println!("{id}: {status}"),
+        http::EventJson::ResourceManaged { id, managed } => {
+            println!("{id}: {}", if managed { "managed" } else { "unmanaged" })
+        }
+        http::EventJson::ResourceTargetState { id, target_state } => {
+            println!(
+                "{id}: {}",
+                if target_state.is_started() {
+                    "enabled"
+                } else {
+                    "disabled"
+                }
+            )
+        }
+        http::EventJson::HostStandby { id, standby } => {
+            println!(
+                "{id}: {}",
+                if standby { "on standby" } else { "off standby" }
+            )
+        }
+        http::EventJson::Lagged { missed } => {
+            eprintln!("Warning: {missed} events were missed because they were not read in time.")
+        }
+    }
+}
@@ -12,51 +12,29 @@ pub struct FailbackArgs {
     hostname: String,
 }
 
-pub fn failback(cli: &Cli, args: &FailbackArgs) -> HandledResult<()> {
-    let addr = match &cli.socket {
-        Some(s) => s,
-        None => &crate::default_socket(),
-    };
-
-    let hostname = &args.hostname;
+pub fn failback(cli: &Cli, args: &FailbackArgs) -> CommandResult<()> {
+    send_failback(&HaloClient::from_cli(cli)?, &args.hostname)
+}
 
-    do_failback(addr, hostname)
+pub fn do_failback(addr: &str, hostname: &str) -> CommandResult<()> {
+    send_failback(&HaloClient::new(ManagerAddress::new(addr))?, hostname)
 }
 
-pub fn do_failback(addr: &str, hostname: &str) -> HandledResult<()> {
+fn send_failback(client: &HaloClient, hostname: &str) -> CommandResult<()> {
     let params = http::HostArgs {
         command: "failback".into(),
     };
 
-    let do_request = || -> reqwest::Result<_> {
-        let client = reqwest::blocking::ClientBuilder::new()
-            .unix_socket(addr)
-            .build()?;
-
-        client
-            .post(format!("http://halo_manager/hosts/{hostname}"))
-            .json(&params)
-            .send()
-    };
-
-    let response = do_request().handle_err(|e| eprintln!("Error making HTTP request: {e}"))?;
+    let response = client
+        .post(&format!("/hosts/{hostname}"))
+        .json(&params)
+        .send()
+        .handle_request_err(client.addr())?;
 
+    let done = format!("could not perform failback onto '{hostname}'");
     match response.status() {
-        StatusCode::OK => return Ok(()),
-        StatusCode::NOT_FOUND => {
-            eprintln!("Could not perform failback onto '{hostname}': host not found.");
-        }
-        StatusCode::BAD_REQUEST => {
-            eprint!("Could not perform failback onto '{hostname}': ");
-            match response.text() {
-                Ok(text) => eprintln!("{text}"),
-                Err(e) => eprintln!("Error decoding response: {e}"),
-            };
-        }
-        other => {
-            eprintln!("Could not perform failback onto '{hostname}': unexpected error: {other}");
-        }
+        StatusCode::OK => Ok(()),
+        StatusCode::NOT_FOUND => Err(CommandError::NotFound(format!("{done}: host not found."))),
+        _ => Err(reply_error(response, &done)),
     }
-
-    handled_error()
 }
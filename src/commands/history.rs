@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+use clap::Args;
+
+use crate::{
+    commands::*,
+    manager::{history, http},
+};
+
+#[derive(Args, Debug, Clone)]
+pub struct HistoryArgs {
+    /// Print the history as a JSON array of entries, for consumption by other tools.
+    #[arg(long)]
+    json: bool,
+
+    /// Only print the actions from this time or later: either an RFC 3339 timestamp such as
+    /// "2025-01-31T12:00:00Z", or how long ago, such as "10m" or "2h". Times are measured by the
+    /// manager's clock.
+    #[arg(long)]
+    since: Option<String>,
+}
+
+/// Print the most recent actions requested on resources, oldest first.
+pub fn history(cli: &Cli, args: &HistoryArgs) -> CommandResult<()> {
+    let client = HaloClient::from_cli(cli)?;
+
+    let query = http::HistoryQuery {
+        since: args.since.clone(),
+    };
+    let response = client
+        .get("/history")
+        .query(&query)
+        .send()
+        .map_err(|e| CommandError::from_request(client.addr(), e))?;
+    if response.status() == reqwest::StatusCode::BAD_REQUEST {
+        let message = response.text().unwrap_or_default();
+        return Err(CommandError::Validation(message));
+    }
+    let entries: Vec<history::Entry> = response
+        .error_for_status()
+        .and_then(|response| response.json())
+        .map_err(|e| CommandError::from_request(client.addr(), e))?;
+
+    if args.json {
+        let out = serde_json::to_string_pretty(&entries)
+            .map_err(|e| CommandError::Agent(format!("could not serialize history: {e}")))?;
+        println!("{out}");
+        return Ok(());
+    }
+
+    for entry in entries {
+        println!(
+            "{}\t{}\t{}\t{}",
+            format_time(entry.time),
+            entry.resource,
+            entry.action,
+            entry.result
+        );
+    }
+
+    Ok(())
+}
+
+/// Format `secs` since the Unix epoch as an RFC 3339 timestamp in UTC, e.g.
+/// "2025-01-31T12:00:00Z".
+pub fn format_time(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+
+    // Convert days since the epoch to a date in the proleptic Gregorian calendar. See
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_time(951782400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_time(1738324800), "2025-01-31T12:00:00Z");
+        assert_eq!(format_time(1740787199), "2025-02-28T23:59:59Z");
+    }
+}
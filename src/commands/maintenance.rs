@@ -0,0 +1,71 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+use {
+    clap::{Args, ValueEnum},
+    reqwest::StatusCode,
+};
+
+use crate::{commands::*, manager::http};
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Switch {
+    On,
+    Off,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct MaintenanceArgs {
+    /// Whether to switch maintenance mode on or off. Without it, whether it is on is printed.
+    #[arg(value_enum)]
+    switch: Option<Switch>,
+}
+
+/// Print whether maintenance mode is on, or switch it on or off.
+pub fn maintenance(cli: &Cli, args: &MaintenanceArgs) -> CommandResult<()> {
+    let client = HaloClient::from_cli(cli)?;
+
+    let Some(switch) = args.switch else {
+        let reply: http::MaintenanceJson = client
+            .get("/maintenance")
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.json())
+            .handle_request_err(client.addr())?;
+        match reply.since {
+            Some(since) => println!("on since {}", history::format_time(since)),
+            None => println!("off"),
+        }
+        return Ok(());
+    };
+
+    let params = http::SetMaintenanceArgs {
+        on: switch == Switch::On,
+        dry_run: cli.dry_run,
+    };
+    let response = client
+        .post("/maintenance")
+        .json(&params)
+        .send()
+        .handle_request_err(client.addr())?;
+
+    if response.status() != StatusCode::OK {
+        return Err(reply_error(response, "could not switch maintenance mode"));
+    }
+
+    let Some(reply): Option<http::SetMaintenanceReply> = read_reply(client.addr(), response)?
+    else {
+        return Ok(());
+    };
+    match (reply.on, reply.changed) {
+        (true, true) => println!(
+            "Maintenance mode is on: no resource will be started, stopped, moved, or failed over \
+             until 'halo maintenance off'."
+        ),
+        (true, false) => println!("Maintenance mode is already on."),
+        (false, true) => println!("Maintenance mode is off."),
+        (false, false) => println!("Maintenance mode is already off."),
+    }
+
+    Ok(())
+}
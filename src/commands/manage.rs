@@ -7,59 +7,455 @@ use crate::{commands::*, manager::http};
 
 #[derive(Args, Debug, Clone)]
 pub struct ManageArgs {
-    /// Resource to manage
-    resource_id: String,
+    #[command(flatten)]
+    selection: ResourceSelection,
 }
 
 #[derive(Args, Debug, Clone)]
 pub struct UnManageArgs {
-    /// Resource to manage
-    resource_id: String,
+    #[command(flatten)]
+    selection: ResourceSelection,
 }
 
-pub fn manage(cli: &Cli, args: &ManageArgs) -> HandledResult<()> {
-    send_command(&cli.socket, &args.resource_id, true)
+/// The resources that a manage or unmanage command applies to.
+#[derive(Args, Debug, Clone)]
+struct ResourceSelection {
+    /// Resources to act on, in nodeset syntax (e.g., 'ost[00-09]'), or patterns when --pattern or
+    /// --regex is given. An argument of the form '@name' selects a group from the config
+    #[arg(required_unless_present = "from_stdin", value_parser = parse_resource_id)]
+    resource_ids: Vec<String>,
+
+    /// Treat each argument as a shell-style glob (e.g., 'ost*') matched against resource IDs
+    #[arg(long, conflicts_with = "regex")]
+    pattern: bool,
+
+    /// Treat each argument as a regular expression that must match an entire resource ID
+    #[arg(long)]
+    regex: bool,
+
+    /// Read the resources to act on from stdin instead: either one resource ID per line, or a
+    /// JSON array of objects like {"resource": "ost0", "manage": false}
+    #[arg(long, conflicts_with_all = ["resource_ids", "pattern", "regex"])]
+    from_stdin: bool,
+
+    /// Print the outcome for each resource group as a JSON array, for consumption by other tools
+    #[arg(long)]
+    json: bool,
+}
+
+/// How the outcome of a batch request is reported, besides the errors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Report {
+    /// Only report the resources which could not be updated.
+    Errors,
+
+    /// Print a table with the outcome for each resource group.
+    Table,
+
+    /// Print the outcome for each resource group as JSON, instead of reporting errors.
+    Json,
+}
+
+/// An entry in a JSON array read by --from-stdin. Without `manage`, the resource is managed or
+/// unmanaged according to the command.
+#[derive(serde::Deserialize, Debug, PartialEq)]
+struct StdinEntry {
+    resource: String,
+
+    #[serde(default)]
+    manage: Option<bool>,
+}
+
+pub fn manage(cli: &Cli, args: &ManageArgs) -> CommandResult<()> {
+    send_selection(cli, &args.selection, true)
+}
+
+pub fn unmanage(cli: &Cli, args: &UnManageArgs) -> CommandResult<()> {
+    send_selection(cli, &args.selection, false)
+}
+
+fn send_selection(cli: &Cli, selection: &ResourceSelection, managed: bool) -> CommandResult<()> {
+    if selection.from_stdin {
+        let mut input = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut input)
+            .map_err(|e| std::io::Error::new(e.kind(), format!("could not read stdin: {e}")))?;
+        return send_entries(cli, &input, managed, selection.json);
+    }
+
+    let (params, report) = selection.batch_request(cli, managed)?;
+    send_batch_command(cli, &HaloClient::from_cli(cli)?, params, report)
+}
+
+impl ManageArgs {
+    /// The request which sets the managed state of the resource groups selected by these options,
+    /// and how to report its outcome, for a client which sends it some other way, i.e. `halo
+    /// shell`. Resources can not be read from stdin here.
+    pub fn batch_request(
+        &self,
+        cli: &Cli,
+        managed: bool,
+    ) -> CommandResult<(http::SetManagedBatchArgs, Report)> {
+        if self.selection.from_stdin {
+            return Err(CommandError::Validation(
+                "--from-stdin is not supported here.".to_string(),
+            ));
+        }
+        self.selection.batch_request(cli, managed)
+    }
+}
+
+impl ResourceSelection {
+    /// The request for the resource groups selected on the command line, and how to report its
+    /// outcome.
+    fn batch_request(
+        &self,
+        cli: &Cli,
+        managed: bool,
+    ) -> CommandResult<(http::SetManagedBatchArgs, Report)> {
+        let matching = if self.pattern {
+            http::Matching::Glob
+        } else if self.regex {
+            http::Matching::Regex
+        } else {
+            http::Matching::Exact
+        };
+
+        let (groups, resource_ids): (Vec<&String>, Vec<&String>) =
+            self.resource_ids.iter().partition(|id| id.starts_with('@'));
+        let groups: Vec<String> = groups.into_iter().map(|id| id[1..].to_string()).collect();
+        let resource_ids: Vec<String> = resource_ids.into_iter().cloned().collect();
+
+        let resources = match matching {
+            http::Matching::Exact => nodesets2hostnames(&resource_ids, cli.verbose)?,
+            _ => resource_ids,
+        };
+
+        // When matching patterns, the user does not know ahead of time which resources will be
+        // affected, so always report them:
+        let report = if self.json {
+            Report::Json
+        } else if cli.verbose || !matches!(matching, http::Matching::Exact) {
+            Report::Table
+        } else {
+            Report::Errors
+        };
+
+        let params = http::SetManagedBatchArgs {
+            resources,
+            managed,
+            matching,
+            dry_run: cli.dry_run,
+            groups,
+            force: false,
+        };
+        Ok((params, report))
+    }
+}
+
+/// Parse the input to --from-stdin into the resources to act on, and whether to manage each one.
+/// Blank lines, and lines starting with '#', are skipped.
+fn parse_entries(input: &str, managed: bool) -> Result<Vec<(String, bool)>, serde_json::Error> {
+    if input.trim_start().starts_with('[') {
+        let entries: Vec<StdinEntry> = serde_json::from_str(input)?;
+        return Ok(entries
+            .into_iter()
+            .map(|entry| (entry.resource, entry.manage.unwrap_or(managed)))
+            .collect());
+    }
+
+    Ok(input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| (line.to_string(), managed))
+        .collect())
+}
+
+/// Act on the resources read from stdin, with one request to the manager for those to manage and
+/// another for those to unmanage, and report the outcome for each one.
+fn send_entries(cli: &Cli, input: &str, managed: bool, json: bool) -> CommandResult<()> {
+    let entries = parse_entries(input, managed).map_err(|e| {
+        CommandError::Validation(format!("could not parse the resources on stdin: {e}"))
+    })?;
+    if entries.is_empty() {
+        eprintln!("No resources were given on stdin; nothing to do.");
+        return Ok(());
+    }
+
+    let client = HaloClient::from_cli(cli)?;
+    let mut not_found = Vec::new();
+    let mut results = Vec::new();
+    for state in [true, false] {
+        let (groups, resources): (Vec<&str>, Vec<&str>) = entries
+            .iter()
+            .filter(|(_, manage)| *manage == state)
+            .map(|(resource, _)| resource.as_str())
+            .partition(|id| id.starts_with('@'));
+        if groups.is_empty() && resources.is_empty() {
+            continue;
+        }
+
+        let params = http::SetManagedBatchArgs {
+            resources: resources.iter().map(|id| id.to_string()).collect(),
+            managed: state,
+            matching: http::Matching::Exact,
+            dry_run: cli.dry_run,
+            groups: groups.iter().map(|id| id[1..].to_string()).collect(),
+            force: false,
+        };
+        let reply = client.manage(&params)?;
+        not_found.extend(reply.not_found);
+        results.extend(reply.results);
+    }
+
+    if json {
+        print_json(&results)?;
+        return check_found(&not_found);
+    }
+
+    print_warnings(&results);
+    print_notes(&results);
+    let action = if cli.dry_run { "would be " } else { "" };
+    let known = known_resources(&cli.config);
+    let mut failed = 0;
+    for (resource, manage) in entries.iter() {
+        if not_found.contains(resource) {
+            failed += 1;
+            println!("{resource}: not found");
+            if !resource.starts_with('@') {
+                suggest_resource(&known, resource);
+            }
+        } else {
+            let state = if *manage { "managed" } else { "unmanaged" };
+            println!("{resource}: {action}{state}");
+        }
+    }
+    eprintln!("{} succeeded, {failed} failed.", entries.len() - failed);
+
+    check_found(&not_found)
+}
+
+/// Send a request to set the managed state of resource groups, with a single request to the
+/// manager, and report its outcome.
+pub fn send_batch_command(
+    cli: &Cli,
+    client: &HaloClient,
+    params: http::SetManagedBatchArgs,
+    report: Report,
+) -> CommandResult<()> {
+    let reply = client.manage(&params)?;
+    report_batch_reply(cli, &params, &reply, report)
+}
+
+/// Report the manager's `reply` to a request to set the managed state of resource groups.
+///
+/// The resources which exist are updated even if some others do not; each resource that could
+/// not be found is reported, and a `CommandError::NotFound` is returned. For a dry run, the
+/// resources which would be updated are printed instead.
+pub fn report_batch_reply(
+    cli: &Cli,
+    params: &http::SetManagedBatchArgs,
+    reply: &http::SetManagedBatchReply,
+    report: Report,
+) -> CommandResult<()> {
+    let (managed, matching) = (params.managed, params.matching);
+    if report == Report::Json {
+        print_json(&reply.results)?;
+        return check_found(&reply.not_found);
+    }
+
+    print_warnings(&reply.results);
+    if report != Report::Table {
+        print_notes(&reply.results);
+    }
+    if reply.dry_run {
+        let state = if managed { "managed" } else { "unmanaged" };
+        for resource in reply.updated.iter() {
+            println!("dry run: would set resource group '{resource}' to {state}");
+        }
+    } else if report == Report::Table {
+        print_results(&reply.results);
+    }
+
+    if reply.not_found.is_empty() {
+        return Ok(());
+    }
+
+    let (groups, not_found): (Vec<&String>, Vec<&String>) = reply
+        .not_found
+        .iter()
+        .partition(|entry| entry.starts_with('@'));
+    for group in groups {
+        eprintln!("Could not update '{group}': group not found.");
+    }
+    if not_found.is_empty() {
+        return check_found(&reply.not_found);
+    }
+
+    if !matches!(matching, http::Matching::Exact) {
+        let patterns: Vec<&str> = not_found.iter().map(|entry| entry.as_str()).collect();
+        return Err(CommandError::NotFound(format!(
+            "no resource groups matched pattern(s): {}",
+            patterns.join(", ")
+        )));
+    }
+
+    let known = known_resources(&cli.config);
+    for resource in not_found {
+        eprintln!("Could not update '{resource}': resource group not found.");
+        suggest_resource(&known, resource);
+    }
+    eprintln!("Specify root resource ID.");
+    check_found(&reply.not_found)
 }
 
-pub fn unmanage(cli: &Cli, args: &UnManageArgs) -> HandledResult<()> {
-    send_command(&cli.socket, &args.resource_id, false)
+/// The error for a batch request in which the resources and groups `not_found` were not found,
+/// once each of them has been reported.
+pub fn check_found(not_found: &[String]) -> CommandResult<()> {
+    match not_found.len() {
+        0 => Ok(()),
+        1 => Err(CommandError::NotFound(format!(
+            "'{}' was not found.",
+            not_found[0]
+        ))),
+        n => Err(CommandError::NotFound(format!(
+            "{n} entries were not found."
+        ))),
+    }
+}
+
+/// Print one line for each result, with the columns lined up.
+fn print_results(results: &[http::ManageResult]) {
+    let width = results
+        .iter()
+        .map(|result| result.resource.len())
+        .max()
+        .unwrap_or(0);
+    for result in results {
+        let outcome = match (result.ok, result.code) {
+            (true, _) => "ok",
+            (false, Some(http::ErrorCode::AlreadyInState)) => "no-op",
+            (false, _) => "failed",
+        };
+        println!(
+            "{:width$}  {outcome:6}  {}",
+            result.resource, result.message
+        );
+    }
+}
+
+/// Print the warnings for each resource group which was updated, e.g. one that can not be started
+/// because every host it may run on is on standby.
+fn print_warnings(results: &[http::ManageResult]) {
+    for warning in results.iter().flat_map(|result| result.warnings.iter()) {
+        eprintln!("Warning: {warning}.");
+    }
+}
+
+/// Print a note for each resource group which was already in the requested state. Like a single
+/// resource group, this is not an error, since there is nothing to do.
+fn print_notes(results: &[http::ManageResult]) {
+    for result in results
+        .iter()
+        .filter(|result| result.code == Some(http::ErrorCode::AlreadyInState))
+    {
+        eprintln!(
+            "Note: resource group '{}' is {}.",
+            result.resource, result.message
+        );
+    }
+}
+
+fn print_json(results: &[http::ManageResult]) -> CommandResult<()> {
+    let out = serde_json::to_string_pretty(results).map_err(std::io::Error::from)?;
+    println!("{out}");
+    Ok(())
 }
 
 pub fn send_command(
     socket_path: &Option<String>,
     resource: &str,
     managed: bool,
-) -> HandledResult<()> {
+) -> CommandResult<()> {
     let addr = match socket_path {
-        Some(s) => s,
-        None => &crate::default_socket(),
+        Some(s) => ManagerAddress::new(s),
+        None => ManagerAddress::new(&crate::default_socket()),
     };
 
-    let params = http::SetManagedArgs { managed };
-
-    let do_request = || -> reqwest::Result<_> {
-        let client = reqwest::blocking::ClientBuilder::new()
-            .unix_socket(addr.as_str())
-            .build()?;
-
-        client
-            .patch(format!("http://halo_manager/resources/{resource}"))
-            .json(&params)
-            .send()
+    let params = http::SetManagedArgs {
+        managed,
+        dry_run: false,
+        force: false,
     };
 
-    let response = do_request().handle_err(|e| eprintln!("Error making HTTP request: {e}"))?;
+    let client = HaloClient::new(addr)?;
+    let response = client
+        .patch(&format!("/resources/{resource}"))
+        .json(&params)
+        .send()
+        .handle_request_err(client.addr())?;
 
-    match response.status() {
-        StatusCode::OK => Ok(()),
-        StatusCode::NOT_FOUND => {
-            eprintln!("Could not update '{resource}': resource group not found.");
-            eprintln!("Specify root resource ID.");
-            handled_error()
+    // The reply says why the request failed; older managers only give a status code:
+    let status = response.status();
+    let reply = response.json::<http::SetManagedReply>().ok();
+    if status == StatusCode::OK {
+        for warning in reply.iter().flat_map(|reply| reply.warnings.iter()) {
+            eprintln!("Warning: {warning}.");
         }
-        other => {
-            eprintln!("Could not update '{resource}': unexpected error: {other}");
-            handled_error()
+        return Ok(());
+    }
+
+    let code = match reply.as_ref().and_then(|reply| reply.code) {
+        Some(code) => code,
+        None if status == StatusCode::NOT_FOUND => http::ErrorCode::NotFound,
+        None if status == StatusCode::CONFLICT => http::ErrorCode::AlreadyInState,
+        None => http::ErrorCode::Unknown,
+    };
+    match code {
+        http::ErrorCode::AlreadyInState => {
+            // The resource group is already in the requested state, so there is nothing to do:
+            if let Some(reply) = reply {
+                eprintln!("Note: {}.", reply.message);
+            }
+            Ok(())
         }
+        http::ErrorCode::NotFound => Err(CommandError::NotFound(format!(
+            "could not update '{resource}': resource group not found. Specify root resource ID."
+        ))),
+        _ => {
+            let message = reply
+                .map(|reply| reply.message)
+                .unwrap_or_else(|| format!("unexpected error: {status}"));
+            Err(CommandError::with_code(
+                match code {
+                    http::ErrorCode::Unknown => status_exit_code(status),
+                    code => code.into(),
+                },
+                format!("could not update '{resource}': {message}"),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_entries() {
+        assert_eq!(
+            parse_entries("# comment\n ost0 \n\n@osts\n", false).unwrap(),
+            vec![("ost0".to_string(), false), ("@osts".to_string(), false)]
+        );
+        assert_eq!(
+            parse_entries(
+                r#" [{"resource": "ost0"}, {"resource": "ost1", "manage": false}]"#,
+                true
+            )
+            .unwrap(),
+            vec![("ost0".to_string(), true), ("ost1".to_string(), false)]
+        );
+        assert!(parse_entries("", true).unwrap().is_empty());
+        assert!(parse_entries("[{\"id\": \"ost0\"}]", true).is_err());
     }
 }
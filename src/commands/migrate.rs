@@ -0,0 +1,78 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+use {clap::Args, reqwest::StatusCode};
+
+use crate::{commands::*, manager::http};
+
+#[derive(Args, Debug, Clone)]
+pub struct MigrateArgs {
+    /// The ID of a resource in the resource group to move, or '@name' to move every resource group
+    /// in a group from the config together
+    #[arg(value_parser = parse_resource_id)]
+    resource: String,
+
+    /// The host to move the resource group to
+    #[arg(long)]
+    to: String,
+
+    /// How many seconds to wait for the migration to finish
+    #[arg(long, default_value_t = 60)]
+    wait: u64,
+}
+
+/// Ask the manager to move a resource group to the other host in its failover pair.
+pub fn migrate(cli: &Cli, args: &MigrateArgs) -> CommandResult<()> {
+    let resource = &args.resource;
+    let params = http::MigrateArgs {
+        to: args.to.clone(),
+        timeout: args.wait,
+        dry_run: cli.dry_run,
+    };
+
+    let path = match resource.strip_prefix('@') {
+        Some(group) => format!("/groups/{group}/migrate"),
+        None => format!("/migrate/{resource}"),
+    };
+
+    let client = HaloClient::from_cli(cli)?;
+    let response = client
+        .post(&path)
+        .timeout(client.wait_timeout(args.wait))
+        .json(&params)
+        .send()
+        .handle_request_err(client.addr())?;
+
+    let done = format!("could not migrate '{resource}'");
+    match response.status() {
+        StatusCode::OK => {}
+        StatusCode::NOT_FOUND if resource.starts_with('@') => {
+            return Err(CommandError::NotFound(format!("{done}: group not found.")));
+        }
+        StatusCode::NOT_FOUND => return Err(resource_not_found(cli, &done, resource)),
+        _ => return Err(reply_error(response, &done)),
+    };
+
+    if resource.starts_with('@') {
+        let Some(reply): Option<http::GroupMigrateReply> = read_reply(client.addr(), response)?
+        else {
+            return Ok(());
+        };
+        reply.resource_groups.iter().for_each(print_reply);
+    } else {
+        let Some(reply): Option<http::MigrateReply> = read_reply(client.addr(), response)? else {
+            return Ok(());
+        };
+        print_reply(&reply);
+    }
+
+    Ok(())
+}
+
+fn print_reply(reply: &http::MigrateReply) {
+    if reply.from == reply.to {
+        println!("{} is already running on {}", reply.id, reply.to);
+    } else {
+        println!("{}: moved from {} to {}", reply.id, reply.from, reply.to);
+    }
+}
@@ -1,48 +1,215 @@
 // SPDX-License-Identifier: MIT
 // Copyright 2025. Triad National Security, LLC.
 
+pub mod cancel;
+pub mod completions;
+pub mod config;
+pub mod describe;
 pub mod discover;
+pub mod discovery;
+pub mod enable;
+pub mod events;
 pub mod failback;
+pub mod history;
+pub mod maintenance;
 pub mod manage;
+pub mod migrate;
+pub mod mode;
+pub mod ping;
 pub mod power;
+pub mod query;
+pub mod resources;
+pub mod set_param;
+pub mod shell;
+pub mod simulate;
+pub mod standby;
 pub mod start;
 pub mod status;
 pub mod stop;
+pub mod top;
 pub mod validate;
+pub mod version;
 
 use {
+    cancel::CancelArgs,
+    completions::CompletionsArgs,
+    config::ConfigArgs,
+    describe::DescribeArgs,
     discover::DiscoverArgs,
+    enable::{DisableArgs, EnableArgs},
+    events::EventsArgs,
     failback::FailbackArgs,
+    history::HistoryArgs,
+    maintenance::MaintenanceArgs,
     manage::{ManageArgs, UnManageArgs},
+    migrate::MigrateArgs,
+    mode::ModeArgs,
+    ping::PingArgs,
     power::PowerArgs,
+    resources::ResourcesArgs,
+    set_param::SetParamArgs,
+    shell::ShellArgs,
+    simulate::SimulateArgs,
+    standby::{StandbyArgs, UnstandbyArgs},
+    start::StartArgs,
     status::StatusArgs,
+    stop::StopArgs,
+    top::TopArgs,
+    version::VersionArgs,
 };
 
 use clap::{Parser, Subcommand};
 
-use crate::cluster::Cluster;
+use crate::manager::http;
 
 /// A `HandledError` represents an error that has already been handled. When you call a function
 /// that returns a `HandledError` or `HandledResult`, you don't need to do anything with that error,
 /// other than just be aware that it happened, and return it on to your caller.
 ///
 /// `main()` has a special responsibility: since its "caller" is, in a certain sense, the operating
-/// system, `main()` must return a nonzero exit status when it gets a `HandledError`.
+/// system, `main()` must return a nonzero exit status when it gets a `HandledError`: the one given
+/// by its `code()`.
 ///
 /// The primary way to construct a `HandledError` is with the `handle_err()` function, which turns a
 /// generic error into a `HandledError`, and also runs some caller-provided code to handle the
 /// error. That provided code would normally do something like report the error to stderr.
 ///
-/// A `HandledError` inentionally has no data about what the specific error was; the process of
-/// handling the error "consumes" that information, and it is no longer needed as the error was
-/// already appropriately handled.
-#[derive(Debug, PartialEq)]
-pub struct HandledError {}
+/// A `HandledError` inentionally has no data about what the specific error was, other than the
+/// kind of exit status it calls for; the process of handling the error "consumes" that
+/// information, and it is no longer needed as the error was already appropriately handled.
+#[derive(Debug, Default, PartialEq)]
+pub struct HandledError {
+    code: ExitCode,
+}
+
+impl HandledError {
+    pub fn new(code: ExitCode) -> Self {
+        Self { code }
+    }
+
+    /// The exit status that `main()` should return for this error.
+    pub fn code(&self) -> ExitCode {
+        self.code
+    }
+}
 
 pub type HandledResult<T> = std::result::Result<T, HandledError>;
 
 pub fn handled_error() -> HandledResult<()> {
-    HandledResult::Err(HandledError {})
+    HandledResult::Err(HandledError::default())
+}
+
+/// The exit statuses of the `halo` command, so that scripts can tell the ways that it fails apart.
+/// A command which succeeds exits with 0. These values are part of the interface of the command,
+/// and must not change.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    /// An error which does not fall into one of the other categories.
+    #[default]
+    Failure = 1,
+
+    /// The command line or the config file is invalid, or the manager rejected the request as
+    /// invalid. This is also the status that clap uses for a command line it can not parse.
+    Usage = 2,
+
+    /// The manager could not be reached, or did not respond in time.
+    Connection = 3,
+
+    /// A resource, group, or host named on the command line does not exist.
+    NotFound = 4,
+}
+
+/// An error from a command which has not been reported yet. Unlike a `HandledError`, it says what
+/// went wrong, so that the code which gets it (or a test) can decide what to do about it, e.g. by
+/// matching on its kind. Every subcommand returns one; `main()` reports it.
+///
+/// Converting a `CommandError` into a `HandledError`, as `?` does in a function which returns a
+/// `HandledResult`, reports it to stderr and keeps the exit status that it calls for. The other
+/// way around, a `HandledError` becomes `CommandError::Reported`, so that code which handles its
+/// own errors can still be called with `?` from a command.
+#[derive(Debug, thiserror::Error)]
+pub enum CommandError {
+    /// The manager could not be reached, or did not respond in time.
+    #[error("{0}")]
+    Connection(String),
+
+    /// A resource, group, or host does not exist.
+    #[error("{0}")]
+    NotFound(String),
+
+    /// The command line or the config file is invalid, or the manager rejected the request as
+    /// invalid.
+    #[error("{0}")]
+    Validation(String),
+
+    /// The manager or a remote agent could not do what was asked, or sent a reply which could not
+    /// be understood.
+    #[error("{0}")]
+    Agent(String),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// An error which was already reported, e.g. one for each of several hosts, so that only its
+    /// exit status is left.
+    #[error("the error was already reported")]
+    Reported(ExitCode),
+}
+
+pub type CommandResult<T> = std::result::Result<T, CommandError>;
+
+impl CommandError {
+    /// The exit status that this error calls for.
+    pub fn code(&self) -> ExitCode {
+        match self {
+            CommandError::Connection(_) => ExitCode::Connection,
+            CommandError::NotFound(_) => ExitCode::NotFound,
+            CommandError::Validation(_) => ExitCode::Usage,
+            CommandError::Agent(_) | CommandError::Io(_) => ExitCode::Failure,
+            CommandError::Reported(code) => *code,
+        }
+    }
+
+    /// The error for a reply from the manager which calls for the exit status `code`, saying
+    /// `message`.
+    pub fn with_code(code: ExitCode, message: String) -> Self {
+        match code {
+            ExitCode::Usage => CommandError::Validation(message),
+            ExitCode::Connection => CommandError::Connection(message),
+            ExitCode::NotFound => CommandError::NotFound(message),
+            ExitCode::Failure => CommandError::Agent(message),
+        }
+    }
+
+    /// Report this error to stderr now, for a command which goes on after it, e.g. to the next of
+    /// several hosts, and leave the exit status that it calls for.
+    pub fn report(self) -> Self {
+        if !matches!(self, CommandError::Reported(_)) {
+            eprintln!("Error: {self}");
+        }
+        CommandError::Reported(self.code())
+    }
+
+    /// The error for a request to the manager at `addr` which failed with `e`.
+    pub fn from_request(addr: &ManagerAddress, e: reqwest::Error) -> Self {
+        if e.is_connect() || e.is_timeout() {
+            CommandError::Connection(describe_request_error(addr, e))
+        } else {
+            CommandError::Agent(describe_request_error(addr, e))
+        }
+    }
+}
+
+impl From<CommandError> for HandledError {
+    fn from(e: CommandError) -> Self {
+        HandledError::new(e.report().code())
+    }
+}
+
+impl From<HandledError> for CommandError {
+    fn from(e: HandledError) -> Self {
+        CommandError::Reported(e.code())
+    }
 }
 
 pub trait Handle<T, F> {
@@ -57,81 +224,1248 @@ impl<T, E, F: FnOnce(E)> Handle<T, F> for std::result::Result<T, E> {
     fn handle_err(self, handler: F) -> HandledResult<T> {
         self.map_err(|e| {
             handler(e);
-            HandledError {}
+            HandledError::default()
         })
     }
 }
 
+pub trait HandleRequest<T> {
+    fn handle_request_err(self, addr: &ManagerAddress) -> CommandResult<T>;
+}
+
+impl<T> HandleRequest<T> for reqwest::Result<T> {
+    /// Turn an error from a request to the manager at `addr` into a `CommandError`, which calls
+    /// for an exit status of `ExitCode::Connection` if the manager could not be reached.
+    fn handle_request_err(self, addr: &ManagerAddress) -> CommandResult<T> {
+        self.map_err(|e| CommandError::from_request(addr, e))
+    }
+}
+
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Cli {
+    /// A config file, or a directory of `.yaml`, `.yml`, and `.conf` files. When given more than
+    /// once, the files are merged in order, so a later one overrides what an earlier one sets.
+    /// Defaults to HALO_CONFIG, or /etc/halo/halo.conf.
     #[arg(long, global = true)]
-    pub config: Option<String>,
+    pub config: Vec<String>,
 
+    /// The path to the manager's unix socket, or a `host:port` address if the manager was
+    /// started with `--listen`.
     #[arg(long, global = true)]
     pub socket: Option<String>,
 
     #[arg(short, long, global = true)]
     pub verbose: bool,
 
+    /// How many seconds to wait for the manager to respond before giving up.
+    #[arg(long, global = true, default_value_t = 10)]
+    pub timeout: u64,
+
+    /// Use mutual TLS when connecting to the manager over TCP. This is not supported for
+    /// connections to the manager over its unix socket, which is protected by filesystem
+    /// permissions instead.
     #[arg(long)]
     pub mtls: bool,
 
+    /// Show what the manager would do for a command that changes the state of the cluster, without
+    /// changing anything.
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// When the manager rejects a request which changes something since its endpoint is over the
+    /// manager's rate limit, wait as long as it says, and try again, rather than failing.
+    #[arg(long, global = true)]
+    pub retry: bool,
+
+    /// When to color output. With `auto`, it is colored only when stdout is a terminal and the
+    /// NO_COLOR environment variable is not set. JSON output is never colored.
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub color: ColorChoice,
+
+    /// Write the results of the command to this open file descriptor, instead of to stdout, e.g.
+    /// `--output-fd 3` in a script which opened fd 3 for them. Errors and logs still go to stderr.
+    #[arg(long, global = true, value_name = "FD", value_parser = clap::value_parser!(i32).range(0..))]
+    pub output_fd: Option<i32>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
+/// When to color the output of commands, for `--color`.
+#[derive(clap::ValueEnum, Debug, Default, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl Cli {
+    /// Whether to color output: always or never if asked to, and otherwise only on a terminal, and
+    /// not if the user opted out with NO_COLOR (see https://no-color.org).
+    pub fn use_color(&self) -> bool {
+        use std::io::IsTerminal;
+
+        match self.color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::io::stdout().is_terminal()
+                    && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+            }
+        }
+    }
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     Status(StatusArgs),
-    Start,
-    Stop,
+    Resources(ResourcesArgs),
+    Describe(DescribeArgs),
+    Start(StartArgs),
+    Stop(StopArgs),
+    Cancel(CancelArgs),
     Discover(DiscoverArgs),
     Failback(FailbackArgs),
     Power(PowerArgs),
     Validate,
     Manage(ManageArgs),
     Unmanage(UnManageArgs),
+    Enable(EnableArgs),
+    Disable(DisableArgs),
+    Ping(PingArgs),
+    Migrate(MigrateArgs),
+    Standby(StandbyArgs),
+    Unstandby(UnstandbyArgs),
+    Events(EventsArgs),
+    History(HistoryArgs),
+    Simulate(SimulateArgs),
+    Mode(ModeArgs),
+    Maintenance(MaintenanceArgs),
+    Config(ConfigArgs),
+    SetParam(SetParamArgs),
+    Top(TopArgs),
+    Shell(ShellArgs),
+    Completions(CompletionsArgs),
+    Version(VersionArgs),
+}
+
+/// The address of the manager service: either the path to its unix socket, or a `host:port` TCP
+/// address if the manager was started with `--listen`.
+#[derive(Debug, Clone)]
+pub struct ManagerAddress {
+    addr: String,
+    mtls: bool,
+    timeout: std::time::Duration,
+    token: Option<String>,
+
+    /// Whether to retry requests which the manager rejects for its rate limit, with --retry.
+    retry: bool,
+}
+
+impl ManagerAddress {
+    pub fn new(addr: &str) -> Self {
+        Self {
+            addr: addr.to_string(),
+            mtls: false,
+            timeout: std::time::Duration::from_secs(10),
+            token: None,
+            retry: false,
+        }
+    }
+
+    /// Get the manager address given on the command line, or the default socket path.
+    pub fn from_cli(cli: &Cli) -> Self {
+        Self {
+            addr: resolve_socket(cli),
+            mtls: cli.mtls,
+            timeout: std::time::Duration::from_secs(cli.timeout),
+            token: resolve_auth_token(cli),
+            retry: cli.retry,
+        }
+    }
+
+    /// Send `token` to the manager with each request, for a manager which requires one.
+    pub fn with_auth_token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    pub fn is_tcp(&self) -> bool {
+        is_tcp_address(&self.addr)
+    }
+
+    /// Build an HTTP client which connects to the manager at this address, and gives up on
+    /// requests which take longer than the timeout.
+    ///
+    /// With mTLS, the client certificates are loaded from the `HALO_{CA,CLIENT}_*` environment
+    /// variables or their default locations in /etc/halo/.
+    pub fn client(&self) -> CommandResult<reqwest::blocking::Client> {
+        self.build_client(reqwest::blocking::ClientBuilder::new().timeout(self.timeout))
+    }
+
+    /// Build an HTTP client for a reply which is streamed for as long as the manager is running.
+    /// Only connecting to the manager is subject to the timeout.
+    pub fn streaming_client(&self) -> CommandResult<reqwest::blocking::Client> {
+        self.build_client(
+            reqwest::blocking::ClientBuilder::new()
+                .timeout(None)
+                .connect_timeout(self.timeout),
+        )
+    }
+
+    fn build_client(
+        &self,
+        builder: reqwest::blocking::ClientBuilder,
+    ) -> CommandResult<reqwest::blocking::Client> {
+        let builder = if !self.is_tcp() {
+            self.check_socket()?;
+            builder.unix_socket(self.addr.as_str())
+        } else if self.mtls {
+            let config = crate::tls::build_client_config(&crate::tls::TlsPaths::new(None))
+                .map_err(|e| {
+                    CommandError::Validation(format!("could not load the TLS certificates: {e}"))
+                })?;
+            builder.use_preconfigured_tls(config)
+        } else {
+            builder
+        };
+        let builder = match self.auth_header()? {
+            Some(value) => {
+                let headers = reqwest::header::HeaderMap::from_iter([(
+                    reqwest::header::AUTHORIZATION,
+                    value,
+                )]);
+                builder.default_headers(headers)
+            }
+            None => builder,
+        };
+
+        builder
+            .build()
+            .map_err(|e| CommandError::from_request(self, e))
+    }
+
+    /// The Authorization header which carries the auth token, if there is one.
+    fn auth_header(&self) -> CommandResult<Option<reqwest::header::HeaderValue>> {
+        let Some(token) = &self.token else {
+            return Ok(None);
+        };
+        let mut value = reqwest::header::HeaderValue::from_str(&format!("Bearer {token}"))
+            .map_err(|_| {
+                CommandError::Validation(
+                    "the auth token may only hold visible ASCII characters".to_string(),
+                )
+            })?;
+        value.set_sensitive(true);
+        Ok(Some(value))
+    }
+
+    /// Open a WebSocket to the given `path` (i.e. "/ws") on the manager, which stays open for as
+    /// long as the caller keeps it. Only connecting to the manager is subject to the timeout.
+    pub async fn websocket(&self, path: &str) -> CommandResult<WebSocket> {
+        use tokio_tungstenite::tungstenite::{self, client::IntoClientRequest};
+
+        let connect = async {
+            let stream: Box<dyn Connection> = if !self.is_tcp() {
+                self.check_socket()?;
+                Box::new(tokio::net::UnixStream::connect(&self.addr).await?)
+            } else if self.mtls {
+                let config = crate::tls::build_client_config(&crate::tls::TlsPaths::new(None))
+                    .map_err(|e| {
+                        CommandError::Validation(format!(
+                            "could not load the TLS certificates: {e}"
+                        ))
+                    })?;
+                let authority = self.url_authority();
+                let host = authority
+                    .rsplit_once(':')
+                    .map_or(authority.as_str(), |(host, _)| host);
+                let name = rustls::pki_types::ServerName::try_from(
+                    host.trim_start_matches('[')
+                        .trim_end_matches(']')
+                        .to_string(),
+                )
+                .map_err(|e| CommandError::Validation(format!("invalid host '{host}': {e}")))?;
+                let stream = tokio::net::TcpStream::connect(&authority).await?;
+                let connector = tokio_rustls::TlsConnector::from(std::sync::Arc::new(config));
+                Box::new(connector.connect(name, stream).await?)
+            } else {
+                Box::new(tokio::net::TcpStream::connect(self.url_authority()).await?)
+            };
+
+            // The URL has the same host and path as for HTTP, but with the WebSocket scheme:
+            let url = self.url(path).replacen("http", "ws", 1);
+            let mut request = url
+                .as_str()
+                .into_client_request()
+                .map_err(|e| CommandError::Validation(format!("invalid URL '{url}': {e}")))?;
+            if let Some(value) = self.auth_header()? {
+                request
+                    .headers_mut()
+                    .insert(reqwest::header::AUTHORIZATION, value);
+            }
+            match tokio_tungstenite::client_async(request, stream).await {
+                Ok((socket, _)) => Ok(socket),
+                Err(tungstenite::Error::Http(response)) => Err(CommandError::Agent(
+                    describe_error_status(self, response.status()),
+                )),
+                Err(e) => Err(CommandError::Agent(format!(
+                    "could not open a WebSocket to manager at {self}: {e}"
+                ))),
+            }
+        };
+
+        match tokio::time::timeout(self.timeout, connect).await {
+            Ok(Err(CommandError::Io(e))) => Err(CommandError::Connection(format!(
+                "could not connect to manager at {self}: {e}"
+            ))),
+            Ok(result) => result,
+            Err(_) => Err(CommandError::Connection(format!(
+                "manager did not respond within {}s",
+                self.timeout.as_secs()
+            ))),
+        }
+    }
+
+    /// Make sure that something other than a socket is not in the way of the manager's socket, which
+    /// would otherwise be reported as a confusing connection failure. A socket which does not exist
+    /// is left for the connection to report.
+    fn check_socket(&self) -> CommandResult<()> {
+        use std::os::unix::fs::FileTypeExt;
+
+        match std::fs::metadata(&self.addr) {
+            Ok(metadata) if !metadata.file_type().is_socket() => {
+                Err(CommandError::Connection(format!(
+                    "{} is not a socket. Is the manager's socket set correctly?",
+                    self.addr
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Get the URL for the given `path` (e.g., "/status") on the manager.
+    pub fn url(&self, path: &str) -> String {
+        if !self.is_tcp() {
+            // The host is ignored when connecting over a unix socket:
+            format!("http://halo_manager{path}")
+        } else if self.mtls {
+            format!("https://{}{path}", self.url_authority())
+        } else {
+            format!("http://{}{path}", self.url_authority())
+        }
+    }
+
+    /// The TCP address as it goes in a URL, where an IPv6 address must be in brackets so that its
+    /// colons are not mistaken for the port. An address given without them, e.g. `::1:8080`, is
+    /// taken to end with the port.
+    fn url_authority(&self) -> String {
+        match self.addr.rsplit_once(':') {
+            Some((host, port)) if host.parse::<std::net::Ipv6Addr>().is_ok() => {
+                format!("[{host}]:{port}")
+            }
+            _ => self.addr.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for ManagerAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.addr)
+    }
+}
+
+/// A connection to the manager over whichever transport it listens on, for a WebSocket.
+pub trait Connection: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send {}
+
+impl<T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send> Connection for T {}
+
+/// A WebSocket session with the manager; see ManagerAddress::websocket().
+pub type WebSocket = tokio_tungstenite::WebSocketStream<Box<dyn Connection>>;
+
+/// A client for the manager at a given address. The underlying HTTP client is built once, so a
+/// command which makes several requests (e.g. `status --watch`, or a batch of hosts) reuses the
+/// connection to the manager rather than reconnecting for each one. Cloning a HaloClient shares
+/// its connections.
+#[derive(Debug, Clone)]
+pub struct HaloClient {
+    addr: ManagerAddress,
+    client: reqwest::blocking::Client,
+}
+
+impl HaloClient {
+    pub fn new(addr: ManagerAddress) -> CommandResult<Self> {
+        let client = addr.client()?;
+        Ok(Self { addr, client })
+    }
+
+    /// Build a client for the manager given on the command line, or at the default socket path.
+    pub fn from_cli(cli: &Cli) -> CommandResult<Self> {
+        Self::new(ManagerAddress::from_cli(cli))
+    }
+
+    pub fn addr(&self) -> &ManagerAddress {
+        &self.addr
+    }
+
+    /// Start a GET request for the given `path` (e.g., "/status") on the manager.
+    pub fn get(&self, path: &str) -> reqwest::blocking::RequestBuilder {
+        self.client.get(self.addr.url(path))
+    }
+
+    /// Start a POST request for the given `path` on the manager. Unlike a GET request, it is
+    /// subject to the manager's rate limit; see WriteRequest::send().
+    pub fn post(&self, path: &str) -> WriteRequest<'_> {
+        WriteRequest {
+            client: self,
+            path: path.to_string(),
+            builder: self.client.post(self.addr.url(path)),
+        }
+    }
+
+    pub fn patch(&self, path: &str) -> WriteRequest<'_> {
+        WriteRequest {
+            client: self,
+            path: path.to_string(),
+            builder: self.client.patch(self.addr.url(path)),
+        }
+    }
+
+    /// How long to wait for a reply to a request that the manager works on for up to `wait`
+    /// seconds before replying.
+    pub fn wait_timeout(&self, wait: u64) -> std::time::Duration {
+        std::time::Duration::from_secs(wait) + self.addr.timeout
+    }
+
+    /// Check that the manager is up and responding to requests.
+    pub fn ping(&self) -> CommandResult<()> {
+        self.get("/")
+            .send()
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| CommandError::from_request(&self.addr, e))?;
+        Ok(())
+    }
+
+    /// Get the status of every resource.
+    pub fn status(&self) -> CommandResult<http::ClusterJson> {
+        self.status_matching(&http::StatusQuery::default())
+    }
+
+    /// Get the status of the resources selected by `query`.
+    pub fn status_matching(&self, query: &http::StatusQuery) -> CommandResult<http::ClusterJson> {
+        let response = self
+            .get("/status")
+            .query(query)
+            .send()
+            .map_err(|e| CommandError::from_request(&self.addr, e))?;
+        if response.status() == reqwest::StatusCode::BAD_REQUEST {
+            let message = response.text().unwrap_or_default();
+            return Err(CommandError::Validation(message));
+        }
+        response
+            .error_for_status()
+            .and_then(|response| response.json())
+            .map_err(|e| CommandError::from_request(&self.addr, e))
+    }
+
+    /// Get the IDs of the resources selected by `query`, without their status.
+    pub fn resource_ids(&self, query: &http::StatusQuery) -> CommandResult<Vec<String>> {
+        let response = self
+            .get("/resources")
+            .query(query)
+            .send()
+            .map_err(|e| CommandError::from_request(&self.addr, e))?;
+        if response.status() == reqwest::StatusCode::BAD_REQUEST {
+            let message = response.text().unwrap_or_default();
+            return Err(CommandError::Validation(message));
+        }
+        response
+            .error_for_status()
+            .and_then(|response| response.json())
+            .map_err(|e| CommandError::from_request(&self.addr, e))
+    }
+
+    /// Get a summary of the state of the cluster as a whole.
+    pub fn summary(&self) -> CommandResult<http::SummaryJson> {
+        self.get("/summary")
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.json())
+            .map_err(|e| CommandError::from_request(&self.addr, e))
+    }
+
+    /// Get the status of the resources selected by `query`, leaving any error for the caller to
+    /// report.
+    pub fn try_status(&self, query: &http::StatusQuery) -> reqwest::Result<http::ClusterJson> {
+        self.get("/status")
+            .query(query)
+            .send()?
+            .error_for_status()?
+            .json()
+    }
+
+    /// Set the managed state of the resource groups selected by `params`. A reply saying that some
+    /// of them were not found is not an error here; the caller decides how to report them.
+    pub fn manage(
+        &self,
+        params: &http::SetManagedBatchArgs,
+    ) -> CommandResult<http::SetManagedBatchReply> {
+        let response = self
+            .patch("/resources")
+            .json(params)
+            .send()
+            .handle_request_err(&self.addr)?;
+
+        match response.status() {
+            reqwest::StatusCode::OK
+            | reqwest::StatusCode::MULTI_STATUS
+            | reqwest::StatusCode::NOT_FOUND
+            | reqwest::StatusCode::CONFLICT => {}
+            _ => return Err(reply_error(response, "could not update resources")),
+        };
+
+        response.json().handle_request_err(&self.addr)
+    }
+}
+
+/// Where the address of the manager was found.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SocketSource {
+    Option,
+    Environment,
+    Config,
+    Default,
+}
+
+impl std::fmt::Display for SocketSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let source = match self {
+            SocketSource::Option => "--socket",
+            SocketSource::Environment => "HALO_SOCKET",
+            SocketSource::Config => "the config file",
+            SocketSource::Default => "the default",
+        };
+        write!(f, "{source}")
+    }
+}
+
+/// Find the address of the manager: the `--socket` option, then the `HALO_SOCKET` environment
+/// variable, then the `socket` in the config file, and then the default path. In verbose mode, the
+/// one that was used is reported.
+pub fn resolve_socket(cli: &Cli) -> String {
+    static REPORTED: std::sync::Once = std::sync::Once::new();
+
+    let (addr, source) = find_socket(
+        cli.socket.as_deref(),
+        std::env::var("HALO_SOCKET").ok(),
+        || config_socket(cli),
+    );
+    if cli.verbose {
+        REPORTED.call_once(|| eprintln!("Using manager at {addr} (from {source})."));
+    }
+    addr
+}
+
+fn find_socket(
+    option: Option<&str>,
+    env: Option<String>,
+    config: impl FnOnce() -> Option<String>,
+) -> (String, SocketSource) {
+    if let Some(addr) = option {
+        return (addr.to_string(), SocketSource::Option);
+    }
+    if let Some(addr) = env {
+        return (addr, SocketSource::Environment);
+    }
+    if let Some(addr) = config() {
+        return (addr, SocketSource::Config);
+    }
+    (crate::default_socket(), SocketSource::Default)
+}
+
+/// The token to send to the manager with each request: the `HALO_AUTH_TOKEN` environment
+/// variable, or else the `auth_token` field of the config file, if either is set.
+fn resolve_auth_token(cli: &Cli) -> Option<String> {
+    std::env::var("HALO_AUTH_TOKEN")
+        .ok()
+        .or_else(|| local_config(&cli.config)?.auth_token)
+}
+
+/// The socket set in the config file, if there is one.
+fn config_socket(cli: &Cli) -> Option<String> {
+    local_config(&cli.config)?.socket
+}
+
+/// The config from `paths`, or the default path. Most commands do not otherwise need the config,
+/// so a config which is missing or can not be parsed is skipped without complaint.
+fn local_config(paths: &[String]) -> Option<crate::config::Config> {
+    crate::config::Config::try_load(paths).ok()
+}
+
+/// Whether `addr` looks like a `host:port` TCP address rather than a path to a unix socket.
+fn is_tcp_address(addr: &str) -> bool {
+    !addr.contains('/')
+        && addr
+            .rsplit_once(':')
+            .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+}
+
+/// Describe an error that occurred while talking to the manager at `addr`.
+pub fn describe_request_error(addr: &ManagerAddress, e: reqwest::Error) -> String {
+    if e.is_timeout() {
+        format!("manager did not respond within {}s", addr.timeout.as_secs())
+    } else if e.is_connect() {
+        format!("could not connect to manager at {addr}")
+    } else if e.is_decode() {
+        format!("could not parse reply from manager at {addr}: {e}")
+    } else if let Some(status) = e.status() {
+        describe_error_status(addr, status)
+    } else {
+        format!("request to manager at {addr} failed: {e}")
+    }
+}
+
+/// Describe an error reply with the given `status` from the manager at `addr`.
+fn describe_error_status(addr: &ManagerAddress, status: reqwest::StatusCode) -> String {
+    match status {
+        reqwest::StatusCode::UNAUTHORIZED => format!(
+            "manager at {addr} requires an auth token; set the right one with HALO_AUTH_TOKEN or \
+            auth_token in the config file"
+        ),
+        reqwest::StatusCode::FORBIDDEN => format!(
+            "manager at {addr} only lets this auth token read, not change anything; use the full \
+            auth token instead"
+        ),
+        status => format!("manager at {addr} returned an error: {status}"),
+    }
+}
+
+/// Ask the manager to do `action` on a single resource, waiting up to `wait` seconds for it to
+/// finish, and print the resulting status of the resource. With `clear_failed`, the manager first
+/// forgets that it gave up on the resource.
+fn send_resource_action(
+    cli: &Cli,
+    client: &HaloClient,
+    action: http::ResourceAction,
+    resource: &str,
+    wait: u64,
+    clear_failed: bool,
+) -> CommandResult<()> {
+    let params = http::ResourceActionArgs {
+        timeout: wait,
+        dry_run: cli.dry_run,
+        clear_failed,
+    };
+
+    // The manager gives up on the action after `wait` seconds, so allow that much longer for it to
+    // reply:
+    let response = client
+        .post(&format!("/{action}/{resource}"))
+        .timeout(client.wait_timeout(wait))
+        .json(&params)
+        .send()
+        .handle_request_err(client.addr())?;
+
+    let done = format!("could not {action} '{resource}'");
+    match response.status() {
+        reqwest::StatusCode::OK => {}
+        reqwest::StatusCode::NOT_FOUND => return Err(resource_not_found(cli, &done, resource)),
+        _ => return Err(reply_error(response, &done)),
+    };
+
+    let Some(reply): Option<http::ResourceJson> = read_reply(client.addr(), response)? else {
+        return Ok(());
+    };
+
+    match reply.comment {
+        Some(comment) => println!("{}: {} ({comment})", reply.id, reply.status),
+        None => println!("{}: {}", reply.id, reply.status),
+    }
+
+    Ok(())
+}
+
+/// How many times to retry a request which is over the manager's rate limit, with --retry.
+const RATE_LIMIT_RETRIES: u32 = 5;
+
+/// A request to the manager which changes something, from HaloClient::post() or patch().
+pub struct WriteRequest<'a> {
+    client: &'a HaloClient,
+    path: String,
+    builder: reqwest::blocking::RequestBuilder,
+}
+
+impl WriteRequest<'_> {
+    pub fn json<T: serde::Serialize + ?Sized>(mut self, json: &T) -> Self {
+        self.builder = self.builder.json(json);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.builder = self.builder.timeout(timeout);
+        self
+    }
+
+    /// Send the request. If the manager rejects it with a 429, since the endpoint is over its rate
+    /// limit, it is sent again after the wait that the manager gives in its Retry-After header, or
+    /// a wait which doubles each time, with --retry, up to RATE_LIMIT_RETRIES times. Otherwise, or
+    /// once the retries run out, the rejection is returned to be reported like any other, after
+    /// saying what it means.
+    pub fn send(self) -> reqwest::Result<reqwest::blocking::Response> {
+        let mut backoff = std::time::Duration::from_secs(1);
+        let mut attempt = 0;
+        loop {
+            let request = self
+                .builder
+                .try_clone()
+                .expect("requests to the manager have JSON bodies, which can be cloned");
+            let response = request.send()?;
+            if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                return Ok(response);
+            }
+
+            let wait = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok())
+                .map(std::time::Duration::from_secs)
+                .unwrap_or(backoff);
+            if !self.client.addr.retry || attempt == RATE_LIMIT_RETRIES {
+                eprintln!(
+                    "The manager is limiting how often {} may be requested; try again in {}s{}.",
+                    self.path,
+                    wait.as_secs(),
+                    if self.client.addr.retry {
+                        ""
+                    } else {
+                        ", or pass --retry to wait for it"
+                    }
+                );
+                return Ok(response);
+            }
+            std::thread::sleep(wait);
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+}
+
+/// The exit status for an error reply from the manager with the given HTTP `status`.
+fn status_exit_code(status: reqwest::StatusCode) -> ExitCode {
+    match status {
+        reqwest::StatusCode::NOT_FOUND => ExitCode::NotFound,
+        reqwest::StatusCode::BAD_REQUEST => ExitCode::Usage,
+        _ => ExitCode::Failure,
+    }
+}
+
+/// The exit status for a request that the manager rejected with the given error code.
+impl From<http::ErrorCode> for ExitCode {
+    fn from(code: http::ErrorCode) -> Self {
+        match code {
+            http::ErrorCode::NotFound => ExitCode::NotFound,
+            http::ErrorCode::ValidationError => ExitCode::Usage,
+            http::ErrorCode::AlreadyInState
+            | http::ErrorCode::Conflict
+            | http::ErrorCode::NoQuorum
+            | http::ErrorCode::AgentFailure
+            | http::ErrorCode::Timeout
+            | http::ErrorCode::Canceled
+            | http::ErrorCode::Internal
+            | http::ErrorCode::Unknown => ExitCode::Failure,
+        }
+    }
+}
+
+/// The error for a request which the manager rejected with `response`, saying what could not be
+/// `done`, and why, with the exit status that the error code of the reply calls for. A manager
+/// which only replies with text is understood too, in which case the exit status is the one for its
+/// HTTP status.
+fn reply_error(response: reqwest::blocking::Response, done: &str) -> CommandError {
+    let status = response.status();
+    let body = response.text().unwrap_or_default();
+    let (message, code) = match serde_json::from_str::<http::ErrorReply>(&body) {
+        Ok(http::ErrorReply {
+            message,
+            code: Some(code),
+            ..
+        }) if code != http::ErrorCode::Unknown => (message, code.into()),
+        Ok(reply) => (reply.message, status_exit_code(status)),
+        Err(_) => (body, status_exit_code(status)),
+    };
+    let message = if message.is_empty() {
+        format!("{done}: {status}")
+    } else {
+        format!("{done}: {message}")
+    };
+    CommandError::with_code(code, message)
+}
+
+/// The result of a command which went on past the `errors` it met, e.g. one for each of several
+/// hosts. A single error is returned as it is; when there are more, each of them is reported, and
+/// only the exit status of the last is left.
+pub fn collect_errors(mut errors: Vec<CommandError>) -> CommandResult<()> {
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(errors.remove(0)),
+        _ => Err(errors.into_iter().map(CommandError::report).last().unwrap()),
+    }
+}
+
+/// The error for a command which could not start the async runtime that it runs on.
+pub fn runtime_error(e: std::io::Error) -> CommandError {
+    std::io::Error::new(e.kind(), format!("could not start the async runtime: {e}")).into()
+}
+
+/// The error for a request about `resource` which the manager did not find, saying what could not
+/// be `done`, and suggesting the resource in the local config which the user may have meant.
+pub fn resource_not_found(cli: &Cli, done: &str, resource: &str) -> CommandError {
+    let mut message = format!("{done}: resource not found.");
+    if let Some(closest) = closest_match(resource, &known_resources(&cli.config)) {
+        message += &format!(" Did you mean '{closest}'?");
+    }
+    CommandError::NotFound(message)
+}
+
+/// Read the manager's reply to a request which changes the state of the cluster.
+///
+/// For a dry run, the manager describes what it would have done instead; that is printed here, and
+/// None is returned.
+fn read_reply<T: serde::de::DeserializeOwned>(
+    addr: &ManagerAddress,
+    response: reqwest::blocking::Response,
+) -> CommandResult<Option<T>> {
+    let body = response.text().handle_request_err(addr)?;
+
+    if let Ok(reply) = serde_json::from_str::<http::DryRunReply>(&body) {
+        println!("{}", reply.message);
+        return Ok(None);
+    }
+
+    serde_json::from_str(&body).map(Some).map_err(|e| {
+        CommandError::Agent(format!("could not parse reply from manager at {addr}: {e}"))
+    })
+}
+
+/// Reject a resource ID on the command line which could not name any resource, before anything is
+/// sent to the manager.
+pub fn parse_resource_id(arg: &str) -> Result<String, String> {
+    if arg.is_empty() {
+        return Err("resource IDs can not be empty".to_string());
+    }
+    if arg.chars().any(char::is_control) {
+        return Err("resource IDs can not contain control characters".to_string());
+    }
+    if arg.chars().any(char::is_whitespace) {
+        return Err("resource IDs can not contain whitespace".to_string());
+    }
+    Ok(arg.to_string())
+}
+
+/// The IDs of the resources in the local config from `paths`, for suggesting what the user
+/// meant when the manager does not find a resource. Empty if the config can not be read.
+pub fn known_resources(paths: &[String]) -> Vec<String> {
+    let Some(config) = local_config(paths) else {
+        return Vec::new();
+    };
+    let mut ids: Vec<String> = config
+        .hosts
+        .into_iter()
+        .flat_map(|host| host.resources.into_keys())
+        .collect();
+    ids.sort();
+    ids.dedup();
+    ids
 }
 
-/// Convert multiple nodeset strings into a single, deduplicated NodeSet object.
+/// If `id` looks like a typo of one of the `known` resource IDs, suggest the closest one, for a
+/// command which reports several IDs which were not found.
+pub fn suggest_resource(known: &[String], id: &str) {
+    if let Some(closest) = closest_match(id, known) {
+        eprintln!("Did you mean '{closest}'?");
+    }
+}
+
+/// The entry in `known` with the smallest edit distance from `id`, if it is close enough to be a
+/// likely typo: within one edit for every three characters. Nothing is suggested if `id` itself is
+/// known, since then the local config is out of date rather than `id` being mistyped.
+fn closest_match<'a>(id: &str, known: &'a [String]) -> Option<&'a str> {
+    if known.iter().any(|candidate| candidate == id) {
+        return None;
+    }
+    let limit = (id.chars().count() / 3).max(1);
+    known
+        .iter()
+        .map(|candidate| (levenshtein(id, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+/// The Levenshtein distance between `a` and `b`: the number of single-character insertions,
+/// deletions, and substitutions needed to turn one into the other.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // The distances from the prefix of `a` seen so far to each prefix of `b`:
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// A nodeset string which could not be parsed.
+#[derive(Debug)]
+pub struct NodesetError {
+    input: String,
+    error: nodeset::NodeSetParseError,
+}
+
+/// A reminder of the syntax of nodesets, for the user of a command that could not parse one.
+const NODESET_HINT: &str = "A nodeset is a list of names separated by commas, with ranges of \
+                            numbers in brackets, e.g. 'node[00-05]' or 'node[00-05,08],login1'.";
+
+impl From<NodesetError> for CommandError {
+    fn from(e: NodesetError) -> Self {
+        CommandError::Validation(format!("{e}\n{NODESET_HINT}"))
+    }
+}
+
+impl std::fmt::Display for NodesetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "could not parse nodeset '{}': {}",
+            self.input, self.error
+        )?;
+        // The parse error only says what kind of problem it was; the reason, e.g. that a range is
+        // descending, is in its source:
+        if let Some(source) = std::error::Error::source(&self.error) {
+            write!(f, ": {source}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Convert multiple nodeset strings into a single, deduplicated NodeSet object, along with the
+/// number of names that were given by more than one of the strings.
 /// A "nodeset" is a string representing shorthand notation for a group of hosts (e.g.,
 /// 'node[00-05]').
-fn merge_nodesets(nodesets: &[String]) -> Result<nodeset::NodeSet, nodeset::NodeSetParseError> {
+fn merge_nodesets(nodesets: &[String]) -> Result<(nodeset::NodeSet, usize), NodesetError> {
     let mut nodeset = nodeset::NodeSet::new();
+    let mut total = 0;
     for nodeset_str in nodesets.iter() {
-        let curr_nodeset = &nodeset_str.parse()?;
-        nodeset = nodeset.union(curr_nodeset);
+        let curr_nodeset: nodeset::NodeSet = nodeset_str.parse().map_err(|error| NodesetError {
+            input: nodeset_str.clone(),
+            error,
+        })?;
+        total += curr_nodeset.len();
+        nodeset = nodeset.union(&curr_nodeset);
+    }
+    let duplicates = total - nodeset.len();
+    Ok((nodeset, duplicates))
+}
+
+/// Convert multiple nodesets into a vector of hostname strings. With `verbose`, report how many
+/// names they expanded to, so that overlapping nodesets are not a surprise.
+fn nodesets2hostnames(nodesets: &[String], verbose: bool) -> Result<Vec<String>, NodesetError> {
+    let (nodeset, duplicates) = merge_nodesets(nodesets)?;
+    if verbose {
+        eprintln!(
+            "{} nodeset(s) expanded to {} name(s), after collapsing {duplicates} duplicate(s).",
+            nodesets.len(),
+            nodeset.len()
+        );
+    }
+    Ok(nodeset.iter().collect())
+}
+
+/// mTLS does not apply to commands which talk to the manager service over its unix socket; reject
+/// `--mtls` for them rather than silently ignoring it.
+fn check_manager_transport(cli: &Cli) -> CommandResult<()> {
+    if cli.mtls && !ManagerAddress::from_cli(cli).is_tcp() {
+        return Err(CommandError::Validation(
+            "--mtls is not supported for connections to the manager over a unix socket."
+                .to_string(),
+        ));
     }
-    Ok(nodeset)
+    Ok(())
 }
 
-/// Convert multiple nodesets into a vector of hostname strings.
-fn nodesets2hostnames(nodesets: &[String]) -> Result<Vec<String>, nodeset::NodeSetParseError> {
-    Ok(merge_nodesets(nodesets)?.iter().collect())
+/// The manager can only report what it would do for some commands; reject `--dry-run` for the
+/// others rather than changing the cluster when the user asked not to.
+fn check_dry_run(cli: &Cli) -> CommandResult<()> {
+    let command = match cli.command {
+        Commands::Failback(_) => "failback",
+        Commands::Power(_) => "power",
+        Commands::Standby(_) => "standby",
+        Commands::Unstandby(_) => "unstandby",
+        _ => return Ok(()),
+    };
+    if cli.dry_run {
+        return Err(CommandError::Validation(format!(
+            "--dry-run is not supported by 'halo {command}'."
+        )));
+    }
+    Ok(())
 }
 
-pub fn main(cli: &Cli) -> HandledResult<()> {
+/// Send what the command writes to stdout to the file descriptor given with --output-fd, if any,
+/// once it is checked to be open for writing.
+fn redirect_output(cli: &Cli) -> CommandResult<()> {
+    use nix::fcntl::{fcntl, FcntlArg, OFlag};
+
+    let Some(fd) = cli.output_fd else {
+        return Ok(());
+    };
+    let problem = match fcntl(fd, FcntlArg::F_GETFL) {
+        Err(nix::errno::Errno::EBADF) => Some("is not open".to_string()),
+        Err(e) => Some(format!("could not be checked: {e}")),
+        Ok(flags) => match OFlag::from_bits_truncate(flags) & OFlag::O_ACCMODE {
+            OFlag::O_RDONLY => Some("is not open for writing".to_string()),
+            _ => nix::unistd::dup2(fd, nix::libc::STDOUT_FILENO)
+                .err()
+                .map(|e| format!("could not be written to: {e}")),
+        },
+    };
+    if let Some(problem) = problem {
+        return Err(CommandError::Validation(format!(
+            "--output-fd {fd} {problem}."
+        )));
+    }
+    Ok(())
+}
+
+/// Run the subcommand given in `cli`. An error is left for the caller to report, once.
+pub fn main(cli: &Cli) -> CommandResult<()> {
+    check_dry_run(cli)?;
+    redirect_output(cli)?;
+
+    if matches!(
+        cli.command,
+        Commands::Status(_)
+            | Commands::Resources(_)
+            | Commands::Describe(_)
+            | Commands::Manage(_)
+            | Commands::Unmanage(_)
+            | Commands::Enable(_)
+            | Commands::Disable(_)
+            | Commands::Failback(_)
+            | Commands::Ping(_)
+            | Commands::Start(_)
+            | Commands::Stop(_)
+            | Commands::Cancel(_)
+            | Commands::Migrate(_)
+            | Commands::Standby(_)
+            | Commands::Unstandby(_)
+            | Commands::Events(_)
+            | Commands::History(_)
+            | Commands::Simulate(_)
+            | Commands::Mode(_)
+            | Commands::Maintenance(_)
+            | Commands::Config(_)
+            | Commands::SetParam(_)
+            | Commands::Top(_)
+            | Commands::Shell(_)
+    ) {
+        check_manager_transport(cli)?;
+    }
+
     match &cli.command {
-        Commands::Discover(args) => return discover::discover(args),
-        Commands::Failback(args) => return failback::failback(cli, args),
-        Commands::Power(args) => return power::power(cli, args),
-        Commands::Validate => return validate::validate(cli),
-        Commands::Status(args) => return status::status(cli, args),
-        Commands::Manage(args) => return manage::manage(cli, args),
-        Commands::Unmanage(args) => return manage::unmanage(cli, args),
-        _ => {}
-    }
-
-    let rt = tokio::runtime::Runtime::new()
-        .handle_err(|e| eprintln!("Error launching tokio runtime: {e}"))?;
-
-    rt.block_on(async {
-        let cluster = Cluster::from_config(cli.config.clone())?;
-        match &cli.command {
-            Commands::Start => start::start(cluster).await,
-            Commands::Stop => stop::stop(cluster).await,
-            _ => unreachable!(),
+        Commands::Discover(args) => discover::discover(cli, args),
+        Commands::Failback(args) => failback::failback(cli, args),
+        Commands::Power(args) => power::power(cli, args),
+        Commands::Validate => validate::validate(cli),
+        Commands::Status(args) => status::status(cli, args),
+        Commands::Resources(args) => resources::resources(cli, args),
+        Commands::Describe(args) => describe::describe(cli, args),
+        Commands::Manage(args) => manage::manage(cli, args),
+        Commands::Unmanage(args) => manage::unmanage(cli, args),
+        Commands::Enable(args) => enable::enable(cli, args),
+        Commands::Disable(args) => enable::disable(cli, args),
+        Commands::Ping(args) => ping::ping(cli, args),
+        Commands::Start(args) => start::start(cli, args),
+        Commands::Stop(args) => stop::stop(cli, args),
+        Commands::Cancel(args) => cancel::cancel(cli, args),
+        Commands::Migrate(args) => migrate::migrate(cli, args),
+        Commands::Standby(args) => standby::standby(cli, args),
+        Commands::Unstandby(args) => standby::unstandby(cli, args),
+        Commands::Events(args) => events::events(cli, args),
+        Commands::History(args) => history::history(cli, args),
+        Commands::Simulate(args) => simulate::simulate(cli, args),
+        Commands::Mode(args) => mode::mode(cli, args),
+        Commands::Maintenance(args) => maintenance::maintenance(cli, args),
+        Commands::Config(args) => config::config(cli, args),
+        Commands::SetParam(args) => set_param::set_param(cli, args),
+        Commands::Top(args) => top::top(cli, args),
+        Commands::Shell(args) => shell::shell(cli, args),
+        Commands::Completions(args) => completions::completions(args),
+        Commands::Version(args) => version::version(cli, args),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_tcp_address() {
+        assert!(is_tcp_address("localhost:8080"));
+        assert!(is_tcp_address("10.0.0.1:8080"));
+        assert!(is_tcp_address("[::1]:8080"));
+        assert!(!is_tcp_address("/var/run/halo.socket"));
+        assert!(!is_tcp_address("halo.socket"));
+        assert!(!is_tcp_address("./halo:8080"));
+        assert!(!is_tcp_address(":8080"));
+        assert!(!is_tcp_address("localhost:http"));
+    }
+
+    #[test]
+    fn test_url() {
+        let url = |addr: &str| ManagerAddress::new(addr).url("/status");
+        assert_eq!(url("localhost:8080"), "http://localhost:8080/status");
+        assert_eq!(url("10.0.0.1:8080"), "http://10.0.0.1:8080/status");
+        assert_eq!(url("[::1]:8080"), "http://[::1]:8080/status");
+        assert_eq!(url("::1:8080"), "http://[::1]:8080/status");
+        assert_eq!(url("fe80::2:8080"), "http://[fe80::2]:8080/status");
+        assert_eq!(url("/var/run/halo.socket"), "http://halo_manager/status");
+    }
+
+    #[test]
+    fn test_command_error() {
+        let e = CommandError::Validation("bad".to_string());
+        assert_eq!(e.code(), ExitCode::Usage);
+        assert_eq!(e.to_string(), "bad");
+        assert_eq!(CommandError::Agent(String::new()).code(), ExitCode::Failure);
+
+        // The error codes of the manager's replies map onto the exit statuses:
+        assert_eq!(
+            ExitCode::from(http::ErrorCode::NotFound),
+            ExitCode::NotFound
+        );
+        assert_eq!(
+            ExitCode::from(http::ErrorCode::ValidationError),
+            ExitCode::Usage
+        );
+        assert_eq!(
+            ExitCode::from(http::ErrorCode::AgentFailure),
+            ExitCode::Failure
+        );
+
+        let e: CommandError = std::io::Error::other("disk on fire").into();
+        assert_eq!(e.code(), ExitCode::Failure);
+        assert_eq!(e.to_string(), "disk on fire");
+        assert_eq!(HandledError::from(e).code(), ExitCode::Failure);
+
+        // An error which was already reported keeps only its exit status, both ways:
+        let e = CommandError::from(HandledError::new(ExitCode::Connection));
+        assert!(matches!(e, CommandError::Reported(ExitCode::Connection)));
+        assert_eq!(HandledError::from(e).code(), ExitCode::Connection);
+        assert!(matches!(
+            CommandError::with_code(ExitCode::NotFound, "gone".to_string()),
+            CommandError::NotFound(_)
+        ));
+
+        // Of several errors, only the exit status of the last is left once they are reported, but
+        // a single one is returned as it is:
+        assert!(collect_errors(Vec::new()).is_ok());
+        let errors = vec![
+            CommandError::NotFound("one".to_string()),
+            CommandError::Agent("two".to_string()),
+        ];
+        assert!(matches!(
+            collect_errors(errors),
+            Err(CommandError::Reported(ExitCode::Failure))
+        ));
+        let errors = vec![CommandError::NotFound("one".to_string())];
+        assert!(matches!(
+            collect_errors(errors),
+            Err(CommandError::NotFound(message)) if message == "one"
+        ));
+
+        // Something other than a socket where the manager's socket should be is a problem with
+        // connecting to it:
+        let path = std::env::temp_dir().join(format!("halo_not_a_socket_{}", std::process::id()));
+        std::fs::write(&path, "").unwrap();
+        let result = ManagerAddress::new(path.to_str().unwrap()).client();
+        std::fs::remove_file(&path).unwrap();
+        match result {
+            Err(CommandError::Connection(message)) => assert!(message.contains("not a socket")),
+            other => panic!("unexpected result: {other:?}"),
         }
-    })
+    }
+
+    #[test]
+    fn test_find_socket() {
+        let env = || Some("/env.socket".to_string());
+        let config = || Some("/config.socket".to_string());
+
+        assert_eq!(
+            find_socket(Some("/option.socket"), env(), config),
+            ("/option.socket".to_string(), SocketSource::Option)
+        );
+        assert_eq!(
+            find_socket(None, env(), config),
+            ("/env.socket".to_string(), SocketSource::Environment)
+        );
+        assert_eq!(
+            find_socket(None, None, config),
+            ("/config.socket".to_string(), SocketSource::Config)
+        );
+        assert_eq!(find_socket(None, None, || None).1, SocketSource::Default);
+    }
+
+    #[test]
+    fn test_merge_nodesets() {
+        let nodesets = vec!["node[00-10]".to_string(), "node[05-15]".to_string()];
+        let (nodeset, duplicates) = merge_nodesets(&nodesets).unwrap();
+        assert_eq!(nodeset.len(), 16);
+        assert_eq!(duplicates, 6);
+
+        let error = |nodeset: &str| {
+            merge_nodesets(&["oss00".to_string(), nodeset.to_string()])
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error("node[05-00]"),
+            "could not parse nodeset 'node[05-00]': invalid range: start id is greater than end id"
+        );
+        assert!(error("node[").starts_with("could not parse nodeset 'node[': "));
+    }
+
+    #[test]
+    fn test_parse_resource_id() {
+        assert_eq!(parse_resource_id("ost[00-09]").unwrap(), "ost[00-09]");
+        assert_eq!(parse_resource_id("@osts").unwrap(), "@osts");
+        assert!(parse_resource_id("").is_err());
+        assert!(parse_resource_id("ost 0").is_err());
+        assert!(parse_resource_id("ost0\u{7}").is_err());
+    }
+
+    #[test]
+    fn test_closest_match() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "ost0"), 4);
+        assert_eq!(levenshtein("ost0", "ost0"), 0);
+
+        let known: Vec<String> = ["mdt_0", "ost_0", "ost_1", "zpool_00"]
+            .iter()
+            .map(|id| id.to_string())
+            .collect();
+        assert_eq!(closest_match("ost_O", &known), Some("ost_0"));
+        assert_eq!(closest_match("zpool_0", &known), Some("zpool_00"));
+        assert_eq!(closest_match("mgs", &known), None);
+        // An exact match is not a suggestion:
+        assert_eq!(closest_match("ost_1", &known), None);
+    }
 }
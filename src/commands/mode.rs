@@ -0,0 +1,57 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+use {clap::Args, reqwest::StatusCode};
+
+use crate::{commands::*, manager::http};
+
+#[derive(Args, Debug, Clone)]
+pub struct ModeArgs {
+    /// The mode to switch the manager to. Without it, the current mode is printed.
+    #[arg(value_enum)]
+    mode: Option<http::Mode>,
+}
+
+/// Print whether the manager is in Manage or Observe mode, or switch it to the given mode.
+pub fn mode(cli: &Cli, args: &ModeArgs) -> CommandResult<()> {
+    let client = HaloClient::from_cli(cli)?;
+
+    let Some(mode) = args.mode else {
+        let reply: http::ModeJson = client
+            .get("/mode")
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.json())
+            .handle_request_err(client.addr())?;
+        println!("{}", reply.mode);
+        return Ok(());
+    };
+
+    let params = http::SetModeArgs {
+        mode,
+        dry_run: cli.dry_run,
+    };
+    let response = client
+        .post("/mode")
+        .json(&params)
+        .send()
+        .handle_request_err(client.addr())?;
+
+    if response.status() != StatusCode::OK {
+        return Err(reply_error(
+            response,
+            &format!("could not switch to {mode} mode"),
+        ));
+    }
+
+    let Some(reply): Option<http::SetModeReply> = read_reply(client.addr(), response)? else {
+        return Ok(());
+    };
+    if reply.changed {
+        println!("Switched to {} mode.", reply.mode);
+    } else {
+        println!("Already in {} mode.", reply.mode);
+    }
+
+    Ok(())
+}
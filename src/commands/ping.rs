@@ -0,0 +1,33 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+use std::time::Instant;
+
+use clap::Args;
+
+use crate::commands::*;
+
+#[derive(Args, Debug, Clone)]
+pub struct PingArgs {}
+
+/// Check whether the manager is up and responding to requests.
+pub fn ping(cli: &Cli, _args: &PingArgs) -> CommandResult<()> {
+    let client = HaloClient::from_cli(cli)?;
+    let addr = client.addr();
+
+    let start = Instant::now();
+    client
+        .ping()
+        .inspect_err(|_| println!("manager at {addr} is not alive"))?;
+
+    if cli.verbose {
+        println!(
+            "manager at {addr} is alive (round trip {:?})",
+            start.elapsed()
+        );
+    } else {
+        println!("manager at {addr} is alive");
+    }
+
+    Ok(())
+}
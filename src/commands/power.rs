@@ -5,15 +5,18 @@ use clap::Args;
 
 use crate::{
     cluster::Cluster,
-    commands::{self, Cli, HandledResult},
+    commands::{self, Cli, CommandError, CommandResult, HandleRequest},
     host::*,
+    manager::http,
 };
 
 #[derive(Args, Debug, Clone)]
 pub struct PowerArgs {
     /// The fencing action to perform.
-    action: FenceCommand,
+    #[arg(required_unless_present = "reset_breaker")]
+    action: Option<FenceCommand>,
 
+    /// The hosts to act on. These may be given in nodeset syntax, e.g., 'node[00-05]'.
     #[arg()]
     hostnames: Vec<String>,
 
@@ -29,15 +32,33 @@ pub struct PowerArgs {
 
     #[arg(short = 'p', long)]
     password: Option<String>,
+
+    /// Let the manager try to fence these hosts again, after it stopped because fencing them
+    /// failed too many times in a row. These may be given in nodeset syntax.
+    #[arg(long, value_name = "HOSTNAMES", num_args = 1.., conflicts_with_all = ["action", "hostnames", "fence_agent"])]
+    reset_breaker: Vec<String>,
+}
+
+impl PowerArgs {
+    fn action(&self) -> FenceCommand {
+        self.action
+            .expect("an action is required unless --reset-breaker is given")
+    }
 }
 
-pub fn power(main_args: &Cli, args: &PowerArgs) -> HandledResult<()> {
+pub fn power(main_args: &Cli, args: &PowerArgs) -> CommandResult<()> {
+    if !args.reset_breaker.is_empty() {
+        return reset_breakers(main_args, &args.reset_breaker);
+    }
+
     if args.hostnames.is_empty() {
         return status_all_hosts_in_config(main_args, args);
     }
 
+    let hostnames = commands::nodesets2hostnames(&args.hostnames, args.verbose)?;
+
     if let Some(fence_agent) = args.fence_agent.as_ref() {
-        return do_fence_given_agent(fence_agent, args);
+        return do_fence_given_agent(fence_agent, &hostnames, args);
     }
 
     // If the user has not specified a fence agent, then assume that the fence parameters for the
@@ -45,73 +66,108 @@ pub fn power(main_args: &Cli, args: &PowerArgs) -> HandledResult<()> {
 
     let cluster = Cluster::from_config(main_args.config.clone())?;
 
-    for hostname in args.hostnames.iter() {
-        let host = cluster.get_host(hostname).unwrap();
-        match host.do_fence(args.action) {
+    // Look up every host before acting on any of them, so that a typo in one hostname does not
+    // result in only some of the hosts being fenced.
+    let mut hosts = Vec::new();
+    for hostname in hostnames.iter() {
+        let Some(host) = cluster.get_host(hostname) else {
+            return Err(CommandError::NotFound(format!(
+                "host '{hostname}' was not found in the cluster config."
+            )));
+        };
+        if host.fence_agent().is_none() {
+            return Err(CommandError::Validation(format!(
+                "host '{hostname}' does not have a fence agent configured."
+            )));
+        }
+        hosts.push(host.as_ref());
+    }
+
+    do_action(hosts.into_iter(), args)
+}
+
+/// Perform the requested action on every host in `hosts`, reporting the result for each one.
+/// Returns an error if the action failed for any of the hosts.
+fn do_action<'a>(hosts: impl Iterator<Item = &'a Host>, args: &PowerArgs) -> CommandResult<()> {
+    let mut failed = 0;
+
+    for host in hosts {
+        if let FenceCommand::Status = args.action() {
+            match host.is_powered_on() {
+                Ok(true) => println!("{} is on", host),
+                Ok(false) => println!("{} is off", host),
+                Err(e) => {
+                    println!("Could not determine power status for {}, {e}", host);
+                    failed += 1;
+                }
+            }
+            continue;
+        }
+
+        if args.verbose {
+            eprintln!("Fencing Host: {}", host.name());
+        }
+        match host.do_fence(args.action()) {
             Ok(()) => {
                 eprintln!("{} Fence: Success", host.name());
             }
             Err(e) => {
                 eprintln!("{} Fence result: Failure: {e}", host.name());
+                failed += 1;
             }
         }
     }
 
-    Ok(())
+    match failed {
+        0 => Ok(()),
+        _ => Err(CommandError::Agent(format!(
+            "the action failed for {failed} host(s)."
+        ))),
+    }
 }
 
 /// Perform a fence action, with the fence agent specified on the command line. In this case, the
 /// specified fence agent will override any potential fence agent found in a config file (if a
 /// config is passed as an argument.)
-fn do_fence_given_agent(fence_agent: &str, args: &PowerArgs) -> HandledResult<()> {
+fn do_fence_given_agent(
+    fence_agent: &str,
+    hostnames: &[String],
+    args: &PowerArgs,
+) -> CommandResult<()> {
     let fence_agent = match fence_agent {
         "powerman" => FenceAgent::Powerman,
         "redfish" => {
-            let user = args.username.clone().unwrap();
-            let pass = args.password.clone().unwrap();
+            let (Some(user), Some(pass)) = (args.username.clone(), args.password.clone()) else {
+                return Err(CommandError::Validation(
+                    "the redfish fence agent requires --username and --password.".to_string(),
+                ));
+            };
             FenceAgent::Redfish(RedfishArgs::new(user, pass))
         }
-        other => panic!("unsupported fence agent {other}"),
+        other => {
+            return Err(CommandError::Validation(format!(
+                "unsupported fence agent \"{other}\"."
+            )));
+        }
     };
 
-    let hosts: Vec<Host> = args
-        .hostnames
+    let hosts: Vec<Host> = hostnames
         .iter()
         .map(|host| Host::new(host, None, Some(fence_agent.clone())))
         .collect();
 
-    let mut error_seen = false;
-
-    for host in hosts {
-        if args.verbose {
-            eprintln!("Fencing Host: {}", host.name());
-        }
-        match host.do_fence(args.action) {
-            Ok(()) => {
-                eprintln!("{} Fence: Success", host.name());
-            }
-            Err(e) => {
-                eprintln!("{} Fence result: Failure: {e}", host.name());
-                error_seen = true;
-            }
-        }
-    }
-
-    if error_seen {
-        commands::handled_error()
-    } else {
-        Ok(())
-    }
+    do_action(hosts.iter(), args)
 }
 
 /// When no hostnames are specified, it is assumed that the user is requesting the power status of
 /// every host in the config.
-fn status_all_hosts_in_config(main_args: &Cli, args: &PowerArgs) -> HandledResult<()> {
-    match &args.action {
+fn status_all_hosts_in_config(main_args: &Cli, args: &PowerArgs) -> CommandResult<()> {
+    match &args.action() {
         FenceCommand::Status => {}
         other => {
-            eprintln!("Must specify host names to perform action \"{other}\".");
-            return commands::handled_error();
+            return Err(CommandError::Validation(format!(
+                "must specify host names to perform action \"{other}\"."
+            )));
         }
     };
 
@@ -127,3 +183,39 @@ fn status_all_hosts_in_config(main_args: &Cli, args: &PowerArgs) -> HandledResul
 
     Ok(())
 }
+
+/// Ask the manager to reset the fencing breaker of each host in `nodesets`, so that it tries to
+/// fence them again when they need it.
+///
+/// Every host is attempted even if some of them fail.
+fn reset_breakers(cli: &Cli, nodesets: &[String]) -> CommandResult<()> {
+    commands::check_manager_transport(cli)?;
+    let hostnames = commands::nodesets2hostnames(nodesets, cli.verbose)?;
+
+    let client = commands::HaloClient::from_cli(cli)?;
+    let params = http::HostArgs {
+        command: "reset_breaker".into(),
+    };
+
+    let mut errors = Vec::new();
+    for hostname in hostnames.iter() {
+        let response = client
+            .post(&format!("/hosts/{hostname}"))
+            .json(&params)
+            .send()
+            .handle_request_err(client.addr())?;
+
+        match response.status() {
+            reqwest::StatusCode::OK => println!("{hostname}: fencing breaker reset"),
+            reqwest::StatusCode::NOT_FOUND => errors.push(CommandError::NotFound(format!(
+                "could not reset the fencing breaker of '{hostname}': host not found."
+            ))),
+            _ => errors.push(commands::reply_error(
+                response,
+                &format!("could not reset the fencing breaker of '{hostname}'"),
+            )),
+        }
+    }
+
+    commands::collect_errors(errors)
+}
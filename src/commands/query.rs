@@ -0,0 +1,496 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+//! Queries on the hosts found by `halo discover`, for its `--where` and `--select` options.
+//!
+//! A query compares attributes of a host with values, e.g. `resources>=2 && hostname!=oss00`.
+//! Comparisons are joined with `&&` and `||`, where `&&` binds more tightly, and may be grouped with
+//! parentheses. A value is a number, a word, or a string in single or double quotes.
+
+use std::{fmt, io};
+
+use serde::Serialize;
+
+use crate::config;
+
+/// An attribute of a discovered host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attribute {
+    Hostname,
+
+    /// Whether the host could be discovered.
+    Reachable,
+
+    /// How many resources were found on the host.
+    Resources,
+
+    /// How many of its resources are zpools.
+    Zpools,
+
+    /// How many of its resources are Lustre targets.
+    Targets,
+
+    /// Why the host could not be discovered, or nothing if it was.
+    Error,
+}
+
+/// What kind of value an attribute has, which decides how it may be compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Text,
+    Bool,
+    Number,
+}
+
+impl Attribute {
+    pub const ALL: [Attribute; 6] = [
+        Attribute::Hostname,
+        Attribute::Reachable,
+        Attribute::Resources,
+        Attribute::Zpools,
+        Attribute::Targets,
+        Attribute::Error,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Attribute::Hostname => "hostname",
+            Attribute::Reachable => "reachable",
+            Attribute::Resources => "resources",
+            Attribute::Zpools => "zpools",
+            Attribute::Targets => "targets",
+            Attribute::Error => "error",
+        }
+    }
+
+    fn kind(self) -> Kind {
+        match self {
+            Attribute::Hostname | Attribute::Error => Kind::Text,
+            Attribute::Reachable => Kind::Bool,
+            Attribute::Resources | Attribute::Zpools | Attribute::Targets => Kind::Number,
+        }
+    }
+
+    /// The value of this attribute for the host `hostname`, given the result of discovering it.
+    pub fn of(self, hostname: &str, result: Result<&config::Host, &io::Error>) -> Value {
+        let count = |zpools: Option<bool>| match result {
+            Ok(host) => Value::Number(
+                host.resources
+                    .values()
+                    .filter(|res| {
+                        zpools.is_none_or(|zpools| zpools == (res.kind == "heartbeat/ZFS"))
+                    })
+                    .count() as u64,
+            ),
+            Err(_) => Value::Number(0),
+        };
+        match self {
+            Attribute::Hostname => Value::Text(hostname.to_string()),
+            Attribute::Reachable => Value::Bool(result.is_ok()),
+            Attribute::Resources => count(None),
+            Attribute::Zpools => count(Some(true)),
+            Attribute::Targets => count(Some(false)),
+            Attribute::Error => Value::Text(match result {
+                Ok(_) => String::new(),
+                Err(e) => e.to_string(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Parse the name of an attribute, e.g. for `--select`.
+pub fn parse_attribute(name: &str) -> Result<Attribute, String> {
+    Attribute::ALL
+        .into_iter()
+        .find(|attr| attr.name() == name)
+        .ok_or_else(|| {
+            let names: Vec<&str> = Attribute::ALL.iter().map(|attr| attr.name()).collect();
+            format!(
+                "unknown attribute '{name}': expected one of {}",
+                names.join(", ")
+            )
+        })
+}
+
+/// The value of an attribute of a host.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Value {
+    Text(String),
+    Bool(bool),
+    Number(u64),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Text(text) => write!(f, "{text}"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Number(n) => write!(f, "{n}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn holds(self, ordering: std::cmp::Ordering) -> bool {
+        use std::cmp::Ordering::*;
+        match self {
+            Op::Eq => ordering == Equal,
+            Op::Ne => ordering != Equal,
+            Op::Lt => ordering == Less,
+            Op::Le => ordering != Greater,
+            Op::Gt => ordering == Greater,
+            Op::Ge => ordering != Less,
+        }
+    }
+}
+
+/// The value that an attribute is compared with, checked against the kind of the attribute when
+/// the query is parsed.
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Text(String),
+    Bool(bool),
+    Number(f64),
+}
+
+/// A parsed `--where` query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query(Node);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Compare(Attribute, Op, Literal),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+}
+
+impl Query {
+    /// Parse a query such as `resources>=2 && hostname!=oss00`.
+    pub fn parse(query: &str) -> Result<Self, String> {
+        let tokens = tokenize(query)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let parsed = parser.or()?;
+        match parser.next() {
+            None => Ok(Query(parsed)),
+            Some(token) => Err(format!("unexpected {token} in query '{query}'")),
+        }
+    }
+
+    /// Whether this query holds for a host whose attributes have the values given by `value_of`.
+    pub fn matches(&self, value_of: &impl Fn(Attribute) -> Value) -> bool {
+        self.0.matches(value_of)
+    }
+}
+
+impl Node {
+    fn matches(&self, value_of: &impl Fn(Attribute) -> Value) -> bool {
+        match self {
+            Node::And(a, b) => a.matches(value_of) && b.matches(value_of),
+            Node::Or(a, b) => a.matches(value_of) || b.matches(value_of),
+            Node::Compare(attr, op, literal) => match (value_of(*attr), literal) {
+                (Value::Text(value), Literal::Text(literal)) => {
+                    op.holds(value.as_str().cmp(literal.as_str()))
+                }
+                (Value::Bool(value), Literal::Bool(literal)) => op.holds(value.cmp(literal)),
+                (Value::Number(value), Literal::Number(literal)) => (value as f64)
+                    .partial_cmp(literal)
+                    .is_some_and(|ordering| op.holds(ordering)),
+                // Literals are checked against the kind of their attribute when parsed:
+                _ => false,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// An attribute name, or a value which is not quoted.
+    Word(String),
+    Quoted(String),
+    Op(Op),
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "'{word}'"),
+            Token::Quoted(text) => write!(f, "\"{text}\""),
+            Token::Op(op) => write!(
+                f,
+                "'{}'",
+                match op {
+                    Op::Eq => "==",
+                    Op::Ne => "!=",
+                    Op::Lt => "<",
+                    Op::Le => "<=",
+                    Op::Gt => ">",
+                    Op::Ge => ">=",
+                }
+            ),
+            Token::And => write!(f, "'&&'"),
+            Token::Or => write!(f, "'||'"),
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+        }
+    }
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+        let mut followed_by = |next: char| chars.next_if_eq(&next).is_some();
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '&' if followed_by('&') => Token::And,
+            '|' if followed_by('|') => Token::Or,
+            '=' if followed_by('=') => Token::Op(Op::Eq),
+            '!' if followed_by('=') => Token::Op(Op::Ne),
+            '<' if followed_by('=') => Token::Op(Op::Le),
+            '>' if followed_by('=') => Token::Op(Op::Ge),
+            '<' => Token::Op(Op::Lt),
+            '>' => Token::Op(Op::Gt),
+            '\'' | '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some(next) if next == c => break,
+                        Some(next) => text.push(next),
+                        None => return Err(format!("unterminated string in query '{query}'")),
+                    }
+                }
+                Token::Quoted(text)
+            }
+            c if is_word_char(c) => {
+                let mut word = c.to_string();
+                while let Some(next) = chars.next_if(|&next| is_word_char(next)) {
+                    word.push(next);
+                }
+                Token::Word(word)
+            }
+            c => return Err(format!("unexpected '{c}' in query '{query}'")),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || "_-./:[]*".contains(c)
+}
+
+/// A recursive descent parser over the tokens of a query.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn next_is(&mut self, token: &Token) -> bool {
+        if self.tokens.get(self.pos) == Some(token) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn or(&mut self) -> Result<Node, String> {
+        let mut query = self.and()?;
+        while self.next_is(&Token::Or) {
+            query = Node::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<Node, String> {
+        let mut query = self.term()?;
+        while self.next_is(&Token::And) {
+            query = Node::And(Box::new(query), Box::new(self.term()?));
+        }
+        Ok(query)
+    }
+
+    fn term(&mut self) -> Result<Node, String> {
+        let attr = match self.next() {
+            Some(Token::Open) => {
+                let query = self.or()?;
+                if !self.next_is(&Token::Close) {
+                    return Err("missing ')' in query".to_string());
+                }
+                return Ok(query);
+            }
+            Some(Token::Word(name)) => parse_attribute(&name)?,
+            Some(token) => return Err(format!("expected an attribute, but found {token}")),
+            None => return Err("expected a comparison such as 'resources>=2'".to_string()),
+        };
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            Some(token) => {
+                return Err(format!(
+                    "expected a comparison after '{attr}', but found {token}"
+                ))
+            }
+            None => return Err(format!("expected a comparison after '{attr}'")),
+        };
+        let value = match self.next() {
+            Some(Token::Word(value) | Token::Quoted(value)) => value,
+            Some(token) => return Err(format!("expected a value, but found {token}")),
+            None => return Err(format!("expected a value to compare '{attr}' with")),
+        };
+
+        let literal = match attr.kind() {
+            Kind::Text => Literal::Text(value),
+            Kind::Bool if !matches!(op, Op::Eq | Op::Ne) => {
+                return Err(format!("'{attr}' can only be compared with == or !="));
+            }
+            Kind::Bool => match value.as_str() {
+                "true" => Literal::Bool(true),
+                "false" => Literal::Bool(false),
+                _ => return Err(format!("'{attr}' is true or false, not '{value}'")),
+            },
+            Kind::Number => match value.parse::<f64>() {
+                Ok(n) if n.is_finite() => Literal::Number(n),
+                _ => return Err(format!("'{attr}' is a number, not '{value}'")),
+            },
+        };
+        Ok(Node::Compare(attr, op, literal))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host(resources: &[(&str, bool)]) -> io::Result<config::Host> {
+        let resources = resources
+            .iter()
+            .map(|(id, zpool)| {
+                let res = if *zpool {
+                    config::Resource::new_zpool(id.to_string())
+                } else {
+                    config::Resource {
+                        kind: "lustre/Lustre".to_string(),
+                        ..Default::default()
+                    }
+                };
+                (id.to_string(), res)
+            })
+            .collect();
+        Ok(config::Host {
+            hostname: "oss00".to_string(),
+            resources,
+            fence_agent: None,
+            fence_parameters: None,
+            attributes: Default::default(),
+        })
+    }
+
+    fn matches(query: &str, hostname: &str, result: &io::Result<config::Host>) -> bool {
+        Query::parse(query)
+            .unwrap()
+            .matches(&|attr| attr.of(hostname, result.as_ref()))
+    }
+
+    #[test]
+    fn test_matches() {
+        let oss00 = host(&[("oss00e0", true), ("ost0", false), ("ost1", false)]);
+        let down: io::Result<config::Host> = Err(io::Error::other("Connection refused"));
+
+        assert!(matches("resources>=3", "oss00", &oss00));
+        assert!(!matches("resources>3", "oss00", &oss00));
+        assert!(matches("zpools==1 && targets==2", "oss00", &oss00));
+        assert!(matches("targets < 2.5", "oss00", &oss00));
+        assert!(matches("hostname==oss00", "oss00", &oss00));
+        assert!(matches(
+            "hostname>=oss00 && hostname<'oss01'",
+            "oss00",
+            &oss00
+        ));
+        assert!(matches("reachable==true", "oss00", &oss00));
+        assert!(!matches("reachable==true", "oss01", &down));
+        assert!(matches("error=='Connection refused'", "oss01", &down));
+        assert!(matches("resources==0", "oss01", &down));
+
+        // && binds more tightly than ||:
+        assert!(matches(
+            "hostname==oss01 || resources==0 && reachable==false",
+            "oss01",
+            &down
+        ));
+        assert!(!matches(
+            "(hostname==oss01 || resources==3) && reachable==true",
+            "oss01",
+            &down
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = |query: &str| Query::parse(query).unwrap_err();
+        assert_eq!(
+            err("cpu_count>=16"),
+            "unknown attribute 'cpu_count': expected one of hostname, reachable, resources, \
+             zpools, targets, error"
+        );
+        assert_eq!(
+            err("resources>=many"),
+            "'resources' is a number, not 'many'"
+        );
+        assert_eq!(
+            err("reachable==yes"),
+            "'reachable' is true or false, not 'yes'"
+        );
+        assert_eq!(
+            err("reachable>false"),
+            "'reachable' can only be compared with == or !="
+        );
+        assert_eq!(err("resources"), "expected a comparison after 'resources'");
+        assert_eq!(
+            err("resources>="),
+            "expected a value to compare 'resources' with"
+        );
+        assert_eq!(err("(resources>=1"), "missing ')' in query");
+        assert_eq!(
+            err("resources>=1 zpools>=1"),
+            "unexpected 'zpools' in query 'resources>=1 zpools>=1'"
+        );
+        assert_eq!(
+            err("hostname=oss00"),
+            "unexpected '=' in query 'hostname=oss00'"
+        );
+        assert_eq!(
+            err("hostname=='oss00"),
+            "unterminated string in query 'hostname=='oss00'"
+        );
+        assert_eq!(err(""), "expected a comparison such as 'resources>=2'");
+    }
+}
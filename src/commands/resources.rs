@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+use clap::Args;
+
+use crate::{
+    commands::{status::parse_status_name, Cli, CommandResult, HaloClient},
+    manager::http,
+};
+
+#[derive(Args, Debug, Clone)]
+pub struct ResourcesArgs {
+    /// Only list resources in this kind of status, e.g. RunningOnAway or FailedStart.
+    #[arg(long, value_name = "STATUS", value_parser = parse_status_name)]
+    status: Option<String>,
+
+    /// Only list resources in managed resource groups.
+    #[arg(long, conflicts_with = "unmanaged")]
+    managed: bool,
+
+    /// Only list resources in unmanaged resource groups.
+    #[arg(long)]
+    unmanaged: bool,
+
+    /// Only list resources whose IDs start with this prefix.
+    #[arg(long)]
+    prefix: Option<String>,
+
+    /// Print the IDs as a JSON array, for consumption by other tools.
+    #[arg(long)]
+    json: bool,
+}
+
+/// List the IDs of the resources in the cluster, one per line, e.g. to feed to other commands in
+/// a script.
+pub fn resources(cli: &Cli, args: &ResourcesArgs) -> CommandResult<()> {
+    let query = http::StatusQuery {
+        status: args.status.clone(),
+        managed: match (args.managed, args.unmanaged) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        },
+        prefix: args.prefix.clone(),
+        limit: None,
+        offset: None,
+    };
+    let ids = HaloClient::from_cli(cli)?.resource_ids(&query)?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&ids).unwrap());
+    } else {
+        for id in ids {
+            println!("{id}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,82 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+use std::collections::HashMap;
+
+use {clap::Args, reqwest::StatusCode};
+
+use crate::{commands::*, manager::http};
+
+#[derive(Args, Debug, Clone)]
+pub struct SetParamArgs {
+    /// The resource whose parameters to change.
+    #[arg(value_parser = parse_resource_id)]
+    resource: String,
+
+    /// The parameters to set, each in the form key=value.
+    #[arg(required = true)]
+    parameters: Vec<String>,
+
+    /// Restart the resource, and the resources which depend on it, so that the new parameters
+    /// take effect right away. Otherwise, they take effect the next time it is started.
+    #[arg(long)]
+    restart: bool,
+
+    /// How many seconds to wait for a restart to finish before giving up.
+    #[arg(long, default_value_t = 60)]
+    wait: u64,
+}
+
+pub fn set_param(cli: &Cli, args: &SetParamArgs) -> CommandResult<()> {
+    let mut parameters = HashMap::new();
+    for arg in args.parameters.iter() {
+        let Some((key, value)) = arg.split_once('=') else {
+            return Err(CommandError::Validation(format!(
+                "invalid parameter '{arg}': expected key=value."
+            )));
+        };
+        parameters.insert(key.to_string(), value.to_string());
+    }
+
+    let params = http::SetParamsArgs {
+        parameters,
+        restart: args.restart,
+        timeout: args.wait,
+        dry_run: cli.dry_run,
+    };
+
+    // The manager gives up on a restart after `wait` seconds, so allow that much longer for it to
+    // reply:
+    let client = HaloClient::from_cli(cli)?;
+    let response = client
+        .post(&format!("/resources/{}/params", args.resource))
+        .timeout(client.wait_timeout(args.wait))
+        .json(&params)
+        .send()
+        .handle_request_err(client.addr())?;
+
+    let done = format!("could not set parameters of '{}'", args.resource);
+    match response.status() {
+        StatusCode::OK => {}
+        StatusCode::NOT_FOUND => return Err(resource_not_found(cli, &done, &args.resource)),
+        _ => return Err(reply_error(response, &done)),
+    };
+
+    let Some(reply): Option<http::SetParamsReply> = read_reply(client.addr(), response)? else {
+        return Ok(());
+    };
+
+    // Sort the parameters so that the output is stable between invocations:
+    let mut params: Vec<_> = reply.parameters.iter().collect();
+    params.sort();
+    let params: Vec<String> = params
+        .into_iter()
+        .map(|(key, val)| format!("{key}: {val}"))
+        .collect();
+    println!("{}: [{}]", reply.id, params.join(", "));
+    if reply.restarted {
+        println!("{}: restarted", reply.id);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,312 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+use std::{
+    collections::HashMap,
+    io::{IsTerminal, Write},
+};
+
+use {
+    clap::{Args, Parser},
+    futures::{SinkExt, StreamExt},
+    tokio_tungstenite::tungstenite::Message,
+};
+
+use crate::{
+    commands::{
+        events::print_event,
+        manage::{self, ManageArgs, Report},
+        status::{self, StatusArgs},
+        *,
+    },
+    manager::http,
+};
+
+#[derive(Args, Debug, Clone)]
+pub struct ShellArgs {
+    /// Print each message from the manager as a JSON object on its own line, as it was sent, for
+    /// consumption by other tools.
+    #[arg(long)]
+    json: bool,
+}
+
+/// A command read by the shell, one per line.
+#[derive(Parser, Debug)]
+#[command(name = "halo shell", no_binary_name = true)]
+enum ShellCommand {
+    /// Print the status of the resources, as `halo status` does.
+    Status(StatusArgs),
+
+    /// Manage resource groups, as `halo manage` does.
+    Manage(ManageArgs),
+
+    /// Unmanage resource groups, as `halo unmanage` does.
+    Unmanage(ManageArgs),
+
+    /// Print changes in the state of the cluster as they happen, as `halo events` does.
+    Subscribe,
+
+    /// Stop printing changes in the state of the cluster.
+    Unsubscribe,
+
+    /// End the session, once the commands which were already sent are answered.
+    #[command(alias = "exit")]
+    Quit,
+}
+
+/// A request which the manager has not answered yet, with what is needed to report its reply.
+enum Pending {
+    Status(StatusArgs),
+    Manage(http::SetManagedBatchArgs, Report),
+    Subscribe,
+    Unsubscribe,
+}
+
+/// Open a session with the manager over a WebSocket, and send it the commands read from stdin, one
+/// per line, printing each reply, and any events, as they arrive.
+///
+/// The session ends at the end of the input, or at `quit`, once every command has been answered,
+/// or right away on Ctrl-C. The exit status is that of the last command which failed, if any.
+pub fn shell(cli: &Cli, args: &ShellArgs) -> CommandResult<()> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(runtime_error)?;
+    runtime.block_on(run(cli, args))
+}
+
+async fn run(cli: &Cli, args: &ShellArgs) -> CommandResult<()> {
+    let addr = ManagerAddress::from_cli(cli);
+    let mut socket = addr.websocket("/ws").await?;
+    let mut lines = read_lines();
+    let interactive = std::io::stdin().is_terminal();
+    let mut pending = HashMap::new();
+    let mut next_id = 0;
+    let mut input_open = true;
+    let mut result = Ok(());
+
+    prompt(interactive);
+    while input_open || !pending.is_empty() {
+        tokio::select! {
+            line = lines.recv(), if input_open => {
+                let Some(line) = line else {
+                    input_open = false;
+                    continue;
+                };
+                let command = match parse_line(&line) {
+                    Ok(Some(command)) => command,
+                    Ok(None) => {
+                        prompt(interactive && pending.is_empty());
+                        continue;
+                    }
+                    Err(e) => {
+                        result = Err(e.report());
+                        prompt(interactive && pending.is_empty());
+                        continue;
+                    }
+                };
+
+                next_id += 1;
+                let (request, waiting) = match request(cli, next_id, command) {
+                    Ok(Some(request)) => request,
+                    Ok(None) => {
+                        input_open = false;
+                        continue;
+                    }
+                    Err(e) => {
+                        result = Err(e.report());
+                        prompt(interactive && pending.is_empty());
+                        continue;
+                    }
+                };
+                // Requests are all plain data, so they always serialize:
+                let text = serde_json::to_string(&request).unwrap();
+                if let Err(e) = socket.send(Message::Text(text.into())).await {
+                    return Err(CommandError::Connection(format!(
+                        "lost connection to manager: {e}"
+                    )));
+                }
+                pending.insert(next_id, waiting);
+            }
+            message = socket.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(frame))) => {
+                        let reason = frame
+                            .map(|frame| format!(": {}", frame.reason))
+                            .filter(|reason| reason.len() > 2)
+                            .unwrap_or_default();
+                        return Err(CommandError::Connection(format!(
+                            "the manager closed the session{reason}."
+                        )));
+                    }
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => {
+                        return Err(CommandError::Connection(format!(
+                            "lost connection to manager: {e}"
+                        )));
+                    }
+                    None => {
+                        return Err(CommandError::Connection(
+                            "lost connection to manager.".to_string(),
+                        ));
+                    }
+                };
+                if let Err(e) = handle_message(cli, args, &text, &mut pending) {
+                    result = Err(e.report());
+                }
+                prompt(interactive && pending.is_empty());
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    let _ = socket.close(None).await;
+    result
+}
+
+/// Read stdin on a thread of its own, since a read from it can not be interrupted, and pass each
+/// line on to the session. Only one line is read ahead of the session, so that a large input is
+/// sent to the manager no faster than it answers.
+fn read_lines() -> tokio::sync::mpsc::Receiver<String> {
+    let (sender, receiver) = tokio::sync::mpsc::channel(1);
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if sender.blocking_send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// Prompt for the next command, if the user is typing them in.
+fn prompt(show: bool) {
+    if show {
+        print!("halo> ");
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// Parse a line of input into a command. Blank lines, and lines starting with '#', are skipped.
+fn parse_line(line: &str) -> CommandResult<Option<ShellCommand>> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    if words.first().is_none_or(|word| word.starts_with('#')) {
+        return Ok(None);
+    }
+    match ShellCommand::try_parse_from(words) {
+        Ok(command) => Ok(Some(command)),
+        Err(e) => {
+            let _ = e.print();
+            if e.use_stderr() {
+                return Err(CommandError::Reported(ExitCode::Usage));
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// The request to send to the manager for `command`, under the given `id`, or None for `quit`.
+fn request(
+    cli: &Cli,
+    id: u64,
+    command: ShellCommand,
+) -> CommandResult<Option<(http::WsRequest, Pending)>> {
+    let manage = |args: &ManageArgs, managed| -> CommandResult<_> {
+        let (params, report) = args.batch_request(cli, managed)?;
+        let request = http::WsRequest::Manage {
+            id,
+            args: params.clone(),
+        };
+        Ok((request, Pending::Manage(params, report)))
+    };
+
+    Ok(Some(match command {
+        ShellCommand::Status(args) => {
+            if args.is_watch() {
+                return Err(CommandError::Validation(
+                    "--watch is not supported here; use subscribe instead.".to_string(),
+                ));
+            }
+            let query = args.query();
+            (http::WsRequest::Status { id, query }, Pending::Status(args))
+        }
+        ShellCommand::Manage(args) => manage(&args, true)?,
+        ShellCommand::Unmanage(args) => manage(&args, false)?,
+        ShellCommand::Subscribe => (http::WsRequest::Subscribe { id }, Pending::Subscribe),
+        ShellCommand::Unsubscribe => (http::WsRequest::Unsubscribe { id }, Pending::Unsubscribe),
+        ShellCommand::Quit => return Ok(None),
+    }))
+}
+
+/// Report a message from the manager: either the reply to one of the `pending` requests, which is
+/// then no longer pending, or an event. An error is returned if the reply says that the request
+/// failed.
+fn handle_message(
+    cli: &Cli,
+    args: &ShellArgs,
+    text: &str,
+    pending: &mut HashMap<u64, Pending>,
+) -> CommandResult<()> {
+    if args.json {
+        println!("{text}");
+    }
+    let message: http::WsReply = serde_json::from_str(text)
+        .map_err(|e| CommandError::Agent(format!("could not parse message from manager: {e}")))?;
+
+    match message {
+        http::WsReply::Event { event } => {
+            if !args.json {
+                print_event(event);
+            }
+            Ok(())
+        }
+        http::WsReply::Status { id, cluster } => match pending.remove(&id) {
+            Some(Pending::Status(status_args)) if !args.json => {
+                status::print_status(cli, &status_args, &cluster)
+            }
+            _ => Ok(()),
+        },
+        http::WsReply::Manage { id, reply, .. } => match pending.remove(&id) {
+            Some(Pending::Manage(params, report)) if !args.json => {
+                manage::report_batch_reply(cli, &params, &reply, report)
+            }
+            // As with `halo manage --json`, the outcomes are left for the caller to read, but an
+            // entry which was not found is still an error:
+            _ => manage::check_found(&reply.not_found),
+        },
+        http::WsReply::Subscribed { id } | http::WsReply::Unsubscribed { id } => {
+            if let Some(waiting) = pending.remove(&id) {
+                if !args.json && cli.verbose {
+                    match waiting {
+                        Pending::Subscribe => eprintln!("Subscribed to events."),
+                        _ => eprintln!("Unsubscribed from events."),
+                    }
+                }
+            }
+            Ok(())
+        }
+        http::WsReply::Error {
+            id,
+            status,
+            message,
+            code,
+        } => {
+            if let Some(id) = id {
+                pending.remove(&id);
+            }
+            let code = match code.filter(|code| *code != http::ErrorCode::Unknown) {
+                Some(code) => code.into(),
+                None => status_exit_code(reqwest::StatusCode::from_u16(status).unwrap_or_default()),
+            };
+            // With --json, the error was already printed along with the rest of the replies:
+            match args.json {
+                true => Err(CommandError::Reported(code)),
+                false => Err(CommandError::with_code(code, message)),
+            }
+        }
+    }
+}
@@ -0,0 +1,83 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+use {clap::Args, reqwest::StatusCode};
+
+use crate::{commands::*, manager::http};
+
+#[derive(Args, Debug, Clone)]
+pub struct SimulateArgs {
+    /// Hosts to treat as down, and fenced, so that their resource groups are failed over. These
+    /// may be given in nodeset syntax, e.g., 'node[00-05]'.
+    #[arg(long, value_name = "HOSTS")]
+    fail_node: Vec<String>,
+
+    /// Hosts to treat as on standby. These may be given in nodeset syntax.
+    #[arg(long, value_name = "HOSTS")]
+    standby: Vec<String>,
+
+    /// A resource to treat as added to the cluster, as a stopped resource group of its own whose
+    /// home node is HOST.
+    #[arg(long, value_name = "ID@HOST")]
+    add_resource: Vec<String>,
+
+    /// Print the result as JSON, for consumption by other tools.
+    #[arg(long)]
+    json: bool,
+}
+
+/// Show what Manage mode would do with the resource groups if the cluster were changed as given,
+/// and where they would end up, without changing anything.
+pub fn simulate(cli: &Cli, args: &SimulateArgs) -> CommandResult<()> {
+    let hosts = |nodesets: &[String]| -> CommandResult<Option<String>> {
+        let hostnames = nodesets2hostnames(nodesets, cli.verbose)?;
+        Ok((!hostnames.is_empty()).then(|| hostnames.join(",")))
+    };
+    let query = http::SimulateQuery {
+        fail: hosts(&args.fail_node)?,
+        standby: hosts(&args.standby)?,
+        add: (!args.add_resource.is_empty()).then(|| args.add_resource.join(",")),
+    };
+
+    let client = HaloClient::from_cli(cli)?;
+    let response = client
+        .get("/simulate")
+        .query(&query)
+        .send()
+        .handle_request_err(client.addr())?;
+
+    if response.status() != StatusCode::OK {
+        return Err(reply_error(response, "could not simulate"));
+    }
+
+    let Some(reply): Option<http::SimulateReply> = read_reply(client.addr(), response)? else {
+        return Ok(());
+    };
+    if args.json {
+        let out = serde_json::to_string_pretty(&reply).map_err(std::io::Error::from)?;
+        println!("{out}");
+        return Ok(());
+    }
+
+    if reply.actions.is_empty() {
+        println!("Nothing would be done.");
+    }
+    for action in reply.actions.iter() {
+        println!("Would {action}.");
+    }
+    println!();
+    for group in reply.resource_groups.iter() {
+        let on = |host: &Option<String>| host.clone().unwrap_or_else(|| "stopped".to_string());
+        let mut line = format!("{}: ", group.id);
+        if group.from != group.to {
+            line.push_str(&format!("{} -> ", on(&group.from)));
+        }
+        line.push_str(&on(&group.to));
+        for warning in group.warnings.iter() {
+            line.push_str(&format!(" (Warning: {warning})"));
+        }
+        println!("{line}");
+    }
+
+    Ok(())
+}
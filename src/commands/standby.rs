@@ -0,0 +1,68 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+use {clap::Args, reqwest::StatusCode};
+
+use crate::{commands::*, manager::http};
+
+#[derive(Args, Debug, Clone)]
+pub struct StandbyArgs {
+    /// The hosts to put on standby. These may be given in nodeset syntax, e.g., 'node[00-05]'.
+    #[arg(required = true)]
+    hostnames: Vec<String>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct UnstandbyArgs {
+    /// The hosts to take off of standby. These may be given in nodeset syntax, e.g.,
+    /// 'node[00-05]'.
+    #[arg(required = true)]
+    hostnames: Vec<String>,
+}
+
+pub fn standby(cli: &Cli, args: &StandbyArgs) -> CommandResult<()> {
+    send_standby(cli, &args.hostnames, true)
+}
+
+pub fn unstandby(cli: &Cli, args: &UnstandbyArgs) -> CommandResult<()> {
+    send_standby(cli, &args.hostnames, false)
+}
+
+/// Put each of the hosts in `nodesets` on standby, or take them off of standby.
+///
+/// Every host is attempted even if some of them fail.
+fn send_standby(cli: &Cli, nodesets: &[String], standby: bool) -> CommandResult<()> {
+    let hostnames = nodesets2hostnames(nodesets, cli.verbose)?;
+
+    let client = HaloClient::from_cli(cli)?;
+    let command = if standby { "standby" } else { "unstandby" };
+    let params = http::HostArgs {
+        command: command.into(),
+    };
+
+    let mut errors = Vec::new();
+    for hostname in hostnames.iter() {
+        let response = client
+            .post(&format!("/hosts/{hostname}"))
+            .json(&params)
+            .send()
+            .handle_request_err(client.addr())?;
+
+        match response.status() {
+            StatusCode::OK => {
+                if cli.verbose {
+                    println!("{hostname}: {command}");
+                }
+            }
+            StatusCode::NOT_FOUND => errors.push(CommandError::NotFound(format!(
+                "could not {command} '{hostname}': host not found."
+            ))),
+            _ => errors.push(reply_error(
+                response,
+                &format!("could not {command} '{hostname}'"),
+            )),
+        }
+    }
+
+    collect_errors(errors)
+}
@@ -1,48 +1,34 @@
 // SPDX-License-Identifier: MIT
 // Copyright 2025. Triad National Security, LLC.
 
-use futures::future;
+use clap::Args;
 
-use crate::{cluster, commands::HandledResult, resource};
+use crate::{commands::*, manager::http};
 
-pub async fn start(cluster: cluster::Cluster) -> HandledResult<()> {
-    // 1. All zpools.
-    let zpool_statuses: Vec<_> = cluster
-        .zpool_resources()
-        .map(|z| async {
-            (
-                z.parameters.clone(),
-                z.start(resource::Location::Home).await,
-            )
-        })
-        .collect();
+#[derive(Args, Debug, Clone)]
+pub struct StartArgs {
+    /// The ID of the resource to start
+    #[arg(value_parser = parse_resource_id)]
+    resource: String,
 
-    let results = future::join_all(zpool_statuses).await;
-    results.iter().for_each(|r| println!("{:?}", r));
+    /// How many seconds to wait for the resource to finish starting
+    #[arg(long, default_value_t = 60)]
+    wait: u64,
 
-    // 2. Lustre MGS target.
-    let mgs = cluster.get_mgs();
-    match mgs {
-        Some(mgs) => {
-            let status = mgs.start(resource::Location::Home).await;
-            println!("{:?}", ("mgs", status));
-        }
-        None => eprintln!("Could not find mgs target."),
-    };
-
-    // 3. All remaining Lustre targets.
-    let target_statuses: Vec<_> = cluster
-        .lustre_resources_no_mgs()
-        .map(|t| async {
-            (
-                t.parameters.clone(),
-                t.start(resource::Location::Home).await,
-            )
-        })
-        .collect();
-
-    let results = future::join_all(target_statuses).await;
-    results.iter().for_each(|r| println!("{:?}", r));
+    /// First clear the failure of a resource which the manager gave up on after too many failed
+    /// attempts to start or stop it
+    #[arg(long)]
+    clear_failed: bool,
+}
 
-    Ok(())
+/// Ask the manager to start a resource on its home node, and wait for it to be running.
+pub fn start(cli: &Cli, args: &StartArgs) -> CommandResult<()> {
+    send_resource_action(
+        cli,
+        &HaloClient::from_cli(cli)?,
+        http::ResourceAction::Start,
+        &args.resource,
+        args.wait,
+        args.clear_failed,
+    )
 }
@@ -4,69 +4,466 @@
 use clap::Args;
 
 use crate::{
-    commands::{Cli, Handle, HandledResult},
+    commands::{self, Cli, CommandError, CommandResult},
     manager::http,
+    resource::ResourceStatus,
 };
 
 #[derive(Args, Debug, Clone)]
 pub struct StatusArgs {
-    #[arg(short = 'x')]
+    /// Only display resources that are in an abnormal status, that is, not running on their
+    /// home node.
+    #[arg(short = 'x', long)]
     exclude_normal: bool,
+
+    /// Only display resources in this kind of status, e.g. RunningOnAway or FailedStart.
+    #[arg(long, value_name = "STATUS", value_parser = parse_status_name)]
+    status: Option<String>,
+
+    /// Only display resources in managed resource groups.
+    #[arg(long, conflicts_with = "unmanaged")]
+    managed: bool,
+
+    /// Only display resources in unmanaged resource groups.
+    #[arg(long)]
+    unmanaged: bool,
+
+    /// Only display resources whose IDs start with this prefix.
+    #[arg(long)]
+    prefix: Option<String>,
+
+    /// Display at most this many of the resources which match the other options.
+    #[arg(long)]
+    limit: Option<usize>,
+
+    /// Skip this many of the resources which match the other options, e.g. to display the next
+    /// page after --limit.
+    #[arg(long)]
+    offset: Option<usize>,
+
+    /// Print the status as a JSON array of resources, for consumption by other tools.
+    #[arg(long)]
+    json: bool,
+
+    /// Keep polling the manager and reprinting the status until interrupted with Ctrl-C.
+    #[arg(long)]
+    watch: bool,
+
+    /// How many seconds to wait between refreshes in --watch mode.
+    #[arg(long, default_value_t = 2, requires = "watch")]
+    interval: u64,
+
+    /// In --watch mode, give up after this many consecutive failed requests to the manager. By
+    /// default, failed requests are retried forever.
+    #[arg(long, requires = "watch")]
+    max_retries: Option<u32>,
+}
+
+pub fn status(cli: &Cli, args: &StatusArgs) -> CommandResult<()> {
+    if args.watch {
+        return watch(cli, args);
+    }
+
+    let cluster = commands::HaloClient::from_cli(cli)?.status_matching(&args.query())?;
+    print_status(cli, args, &cluster)
+}
+
+impl StatusArgs {
+    /// The query which asks the manager for only the resources selected by these options.
+    pub fn query(&self) -> http::StatusQuery {
+        http::StatusQuery {
+            status: self.status.clone(),
+            managed: match (self.managed, self.unmanaged) {
+                (true, _) => Some(true),
+                (_, true) => Some(false),
+                _ => None,
+            },
+            prefix: self.prefix.clone(),
+            limit: self.limit,
+            offset: self.offset,
+        }
+    }
+
+    /// Whether to keep reprinting the status, rather than print it once.
+    pub fn is_watch(&self) -> bool {
+        self.watch
+    }
+}
+
+/// Accept the name of a kind of status, such as RunningOnAway, in any case.
+pub fn parse_status_name(arg: &str) -> Result<String, String> {
+    match ResourceStatus::NAMES
+        .iter()
+        .find(|name| name.eq_ignore_ascii_case(arg))
+    {
+        Some(name) => Ok(name.to_string()),
+        None => Err(format!(
+            "expected one of {}",
+            ResourceStatus::NAMES.join(", ")
+        )),
+    }
 }
 
-pub fn status(cli: &Cli, args: &StatusArgs) -> HandledResult<()> {
-    let addr = match &cli.socket {
-        Some(s) => s,
-        None => &crate::default_socket(),
-    };
+pub fn print_status(
+    cli: &Cli,
+    args: &StatusArgs,
+    cluster: &http::ClusterJson,
+) -> CommandResult<()> {
+    let resources = cluster.resources.iter().filter(|res| {
+        !(args.exclude_normal && StatusKind::from(res.status.as_str()) == StatusKind::Normal)
+    });
+
+    if args.json {
+        let resources: Vec<_> = resources.collect();
+        let out = serde_json::to_string_pretty(&resources).map_err(std::io::Error::from)?;
+        println!("{out}");
+        return Ok(());
+    }
 
-    let cluster = get_status(addr)?;
+    // Maintenance mode overrides everything below, so it comes first:
+    let color = cli.use_color();
+    if let Some(since) = cluster.maintenance {
+        println!("{}", format_maintenance(since, color));
+    }
 
-    for res in cluster.resources {
-        if args.exclude_normal && res.status == "Running" {
+    // Resources which are not in any group come first, followed by each group's members under a
+    // header of their own:
+    let resources: Vec<&http::ResourceJson> = resources.collect();
+    for res in resources.iter().filter(|res| res.groups.is_empty()) {
+        print_resource(cli, res, color);
+    }
+    for group in cluster.groups.iter() {
+        let members: Vec<&&http::ResourceJson> = resources
+            .iter()
+            .filter(|res| res.groups.contains(&group.name))
+            .collect();
+        if members.is_empty() {
             continue;
         }
+        println!("Group @{}:", group.name);
+        for res in members {
+            print_resource(cli, res, color);
+        }
+    }
+
+    let standby: Vec<&str> = cluster
+        .hosts
+        .iter()
+        .filter(|host| host.standby)
+        .map(|host| host.id.as_str())
+        .collect();
+    if !standby.is_empty() {
+        println!(
+            "Hosts on standby: {}",
+            StatusKind::Abnormal.paint(&standby.join(", "), color)
+        );
+    }
+
+    let fence_failed: Vec<&str> = cluster
+        .hosts
+        .iter()
+        .filter(|host| host.fence_failed)
+        .map(|host| host.id.as_str())
+        .collect();
+    if !fence_failed.is_empty() {
+        println!(
+            "Hosts whose fencing failed: {} (fencing stopped; reset with 'halo power \
+             --reset-breaker')",
+            StatusKind::Failed.paint(&fence_failed.join(", "), color)
+        );
+    }
 
-        print!("{}: ", res.status);
-        print!("{}\t", res.kind);
+    // A manager which does not support pages does not report the total:
+    if cluster.total > cluster.resources.len() {
+        println!(
+            "Showing {} of {} matching resources.",
+            cluster.resources.len(),
+            cluster.total
+        );
+    }
 
-        print!("{}\t", res.id);
+    if let Some(quorum) = &cluster.quorum {
+        println!("{}", format_quorum(quorum, color));
+    }
 
-        if cli.verbose {
-            print!(" [");
-            let mut params = res.parameters.iter();
-            if let Some((key, val)) = params.next() {
-                print!("{key}: {val}");
-                for (key, val) in params {
-                    print!("; {key}: {val}");
-                }
+    Ok(())
+}
+
+/// Format the line which says that maintenance mode has been on since `since`, in seconds since
+/// the Unix epoch. With `color`, it is colored as abnormal.
+pub fn format_maintenance(since: u64, color: bool) -> String {
+    StatusKind::Abnormal.paint(
+        &format!(
+            "MAINTENANCE MODE since {}: no resource will be started, stopped, moved, or failed over \
+             until 'halo maintenance off'.",
+            commands::history::format_time(since)
+        ),
+        color,
+    )
+}
+
+/// Format the "Quorum:" line for a cluster with a quorum policy. With `color`, a lost quorum is
+/// shown as a failure.
+pub fn format_quorum(quorum: &http::QuorumJson, color: bool) -> String {
+    format!(
+        "Quorum: {} ({} of {} hosts reachable, {} needed, tiebreak {})",
+        if quorum.has_quorum {
+            "yes".to_string()
+        } else {
+            StatusKind::Failed.paint("LOST", color)
+        },
+        quorum.reachable,
+        quorum.expected,
+        quorum.needed,
+        quorum.tiebreak
+    )
+}
+
+/// How a status that the manager reports looks to an admin.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatusKind {
+    /// Running on its home node.
+    Normal,
+
+    /// Not where it would normally be, such as stopped or failed over, but not failed either.
+    Abnormal,
+
+    /// Failed in a way that needs an admin to look into it.
+    Failed,
+}
+
+impl From<&str> for StatusKind {
+    fn from(status: &str) -> Self {
+        match status {
+            "Running" => StatusKind::Normal,
+            "Unknown" | "Error" | "FailedStart" | "FailedStop" | "FailedMigrate" => {
+                StatusKind::Failed
             }
-            print!("]");
+            _ => StatusKind::Abnormal,
         }
+    }
+}
+
+impl StatusKind {
+    /// Color `text` for this kind of status with `color`: failures in bold red, and abnormal
+    /// statuses in yellow.
+    pub fn paint(self, text: &str, color: bool) -> String {
+        match self {
+            StatusKind::Failed if color => format!("\x1b[1;31m{text}\x1b[0m"),
+            StatusKind::Abnormal if color => format!("\x1b[33m{text}\x1b[0m"),
+            _ => text.to_string(),
+        }
+    }
+}
+
+fn print_resource(cli: &Cli, res: &http::ResourceJson, color: bool) {
+    let kind = StatusKind::from(res.status.as_str());
+    print!("{}: ", kind.paint(&res.status, color));
+    print!("{}\t", res.kind);
 
-        if let Some(comment) = res.comment {
-            print!(" {comment} ");
+    print!("{}\t", res.id);
+
+    if cli.verbose {
+        // Sort the parameters so that the output is stable between invocations:
+        let mut params: Vec<_> = res.parameters.iter().collect();
+        params.sort();
+        let params: Vec<String> = params
+            .into_iter()
+            .map(|(key, val)| format!("{key}: {val}"))
+            .collect();
+        print!(" [{}]", params.join(", "));
+        print!(
+            " (home: {}, allowed: {}",
+            res.home_node,
+            res.allowed_nodes.join(", ")
+        );
+        if !res.depends_on.is_empty() {
+            print!(", depends on: {}", res.depends_on.join(", "));
         }
+        print!(")");
+    }
+
+    if let Some(comment) = &res.comment {
+        print!(" {comment} ");
+    }
+
+    for warning in res.warnings.iter() {
+        let warning = format!("Warning: {warning}");
+        print!(" ({})", StatusKind::Abnormal.paint(&warning, color));
+    }
 
-        if !res.managed {
-            print!(" (Unmanaged)");
+    if res.retries > 0 {
+        match res.next_retry {
+            Some(next) => print!(
+                " (failed {} times, next attempt at {})",
+                res.retries,
+                commands::history::format_time(next)
+            ),
+            None => print!(" (failed {} times)", res.retries),
         }
+    }
+
+    if !res.managed {
+        print!(" (Unmanaged)");
+    }
+
+    if !res.target_state.is_started() {
+        print!(" (Disabled)");
+    }
+
+    println!();
+}
+
+/// Poll the manager every `args.interval` seconds and reprint the status, until interrupted.
+///
+/// When stdout is a terminal, the screen is cleared before each refresh, and the last status is
+/// redrawn when the terminal is resized. Otherwise, successive snapshots are printed one after
+/// another, separated by a blank line.
+fn watch(cli: &Cli, args: &StatusArgs) -> CommandResult<()> {
+    let client = commands::HaloClient::from_cli(cli)?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(commands::runtime_error)?;
+
+    runtime.block_on(watch_loop(cli, args, &client))
+}
+
+async fn watch_loop(
+    cli: &Cli,
+    args: &StatusArgs,
+    client: &commands::HaloClient,
+) -> CommandResult<()> {
+    use std::io::IsTerminal;
 
+    let is_tty = std::io::stdout().is_terminal();
+    let period = std::time::Duration::from_secs(args.interval.max(1));
+    let mut interval = tokio::time::interval(period);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    let mut resized = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::window_change())
+        .map_err(|e| {
+            std::io::Error::new(
+                e.kind(),
+                format!("could not watch for terminal resizes: {e}"),
+            )
+        })?;
+
+    let mut last: Option<http::ClusterJson> = None;
+    let mut failures = 0;
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = resized.recv(), if is_tty => {
+                if let Some(cluster) = &last {
+                    redraw(cli, args, cluster, is_tty)?;
+                }
+                continue;
+            }
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+
+        // The blocking client must not be used on the runtime's own thread:
+        let result = tokio::task::spawn_blocking({
+            let client = client.clone();
+            let query = args.query();
+            move || {
+                client
+                    .try_status(&query)
+                    .map_err(|e| commands::describe_request_error(client.addr(), e))
+            }
+        })
+        .await
+        .unwrap_or_else(|e| {
+            Err(format!(
+                "request to manager at {} failed: {e}",
+                client.addr()
+            ))
+        });
+
+        match result {
+            Ok(cluster) => {
+                failures = 0;
+                if !is_tty && last.is_some() {
+                    println!();
+                }
+                redraw(cli, args, &cluster, is_tty)?;
+                last = Some(cluster);
+            }
+            Err(reason) => {
+                failures += 1;
+                if args.max_retries.is_some_and(|max| failures > max) {
+                    return Err(CommandError::Connection(format!(
+                        "{reason}; giving up after {failures} attempts."
+                    )));
+                }
+                eprintln!("Warning: {reason}; retrying in {}s.", period.as_secs());
+            }
+        }
+    }
+}
+
+/// Print a fresh snapshot of the status, replacing the previous one on a terminal.
+fn redraw(
+    cli: &Cli,
+    args: &StatusArgs,
+    cluster: &http::ClusterJson,
+    is_tty: bool,
+) -> CommandResult<()> {
+    if is_tty {
+        // Clear the screen and move the cursor to the top left corner:
+        print!("\x1b[2J\x1b[H");
+        println!(
+            "Every {}s: halo status (Ctrl-C to quit)",
+            args.interval.max(1)
+        );
         println!();
     }
+    print_status(cli, args, cluster)
+}
 
-    Ok(())
+pub fn get_status(socket: &str) -> commands::CommandResult<http::ClusterJson> {
+    commands::HaloClient::new(commands::ManagerAddress::new(socket))?.status()
 }
 
-pub fn get_status(socket: &str) -> HandledResult<http::ClusterJson> {
-    let do_request = || -> reqwest::Result<http::ClusterJson> {
-        let client = reqwest::blocking::ClientBuilder::new()
-            .unix_socket(socket)
-            .build()?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource::ResourceStatus;
 
-        let response = client.get("http://halo_manager/status").send()?;
-        response.json()
-    };
+    #[test]
+    fn test_status_kind() {
+        assert_eq!(StatusKind::from("Running"), StatusKind::Normal);
+        for status in ["Stopped", "Blocked", "Migrating", "Running (Failed Over)"] {
+            assert_eq!(StatusKind::from(status), StatusKind::Abnormal, "{status}");
+        }
+        for status in [
+            "Unknown",
+            "Error",
+            "FailedStart",
+            "FailedStop",
+            "FailedMigrate",
+        ] {
+            assert_eq!(StatusKind::from(status), StatusKind::Failed, "{status}");
+        }
 
-    do_request().handle_err(|e| eprintln!("Error making HTTP request: {e}"))
+        // The names must match the ones that the manager displays:
+        let reason = || String::new();
+        for status in [
+            ResourceStatus::Unknown(reason()),
+            ResourceStatus::Error(reason()),
+            ResourceStatus::FailedStart(reason()),
+            ResourceStatus::FailedStop(reason()),
+            ResourceStatus::FailedMigrate(reason()),
+            ResourceStatus::Blocked(reason()),
+            ResourceStatus::Stopped,
+            ResourceStatus::RunningOnAway,
+        ] {
+            let kind = StatusKind::from(status.to_string().as_str());
+            assert_eq!(kind == StatusKind::Failed, status.is_failed(), "{status}");
+            assert_ne!(kind, StatusKind::Normal, "{status}");
+        }
+    }
 }
@@ -0,0 +1,215 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+use std::time::{Duration, Instant};
+
+use clap::Args;
+
+use crate::{
+    commands::{
+        status::{format_maintenance, format_quorum, StatusKind},
+        *,
+    },
+    manager::http,
+    resource::ResourceStatus,
+};
+
+#[derive(Args, Debug, Clone)]
+pub struct TopArgs {
+    /// How many seconds to wait between refreshes.
+    #[arg(long, default_value_t = 2)]
+    interval: u64,
+
+    /// Print a single summary as a JSON object, for consumption by other tools, and exit.
+    #[arg(long)]
+    json: bool,
+}
+
+/// Print a summary of the state of the cluster as a whole, refreshing it in place until
+/// interrupted with Ctrl-C. When stdout is not a terminal, a single summary is printed instead.
+pub fn top(cli: &Cli, args: &TopArgs) -> CommandResult<()> {
+    use std::io::IsTerminal;
+
+    let client = HaloClient::from_cli(cli)?;
+
+    if args.json {
+        let out = serde_json::to_string_pretty(&client.summary()?).map_err(std::io::Error::from)?;
+        println!("{out}");
+        return Ok(());
+    }
+    if !std::io::stdout().is_terminal() {
+        print!(
+            "{}",
+            format_summary(&client.summary()?, None, cli.use_color())
+        );
+        return Ok(());
+    }
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(runtime_error)?;
+
+    runtime.block_on(top_loop(args, &client, cli.use_color()));
+    Ok(())
+}
+
+async fn top_loop(args: &TopArgs, client: &HaloClient, color: bool) {
+    let period = Duration::from_secs(args.interval.max(1));
+    let mut interval = tokio::time::interval(period);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    // The previous number of passes of the management loops, and when it was fetched, for working
+    // out how fast they are going:
+    let mut last: Option<(u64, Instant)> = None;
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = tokio::signal::ctrl_c() => return,
+        }
+
+        // The blocking client must not be used on the runtime's own thread:
+        let result = tokio::task::spawn_blocking({
+            let client = client.clone();
+            move || client.summary().map_err(|e| e.to_string())
+        })
+        .await
+        .unwrap_or_else(|e| {
+            Err(format!(
+                "request to manager at {} failed: {e}",
+                client.addr()
+            ))
+        });
+
+        match result {
+            Ok(summary) => {
+                let now = Instant::now();
+                let rate = last.map(|(iterations, then)| {
+                    summary.main_loop_iterations.saturating_sub(iterations) as f64
+                        / now.duration_since(then).as_secs_f64()
+                });
+                last = Some((summary.main_loop_iterations, now));
+
+                // Clear the screen and move the cursor to the top left corner:
+                print!("\x1b[2J\x1b[H");
+                println!("Every {}s: halo top (Ctrl-C to quit)", period.as_secs());
+                println!();
+                print!("{}", format_summary(&summary, rate, color));
+            }
+            Err(reason) => eprintln!("Warning: {reason}; retrying in {}s.", period.as_secs()),
+        }
+    }
+}
+
+/// Format `summary` as one line for each part of it, listing only the statuses that some resource
+/// is in. `rate` is how many passes per second the management loops have made since the last
+/// summary, if there was one. With `color`, statuses are colored by their kind, as are the hosts on
+/// standby.
+fn format_summary(summary: &http::SummaryJson, rate: Option<f64>, color: bool) -> String {
+    let mut out = String::new();
+    if let Some(since) = summary.maintenance {
+        out.push_str(&format_maintenance(since, color));
+        out.push('\n');
+    }
+    out.push_str(&format!(
+        "Resources: {} ({} managed, {} unmanaged)\n",
+        summary.managed + summary.unmanaged,
+        summary.managed,
+        summary.unmanaged
+    ));
+    for entry in summary.statuses.iter().filter(|entry| entry.count > 0) {
+        let status = ResourceStatus::from_name(&entry.status)
+            .map(|status| status.to_string())
+            .unwrap_or_else(|| entry.status.clone());
+        // Pad the status before coloring it, so that the escape codes do not count towards the
+        // width:
+        let kind = StatusKind::from(status.as_str());
+        out.push_str(&format!(
+            "  {}{}\n",
+            kind.paint(&format!("{status:<24}"), color),
+            entry.count
+        ));
+    }
+
+    out.push_str(&format!(
+        "Hosts: {} ({} reachable)\n",
+        summary.hosts, summary.reachable_hosts
+    ));
+    if !summary.standby.is_empty() {
+        out.push_str(&format!(
+            "Hosts on standby: {}\n",
+            StatusKind::Abnormal.paint(&summary.standby.join(", "), color)
+        ));
+    }
+    if let Some(quorum) = &summary.quorum {
+        out.push_str(&format_quorum(quorum, color));
+        out.push('\n');
+    }
+
+    out.push_str(&format!(
+        "Main loop: {} passes",
+        summary.main_loop_iterations
+    ));
+    if let Some(rate) = rate {
+        out.push_str(&format!(" ({rate:.1}/s)"));
+    }
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_summary() {
+        let summary = http::SummaryJson {
+            statuses: vec![
+                http::StatusCountJson {
+                    status: "FailedStart".to_string(),
+                    count: 1,
+                },
+                http::StatusCountJson {
+                    status: "Stopped".to_string(),
+                    count: 0,
+                },
+                http::StatusCountJson {
+                    status: "RunningOnAway".to_string(),
+                    count: 3,
+                },
+            ],
+            managed: 3,
+            unmanaged: 1,
+            hosts: 2,
+            reachable_hosts: 1,
+            standby: vec!["oss01".to_string()],
+            quorum: None,
+            main_loop_iterations: 120,
+            maintenance: None,
+        };
+
+        assert_eq!(
+            format_summary(&summary, Some(2.0), false),
+            "Resources: 4 (3 managed, 1 unmanaged)
+  FailedStart             1
+  Running (Failed Over)   3
+Hosts: 2 (1 reachable)
+Hosts on standby: oss01
+Main loop: 120 passes (2.0/s)
+"
+        );
+        assert!(format_summary(&summary, None, false).ends_with("Main loop: 120 passes\n"));
+
+        let summary = http::SummaryJson {
+            maintenance: Some(1738324800),
+            ..summary
+        };
+        assert!(format_summary(&summary, None, false).starts_with(
+            "MAINTENANCE MODE since 2025-01-31T12:00:00Z: no resource will be started, stopped, \
+             moved, or failed over until 'halo maintenance off'.\nResources: 4"
+        ));
+        assert!(format_summary(&summary, None, true)
+            .contains("  \x1b[1;31mFailedStart             \x1b[0m1\n"));
+    }
+}
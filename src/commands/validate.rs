@@ -3,21 +3,50 @@
 
 use crate::{
     cluster::Cluster,
-    commands::{handled_error, Cli, HandledResult},
+    commands::{Cli, CommandError, CommandResult, ExitCode},
+    config::Config,
 };
 
-pub fn validate(args: &Cli) -> HandledResult<()> {
-    match &args.config {
-        Some(config) => {
-            let cluster = Cluster::from_config(Some(config.to_string()))?;
-
-            cluster.print_summary();
+/// Check the config file for problems, reporting every one that is found, and print a summary of
+/// the cluster if there are none. With --verbose, also print which file each resource is defined
+/// in, for a config which is merged from several files.
+pub fn validate(args: &Cli) -> CommandResult<()> {
+    if args.config.is_empty() {
+        return Err(CommandError::Validation(
+            "must specify config file using --config.".to_string(),
+        ));
+    }
 
-            Ok(())
+    let config = Config::try_load(&args.config).map_err(CommandError::Validation)?;
+    let problems = config.validate();
+    if !problems.is_empty() {
+        let name = config.sources.files.join("\", \"");
+        for problem in problems.iter() {
+            eprintln!("{name}: {problem}");
         }
-        None => {
-            eprintln!("Must specify config file using --config.");
-            handled_error()
+        if config.sources.files.len() == 1 {
+            eprintln!(
+                "Found {} problem(s) in config file \"{name}\".",
+                problems.len()
+            );
+        } else {
+            eprintln!(
+                "Found {} problem(s) in config files \"{name}\".",
+                problems.len()
+            );
+        }
+        return Err(CommandError::Reported(ExitCode::Usage));
+    }
+
+    let cluster = Cluster::from_config(args.config.clone())?;
+
+    cluster.print_summary();
+
+    if args.verbose {
+        for (id, file) in &cluster.sources().resources {
+            println!("Resource {id} is defined in \"{file}\".");
         }
     }
+
+    Ok(())
 }
@@ -0,0 +1,80 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+use clap::Args;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::{history::format_time, Cli, CommandResult};
+
+#[derive(Args, Debug, Clone)]
+pub struct VersionArgs {
+    /// Print the details of the build as a JSON object, for consumption by other tools.
+    #[arg(long)]
+    json: bool,
+}
+
+/// The details of how this binary was built, recorded by the build script.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct BuildInfo {
+    pub version: String,
+
+    /// The git commit that the binary was built from, or "unknown".
+    pub commit: String,
+
+    /// When the binary was built, as an RFC 3339 timestamp in UTC.
+    pub built: String,
+
+    pub rustc: String,
+
+    /// "debug" or "release".
+    pub profile: String,
+
+    /// The cargo features that were enabled.
+    pub features: Vec<String>,
+}
+
+impl BuildInfo {
+    pub fn get() -> Self {
+        let features = env!("HALO_BUILD_FEATURES");
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            commit: env!("HALO_BUILD_COMMIT").to_string(),
+            built: format_time(env!("HALO_BUILD_TIME").parse().unwrap_or(0)),
+            rustc: env!("HALO_BUILD_RUSTC").to_string(),
+            profile: env!("HALO_BUILD_PROFILE").to_string(),
+            features: features
+                .split(',')
+                .filter(|feature| !feature.is_empty())
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+}
+
+/// Print the version of halo. With the global --verbose option, or --json, also print the details
+/// of the build, for matching up a report from the field with the exact binary that it came from.
+pub fn version(cli: &Cli, args: &VersionArgs) -> CommandResult<()> {
+    let info = BuildInfo::get();
+
+    if args.json {
+        let out = serde_json::to_string_pretty(&info).map_err(std::io::Error::from)?;
+        println!("{out}");
+        return Ok(());
+    }
+
+    println!("halo {}", info.version);
+    if cli.verbose {
+        let features = if info.features.is_empty() {
+            "none".to_string()
+        } else {
+            info.features.join(", ")
+        };
+        println!("commit:   {}", info.commit);
+        println!("built:    {}", info.built);
+        println!("rustc:    {}", info.rustc);
+        println!("profile:  {}", info.profile);
+        println!("features: {features}");
+    }
+
+    Ok(())
+}
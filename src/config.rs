@@ -1,21 +1,1057 @@
 // SPDX-License-Identifier: MIT
 // Copyright 2025. Triad National Security, LLC.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
 
-#[derive(Serialize, Deserialize, Debug)]
+use crate::{
+    commands::{Handle, HandledResult},
+    resource::TargetState,
+};
+
+/// Serialize a map in order of its keys, so that a config which is written out, e.g. by `halo
+/// discover`, is the same every time.
+fn sorted_map<S, V>(map: &HashMap<String, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    V: Serialize,
+{
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Config {
     pub hosts: Vec<Host>,
     pub failover_pairs: Option<Vec<Vec<String>>>,
+
+    /// Locations of the certificates and keys used when mTLS is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+
+    /// The path to the manager's unix socket, for when it is not the default. The `--socket`
+    /// option and the `HALO_SOCKET` environment variable take precedence over this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub socket: Option<String>,
+
+    /// The mode and group to give the manager's unix socket, e.g. "0660" and "halo-admins", for
+    /// when only some users should be able to send it commands. The `--socket-mode` and
+    /// `--socket-group` options take precedence over these.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub socket_mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub socket_group: Option<String>,
+
+    /// A token which requests to the manager must carry, and which the CLI sends with them. The
+    /// manager's `--auth-token` option and the CLI's `HALO_AUTH_TOKEN` environment variable take
+    /// precedence over this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_token: Option<String>,
+
+    /// A second token, which only lets requests read from the manager, e.g. for monitoring
+    /// systems. It is only accepted alongside `auth_token`. The manager's `--read-only-token`
+    /// option takes precedence over this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_only_token: Option<String>,
+
+    /// Named sets of resources which are managed and migrated together, by referring to them as
+    /// `@name` on the command line. Each member is the ID of a resource; an action on the group
+    /// applies to the resource groups that its members belong to.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub groups: BTreeMap<String, Vec<String>>,
+
+    /// How many milliseconds the manager waits between passes over the resources, for when it is
+    /// not the default. The `--poll-interval` option takes precedence over this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poll_interval: Option<u64>,
+
+    /// The most milliseconds, chosen at random, that the manager adds to each wait between passes.
+    /// The `--poll-jitter` option takes precedence over this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poll_jitter: Option<u64>,
+
+    /// How a failed start or stop of a resource is retried, for when it is not the default. The
+    /// `--retry-attempts`, `--retry-backoff`, and `--retry-backoff-max` options take precedence
+    /// over these.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_attempts: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_backoff: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_backoff_max: Option<u64>,
+
+    /// How many starts and stops of resources may run at once, for when it is not the default. The
+    /// `--action-concurrency` option takes precedence over this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action_concurrency: Option<usize>,
+
+    /// The file in which the manager records maintenance mode while it is on, for when it is not
+    /// the default. The `--maintenance-file` option takes precedence over this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintenance_file: Option<String>,
+
+    /// When set, the manager only starts, moves, and fails over resources while it can reach
+    /// enough of the hosts to have quorum.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quorum: Option<QuorumConfig>,
+
+    /// How much each attribute of a host counts towards its score, e.g. `memory_gb: 1`. When this
+    /// is set, a resource group which is not running on either host that it may start on is started
+    /// on the one with the highest score, rather than always on its home node.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub placement_weights: BTreeMap<String, f64>,
+
+    /// Sets of resources whose resource groups must run on the same host, e.g. a target and a
+    /// service which exports it. A group which is not running is started where the others in its
+    /// set are, and migrating one of them moves the others along with it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub colocation: Vec<Vec<String>>,
+
+    /// Sets of resources whose resource groups must not run on the same host, e.g. two targets
+    /// which each need most of a host's memory. The manager only puts them together when there is
+    /// nowhere else for them to run, and reports them with a warning while they are.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub anti_affinity: Vec<Vec<String>>,
+
+    /// The files that this config was read from.
+    #[serde(skip)]
+    pub sources: Sources,
+}
+
+/// The attribute of every host which the manager keeps itself, for `placement_weights`: how many
+/// resource groups are running on the host.
+pub const RUNNING_GROUPS: &str = "running_groups";
+
+/// Where a config came from, for a config which may be merged from several files.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Sources {
+    /// The files that the config was merged from, in order.
+    pub files: Vec<String>,
+
+    /// The file that each resource is defined in, by the resource's ID.
+    pub resources: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// Read and parse the config from the files at `paths`, or from the default path if none are
+    /// given. See try_load() for how several files are merged.
+    ///
+    /// Problems are reported to stderr: a parse error includes the field and line at which it
+    /// occurred.
+    pub fn load(paths: &[String]) -> HandledResult<Self> {
+        Self::try_load(paths).handle_err(|e| eprintln!("{e}"))
+    }
+
+    /// Like load(), but returns the problem instead of reporting it.
+    ///
+    /// A directory stands for the `.yaml`, `.yml`, and `.conf` files in it, in order of name.
+    /// When there is more than one file, they are merged in order, so that a later file overrides
+    /// what an earlier one sets:
+    ///
+    /// - Each host is merged with the host of the same hostname in earlier files, if any. Its
+    ///   resources are added to those of the earlier host, and its other fields override theirs.
+    /// - `failover_pairs` are added to those of earlier files.
+    /// - Any other mapping, such as `tls`, `quorum`, or `groups`, is merged key by key.
+    /// - Any other value replaces the earlier one.
+    ///
+    /// A resource ID may only be defined in one of the files.
+    ///
+    /// Environment variables are then substituted into the config, as described in
+    /// substitute_env(), so that a variable which is not set is caught by `halo validate` too.
+    pub fn try_load(paths: &[String]) -> Result<Self, String> {
+        let defaulted = paths.is_empty();
+        let default_path = [crate::default_config_path()];
+        let paths = if defaulted { &default_path[..] } else { paths };
+
+        let mut contents = Vec::new();
+        for file in config_files(paths)? {
+            let text = std::fs::read_to_string(&file).map_err(|e| {
+                if defaulted && e.kind() == std::io::ErrorKind::NotFound {
+                    format!(
+                        "No config file found at the default path \"{file}\". \
+                        Specify one with --config, or set HALO_CONFIG."
+                    )
+                } else {
+                    format!("Could not open config file \"{file}\": {e}")
+                }
+            })?;
+            contents.push((file, text));
+        }
+
+        let mut config = merge(contents)?;
+        let problems = config.substitute_env(|name| std::env::var(name).ok());
+        if !problems.is_empty() {
+            return Err(format!(
+                "Could not substitute environment variables into config \"{}\":\n{}",
+                config.sources.files.join("\", \""),
+                problems.join("\n")
+            ));
+        }
+        Ok(config)
+    }
+
+    /// Replace references to environment variables in the fields that support them: the values
+    /// of resource parameters and fence parameters, and fence agents. See substitute() for the
+    /// syntax. `lookup` gives the value of a variable, if it is set.
+    ///
+    /// Every reference that can not be resolved is returned as a problem, prefixed with the field
+    /// it is in, and the field is left as it was.
+    pub fn substitute_env(&mut self, lookup: impl Fn(&str) -> Option<String>) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut substitute_field =
+            |field: String, value: &mut String| match substitute(value, &lookup) {
+                Ok(substituted) => *value = substituted,
+                Err(e) => problems.push(format!("{field}: {e}")),
+            };
+
+        for (i, host) in self.hosts.iter_mut().enumerate() {
+            let field = format!("hosts[{i}]");
+
+            if let Some(agent) = &mut host.fence_agent {
+                substitute_field(format!("{field}.fence_agent"), agent);
+            }
+            if let Some(params) = &mut host.fence_parameters {
+                for (key, value) in params.iter_mut().collect::<BTreeMap<_, _>>() {
+                    substitute_field(format!("{field}.fence_parameters.{key}"), value);
+                }
+            }
+
+            // Sort the resources so that problems are reported in a stable order:
+            for (id, resource) in host.resources.iter_mut().collect::<BTreeMap<_, _>>() {
+                for (key, value) in resource.parameters.iter_mut().collect::<BTreeMap<_, _>>() {
+                    substitute_field(format!("{field}.resources.{id}.parameters.{key}"), value);
+                }
+            }
+        }
+        problems
+    }
+    /// Check the config for problems that parsing alone does not catch, such as a resource that
+    /// requires an unknown resource, or a host without a failover partner.
+    ///
+    /// Every problem that is found is returned, each prefixed with the field it applies to.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        let mut hostnames = HashSet::new();
+        let mut resource_ids: HashMap<&str, &str> = HashMap::new();
+
+        for (i, host) in self.hosts.iter().enumerate() {
+            let field = format!("hosts[{i}]");
+
+            if !hostnames.insert(host.hostname.as_str()) {
+                problems.push(format!(
+                    "{field}.hostname: host \"{}\" is listed more than once",
+                    host.hostname
+                ));
+            }
+            if let Some(problem) = check_nodeset_name(&host.hostname) {
+                problems.push(format!("{field}.hostname: {problem}"));
+            }
+
+            if let Some(agent) = &host.fence_agent {
+                if let Err(e) =
+                    crate::host::FenceAgent::try_from_params(agent, &host.fence_parameters)
+                {
+                    problems.push(format!("{field}.fence_agent: {e}"));
+                }
+            }
+
+            // Sort the resources so that problems are reported in a stable order:
+            let mut ids: Vec<&String> = host.resources.keys().collect();
+            ids.sort();
+            for id in ids {
+                let resource = &host.resources[id];
+                let field = format!("{field}.resources.{id}");
+
+                match resource_ids.get(id.as_str()) {
+                    Some(other_host) => problems.push(format!(
+                        "{field}: resource ID \"{id}\" is also used on host \"{other_host}\""
+                    )),
+                    None => {
+                        resource_ids.insert(id, &host.hostname);
+                    }
+                }
+                if let Some(problem) = check_nodeset_name(id) {
+                    problems.push(format!("{field}: {problem}"));
+                }
+
+                match &resource.requires {
+                    Some(parent) if !host.resources.contains_key(parent) => problems.push(format!(
+                        "{field}.requires: unknown resource \"{parent}\" on host \"{}\"",
+                        host.hostname
+                    )),
+                    Some(_) if requires_cycle(&host.resources, id) => {
+                        problems.push(format!("{field}.requires: resource depends on itself"))
+                    }
+                    Some(_) => {}
+                    None if resource.kind != "heartbeat/ZFS" => problems.push(format!(
+                        "{field}.requires: a {} resource must require another resource; only \
+                        heartbeat/ZFS resources can stand alone",
+                        resource.kind
+                    )),
+                    None => {}
+                }
+
+                if resource.start_timeout == Some(0) {
+                    problems.push(format!(
+                        "{field}.start_timeout: the timeout must be at least 1 second"
+                    ));
+                }
+                if resource.stop_timeout == Some(0) {
+                    problems.push(format!(
+                        "{field}.stop_timeout: the timeout must be at least 1 second"
+                    ));
+                }
+            }
+        }
+
+        if let Some(pairs) = &self.failover_pairs {
+            let mut paired = HashSet::new();
+            for (i, pair) in pairs.iter().enumerate() {
+                if pair.len() != 2 {
+                    problems.push(format!(
+                        "failover_pairs[{i}]: a failover pair must have exactly two hosts, not {}",
+                        pair.len()
+                    ));
+                }
+                for hostname in pair {
+                    if !hostnames.contains(hostname.as_str()) {
+                        problems.push(format!("failover_pairs[{i}]: unknown host \"{hostname}\""));
+                    }
+                    if !paired.insert(hostname.as_str()) {
+                        problems.push(format!(
+                            "failover_pairs[{i}]: host \"{hostname}\" is in more than one pair"
+                        ));
+                    }
+                }
+            }
+            for (i, host) in self.hosts.iter().enumerate() {
+                if !paired.contains(host.hostname.as_str()) {
+                    problems.push(format!(
+                        "hosts[{i}].hostname: host \"{}\" is not in any failover pair",
+                        host.hostname
+                    ));
+                }
+            }
+        }
+
+        if self.poll_interval == Some(0) {
+            problems.push("poll_interval: the poll interval must be at least 1 ms".to_string());
+        }
+        if self.retry_attempts == Some(0) {
+            problems.push("retry_attempts: there must be at least 1 attempt".to_string());
+        }
+        if self.action_concurrency == Some(0) {
+            problems
+                .push("action_concurrency: at least 1 action must be allowed at once".to_string());
+        }
+        if let Some(Err(e)) = self
+            .socket_mode
+            .as_deref()
+            .map(crate::manager::parse_socket_mode)
+        {
+            problems.push(format!("socket_mode: {e}"));
+        }
+        if self.auth_token.as_deref() == Some("") {
+            problems.push("auth_token: the token must not be empty".to_string());
+        }
+        match (&self.auth_token, self.read_only_token.as_deref()) {
+            (_, Some("")) => {
+                problems.push("read_only_token: the token must not be empty".to_string())
+            }
+            (None, Some(_)) => problems.push(
+                "read_only_token: a read-only token needs an auth_token for full access"
+                    .to_string(),
+            ),
+            _ => {}
+        }
+
+        problems.extend(self.placement_problems());
+        problems.extend(self.constraint_problems());
+        problems.extend(self.dependency_problems());
+        problems.extend(self.group_problems());
+        problems.extend(self.quorum_problems());
+        problems.extend(self.parameter_problems());
+
+        problems
+    }
+
+    /// Check the `colocation` and `anti_affinity` constraints: each must name at least two
+    /// resources in the config, the resource groups in a colocation set must have a host that they
+    /// may all run on, and no two resources may need to be both together and apart, whether by the
+    /// constraints or by being in the same resource group. The manager checks these when it
+    /// starts, since it could never satisfy constraints which contradict each other.
+    pub fn constraint_problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        // The root resource of the resource group that each resource is in, and the hosts that
+        // each resource group may run on, by its root:
+        let mut roots: HashMap<&str, &str> = HashMap::new();
+        let mut hosts: HashMap<&str, Vec<&str>> = HashMap::new();
+        for host in self.hosts.iter() {
+            for id in host.resources.keys() {
+                let mut root = id.as_str();
+                // A cycle or a missing resource here is reported by validate():
+                for _ in 0..host.resources.len() {
+                    match host.resources[root].requires.as_deref() {
+                        Some(parent) if host.resources.contains_key(parent) => root = parent,
+                        _ => break,
+                    }
+                }
+                roots.insert(id, root);
+            }
+            for (id, resource) in host.resources.iter() {
+                if resource.requires.is_none() {
+                    let allowed = match &resource.allowed_nodes {
+                        Some(allowed) => allowed.iter().map(|name| name.as_str()).collect(),
+                        None => [
+                            Some(host.hostname.as_str()),
+                            self.failover_partner(&host.hostname),
+                        ]
+                        .into_iter()
+                        .flatten()
+                        .collect(),
+                    };
+                    hosts.insert(id, allowed);
+                }
+            }
+        }
+        let hosts_of = |id: &str| hosts.get(roots[id]).map(Vec::as_slice).unwrap_or_default();
+
+        // The resource groups which must run together, by their roots; see representative():
+        let mut together: HashMap<&str, &str> = HashMap::new();
+
+        let mut check_members = |field: &str, members: &[String]| -> Vec<String> {
+            if members.len() < 2 {
+                problems.push(format!(
+                    "{field}: a constraint must name at least two resources"
+                ));
+            }
+            let mut known = Vec::new();
+            for member in members.iter() {
+                if roots.contains_key(member.as_str()) {
+                    known.push(member.clone());
+                } else {
+                    problems.push(format!("{field}: unknown resource \"{member}\""));
+                }
+            }
+            known
+        };
+
+        let mut colocated = Vec::new();
+        for (i, members) in self.colocation.iter().enumerate() {
+            let field = format!("colocation[{i}]");
+            colocated.push((field.clone(), check_members(&field, members)));
+        }
+        let mut anti_affine = Vec::new();
+        for (i, members) in self.anti_affinity.iter().enumerate() {
+            let field = format!("anti_affinity[{i}]");
+            anti_affine.push((field.clone(), check_members(&field, members)));
+        }
+
+        for (field, members) in colocated.iter() {
+            for (j, a) in members.iter().enumerate() {
+                for b in members[j + 1..].iter() {
+                    if !hosts_of(a).iter().any(|host| hosts_of(b).contains(host)) {
+                        problems.push(format!(
+                            "{field}: {a} and {b} can never run on the same host"
+                        ));
+                    }
+                    let (a, b) = (
+                        representative(&together, roots[a.as_str()]),
+                        representative(&together, roots[b.as_str()]),
+                    );
+                    together.insert(a, b);
+                }
+            }
+        }
+
+        for (field, members) in anti_affine.iter() {
+            for (j, a) in members.iter().enumerate() {
+                for b in members[j + 1..].iter() {
+                    let (a_root, b_root) = (roots[a.as_str()], roots[b.as_str()]);
+                    if a_root == b_root {
+                        problems.push(format!(
+                            "{field}: {a} and {b} are in the same resource group, so they always \
+                            run together"
+                        ));
+                    } else if representative(&together, a_root) == representative(&together, b_root)
+                    {
+                        problems.push(format!(
+                            "{field}: {a} and {b} must also run together, by the colocation \
+                            constraints"
+                        ));
+                    }
+                }
+            }
+        }
+
+        problems
+    }
+
+    /// The failover partner of the host `hostname`, if it is in a pair. Pairs of the wrong size
+    /// are reported by validate(), and are ignored here.
+    fn failover_partner(&self, hostname: &str) -> Option<&str> {
+        self.failover_pairs
+            .iter()
+            .flatten()
+            .find(|pair| pair.len() == 2 && pair.iter().any(|name| name == hostname))
+            .map(|pair| {
+                if pair[0] == hostname {
+                    pair[1].as_str()
+                } else {
+                    pair[0].as_str()
+                }
+            })
+    }
+
+    /// Check that the quorum policy, if any, expects at least one host. The manager checks this
+    /// when it starts, since it could never have quorum otherwise.
+    pub fn quorum_problems(&self) -> Vec<String> {
+        match &self.quorum {
+            Some(quorum) if quorum.expected_hosts == Some(0) => {
+                vec!["quorum.expected_hosts: a cluster must expect at least one host".to_string()]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Check that every group has a usable name, and only members which are resources in the
+    /// config. The manager checks these when it starts, so that an action on a group never
+    /// silently skips a member.
+    pub fn group_problems(&self) -> Vec<String> {
+        let resource_ids: HashSet<&str> = self
+            .hosts
+            .iter()
+            .flat_map(|host| host.resources.keys())
+            .map(|id| id.as_str())
+            .collect();
+        let mut problems = Vec::new();
+
+        for (name, members) in self.groups.iter() {
+            let field = format!("groups.{name}");
+            if name.is_empty() || name.contains(['@', ',', ' ']) {
+                problems.push(format!(
+                    "{field}: \"{name}\" is not a valid group name; it must not be empty or \
+                    contain '@', ',', or spaces"
+                ));
+            }
+            if members.is_empty() {
+                problems.push(format!("{field}: a group must have at least one member"));
+            }
+            for member in members.iter() {
+                if !resource_ids.contains(member.as_str()) {
+                    problems.push(format!("{field}: unknown resource \"{member}\""));
+                }
+            }
+        }
+
+        problems
+    }
+
+    /// Check the parameters of every resource against the schema for its kind, if halo knows
+    /// about that kind; see agent::Schema. The manager checks these when it starts, so that a
+    /// resource which its agent could not run is caught before it is ever started.
+    pub fn parameter_problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (i, host) in self.hosts.iter().enumerate() {
+            let mut ids: Vec<&String> = host.resources.keys().collect();
+            ids.sort();
+            for id in ids {
+                let resource = &host.resources[id];
+                let Some(schema) = crate::agent::Schema::of(&resource.kind) else {
+                    continue;
+                };
+                for problem in schema.check(&resource.parameters) {
+                    problems.push(format!("hosts[{i}].resources.{id}.parameters.{problem}"));
+                }
+            }
+        }
+        problems
+    }
+
+    /// Check the `depends_on` of every resource: each dependency must be a resource in the config,
+    /// and following dependencies, along with `requires`, must never lead back to where it
+    /// started. The manager checks these when it starts, since it could never start a resource
+    /// which depends on itself.
+    pub fn dependency_problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        // The resources that each resource must wait for before it is started:
+        let mut graph: HashMap<&str, Vec<&str>> = HashMap::new();
+        for host in self.hosts.iter() {
+            for (id, resource) in host.resources.iter() {
+                let deps = graph.entry(id).or_default();
+                deps.extend(resource.depends_on.iter().map(|dep| dep.as_str()));
+                deps.extend(resource.requires.as_deref());
+            }
+        }
+
+        for (i, host) in self.hosts.iter().enumerate() {
+            let mut ids: Vec<&String> = host.resources.keys().collect();
+            ids.sort();
+            for id in ids {
+                let resource = &host.resources[id];
+                let field = format!("hosts[{i}].resources.{id}.depends_on");
+
+                for dep in resource.depends_on.iter() {
+                    if dep == id {
+                        problems.push(format!("{field}: resource depends on itself"));
+                    } else if !graph.contains_key(dep.as_str()) {
+                        problems.push(format!("{field}: unknown resource \"{dep}\""));
+                    }
+                }
+                // A cycle through `requires` alone is reported by validate():
+                if resource.depends_on.is_empty() || resource.depends_on.iter().any(|dep| dep == id)
+                {
+                    continue;
+                }
+                if let Some(cycle) = dependency_cycle(&graph, id) {
+                    problems.push(format!("{field}: dependency cycle: {}", cycle.join(" -> ")));
+                }
+            }
+        }
+
+        problems
+    }
+
+    /// Check the `home_node` and `allowed_nodes` of every resource, and the attributes that hosts
+    /// are scored by. The manager checks these when it starts, since a mistake in them could
+    /// otherwise lead it to run resources on the wrong host.
+    pub fn placement_problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        for (i, host) in self.hosts.iter().enumerate() {
+            for (name, value) in host.attributes.iter() {
+                if name == RUNNING_GROUPS {
+                    problems.push(format!(
+                        "hosts[{i}].attributes.{name}: this is counted by the manager, and can not \
+                        be set"
+                    ));
+                } else if !value.is_finite() {
+                    problems.push(format!(
+                        "hosts[{i}].attributes.{name}: {value} is not a number"
+                    ));
+                }
+            }
+        }
+        for (name, weight) in self.placement_weights.iter() {
+            if !weight.is_finite() {
+                problems.push(format!(
+                    "placement_weights.{name}: {weight} is not a number"
+                ));
+            } else if name != RUNNING_GROUPS
+                && !self
+                    .hosts
+                    .iter()
+                    .any(|host| host.attributes.contains_key(name))
+            {
+                problems.push(format!(
+                    "placement_weights.{name}: no host has the attribute \"{name}\""
+                ));
+            }
+        }
+
+        for (i, host) in self.hosts.iter().enumerate() {
+            let partner = self.failover_partner(&host.hostname);
+
+            let mut ids: Vec<&String> = host.resources.keys().collect();
+            ids.sort();
+            for id in ids {
+                problems.extend(
+                    check_placement(&host.resources[id], &host.hostname, partner)
+                        .into_iter()
+                        .map(|problem| format!("hosts[{i}].resources.{id}.{problem}")),
+                );
+            }
+        }
+
+        problems
+    }
+}
+
+/// Hosts and resources are named with nodeset syntax on the command line, so a name which does not
+/// parse as a nodeset of just that one name could not be referred to.
+fn check_nodeset_name(name: &str) -> Option<String> {
+    match name.parse::<nodeset::NodeSet>() {
+        Ok(nodeset) if nodeset.len() == 1 && nodeset.iter().any(|n| n == name) => None,
+        Ok(_) => Some(format!(
+            "\"{name}\" can not be referred to in nodeset syntax"
+        )),
+        Err(e) => Some(format!("\"{name}\" is not valid nodeset syntax: {e}")),
+    }
+}
+
+/// Check the `home_node` and `allowed_nodes` of a resource listed under the host `hostname`, whose
+/// failover partner is `partner`. Each problem is prefixed with the name of the field.
+fn check_placement(resource: &Resource, hostname: &str, partner: Option<&str>) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if resource.requires.is_some() {
+        for (field, set) in [
+            ("home_node", resource.home_node.is_some()),
+            ("allowed_nodes", resource.allowed_nodes.is_some()),
+            ("managed", resource.managed.is_some()),
+        ] {
+            if set {
+                problems.push(format!(
+                    "{field}: this can only be set on the root resource of a resource group"
+                ));
+            }
+        }
+        return problems;
+    }
+
+    let can_run_on = |name: &str| name == hostname || Some(name) == partner;
+    let candidates = match partner {
+        Some(partner) => format!("\"{hostname}\" or its failover partner \"{partner}\""),
+        None => format!("\"{hostname}\""),
+    };
+
+    if let Some(home) = &resource.home_node {
+        if !can_run_on(home) {
+            problems.push(format!("home_node: \"{home}\" is not {candidates}"));
+        }
+    }
+
+    if let Some(allowed) = &resource.allowed_nodes {
+        for name in allowed.iter().filter(|name| !can_run_on(name)) {
+            problems.push(format!("allowed_nodes: \"{name}\" is not {candidates}"));
+        }
+        let home = resource.home_node.as_deref().unwrap_or(hostname);
+        if !allowed.iter().any(|name| name == home) {
+            problems.push(format!(
+                "allowed_nodes: the home node \"{home}\" must be allowed"
+            ));
+        }
+    }
+
+    problems
+}
+
+/// Of the resource groups which must run together with the one whose root is `root`, the one
+/// which stands for them all. Each group in `together` points towards another that it must run
+/// with, and the representative is the one at the end.
+fn representative<'a>(together: &HashMap<&'a str, &'a str>, mut root: &'a str) -> &'a str {
+    while let Some(next) = together.get(root).filter(|next| **next != root) {
+        root = next;
+    }
+    root
+}
+
+/// Whether following the `requires` chain from the resource `id` leads back to it.
+fn requires_cycle(resources: &HashMap<String, Resource>, id: &str) -> bool {
+    let mut current = id;
+    // A chain without a cycle can visit each resource at most once:
+    for _ in 0..resources.len() {
+        match resources.get(current).and_then(|r| r.requires.as_deref()) {
+            Some(parent) if parent == id => return true,
+            Some(parent) => current = parent,
+            None => return false,
+        }
+    }
+    false
+}
+
+/// Find a path of dependencies in `graph` which leads from the resource `id` back to it, if there
+/// is one. The path starts and ends with `id`.
+fn dependency_cycle<'a>(
+    graph: &HashMap<&'a str, Vec<&'a str>>,
+    id: &'a str,
+) -> Option<Vec<&'a str>> {
+    // A depth-first search, where `path` holds the resources from `id` to the one being visited:
+    let mut path = vec![id];
+    let mut next: Vec<std::slice::Iter<&str>> = vec![graph.get(id)?.iter()];
+    let mut visited = HashSet::new();
+
+    while let Some(deps) = next.last_mut() {
+        let Some(&dep) = deps.next() else {
+            next.pop();
+            path.pop();
+            continue;
+        };
+        if dep == id {
+            path.push(id);
+            return Some(path);
+        }
+        if !visited.insert(dep) {
+            continue;
+        }
+        if let Some(deps) = graph.get(dep) {
+            path.push(dep);
+            next.push(deps.iter());
+        }
+    }
+
+    None
+}
+
+/// Replace each `${VAR}` in `value` with the value of the environment variable VAR, as given by
+/// `lookup`. In the form `${VAR:-default}`, `default` is used when VAR is not set or is empty;
+/// otherwise, it is an error for VAR not to be set. `$$` stands for a literal `$`, and a `$` which
+/// is not followed by `{` is left as it is.
+fn substitute(value: &str, lookup: &impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut substituted = String::new();
+    let mut rest = value;
+    while let Some(i) = rest.find('$') {
+        substituted.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            substituted.push('$');
+            rest = after;
+            continue;
+        }
+        let Some(after) = rest.strip_prefix('{') else {
+            substituted.push('$');
+            continue;
+        };
+        let Some(end) = after.find('}') else {
+            return Err(
+                "\"${\" is not closed with \"}\"; write \"$$\" for a literal \"$\"".to_string(),
+            );
+        };
+
+        let (name, default) = match after[..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&after[..end], None),
+        };
+        let is_name = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_name {
+            return Err(format!(
+                "\"{name}\" is not the name of an environment variable"
+            ));
+        }
+        match (
+            lookup(name).filter(|value| !value.is_empty() || default.is_none()),
+            default,
+        ) {
+            (Some(value), _) => substituted.push_str(&value),
+            (None, Some(default)) => substituted.push_str(default),
+            (None, None) => return Err(format!("environment variable {name} is not set")),
+        }
+        rest = &after[end + 1..];
+    }
+    substituted.push_str(rest);
+    Ok(substituted)
+}
+
+/// The config files given by `paths`: each path in order, with a directory standing for the
+/// `.yaml`, `.yml`, and `.conf` files in it, in order of name.
+fn config_files(paths: &[String]) -> Result<Vec<String>, String> {
+    let mut files = Vec::new();
+    for path in paths {
+        if !std::path::Path::new(path).is_dir() {
+            files.push(path.clone());
+            continue;
+        }
+
+        let entries = std::fs::read_dir(path)
+            .map_err(|e| format!("Could not open config directory \"{path}\": {e}"))?;
+        let mut found = Vec::new();
+        for entry in entries {
+            let entry =
+                entry.map_err(|e| format!("Could not open config directory \"{path}\": {e}"))?;
+            let file = entry.path();
+            let is_config = file
+                .extension()
+                .is_some_and(|ext| ext == "yaml" || ext == "yml" || ext == "conf");
+            if is_config && !file.is_dir() {
+                found.push(file.to_string_lossy().into_owned());
+            }
+        }
+        if found.is_empty() {
+            return Err(format!(
+                "Config directory \"{path}\" has no .yaml, .yml, or .conf files in it."
+            ));
+        }
+        found.sort();
+        files.extend(found);
+    }
+    Ok(files)
+}
+
+/// Parse a config from the contents of the files that it is made of, given with the name of each
+/// file, merging them in order as described in Config::try_load().
+fn merge(contents: Vec<(String, String)>) -> Result<Config, String> {
+    // A single file is parsed as it is, so that a parse error gives the line that it occurred on:
+    if let [(file, text)] = &contents[..] {
+        let mut config: Config =
+            serde_yaml::from_str(text).map_err(|e| parse_error(file, text, e))?;
+        config.sources.files = vec![file.clone()];
+        for host in &config.hosts {
+            for id in host.resources.keys() {
+                config.sources.resources.insert(id.clone(), file.clone());
+            }
+        }
+        return Ok(config);
+    }
+
+    let mut sources = Sources::default();
+    let mut merged = Mapping::new();
+    for (file, text) in contents {
+        let value: Value = serde_yaml::from_str(&text).map_err(|e| parse_error(&file, &text, e))?;
+        let Value::Mapping(mapping) = value else {
+            return Err(format!(
+                "Could not parse config file \"{file}\": expected a mapping of settings"
+            ));
+        };
+
+        // Each of the file's resources must not have been defined by an earlier file:
+        let mut ids = BTreeMap::new();
+        for id in resource_ids(&mapping) {
+            if let Some(earlier) = sources.resources.get(&id) {
+                return Err(format!(
+                    "Could not merge config file \"{file}\": resource \"{id}\" is already \
+                    defined in \"{earlier}\""
+                ));
+            }
+            ids.insert(id, file.clone());
+        }
+        sources.resources.extend(ids);
+
+        merge_mapping(&mut merged, mapping);
+        sources.files.push(file);
+    }
+
+    let mut config: Config = serde_yaml::from_value(Value::Mapping(merged)).map_err(|e| {
+        format!(
+            "Could not parse config merged from \"{}\": {e}",
+            sources.files.join("\", \"")
+        )
+    })?;
+    config.sources = sources;
+    Ok(config)
+}
+
+/// The error for a config file which could not be parsed. YAML only has `#` comments, and a JSON5
+/// comment is read as part of a key or value instead, which gives an error that does not point at
+/// it, so the error says so when the file looks like it has one.
+fn parse_error(file: &str, text: &str, e: serde_yaml::Error) -> String {
+    let json5_comment = text.lines().any(|line| {
+        let line = line.trim_start();
+        ["//", "/*"]
+            .iter()
+            .any(|start| line.starts_with(start) || line.contains(&format!(" {start}")))
+    });
+    if json5_comment {
+        format!(
+            "Could not parse config file \"{file}\": {e} (comments must start with '#'; \
+            '//' and '/* */' comments are not accepted)"
+        )
+    } else {
+        format!("Could not parse config file \"{file}\": {e}")
+    }
+}
+
+/// The IDs of the resources under the hosts of a config which has not been parsed yet.
+fn resource_ids(config: &Mapping) -> Vec<String> {
+    let Some(Value::Sequence(hosts)) = config.get("hosts") else {
+        return Vec::new();
+    };
+    hosts
+        .iter()
+        .filter_map(|host| host.get("resources")?.as_mapping())
+        .flat_map(|resources| resources.keys().filter_map(Value::as_str))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Merge the top level of a config into `base`, as described in Config::try_load().
+fn merge_mapping(base: &mut Mapping, overlay: Mapping) {
+    for (key, value) in overlay {
+        match (key.as_str(), base.get_mut(&key), value) {
+            (Some("hosts"), Some(Value::Sequence(hosts)), Value::Sequence(overlay)) => {
+                for host in overlay {
+                    merge_host(hosts, host);
+                }
+            }
+            (Some("failover_pairs"), Some(Value::Sequence(pairs)), Value::Sequence(overlay)) => {
+                pairs.extend(overlay);
+            }
+            (_, Some(existing), value) => merge_value(existing, value),
+            (_, None, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Merge `host` into the host of the same hostname in `hosts`, or add it if there is none.
+fn merge_host(hosts: &mut Vec<Value>, host: Value) {
+    let position = host.get("hostname").and_then(|hostname| {
+        hosts
+            .iter()
+            .position(|h| h.get("hostname") == Some(hostname))
+    });
+    let (Some(Value::Mapping(existing)), Value::Mapping(host)) =
+        (position.map(|i| &mut hosts[i]), &host)
+    else {
+        hosts.push(host);
+        return;
+    };
+
+    for (key, value) in host.clone() {
+        match (key.as_str(), existing.get_mut(&key), value) {
+            (Some("resources"), Some(Value::Mapping(resources)), Value::Mapping(overlay)) => {
+                resources.extend(overlay);
+            }
+            (_, Some(existing), value) => merge_value(existing, value),
+            (_, None, value) => {
+                existing.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Merge `overlay` into `base`: key by key if both are mappings, or else by replacing `base`.
+fn merge_value(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_value(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Paths to the files used for mTLS. Any path that is not set falls back to its environment
+/// variable (e.g., `HALO_CA_CERT`), and then to a default location in /etc/halo/.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct TlsConfig {
+    pub ca_cert: Option<String>,
+    pub server_cert: Option<String>,
+    pub server_key: Option<String>,
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// The quorum policy of the cluster.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct QuorumConfig {
+    /// How many hosts the cluster is expected to have. Defaults to the number of hosts in the
+    /// config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_hosts: Option<usize>,
+
+    /// Whether exactly half of the expected hosts are enough for quorum.
+    #[serde(default)]
+    pub tiebreak: crate::manager::quorum::Tiebreak,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Host {
     pub hostname: String,
 
     /// Resources should be given a unique identifier to identify them in this hashmap.
+    #[serde(serialize_with = "sorted_map")]
     pub resources: HashMap<String, Resource>,
 
     /// Name of the fence agent binary to use for fencing this host.
@@ -23,19 +1059,68 @@ pub struct Host {
 
     /// Fence parameters for this host.
     pub fence_parameters: Option<HashMap<String, String>>,
+
+    /// Numbers describing this host, e.g. `memory_gb: 512`, for `placement_weights` to score it
+    /// by. An attribute which is not given counts as 0.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attributes: BTreeMap<String, f64>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 pub struct Resource {
     /// An OCF Resource Agent identifier, such as "heartbeat/ZFS" or "lustre/Lustre"
     pub kind: String,
 
     /// The resource parameters, which are to be passed to the OCF Resource Agent.
+    #[serde(serialize_with = "sorted_map")]
     pub parameters: HashMap<String, String>,
 
     /// Each resource is allowed to specify a single dependency. The named resource must be started
     /// before this one.
     pub requires: Option<String>,
+
+    /// Other resources which must be running before this one is started, and which are only
+    /// stopped after it. Unlike `requires`, these may be in other resource groups, on any host.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+
+    /// The host that this resource group prefers to run on: either the host that it is listed
+    /// under (the default), or that host's failover partner. When this is set, the manager also
+    /// moves the resource group back to this host once it is healthy again after a failover.
+    ///
+    /// This can only be set on the root resource of a resource group.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub home_node: Option<String>,
+
+    /// The hosts that this resource group may be started on, which must include its home node. By
+    /// default, it may run on either host in its failover pair.
+    ///
+    /// This can only be set on the root resource of a resource group.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_nodes: Option<Vec<String>>,
+
+    /// Whether the manager should keep this resource running (the default) or stopped, while it
+    /// is managed. This can be changed while the manager runs, with `halo enable` and `halo
+    /// disable`.
+    #[serde(default, skip_serializing_if = "TargetState::is_started")]
+    pub target_state: TargetState,
+
+    /// Whether the manager manages this resource group from the moment it starts (the default),
+    /// or leaves it unmanaged until `halo manage` is run for it.
+    ///
+    /// This can only be set on the root resource of a resource group.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub managed: Option<bool>,
+
+    /// How many seconds the resource agent may take to start this resource before the manager
+    /// gives up on it and marks it as failed to start. Defaults to 300.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_timeout: Option<u64>,
+
+    /// How many seconds the resource agent may take to stop this resource before the manager
+    /// gives up on it and marks it as failed to stop. Defaults to 300.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_timeout: Option<u64>,
 }
 
 impl Resource {
@@ -43,7 +1128,7 @@ impl Resource {
         Self {
             kind: "heartbeat/ZFS".to_string(),
             parameters: HashMap::from([("pool".to_string(), pool)]),
-            requires: None,
+            ..Default::default()
         }
     }
 
@@ -82,6 +1167,500 @@ impl Resource {
                 ("kind".to_string(), kind.to_string()),
             ]),
             requires: Some(zpool.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let zpool = Resource::new_zpool("pool0".to_string());
+        let mut ost = Resource::new_zpool("unused".to_string());
+        ost.kind = "lustre/Lustre".to_string();
+        ost.requires = Some("missing".to_string());
+        let mut placed = Resource::new_zpool("pool1".to_string());
+        placed.home_node = Some("oss01".to_string());
+        placed.allowed_nodes = Some(vec!["oss00".to_string()]);
+        placed.stop_timeout = Some(0);
+
+        let config = Config {
+            hosts: vec![
+                Host {
+                    hostname: "oss00".to_string(),
+                    resources: HashMap::from([
+                        ("zpool0".to_string(), zpool.clone()),
+                        ("zpool1".to_string(), placed),
+                        ("ost0".to_string(), ost),
+                    ]),
+                    fence_agent: Some("no_such_agent".to_string()),
+                    fence_parameters: None,
+                    attributes: BTreeMap::new(),
+                },
+                Host {
+                    hostname: "oss01".to_string(),
+                    resources: HashMap::from([("zpool0".to_string(), zpool)]),
+                    fence_agent: Some("powerman".to_string()),
+                    fence_parameters: None,
+                    attributes: BTreeMap::new(),
+                },
+            ],
+            failover_pairs: Some(vec![vec!["oss00".to_string(), "oss02".to_string()]]),
+            groups: BTreeMap::from([
+                (
+                    "pools".to_string(),
+                    vec!["zpool0".to_string(), "missing".to_string()],
+                ),
+                ("@bad".to_string(), Vec::new()),
+            ]),
+            poll_interval: Some(0),
+            retry_attempts: Some(0),
+            action_concurrency: Some(0),
+            socket_mode: Some("999".to_string()),
+            auth_token: Some(String::new()),
+            read_only_token: Some(String::new()),
+            quorum: Some(QuorumConfig {
+                expected_hosts: Some(0),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let problems = config.validate();
+        let expected = [
+            "hosts[0].fence_agent: Unknown fence agent",
+            "hosts[0].resources.ost0.requires: unknown resource \"missing\"",
+            "hosts[0].resources.zpool1.stop_timeout: the timeout must be at least 1 second",
+            "hosts[1].resources.zpool0: resource ID \"zpool0\" is also used",
+            "failover_pairs[0]: unknown host \"oss02\"",
+            "hosts[1].hostname: host \"oss01\" is not in any failover pair",
+            "poll_interval: the poll interval must be at least 1 ms",
+            "retry_attempts: there must be at least 1 attempt",
+            "action_concurrency: at least 1 action must be allowed at once",
+            "socket_mode: invalid socket mode '999'",
+            "auth_token: the token must not be empty",
+            "read_only_token: the token must not be empty",
+            "hosts[0].resources.zpool1.home_node: \"oss01\" is not \"oss00\" or its failover partner \"oss02\"",
+            "hosts[0].resources.zpool1.allowed_nodes: the home node \"oss01\" must be allowed",
+            "groups.@bad: \"@bad\" is not a valid group name",
+            "groups.@bad: a group must have at least one member",
+            "groups.pools: unknown resource \"missing\"",
+            "quorum.expected_hosts: a cluster must expect at least one host",
+            "hosts[0].resources.ost0.parameters.target: a lustre/Lustre resource must set this",
+            "hosts[0].resources.ost0.parameters.mountpoint: a lustre/Lustre resource must set",
+        ];
+        assert_eq!(problems.len(), expected.len(), "{problems:?}");
+        for (problem, expected) in problems.iter().zip(expected) {
+            assert!(problem.starts_with(expected), "{problem}");
         }
     }
+
+    #[test]
+    fn test_parameter_problems() {
+        let config = merge(vec![(
+            "parameters.yaml".to_string(),
+            "
+hosts:
+- hostname: oss00
+  resources:
+    zpool0: {kind: heartbeat/ZFS, parameters: {pool: pool0, importforce: maybe}}
+    ost0:
+      kind: lustre/Lustre
+      parameters: {target: pool0/ost0, mountpoint: mnt/ost0, kind: oss}
+      requires: zpool0
+    ip0: {kind: heartbeat/IPaddr2, parameters: {}, requires: zpool0}
+  fence_agent: null
+  fence_parameters: null
+"
+            .to_string(),
+        )])
+        .unwrap();
+
+        // Resources of a kind without a schema, like ip0, are not checked:
+        assert_eq!(
+            config.parameter_problems(),
+            [
+                "hosts[0].resources.ost0.parameters.mountpoint: expected an absolute path, but \
+                 got \"mnt/ost0\"",
+                "hosts[0].resources.ost0.parameters.kind: expected one of mgs, mdt, ost, but got \
+                 \"oss\"",
+                "hosts[0].resources.zpool0.parameters.importforce: expected true or false, but \
+                 got \"maybe\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_placement_weights() {
+        let config = merge(vec![(
+            "weights.yaml".to_string(),
+            "
+hosts:
+- hostname: oss00
+  resources: {}
+  fence_agent: null
+  fence_parameters: null
+  attributes: {memory_gb: 512, running_groups: 1, load: .nan}
+- hostname: oss01
+  resources: {}
+  fence_agent: null
+  fence_parameters: null
+failover_pairs: [[oss00, oss01]]
+placement_weights: {memory_gb: 1, running_groups: -10, cores: 2, load: .inf}
+"
+            .to_string(),
+        )])
+        .unwrap();
+        assert_eq!(config.hosts[0].attributes["memory_gb"], 512.0);
+        assert!(config.hosts[1].attributes.is_empty());
+
+        assert_eq!(
+            config.placement_problems(),
+            [
+                "hosts[0].attributes.load: NaN is not a number",
+                "hosts[0].attributes.running_groups: this is counted by the manager, and can not \
+                 be set",
+                "placement_weights.cores: no host has the attribute \"cores\"",
+                "placement_weights.load: inf is not a number",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_constraint_problems() {
+        let config = merge(vec![(
+            "constraints.yaml".to_string(),
+            "
+hosts:
+- hostname: oss00
+  resources:
+    zpool0: {kind: heartbeat/ZFS, parameters: {pool: pool0}}
+    ost0: {kind: lustre/Lustre, parameters: {}, requires: zpool0}
+    zpool1: {kind: heartbeat/ZFS, parameters: {pool: pool1}}
+    zpool2: {kind: heartbeat/ZFS, parameters: {pool: pool2}, allowed_nodes: [oss00]}
+  fence_agent: null
+  fence_parameters: null
+- hostname: oss01
+  resources:
+    zpool3: {kind: heartbeat/ZFS, parameters: {pool: pool3}, allowed_nodes: [oss01]}
+  fence_agent: null
+  fence_parameters: null
+failover_pairs: [[oss00, oss01]]
+colocation: [[zpool0, zpool1], [zpool2, zpool3], [zpool0]]
+anti_affinity: [[ost0, zpool0], [ost0, zpool1, zpool3], [zpool1, missing]]
+"
+            .to_string(),
+        )])
+        .unwrap();
+
+        assert_eq!(
+            config.constraint_problems(),
+            [
+                "colocation[2]: a constraint must name at least two resources",
+                "anti_affinity[2]: unknown resource \"missing\"",
+                "colocation[1]: zpool2 and zpool3 can never run on the same host",
+                "anti_affinity[0]: ost0 and zpool0 are in the same resource group, so they always \
+                 run together",
+                "anti_affinity[1]: ost0 and zpool1 must also run together, by the colocation \
+                 constraints",
+            ]
+        );
+
+        let config = Config {
+            colocation: vec![vec!["zpool0".to_string(), "zpool1".to_string()]],
+            anti_affinity: vec![vec!["zpool1".to_string(), "zpool3".to_string()]],
+            ..config
+        };
+        assert!(config.constraint_problems().is_empty());
+    }
+
+    #[test]
+    fn test_dependency_problems() {
+        let mgs_pool = Resource::new_zpool("mgs".to_string());
+        let mut mgs = Resource::new_zpool("unused".to_string());
+        mgs.kind = "lustre/Lustre".to_string();
+        mgs.requires = Some("mgs_pool".to_string());
+        mgs.depends_on = vec!["ost0".to_string()];
+        let mut ost_pool = Resource::new_zpool("ost0".to_string());
+        ost_pool.depends_on = vec!["mgs".to_string(), "missing".to_string()];
+        let mut ost = mgs.clone();
+        ost.requires = Some("ost_pool".to_string());
+        ost.depends_on = vec!["ost0".to_string()];
+
+        let config = Config {
+            hosts: vec![
+                Host {
+                    hostname: "mds00".to_string(),
+                    resources: HashMap::from([
+                        ("mgs_pool".to_string(), mgs_pool),
+                        ("mgs".to_string(), mgs),
+                    ]),
+                    fence_agent: None,
+                    fence_parameters: None,
+                    attributes: BTreeMap::new(),
+                },
+                Host {
+                    hostname: "oss00".to_string(),
+                    resources: HashMap::from([
+                        ("ost_pool".to_string(), ost_pool),
+                        ("ost0".to_string(), ost),
+                    ]),
+                    fence_agent: None,
+                    fence_parameters: None,
+                    attributes: BTreeMap::new(),
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            config.dependency_problems(),
+            [
+                "hosts[0].resources.mgs.depends_on: dependency cycle: mgs -> ost0 -> ost_pool -> mgs",
+                "hosts[1].resources.ost0.depends_on: resource depends on itself",
+                "hosts[1].resources.ost_pool.depends_on: unknown resource \"missing\"",
+                "hosts[1].resources.ost_pool.depends_on: dependency cycle: ost_pool -> mgs -> ost0 -> ost_pool",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let base = "
+hosts:
+- hostname: oss00
+  resources:
+    zpool0: {kind: heartbeat/ZFS, parameters: {pool: pool0}}
+  fence_agent: powerman
+  fence_parameters: null
+failover_pairs: [[oss00, oss01]]
+poll_interval: 1000
+poll_jitter: 100
+tls: {ca_cert: /base/ca.pem, client_cert: /base/client.pem}
+groups: {north: [zpool0], south: [zpool0]}
+";
+        let overrides = "
+hosts:
+- hostname: oss00
+  resources:
+    zpool1: {kind: heartbeat/ZFS, parameters: {pool: pool1}}
+  fence_agent: null
+- hostname: oss01
+  resources:
+    zpool2: {kind: heartbeat/ZFS, parameters: {pool: pool2}}
+  fence_agent: null
+  fence_parameters: null
+failover_pairs: [[oss02, oss03]]
+poll_interval: 2000
+tls: {ca_cert: /site/ca.pem}
+groups: {south: [zpool1, zpool2]}
+";
+        let config = merge(vec![
+            ("base.yaml".to_string(), base.to_string()),
+            ("site.yaml".to_string(), overrides.to_string()),
+        ])
+        .unwrap();
+
+        // Later files override the settings of earlier ones, key by key in mappings:
+        assert_eq!(config.poll_interval, Some(2000));
+        assert_eq!(config.poll_jitter, Some(100));
+        let tls = config.tls.unwrap();
+        assert_eq!(tls.ca_cert.as_deref(), Some("/site/ca.pem"));
+        assert_eq!(tls.client_cert.as_deref(), Some("/base/client.pem"));
+        assert_eq!(config.groups["north"], ["zpool0"]);
+        assert_eq!(config.groups["south"], ["zpool1", "zpool2"]);
+
+        // Hosts are merged by hostname, and their resources combined:
+        assert_eq!(config.hosts.len(), 2);
+        assert_eq!(config.hosts[0].hostname, "oss00");
+        assert_eq!(config.hosts[0].fence_agent, None);
+        let mut ids: Vec<&String> = config.hosts[0].resources.keys().collect();
+        ids.sort();
+        assert_eq!(ids, ["zpool0", "zpool1"]);
+        assert_eq!(config.hosts[1].hostname, "oss01");
+        assert_eq!(
+            config.failover_pairs,
+            Some(vec![
+                vec!["oss00".to_string(), "oss01".to_string()],
+                vec!["oss02".to_string(), "oss03".to_string()],
+            ])
+        );
+
+        assert_eq!(config.sources.files, ["base.yaml", "site.yaml"]);
+        assert_eq!(
+            config.sources.resources,
+            BTreeMap::from([
+                ("zpool0".to_string(), "base.yaml".to_string()),
+                ("zpool1".to_string(), "site.yaml".to_string()),
+                ("zpool2".to_string(), "site.yaml".to_string()),
+            ])
+        );
+
+        // A resource may only be defined in one file, even under another host:
+        let duplicate = "
+hosts:
+- hostname: oss01
+  resources:
+    zpool0: {kind: heartbeat/ZFS, parameters: {pool: pool0}}
+  fence_agent: null
+  fence_parameters: null
+";
+        let e = merge(vec![
+            ("base.yaml".to_string(), base.to_string()),
+            ("dup.yaml".to_string(), duplicate.to_string()),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            e,
+            "Could not merge config file \"dup.yaml\": resource \"zpool0\" is already defined \
+            in \"base.yaml\""
+        );
+
+        // A single file records where its resources came from too:
+        let config = merge(vec![("base.yaml".to_string(), base.to_string())]).unwrap();
+        assert_eq!(config.sources.files, ["base.yaml"]);
+        assert_eq!(config.sources.resources["zpool0"], "base.yaml");
+    }
+
+    #[test]
+    fn test_comments() {
+        let commented = "
+# The cluster's servers, in failover pairs.
+hosts:
+# The first server of the pair.
+- hostname: oss00  # on rack 1
+  resources:
+    # Every target needs its pool.
+    zpool0:
+      kind: heartbeat/ZFS
+      parameters: {pool: pool0}  # imported by name
+    ost0:
+      kind: lustre/Lustre
+      parameters:
+        target: pool0/ost0
+        mountpoint: /mnt/ost0
+        kind: ost
+      requires: zpool0 # mounted once the pool is imported
+  fence_agent: powerman
+  fence_parameters: null # powerman needs none
+failover_pairs: [[oss00, oss01]] # oss01 is listed elsewhere
+poll_interval: 1000 # milliseconds; 500 is too fast for this filesystem
+";
+        let config = merge(vec![("commented.yaml".to_string(), commented.to_string())]).unwrap();
+        assert_eq!(config.hosts.len(), 1);
+        let host = &config.hosts[0];
+        assert_eq!(host.hostname, "oss00");
+        assert_eq!(host.fence_agent.as_deref(), Some("powerman"));
+        assert_eq!(host.fence_parameters, None);
+        assert_eq!(host.resources["zpool0"].parameters["pool"], "pool0");
+        assert_eq!(host.resources["ost0"].requires.as_deref(), Some("zpool0"));
+        assert_eq!(host.resources["ost0"].parameters["mountpoint"], "/mnt/ost0");
+        assert_eq!(config.poll_interval, Some(1000));
+
+        // The same config, written as JSON with comments, trailing commas, and unquoted keys:
+        let json = r#"
+{
+  # The cluster's servers, in failover pairs.
+  hosts: [
+    {
+      hostname: "oss00", # on rack 1
+      resources: {
+        zpool0: {kind: "heartbeat/ZFS", parameters: {pool: "pool0"}},
+        ost0: {
+          kind: "lustre/Lustre",
+          parameters: {target: "pool0/ost0", mountpoint: "/mnt/ost0", kind: "ost",},
+          requires: "zpool0",
+        },
+      },
+      fence_agent: "powerman",
+      fence_parameters: null,
+    },
+  ],
+  failover_pairs: [["oss00", "oss01"]],
+  "poll_interval": 1000,
+}
+"#;
+        let from_json = merge(vec![("commented.yaml".to_string(), json.to_string())]).unwrap();
+        assert_eq!(
+            serde_yaml::to_string(&from_json).unwrap(),
+            serde_yaml::to_string(&config).unwrap()
+        );
+
+        // JSON5 comments are not YAML comments, so they are rejected, with an error which says so:
+        for json in [
+            "{\n  // servers\n  hosts: []\n}",
+            "{\n  /* servers */ hosts: []\n}",
+            "{\n  hosts: [], // servers\n  poll_interval: 1000\n}",
+        ] {
+            let e = merge(vec![("commented.json".to_string(), json.to_string())]).unwrap_err();
+            assert!(e.contains("comments must start with '#'"), "{e}");
+        }
+        let e = merge(vec![("plain.yaml".to_string(), "hosts: [".to_string())]).unwrap_err();
+        assert!(!e.contains("comments must start with '#'"), "{e}");
+    }
+
+    #[test]
+    fn test_substitute() {
+        let lookup = |name: &str| match name {
+            "POOL" => Some("pool0".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        };
+        let substitute = |value: &str| substitute(value, &lookup);
+
+        assert_eq!(substitute("plain").unwrap(), "plain");
+        assert_eq!(substitute("${POOL}").unwrap(), "pool0");
+        assert_eq!(
+            substitute("/dev/${POOL}/${POOL}").unwrap(),
+            "/dev/pool0/pool0"
+        );
+        assert_eq!(substitute("${UNSET:-/mnt/ost0}").unwrap(), "/mnt/ost0");
+        assert_eq!(substitute("${POOL:-other}").unwrap(), "pool0");
+        assert_eq!(substitute("${EMPTY:-other}").unwrap(), "other");
+        assert_eq!(substitute("${EMPTY}").unwrap(), "");
+        assert_eq!(substitute("${UNSET:-}").unwrap(), "");
+        assert_eq!(substitute("cost: $$5, $POOL").unwrap(), "cost: $5, $POOL");
+        assert_eq!(substitute("$${POOL}").unwrap(), "${POOL}");
+
+        assert_eq!(
+            substitute("${UNSET}").unwrap_err(),
+            "environment variable UNSET is not set"
+        );
+        assert_eq!(
+            substitute("${POOL").unwrap_err(),
+            "\"${\" is not closed with \"}\"; write \"$$\" for a literal \"$\""
+        );
+        assert_eq!(
+            substitute("${1POOL}").unwrap_err(),
+            "\"1POOL\" is not the name of an environment variable"
+        );
+
+        // Only parameters and fence agents are substituted, and every problem is reported:
+        let text = "
+hosts:
+- hostname: oss${POOL}
+  resources:
+    zpool0: {kind: heartbeat/ZFS, parameters: {pool: '${POOL}', cache: '${CACHE}'}}
+    zpool1: {kind: heartbeat/ZFS, parameters: {pool: '${POOL1}'}}
+  fence_agent: ${AGENT:-powerman}
+  fence_parameters: {target: '${POOL}'}
+";
+        let mut config = merge(vec![("a.yaml".to_string(), text.to_string())]).unwrap();
+        assert_eq!(
+            config.substitute_env(lookup),
+            [
+                "hosts[0].resources.zpool0.parameters.cache: environment variable CACHE is not set",
+                "hosts[0].resources.zpool1.parameters.pool: environment variable POOL1 is not set",
+            ]
+        );
+        let host = &config.hosts[0];
+        assert_eq!(host.hostname, "oss${POOL}");
+        assert_eq!(host.fence_agent.as_deref(), Some("powerman"));
+        assert_eq!(host.fence_parameters.as_ref().unwrap()["target"], "pool0");
+        assert_eq!(host.resources["zpool0"].parameters["pool"], "pool0");
+        assert_eq!(host.resources["zpool0"].parameters["cache"], "${CACHE}");
+    }
 }
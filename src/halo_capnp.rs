@@ -3,7 +3,7 @@
 
 use std::{env, io};
 
-use {futures::AsyncReadExt, rustls::pki_types::ServerName};
+use {futures::AsyncReadExt, rustls::pki_types::ServerName, tracing::Instrument};
 
 use crate::{
     remote::ocf,
@@ -85,12 +85,19 @@ pub async fn remote_ocf_operation_given_client(
     client: &ocf_resource_agent::Client,
     op: ocf_resource_agent::Operation,
 ) -> Result<AgentReply, capnp::Error> {
-    let mut request = client.operation_request();
-    prep_request(&mut request, res, op);
+    let span = tracing::trace_span!("agent_rpc", resource = %res.id, ?op);
+    async {
+        let mut request = client.operation_request();
+        prep_request(&mut request, res, op);
 
-    let reply = request.send().promise.await?;
+        let start = std::time::Instant::now();
+        let reply = request.send().promise.await;
+        tracing::trace!(elapsed = ?start.elapsed(), ok = reply.is_ok(), "agent RPC finished");
 
-    get_status(reply)
+        get_status(reply?)
+    }
+    .instrument(span)
+    .await
 }
 
 fn get_status(reply: OcfOperationResults) -> Result<AgentReply, capnp::Error> {
@@ -178,8 +185,9 @@ fn prep_request(request: &mut OperationRequest, res: &Resource, op: ocf_resource
     request.set_op(op);
 
     request.set_resource(res.kind.clone());
-    let mut args = request.init_args(res.parameters.len() as u32);
-    for (i, param) in res.parameters.iter().enumerate() {
+    let parameters = res.parameters();
+    let mut args = request.init_args(parameters.len() as u32);
+    for (i, param) in parameters.iter().enumerate() {
         let mut arg = args.reborrow().get(i as u32);
         arg.set_key(param.0.clone());
         arg.set_value(param.1.clone());
@@ -3,14 +3,22 @@
 
 //! Management of a failover cluster with HA pairs.
 
-use std::{io, mem::take};
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    mem::take,
+};
 
 use {
     futures::{future, stream::FuturesUnordered, StreamExt},
-    log::{debug, warn},
+    log::{debug, error, warn},
 };
 
-use crate::{cluster::Cluster, halo_capnp::*, resource::ManagementError};
+use crate::{
+    cluster::Cluster,
+    manager::placement::{Action, MoveReason, PlacementReason},
+    resource::ManagementError,
+};
 
 use super::*;
 
@@ -96,7 +104,7 @@ impl Host {
         state.manage_these_resources = self.startup(cluster, my_resources).await;
 
         loop {
-            match get_client(&self.address()).await {
+            match self.connect().await {
                 Ok(mut client) => {
                     debug!(
                         "Host {} established connection to its remote agent.",
@@ -107,6 +115,7 @@ impl Host {
                             .await;
                         // remote_connected_loop() only returns once a failover has been requested, and
                         // all host tasks are cancelled.
+                        self.set_reachable(false);
                         match self.maybe_do_failover(&mut state, cluster).await {
                             // If maybe_do_failover() returned a Client (because it was able to
                             // re-establish connection), we can use that client to re-enter the
@@ -141,11 +150,11 @@ impl Host {
 
     async fn remote_liveness_check(&self, cluster: &Cluster) {
         loop {
-            if get_client(&self.address()).await.is_ok() {
+            if self.connect().await.is_ok() {
                 return;
             }
 
-            tokio::time::sleep(tokio::time::Duration::from_millis(cluster.args.sleep_time)).await;
+            tokio::time::sleep(cluster.args.poll_delay()).await;
         }
     }
 
@@ -172,12 +181,37 @@ impl Host {
                         rg.root.set_error_recursive(away_message.to_string());
                         state.check_these_resources.push(message.resource_group);
                     }
+                    // The partner stopped managing a resource group because it is unmanaged, or
+                    // may not run there. Once the connection is back, management of it resumes
+                    // here, and goes back to observation if it may not be started here either.
+                    Message::ObserveResourceGroup => {
+                        let rg = cluster.get_resource_group(&message.resource_group.id);
+                        rg.root
+                            .set_error_recursive(match message.resource_group.location {
+                                Location::Home => home_message.to_string(),
+                                Location::Away => away_message.to_string(),
+                            });
+                        state.manage_these_resources.push(message.resource_group);
+                    }
                     other => {
                         panic!("Unexpected message type {other:?} in client disconnected routine.");
                     }
                 },
+                // The resource group is checked once the connection is back, like the others which
+                // were waiting for it:
+                HostMessage::ResourceGroupAdded(id) => {
+                    let rg = cluster.get_resource_group(&id);
+                    rg.root.set_error_recursive(home_message.to_string());
+                    state
+                        .check_these_resources
+                        .push(Self::mint_resource_token(rg));
+                }
                 HostMessage::Command(command) => match command {
                     HostCommand::Failback => warn!("{}", failback_message),
+                    HostCommand::Migrate(id) => warn!(
+                        "Warning: Migrate command for {id} received by host {} but remote is disconnected.",
+                        self.id()
+                    ),
                 },
                 HostMessage::None => {
                     panic!("Unexpected message type 'None' in client disconnected routine.")
@@ -200,6 +234,10 @@ impl Host {
         // as messages from child tasks (like "connection timed out; failover needed").
         tasks.push(Box::pin(self.receive_message()));
 
+        // While connected, this host is healthy, so resource groups that prefer it can come back,
+        // and resource groups can be drained from it when it is put on standby:
+        tasks.push(Box::pin(self.rebalance_resource_groups(cluster)));
+
         // Create a task to manage each resource group that should run on this host.
         for token in take(&mut state.manage_these_resources) {
             let id = token.id.clone();
@@ -226,10 +264,21 @@ impl Host {
                 HostMessage::Command(command) => {
                     match command {
                         HostCommand::Failback => self.do_failback(state, cluster),
+                        HostCommand::Migrate(id) => self.do_migrate(state, &id),
                     };
 
                     tasks.push(Box::pin(self.receive_message()));
                 }
+                // A resource group that was added to the cluster starts out like the others do
+                // when the manager starts: by checking where it is running.
+                HostMessage::ResourceGroupAdded(id) => {
+                    tasks.push(Box::pin(self.check_resource_group_managed(
+                        Self::mint_resource_token(cluster.get_resource_group(&id)),
+                        cluster,
+                        client,
+                    )));
+                    tasks.push(Box::pin(self.receive_message()));
+                }
                 HostMessage::Resource(event) => {
                     let id = &event.resource_group.id;
                     match event.kind {
@@ -295,6 +344,10 @@ impl Host {
                         Message::ResourceError => {
                             state.resources_with_errors.push(event.resource_group);
                         }
+                        Message::Removed => {
+                            state.resource_task_exited(id);
+                            event.resource_group.retire(cluster);
+                        }
                     };
                 }
                 HostMessage::None => {}
@@ -353,7 +406,7 @@ impl Host {
                 "Trying to reconnect to remote agent at {}, attempt {tries}",
                 self.id()
             );
-            match get_client(&self.address()).await {
+            match self.connect().await {
                 // If we were able to re-establish connection to the client, then return and let
                 // the manager try again to manage the resources that were running on this Host.
                 Ok(client) => {
@@ -396,22 +449,111 @@ impl Host {
             tries -= 1;
         }
 
-        self.do_failover(state).await;
-
-        None
+        self.do_failover(state, cluster).await
     }
 
-    async fn do_failover(&self, state: &mut HostState) {
-        self.do_fence_nonblocking(FenceCommand::Off)
-            .await
-            .expect("Fencing failed... TODO: handle this case...");
+    /// Fence this host, then hand its resource groups over to its partner.
+    ///
+    /// The resource groups are only handed over once the host is confirmed to be powered off, so
+    /// that they can never run in two places at once, and only while the cluster has quorum. Until
+    /// then, they stay stopped: this is retried on every pass, and if the remote agent becomes
+    /// reachable again, management of them resumes on this host instead, and its Client is
+    /// returned.
+    async fn do_failover(
+        &self,
+        state: &mut HostState,
+        cluster: &Cluster,
+    ) -> Option<ocf_resource_agent::Client> {
+        // Each problem is logged once, rather than on every attempt:
+        let (mut waited_for_quorum, mut waited_for_manage, mut fencing_failed, mut fencing_stopped) =
+            (false, false, false, false);
+        loop {
+            if !cluster.may_act() {
+                if !waited_for_manage {
+                    warn!(
+                        "Host {} will not be failed over while the manager is in {}.",
+                        self.id(),
+                        if cluster.maintenance().is_on() {
+                            "maintenance mode"
+                        } else {
+                            "Observe mode"
+                        }
+                    );
+                    waited_for_manage = true;
+                }
+            } else if !cluster.update_quorum() {
+                if !waited_for_quorum {
+                    warn!(
+                        "Host {} will not be failed over while the cluster does not have quorum.",
+                        self.id()
+                    );
+                    waited_for_quorum = true;
+                }
+            } else if cluster.args.no_fence {
+                warn!(
+                    "Host {} was not fenced because fencing is disabled; failing over its resources anyway.",
+                    self.id()
+                );
+                break;
+            } else if self.is_fence_failed() {
+                if !fencing_stopped {
+                    error!(
+                        "Host {0} will not be fenced again until its fencing breaker is reset \
+                         with 'halo power --reset-breaker {0}'. Its resources will stay stopped \
+                         until then.",
+                        self.id()
+                    );
+                    fencing_stopped = true;
+                }
+            } else {
+                match self.fence_off_confirmed().await {
+                    Ok(()) => {
+                        self.fence_succeeded();
+                        warn!("Host {} has been powered off.", self.id());
+                        break;
+                    }
+                    Err(e) => {
+                        // Fencing is given up on once it has failed too many times in a row, so
+                        // that a broken fence agent is not run forever:
+                        if self.fence_failed(cluster.args.fence_attempts) {
+                            error!(
+                                "CRITICAL: could not fence host {}: {e}. Fencing failed {} times \
+                                 in a row, so it will not be tried again until an admin resets it.",
+                                self.id(),
+                                self.fence_failures()
+                            );
+                        } else if !fencing_failed {
+                            error!(
+                                "CRITICAL: could not fence host {}: {e}. Its resources will stay \
+                                 stopped until it is confirmed to be powered off.",
+                                self.id()
+                            );
+                            fencing_failed = true;
+                        } else {
+                            debug!("Fencing host {} failed again: {e}", self.id());
+                        }
+                    }
+                }
+            }
 
-        warn!("Host {} has been powered off.", self.id());
+            tokio::time::sleep(cluster.args.poll_delay()).await;
+
+            if let Ok(client) = self.connect().await {
+                warn!(
+                    "Host {} is reachable again; resuming management of its resources there.",
+                    self.id()
+                );
+                state.manage_these_resources = take(&mut state.resources_in_transit);
+                return Some(client);
+            }
+        }
 
         for rg in take(&mut state.resources_in_transit) {
             self.send_message_to_partner(rg, Message::ManageResourceGroup)
                 .await;
         }
+
+        None
     }
 
     fn do_failback(&self, state: &mut HostState, cluster: &Cluster) {
@@ -437,6 +579,111 @@ impl Host {
         state.outstanding_resource_tasks = still_running;
     }
 
+    fn do_migrate(&self, state: &mut HostState, id: &str) {
+        let Some(pos) = state
+            .outstanding_resource_tasks
+            .iter()
+            .position(|task| task.id == id)
+        else {
+            warn!(
+                "Migrate command for {id} received by host {}, but it is not managing it.",
+                self.id()
+            );
+            return;
+        };
+
+        warn!("{id} will be moved off of host {}.", self.id());
+
+        let task = state.outstanding_resource_tasks.remove(pos);
+        task.switch_host.notify_one();
+    }
+
+    /// Move resource groups to this host when they should run here, checking for as long as this
+    /// runs. The moves are those of Snapshot::plan() whose target is this host:
+    ///
+    /// - While the partner is on standby, each resource group running on it is moved here, if it
+    ///   may run here.
+    /// - A resource group which prefers to run on this host, and is failed over, is moved back,
+    ///   unless that would break its constraints.
+    /// - A resource group which breaks its constraints on the partner, and could keep to them here,
+    ///   is moved here; see Snapshot::resolve_violation().
+    ///
+    /// The resource groups which must run with a group that is moved are moved along with it. The
+    /// constraints that the groups running on this host break are logged when they start to.
+    async fn rebalance_resource_groups(&self, cluster: &Cluster) -> HostMessage {
+        let partner = self.ha_failover_partner();
+
+        // The IDs of the resource groups that were already requested to move, so that the request
+        // is not repeated while they are still being stopped:
+        let mut requested = HashSet::new();
+
+        // The constraints that each resource group running here was last seen to break:
+        let mut violations: HashMap<String, Vec<String>> = HashMap::new();
+
+        loop {
+            let snapshot = cluster.snapshot();
+            for group in snapshot.groups.iter() {
+                let broken = if group.running_on.as_deref() == Some(&self.id()) {
+                    snapshot.violations(group)
+                } else {
+                    Vec::new()
+                };
+                if violations.get(&group.id) != Some(&broken) {
+                    if !broken.is_empty() {
+                        warn!(
+                            "Resource group {} is running on host {}, but {}.",
+                            group.id,
+                            self.id(),
+                            broken.join(", and ")
+                        );
+                    }
+                    violations.insert(group.id.clone(), broken);
+                }
+            }
+
+            let mut moving = HashSet::new();
+            if cluster.update_quorum() && cluster.may_act() {
+                for action in snapshot.plan() {
+                    let Action::Move {
+                        group,
+                        from,
+                        to,
+                        reason,
+                    } = action
+                    else {
+                        continue;
+                    };
+                    if to != self.id() {
+                        continue;
+                    }
+                    moving.insert(group.clone());
+                    if !requested.insert(group.clone()) {
+                        continue;
+                    }
+                    match reason {
+                        MoveReason::Standby => warn!(
+                            "Host {from} is on standby, so {group} will be moved off of it."
+                        ),
+                        MoveReason::Home => {
+                            warn!("{group} prefers to run on host {to} and will be moved back.")
+                        }
+                        MoveReason::Apart(other) => warn!(
+                            "{group} must not run on the same host as {other}, so it will be moved to host {to}."
+                        ),
+                        MoveReason::Together(other) => warn!(
+                            "{group} must run on the same host as {other}, so it will be moved to host {to}."
+                        ),
+                        MoveReason::Down | MoveReason::With(_) => {}
+                    }
+                    partner.command(HostCommand::Migrate(group)).await;
+                }
+            }
+            requested.retain(|id| moving.contains(id));
+
+            tokio::time::sleep(cluster.args.poll_delay()).await;
+        }
+    }
+
     async fn switch_host(
         &self,
         token: ResourceToken,
@@ -445,7 +692,7 @@ impl Host {
     ) -> HostMessage {
         let rg = cluster.get_resource_group(&token.id);
 
-        match rg.stop_resources(client).await {
+        match rg.stop_resources(cluster, client).await {
             Ok(()) => {}
             Err(ManagementError::Configuration) => {
                 debug!("Switch host operation recieved unexpected configuration error from remote agent.");
@@ -483,7 +730,7 @@ impl Host {
         my_resources: Vec<ResourceToken>,
     ) -> Vec<ResourceToken> {
         let (manage_these, send_these): (Vec<ResourceToken>, Vec<ResourceToken>) =
-            match get_client(&self.address()).await {
+            match self.connect().await {
                 Ok(client) => {
                     let mut manage_these = Vec::new();
                     let mut send_these = Vec::new();
@@ -546,6 +793,9 @@ impl Host {
         cluster: &Cluster,
         client: &ocf_resource_agent::Client,
     ) -> HostMessage {
+        if cluster.get_resource_group(&token.id).is_removed() {
+            return new_message(token, Message::Removed);
+        }
         match is_resource_group_running_here(&token, cluster, client, true).await {
             Ok(is_running_here) => {
                 if is_running_here {
@@ -553,6 +803,10 @@ impl Host {
                         .await;
                 } else {
                     match token.location {
+                        Location::Away if self.placed_here(cluster, &token) => {
+                            self.send_message_to_self(token, Message::ManageResourceGroup)
+                                .await;
+                        }
                         Location::Away => {
                             self.send_message_to_partner(token, Message::ManageResourceGroup)
                                 .await;
@@ -571,6 +825,36 @@ impl Host {
         }
     }
 
+    /// For a resource group which is running on neither host in the pair, and whose partner
+    /// (home) host has already checked on it, decide whether to start it on this host instead of
+    /// its home, by its constraints and the placement scores of the hosts; see Snapshot::place().
+    /// The choice is logged, and recorded in the history.
+    fn placed_here(&self, cluster: &Cluster, token: &ResourceToken) -> bool {
+        let rg = cluster.get_resource_group(&token.id);
+        if !rg.get_managed() {
+            return false;
+        }
+        let Some((loc, reason)) = cluster.place(rg) else {
+            return false;
+        };
+        let host = rg.root.node(loc).unwrap().id();
+        let why = match &reason {
+            PlacementReason::Colocated(other) => format!("since it must run with {other}"),
+            PlacementReason::AntiAffine(other) => format!("since it must not run with {other}"),
+            PlacementReason::Score(score) => {
+                format!("whose placement score of {score} is the highest")
+            }
+        };
+        warn!(
+            "Resource group {} is not running, and will be started on {host}, {why}.",
+            rg.id()
+        );
+        cluster
+            .history()
+            .record(rg.id(), &format!("place on {host} ({reason})"), Ok(()));
+        loc == token.location
+    }
+
     /// Perform observation of a resource group when the manager process is in "managed mode", but
     /// the resource group itself is set to "managed = false".
     ///
@@ -583,6 +867,9 @@ impl Host {
         client: &ocf_resource_agent::Client,
     ) -> HostMessage {
         let rg = cluster.get_resource_group(&token.id);
+        if rg.is_removed() {
+            return new_message(token, Message::Removed);
+        }
 
         match is_resource_group_running_here(&token, cluster, client, true).await {
             Ok(is_running_here) => {
@@ -593,8 +880,7 @@ impl Host {
                     self.send_message_to_partner(token, Message::CheckResourceGroup)
                         .await;
                 } else {
-                    tokio::time::sleep(tokio::time::Duration::from_millis(cluster.args.sleep_time))
-                        .await;
+                    tokio::time::sleep(cluster.args.poll_delay()).await;
                     self.send_message_to_partner(token, Message::ObserveResourceGroup)
                         .await;
                 }
@@ -636,8 +922,10 @@ impl Host {
 
             // If the resource management loop returns, it is either because an error was observed,
             // or because the "managed" flag is set to false and the resource was stopped.
-            res = rg.manage_loop(client, token.location) => {
+            res = rg.manage_loop(cluster, client, token.location) => {
                 match res {
+                    // The resource group was removed from the cluster:
+                    Ok(()) if rg.is_removed() => new_message(token, Message::Removed),
                     // Resource was stopped, and it is no longer supposed to be managed.
                     // Enter "Observe" mode, starting with a check on the partner host.
                     Ok(()) => {
@@ -3,12 +3,11 @@
 
 use std::{future::Future, pin::Pin, rc::Rc};
 
-use {futures::stream::FuturesUnordered, tokio::sync::Notify};
+use {futures::stream::FuturesUnordered, log::debug, tokio::sync::Notify};
 
 use crate::{
     cluster::Cluster,
-    halo_capnp::*,
-    resource::{Location, ManagementError},
+    resource::{Location, ManagementError, ResourceGroup},
 };
 
 use super::*;
@@ -29,6 +28,10 @@ pub enum HostMessage {
     /// that should be taken for a particular ResourceGroup.
     Resource(ResourceMessage),
 
+    /// The resource group with the given ID was added to the cluster, and its home is this Host.
+    /// A ResourceToken needs to be minted for it, so that it can be managed like the others.
+    ResourceGroupAdded(String),
+
     /// A message from a child task indicating that it exited normally, and no further action is
     /// needed from this Host on the ResourceGroup that the task had been managing.
     ///
@@ -73,6 +76,10 @@ enum Message {
     /// A resource management task reported that this resource has an error which prevents the
     /// service from managing it.
     ResourceError,
+
+    /// The resource group was removed from the cluster, so nothing more is to be done with it, and
+    /// its ResourceToken can be retired.
+    Removed,
 }
 
 fn new_message(rg: ResourceToken, kind: Message) -> HostMessage {
@@ -122,32 +129,57 @@ impl ResourceTaskCancel {
 /// "dropped" and thus forgotten about.
 ///
 /// To ensure that a ResourceGroup is never forgotten about, the drop() implementation panics, so
-/// that it is a runtime error for a ResourceGroup to transition to an unexpected state.
+/// that it is a runtime error for a ResourceGroup to transition to an unexpected state. The
+/// exceptions are when the manager is shutting down, and all of the management tasks are dropped,
+/// and when the ResourceGroup was removed from the cluster, and its token is retired.
 #[derive(Debug)]
 struct ResourceToken {
     id: String,
     location: Location,
+    retired: bool,
+}
+
+impl ResourceToken {
+    /// Drop the token of a resource group which was removed from the cluster, so that the group
+    /// may be added back.
+    fn retire(mut self, cluster: &Cluster) {
+        debug!("Resource group {} is no longer managed.", self.id);
+        cluster.get_resource_group(&self.id).release();
+        self.retired = true;
+    }
 }
 
 impl Drop for ResourceToken {
     fn drop(&mut self) {
-        panic!("Resource token {self:?} was illegally dropped!");
+        if !self.retired && !crate::manager::shutdown::is_shutting_down() {
+            panic!("Resource token {self:?} was illegally dropped!");
+        }
     }
 }
 
 impl Host {
-    /// Mint a ResourceToken for each ResourceGroup whose home is this Host. This is the only place
-    /// that ResourceTokens can be created - and they must never be destroyed.
+    /// Mint a ResourceToken for each ResourceGroup whose home is this Host. This, and
+    /// mint_resource_token() for a ResourceGroup added later, are the only places that
+    /// ResourceTokens can be created - and they must never be destroyed, only retired.
     fn mint_resource_tokens(&self, cluster: &Cluster) -> Vec<ResourceToken> {
         cluster
             .host_home_resource_groups(self)
-            .map(|rg| ResourceToken {
-                id: rg.id().to_string(),
-                location: Location::Home,
-            })
+            .map(Self::mint_resource_token)
             .collect()
     }
 
+    /// Mint the ResourceToken of `rg`, which holds the group until the token is retired. A group
+    /// only has one token at a time: one which was removed is only added back, to be minted a new
+    /// token, once its old one was retired.
+    fn mint_resource_token(rg: &ResourceGroup) -> ResourceToken {
+        rg.hold();
+        ResourceToken {
+            id: rg.id().to_string(),
+            location: Location::Home,
+            retired: false,
+        }
+    }
+
     fn ha_failover_partner(&self) -> &Arc<Host> {
         self.failover_partner()
             .expect("Host without failover partner in HA routine.")
@@ -20,14 +20,15 @@ impl Host {
         debug!("host {}: resources: {my_resources:?}", self.id());
 
         loop {
-            match get_client(&self.address()).await {
+            match self.connect().await {
                 Ok(client) => {
                     debug!(
                         "Host {} established connection to its remote agent.",
                         self.id()
                     );
                     self.remote_connected_loop_observe(take(&mut my_resources), cluster, &client)
-                        .await
+                        .await;
+                    self.set_reachable(false);
                 }
                 Err(_) => {
                     debug!(
@@ -62,6 +63,15 @@ impl Host {
             trace!("Host {} got event: {event:?}", self.id());
             match event {
                 HostMessage::Command(_) => todo!(),
+                HostMessage::ResourceGroupAdded(id) => {
+                    tasks.push(Box::pin(self.check_resource_group(
+                        Self::mint_resource_token(cluster.get_resource_group(&id)),
+                        cluster,
+                        client,
+                        false,
+                    )));
+                    tasks.push(Box::pin(self.receive_message()));
+                }
                 HostMessage::Resource(event) => match event.kind {
                     Message::CheckResourceGroup => {
                         tasks.push(Box::pin(self.check_resource_group(
@@ -89,6 +99,7 @@ impl Host {
                     Message::TaskCanceled => todo!(),
                     Message::SwitchHost => todo!(),
                     Message::ResourceError => todo!(),
+                    Message::Removed => event.resource_group.retire(cluster),
                 },
                 HostMessage::None => {}
             }
@@ -102,6 +113,9 @@ impl Host {
         client: &ocf_resource_agent::Client,
         update_status_if_stopped: bool,
     ) -> HostMessage {
+        if cluster.get_resource_group(&token.id).is_removed() {
+            return new_message(token, Message::Removed);
+        }
         match is_resource_group_running_here(&token, cluster, client, update_status_if_stopped)
             .await
         {
@@ -112,8 +126,7 @@ impl Host {
 
                     HostMessage::None
                 } else {
-                    tokio::time::sleep(tokio::time::Duration::from_millis(cluster.args.sleep_time))
-                        .await;
+                    tokio::time::sleep(cluster.args.poll_delay()).await;
                     self.send_message_to_partner(token, Message::CheckResourceGroup)
                         .await;
 
@@ -132,11 +145,11 @@ impl Host {
         client: &ocf_resource_agent::Client,
     ) -> HostMessage {
         let rg = cluster.get_resource_group(&token.id);
-        match rg.observe_loop(client, true, token.location).await {
+        match rg.observe_loop(cluster, client, true, token.location).await {
+            Ok(()) if rg.is_removed() => new_message(token, Message::Removed),
             // Resource stopped: need to see if it started running on partner.
             Ok(()) => {
-                tokio::time::sleep(tokio::time::Duration::from_millis(cluster.args.sleep_time))
-                    .await;
+                tokio::time::sleep(cluster.args.poll_delay()).await;
                 self.send_message_to_partner(token, Message::CheckResourceGroup)
                     .await;
 
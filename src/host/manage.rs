@@ -0,0 +1,66 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+//! Active management of a cluster without high-availability.
+//!
+//! Without a failover partner, there is nowhere to move resources to: the manager can only keep
+//! starting them on their home node.
+
+use log::error;
+
+use crate::{
+    cluster::Cluster,
+    resource::{Location, ManagementError},
+};
+
+use super::*;
+
+impl Host {
+    pub async fn manage(&self, cluster: &Cluster) {
+        loop {
+            match self.connect().await {
+                Ok(client) => {
+                    self.run_home_resource_groups(cluster, |rg| {
+                        self.manage_home_resource_group(cluster, rg, &client)
+                    })
+                    .await;
+                    self.set_reachable(false);
+                }
+                Err(e) => error!(
+                    "Host {} failed to establish connection to its remote agent: {e}",
+                    self.id()
+                ),
+            }
+
+            // Once all tasks exited (because the connection failed), wait a bit and try again:
+            tokio::time::sleep(cluster.args.poll_delay()).await;
+        }
+    }
+
+    /// Manage a resource group until the connection to the remote agent fails, or the group is
+    /// removed from the cluster.
+    async fn manage_home_resource_group(
+        &self,
+        cluster: &Cluster,
+        rg: &str,
+        client: &ocf_resource_agent::Client,
+    ) {
+        let rg = cluster.get_resource_group(rg);
+        loop {
+            match rg.manage_loop(cluster, client, Location::Home).await {
+                Ok(()) if rg.is_removed() => return,
+                // The resource group was stopped while unmanaged. Keep checking on it, since it may
+                // be started by hand or managed again later.
+                Ok(()) => {}
+                // A configuration error needs admin intervention, but the resource group is still
+                // monitored so that its status stays up to date.
+                Err(ManagementError::Configuration) => {
+                    error!("Resource group {} could not be managed.", rg.id());
+                }
+                Err(ManagementError::Connection) => return,
+            }
+
+            tokio::time::sleep(cluster.args.poll_delay()).await;
+        }
+    }
+}
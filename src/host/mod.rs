@@ -2,18 +2,34 @@
 // Copyright 2025. Triad National Security, LLC.
 
 use std::{
+    collections::BTreeMap,
     fmt,
-    sync::{Arc, OnceLock},
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, OnceLock,
+    },
 };
 
-use tokio::sync::mpsc;
+use {
+    futures::{stream::FuturesUnordered, FutureExt, StreamExt},
+    log::warn,
+    tokio::sync::mpsc,
+};
 
-use crate::{commands::Handle, halo_capnp::*};
+use crate::{
+    cluster::Cluster,
+    commands::Handle,
+    halo_capnp::*,
+    manager::events::{Event, Publisher},
+};
 
 pub mod power;
 pub use power::{FenceAgent, FenceCommand, RedfishArgs};
 
 mod ha;
+mod manage;
 mod observe;
 
 use ha::HostMessage;
@@ -37,6 +53,10 @@ pub enum HostCommand {
     /// Failback resources. If any of this host's resources are not currently home, then reclaim
     /// them from the partner and start managing them (if possible).
     Failback,
+
+    /// Migrate the resource group with the given ID. If this host is managing it, then stop it and
+    /// pass management on to the partner, which starts it.
+    Migrate(String),
 }
 
 /// A server on which services can run.
@@ -46,6 +66,28 @@ pub struct Host {
     fence_agent: Option<FenceAgent>,
     failover_partner: OnceLock<Option<Arc<Host>>>,
 
+    /// A host on standby is being drained for maintenance: resources are not started on it, and in
+    /// Manage mode, the ones running on it are moved to its partner.
+    standby: AtomicBool,
+
+    /// Whether the manager is currently connected to this host's remote agent, for judging
+    /// quorum.
+    reachable: AtomicBool,
+
+    /// How many times in a row the manager has failed to fence this host.
+    fence_failures: AtomicU32,
+
+    /// Whether the manager has stopped trying to fence this host, after too many failures in a
+    /// row, until an admin resets it; see Host::fence_failed().
+    fence_breaker_open: AtomicBool,
+
+    /// The numbers from the config which describe this host, for scoring where to place resource
+    /// groups.
+    attributes: BTreeMap<String, f64>,
+
+    /// Where changes to this host, and to the resources on it, are published.
+    events: Publisher,
+
     /// The sender, receiver pair is used to send commands to the Host management task.
     sender: mpsc::Sender<HostMessage>,
     receiver: tokio::sync::Mutex<mpsc::Receiver<HostMessage>>,
@@ -64,19 +106,30 @@ impl Host {
             },
             fence_agent,
             failover_partner: OnceLock::new(),
+            standby: AtomicBool::new(false),
+            reachable: AtomicBool::new(false),
+            fence_failures: AtomicU32::new(0),
+            fence_breaker_open: AtomicBool::new(false),
+            attributes: BTreeMap::new(),
+            events: Publisher::new(),
             sender,
             receiver: tokio::sync::Mutex::new(receiver),
         }
     }
 
-    /// Create a Host object from a given config::Host object.
-    pub fn from_config(config: &crate::config::Host) -> Self {
+    /// Create a Host object from a given config::Host object, which publishes its changes to
+    /// `events`.
+    pub fn from_config(config: &crate::config::Host, events: Publisher) -> Self {
         let (name, port) = Self::get_host_port(&config.hostname);
         let fence_agent = config
             .fence_agent
             .as_ref()
             .map(|agent| FenceAgent::from_params(agent, &config.fence_parameters));
-        Host::new(name, port, fence_agent)
+        Host {
+            attributes: config.attributes.clone(),
+            events,
+            ..Host::new(name, port, fence_agent)
+        }
     }
 
     /// Given a string that may be of the form "<address>:port number>", split it out into the address
@@ -128,6 +181,68 @@ impl Host {
         ))
     }
 
+    /// Run `task` on each resource group whose home is this Host, and on each one which is added to
+    /// the cluster while they run, until all of the tasks have exited. Without any resource groups
+    /// to run it on, this returns after a poll interval instead, so that the caller still checks
+    /// the connection to the remote agent periodically.
+    ///
+    /// Each group is held while its task runs, so that a group which was removed is only added
+    /// back once its task is gone, and no group has two tasks at once.
+    async fn run_home_resource_groups<'a, F, Fut>(&'a self, cluster: &'a Cluster, task: F)
+    where
+        F: Fn(&'a str) -> Fut,
+        Fut: Future<Output = ()> + 'a,
+    {
+        // A task's output is None when it is one of the resource group tasks, which have exited:
+        let mut tasks: FuturesUnordered<Pin<Box<dyn Future<Output = Option<HostMessage>> + 'a>>> =
+            FuturesUnordered::new();
+        let mut running = 0;
+        for rg in cluster.host_home_resource_groups(self) {
+            if rg.hold() {
+                tasks.push(Box::pin(task(rg.id()).map(move |()| {
+                    rg.release();
+                    None
+                })));
+                running += 1;
+            }
+        }
+        if running == 0 {
+            let delay = tokio::time::sleep(cluster.args.poll_delay());
+            tasks.push(Box::pin(delay.map(|()| None)));
+            running += 1;
+        }
+        tasks.push(Box::pin(self.receive_message().map(Some)));
+
+        while let Some(event) = tasks.next().await {
+            match event {
+                None => {
+                    running -= 1;
+                    if running == 0 {
+                        return;
+                    }
+                }
+                Some(message) => {
+                    match message {
+                        // The group may already have a task, if it was added while this host
+                        // was reconnecting:
+                        HostMessage::ResourceGroupAdded(id) => {
+                            let rg = cluster.get_resource_group(&id);
+                            if rg.hold() {
+                                tasks.push(Box::pin(task(rg.id()).map(move |()| {
+                                    rg.release();
+                                    None
+                                })));
+                                running += 1;
+                            }
+                        }
+                        other => warn!("Host {} got unexpected message {other:?}.", self.id()),
+                    }
+                    tasks.push(Box::pin(self.receive_message().map(Some)));
+                }
+            }
+        }
+    }
+
     /// Handle a request from the CLI utility to do an action on this Host.
     pub async fn command(&self, command: HostCommand) {
         self.sender
@@ -136,6 +251,50 @@ impl Host {
             .expect("Sending host message {command} failed");
     }
 
+    /// Tell this host's management task that the resource group `id` was added to the cluster, so
+    /// that it starts managing (or observing) it.
+    pub fn resource_group_added(&self, id: String) {
+        if let Err(e) = self.sender.try_send(HostMessage::ResourceGroupAdded(id)) {
+            warn!(
+                "Host {} could not be told that a resource group was added: {e}",
+                self.id()
+            );
+        }
+    }
+
+    pub fn is_standby(&self) -> bool {
+        self.standby.load(Ordering::Relaxed)
+    }
+
+    pub fn set_standby(&self, standby: bool) {
+        if self.standby.swap(standby, Ordering::Relaxed) != standby {
+            self.events.publish(Event::HostStandby {
+                id: self.id(),
+                standby,
+            });
+        }
+    }
+
+    pub fn is_reachable(&self) -> bool {
+        self.reachable.load(Ordering::Relaxed)
+    }
+
+    /// Record whether the manager could connect to this host's remote agent.
+    pub fn set_reachable(&self, reachable: bool) {
+        self.reachable.store(reachable, Ordering::Relaxed);
+    }
+
+    /// Connect to this host's remote agent, recording whether it is reachable.
+    pub async fn connect(&self) -> std::io::Result<ocf_resource_agent::Client> {
+        let client = get_client(&self.address()).await;
+        self.set_reachable(client.is_ok());
+        client
+    }
+
+    pub fn events(&self) -> &Publisher {
+        &self.events
+    }
+
     pub fn fence_agent(&self) -> &Option<FenceAgent> {
         &self.fence_agent
     }
@@ -155,6 +314,11 @@ impl Host {
     /// Get a unique identifier for this host. Typically, this will just be the hostname, but in
     /// the test environment, where Hosts do not have a unique hostname, the fencing target is used
     /// instead as a unique ID.
+    /// The numbers from the config which describe this host, by the names of the attributes.
+    pub fn attributes(&self) -> &BTreeMap<String, f64> {
+        &self.attributes
+    }
+
     pub fn id(&self) -> String {
         if let Some(FenceAgent::Test(test_args)) = &self.fence_agent {
             test_args.target.to_string()
@@ -3,7 +3,7 @@
 
 //! Observe-only management of a cluster without high-availability.
 
-use {futures::future, log::error};
+use log::error;
 
 use crate::{cluster::Cluster, resource::Location};
 
@@ -13,19 +13,16 @@ use super::*;
 impl Host {
     pub async fn observe(&self, cluster: &Cluster) {
         loop {
-            let client = crate::halo_capnp::get_client(&self.address())
-                .await
-                .expect("TODO: handle error here.");
+            let client = self.connect().await.expect("TODO: handle error here.");
 
-            let futures: Vec<_> = cluster
-                .host_home_resource_groups(self)
-                .map(|rg| self.observe_resource_group(cluster, rg.id(), &client))
-                .collect();
-
-            let _ = future::join_all(futures).await;
+            self.run_home_resource_groups(cluster, |rg| {
+                self.observe_resource_group(cluster, rg, &client)
+            })
+            .await;
+            self.set_reachable(false);
 
             // Once all tasks exited (because an RPC error occurred), just wait a bit and try again:
-            tokio::time::sleep(tokio::time::Duration::from_millis(cluster.args.sleep_time)).await;
+            tokio::time::sleep(cluster.args.poll_delay()).await;
         }
     }
 
@@ -36,10 +33,12 @@ impl Host {
         client: &ocf_resource_agent::Client,
     ) {
         let rg = cluster.get_resource_group(rg);
-        let err = rg
-            .observe_loop(client, false, Location::Home)
+        // The loop only returns without an error once the group is removed from the cluster:
+        if let Err(err) = rg
+            .observe_loop(cluster, client, false, Location::Home)
             .await
-            .unwrap_err();
-        error!("{err:?}");
+        {
+            error!("{err:?}");
+        }
     }
 }
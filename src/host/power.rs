@@ -7,6 +7,7 @@ use std::{
     fmt,
     io::{Read, Write},
     process::{Command, Stdio},
+    sync::atomic::Ordering,
 };
 
 use {
@@ -31,6 +32,7 @@ impl Error for FenceError {}
 pub enum FenceCommand {
     On,
     Off,
+    Reboot,
     Status,
 }
 
@@ -39,6 +41,7 @@ impl fmt::Display for FenceCommand {
         match self {
             FenceCommand::On => write!(f, "on"),
             FenceCommand::Off => write!(f, "off"),
+            FenceCommand::Reboot => write!(f, "reboot"),
             FenceCommand::Status => write!(f, "status"),
         }
     }
@@ -63,33 +66,54 @@ impl FenceAgent {
     /// since the hostname is the only needed parameter, and that is already stored on the Host
     /// object. However, the other fence agents need additional parameters.
     pub fn from_params(agent: &str, params: &Option<HashMap<String, String>>) -> Self {
+        Self::try_from_params(agent, params)
+            .unwrap_or_else(|e| panic!("Could not load config: {e}"))
+    }
+
+    /// Like from_params(), but returns an error describing what is wrong with the agent name or
+    /// parameters instead of panicking.
+    pub fn try_from_params(
+        agent: &str,
+        params: &Option<HashMap<String, String>>,
+    ) -> Result<Self, String> {
         if agent == "powerman" {
-            return Self::Powerman;
+            return Ok(Self::Powerman);
         }
 
-        let params = params
-            .as_ref()
-            .expect("Could not load config: Fence params are needed but not set.");
+        let params = || {
+            params
+                .as_ref()
+                .ok_or_else(|| "Fence params are needed but not set.".to_string())
+        };
 
         match agent {
             "redfish" => {
+                let params = params()?;
                 let Some(user) = params.get("username") else {
-                    panic!("Redfish username needed but not in config parameters");
+                    return Err("Redfish username needed but not in config parameters".into());
                 };
                 let Some(pass) = params.get("password") else {
-                    panic!("Redfish password needed but not in config parameters");
-                };
-                Self::Redfish(RedfishArgs::new(user.to_string(), pass.to_string()))
-            }
-            "fence_test" => {
-                let Some(args) = TestFenceArgs::new(params) else {
-                    panic!("Test fence agent is missing needed parameters");
+                    return Err("Redfish password needed but not in config parameters".into());
                 };
-                Self::Test(args)
-            }
-            other => {
-                panic!("Could not load config: Unknown fence agent \"{other}\".");
+                Ok(Self::Redfish(RedfishArgs::new(
+                    user.to_string(),
+                    pass.to_string(),
+                )))
             }
+            "fence_test" => match TestFenceArgs::new(params()?) {
+                Some(args) => Ok(Self::Test(args)),
+                None => Err("Test fence agent is missing needed parameters".into()),
+            },
+            other => Err(format!("Unknown fence agent \"{other}\".")),
+        }
+    }
+
+    /// The name that the config gives this fence agent by, e.g. "redfish".
+    pub fn name(&self) -> &str {
+        match self {
+            FenceAgent::Powerman => "powerman",
+            FenceAgent::Redfish(_) => "redfish",
+            FenceAgent::Test(_) => "fence_test",
         }
     }
 
@@ -162,14 +186,47 @@ impl fmt::Debug for RedfishArgs {
 }
 
 impl super::Host {
-    /// Attempt to power on or off this host.
+    /// Whether the manager has stopped trying to fence this host, since it failed too many times
+    /// in a row. Its resources stay stopped until an admin resets the breaker.
+    pub fn is_fence_failed(&self) -> bool {
+        self.fence_breaker_open.load(Ordering::Relaxed)
+    }
+
+    /// How many times in a row the manager has failed to fence this host.
+    pub fn fence_failures(&self) -> u32 {
+        self.fence_failures.load(Ordering::Relaxed)
+    }
+
+    /// Record that fencing this host succeeded.
+    pub fn fence_succeeded(&self) {
+        self.fence_failures.store(0, Ordering::Relaxed);
+    }
+
+    /// Record that fencing this host failed, opening the breaker once it has failed `attempts`
+    /// times in a row. Returns whether this failure opened it.
+    pub fn fence_failed(&self, attempts: u32) -> bool {
+        let failures = self.fence_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        failures >= attempts && !self.fence_breaker_open.swap(true, Ordering::Relaxed)
+    }
+
+    /// Let the manager try to fence this host again, with a fresh count of failures. Returns
+    /// whether the breaker was open.
+    pub fn reset_fence_breaker(&self) -> bool {
+        self.fence_failures.store(0, Ordering::Relaxed);
+        self.fence_breaker_open.swap(false, Ordering::Relaxed)
+    }
+
+    /// Attempt to power on, off, or reboot this host.
     ///
-    /// If self.fence_agent is not set, then panics.
+    /// If self.fence_agent is not set, then returns an error.
     ///
     /// This is the blocking variant - it is safe to use in commands, but should not be called from
     /// the management service.
     pub fn do_fence(&self, command: FenceCommand) -> Result<(), Box<dyn Error>> {
-        let agent = self.fence_agent.as_ref().unwrap();
+        let agent = self
+            .fence_agent
+            .as_ref()
+            .ok_or("no fence agent is configured")?;
 
         if matches!(command, FenceCommand::Status) {
             panic!("Please use is_powered_on() for power status.");
@@ -203,7 +260,39 @@ impl super::Host {
     /// Do a fence operation using the non-blocking APIs for spawning a command and waiting for its
     /// result. Suitable to be called by the management service.
     pub async fn do_fence_nonblocking(&self, command: FenceCommand) -> Result<(), Box<dyn Error>> {
-        let agent = self.fence_agent.as_ref().unwrap();
+        self.run_fence_agent_nonblocking(command).await.map(|_| ())
+    }
+
+    /// The non-blocking variant of is_powered_on(), suitable to be called by the management
+    /// service.
+    pub async fn is_powered_on_nonblocking(&self) -> Result<bool, Box<dyn Error>> {
+        let out = self
+            .run_fence_agent_nonblocking(FenceCommand::Status)
+            .await?;
+        parse_power_status(&out)
+    }
+
+    /// Power this host off, then check that it really is off. Only once this succeeds is it safe
+    /// to start this host's resources anywhere else.
+    pub async fn fence_off_confirmed(&self) -> Result<(), Box<dyn Error>> {
+        self.do_fence_nonblocking(FenceCommand::Off).await?;
+
+        if self.is_powered_on_nonblocking().await? {
+            return Err("the host is still powered on after fencing it".into());
+        }
+
+        Ok(())
+    }
+
+    /// Run the fence agent for `command` on this host, returning its output if it succeeds.
+    async fn run_fence_agent_nonblocking(
+        &self,
+        command: FenceCommand,
+    ) -> Result<String, Box<dyn Error>> {
+        let agent = self
+            .fence_agent
+            .as_ref()
+            .ok_or("no fence agent is configured")?;
 
         let mut child = tokio::process::Command::new(agent.get_executable())
             .stdin(Stdio::piped())
@@ -231,7 +320,7 @@ impl super::Host {
         debug!("out: {out}");
 
         if status.success() {
-            Ok(())
+            Ok(out)
         } else {
             Err(Box::new(FenceError {}))
         }
@@ -239,9 +328,12 @@ impl super::Host {
 
     /// Attempt to check this host's power status.
     ///
-    /// If self.fence_agent is not set, then panics.
+    /// If self.fence_agent is not set, then returns an error.
     pub fn is_powered_on(&self) -> Result<bool, Box<dyn Error>> {
-        let agent = self.fence_agent.as_ref().unwrap();
+        let agent = self
+            .fence_agent
+            .as_ref()
+            .ok_or("no fence agent is configured")?;
 
         let mut child = Command::new(agent.get_executable())
             .stdin(Stdio::piped())
@@ -264,12 +356,17 @@ impl super::Host {
         let mut out = String::new();
         child.stdout.unwrap().read_to_string(&mut out)?;
 
-        if out.contains("is ON") {
-            Ok(true)
-        } else if out.contains("is OFF") {
-            Ok(false)
-        } else {
-            Err(Box::new(FenceError {}))
-        }
+        parse_power_status(&out)
+    }
+}
+
+/// Interpret the output of a fence agent's status action.
+fn parse_power_status(out: &str) -> Result<bool, Box<dyn Error>> {
+    if out.contains("is ON") {
+        Ok(true)
+    } else if out.contains("is OFF") {
+        Ok(false)
+    } else {
+        Err(Box::new(FenceError {}))
     }
 }
@@ -1,11 +1,13 @@
 // SPDX-License-Identifier: MIT
 // Copyright 2025. Triad National Security, LLC.
 
+pub mod agent;
 pub mod cluster;
 pub mod commands;
 pub mod config;
 pub mod halo_capnp;
 pub mod host;
+pub mod logging;
 pub mod manager;
 pub mod remote;
 pub mod resource;
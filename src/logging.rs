@@ -0,0 +1,77 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+//! Setup of the log output of the daemons.
+
+use std::io::Write;
+
+use crate::commands::{Handle, HandledResult};
+
+/// The format of each line of log output.
+#[derive(clap::ValueEnum, Debug, Default, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    /// Human-readable lines with a timestamp, level, and message.
+    #[default]
+    Text,
+
+    /// One JSON object per line, with `timestamp`, `level`, `target`, and `message` fields, for
+    /// shipping logs to an aggregator.
+    Json,
+}
+
+/// Set up logging to stderr, or to `log_file` if given. The `HALO_LOG` environment variable sets
+/// the log level, or if it is unset, `RUST_LOG`; otherwise it is `debug` in verbose mode, and
+/// `warn` normally. Either takes filters such as `warn,halo_lib::manager::http=debug`.
+///
+/// Events of the `tracing` crate are logged like any others. Its spans, around each HTTP request,
+/// agent RPC, and pass of the management loops, are logged at the trace level when they are
+/// created, and as they are entered and exited with the target `tracing::span::active`.
+///
+/// The log file is opened for appending, and its directory is created if needed. Each record is
+/// written with a single write, so lines from concurrent tasks do not interleave.
+pub fn init(verbose: bool, format: LogFormat, log_file: Option<&str>) -> HandledResult<()> {
+    let default_log_level = if verbose { "debug" } else { "warn" };
+    let filter = if std::env::var_os("HALO_LOG").is_some() {
+        "HALO_LOG"
+    } else {
+        "RUST_LOG"
+    };
+    let mut builder = env_logger::Builder::from_env(
+        env_logger::Env::default().filter_or(filter, default_log_level),
+    );
+
+    if format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let line = serde_json::json!({
+                "timestamp": buf.timestamp().to_string(),
+                "level": record.level().as_str().to_lowercase(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            writeln!(buf, "{line}")
+        });
+    }
+
+    if let Some(path) = log_file {
+        builder.target(env_logger::Target::Pipe(Box::new(open_log_file(path)?)));
+    }
+
+    builder.init();
+    Ok(())
+}
+
+fn open_log_file(path: &str) -> HandledResult<std::fs::File> {
+    if let Some(dir) = std::path::Path::new(path).parent() {
+        if !dir.as_os_str().is_empty() {
+            std::fs::create_dir_all(dir).handle_err(|e| {
+                eprintln!("Could not create directory for log file '{path}': {e}")
+            })?;
+        }
+    }
+
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .handle_err(|e| eprintln!("Could not open log file '{path}': {e}"))
+}
@@ -0,0 +1,52 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+//! Notification of changes in the state of the cluster, for clients of the `/events` stream.
+//!
+//! Events are published onto a broadcast channel which every host in a cluster shares. A
+//! subscriber which falls too far behind misses the oldest events, rather than holding up the
+//! management tasks which publish them.
+
+use tokio::sync::broadcast;
+
+use crate::resource::{ResourceStatus, TargetState};
+
+/// How many events are kept for a subscriber which has not received them yet.
+const CAPACITY: usize = 256;
+
+/// A change in the state of the cluster.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// A resource changed status.
+    ResourceStatus { id: String, status: ResourceStatus },
+
+    /// A resource group was managed or unmanaged.
+    ResourceManaged { id: String, managed: bool },
+
+    /// A resource was enabled or disabled.
+    ResourceTargetState { id: String, target: TargetState },
+
+    /// A host was put on standby, or taken off of it.
+    HostStandby { id: String, standby: bool },
+}
+
+/// The sending side of the event channel. Cloning a Publisher gives another handle on the same
+/// channel.
+#[derive(Debug, Clone)]
+pub struct Publisher(broadcast::Sender<Event>);
+
+impl Publisher {
+    pub fn new() -> Self {
+        Self(broadcast::channel(CAPACITY).0)
+    }
+
+    /// Send `event` to every current subscriber. It is not an error for there to be none.
+    pub fn publish(&self, event: Event) {
+        let _ = self.0.send(event);
+    }
+
+    /// Receive the events published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.0.subscribe()
+    }
+}
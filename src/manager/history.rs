@@ -0,0 +1,331 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+//! A record of the actions that admins have requested on resources, for `halo history`.
+//!
+//! Only the most recent entries are kept, so that the history does not grow without bound in a
+//! long-running manager.
+//!
+//! The times of entries come from the manager's clock, which is also the clock that a `since`
+//! cutoff is measured against, so a client whose clock is skewed still sees the right entries.
+
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// One action requested on a resource, and how it turned out.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// When the action finished, in seconds since the Unix epoch.
+    pub time: u64,
+
+    pub resource: String,
+
+    /// The action requested, e.g. "unmanage", or "migrate to oss01".
+    pub action: String,
+
+    /// "ok" if the action succeeded, otherwise the reason that it did not.
+    pub result: String,
+}
+
+/// The most recent actions requested on resources, oldest first.
+#[derive(Debug)]
+pub struct History {
+    entries: Mutex<VecDeque<Entry>>,
+    capacity: usize,
+}
+
+impl History {
+    /// Create a History which keeps the last `capacity` entries. With a capacity of 0, nothing is
+    /// recorded.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+        }
+    }
+
+    /// Record that `action` was requested on `resource`, with the given `result`, dropping the
+    /// oldest entry if the history is full.
+    pub fn record(&self, resource: &str, action: &str, result: Result<(), &str>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let entry = Entry {
+            time: now(),
+            resource: resource.to_string(),
+            action: action.to_string(),
+            result: match result {
+                Ok(()) => "ok".to_string(),
+                Err(reason) => reason.to_string(),
+            },
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// A copy of the entries in the history, oldest first.
+    pub fn entries(&self) -> Vec<Entry> {
+        self.entries.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// A copy of the entries in the history from `cutoff` seconds since the Unix epoch or later,
+    /// oldest first.
+    pub fn entries_since(&self, cutoff: u64) -> Vec<Entry> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|entry| entry.time >= cutoff)
+            .cloned()
+            .collect()
+    }
+}
+
+/// The current time by the manager's clock, in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0)
+}
+
+/// The start of a window of history to list, as given to `GET /history?since=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Since {
+    /// A point in time, in seconds since the Unix epoch.
+    Time(u64),
+
+    /// This long before now.
+    Ago(Duration),
+}
+
+impl Since {
+    /// Parse either an RFC 3339 timestamp, e.g. "2025-01-31T12:00:00Z", or a duration made of
+    /// whole numbers of days, hours, minutes, or seconds, e.g. "10m", "2h", or "1h30m".
+    pub fn parse(s: &str) -> Result<Self, String> {
+        if s.contains('-') {
+            parse_time(s).map(Since::Time)
+        } else {
+            parse_duration(s).map(Since::Ago)
+        }
+    }
+
+    /// The earliest time in the window, in seconds since the Unix epoch, given the time `now`.
+    pub fn cutoff(&self, now: u64) -> u64 {
+        match self {
+            Since::Time(time) => *time,
+            Since::Ago(ago) => now.saturating_sub(ago.as_secs()),
+        }
+    }
+}
+
+/// Parse a duration such as "90s", "10m", "2h", "1d", or "1h30m".
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration '{s}': expected e.g. 30s, 10m, 2h, or 1d");
+
+    let mut secs: u64 = 0;
+    let mut rest = s;
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let number: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+        let unit = match rest[digits..].chars().next() {
+            Some('s') => 1,
+            Some('m') => 60,
+            Some('h') => 3600,
+            Some('d') => 86400,
+            _ => return Err(invalid()),
+        };
+        secs = number
+            .checked_mul(unit)
+            .and_then(|n| secs.checked_add(n))
+            .ok_or_else(invalid)?;
+        rest = &rest[digits + 1..];
+    }
+
+    Ok(Duration::from_secs(secs))
+}
+
+/// Parse an RFC 3339 timestamp, e.g. "2025-01-31T12:00:00Z" or "2025-01-31T06:00:00.5-06:00",
+/// into seconds since the Unix epoch. Fractions of a second are dropped.
+pub fn parse_time(s: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid time '{s}': expected e.g. 2025-01-31T12:00:00Z");
+    let number = |field: Option<&str>, len: usize| -> Result<i64, String> {
+        match field {
+            Some(field) if field.len() == len && field.bytes().all(|b| b.is_ascii_digit()) => {
+                field.parse().map_err(|_| invalid())
+            }
+            _ => Err(invalid()),
+        }
+    };
+
+    let (date, time) = s.split_once(['T', 't', ' ']).ok_or_else(invalid)?;
+    let mut date = date.split('-');
+    let year = number(date.next(), 4)?;
+    let month = number(date.next(), 2)?;
+    let day = number(date.next(), 2)?;
+    if date.next().is_some() {
+        return Err(invalid());
+    }
+
+    // Split the offset from UTC off the end of the time of day:
+    let (time, offset) = if let Some(time) = time.strip_suffix(['Z', 'z']) {
+        (time, 0)
+    } else {
+        let sign_at = time.rfind(['+', '-']).ok_or_else(invalid)?;
+        let (time, offset) = time.split_at(sign_at);
+        let sign = if offset.starts_with('-') { -1 } else { 1 };
+        let (hours, minutes) = offset[1..].split_once(':').ok_or_else(invalid)?;
+        let offset = number(Some(hours), 2)? * 3600 + number(Some(minutes), 2)? * 60;
+        (time, sign * offset)
+    };
+    let time = match time.split_once('.') {
+        Some((time, fraction))
+            if !fraction.is_empty() && fraction.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            time
+        }
+        Some(_) => return Err(invalid()),
+        None => time,
+    };
+    let mut time = time.split(':');
+    let hour = number(time.next(), 2)?;
+    let minute = number(time.next(), 2)?;
+    let second = number(time.next(), 2)?;
+    if time.next().is_some() {
+        return Err(invalid());
+    }
+
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        _ => return Err(invalid()),
+    };
+    // A second of 60 is allowed for a leap second, and counted as the next one:
+    if day < 1 || day > days_in_month || hour > 23 || minute > 59 || second > 60 {
+        return Err(invalid());
+    }
+
+    // Convert the date to days since the epoch in the proleptic Gregorian calendar. See
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    let secs = days * 86400 + hour * 3600 + minute * 60 + second - offset;
+    u64::try_from(secs).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_is_bounded() {
+        let history = History::new(2);
+        history.record("zpool_0", "unmanage", Ok(()));
+        history.record("zpool_1", "start", Err("timed out"));
+        history.record("zpool_2", "stop", Ok(()));
+
+        let entries = history.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].resource, "zpool_1");
+        assert_eq!(entries[0].result, "timed out");
+        assert_eq!(entries[1].resource, "zpool_2");
+        assert_eq!(entries[1].result, "ok");
+
+        let history = History::new(0);
+        history.record("zpool_0", "unmanage", Ok(()));
+        assert!(history.entries().is_empty());
+    }
+
+    #[test]
+    fn test_entries_since() {
+        let history = History::new(3);
+        history.record("zpool_0", "unmanage", Ok(()));
+        history.record("zpool_1", "manage", Ok(()));
+        history.entries.lock().unwrap()[0].time = 100;
+        history.entries.lock().unwrap()[1].time = 200;
+
+        let entries = history.entries_since(150);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].resource, "zpool_1");
+        assert_eq!(history.entries_since(100).len(), 2);
+        assert!(history.entries_since(201).is_empty());
+    }
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(
+            Since::parse("10m"),
+            Ok(Since::Ago(Duration::from_secs(600)))
+        );
+        assert_eq!(
+            Since::parse("2h"),
+            Ok(Since::Ago(Duration::from_secs(7200)))
+        );
+        assert_eq!(
+            Since::parse("1d1h30m5s"),
+            Ok(Since::Ago(Duration::from_secs(91805)))
+        );
+        assert_eq!(Since::parse("10m").unwrap().cutoff(1000), 400);
+        assert_eq!(Since::parse("1d").unwrap().cutoff(1000), 0);
+
+        assert_eq!(
+            Since::parse("2025-01-31T12:00:00Z"),
+            Ok(Since::Time(1738324800))
+        );
+        assert_eq!(
+            Since::parse("2025-01-31T12:00:00Z").unwrap().cutoff(0),
+            1738324800
+        );
+
+        for invalid in ["", "10", "m", "10x", "10m5", "-5m"] {
+            assert!(Since::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("1970-01-01T00:00:00Z"), Ok(0));
+        assert_eq!(parse_time("2000-02-29T00:00:00Z"), Ok(951782400));
+        assert_eq!(parse_time("2025-01-31T12:00:00Z"), Ok(1738324800));
+        assert_eq!(parse_time("2025-01-31t12:00:00.250z"), Ok(1738324800));
+        assert_eq!(parse_time("2025-01-31T06:00:00-06:00"), Ok(1738324800));
+        assert_eq!(parse_time("2025-01-31T13:30:00+01:30"), Ok(1738324800));
+        assert_eq!(parse_time("2025-02-28T23:59:59Z"), Ok(1740787199));
+
+        for invalid in [
+            "2025-01-31",
+            "2025-01-31T12:00:00",
+            "2025-1-31T12:00:00Z",
+            "2025-02-29T12:00:00Z",
+            "2025-01-31T24:00:00Z",
+            "2025-01-31T12:00:00.Z",
+            "2025-01-31T12:00:00+0100",
+            "1969-12-31T23:59:59Z",
+        ] {
+            assert!(parse_time(invalid).is_err(), "{invalid}");
+        }
+    }
+}
@@ -1,58 +1,468 @@
 // SPDX-License-Identifier: MIT
 // Copyright 2025. Triad National Security, LLC.
 
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use {
     axum::{
-        extract::Path,
-        http::StatusCode,
-        routing::{get, patch, post},
+        extract::{
+            ws::{self, WebSocket, WebSocketUpgrade},
+            Extension, MatchedPath, Path, Query, Request,
+        },
+        http::{header, HeaderValue, StatusCode},
+        middleware::{self, Next},
+        response::{
+            sse::{self, KeepAlive, Sse},
+            IntoResponse, Response,
+        },
+        routing::{get, post},
         Json, Router,
     },
-    log::warn,
+    futures::{Stream, StreamExt},
+    log::{debug, warn},
     serde::{Deserialize, Serialize},
+    tokio::sync::broadcast,
+    tokio_util::sync::CancellationToken,
+    tracing::Instrument,
 };
 
 use crate::{
+    agent::Schema,
     cluster::Cluster,
     host::HostCommand,
-    resource::{Resource, ResourceStatus},
+    manager::{events::Event, history, placement::GroupState, rate_limit::RateLimiter, shutdown},
+    resource::{Location, Resource, ResourceGroup, ResourceStatus, TargetState},
 };
 
 /// Main entrypoint for the command server.
 ///
-/// This listens for commands on a unix socket and acts on them.
-pub async fn server_main(listener: tokio::net::UnixListener, cluster: Arc<Cluster>) {
+/// This listens for commands on a unix socket or TCP listener and acts on them. Once a shutdown is
+/// requested, it stops accepting connections, and returns when the in-flight requests finish.
+pub async fn server_main<L>(listener: L, cluster: Arc<Cluster>)
+where
+    L: axum::serve::Listener,
+    L::Addr: std::fmt::Debug,
+{
+    let limiter = (cluster.args.rate_limit > 0).then(|| {
+        Arc::new(RateLimiter::new(
+            cluster.args.rate_limit,
+            cluster.args.rate_limit_burst,
+        ))
+    });
     let server = Router::new()
+        .route("/", get(is_manager_alive))
+        .route(
+            "/ready",
+            get({
+                let cluster = Arc::clone(&cluster);
+                || is_manager_ready(cluster)
+            }),
+        )
         .route(
             "/status",
             get({
                 let cluster = Arc::clone(&cluster);
-                || get_status(cluster)
+                |query| get_status(query, cluster)
+            }),
+        )
+        .route(
+            "/metrics",
+            get({
+                let cluster = Arc::clone(&cluster);
+                || get_metrics(cluster)
+            }),
+        )
+        .route(
+            "/summary",
+            get({
+                let cluster = Arc::clone(&cluster);
+                || get_summary(cluster)
+            }),
+        )
+        .route(
+            "/history",
+            get({
+                let cluster = Arc::clone(&cluster);
+                |query| get_history(query, cluster)
+            }),
+        )
+        .route(
+            "/simulate",
+            get({
+                let cluster = Arc::clone(&cluster);
+                |query| get_simulation(query, cluster)
+            }),
+        )
+        .route(
+            "/mode",
+            get({
+                let cluster = Arc::clone(&cluster);
+                || get_mode(cluster)
+            })
+            .post({
+                let cluster = Arc::clone(&cluster);
+                |payload| set_mode(payload, cluster)
+            }),
+        )
+        .route(
+            "/maintenance",
+            get({
+                let cluster = Arc::clone(&cluster);
+                || get_maintenance(cluster)
+            })
+            .post({
+                let cluster = Arc::clone(&cluster);
+                |payload| set_maintenance(payload, cluster)
+            }),
+        )
+        .route(
+            "/config",
+            get({
+                let cluster = Arc::clone(&cluster);
+                || get_config(cluster)
+            }),
+        )
+        .route(
+            "/events",
+            get({
+                let cluster = Arc::clone(&cluster);
+                || get_events(cluster)
+            }),
+        )
+        .route(
+            "/ws",
+            get({
+                let cluster = Arc::clone(&cluster);
+                let limiter = limiter.clone();
+                |upgrade, access| get_ws(upgrade, access, cluster, limiter)
+            }),
+        )
+        .route(
+            "/resources",
+            get({
+                let cluster = Arc::clone(&cluster);
+                |query| get_resource_ids(query, cluster)
+            })
+            .patch({
+                let cluster = Arc::clone(&cluster);
+                |payload| set_managed_batch(payload, cluster)
             }),
         )
         .route(
             "/resources/{id}",
-            patch({
+            get({
+                let cluster = Arc::clone(&cluster);
+                |path| get_resource(path, cluster)
+            })
+            .patch({
                 let cluster = Arc::clone(&cluster);
                 |path, payload| set_managed(path, payload, cluster)
             }),
         )
+        .route(
+            "/resources/{id}/params",
+            post({
+                let cluster = Arc::clone(&cluster);
+                |path, payload| set_params(path, payload, cluster)
+            }),
+        )
+        .route(
+            "/start/{id}",
+            post({
+                let cluster = Arc::clone(&cluster);
+                |path, payload| resource_action(ResourceAction::Start, path, payload, cluster)
+            }),
+        )
+        .route(
+            "/stop/{id}",
+            post({
+                let cluster = Arc::clone(&cluster);
+                |path, payload| resource_action(ResourceAction::Stop, path, payload, cluster)
+            }),
+        )
+        .route(
+            "/cancel/{id}",
+            post({
+                let cluster = Arc::clone(&cluster);
+                |path, payload| cancel(path, payload, cluster)
+            }),
+        )
+        .route(
+            "/target/{id}",
+            post({
+                let cluster = Arc::clone(&cluster);
+                |path, payload| set_target_state(path, payload, cluster)
+            }),
+        )
+        .route(
+            "/migrate/{id}",
+            post({
+                let cluster = Arc::clone(&cluster);
+                |path, payload| migrate(path, payload, cluster)
+            }),
+        )
+        .route(
+            "/groups/{name}/migrate",
+            post({
+                let cluster = Arc::clone(&cluster);
+                |path, payload| migrate_group(path, payload, cluster)
+            }),
+        )
         .route(
             "/hosts/{id}",
             post({
                 let cluster = Arc::clone(&cluster);
                 |path, payload| host_post(path, payload, cluster)
             }),
-        );
+        )
+        .layer(middleware::from_fn(move |request, next| {
+            limit_rate(request, next, limiter.clone())
+        }))
+        .layer(middleware::from_fn({
+            let cluster = Arc::clone(&cluster);
+            move |request, next| check_auth_token(request, next, Arc::clone(&cluster))
+        }))
+        .layer(middleware::from_fn(log_request));
+
+    axum::serve(listener, server)
+        .with_graceful_shutdown(super::shutdown::shutdown_requested())
+        .await
+        .unwrap();
+}
+
+/// The paths which health checks use, which are served without an auth token, so that a health
+/// checker does not need to know it.
+const UNAUTHENTICATED_PATHS: [&str; 2] = ["/", "/ready"];
+
+/// Whether a request only reads from the manager, so that the read-only token is enough for it.
+/// Every request which changes something uses another method than GET, so a request is classified
+/// by its method alone.
+fn is_read_request(method: &axum::http::Method) -> bool {
+    matches!(*method, axum::http::Method::GET | axum::http::Method::HEAD)
+}
+
+/// Marks a request which carries only the read-only token, for handlers which go on to receive
+/// requests of their own that may change something, i.e. `/ws`.
+#[derive(Debug, Clone, Copy)]
+struct ReadOnlyAccess;
+
+/// Reject requests which do not carry the manager's auth token, if it has one, with a 401.
+///
+/// A request which only reads from the manager may carry the read-only token instead. One which
+/// changes something and carries only the read-only token is rejected with a 403. When the two
+/// tokens are the same, the auth token takes precedence, so the token gives full access.
+async fn check_auth_token(mut request: Request, next: Next, cluster: Arc<Cluster>) -> Response {
+    let Some(token) = cluster.args.auth_token.as_deref() else {
+        return next.run(request).await;
+    };
+    if UNAUTHENTICATED_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let given = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let read_only = cluster.args.read_only_token.as_deref();
+    match given {
+        Some(given) if tokens_match(given.as_bytes(), token.as_bytes()) => next.run(request).await,
+        Some(given)
+            if read_only.is_some_and(|ro| tokens_match(given.as_bytes(), ro.as_bytes())) =>
+        {
+            if is_read_request(request.method()) {
+                request.extensions_mut().insert(ReadOnlyAccess);
+                next.run(request).await
+            } else {
+                (
+                    StatusCode::FORBIDDEN,
+                    "the read-only token can not be used to change anything\n",
+                )
+                    .into_response()
+            }
+        }
+        _ => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            "missing or wrong auth token\n",
+        )
+            .into_response(),
+    }
+}
+
+/// Reject requests which change something with a 429, once their endpoint has been requested more
+/// often than `--rate-limit` allows, with a Retry-After header saying how many seconds until it
+/// may be requested again. Requests which only read from the manager are never limited.
+async fn limit_rate(request: Request, next: Next, limiter: Option<Arc<RateLimiter>>) -> Response {
+    let Some(limiter) = limiter.filter(|_| !is_read_request(request.method())) else {
+        return next.run(request).await;
+    };
+    let path = match request.extensions().get::<MatchedPath>() {
+        Some(path) => path.as_str().to_string(),
+        None => request.uri().path().to_string(),
+    };
+    let endpoint = format!("{} {path}", request.method());
+
+    match check_rate_limit(&limiter, &endpoint) {
+        Ok(()) => next.run(request).await,
+        Err((secs, message)) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, secs.to_string())],
+            format!("{message}\n"),
+        )
+            .into_response(),
+    }
+}
+
+/// Count a request to `endpoint` against its rate limit. If it is over the limit, return how many
+/// seconds until it may be requested again, and a message saying so.
+fn check_rate_limit(limiter: &RateLimiter, endpoint: &str) -> Result<(), (u64, String)> {
+    limiter.check(endpoint, Instant::now()).map_err(|wait| {
+        let secs = wait.as_secs_f64().ceil().max(1.0) as u64;
+        warn!("Rejected a request to {endpoint}, which is over its rate limit.");
+        (
+            secs,
+            format!("too many requests to {endpoint}; try again in {secs}s"),
+        )
+    })
+}
+
+/// Compare two tokens in time that depends only on their lengths, not on how much of them matches,
+/// so that a client can not guess the token a byte at a time by timing the replies.
+fn tokens_match(given: &[u8], token: &[u8]) -> bool {
+    if given.len() != token.len() {
+        return false;
+    }
+    given
+        .iter()
+        .zip(token)
+        .fold(0, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+/// The header which carries the ID of a request, so that the manager's log lines about a request
+/// can be found from the reply, or from the client's own logs.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Log each request that the manager serves, with its ID, method, path, status, and how long it
+/// took. This is at debug level, except for requests which fail with a server error.
+///
+/// Only the path of a request is logged, never its query or body. A client may choose the ID by
+/// sending it in the request ID header; otherwise, requests are numbered from 1.
+async fn log_request(mut request: Request, next: Next) -> Response {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= 64 && id.chars().all(|c| c.is_ascii_graphic()))
+        .map(str::to_string)
+        .unwrap_or_else(|| NEXT_ID.fetch_add(1, Ordering::Relaxed).to_string());
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    // The ID was checked to be printable ASCII above, so it is a valid header value:
+    let header = HeaderValue::from_str(&id).unwrap();
+    request
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, header.clone());
 
-    axum::serve(listener, server).await.unwrap();
+    debug!("HTTP request {id}: {method} {path}");
+    let span = tracing::trace_span!("http_request", %id, %method, %path);
+    let start = Instant::now();
+    let mut response = next.run(request).instrument(span).await;
+    let status = response.status();
+    let elapsed = start.elapsed();
+    if status.is_server_error() {
+        warn!("HTTP request {id}: {method} {path} -> {status} in {elapsed:?}");
+    } else {
+        debug!("HTTP request {id}: {method} {path} -> {status} in {elapsed:?}");
+    }
+
+    response.headers_mut().insert(REQUEST_ID_HEADER, header);
+    response
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ClusterJson {
     pub resources: Vec<ResourceJson>,
+
+    /// How many resources matched the query, of which `resources` holds the requested page.
+    #[serde(default)]
+    pub total: usize,
+
+    #[serde(default)]
+    pub hosts: Vec<HostJson>,
+
+    #[serde(default)]
+    pub groups: Vec<GroupJson>,
+
+    /// Whether the cluster has quorum, if the config sets a quorum policy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quorum: Option<QuorumJson>,
+
+    /// Which version of the config file is in effect: 1 until the config is reloaded.
+    #[serde(default)]
+    pub config_generation: u64,
+
+    /// When maintenance mode was switched on, in seconds since the Unix epoch, if it is on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintenance: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct QuorumJson {
+    pub has_quorum: bool,
+
+    /// How many hosts the manager is connected to.
+    pub reachable: usize,
+
+    /// How many hosts the cluster is expected to have.
+    pub expected: usize,
+
+    /// The fewest reachable hosts that can have quorum.
+    pub needed: usize,
+
+    /// What happens when exactly half of the expected hosts are reachable: "deny", "allow", or
+    /// "first_host".
+    pub tiebreak: String,
+}
+
+/// A named group of resources from the config.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GroupJson {
+    pub name: String,
+    pub members: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HostJson {
+    pub id: String,
+
+    /// Whether the host has been put on standby, so that resources are not started on it.
+    pub standby: bool,
+
+    /// Whether the manager is connected to the host's remote agent.
+    #[serde(default)]
+    pub reachable: bool,
+
+    /// Whether the manager has stopped trying to fence the host, since it failed too many times in
+    /// a row, until an admin resets its fencing breaker.
+    #[serde(default)]
+    pub fence_failed: bool,
+
+    /// How many times in a row the manager has failed to fence the host.
+    #[serde(default)]
+    pub fence_failures: u32,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -63,105 +473,3017 @@ pub struct ResourceJson {
     pub status: String,
     pub comment: Option<String>,
     pub managed: bool,
+
+    /// Whether the manager keeps this resource running or stopped while it is managed.
+    #[serde(default)]
+    pub target_state: TargetState,
+
+    /// The host that this resource prefers to run on.
+    pub home_node: String,
+
+    /// The host that this resource is running on, if it is running.
+    #[serde(default)]
+    pub location: Option<String>,
+
+    /// The hosts that this resource may be started on.
+    pub allowed_nodes: Vec<String>,
+
+    /// The resources, in any resource group, which must be running before this one is started.
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+
+    /// The names of the groups from the config that this resource is a member of.
+    #[serde(default)]
+    pub groups: Vec<String>,
+
+    /// How many times in a row the manager failed to start or stop this resource.
+    #[serde(default)]
+    pub retries: u32,
+
+    /// When the manager will next try to start or stop this resource after a failure, in seconds
+    /// since the Unix epoch. None once it has given up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_retry: Option<u64>,
+
+    /// How many seconds the resource agent may take to start this resource.
+    #[serde(default)]
+    pub start_timeout: u64,
+
+    /// How many seconds the resource agent may take to stop this resource.
+    #[serde(default)]
+    pub stop_timeout: u64,
+
+    /// The colocation and anti-affinity constraints that this resource's group breaks where it is
+    /// running, e.g. "must not run on the same host as ost1".
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
 }
 
-impl ResourceJson {
-    fn build(res: &Resource, managed: bool) -> Self {
-        let mut comment = None;
+/// Describe `status` the way that it is shown to users: as a name, and for a status which has a
+/// reason, a comment.
+fn describe_status(status: ResourceStatus) -> (String, Option<String>) {
+    (status.to_string(), status.comment())
+}
 
-        let status = match *res.status.lock().unwrap() {
-            ResourceStatus::Unknown(ref reason) => {
-                comment = Some(reason.clone());
-                "Unknown"
-            }
-            ResourceStatus::Error(ref reason) => {
-                comment = Some(reason.clone());
-                "Error"
-            }
-            ResourceStatus::Stopped => "Stopped",
-            ResourceStatus::RunningOnAway => "Running (Failed Over)",
-            ResourceStatus::RunningOnHome => "Running",
-        }
-        .to_string();
+impl ResourceJson {
+    fn build(cluster: &Cluster, rg: &ResourceGroup, res: &Resource) -> Self {
+        let (status, comment) = describe_status(rg.reported_status(res));
+        let retry = res.retry();
+        let timeouts = res.timeouts();
 
         Self {
             id: res.id.clone(),
             kind: res.kind.clone(),
-            parameters: res.parameters.clone(),
+            parameters: res.parameters(),
             status,
             comment,
-            managed,
+            managed: rg.get_managed(),
+            target_state: res.target_state(),
+            home_node: rg.home_node().id(),
+            location: res
+                .location()
+                .and_then(|loc| res.node(loc))
+                .map(|host| host.id()),
+            allowed_nodes: rg.allowed_nodes().map(|host| host.id()).collect(),
+            depends_on: res.depends_on(),
+            groups: cluster.groups_of(&res.id),
+            retries: retry.as_ref().map_or(0, |retry| retry.failures),
+            next_retry: retry
+                .and_then(|retry| retry.next_attempt)
+                .and_then(|next| next.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|since| since.as_secs()),
+            start_timeout: timeouts.start.as_secs(),
+            stop_timeout: timeouts.stop.as_secs(),
+            warnings: cluster.constraint_violations(rg),
         }
     }
 }
 
-async fn get_status(cluster: Arc<Cluster>) -> Json<ClusterJson> {
-    let status = ClusterJson {
-        resources: cluster
-            .resource_groups()
-            .flat_map(|rg| {
-                let managed = rg.get_managed();
-                rg.resources()
-                    .map(move |res| ResourceJson::build(res, managed))
-            })
-            .collect(),
-    };
+/// A change in the state of the cluster, as sent on the `/events` stream. The name of each SSE
+/// event is the same as its `event` field.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventJson {
+    ResourceStatus {
+        id: String,
+        status: String,
+        comment: Option<String>,
+    },
+    ResourceManaged {
+        id: String,
+        managed: bool,
+    },
+    ResourceTargetState {
+        id: String,
+        target_state: TargetState,
+    },
+    HostStandby {
+        id: String,
+        standby: bool,
+    },
 
-    Json(status)
+    /// This subscriber fell behind, and the `missed` oldest events were dropped.
+    Lagged {
+        missed: u64,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct SetManagedArgs {
-    pub managed: bool,
+impl EventJson {
+    fn name(&self) -> &'static str {
+        match self {
+            EventJson::ResourceStatus { .. } => "resource_status",
+            EventJson::ResourceManaged { .. } => "resource_managed",
+            EventJson::ResourceTargetState { .. } => "resource_target_state",
+            EventJson::HostStandby { .. } => "host_standby",
+            EventJson::Lagged { .. } => "lagged",
+        }
+    }
 }
 
-async fn set_managed(
-    Path(resource_id): Path<String>,
-    Json(payload): Json<SetManagedArgs>,
-    cluster: Arc<Cluster>,
-) -> Result<(), StatusCode> {
-    for rg in cluster.resource_groups() {
-        if rg.root.id == resource_id {
-            warn!(
-                "Resource group {}: setting managed={}",
-                rg.id(),
-                if payload.managed { "true" } else { "false" }
-            );
-            rg.set_managed(payload.managed);
-            return Ok(());
+impl From<Event> for EventJson {
+    fn from(event: Event) -> Self {
+        match event {
+            Event::ResourceStatus { id, status } => {
+                let (status, comment) = describe_status(status);
+                EventJson::ResourceStatus {
+                    id,
+                    status,
+                    comment,
+                }
+            }
+            Event::ResourceManaged { id, managed } => EventJson::ResourceManaged { id, managed },
+            Event::ResourceTargetState { id, target } => EventJson::ResourceTargetState {
+                id,
+                target_state: target,
+            },
+            Event::HostStandby { id, standby } => EventJson::HostStandby { id, standby },
         }
     }
+}
+
+/// Stream changes in the state of the cluster as server-sent events, until the manager shuts
+/// down.
+async fn get_events(
+    cluster: Arc<Cluster>,
+) -> Sse<impl Stream<Item = Result<sse::Event, Infallible>>> {
+    let receiver = cluster.subscribe();
 
-    Err(StatusCode::NOT_FOUND)
+    let events = futures::stream::unfold(receiver, |mut receiver| async move {
+        let event = match receiver.recv().await {
+            Ok(event) => EventJson::from(event),
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                warn!("An /events subscriber fell behind; dropped {missed} events.");
+                EventJson::Lagged { missed }
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
+        };
+        let sse_event = sse::Event::default()
+            .event(event.name())
+            .json_data(&event)
+            .unwrap_or_else(|e| sse::Event::default().comment(format!("{e}")));
+        Some((Ok(sse_event), receiver))
+    })
+    .take_until(Box::pin(shutdown::shutdown_requested()));
+
+    Sse::new(events).keep_alive(KeepAlive::default())
 }
 
+/// A request sent to the manager on the `/ws` channel, as a JSON text frame. The `id` is chosen by
+/// the client, and the manager's reply to the request carries the same one.
 #[derive(Serialize, Deserialize, Debug)]
-pub struct HostArgs {
-    pub command: String,
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsRequest {
+    /// Set the managed state of resource groups, as `PATCH /resources` does.
+    Manage {
+        id: u64,
+        args: SetManagedBatchArgs,
+    },
+
+    /// Get the status of the resources selected by `query`, as `GET /status` does.
+    Status {
+        id: u64,
+        #[serde(default)]
+        query: StatusQuery,
+    },
+
+    /// Start sending the events that `/events` streams, until Unsubscribe.
+    Subscribe {
+        id: u64,
+    },
+    Unsubscribe {
+        id: u64,
+    },
 }
 
-async fn host_post(
-    Path(host_id): Path<String>,
-    Json(payload): Json<HostArgs>,
+/// A message sent by the manager on the `/ws` channel, as a JSON text frame: either the reply to a
+/// request, or an event for a subscribed client.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsReply {
+    /// The reply to a Manage request, with the HTTP status that `PATCH /resources` would have
+    /// replied with.
+    Manage {
+        id: u64,
+        status: u16,
+        reply: SetManagedBatchReply,
+    },
+    Status {
+        id: u64,
+        cluster: ClusterJson,
+    },
+    Subscribed {
+        id: u64,
+    },
+    Unsubscribed {
+        id: u64,
+    },
+    Event {
+        event: EventJson,
+    },
+
+    /// A request failed with the HTTP status, and error code, that the same request to the REST API
+    /// would have. The `id` is missing if the request could not be parsed.
+    Error {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<u64>,
+        status: u16,
+        message: String,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        code: Option<ErrorCode>,
+    },
+}
+
+/// How long the manager waits for a `/ws` client to take a message before it gives up on the
+/// client and disconnects it, so that one which stopped reading does not hold a task forever.
+const WS_SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Upgrade the connection to a WebSocket, on which the client can send requests and subscribe to
+/// events for as long as it stays connected; see WsRequest and WsReply. A client with the
+/// read-only token can not send requests which change something.
+async fn get_ws(
+    upgrade: WebSocketUpgrade,
+    access: Option<Extension<ReadOnlyAccess>>,
     cluster: Arc<Cluster>,
-) -> Result<(), (StatusCode, &'static str)> {
-    match payload.command.as_str() {
-        "failback" => {
-            let Some(host) = cluster.get_host(&host_id) else {
-                return Err((StatusCode::NOT_FOUND, ""));
-            };
+    limiter: Option<Arc<RateLimiter>>,
+) -> Response {
+    let read_only = access.is_some();
+    upgrade.on_upgrade(move |socket| serve_ws(socket, cluster, limiter, read_only))
+}
 
-            let Some(partner) = host.failover_partner() else {
-                return Err((
+/// What the loop in serve_ws() was woken up for.
+enum WsWake {
+    Frame(Option<Result<ws::Message, axum::Error>>),
+    Event(EventJson),
+    Shutdown,
+}
+
+/// Serve requests from a `/ws` client, one at a time, until it disconnects or the manager shuts
+/// down.
+///
+/// Each message is sent before the next request is read, so a client which does not keep up only
+/// slows itself down. Events which it falls too far behind on are dropped, and reported with a
+/// Lagged event as on `/events`; a client which takes no message at all for WS_SEND_TIMEOUT is
+/// disconnected.
+async fn serve_ws(
+    mut socket: WebSocket,
+    cluster: Arc<Cluster>,
+    limiter: Option<Arc<RateLimiter>>,
+    read_only: bool,
+) {
+    let mut events = None;
+    let shutdown = shutdown::shutdown_requested();
+    tokio::pin!(shutdown);
+
+    loop {
+        // Events come before the next request, so that the reply to a request is never sent
+        // ahead of an event for a change which was made before it:
+        let wake = tokio::select! {
+            biased;
+            () = &mut shutdown => WsWake::Shutdown,
+            event = next_event(&mut events) => WsWake::Event(event),
+            frame = socket.recv() => WsWake::Frame(frame),
+        };
+        let reply = match wake {
+            WsWake::Frame(None | Some(Err(_))) => return,
+            WsWake::Frame(Some(Ok(ws::Message::Text(text)))) => match serde_json::from_str(&text) {
+                Ok(request) => {
+                    handle_ws_request(
+                        request,
+                        &cluster,
+                        limiter.as_deref(),
+                        read_only,
+                        &mut events,
+                    )
+                    .await
+                }
+                Err(e) => ws_error(
+                    None,
                     StatusCode::BAD_REQUEST,
-                    "Host does not have a failover partner.",
-                ));
-            };
+                    format!("invalid request: {e}"),
+                ),
+            },
+            WsWake::Frame(Some(Ok(ws::Message::Binary(_)))) => ws_error(
+                None,
+                StatusCode::BAD_REQUEST,
+                "requests must be sent as JSON text frames".to_string(),
+            ),
+            // Pings are answered, and a close is echoed, by the WebSocket itself, after which the
+            // next recv() returns None:
+            WsWake::Frame(Some(Ok(_))) => continue,
+            WsWake::Event(event) => WsReply::Event { event },
+            WsWake::Shutdown => {
+                let close = ws::CloseFrame {
+                    code: ws::close_code::AWAY,
+                    reason: "the manager is shutting down".into(),
+                };
+                let _ = tokio::time::timeout(
+                    WS_SEND_TIMEOUT,
+                    socket.send(ws::Message::Close(Some(close))),
+                )
+                .await;
+                return;
+            }
+        };
 
-            partner.command(HostCommand::Failback).await;
+        // The replies are all plain data, so they always serialize:
+        let text = serde_json::to_string(&reply).unwrap();
+        match tokio::time::timeout(WS_SEND_TIMEOUT, socket.send(text.into())).await {
+            Ok(Ok(())) => {}
+            Ok(Err(_)) => return,
+            Err(_) => {
+                warn!("A /ws client stopped taking messages; disconnecting it.");
+                return;
+            }
+        }
+    }
+}
 
-            Ok(())
+fn ws_error(id: Option<u64>, status: StatusCode, message: String) -> WsReply {
+    WsReply::Error {
+        id,
+        status: status.as_u16(),
+        message,
+        code: None,
+    }
+}
+
+/// Wait for the next event for a `/ws` client which subscribed to them. This never finishes for
+/// one which did not.
+async fn next_event(events: &mut Option<broadcast::Receiver<Event>>) -> EventJson {
+    let Some(receiver) = events else {
+        return std::future::pending().await;
+    };
+    match receiver.recv().await {
+        Ok(event) => EventJson::from(event),
+        Err(broadcast::error::RecvError::Lagged(missed)) => {
+            warn!("A /ws subscriber fell behind; dropped {missed} events.");
+            EventJson::Lagged { missed }
         }
-        _ => Err((StatusCode::BAD_REQUEST, "Unsupported command.")),
+        Err(broadcast::error::RecvError::Closed) => {
+            *events = None;
+            std::future::pending().await
+        }
+    }
+}
+
+/// Handle a request from a `/ws` client as the REST API would handle the same request.
+async fn handle_ws_request(
+    request: WsRequest,
+    cluster: &Arc<Cluster>,
+    limiter: Option<&RateLimiter>,
+    read_only: bool,
+    events: &mut Option<broadcast::Receiver<Event>>,
+) -> WsReply {
+    match request {
+        WsRequest::Manage { id, args } => {
+            if read_only {
+                return ws_error(
+                    Some(id),
+                    StatusCode::FORBIDDEN,
+                    "the read-only token can not be used to change anything".to_string(),
+                );
+            }
+            if let Some(Err((_, message))) =
+                limiter.map(|limiter| check_rate_limit(limiter, "PATCH /resources"))
+            {
+                return ws_error(Some(id), StatusCode::TOO_MANY_REQUESTS, message);
+            }
+            match set_managed_batch(Json(args), Arc::clone(cluster)).await {
+                Ok((status, Json(reply))) => WsReply::Manage {
+                    id,
+                    status: status.as_u16(),
+                    reply,
+                },
+                Err(e) => WsReply::Error {
+                    id: Some(id),
+                    status: e.status.as_u16(),
+                    message: e.message,
+                    code: Some(e.code),
+                },
+            }
+        }
+        WsRequest::Status { id, query } => {
+            match get_status(Query(query), Arc::clone(cluster)).await {
+                Ok(Json(cluster)) => WsReply::Status { id, cluster },
+                Err((status, message)) => ws_error(Some(id), status, message),
+            }
+        }
+        WsRequest::Subscribe { id } => {
+            if events.is_none() {
+                *events = Some(cluster.subscribe());
+            }
+            WsReply::Subscribed { id }
+        }
+        WsRequest::Unsubscribe { id } => {
+            *events = None;
+            WsReply::Unsubscribed { id }
+        }
+    }
+}
+
+/// The query parameters of `GET /history`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct HistoryQuery {
+    /// Only list entries from this time or later: either an RFC 3339 timestamp, or a duration
+    /// before now such as "10m", measured by the manager's clock. See history::Since::parse.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+}
+
+/// List the most recent actions requested on resources, oldest first.
+async fn get_history(
+    Query(query): Query<HistoryQuery>,
+    cluster: Arc<Cluster>,
+) -> Result<Json<Vec<history::Entry>>, (StatusCode, String)> {
+    let Some(since) = &query.since else {
+        return Ok(Json(cluster.history().entries()));
+    };
+    let since = history::Since::parse(since).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    Ok(Json(
+        cluster
+            .history()
+            .entries_since(since.cutoff(history::now())),
+    ))
+}
+
+/// The query parameters of `GET /simulate`: the hypothetical changes to the cluster, each a
+/// comma-separated list.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SimulateQuery {
+    /// The hosts to treat as down, and fenced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fail: Option<String>,
+
+    /// The hosts to treat as on standby.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub standby: Option<String>,
+
+    /// The resources to treat as added to the cluster, each as "ID@HOST": a stopped resource group
+    /// of its own, whose home node is HOST.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub add: Option<String>,
+}
+
+/// Where a resource group is running, and where it would be running after a simulation.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SimulatedGroup {
+    pub id: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+
+    /// The constraints that the group would break where it ends up, e.g. "must not run on the
+    /// same host as ost1".
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// The reply to `GET /simulate`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SimulateReply {
+    /// The actions that Manage mode would take, in order, e.g. "move resource group ost0 from
+    /// oss00 to oss01, since oss00 is down".
+    pub actions: Vec<String>,
+
+    pub resource_groups: Vec<SimulatedGroup>,
+}
+
+/// Show where the resource groups would end up if the cluster were changed as in `query`, by the
+/// placement decisions of Manage mode; see Snapshot::simulate(). Nothing is changed.
+async fn get_simulation(
+    Query(query): Query<SimulateQuery>,
+    cluster: Arc<Cluster>,
+) -> Result<Json<SimulateReply>, (StatusCode, String)> {
+    let bad_request = |message: String| (StatusCode::BAD_REQUEST, message);
+    let list = |value: &Option<String>| -> Vec<String> {
+        value
+            .iter()
+            .flat_map(|value| value.split(','))
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+
+    let mut snapshot = cluster.snapshot();
+    for (ids, down) in [(&query.fail, true), (&query.standby, false)] {
+        for id in list(ids) {
+            let Some(host) = snapshot.hosts.iter_mut().find(|host| host.id == id) else {
+                return Err(bad_request(format!("'{id}' is not a host in this cluster")));
+            };
+            if down {
+                host.down = true;
+            } else {
+                host.standby = true;
+            }
+        }
+    }
+    for spec in list(&query.add) {
+        let Some((id, home)) = spec.split_once('@') else {
+            return Err(bad_request(format!(
+                "'{spec}' should be a resource ID and a host, as ID@HOST"
+            )));
+        };
+        if snapshot.group_of(id).is_some() {
+            return Err(bad_request(format!(
+                "resource '{id}' is already in this cluster"
+            )));
+        }
+        let Some(host) = cluster.get_host(home) else {
+            return Err(bad_request(format!(
+                "'{home}' is not a host in this cluster"
+            )));
+        };
+        snapshot.groups.push(GroupState {
+            id: id.to_string(),
+            members: vec![id.to_string()],
+            home: host.id(),
+            away: host.failover_partner().map(|partner| partner.id()),
+            placement: Default::default(),
+            managed: true,
+            migrating: false,
+            running_on: None,
+            stopped: true,
+        });
+    }
+    snapshot.groups.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let before = snapshot.clone();
+    let actions = snapshot.simulate();
+    let resource_groups = snapshot
+        .groups
+        .iter()
+        .map(|group| SimulatedGroup {
+            id: group.id.clone(),
+            from: before
+                .group(&group.id)
+                .and_then(|group| group.running_on.clone()),
+            to: group.running_on.clone(),
+            warnings: snapshot.violations(group),
+        })
+        .collect();
+
+    Ok(Json(SimulateReply {
+        actions: actions.iter().map(|action| action.to_string()).collect(),
+        resource_groups,
+    }))
+}
+
+/// Whether the manager acts on the resources, or only observes them.
+#[derive(Serialize, Deserialize, clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    Manage,
+    Observe,
+}
+
+impl std::fmt::Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mode::Manage => write!(f, "manage"),
+            Mode::Observe => write!(f, "observe"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ModeJson {
+    pub mode: Mode,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetModeArgs {
+    pub mode: Mode,
+
+    /// Only report what would be done, without changing anything.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetModeReply {
+    pub mode: Mode,
+
+    /// False if the manager was already in the requested mode.
+    pub changed: bool,
+}
+
+fn current_mode(cluster: &Cluster) -> Mode {
+    if cluster.is_managing() {
+        Mode::Manage
+    } else {
+        Mode::Observe
+    }
+}
+
+/// Report whether the manager is in Manage or Observe mode.
+async fn get_mode(cluster: Arc<Cluster>) -> Json<ModeJson> {
+    Json(ModeJson {
+        mode: current_mode(&cluster),
+    })
+}
+
+/// Switch the manager between Manage and Observe mode. In Observe mode, the management loops keep
+/// checking on the resources, but stop starting, moving, and failing them over from their next
+/// pass on. Only a manager which was started in Manage mode can switch back to it.
+async fn set_mode(
+    Json(payload): Json<SetModeArgs>,
+    cluster: Arc<Cluster>,
+) -> Result<Response, RequestError> {
+    if cluster.args.dry_run {
+        return Err(RequestError::conflict(
+            "The manager was started with --dry-run, so it only observes.".to_string(),
+        ));
+    }
+    if payload.mode == Mode::Manage && !cluster.args.manage_resources {
+        return Err(RequestError::conflict(
+            "The manager was started in Observe mode, so it can not switch to Manage mode. \
+             Restart it with --manage-resources."
+                .to_string(),
+        ));
+    }
+
+    if payload.dry_run {
+        return Ok(dry_run_reply(if current_mode(&cluster) == payload.mode {
+            format!("already in {} mode", payload.mode)
+        } else {
+            format!("would switch to {} mode", payload.mode)
+        }));
+    }
+
+    let changed = cluster.set_managing(payload.mode == Mode::Manage);
+    Ok(Json(SetModeReply {
+        mode: payload.mode,
+        changed,
+    })
+    .into_response())
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MaintenanceJson {
+    /// When maintenance mode was switched on, in seconds since the Unix epoch, if it is on.
+    pub since: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetMaintenanceArgs {
+    pub on: bool,
+
+    /// Only report what would be done, without changing anything.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetMaintenanceReply {
+    pub on: bool,
+
+    /// False if maintenance mode was already on, or off.
+    pub changed: bool,
+}
+
+/// Report whether maintenance mode is on, and since when.
+async fn get_maintenance(cluster: Arc<Cluster>) -> Json<MaintenanceJson> {
+    Json(MaintenanceJson {
+        since: cluster.maintenance().since(),
+    })
+}
+
+/// Switch maintenance mode on or off. While it is on, the management loops keep checking on the
+/// resources, but take no action on any of them from their next pass on, whatever the mode and
+/// whether or not each resource is managed. It is recorded in the maintenance file, so that it
+/// stays on when the manager restarts.
+async fn set_maintenance(
+    Json(payload): Json<SetMaintenanceArgs>,
+    cluster: Arc<Cluster>,
+) -> Result<Response, RequestError> {
+    let on_off = |on| if on { "on" } else { "off" };
+    if payload.dry_run || cluster.args.dry_run {
+        return Ok(dry_run_reply(
+            if cluster.maintenance().is_on() == payload.on {
+                format!("maintenance mode is already {}", on_off(payload.on))
+            } else {
+                format!("would switch maintenance mode {}", on_off(payload.on))
+            },
+        ));
+    }
+
+    let changed = cluster.set_maintenance(payload.on).map_err(|e| {
+        RequestError::internal(format!(
+            "Could not record maintenance mode in '{}': {e}",
+            cluster.maintenance().path()
+        ))
+    })?;
+    Ok(Json(SetMaintenanceReply {
+        on: payload.on,
+        changed,
+    })
+    .into_response())
+}
+
+/// What is shown in place of a secret, such as the auth token, by `/config`.
+pub const REDACTED: &str = "<redacted>";
+
+/// The settings that the manager is running with, once its command line, environment, and config
+/// file are combined, for `halo config dump`. Secrets are redacted.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ConfigJson {
+    /// The paths of the config files that the manager loaded, in the order they were merged.
+    pub config_files: Vec<String>,
+
+    /// Which version of the config file is in effect: 1 until the config is reloaded.
+    pub config_generation: u64,
+
+    pub socket: String,
+
+    /// The octal mode that the socket was given, e.g. "0660", if any.
+    pub socket_mode: Option<String>,
+    pub socket_group: Option<String>,
+    pub listen: Option<String>,
+    pub mtls: bool,
+
+    /// REDACTED if the manager requires an auth token.
+    pub auth_token: Option<String>,
+
+    /// REDACTED if the manager also accepts a read-only token.
+    #[serde(default)]
+    pub read_only_token: Option<String>,
+
+    pub mode: Mode,
+    pub dry_run: bool,
+
+    /// False if the manager fails resources over without fencing their host first.
+    pub fencing: bool,
+
+    /// In milliseconds.
+    pub poll_interval: u64,
+
+    /// In milliseconds.
+    pub poll_jitter: u64,
+
+    pub history_size: usize,
+    pub retry_attempts: u32,
+
+    /// In milliseconds.
+    pub retry_backoff: u64,
+
+    /// In milliseconds.
+    pub retry_backoff_max: u64,
+
+    pub action_concurrency: usize,
+
+    /// How many times in a row fencing a host may fail before the manager stops trying.
+    #[serde(default)]
+    pub fence_attempts: u32,
+
+    /// The file in which maintenance mode is recorded while it is on.
+    #[serde(default)]
+    pub maintenance_file: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quorum: Option<QuorumConfigJson>,
+
+    pub hosts: Vec<HostConfigJson>,
+    pub groups: BTreeMap<String, Vec<String>>,
+    pub resources: Vec<ResourceConfigJson>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct QuorumConfigJson {
+    pub expected_hosts: usize,
+    pub tiebreak: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HostConfigJson {
+    pub id: String,
+
+    /// The address of the host's remote agent, as host:port.
+    pub address: String,
+
+    pub failover_partner: Option<String>,
+
+    /// The name of the host's fence agent. Its parameters are left out, since they are mostly
+    /// credentials.
+    pub fence_agent: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ResourceConfigJson {
+    pub id: String,
+    pub kind: String,
+    pub parameters: BTreeMap<String, String>,
+    pub home_node: String,
+    pub allowed_nodes: Vec<String>,
+    pub depends_on: Vec<String>,
+    pub managed: bool,
+    pub target_state: TargetState,
+
+    /// In seconds.
+    pub start_timeout: u64,
+
+    /// In seconds.
+    pub stop_timeout: u64,
+
+    /// The config file that the resource is defined in.
+    pub source: Option<String>,
+}
+
+/// Report the settings that the manager is running with. These are the ones it resolved at
+/// startup, with the changes from any config reloads and requests since.
+async fn get_config(cluster: Arc<Cluster>) -> Json<ConfigJson> {
+    let args = &cluster.args;
+
+    let mut hosts: Vec<_> = cluster
+        .hosts()
+        .map(|host| HostConfigJson {
+            id: host.id(),
+            address: host.address(),
+            failover_partner: host.failover_partner().map(|partner| partner.id()),
+            fence_agent: host
+                .fence_agent()
+                .as_ref()
+                .map(|agent| agent.name().to_string()),
+        })
+        .collect();
+    hosts.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let sources = cluster.sources();
+    let resources = cluster
+        .resource_groups()
+        .flat_map(|rg| rg.resources().map(move |res| (rg, res)))
+        .map(|(rg, res)| {
+            let timeouts = res.timeouts();
+            ResourceConfigJson {
+                id: res.id.clone(),
+                kind: res.kind.clone(),
+                parameters: res.parameters().into_iter().collect(),
+                home_node: rg.home_node().id(),
+                allowed_nodes: rg.allowed_nodes().map(|host| host.id()).collect(),
+                depends_on: res.depends_on(),
+                managed: rg.get_managed(),
+                target_state: res.target_state(),
+                start_timeout: timeouts.start.as_secs(),
+                stop_timeout: timeouts.stop.as_secs(),
+                source: sources.resources.get(&res.id).cloned(),
+            }
+        })
+        .collect();
+
+    Json(ConfigJson {
+        config_files: sources.files.clone(),
+        config_generation: cluster.config_generation(),
+        socket: args.socket.clone().unwrap_or_else(crate::default_socket),
+        socket_mode: args.socket_mode.map(|mode| format!("{mode:04o}")),
+        socket_group: args.socket_group.clone(),
+        listen: args.listen.clone(),
+        mtls: args.mtls,
+        auth_token: args.auth_token.as_ref().map(|_| REDACTED.to_string()),
+        read_only_token: args.read_only_token.as_ref().map(|_| REDACTED.to_string()),
+        mode: current_mode(&cluster),
+        dry_run: args.dry_run,
+        fencing: !args.no_fence,
+        poll_interval: args
+            .poll_interval
+            .unwrap_or(crate::manager::DEFAULT_POLL_INTERVAL),
+        poll_jitter: args.poll_jitter.unwrap_or(0),
+        history_size: args.history_size,
+        retry_attempts: args.retry_attempts(),
+        retry_backoff: args
+            .retry_backoff
+            .unwrap_or(crate::manager::DEFAULT_RETRY_BACKOFF),
+        retry_backoff_max: args
+            .retry_backoff_max
+            .unwrap_or(crate::manager::DEFAULT_RETRY_BACKOFF_MAX),
+        action_concurrency: args.action_concurrency(),
+        fence_attempts: args.fence_attempts,
+        maintenance_file: args.maintenance_file().to_string(),
+        quorum: cluster.quorum().map(|quorum| QuorumConfigJson {
+            expected_hosts: quorum.expected,
+            tiebreak: quorum.tiebreak.to_string(),
+        }),
+        hosts,
+        groups: cluster.groups().into_iter().collect(),
+        resources,
+    })
+}
+
+/// Record the outcome of an action which a request handler performed on `resource`.
+fn record(
+    cluster: &Cluster,
+    resource: &str,
+    action: &str,
+    outcome: &Result<impl Sized, RequestError>,
+) {
+    let reason = match outcome {
+        Ok(_) => None,
+        Err(e) if e.message.is_empty() => Some(
+            e.status
+                .canonical_reason()
+                .unwrap_or("failed")
+                .to_lowercase(),
+        ),
+        Err(e) => Some(e.message.clone()),
+    };
+    cluster
+        .history()
+        .record(resource, action, reason.as_deref().map_or(Ok(()), Err));
+}
+
+/// The reply to a request which was a dry run: what the manager would have done, had it been asked
+/// to do it for real.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DryRunReply {
+    pub message: String,
+}
+
+/// Whether a request should only report what it would do. Every request is a dry run when the
+/// manager itself was started with `--dry-run`.
+fn is_dry_run(cluster: &Cluster, requested: bool) -> bool {
+    requested || cluster.args.dry_run
+}
+
+fn dry_run_reply(message: String) -> Response {
+    Json(DryRunReply {
+        message: format!("dry run: {message}"),
+    })
+    .into_response()
+}
+
+/// Get the status of a single resource, in the same form as in the status of the whole cluster.
+async fn get_resource(
+    Path(resource_id): Path<String>,
+    cluster: Arc<Cluster>,
+) -> Result<Json<ResourceJson>, StatusCode> {
+    let Some((rg, res)) = cluster.find_resource(&resource_id) else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    Ok(Json(ResourceJson::build(&cluster, rg, res)))
+}
+
+/// Health check: the manager is alive if it is able to respond to this at all.
+async fn is_manager_alive() -> StatusCode {
+    StatusCode::OK
+}
+
+/// Readiness check: unlike being alive, the manager is only ready once its management loops have
+/// finished a pass, and only while they keep doing so. Replies with 503 and the reason otherwise.
+async fn is_manager_ready(cluster: Arc<Cluster>) -> (StatusCode, String) {
+    match cluster.readiness() {
+        Ok(()) => (StatusCode::OK, "ready\n".to_string()),
+        Err(reason) => (StatusCode::SERVICE_UNAVAILABLE, format!("{reason}\n")),
+    }
+}
+
+/// The query parameters of `GET /status`, which select the resources to list so that a large
+/// cluster does not have to be listed in full. Every parameter is optional.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct StatusQuery {
+    /// Only list resources in this kind of status: one of ResourceStatus::NAMES, e.g.
+    /// "RunningOnAway", in any case.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+
+    /// Only list resources in managed (true) or unmanaged (false) resource groups.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub managed: Option<bool>,
+
+    /// Only list resources whose IDs start with this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+
+    /// List at most this many of the resources which match, after skipping the first `offset`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+}
+
+/// Reject a query for a kind of status that does not exist, rather than list no resources.
+fn check_status_query(query: &StatusQuery) -> Result<(), (StatusCode, String)> {
+    if let Some(status) = &query.status {
+        if !ResourceStatus::NAMES
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(status))
+        {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "unknown status '{status}': expected one of {}",
+                    ResourceStatus::NAMES.join(", ")
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// List the IDs of the resources selected by `query`, in the same order as `GET /status`, but
+/// without anything else about them, so that scripts can list even a large cluster cheaply.
+async fn get_resource_ids(
+    Query(query): Query<StatusQuery>,
+    cluster: Arc<Cluster>,
+) -> Result<Json<Vec<String>>, (StatusCode, String)> {
+    check_status_query(&query)?;
+
+    let ids = cluster
+        .filter_resources(
+            query.status.as_deref(),
+            query.managed,
+            query.prefix.as_deref(),
+        )
+        .map(|(_, res)| res.id.clone())
+        .skip(query.offset.unwrap_or(0))
+        .take(query.limit.unwrap_or(usize::MAX))
+        .collect();
+    Ok(Json(ids))
+}
+
+async fn get_status(
+    Query(query): Query<StatusQuery>,
+    cluster: Arc<Cluster>,
+) -> Result<Json<ClusterJson>, (StatusCode, String)> {
+    check_status_query(&query)?;
+
+    let matching = cluster.filter_resources(
+        query.status.as_deref(),
+        query.managed,
+        query.prefix.as_deref(),
+    );
+    let mut total = 0;
+    let mut resources = Vec::new();
+    let offset = query.offset.unwrap_or(0);
+    let limit = query.limit.unwrap_or(usize::MAX);
+    for (rg, res) in matching {
+        if total >= offset && resources.len() < limit {
+            resources.push(ResourceJson::build(&cluster, rg, res));
+        }
+        total += 1;
+    }
+
+    let status = ClusterJson {
+        resources,
+        total,
+        hosts: cluster
+            .hosts()
+            .map(|host| HostJson {
+                id: host.id(),
+                standby: host.is_standby(),
+                reachable: host.is_reachable(),
+                fence_failed: host.is_fence_failed(),
+                fence_failures: host.fence_failures(),
+            })
+            .collect(),
+        groups: cluster
+            .groups()
+            .into_iter()
+            .map(|(name, members)| GroupJson { name, members })
+            .collect(),
+        quorum: quorum_json(&cluster),
+        config_generation: cluster.config_generation(),
+        maintenance: cluster.maintenance().since(),
+    };
+
+    Ok(Json(status))
+}
+
+fn quorum_json(cluster: &Cluster) -> Option<QuorumJson> {
+    cluster.quorum().map(|quorum| QuorumJson {
+        has_quorum: cluster.has_quorum(),
+        reachable: cluster.reachable_hosts(),
+        expected: quorum.expected,
+        needed: quorum.needed(),
+        tiebreak: quorum.tiebreak.to_string(),
+    })
+}
+
+/// The number of resources in each kind of status, in the order of ResourceStatus::NAMES. Every
+/// status is counted, even ones that no resource is in.
+fn count_statuses(cluster: &Cluster) -> Vec<(&'static str, usize)> {
+    let mut statuses: Vec<(&str, usize)> = ResourceStatus::NAMES
+        .iter()
+        .map(|name| (*name, 0))
+        .collect();
+    for rg in cluster.resource_groups() {
+        for res in rg.resources() {
+            let status = rg.reported_status(res).name();
+            if let Some(entry) = statuses.iter_mut().find(|(name, _)| *name == status) {
+                entry.1 += 1;
+            }
+        }
+    }
+    statuses
+}
+
+/// The state of the cluster as a whole, from `GET /summary`, for `halo top`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SummaryJson {
+    /// How many resources are in each kind of status, in the order of ResourceStatus::NAMES.
+    pub statuses: Vec<StatusCountJson>,
+
+    /// How many resources are in managed and in unmanaged resource groups.
+    pub managed: usize,
+    pub unmanaged: usize,
+
+    /// How many hosts there are, and how many of them the manager is connected to.
+    pub hosts: usize,
+    pub reachable_hosts: usize,
+
+    /// The IDs of the hosts which are on standby.
+    pub standby: Vec<String>,
+
+    /// Whether the cluster has quorum, if the config sets a quorum policy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quorum: Option<QuorumJson>,
+
+    /// The number of passes that the management loops have made over resource groups. The rate at
+    /// which it grows shows whether the loops are keeping up.
+    pub main_loop_iterations: u64,
+
+    /// When maintenance mode was switched on, in seconds since the Unix epoch, if it is on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintenance: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct StatusCountJson {
+    /// A kind of status, as given by ResourceStatus::name(), e.g. "RunningOnAway".
+    pub status: String,
+    pub count: usize,
+}
+
+async fn get_summary(cluster: Arc<Cluster>) -> Json<SummaryJson> {
+    let managed = cluster.managed_resources().count();
+    let mut standby: Vec<String> = cluster
+        .hosts()
+        .filter(|host| host.is_standby())
+        .map(|host| host.id())
+        .collect();
+    standby.sort();
+
+    Json(SummaryJson {
+        statuses: count_statuses(&cluster)
+            .into_iter()
+            .map(|(status, count)| StatusCountJson {
+                status: status.to_string(),
+                count,
+            })
+            .collect(),
+        managed,
+        unmanaged: cluster.unmanaged_resources().count(),
+        hosts: cluster.hosts().count(),
+        reachable_hosts: cluster.reachable_hosts(),
+        standby,
+        quorum: quorum_json(&cluster),
+        main_loop_iterations: cluster.main_loop_iterations(),
+        maintenance: cluster.maintenance().since(),
+    })
+}
+
+/// Report resource statuses and management activity in the Prometheus text format.
+async fn get_metrics(cluster: Arc<Cluster>) -> ([(header::HeaderName, &'static str); 1], String) {
+    // Count every status, even ones that no resource is in, so that each series always exists:
+    let statuses = count_statuses(&cluster);
+    let managed = cluster.managed_resources().count();
+    let unmanaged = cluster.unmanaged_resources().count();
+
+    // Writing to a String can not fail, so the results of writeln!() are ignored:
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "# HELP halo_resources Number of resources in each status."
+    );
+    let _ = writeln!(out, "# TYPE halo_resources gauge");
+    for (status, count) in statuses {
+        let _ = writeln!(out, "halo_resources{{status=\"{status}\"}} {count}");
+    }
+    let _ = writeln!(
+        out,
+        "# HELP halo_resources_managed Number of managed and unmanaged resources."
+    );
+    let _ = writeln!(out, "# TYPE halo_resources_managed gauge");
+    let _ = writeln!(out, "halo_resources_managed{{managed=\"true\"}} {managed}");
+    let _ = writeln!(
+        out,
+        "halo_resources_managed{{managed=\"false\"}} {unmanaged}"
+    );
+    let _ = writeln!(
+        out,
+        "# HELP halo_host_standby Whether each host is on standby (1) or not (0)."
+    );
+    let _ = writeln!(out, "# TYPE halo_host_standby gauge");
+    for host in cluster.hosts() {
+        let _ = writeln!(
+            out,
+            "halo_host_standby{{host=\"{}\"}} {}",
+            host.id(),
+            u8::from(host.is_standby())
+        );
+    }
+    let _ = writeln!(
+        out,
+        "# HELP halo_host_reachable Whether the manager is connected to each host (1) or not (0)."
+    );
+    let _ = writeln!(out, "# TYPE halo_host_reachable gauge");
+    for host in cluster.hosts() {
+        let _ = writeln!(
+            out,
+            "halo_host_reachable{{host=\"{}\"}} {}",
+            host.id(),
+            u8::from(host.is_reachable())
+        );
+    }
+    let _ = writeln!(
+        out,
+        "# HELP halo_host_fence_failures How many times in a row fencing each host has failed."
+    );
+    let _ = writeln!(out, "# TYPE halo_host_fence_failures gauge");
+    for host in cluster.hosts() {
+        let _ = writeln!(
+            out,
+            "halo_host_fence_failures{{host=\"{}\"}} {}",
+            host.id(),
+            host.fence_failures()
+        );
+    }
+    let _ = writeln!(
+        out,
+        "# HELP halo_host_fence_failed Whether the manager has stopped trying to fence each host (1) or not (0)."
+    );
+    let _ = writeln!(out, "# TYPE halo_host_fence_failed gauge");
+    for host in cluster.hosts() {
+        let _ = writeln!(
+            out,
+            "halo_host_fence_failed{{host=\"{}\"}} {}",
+            host.id(),
+            u8::from(host.is_fence_failed())
+        );
+    }
+    let _ = writeln!(
+        out,
+        "# HELP halo_quorum Whether the cluster has quorum (1) or not (0); always 1 without a quorum policy."
+    );
+    let _ = writeln!(out, "# TYPE halo_quorum gauge");
+    let _ = writeln!(out, "halo_quorum {}", u8::from(cluster.has_quorum()));
+    let _ = writeln!(
+        out,
+        "# HELP halo_maintenance Whether maintenance mode is on (1) or off (0)."
+    );
+    let _ = writeln!(out, "# TYPE halo_maintenance gauge");
+    let _ = writeln!(
+        out,
+        "halo_maintenance {}",
+        u8::from(cluster.maintenance().is_on())
+    );
+    let _ = writeln!(
+        out,
+        "# HELP halo_main_loop_iterations_total Passes made by the management loops over resource groups."
+    );
+    let _ = writeln!(out, "# TYPE halo_main_loop_iterations_total counter");
+    let _ = writeln!(
+        out,
+        "halo_main_loop_iterations_total {}",
+        cluster.main_loop_iterations()
+    );
+    let _ = writeln!(
+        out,
+        "# HELP halo_actions_in_flight Starts and stops of resources which are running right now."
+    );
+    let _ = writeln!(out, "# TYPE halo_actions_in_flight gauge");
+    let _ = writeln!(
+        out,
+        "halo_actions_in_flight {}",
+        cluster.actions_in_flight()
+    );
+    if let Some(profiler) = cluster.profiler() {
+        profiler.write_metrics(&mut out);
+    }
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], out)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetManagedArgs {
+    pub managed: bool,
+
+    /// Only report what would be done, without changing anything.
+    #[serde(default)]
+    pub dry_run: bool,
+
+    /// Treat a resource group that is already in the requested state as a success with no change,
+    /// rather than a conflict, so that scripts can safely retry the request.
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// Why a request, or one entry of a batch request, failed, so that clients can tell failures apart
+/// without matching on the message, which is meant for humans.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The resource, resource group, or group named in the request does not exist.
+    NotFound,
+
+    /// The resource group is already in the requested state.
+    AlreadyInState,
+
+    /// The request was invalid, e.g. a parameter which the resource's agent does not accept, or a
+    /// host which the resource group can not run on.
+    ValidationError,
+
+    /// The request can not be carried out in the current state of the cluster, e.g. a start of a
+    /// resource which is disabled, or a migration of a group which is already being migrated.
+    Conflict,
+
+    /// The cluster does not have quorum, so resources are not being moved.
+    NoQuorum,
+
+    /// A remote agent was asked to do something, and failed.
+    AgentFailure,
+
+    /// The action did not finish within the time which the request allowed for it.
+    Timeout,
+
+    /// The action was canceled with `halo cancel` before it finished.
+    Canceled,
+
+    /// The manager itself failed to carry out the request.
+    Internal,
+
+    /// A code from a newer manager which this client does not know about.
+    #[serde(other)]
+    Unknown,
+}
+
+/// The reply to a request which failed, from the handlers which change the state of the cluster.
+/// The status code of the reply is the HTTP status code which goes with `code`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ErrorReply {
+    pub error: bool,
+    pub message: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>,
+}
+
+/// Why a request handler failed, as it is sent to the client: see ErrorReply.
+#[derive(Debug, Clone, PartialEq)]
+struct RequestError {
+    status: StatusCode,
+    code: ErrorCode,
+    message: String,
+}
+
+impl RequestError {
+    /// The resource, resource group, group, or host named in the request does not exist. The
+    /// client says which, so no message is needed.
+    fn not_found() -> Self {
+        Self::new(StatusCode::NOT_FOUND, ErrorCode::NotFound, String::new())
+    }
+
+    fn invalid(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, ErrorCode::ValidationError, message)
+    }
+
+    fn conflict(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, ErrorCode::Conflict, message)
+    }
+
+    fn no_quorum(message: impl Into<String>) -> Self {
+        Self::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::NoQuorum,
+            message,
+        )
+    }
+
+    fn agent(message: impl Into<String>) -> Self {
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::AgentFailure,
+            message,
+        )
+    }
+
+    fn internal(message: impl Into<String>) -> Self {
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::Internal,
+            message,
+        )
+    }
+
+    fn new(status: StatusCode, code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl IntoResponse for RequestError {
+    fn into_response(self) -> Response {
+        let reply = ErrorReply {
+            error: true,
+            message: self.message,
+            code: Some(self.code),
+        };
+        (self.status, Json(reply)).into_response()
+    }
+}
+
+/// The reply to a request to change the managed state of a single resource group.
+///
+/// The status code of the reply says whether the request succeeded; `error` is also set whenever
+/// it did not, for clients that only look at the body, and `code` says why.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetManagedReply {
+    pub error: bool,
+    pub message: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>,
+
+    /// Whether the managed state of the resource group was (or, for a dry run, would be) changed.
+    #[serde(default)]
+    pub changed: bool,
+
+    /// Things that the caller should know about the outcome, though the request succeeded, e.g.
+    /// that a resource group which is now managed can not be started anywhere.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// The warnings for a resource group that is, or would be, managed: if every host that it may run
+/// on is on standby, the manager will not start it.
+fn manage_warnings(rg: &ResourceGroup) -> Vec<String> {
+    if rg.allowed_nodes().any(|host| !host.is_standby()) {
+        return Vec::new();
+    }
+    let hosts: Vec<String> = rg.allowed_nodes().map(|host| host.id()).collect();
+    vec![format!(
+        "every host that resource group '{}' may run on is on standby ({}), so it will not be \
+         started until one is taken off standby",
+        rg.id(),
+        hosts.join(", ")
+    )]
+}
+
+/// Set the managed state of a single resource group.
+///
+/// Replies with 404 if there is no such resource group, and 409 if it is already in the requested
+/// state, unless `force` is set.
+async fn set_managed(
+    Path(resource_id): Path<String>,
+    Json(payload): Json<SetManagedArgs>,
+    cluster: Arc<Cluster>,
+) -> (StatusCode, Json<SetManagedReply>) {
+    let action = if payload.managed {
+        "manage"
+    } else {
+        "unmanage"
+    };
+    let dry_run = is_dry_run(&cluster, payload.dry_run);
+    let reply = |status: StatusCode, message: String, changed: bool, warnings: Vec<String>| {
+        let code = match status {
+            StatusCode::OK => None,
+            StatusCode::NOT_FOUND => Some(ErrorCode::NotFound),
+            StatusCode::CONFLICT => Some(ErrorCode::AlreadyInState),
+            _ => Some(ErrorCode::Unknown),
+        };
+        let result = if code.is_some() {
+            Err(message.as_str())
+        } else {
+            Ok(())
+        };
+        if !dry_run {
+            cluster.history().record(&resource_id, action, result);
+        }
+        (
+            status,
+            Json(SetManagedReply {
+                error: code.is_some(),
+                message,
+                code,
+                changed,
+                warnings,
+            }),
+        )
+    };
+    let already = |rg: &ResourceGroup, state: &str| {
+        if payload.force {
+            (
+                StatusCode::OK,
+                format!("resource group '{}' is already {state}; no change", rg.id()),
+                false,
+            )
+        } else {
+            (
+                StatusCode::CONFLICT,
+                format!("resource group '{}' is already {state}", rg.id()),
+                false,
+            )
+        }
+    };
+
+    let Some(rg) = cluster.find_resource_group(&resource_id) else {
+        return reply(
+            StatusCode::NOT_FOUND,
+            format!("resource group '{resource_id}' not found"),
+            false,
+            Vec::new(),
+        );
+    };
+
+    let state = if payload.managed {
+        "managed"
+    } else {
+        "unmanaged"
+    };
+    let warnings = || {
+        if payload.managed {
+            manage_warnings(rg)
+        } else {
+            Vec::new()
+        }
+    };
+    if dry_run {
+        if rg.get_managed() == payload.managed {
+            let (status, message, changed) = already(rg, state);
+            return reply(status, message, changed, warnings());
+        }
+        return reply(
+            StatusCode::OK,
+            format!("dry run: would set resource group '{}' to {state}", rg.id()),
+            true,
+            warnings(),
+        );
+    }
+    if !rg.set_managed(payload.managed) {
+        let (status, message, changed) = already(rg, state);
+        return reply(status, message, changed, warnings());
+    }
+    warn!(
+        "Resource group {}: set managed={}",
+        rg.id(),
+        if payload.managed { "true" } else { "false" }
+    );
+
+    reply(
+        StatusCode::OK,
+        format!("resource group '{}' is now {state}", rg.id()),
+        true,
+        warnings(),
+    )
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SetManagedBatchArgs {
+    pub resources: Vec<String>,
+    pub managed: bool,
+
+    /// How the entries in `resources` are compared to resource group IDs.
+    #[serde(default)]
+    pub matching: Matching,
+
+    /// Only report which resource groups would be updated, without changing anything.
+    #[serde(default)]
+    pub dry_run: bool,
+
+    /// Named groups of resources, without the leading '@', whose resource groups are updated as
+    /// well as the ones selected by `resources`.
+    #[serde(default)]
+    pub groups: Vec<String>,
+
+    /// Treat a resource group which is already in the requested state as a success, as for a
+    /// single resource group.
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// The ways that a batch request can select resource groups.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Matching {
+    /// Each entry is the exact ID of a resource group.
+    #[default]
+    Exact,
+
+    /// Each entry is a shell-style glob, supporting `*` and `?`.
+    Glob,
+
+    /// Each entry is a regular expression which must match the whole resource group ID.
+    Regex,
+}
+
+/// The reply to a batch request lists which of the requested resources were updated, and which
+/// could not be found. Resources that were found are updated even if others were not.
+///
+/// When matching by glob or regex, `not_found` holds the patterns which did not match any resource
+/// group. Named groups which do not exist are listed in `not_found` with a leading '@'. For a dry
+/// run, `updated` holds the resource groups which would have been updated.
+///
+/// Each entry succeeds or fails as it would for a single resource group: one which is already in
+/// the requested state fails with `already_in_state`, unless `force` is set. The reply's status is
+/// 200 if every entry succeeded, and 207 if only some of them did. If none of them did, it is 404
+/// or 409 when they all failed for the same reason, and 207 otherwise.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetManagedBatchReply {
+    pub updated: Vec<String>,
+    pub not_found: Vec<String>,
+
+    #[serde(default)]
+    pub dry_run: bool,
+
+    /// The outcome for each resource group that was updated, followed by each entry that was not
+    /// found, in the order that they were handled.
+    #[serde(default)]
+    pub results: Vec<ManageResult>,
+}
+
+/// The outcome of a batch request for one resource group, or for one entry which was not found.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ManageResult {
+    /// The ID of the resource group, or the entry from the request if it was not found.
+    pub resource: String,
+    pub ok: bool,
+
+    /// What happened, e.g. "now managed", "already managed", or "resource group not found".
+    pub message: String,
+
+    /// Why the entry failed, when it did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>,
+
+    /// Things that the caller should know about an entry that succeeded, as in SetManagedReply.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// A compiled form of one entry in a batch request.
+enum Matcher<'a> {
+    Exact(&'a str),
+    Glob(&'a str),
+    Regex(regex::Regex),
+}
+
+impl Matcher<'_> {
+    fn matches(&self, id: &str) -> bool {
+        match self {
+            Matcher::Exact(want) => *want == id,
+            Matcher::Glob(pattern) => glob_match(pattern, id),
+            Matcher::Regex(re) => re.is_match(id),
+        }
+    }
+}
+
+async fn set_managed_batch(
+    Json(payload): Json<SetManagedBatchArgs>,
+    cluster: Arc<Cluster>,
+) -> Result<(StatusCode, Json<SetManagedBatchReply>), RequestError> {
+    // Compile all of the patterns up front so that an invalid one is rejected before any resource
+    // is updated:
+    let mut matchers = Vec::with_capacity(payload.resources.len());
+    for entry in payload.resources.iter() {
+        let matcher = match payload.matching {
+            Matching::Exact => Matcher::Exact(entry),
+            Matching::Glob => Matcher::Glob(entry),
+            Matching::Regex => {
+                let re = regex::Regex::new(&format!("^(?:{entry})$")).map_err(|e| {
+                    RequestError::invalid(format!("invalid regular expression '{entry}': {e}"))
+                })?;
+                Matcher::Regex(re)
+            }
+        };
+        matchers.push((entry, matcher));
+    }
+
+    let action = if payload.managed {
+        "manage"
+    } else {
+        "unmanage"
+    };
+    let dry_run = is_dry_run(&cluster, payload.dry_run);
+    let state = if payload.managed {
+        "managed"
+    } else {
+        "unmanaged"
+    };
+    let mut reply = SetManagedBatchReply {
+        updated: Vec::new(),
+        not_found: Vec::new(),
+        dry_run,
+        results: Vec::new(),
+    };
+
+    let mut update = |rg: &ResourceGroup| {
+        if reply
+            .results
+            .iter()
+            .any(|result| result.resource == rg.id())
+        {
+            return;
+        }
+        let changed = if dry_run {
+            rg.get_managed() != payload.managed
+        } else {
+            rg.set_managed(payload.managed)
+        };
+        let (ok, message, code) = match (changed, dry_run) {
+            (true, true) => (true, format!("would be {state}"), None),
+            (true, false) => (true, format!("now {state}"), None),
+            (false, _) if payload.force => (true, format!("already {state}; no change"), None),
+            (false, _) => (
+                false,
+                format!("already {state}"),
+                Some(ErrorCode::AlreadyInState),
+            ),
+        };
+        if changed {
+            reply.updated.push(rg.id().to_string());
+        }
+        if !dry_run {
+            if changed {
+                warn!(
+                    "Resource group {}: set managed={}",
+                    rg.id(),
+                    if payload.managed { "true" } else { "false" }
+                );
+            }
+            let reason = (!ok).then(|| format!("resource group '{}' is already {state}", rg.id()));
+            cluster
+                .history()
+                .record(rg.id(), action, reason.as_deref().map_or(Ok(()), Err));
+        }
+        reply.results.push(ManageResult {
+            resource: rg.id().to_string(),
+            ok,
+            message,
+            code,
+            warnings: if payload.managed {
+                manage_warnings(rg)
+            } else {
+                Vec::new()
+            },
+        });
+    };
+
+    let mut not_found = Vec::new();
+    for (entry, matcher) in matchers {
+        let mut found = false;
+        for rg in cluster
+            .resource_groups()
+            .filter(|rg| matcher.matches(&rg.root.id))
+        {
+            found = true;
+            update(rg);
+        }
+        if !found {
+            not_found.push(entry.clone());
+        }
+    }
+    for name in payload.groups.iter() {
+        match cluster.group_resource_groups(name) {
+            Some(rgs) => rgs.into_iter().for_each(&mut update),
+            None => not_found.push(format!("@{name}")),
+        }
+    }
+
+    for entry in not_found {
+        let reason = if entry.starts_with('@') {
+            "group not found"
+        } else if payload.matching != Matching::Exact {
+            "no resource group matched"
+        } else {
+            "resource group not found"
+        };
+        if !dry_run {
+            cluster.history().record(&entry, action, Err(reason));
+        }
+        reply.results.push(ManageResult {
+            resource: entry.clone(),
+            ok: false,
+            message: reason.to_string(),
+            code: Some(ErrorCode::NotFound),
+            warnings: Vec::new(),
+        });
+        reply.not_found.push(entry);
+    }
+
+    let failed: Vec<Option<ErrorCode>> = reply
+        .results
+        .iter()
+        .filter(|result| !result.ok)
+        .map(|result| result.code)
+        .collect();
+    let status = if failed.is_empty() {
+        StatusCode::OK
+    } else if failed.len() < reply.results.len() {
+        StatusCode::MULTI_STATUS
+    } else if failed.iter().all(|code| *code == Some(ErrorCode::NotFound)) {
+        StatusCode::NOT_FOUND
+    } else if failed
+        .iter()
+        .all(|code| *code == Some(ErrorCode::AlreadyInState))
+    {
+        StatusCode::CONFLICT
+    } else {
+        StatusCode::MULTI_STATUS
+    };
+
+    Ok((status, Json(reply)))
+}
+
+/// Match `text` against a shell-style glob `pattern`, where `*` matches any sequence of
+/// characters and `?` matches any single character.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // The position of the most recent '*' in the pattern, and the position in the text that it was
+    // matched up to, used for backtracking:
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetParamsArgs {
+    /// The parameters to set. Parameters which are not given keep their current values.
+    pub parameters: HashMap<String, String>,
+
+    /// Whether to restart the resource, and the resources which depend on it, so that the new
+    /// parameters take effect right away. Otherwise, they take effect the next time it is started.
+    pub restart: bool,
+
+    /// How many seconds to wait for a restart to finish before giving up.
+    pub timeout: u64,
+
+    /// Only report what would be done, without changing anything.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetParamsReply {
+    pub id: String,
+    pub parameters: HashMap<String, String>,
+
+    /// Whether the resource was restarted. A resource which is not running is not restarted.
+    pub restarted: bool,
+}
+
+/// Change the parameters of a single resource, optionally restarting it where it is running.
+async fn set_params(
+    Path(resource_id): Path<String>,
+    payload: Json<SetParamsArgs>,
+    cluster: Arc<Cluster>,
+) -> Result<Response, RequestError> {
+    let mut keys: Vec<&String> = payload.parameters.keys().collect();
+    keys.sort();
+    let keys: Vec<&str> = keys.into_iter().map(|key| key.as_str()).collect();
+    let action = format!(
+        "set {}{}",
+        keys.join(", "),
+        if payload.restart { " and restart" } else { "" }
+    );
+    let dry_run = is_dry_run(&cluster, payload.dry_run);
+    let outcome = try_set_params(Path(resource_id.clone()), payload, Arc::clone(&cluster)).await;
+    if !dry_run {
+        record(&cluster, &resource_id, &action, &outcome);
+    }
+    outcome
+}
+
+async fn try_set_params(
+    Path(resource_id): Path<String>,
+    Json(payload): Json<SetParamsArgs>,
+    cluster: Arc<Cluster>,
+) -> Result<Response, RequestError> {
+    let Some((_, res)) = cluster.find_resource(&resource_id) else {
+        return Err(RequestError::not_found());
+    };
+
+    if payload.parameters.is_empty() {
+        return Err(RequestError::invalid("no parameters given".to_string()));
+    }
+    if payload.parameters.keys().any(|key| key.is_empty()) {
+        return Err(RequestError::invalid(
+            "parameter names must not be empty".to_string(),
+        ));
+    }
+    if let Some(schema) = Schema::of(&res.kind) {
+        let mut parameters = res.parameters();
+        parameters.extend(payload.parameters.clone());
+        let problems = schema.check(&parameters);
+        if !problems.is_empty() {
+            return Err(RequestError::invalid(format!(
+                "invalid parameters: {}",
+                problems.join("; ")
+            )));
+        }
+    }
+
+    let location = res.location().filter(|_| payload.restart);
+
+    if is_dry_run(&cluster, payload.dry_run) {
+        let mut params: Vec<String> = payload
+            .parameters
+            .iter()
+            .map(|(key, val)| format!("{key}={val}"))
+            .collect();
+        params.sort();
+        let restart = match location.and_then(|loc| res.node(loc)) {
+            Some(host) => format!(" and restart it on {}", host.id()),
+            None => String::new(),
+        };
+        return Ok(dry_run_reply(format!(
+            "would set {} on '{resource_id}'{restart}",
+            params.join(", ")
+        )));
+    }
+
+    warn!(
+        "Resource {resource_id}: setting parameters {:?}",
+        payload.parameters
+    );
+    let parameters = res.update_parameters(payload.parameters);
+    if let Some(loc) = location {
+        warn!("Resource {resource_id}: restarting with the new parameters.");
+
+        let timeout = Duration::from_secs(payload.timeout);
+        let action = cluster.begin_action(vec![resource_id.clone()]);
+        tokio::task::spawn_blocking({
+            let cluster = Arc::clone(&cluster);
+            let resource_id = resource_id.clone();
+            let cancel = action.token().clone();
+            move || run_restart(&cluster, &resource_id, loc, timeout, &cancel)
+        })
+        .await
+        .unwrap_or_else(|e| Err(RequestError::internal(format!("{e}"))))
+        .inspect_err(|e| {
+            warn!("Resource {resource_id}: could not restart: {}", e.message);
+        })?;
+    }
+
+    Ok(Json(SetParamsReply {
+        id: resource_id,
+        parameters,
+        restarted: location.is_some(),
+    })
+    .into_response())
+}
+
+/// Restart a resource at `loc`, giving up after `timeout`, or once `cancel` is canceled. This must
+/// be called on a blocking thread.
+///
+/// The resource is looked up again here, since its group may have been removed by a reload of the
+/// config since the request was checked.
+fn run_restart(
+    cluster: &Cluster,
+    resource_id: &str,
+    loc: Location,
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<(), RequestError> {
+    let Some((_, res)) = cluster.find_resource(resource_id) else {
+        return Err(RequestError::not_found());
+    };
+
+    restore_if_canceled(&[res], cancel, || {
+        block_on_with_timeout(timeout, cancel, res.restart(loc))
+    })
+}
+
+/// The actions that can be performed on a single resource.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResourceAction {
+    Start,
+    Stop,
+}
+
+impl std::fmt::Display for ResourceAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceAction::Start => write!(f, "start"),
+            ResourceAction::Stop => write!(f, "stop"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ResourceActionArgs {
+    /// How many seconds to wait for the action to finish before giving up.
+    pub timeout: u64,
+
+    /// Only report what would be done, without doing it.
+    #[serde(default)]
+    pub dry_run: bool,
+
+    /// Forget that the manager gave up on starting or stopping the resource, before doing the
+    /// action.
+    #[serde(default)]
+    pub clear_failed: bool,
+}
+
+/// Start or stop a single resource, and reply with its status once the remote agent reports that
+/// it is running or stopped.
+///
+/// A resource is started on its home node, and stopped wherever it is running. An unmanaged
+/// resource is not started, since the admin has asked for the manager to leave it alone.
+async fn resource_action(
+    action: ResourceAction,
+    Path(resource_id): Path<String>,
+    payload: Json<ResourceActionArgs>,
+    cluster: Arc<Cluster>,
+) -> Result<Response, RequestError> {
+    let dry_run = is_dry_run(&cluster, payload.dry_run);
+    let outcome = try_resource_action(
+        action,
+        Path(resource_id.clone()),
+        payload,
+        Arc::clone(&cluster),
+    )
+    .await;
+    if !dry_run {
+        record(&cluster, &resource_id, &action.to_string(), &outcome);
+    }
+    outcome
+}
+
+async fn try_resource_action(
+    action: ResourceAction,
+    Path(resource_id): Path<String>,
+    Json(payload): Json<ResourceActionArgs>,
+    cluster: Arc<Cluster>,
+) -> Result<Response, RequestError> {
+    let Some((rg, res)) = cluster.find_resource(&resource_id) else {
+        return Err(RequestError::not_found());
+    };
+
+    // Once the manager has given up on a resource, an admin must say so before it is acted on
+    // again:
+    if let Some(retry) = res.retry().filter(|retry| retry.next_attempt.is_none()) {
+        if !payload.clear_failed {
+            return Err(RequestError::conflict(format!(
+                "'{resource_id}' failed to {} {} times in a row: {}. Run 'halo {action} \
+                     --clear-failed {resource_id}' to try again.",
+                retry.action, retry.failures, retry.reason
+            )));
+        }
+        if !is_dry_run(&cluster, payload.dry_run) {
+            res.clear_failures();
+        }
+    }
+    if payload.clear_failed && !is_dry_run(&cluster, payload.dry_run) {
+        rg.set_migration_result(Ok(()));
+    }
+
+    if action == ResourceAction::Start {
+        if !rg.get_managed() {
+            return Err(RequestError::conflict(format!(
+                "resource group '{0}' is unmanaged. Run 'halo manage {0}' first.",
+                rg.id()
+            )));
+        }
+        if res.is_running() {
+            return Ok(Json(ResourceJson::build(&cluster, rg, res)).into_response());
+        }
+        if res.target_state() == TargetState::Stopped {
+            return Err(RequestError::conflict(format!(
+                "'{resource_id}' is disabled. Run 'halo enable {resource_id}' first."
+            )));
+        }
+        if !rg.may_start_on(Location::Home) {
+            return Err(RequestError::conflict(format!(
+                "host '{0}' is on standby. Run 'halo unstandby {0}' first.",
+                rg.home_node().id()
+            )));
+        }
+        if let Some(dep) = cluster.unmet_dependency(res) {
+            return Err(RequestError::conflict(format!(
+                "'{resource_id}' depends on '{dep}', which is not running. Start it first."
+            )));
+        }
+    }
+
+    if is_dry_run(&cluster, payload.dry_run) {
+        // This is where run_resource_action() would start or stop the resource:
+        let loc = match action {
+            ResourceAction::Start => Location::Home,
+            ResourceAction::Stop => res.location().unwrap_or(Location::Home),
+        };
+        let host = res.node(loc).map(|host| host.id()).unwrap_or_default();
+        let dependents: Vec<String> = match action {
+            ResourceAction::Start => Vec::new(),
+            ResourceAction::Stop => cluster
+                .dependents_of(res)
+                .into_iter()
+                .filter(|dependent| dependent.is_running())
+                .map(|dependent| format!("'{}'", dependent.id))
+                .collect(),
+        };
+        let after = if dependents.is_empty() {
+            String::new()
+        } else {
+            format!(", after stopping {}", dependents.join(", "))
+        };
+        return Ok(dry_run_reply(format!(
+            "would {action} '{resource_id}' on {host}{after}"
+        )));
+    }
+
+    warn!("Resource {resource_id}: {action} requested.");
+
+    let timeout = Duration::from_secs(payload.timeout);
+    let running = cluster.begin_action(vec![resource_id.clone()]);
+    let outcome = tokio::task::spawn_blocking({
+        let cluster = Arc::clone(&cluster);
+        let resource_id = resource_id.clone();
+        let cancel = running.token().clone();
+        move || run_resource_action(&cluster, &resource_id, action, timeout, &cancel)
+    })
+    .await
+    .unwrap_or_else(|e| Err(RequestError::internal(format!("{e}"))));
+
+    if let Err(e) = &outcome {
+        warn!("Resource {resource_id}: could not {action}: {}", e.message);
+    }
+    outcome?;
+    res.clear_failures();
+
+    Ok(Json(ResourceJson::build(&cluster, rg, res)).into_response())
+}
+
+/// Perform `action` on a resource, giving up after `timeout`, or once `cancel` is canceled. This
+/// must be called on a blocking thread.
+///
+/// A resource is only stopped after the running resources which depend on it, so they are stopped
+/// first, in reverse dependency order.
+fn run_resource_action(
+    cluster: &Cluster,
+    resource_id: &str,
+    action: ResourceAction,
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<(), RequestError> {
+    let Some((_, res)) = cluster.find_resource(resource_id) else {
+        return Err(RequestError::not_found());
+    };
+
+    match action {
+        ResourceAction::Start => restore_if_canceled(&[res], cancel, || {
+            block_on_with_timeout(timeout, cancel, res.start_and_wait(Location::Home))
+        }),
+        ResourceAction::Stop => {
+            let dependents = cluster.dependents_of(res);
+            let mut ids: Vec<String> = dependents.iter().map(|res| res.id.clone()).collect();
+            ids.push(resource_id.to_string());
+            let _stopping = cluster.begin_stopping(ids);
+
+            let mut resources = dependents.clone();
+            resources.push(res);
+            restore_if_canceled(&resources, cancel, || {
+                block_on_with_timeout(timeout, cancel, async {
+                    for dependent in dependents.iter().filter(|dependent| dependent.is_running()) {
+                        warn!(
+                            "Resource {}: stopping, since it depends on {resource_id}.",
+                            dependent.id
+                        );
+                        dependent
+                            .stop_and_wait(dependent.location().unwrap_or(Location::Home))
+                            .await?;
+                    }
+                    res.stop_and_wait(res.location().unwrap_or(Location::Home))
+                        .await
+                })
+            })
+        }
+    }
+}
+
+/// Run `action` on `resources`, and if it is canceled part way, set each of them back to the
+/// status it had before. The agents may not have finished what they were asked to do, so this is
+/// only a guess at where they are, which the next pass over the resources checks.
+fn restore_if_canceled(
+    resources: &[&Resource],
+    cancel: &CancellationToken,
+    action: impl FnOnce() -> Result<(), RequestError>,
+) -> Result<(), RequestError> {
+    let before: Vec<ResourceStatus> = resources.iter().map(|res| res.get_status()).collect();
+    let outcome = action();
+    if outcome.is_err() && cancel.is_cancelled() {
+        for (res, status) in resources.iter().zip(before) {
+            res.set_status(status);
+        }
+    }
+    outcome
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CancelArgs {
+    /// Only report what would be canceled, without canceling anything.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CancelReply {
+    pub id: String,
+
+    /// How many actions were canceled: none if nothing was being done to the resource.
+    pub canceled: usize,
+}
+
+/// Cancel the actions on a resource which are still in progress: a start, stop, or restart of the
+/// resource, or a migration of its resource group, which an admin requested, or a start or stop of
+/// it by the management loops. Each action gives up, and one that an admin requested replies that
+/// it was canceled. It is not an error for there to be nothing to cancel.
+async fn cancel(
+    Path(resource_id): Path<String>,
+    Json(payload): Json<CancelArgs>,
+    cluster: Arc<Cluster>,
+) -> Result<Response, RequestError> {
+    let dry_run = is_dry_run(&cluster, payload.dry_run);
+    let outcome = try_cancel(&resource_id, dry_run, &cluster);
+    if !dry_run {
+        record(&cluster, &resource_id, "cancel", &outcome);
+    }
+    outcome
+}
+
+fn try_cancel(
+    resource_id: &str,
+    dry_run: bool,
+    cluster: &Cluster,
+) -> Result<Response, RequestError> {
+    if cluster.find_resource(resource_id).is_none() {
+        return Err(RequestError::not_found());
+    }
+
+    let running = cluster.running_actions(resource_id);
+    if dry_run {
+        return Ok(dry_run_reply(match running.len() {
+            0 => format!("'{resource_id}' has no action in progress to cancel"),
+            1 => format!("would cancel 1 action on '{resource_id}'"),
+            n => format!("would cancel {n} actions on '{resource_id}'"),
+        }));
+    }
+
+    if !running.is_empty() {
+        warn!("Resource {resource_id}: canceling the actions in progress.");
+    }
+    for token in running.iter() {
+        token.cancel();
+    }
+    Ok(Json(CancelReply {
+        id: resource_id.to_string(),
+        canceled: running.len(),
+    })
+    .into_response())
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetTargetStateArgs {
+    pub target_state: TargetState,
+
+    /// Only report what would be done, without changing anything.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetTargetStateReply {
+    pub id: String,
+    pub target_state: TargetState,
+
+    /// Whether the target state was changed, rather than already being the one requested.
+    pub changed: bool,
+}
+
+/// Set whether the manager should keep a resource running or stopped. This does not wait for the
+/// resource to be started or stopped: the management loop does that on its next pass, if the
+/// resource is managed.
+async fn set_target_state(
+    Path(resource_id): Path<String>,
+    Json(payload): Json<SetTargetStateArgs>,
+    cluster: Arc<Cluster>,
+) -> Result<Response, RequestError> {
+    let action = match payload.target_state {
+        TargetState::Started => "enable",
+        TargetState::Stopped => "disable",
+    };
+    let dry_run = is_dry_run(&cluster, payload.dry_run);
+    let outcome = try_set_target_state(&resource_id, &payload, dry_run, &cluster);
+    if !dry_run {
+        record(&cluster, &resource_id, action, &outcome);
+    }
+    outcome
+}
+
+fn try_set_target_state(
+    resource_id: &str,
+    payload: &SetTargetStateArgs,
+    dry_run: bool,
+    cluster: &Cluster,
+) -> Result<Response, RequestError> {
+    let Some((_, res)) = cluster.find_resource(resource_id) else {
+        return Err(RequestError::not_found());
+    };
+
+    let target = payload.target_state;
+    if dry_run {
+        if res.target_state() == target {
+            return Ok(dry_run_reply(format!(
+                "'{resource_id}' already has target state {target}"
+            )));
+        }
+        return Ok(dry_run_reply(format!(
+            "would set the target state of '{resource_id}' to {target}"
+        )));
+    }
+
+    let changed = res.set_target_state(target);
+    if changed {
+        warn!("Resource {resource_id}: set target state to {target}");
+    }
+    Ok(Json(SetTargetStateReply {
+        id: resource_id.to_string(),
+        target_state: target,
+        changed,
+    })
+    .into_response())
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MigrateArgs {
+    /// The ID of the host to move the resource group to.
+    pub to: String,
+
+    /// How many seconds to wait for the migration to finish before giving up.
+    pub timeout: u64,
+
+    /// Only report what would be done, without moving anything.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MigrateReply {
+    /// The ID of the resource group that was moved.
+    pub id: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// Move the resource group containing a resource to the given node in its failover pair.
+///
+/// In Manage mode, the host task which is managing the group is asked to hand it over to its
+/// partner, the same way as a failback; otherwise, no task is responsible for the group, so it is
+/// stopped and started here.
+async fn migrate(
+    Path(resource_id): Path<String>,
+    payload: Json<MigrateArgs>,
+    cluster: Arc<Cluster>,
+) -> Result<Response, RequestError> {
+    let action = format!("migrate to {}", payload.to);
+    let dry_run = is_dry_run(&cluster, payload.dry_run);
+    let outcome = try_migrate(Path(resource_id.clone()), payload, Arc::clone(&cluster)).await;
+    if !dry_run {
+        record(&cluster, &resource_id, &action, &outcome);
+    }
+    outcome
+}
+
+async fn try_migrate(
+    Path(resource_id): Path<String>,
+    Json(payload): Json<MigrateArgs>,
+    cluster: Arc<Cluster>,
+) -> Result<Response, RequestError> {
+    let Some((rg, _)) = cluster.find_resource(&resource_id) else {
+        return Err(RequestError::not_found());
+    };
+
+    // The resource groups which must run with this one are moved along with it:
+    let along = cluster.moves_with(rg);
+    if !along.is_empty() {
+        let rgs = std::iter::once(rg).chain(along).collect();
+        return migrate_together(&cluster, rgs, &payload, |mut replies| {
+            Json(replies.remove(0)).into_response()
+        })
+        .await;
+    }
+
+    let plan = plan_migration(&cluster, rg, &payload.to)?;
+    if plan.from == plan.to {
+        return Ok(Json(plan.reply).into_response());
+    }
+    check_quorum(&cluster)?;
+
+    if is_dry_run(&cluster, payload.dry_run) {
+        if rg.is_migrating() {
+            return Err(already_migrating(rg));
+        }
+        return Ok(dry_run_reply(format!(
+            "would move resource group '{}' from {} to {}",
+            plan.reply.id, plan.reply.from, plan.reply.to
+        )));
+    }
+
+    let Some(_migration) = rg.begin_migration(plan.to) else {
+        return Err(already_migrating(rg));
+    };
+
+    run_migration(&cluster, &plan, Duration::from_secs(payload.timeout)).await?;
+
+    Ok(Json(plan.reply).into_response())
+}
+
+/// Where a resource group is, and where it has been asked to move to.
+struct MigrationPlan<'a> {
+    rg: &'a ResourceGroup,
+    from: Location,
+    to: Location,
+    reply: MigrateReply,
+}
+
+impl MigrationPlan<'_> {
+    /// The plan for moving the group back to where it came from.
+    fn reversed(&self) -> Self {
+        Self {
+            rg: self.rg,
+            from: self.to,
+            to: self.from,
+            reply: MigrateReply {
+                id: self.reply.id.clone(),
+                from: self.reply.to.clone(),
+                to: self.reply.from.clone(),
+            },
+        }
+    }
+}
+
+/// Check that `rg` can be moved to the host `to`, and work out where it is moving from.
+fn plan_migration<'a>(
+    cluster: &Cluster,
+    rg: &'a ResourceGroup,
+    to: &str,
+) -> Result<MigrationPlan<'a>, RequestError> {
+    if !rg.get_managed() {
+        return Err(RequestError::conflict(format!(
+            "resource group '{0}' is unmanaged. Run 'halo manage {0}' first.",
+            rg.id()
+        )));
+    }
+
+    let nodes: Vec<(Location, &Arc<crate::host::Host>)> = [Location::Home, Location::Away]
+        .into_iter()
+        .filter(|loc| rg.may_start_on(*loc))
+        .filter_map(|loc| rg.root.node(loc).map(|host| (loc, host)))
+        .collect();
+    let Some(&(to_loc, to_host)) = nodes.iter().find(|(_, host)| host.id() == to) else {
+        let names: Vec<String> = nodes.iter().map(|(_, host)| host.id()).collect();
+        let reason = if cluster.get_host(to).is_none() {
+            format!("'{to}' is not a host in this cluster")
+        } else {
+            format!("resource group '{}' can not run on '{to}'", rg.id())
+        };
+        return Err(RequestError::invalid(format!(
+            "{reason}; it can run on: {}",
+            names.join(", ")
+        )));
+    };
+
+    let Some(from) = rg.root.location() else {
+        return Err(RequestError::conflict(format!(
+            "resource group '{0}' is not running. Use 'halo start {0}' instead.",
+            rg.id()
+        )));
+    };
+    // The failover node must exist, since the group is running there or the target is there:
+    let from_host = rg.root.node(from).unwrap();
+
+    if let Some(other) = cluster.anti_affine_with(rg).into_iter().find(|other| {
+        other
+            .running_on()
+            .is_some_and(|host| host.id() == to_host.id())
+    }) {
+        return Err(RequestError::conflict(format!(
+            "resource group '{}' must not run on the same host as '{}', which is running on {to}",
+            rg.id(),
+            other.id()
+        )));
+    }
+
+    Ok(MigrationPlan {
+        rg,
+        from,
+        to: to_loc,
+        reply: MigrateReply {
+            id: rg.id().to_string(),
+            from: from_host.id(),
+            to: to_host.id(),
+        },
+    })
+}
+
+/// Resources are only moved while the cluster has quorum.
+fn check_quorum(cluster: &Cluster) -> Result<(), RequestError> {
+    if cluster.has_quorum() {
+        Ok(())
+    } else {
+        Err(RequestError::no_quorum(
+            "the cluster does not have quorum, so resources are not being moved".to_string(),
+        ))
+    }
+}
+
+fn already_migrating(rg: &ResourceGroup) -> RequestError {
+    RequestError::conflict(format!(
+        "resource group '{}' is already being migrated.",
+        rg.id()
+    ))
+}
+
+/// Carry out `plan`. The caller must hold the group's migration guard.
+///
+/// In Manage mode, the host task which is managing the group is asked to hand it over to its
+/// partner; otherwise, the group is stopped and started here.
+async fn run_migration(
+    cluster: &Arc<Cluster>,
+    plan: &MigrationPlan<'_>,
+    timeout: Duration,
+) -> Result<(), RequestError> {
+    let rg = plan.rg;
+    let (from, to) = (plan.from, plan.to);
+    warn!(
+        "Resource group {}: migration from {} to {} requested.",
+        rg.id(),
+        plan.reply.from,
+        plan.reply.to
+    );
+
+    let running = cluster.begin_action(rg.resources().map(|res| res.id.clone()).collect());
+    let outcome = if cluster.may_act() {
+        // The management loops move the group. The stop or start which they are running for it is
+        // an action of its own, so canceling gives up on that too, rather than only on waiting:
+        rg.root
+            .node(from)
+            .unwrap()
+            .command(HostCommand::Migrate(rg.id().to_string()))
+            .await;
+        let moved = tokio::time::timeout(timeout, async {
+            while !rg.resources().all(|res| res.location() == Some(to)) {
+                tokio::time::sleep(cluster.args.poll_delay()).await;
+            }
+        });
+        tokio::select! {
+            moved = moved => moved.map_err(|_| timed_out(timeout)),
+            _ = running.token().cancelled() => Err(canceled()),
+        }
+    } else {
+        let id = rg.id().to_string();
+        tokio::task::spawn_blocking({
+            let cluster = Arc::clone(cluster);
+            let cancel = running.token().clone();
+            move || {
+                let rg = cluster.get_resource_group(&id);
+                block_on_with_timeout(timeout, &cancel, rg.move_resources(from, to))
+            }
+        })
+        .await
+        .unwrap_or_else(|e| Err(RequestError::internal(format!("{e}"))))
+    };
+
+    if let Err(e) = &outcome {
+        warn!(
+            "Resource group {}: could not migrate: {}",
+            rg.id(),
+            e.message
+        );
+    }
+    rg.set_migration_result(outcome.as_ref().map(|_| ()).map_err(|e| e.message.as_str()));
+    outcome
+}
+
+/// The reply to a group migration lists every resource group in the group, including the ones
+/// that were already on the target host.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GroupMigrateReply {
+    pub group: String,
+    pub resource_groups: Vec<MigrateReply>,
+}
+
+/// Move every resource group with a member in the group `name` to the given host.
+///
+/// This is all or nothing: every resource group is checked before any is moved, and if one can
+/// not be moved, the ones which already were are moved back.
+async fn migrate_group(
+    Path(name): Path<String>,
+    payload: Json<MigrateArgs>,
+    cluster: Arc<Cluster>,
+) -> Result<Response, RequestError> {
+    let action = format!("migrate to {}", payload.to);
+    let dry_run = is_dry_run(&cluster, payload.dry_run);
+    let outcome = try_migrate_group(Path(name.clone()), payload, Arc::clone(&cluster)).await;
+    if !dry_run {
+        record(&cluster, &format!("@{name}"), &action, &outcome);
+    }
+    outcome
+}
+
+async fn try_migrate_group(
+    Path(name): Path<String>,
+    Json(payload): Json<MigrateArgs>,
+    cluster: Arc<Cluster>,
+) -> Result<Response, RequestError> {
+    let Some(mut rgs) = cluster.group_resource_groups(&name) else {
+        return Err(RequestError::not_found());
+    };
+    // The resource groups which must run with the members are moved along with them:
+    for rg in rgs.clone() {
+        for other in cluster.moves_with(rg) {
+            if !rgs.iter().any(|rg| rg.id() == other.id()) {
+                rgs.push(other);
+            }
+        }
+    }
+
+    migrate_together(&cluster, rgs, &payload, |replies| {
+        Json(GroupMigrateReply {
+            group: name,
+            resource_groups: replies,
+        })
+        .into_response()
+    })
+    .await
+}
+
+/// Move each of `rgs` to the host `payload.to`, and make the reply from where each one was moved
+/// from, in order, with `reply`.
+///
+/// This is all or nothing: every resource group is checked before any is moved, and if one can
+/// not be moved, the ones which already were are moved back.
+async fn migrate_together(
+    cluster: &Arc<Cluster>,
+    rgs: Vec<&ResourceGroup>,
+    payload: &MigrateArgs,
+    reply: impl FnOnce(Vec<MigrateReply>) -> Response,
+) -> Result<Response, RequestError> {
+    let plans = rgs
+        .into_iter()
+        .map(|rg| plan_migration(cluster, rg, &payload.to))
+        .collect::<Result<Vec<_>, _>>()?;
+    let pending: Vec<&MigrationPlan> = plans.iter().filter(|plan| plan.from != plan.to).collect();
+    if !pending.is_empty() {
+        check_quorum(cluster)?;
+    }
+
+    if is_dry_run(cluster, payload.dry_run) {
+        if let Some(plan) = pending.iter().find(|plan| plan.rg.is_migrating()) {
+            return Err(already_migrating(plan.rg));
+        }
+        if !pending.is_empty() {
+            let moves: Vec<String> = pending
+                .iter()
+                .map(|plan| {
+                    format!(
+                        "resource group '{}' from {} to {}",
+                        plan.reply.id, plan.reply.from, plan.reply.to
+                    )
+                })
+                .collect();
+            return Ok(dry_run_reply(format!("would move {}", moves.join(", "))));
+        }
+    }
+
+    let mut guards = Vec::with_capacity(pending.len());
+    for plan in pending.iter() {
+        let Some(guard) = plan.rg.begin_migration(plan.to) else {
+            return Err(already_migrating(plan.rg));
+        };
+        guards.push(guard);
+    }
+
+    let timeout = Duration::from_secs(payload.timeout);
+    for (i, plan) in pending.iter().enumerate() {
+        let Err(e) = run_migration(cluster, plan, timeout).await else {
+            continue;
+        };
+        drop(guards);
+
+        // Move back the groups which were already moved, most recent first, so that the group is
+        // left the way it was found:
+        let mut stranded = Vec::new();
+        for moved in pending[..i].iter().rev() {
+            let back = moved.reversed();
+            let rolled_back = match moved.rg.begin_migration(back.to) {
+                Some(_migration) => run_migration(cluster, &back, timeout).await.is_ok(),
+                None => false,
+            };
+            if !rolled_back {
+                stranded.push(format!("'{}'", moved.reply.id));
+            }
+        }
+
+        let mut message = format!(
+            "could not move resource group '{}': {}",
+            plan.reply.id, e.message
+        );
+        if i > 0 && stranded.is_empty() {
+            message += "; moved the others back";
+        } else if !stranded.is_empty() {
+            message += &format!(
+                "; could not move {} back, which are still on {}",
+                stranded.join(", "),
+                payload.to
+            );
+        }
+        return Err(RequestError { message, ..e });
+    }
+
+    Ok(reply(plans.into_iter().map(|plan| plan.reply).collect()))
+}
+
+/// Run `future` to completion on a runtime of its own, giving up after `timeout`, or once `cancel`
+/// is canceled.
+///
+/// Operations on remote agents can not be awaited by a request handler directly, since they are not
+/// `Send`, so they are run this way instead; this must be called on a blocking thread.
+fn block_on_with_timeout<F>(
+    timeout: Duration,
+    cancel: &CancellationToken,
+    future: F,
+) -> Result<(), RequestError>
+where
+    F: std::future::Future<Output = Result<(), String>>,
+{
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| RequestError::internal(format!("could not launch runtime: {e}")))?;
+
+    let outcome = rt.block_on(async {
+        tokio::select! {
+            outcome = tokio::time::timeout(timeout, future) => Some(outcome),
+            _ = cancel.cancelled() => None,
+        }
+    });
+    match outcome {
+        Some(Ok(Ok(()))) => Ok(()),
+        Some(Ok(Err(reason))) => Err(RequestError::agent(reason)),
+        Some(Err(_)) => Err(timed_out(timeout)),
+        None => Err(canceled()),
+    }
+}
+
+fn canceled() -> RequestError {
+    RequestError::new(
+        StatusCode::CONFLICT,
+        ErrorCode::Canceled,
+        "canceled with 'halo cancel'",
+    )
+}
+
+fn timed_out(timeout: Duration) -> RequestError {
+    RequestError::new(
+        StatusCode::GATEWAY_TIMEOUT,
+        ErrorCode::Timeout,
+        format!("did not finish within {}s", timeout.as_secs()),
+    )
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HostArgs {
+    pub command: String,
+}
+
+async fn host_post(
+    Path(host_id): Path<String>,
+    Json(payload): Json<HostArgs>,
+    cluster: Arc<Cluster>,
+) -> Result<(), RequestError> {
+    if cluster.args.dry_run {
+        return Err(RequestError::conflict(
+            "The manager was started with --dry-run, so it does not act on hosts.",
+        ));
+    }
+
+    match payload.command.as_str() {
+        "failback" => {
+            let Some(host) = cluster.get_host(&host_id) else {
+                return Err(RequestError::not_found());
+            };
+
+            let Some(partner) = host.failover_partner() else {
+                return Err(RequestError::invalid(
+                    "Host does not have a failover partner.",
+                ));
+            };
+            if !cluster.has_quorum() {
+                return Err(RequestError::no_quorum(
+                    "The cluster does not have quorum, so resources are not being moved.",
+                ));
+            }
+
+            partner.command(HostCommand::Failback).await;
+
+            Ok(())
+        }
+        "standby" | "unstandby" => {
+            let Some(host) = cluster.get_host(&host_id) else {
+                return Err(RequestError::not_found());
+            };
+
+            let standby = payload.command == "standby";
+            if host.is_standby() != standby {
+                warn!("Host {host_id}: setting standby={standby}.");
+                host.set_standby(standby);
+            }
+
+            Ok(())
+        }
+        "reset_breaker" => {
+            let Some(host) = cluster.get_host(&host_id) else {
+                return Err(RequestError::not_found());
+            };
+
+            if host.reset_fence_breaker() {
+                warn!("Host {host_id}: fencing breaker reset; fencing will be tried again.");
+            }
+
+            Ok(())
+        }
+        _ => Err(RequestError::invalid("Unsupported command.")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match(b"secret", b"secret"));
+        assert!(!tokens_match(b"secreT", b"secret"));
+        assert!(!tokens_match(b"secre", b"secret"));
+        assert!(!tokens_match(b"", b"secret"));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("ost*", "ost01"));
+        assert!(glob_match("ost*", "ost"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("o?t0*", "ost01"));
+        assert!(glob_match("*ost*2", "fs_ost12"));
+        assert!(glob_match("mdt_0", "mdt_0"));
+        assert!(!glob_match("ost*", "mdt01"));
+        assert!(!glob_match("ost?", "ost01"));
+        assert!(!glob_match("*2", "ost21"));
+        assert!(!glob_match("", "ost"));
+    }
+
+    /// A reload of the config may remove a resource's group after a request to restart it was
+    /// checked, but before the restart begins. The resource is then gone, rather than an error.
+    #[test]
+    fn test_restart_removed_by_reload() {
+        let dir = std::env::temp_dir().join(format!("halo_restart_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.yaml");
+        let simple = std::fs::read_to_string(crate::test_env::test_path("simple.yaml")).unwrap();
+        std::fs::write(&path, &simple).unwrap();
+        let cluster = Cluster::from_config(vec![path.to_str().unwrap().to_string()]).unwrap();
+
+        let (rg, _) = cluster.find_resource("test_mgt").unwrap();
+        for res in rg.resources() {
+            res.set_status(ResourceStatus::Stopped);
+        }
+        std::fs::write(&path, simple.replace("test_", "other_")).unwrap();
+        let reloaded = cluster.reload().unwrap();
+        assert!(reloaded
+            .applied
+            .contains(&"resource group test_zpool (removed)".to_string()));
+
+        let cancel = CancellationToken::new();
+        let outcome = run_restart(
+            &cluster,
+            "test_mgt",
+            Location::Home,
+            Duration::from_secs(1),
+            &cancel,
+        );
+        assert_eq!(outcome, Err(RequestError::not_found()));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_error_code_serde() {
+        let reply = SetManagedReply {
+            error: false,
+            message: "resource group 'ost0' is now managed".to_string(),
+            code: None,
+            changed: true,
+            warnings: Vec::new(),
+        };
+        let json = serde_json::to_string(&reply).unwrap();
+        assert!(!json.contains("code"));
+        assert!(!json.contains("warnings"));
+
+        let reply: SetManagedReply = serde_json::from_str(
+            r#"{"error": true, "message": "already managed", "code": "already_in_state"}"#,
+        )
+        .unwrap();
+        assert_eq!(reply.code, Some(ErrorCode::AlreadyInState));
+
+        // Codes from a newer manager, and replies from an older one, are understood too:
+        let reply: SetManagedReply =
+            serde_json::from_str(r#"{"error": true, "message": "", "code": "no_such_code"}"#)
+                .unwrap();
+        assert_eq!(reply.code, Some(ErrorCode::Unknown));
+        let reply: SetManagedReply =
+            serde_json::from_str(r#"{"error": true, "message": ""}"#).unwrap();
+        assert_eq!(reply.code, None);
+    }
+
+    #[test]
+    fn test_request_error_reply() {
+        let body = |e: RequestError| {
+            let response = e.into_response();
+            let status = response.status();
+            let body =
+                futures::executor::block_on(axum::body::to_bytes(response.into_body(), usize::MAX))
+                    .unwrap();
+            (status, serde_json::from_slice::<ErrorReply>(&body).unwrap())
+        };
+
+        let (status, reply) = body(RequestError::agent("zpool import failed"));
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(reply.error);
+        assert_eq!(reply.message, "zpool import failed");
+        assert_eq!(reply.code, Some(ErrorCode::AgentFailure));
+
+        // Errors which share a status code are told apart by their code:
+        let (status, reply) = body(RequestError::invalid("no parameters given"));
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(reply.code, Some(ErrorCode::ValidationError));
+        let (conflict, reply) = body(RequestError::conflict("disabled"));
+        assert_eq!(reply.code, Some(ErrorCode::Conflict));
+        let (status, reply) = body(canceled());
+        assert_eq!(status, conflict);
+        assert_eq!(reply.code, Some(ErrorCode::Canceled));
+        let (status, reply) = body(timed_out(Duration::from_secs(5)));
+        assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(reply.code, Some(ErrorCode::Timeout));
+
+        let json = serde_json::to_string(&ErrorReply {
+            error: true,
+            message: String::new(),
+            code: None,
+        })
+        .unwrap();
+        assert!(!json.contains("code"));
     }
 }
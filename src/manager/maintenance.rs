@@ -0,0 +1,123 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+//! Maintenance mode, which freezes the automatic management of the whole cluster.
+//!
+//! While it is on, the management loops keep observing the resources, but take no action on them
+//! at all, whatever the mode of the manager and whether or not each resource is managed. Unlike
+//! Observe mode, it is recorded in a file, so that it stays on when the manager restarts, until an
+//! admin turns it off.
+
+use std::{io, sync::Mutex};
+
+/// Whether maintenance mode is on, along with the file which records it.
+#[derive(Debug)]
+pub struct Maintenance {
+    path: String,
+
+    /// When maintenance mode was turned on, in seconds since the Unix epoch, while it is on.
+    since: Mutex<Option<u64>>,
+}
+
+impl Maintenance {
+    /// Maintenance mode, off, recorded in the file at `path`.
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            since: Mutex::new(None),
+        }
+    }
+
+    /// Read whether maintenance mode is on from its file: it is on if the file exists. The file
+    /// holds the time that it was turned on; if that can not be read, it is taken to be now.
+    pub fn load(&self) -> io::Result<()> {
+        let since = match std::fs::read_to_string(&self.path) {
+            Ok(contents) => Some(
+                contents
+                    .trim()
+                    .parse()
+                    .unwrap_or_else(|_| super::history::now()),
+            ),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        *self.since.lock().unwrap() = since;
+        Ok(())
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.since().is_some()
+    }
+
+    /// When maintenance mode was turned on, in seconds since the Unix epoch, if it is on.
+    pub fn since(&self) -> Option<u64> {
+        *self.since.lock().unwrap()
+    }
+
+    /// Turn maintenance mode on or off. The file is changed first, so that maintenance mode is
+    /// never on without being recorded; if that fails, nothing changes. Returns false if it was
+    /// already on or off.
+    pub fn set(&self, on: bool) -> io::Result<bool> {
+        let mut since = self.since.lock().unwrap();
+        if since.is_some() == on {
+            return Ok(false);
+        }
+
+        if on {
+            let now = super::history::now();
+            if let Some(dir) = std::path::Path::new(&self.path).parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(&self.path, format!("{now}\n"))?;
+            *since = Some(now);
+        } else {
+            match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => *since = None,
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maintenance() {
+        let dir = std::env::temp_dir().join(format!("halo_maintenance_{}", std::process::id()));
+        let path = dir.join("maintenance");
+        let path = path.to_str().unwrap();
+
+        let maintenance = Maintenance::new(path);
+        maintenance.load().unwrap();
+        assert!(!maintenance.is_on());
+
+        // Turning it on records it, creating the directory if need be:
+        assert!(maintenance.set(true).unwrap());
+        assert!(!maintenance.set(true).unwrap());
+        let since = maintenance.since().unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), format!("{since}\n"));
+
+        // So it is still on when loaded again, as it is when the manager restarts:
+        let reloaded = Maintenance::new(path);
+        reloaded.load().unwrap();
+        assert_eq!(reloaded.since(), Some(since));
+
+        assert!(reloaded.set(false).unwrap());
+        assert!(!reloaded.set(false).unwrap());
+        assert!(!std::path::Path::new(path).exists());
+
+        // A file whose contents can not be read still means that it is on:
+        std::fs::write(path, "garbage").unwrap();
+        reloaded.load().unwrap();
+        assert!(reloaded.is_on());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,89 +1,781 @@
 // SPDX-License-Identifier: MIT
 // Copyright 2025. Triad National Security, LLC.
 
-use std::{io, sync::Arc};
+use std::{io, net::SocketAddr, sync::Arc};
 
-use {clap::Parser, log::info};
+use {
+    clap::Parser,
+    log::{error, info, warn},
+};
 
 use crate::{
     cluster,
     commands::{Handle, HandledResult},
 };
 
+pub mod events;
+pub mod history;
 pub mod http;
+pub mod maintenance;
+pub mod once;
+pub mod placement;
+pub mod profile;
+pub mod quorum;
+pub mod rate_limit;
+pub mod shutdown;
+pub mod state;
 
 #[derive(Parser, Debug, Default, Clone)]
 #[command(version, about, long_about = None)]
 pub struct Cli {
+    /// A config file, or a directory of `.yaml`, `.yml`, and `.conf` files. When given more than
+    /// once, the files are merged in order, so a later one overrides what an earlier one sets.
+    /// Defaults to HALO_CONFIG, or /etc/halo/halo.conf.
+    #[arg(long)]
+    pub config: Vec<String>,
+
+    /// Check the config file for problems, as the manager would when it starts or reloads it, and
+    /// exit without starting.
     #[arg(long)]
-    pub config: Option<String>,
+    pub config_check: bool,
 
     #[arg(long)]
     pub socket: Option<String>,
 
+    /// Set the permissions of the unix socket to this octal mode (e.g., `0660`) once it is
+    /// created. Defaults to `socket_mode` in the config file; otherwise the socket gets whatever
+    /// permissions the umask allows. Does not apply to the --listen address.
+    #[arg(long, value_parser = parse_socket_mode)]
+    pub socket_mode: Option<u32>,
+
+    /// Give the unix socket to this group once it is created, so that together with
+    /// --socket-mode, only its members can send commands. Defaults to `socket_group` in the
+    /// config file. Does not apply to the --listen address.
+    #[arg(long)]
+    pub socket_group: Option<String>,
+
+    /// Leave the unix socket file alone: neither remove a stale one at startup, nor remove the
+    /// manager's own one when it exits. For hosts where a supervisor manages the file instead.
+    #[arg(long)]
+    pub no_cleanup_socket: bool,
+
+    /// Start even if another process answers on the unix socket, replacing its socket with this
+    /// manager's own. A manager which holds the socket's lock is never replaced.
+    #[arg(long)]
+    pub force: bool,
+
+    /// Also accept CLI connections on this TCP address, in addition to the unix socket: an IP
+    /// address and port such as `0.0.0.0:8080` or `[::]:8080`, or a hostname and port, which is
+    /// resolved to its first address. With --mtls, connections on this address must use mutual
+    /// TLS.
+    #[arg(long)]
+    pub listen: Option<String>,
+
     #[arg(long)]
     pub mtls: bool,
 
+    /// Require requests to carry this token in an `Authorization: Bearer` header, on the unix
+    /// socket and the --listen address alike. The health checks at `/` and `/ready` do not need
+    /// it. Defaults to `auth_token` in the config file, which unlike this option is not visible
+    /// to other users of the host.
+    #[arg(long)]
+    pub auth_token: Option<String>,
+
+    /// Also accept this token, but only for requests which read from the manager, i.e. GET
+    /// requests such as `/status` and `/metrics`, so that monitoring systems can be given a token
+    /// which can not change anything. Requires an auth token for full access. Defaults to
+    /// `read_only_token` in the config file.
+    #[arg(long)]
+    pub read_only_token: Option<String>,
+
     #[arg(long)]
     pub verbose: bool,
 
+    /// The format of log output.
+    #[arg(long, value_enum, default_value_t)]
+    pub log_format: crate::logging::LogFormat,
+
+    /// Append log output to this file instead of writing it to stderr.
+    #[arg(long)]
+    pub log_file: Option<String>,
+
     /// Whether to run in Observe mode (Default, only check on resource status, don't actively
     /// start/stop resources), or Manage mode (actively manage resource state)
     #[arg(long)]
     pub manage_resources: bool,
 
+    /// Observe resources, and log the actions that Manage mode would take, such as starting or
+    /// moving resources, without taking them. Requests from the CLI are treated as dry runs too,
+    /// so the manager never acts on resources or hosts.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Check on every resource group once, in Observe or Manage mode, then print a report of what
+    /// was found and done, and exit: with status 0 if every resource is healthy and where it
+    /// belongs, and 3 otherwise. No socket is opened, so the CLI can not reach the manager while
+    /// it runs. For cron jobs and other periodic checks.
+    #[arg(long, conflicts_with = "config_check")]
+    pub once: bool,
+
     /// Whether to treat network errors like "Connection Reset" or "Connection Refused" as
     /// fencable. This is ONLY for use in the test environment; in production environments, such
     /// errors indicate a configuration issue that needs to be resolved.
     #[arg(long, hide = true)]
     pub fence_on_connection_close: bool,
 
-    /// How many milliseconds to sleep between each iteration of the resource management loops.
-    #[arg(long, hide = true, default_value_t = 5000)]
-    pub sleep_time: u64,
+    /// UNSAFE: fail resources over to a host's partner without first fencing the host. If the
+    /// host is not really down, its resources can end up running in two places at once, which can
+    /// corrupt them. Only for clusters without working fencing, such as test setups.
+    #[arg(long)]
+    pub no_fence: bool,
+
+    /// How many milliseconds to wait between passes of the resource management loops, each of
+    /// which checks on the resources. Defaults to `poll_interval` in the config file, or 5000.
+    #[arg(long, alias = "sleep-time")]
+    pub poll_interval: Option<u64>,
+
+    /// Wait up to this many more milliseconds, chosen at random, between passes, so that managers
+    /// which restart together do not poll their agents in lockstep. Defaults to `poll_jitter` in
+    /// the config file, or 0.
+    #[arg(long)]
+    pub poll_jitter: Option<u64>,
+
+    /// How many of the most recent actions on resources to keep for `halo history`.
+    #[arg(long, default_value_t = 500)]
+    pub history_size: usize,
+
+    /// How many times in a row to try starting or stopping a resource before giving up on it,
+    /// until an admin clears the failure with `--clear-failed`. Defaults to `retry_attempts` in
+    /// the config file, or 5.
+    #[arg(long)]
+    pub retry_attempts: Option<u32>,
+
+    /// How many milliseconds to wait before trying a failed start or stop again. The wait doubles
+    /// after each failure in a row. Defaults to `retry_backoff` in the config file, or 1000.
+    #[arg(long)]
+    pub retry_backoff: Option<u64>,
+
+    /// The longest that the wait before trying a failed start or stop again can grow to, in
+    /// milliseconds. Defaults to `retry_backoff_max` in the config file, or 60000.
+    #[arg(long)]
+    pub retry_backoff_max: Option<u64>,
+
+    /// How many times in a row fencing a host may fail before the manager stops trying, so that a
+    /// broken fence agent is not run forever. The host's resources then stay stopped until an admin
+    /// resets it with `halo power --reset-breaker`.
+    #[arg(long, default_value_t = DEFAULT_FENCE_ATTEMPTS, value_parser = clap::value_parser!(u32).range(1..))]
+    pub fence_attempts: u32,
+
+    /// How many starts and stops of resources may run at once; the rest wait for their turn.
+    /// Defaults to `action_concurrency` in the config file, or 8.
+    #[arg(long)]
+    pub action_concurrency: Option<usize>,
+
+    /// The file in which maintenance mode is recorded while it is on, so that it stays on when the
+    /// manager restarts. Defaults to `maintenance_file` in the config file, or
+    /// /var/lib/halo/maintenance.
+    #[arg(long)]
+    pub maintenance_file: Option<String>,
+
+    /// Keep which resource groups are managed, which hosts are on standby, and the target state
+    /// of each resource in this file, so that they are restored when the manager restarts, rather
+    /// than reverting to what the config file says. Without it, they are only kept in memory.
+    #[arg(long)]
+    pub state_file: Option<String>,
+
+    /// Time each pass of the management loops, and how long the agents take to check on each
+    /// resource. A summary of each pass is logged, and the timings are reported at `/metrics` as
+    /// histograms.
+    #[arg(long)]
+    pub profile: bool,
+
+    /// How many of the slowest resources to name in the summary of each pass, with --profile.
+    #[arg(long, default_value_t = 3, requires = "profile")]
+    pub profile_slowest: usize,
+
+    /// How many requests per second each endpoint which changes something accepts, e.g.
+    /// `PATCH /resources/{id}`, before replying 429 Too Many Requests, so that a runaway script can
+    /// not overwhelm the manager and its agents. Each endpoint is limited separately; requests
+    /// which only read from the manager are not limited. 0, the default, means no limit.
+    #[arg(long, default_value_t = 0)]
+    pub rate_limit: u32,
+
+    /// How many requests each endpoint accepts at once, after it has been idle, with --rate-limit.
+    #[arg(long, default_value_t = 5, requires = "rate_limit", value_parser = clap::value_parser!(u32).range(1..))]
+    pub rate_limit_burst: u32,
 }
 
-/// Get a unix socket listener from a given socket path.
+/// The number of milliseconds between passes of the management loops, when neither the command line
+/// nor the config file sets it.
+pub const DEFAULT_POLL_INTERVAL: u64 = 5000;
+
+/// How many poll intervals may go by without any resource group finishing a pass before the
+/// manager is no longer ready, e.g. because its management loops are stuck.
+pub const STALL_PASSES: u32 = 10;
+
+/// The defaults for retrying failed starts and stops, when neither the command line nor the config
+/// file sets them.
+pub const DEFAULT_RETRY_ATTEMPTS: u32 = 5;
+pub const DEFAULT_RETRY_BACKOFF: u64 = 1000;
+pub const DEFAULT_RETRY_BACKOFF_MAX: u64 = 60000;
+
+/// How many times in a row fencing a host may fail before the manager stops trying.
+pub const DEFAULT_FENCE_ATTEMPTS: u32 = 10;
+
+/// How many starts and stops of resources may run at once, when neither the command line nor the
+/// config file sets it.
+pub const DEFAULT_ACTION_CONCURRENCY: usize = 8;
+
+/// Where maintenance mode is recorded, when neither the command line nor the config file sets it.
+pub const DEFAULT_MAINTENANCE_FILE: &str = "/var/lib/halo/maintenance";
+
+impl Cli {
+    /// How long to wait before the next pass of a management loop: the poll interval, plus a
+    /// random share of the jitter.
+    pub fn poll_delay(&self) -> std::time::Duration {
+        let interval = self.poll_interval.unwrap_or(DEFAULT_POLL_INTERVAL);
+        let jitter = match self.poll_jitter {
+            Some(jitter) if jitter > 0 => random() % (jitter + 1),
+            _ => 0,
+        };
+        std::time::Duration::from_millis(interval + jitter)
+    }
+
+    /// How long the management loops may go without finishing a pass before they are considered
+    /// stalled: STALL_PASSES of the longest possible wait between passes.
+    pub fn stall_timeout(&self) -> std::time::Duration {
+        let interval = self.poll_interval.unwrap_or(DEFAULT_POLL_INTERVAL);
+        let jitter = self.poll_jitter.unwrap_or(0);
+        std::time::Duration::from_millis(interval + jitter) * STALL_PASSES
+    }
+
+    /// How many starts and stops of resources may run at once.
+    pub fn action_concurrency(&self) -> usize {
+        self.action_concurrency
+            .unwrap_or(DEFAULT_ACTION_CONCURRENCY)
+    }
+
+    /// The file in which maintenance mode is recorded.
+    pub fn maintenance_file(&self) -> &str {
+        self.maintenance_file
+            .as_deref()
+            .unwrap_or(DEFAULT_MAINTENANCE_FILE)
+    }
+
+    /// How many times in a row a start or stop may fail before it is no longer retried.
+    pub fn retry_attempts(&self) -> u32 {
+        self.retry_attempts.unwrap_or(DEFAULT_RETRY_ATTEMPTS)
+    }
+
+    /// How long to wait before trying a failed start or stop again, after it failed `failures`
+    /// times in a row.
+    pub fn retry_delay(&self, failures: u32) -> std::time::Duration {
+        let backoff = self.retry_backoff.unwrap_or(DEFAULT_RETRY_BACKOFF);
+        let max = self.retry_backoff_max.unwrap_or(DEFAULT_RETRY_BACKOFF_MAX);
+        let factor = 1u64
+            .checked_shl(failures.saturating_sub(1))
+            .unwrap_or(u64::MAX);
+        std::time::Duration::from_millis(backoff.saturating_mul(factor).min(max))
+    }
+}
+
+/// A random number, which is good enough for spreading out polls but nothing more. Each
+/// `RandomState` is seeded differently, which saves depending on a crate for this.
+fn random() -> u64 {
+    use std::hash::BuildHasher;
+    std::collections::hash_map::RandomState::new().hash_one(std::time::Instant::now())
+}
+
+/// Removes the unix socket file when dropped, so that it is not left behind when the manager exits.
 ///
-/// To avoid clobbering an already-in-use unix socket, a connection is attempted to an existing
-/// unix socket first. If this fails, a new socket listener can be returned, since an existing
-/// in-use socket was determined to be absent at the given location.
-async fn prepare_unix_socket(addr: &String) -> io::Result<tokio::net::UnixListener> {
-    // Check for existing socket in use
-    match tokio::net::UnixStream::connect(&addr).await {
-        Ok(_) => {
-            eprintln!("Address already in use: {addr}");
+/// The socket file's inode is recorded when the guard is created. If the file at the path was since
+/// replaced (e.g., by another manager that was started with the same socket path), it is left
+/// alone.
+///
+/// The guard also holds the socket's lock, if any, which is released only after the socket file is
+/// removed. With `remove` unset, the file is left for whoever manages it, and only the lock is
+/// released.
+struct SocketGuard {
+    path: String,
+    dev: u64,
+    ino: u64,
+    remove: bool,
+    lock: Option<nix::fcntl::Flock<std::fs::File>>,
+}
+
+impl SocketGuard {
+    fn new(path: &str) -> io::Result<Self> {
+        use std::os::unix::fs::MetadataExt;
+        let metadata = std::fs::metadata(path)?;
+        Ok(Self {
+            path: path.to_string(),
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+            remove: true,
+            lock: None,
+        })
+    }
+}
+
+impl Drop for SocketGuard {
+    fn drop(&mut self) {
+        use std::os::unix::fs::MetadataExt;
+        if !self.remove {
+            return;
+        }
+        let path = &self.path;
+        match std::fs::metadata(path) {
+            Ok(metadata) if metadata.dev() == self.dev && metadata.ino() == self.ino => {
+                if let Err(e) = std::fs::remove_file(path) {
+                    warn!("Could not remove socket '{path}': {e}");
+                }
+            }
+            Ok(_) => warn!("Socket '{path}' was replaced by another process; not removing it."),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Could not check socket '{path}': {e}"),
+        }
+    }
+}
+
+/// Take the lock file for the unix socket at `addr`, `<addr>.lock`, and write this process's ID to
+/// it. The lock is held until the returned file is dropped.
+///
+/// Only one manager can hold the lock, so if another one already does, this fails with an error
+/// that says which process it is.
+fn lock_socket(addr: &str) -> io::Result<nix::fcntl::Flock<std::fs::File>> {
+    use {
+        nix::fcntl::{Flock, FlockArg},
+        std::io::{Read, Seek, Write},
+    };
+
+    let path = format!("{addr}.lock");
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)?;
+
+    let mut lock = match Flock::lock(file, FlockArg::LockExclusiveNonblock) {
+        Ok(lock) => lock,
+        Err((mut file, nix::errno::Errno::EWOULDBLOCK)) => {
+            let mut pid = String::new();
+            let _ = file.read_to_string(&mut pid);
+            let message = match pid.trim() {
+                "" => "another halo manager is already running".to_string(),
+                pid => format!("another halo manager is already running (pid {pid})"),
+            };
+            return Err(io::Error::new(io::ErrorKind::AddrInUse, message));
+        }
+        Err((_, errno)) => return Err(errno.into()),
+    };
+
+    lock.set_len(0)?;
+    lock.rewind()?;
+    writeln!(lock, "{}", std::process::id())?;
+    Ok(lock)
+}
+
+/// Parse the mode of the unix socket, an octal number of at most 0777 such as "0660".
+pub fn parse_socket_mode(mode: &str) -> Result<u32, String> {
+    let invalid = || format!("invalid socket mode '{mode}': expected an octal mode such as 0660");
+    if mode.is_empty() || !mode.chars().all(|c| ('0'..='7').contains(&c)) {
+        return Err(invalid());
+    }
+    match u32::from_str_radix(mode, 8) {
+        Ok(bits) if bits <= 0o777 => Ok(bits),
+        _ => Err(invalid()),
+    }
+}
+
+/// Look up the ID of the group named `group`, for the owner of the unix socket.
+pub fn socket_group_id(group: &str) -> Result<nix::unistd::Gid, String> {
+    match nix::unistd::Group::from_name(group) {
+        Ok(Some(group)) => Ok(group.gid),
+        Ok(None) => Err(format!("invalid socket group '{group}': no such group")),
+        Err(e) => Err(format!("could not look up socket group '{group}': {e}")),
+    }
+}
+
+/// Give the socket at `addr` the group and mode that were asked for, if any.
+fn set_socket_permissions(addr: &str, mode: Option<u32>, group: Option<&str>) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if let Some(group) = group {
+        let gid = socket_group_id(group).map_err(io::Error::other)?;
+        std::os::unix::fs::chown(addr, None, Some(gid.as_raw()))?;
+    }
+    if let Some(mode) = mode {
+        std::fs::set_permissions(addr, std::fs::Permissions::from_mode(mode))?;
+    }
+    Ok(())
+}
+
+/// Make way for a new socket at `addr`. If another process answers on an existing socket there,
+/// this fails unless `force` is set. Otherwise the existing socket file is stale, and is removed,
+/// unless `cleanup` is unset.
+async fn clear_socket(addr: &str, force: bool, cleanup: bool) -> io::Result<()> {
+    match tokio::net::UnixStream::connect(addr).await {
+        Ok(_) if !force => {
+            error!(
+                "Address already in use: {addr}; another process is listening on it. Stop that \
+                 process, or start with --force to replace its socket."
+            );
             return Err(io::Error::from(io::ErrorKind::AddrInUse));
         }
+        Ok(_) => warn!("Another process is listening on socket '{addr}'; replacing it (--force)."),
         Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {}
-        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
         Err(e) => {
-            eprintln!("Unexpected error while preparing unix socket '{addr}': {e}");
+            error!("Unexpected error while preparing unix socket '{addr}': {e}");
             return Err(e);
         }
     };
+    if !cleanup {
+        return Ok(());
+    }
     match std::fs::remove_file(addr) {
-        Ok(_) => {}
-        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
         Err(e) => {
-            eprintln!("error removing old socket: {e}");
+            error!("error removing old socket: {e}");
+            Err(e)
+        }
+    }
+}
+
+/// Get a unix socket listener from the socket path in `args`, along with a guard that removes the
+/// socket file when dropped. Once the socket is created, it is given the mode and group that were
+/// asked for.
+///
+/// The socket's lock file is taken first, so that a manager which is already running with the same
+/// socket is never cut off from it. To also avoid clobbering a socket that is in use by something
+/// else, a connection is attempted to an existing unix socket, and only a socket which nothing
+/// answers on is removed. Should another process create the socket between that check and the
+/// bind, the check is made once more before giving up.
+async fn prepare_unix_socket(
+    addr: &str,
+    args: &Cli,
+) -> io::Result<(tokio::net::UnixListener, SocketGuard)> {
+    let lock = match lock_socket(addr) {
+        Ok(lock) => lock,
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse => {
+            error!("{e}");
+            return Err(e);
+        }
+        Err(e) => {
+            error!("Could not lock socket '{addr}': {e}");
             return Err(e);
         }
     };
-    // Create new socket
-    match tokio::net::UnixListener::bind(addr) {
-        Ok(l) => Ok(l),
+
+    let cleanup = !args.no_cleanup_socket;
+    let mut retried = false;
+    let listener = loop {
+        clear_socket(addr, args.force, cleanup).await?;
+        match tokio::net::UnixListener::bind(addr) {
+            Ok(l) => break l,
+            Err(e) if e.kind() == io::ErrorKind::AddrInUse && !cleanup => {
+                error!(
+                    "Socket '{addr}' already exists, and is not removed because of \
+                     --no-cleanup-socket."
+                );
+                return Err(e);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AddrInUse && !retried => {
+                warn!("Socket '{addr}' was created by another process while preparing it; checking it again.");
+                retried = true;
+            }
+            Err(e) => {
+                error!("error binding to socket '{addr}': {e}");
+                return Err(e);
+            }
+        }
+    };
+    let guard = match SocketGuard::new(addr) {
+        Ok(mut guard) => {
+            guard.remove = cleanup;
+            guard.lock = Some(lock);
+            guard
+        }
         Err(e) => {
-            eprintln!("error binding to socket '{addr}': {e}");
-            Err(e)
+            error!("error checking socket '{addr}': {e}");
+            return Err(e);
         }
+    };
+    // Should this fail, dropping the guard removes the socket, rather than leaving it open to
+    // more users than intended:
+    if let Err(e) = set_socket_permissions(addr, args.socket_mode, args.socket_group.as_deref()) {
+        error!("Could not set permissions of socket '{addr}': {e}");
+        return Err(e);
+    }
+    Ok((listener, guard))
+}
+
+/// How long a client connecting to the --listen address with --mtls has to finish its TLS
+/// handshake before the connection is dropped.
+pub const TLS_HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// A TCP listener which performs a handshake, such as the TLS one, on each connection before
+/// handing it to the command server. Each handshake runs in a task of its own, so that a client
+/// which is slow to finish one, or never does, does not hold up the connections of the others; one
+/// which takes longer than the timeout is dropped.
+struct HandshakeListener<Io> {
+    listener: tokio::net::TcpListener,
+    handshake: Arc<dyn Fn(tokio::net::TcpStream) -> HandshakeFuture<Io> + Send + Sync>,
+    timeout: std::time::Duration,
+    done_sender: tokio::sync::mpsc::UnboundedSender<(Io, SocketAddr)>,
+    done: tokio::sync::mpsc::UnboundedReceiver<(Io, SocketAddr)>,
+}
+
+type HandshakeFuture<Io> =
+    std::pin::Pin<Box<dyn std::future::Future<Output = io::Result<Io>> + Send>>;
+
+impl<Io: Send + 'static> HandshakeListener<Io> {
+    fn new<F, Fut>(
+        listener: tokio::net::TcpListener,
+        timeout: std::time::Duration,
+        handshake: F,
+    ) -> Self
+    where
+        F: Fn(tokio::net::TcpStream) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = io::Result<Io>> + Send + 'static,
+    {
+        let (done_sender, done) = tokio::sync::mpsc::unbounded_channel();
+        Self {
+            listener,
+            handshake: Arc::new(move |stream| Box::pin(handshake(stream))),
+            timeout,
+            done_sender,
+            done,
+        }
+    }
+}
+
+/// A listener which performs the TLS handshake of --mtls, which requires clients to present a
+/// certificate signed by the CA.
+fn tls_listener(
+    listener: tokio::net::TcpListener,
+    acceptor: tokio_rustls::TlsAcceptor,
+) -> HandshakeListener<tokio_rustls::server::TlsStream<tokio::net::TcpStream>> {
+    HandshakeListener::new(listener, TLS_HANDSHAKE_TIMEOUT, move |stream| {
+        acceptor.accept(stream)
+    })
+}
+
+impl<Io> axum::serve::Listener for HandshakeListener<Io>
+where
+    Io: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+    type Io = Io;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        loop {
+            tokio::select! {
+                (stream, addr) = axum::serve::Listener::accept(&mut self.listener) => {
+                    let handshake = (self.handshake)(stream);
+                    let timeout = self.timeout;
+                    let done = self.done_sender.clone();
+                    tokio::spawn(async move {
+                        match tokio::time::timeout(timeout, handshake).await {
+                            Ok(Ok(stream)) => {
+                                let _ = done.send((stream, addr));
+                            }
+                            Ok(Err(e)) => warn!("TLS handshake with {addr} failed: {e}"),
+                            Err(_) => warn!("TLS handshake with {addr} timed out after {timeout:?}."),
+                        }
+                    });
+                }
+                // The listener holds a sender itself, so the channel is never closed:
+                Some(done) = self.done.recv() => return done,
+            }
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        self.listener.local_addr()
+    }
+}
+
+/// Split a --listen address into its host and port, rejecting one which could be read more than
+/// one way: an IPv6 address must be in brackets, since otherwise its last group could be taken for
+/// the port.
+pub fn split_listen_address(addr: &str) -> Result<(&str, u16), String> {
+    let Some((host, port)) = addr.rsplit_once(':') else {
+        return Err(format!("'{addr}' has no port; give it as host:port"));
+    };
+    let port = port
+        .parse::<u16>()
+        .map_err(|_| format!("'{port}' in '{addr}' is not a valid port"))?;
+    let host = match host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+        Some(bracketed) if bracketed.parse::<std::net::Ipv6Addr>().is_ok() => bracketed,
+        Some(_) => return Err(format!("'{host}' in '{addr}' is not an IPv6 address")),
+        None if host.contains(':') => {
+            return Err(format!(
+                "'{addr}' is ambiguous; put an IPv6 address in brackets, e.g. '[::1]:8080'"
+            ))
+        }
+        None if host.is_empty() => return Err(format!("'{addr}' has no host")),
+        None => host,
+    };
+    Ok((host, port))
+}
+
+/// Resolve a --listen address to the one to bind. A hostname is looked up, and the first address
+/// that it resolves to is used.
+pub async fn resolve_listen_address(addr: &str) -> Result<SocketAddr, String> {
+    let (host, port) = split_listen_address(addr)?;
+    tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| format!("could not resolve '{host}': {e}"))?
+        .next()
+        .ok_or_else(|| format!("'{host}' did not resolve to any address"))
+}
+
+/// Serve CLI commands on the TCP address given by --listen, if any.
+async fn tcp_server_main(
+    listener: Option<tokio::net::TcpListener>,
+    cluster: Arc<cluster::Cluster>,
+) {
+    let Some(listener) = listener else {
+        return;
+    };
+
+    if cluster.args.mtls {
+        let listener = tls_listener(listener, crate::tls::get_acceptor());
+        http::server_main(listener, cluster).await;
+    } else {
+        http::server_main(listener, cluster).await;
     }
 }
 
 /// Main entrypoint for the management service, which monitors and controls the state of
 /// the cluster.
+///
+/// This returns once a shutdown was requested and the current iteration of resource management has
+/// finished.
 async fn manager_main(cluster: Arc<cluster::Cluster>) {
-    cluster.main_loop().await;
+    tokio::select! {
+        _ = cluster.main_loop() => {}
+        _ = async {
+            shutdown::shutdown_requested().await;
+            shutdown::wait_for_idle().await;
+        } => {}
+    }
+}
+
+/// Reload the config file each time SIGHUP is received, until a shutdown is requested.
+async fn handle_reloads(mut sighup: tokio::signal::unix::Signal, cluster: Arc<cluster::Cluster>) {
+    tokio::select! {
+        _ = async {
+            while sighup.recv().await.is_some() {
+                info!("Received SIGHUP, reloading config.");
+                reload_config(&cluster);
+            }
+        } => {}
+        _ = shutdown::shutdown_requested() => {}
+    }
+}
+
+/// Reload the config file, logging what changed. If the new config has problems, they are logged
+/// and the config that was in effect is kept.
+fn reload_config(cluster: &cluster::Cluster) {
+    match cluster.reload() {
+        Ok(reloaded) => {
+            for change in reloaded.applied.iter() {
+                info!("Reloaded config: changed {change}.");
+            }
+            for change in reloaded.needs_restart.iter() {
+                warn!(
+                    "Reloaded config, but {change}, which only takes effect once the manager is \
+                     restarted."
+                );
+            }
+            if reloaded.applied.is_empty() && reloaded.needs_restart.is_empty() {
+                info!("Reloaded config: nothing changed.");
+            }
+            info!("Config generation {} is active.", reloaded.generation);
+        }
+        Err(problems) => {
+            for problem in problems.iter() {
+                error!("Invalid config: {problem}");
+            }
+            error!(
+                "Could not reload config; config generation {} is still active.",
+                cluster.config_generation()
+            );
+        }
+    }
+}
+
+/// Check the config file for every problem that the manager would find with it, for
+/// --config-check.
+pub fn check_config(args: Cli) -> HandledResult<()> {
+    let config = crate::config::Config::load(&args.config)?;
+    let problems = config.validate();
+    if !problems.is_empty() {
+        for problem in problems.iter() {
+            eprintln!("Invalid config: {problem}");
+        }
+        return Err(crate::commands::HandledError::default());
+    }
+    cluster::Cluster::new(args)?;
+
+    match &config.sources.files[..] {
+        [file] => println!("Config file \"{file}\" is valid."),
+        files => println!("Config files \"{}\" are valid.", files.join("\", \"")),
+    }
+    Ok(())
+}
+
+/// Request a graceful shutdown when SIGTERM or SIGINT is received.
+async fn handle_signals(
+    mut sigterm: tokio::signal::unix::Signal,
+    mut sigint: tokio::signal::unix::Signal,
+) {
+    let name = tokio::select! {
+        _ = sigterm.recv() => "SIGTERM",
+        _ = sigint.recv() => "SIGINT",
+    };
+    warn!("Received {name}, shutting down.");
+    shutdown::request_shutdown();
+}
+
+/// Get the manager ready to run: build its runtime, and restore maintenance mode and the state
+/// file, as both `main()` and `--once` do before they manage anything.
+fn prepare(cluster: &cluster::Cluster) -> HandledResult<tokio::runtime::Runtime> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .handle_err(|e| error!("Could not launch manager runtime: {e}"))?;
+
+    if cluster.args.no_fence {
+        warn!(
+            "Running with --no-fence: hosts will not be fenced before their resources are failed \
+             over, so resources may end up running in two places at once."
+        );
+    }
+
+    let maintenance = cluster.maintenance();
+    maintenance.load().handle_err(|e| {
+        error!(
+            "Could not read maintenance file '{}': {e}",
+            maintenance.path()
+        )
+    })?;
+    if let Some(since) = maintenance.since() {
+        warn!(
+            "Maintenance mode has been on since {}, as recorded in '{}': no resource will be \
+             started, stopped, moved, or failed over until it is switched off with `halo \
+             maintenance off`.",
+            crate::commands::history::format_time(since),
+            maintenance.path()
+        );
+    }
+    state::restore(cluster);
+
+    Ok(rt)
 }
 
 /// Rust client management daemon -
@@ -94,21 +786,31 @@ async fn manager_main(cluster: Arc<cluster::Cluster>) {
 ///   The monitoring service takes actions based on cluster status, such as migrating resources,
 ///   fencing nodes, etc.
 ///
-/// - A server that listens on a unix socket (/var/run/halo.socket) for
-///   commands from the command line interface.
+/// - A server that listens on a unix socket (/var/run/halo.socket), and optionally a TCP address,
+///   for commands from the command line interface.
 pub fn main(cluster: cluster::Cluster) -> HandledResult<()> {
-    let rt = tokio::runtime::Builder::new_current_thread()
-        .enable_all()
-        .build()
-        .handle_err(|e| eprintln!("Could not launch manager runtime: {e}"))?;
+    let rt = prepare(&cluster)?;
 
     rt.block_on(tokio::task::LocalSet::new().run_until(async {
+        use tokio::signal::unix::{signal, SignalKind};
+        let (sigterm, sigint, sighup) = match (
+            signal(SignalKind::terminate()),
+            signal(SignalKind::interrupt()),
+            signal(SignalKind::hangup()),
+        ) {
+            (Ok(sigterm), Ok(sigint), Ok(sighup)) => (sigterm, sigint, sighup),
+            (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+                error!("Could not install signal handlers: {e}");
+                std::process::exit(1);
+            }
+        };
+
         let addr = match &cluster.args.socket {
-            Some(s) => s,
-            None => &crate::default_socket(),
+            Some(s) => s.clone(),
+            None => crate::default_socket(),
         };
 
-        let listener = match prepare_unix_socket(addr).await {
+        let (listener, socket_guard) = match prepare_unix_socket(&addr, &cluster.args).await {
             Ok(l) => l,
             Err(_) => {
                 std::process::exit(1);
@@ -117,13 +819,221 @@ pub fn main(cluster: cluster::Cluster) -> HandledResult<()> {
 
         info!("listening on socket '{addr}'");
 
+        let tcp_listener = match &cluster.args.listen {
+            Some(addr) => {
+                let bound = match resolve_listen_address(addr).await {
+                    Ok(resolved) => tokio::net::TcpListener::bind(resolved)
+                        .await
+                        .map(|l| (l, resolved))
+                        .map_err(|e| format!("error binding to address '{addr}': {e}")),
+                    Err(e) => Err(format!("invalid --listen address: {e}")),
+                };
+                match bound {
+                    Ok((l, resolved)) => {
+                        if addr.parse::<SocketAddr>().is_ok() {
+                            info!("listening on address '{addr}'");
+                        } else {
+                            info!("listening on address '{addr}', resolved to {resolved}");
+                        }
+                        Some(l)
+                    }
+                    Err(e) => {
+                        error!("{e}");
+                        std::mem::drop(socket_guard);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            None => None,
+        };
+
         let cluster = Arc::new(cluster);
 
         futures::join!(
+            handle_signals(sigterm, sigint),
+            handle_reloads(sighup, Arc::clone(&cluster)),
             http::server_main(listener, Arc::clone(&cluster)),
+            tcp_server_main(tcp_listener, Arc::clone(&cluster)),
+            state::keep_saved(Arc::clone(&cluster)),
             manager_main(cluster)
         );
+
+        std::mem::drop(socket_guard);
     }));
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        lock_socket, parse_socket_mode, split_listen_address, Cli, HandshakeListener, SocketGuard,
+    };
+
+    #[test]
+    fn test_poll_delay() {
+        let args = Cli::default();
+        assert_eq!(args.poll_delay().as_millis(), 5000);
+
+        let args = Cli {
+            poll_interval: Some(100),
+            poll_jitter: Some(50),
+            ..Default::default()
+        };
+        for _ in 0..100 {
+            let delay = args.poll_delay().as_millis();
+            assert!((100..=150).contains(&delay), "{delay}");
+        }
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let args = Cli::default();
+        assert_eq!(args.retry_attempts(), 5);
+        assert_eq!(args.retry_delay(1).as_millis(), 1000);
+        assert_eq!(args.retry_delay(2).as_millis(), 2000);
+        assert_eq!(args.retry_delay(4).as_millis(), 8000);
+        assert_eq!(args.retry_delay(100).as_millis(), 60000);
+
+        let args = Cli {
+            retry_backoff: Some(100),
+            retry_backoff_max: Some(250),
+            ..Default::default()
+        };
+        assert_eq!(args.retry_delay(2).as_millis(), 200);
+        assert_eq!(args.retry_delay(3).as_millis(), 250);
+    }
+
+    #[test]
+    fn test_socket_guard() {
+        let path = std::env::temp_dir().join(format!("halo_socket_guard_{}", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        // The guard removes the file that it was created for:
+        std::fs::write(path, "").unwrap();
+        let guard = SocketGuard::new(path).unwrap();
+        drop(guard);
+        assert!(!std::path::Path::new(path).exists());
+
+        // A missing file is not an error:
+        std::fs::write(path, "").unwrap();
+        let guard = SocketGuard::new(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        drop(guard);
+
+        // A file that was replaced by another one is left alone:
+        std::fs::write(path, "").unwrap();
+        let guard = SocketGuard::new(path).unwrap();
+        std::fs::write(format!("{path}.new"), "").unwrap();
+        std::fs::rename(format!("{path}.new"), path).unwrap();
+        drop(guard);
+        assert!(std::path::Path::new(path).exists());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_socket_lock() {
+        let path = std::env::temp_dir().join(format!("halo_socket_lock_{}", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        // While the lock is held, it can not be taken again, and the error says who holds it:
+        let lock = lock_socket(path).unwrap();
+        let err = lock_socket(path).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "another halo manager is already running (pid {})",
+                std::process::id()
+            )
+        );
+
+        // Once it is released, it can be:
+        drop(lock);
+        let lock = lock_socket(path).unwrap();
+        drop(lock);
+        std::fs::remove_file(format!("{path}.lock")).unwrap();
+    }
+
+    #[test]
+    fn test_parse_socket_mode() {
+        assert_eq!(parse_socket_mode("0660"), Ok(0o660));
+        assert_eq!(parse_socket_mode("600"), Ok(0o600));
+        assert_eq!(parse_socket_mode("0777"), Ok(0o777));
+        for mode in ["", "0o660", "0680", "1777", "rw-rw----", "-1"] {
+            assert_eq!(
+                parse_socket_mode(mode),
+                Err(format!(
+                    "invalid socket mode '{mode}': expected an octal mode such as 0660"
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn test_handshake_listener() {
+        use {
+            std::time::Duration,
+            tokio::{
+                io::{AsyncReadExt, AsyncWriteExt},
+                net::{TcpListener, TcpStream},
+            },
+        };
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = tcp.local_addr().unwrap();
+            // Like the TLS one, this handshake waits for the client to speak first:
+            let mut listener =
+                HandshakeListener::new(tcp, Duration::from_millis(500), |mut stream| async move {
+                    let mut hello = [0];
+                    stream.read_exact(&mut hello).await?;
+                    Ok::<TcpStream, std::io::Error>(stream)
+                });
+            // A client which never finishes its handshake does not hold up the one after it:
+            let _silent = TcpStream::connect(addr).await.unwrap();
+            let mut client = TcpStream::connect(addr).await.unwrap();
+            client.write_all(b"x").await.unwrap();
+            let second = Duration::from_secs(1);
+            let (_, from) =
+                tokio::time::timeout(second, axum::serve::Listener::accept(&mut listener))
+                    .await
+                    .unwrap();
+            assert_eq!(from, client.local_addr().unwrap());
+
+            // It is dropped once its handshake times out, rather than ever being accepted:
+            assert!(
+                tokio::time::timeout(second, axum::serve::Listener::accept(&mut listener))
+                    .await
+                    .is_err()
+            );
+        });
+    }
+
+    #[test]
+    fn test_split_listen_address() {
+        assert_eq!(split_listen_address("0.0.0.0:8080"), Ok(("0.0.0.0", 8080)));
+        assert_eq!(split_listen_address("[::]:8080"), Ok(("::", 8080)));
+        assert_eq!(split_listen_address("[::1]:80"), Ok(("::1", 80)));
+        assert_eq!(
+            split_listen_address("mgmt.example.com:8080"),
+            Ok(("mgmt.example.com", 8080))
+        );
+        for addr in [
+            "8080",
+            "localhost",
+            "localhost:http",
+            ":8080",
+            "::1:8080",
+            "[mgmt]:80",
+        ] {
+            assert!(split_listen_address(addr).is_err(), "{addr}");
+        }
+        assert!(split_listen_address("::1:8080")
+            .unwrap_err()
+            .contains("put an IPv6 address in brackets"));
+    }
+}
@@ -0,0 +1,156 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+//! A single check of the cluster, for `--once`.
+//!
+//! The usual management loops are run, in Observe or Manage mode, until each resource group has
+//! been checked: either a pass of its loop finished, or its resources were all found stopped and
+//! nothing is going to start them. Then the manager shuts down as it would on SIGTERM, letting
+//! any pass in progress finish, and reports what it found. A group which could not be checked
+//! within the stall timeout, e.g. because its hosts are unreachable, is reported as such.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use {
+    log::{error, warn},
+    tokio::sync::broadcast::{error::RecvError, Receiver},
+};
+
+use crate::{
+    cluster::Cluster,
+    commands::HandledResult,
+    manager::{events::Event, shutdown},
+    resource::{ResourceGroup, ResourceStatus},
+};
+
+/// The exit status of `halo_manager --once` when it found a problem with the cluster.
+pub const UNHEALTHY: i32 = 3;
+
+/// Check on every resource group in `cluster` once, print a report of it, and return whether the
+/// cluster is healthy.
+pub fn main(cluster: Cluster) -> HandledResult<bool> {
+    let rt = super::prepare(&cluster)?;
+
+    let mut found = HashMap::new();
+    let interrupted = rt.block_on(tokio::task::LocalSet::new().run_until(async {
+        use tokio::signal::unix::{signal, SignalKind};
+        let (sigterm, sigint) = match (
+            signal(SignalKind::terminate()),
+            signal(SignalKind::interrupt()),
+        ) {
+            (Ok(sigterm), Ok(sigint)) => (sigterm, sigint),
+            (Err(e), _) | (_, Err(e)) => {
+                error!("Could not install signal handlers: {e}");
+                std::process::exit(1);
+            }
+        };
+
+        let mut events = cluster.subscribe();
+        let mut interrupted = true;
+        tokio::select! {
+            _ = cluster.main_loop() => {}
+            _ = record_found(&mut events, &mut found) => {}
+            _ = async {
+                tokio::select! {
+                    _ = wait_until_checked(&cluster) => {
+                        interrupted = false;
+                        shutdown::request_shutdown();
+                    }
+                    _ = super::handle_signals(sigterm, sigint) => {}
+                }
+                shutdown::wait_for_idle().await;
+            } => {}
+        }
+        interrupted
+    }));
+
+    let problems = report(&cluster, &found);
+    if interrupted {
+        warn!("Interrupted before every resource group was checked.");
+    }
+    Ok(!interrupted && problems == 0)
+}
+
+/// Wait until every resource group has been checked, or the stall timeout has passed.
+async fn wait_until_checked(cluster: &Cluster) {
+    let deadline = Instant::now() + cluster.args.stall_timeout();
+    while Instant::now() < deadline && !cluster.resource_groups().all(|rg| is_checked(cluster, rg))
+    {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+/// Whether `rg` has been checked. A group which is stopped is watched for being started elsewhere
+/// rather than passed over by a loop of its own, so it counts as checked once the status of each
+/// of its resources is known, unless it is about to be started.
+fn is_checked(cluster: &Cluster, rg: &ResourceGroup) -> bool {
+    if rg.last_pass().is_some() {
+        return true;
+    }
+    let known = rg
+        .resources()
+        .all(|res| !matches!(res.get_status(), ResourceStatus::Unknown(_)));
+    known && !(rg.get_managed() && cluster.may_act() && cluster.has_quorum())
+}
+
+/// Record the first status that each resource was found in, from the stream of events.
+async fn record_found(events: &mut Receiver<Event>, found: &mut HashMap<String, ResourceStatus>) {
+    loop {
+        match events.recv().await {
+            Ok(Event::ResourceStatus { id, status }) => {
+                found.entry(id).or_insert(status);
+            }
+            Ok(_) | Err(RecvError::Lagged(_)) => {}
+            Err(RecvError::Closed) => std::future::pending().await,
+        }
+    }
+}
+
+/// Print the status of each resource, where it is running, and how its status changed from what
+/// it was found in, followed by the problems with the cluster: resources which failed, groups
+/// which were not checked, and anything that Manage mode would still do to put the groups where
+/// they belong. Returns how many problems there were.
+fn report(cluster: &Cluster, found: &HashMap<String, ResourceStatus>) -> usize {
+    let mut problems = Vec::new();
+    for rg in cluster.resource_groups() {
+        if !is_checked(cluster, rg) {
+            problems.push(format!("resource group {} could not be checked", rg.id()));
+        }
+        for res in rg.resources() {
+            let status = res.get_status();
+            let mut line = format!("{}: ", res.id);
+            match found.get(&res.id) {
+                Some(was) if *was != status => line.push_str(&format!("{was} -> {status}")),
+                _ => line.push_str(&status.to_string()),
+            }
+            if let Some(host) = res.location().and_then(|loc| res.node(loc)) {
+                line.push_str(&format!(" on {}", host.id()));
+            }
+            if let Some(comment) = status.comment() {
+                line.push_str(&format!(" ({comment})"));
+            }
+            println!("{line}");
+
+            if status.is_failed() {
+                problems.push(format!("resource {} is {status}", res.id));
+            }
+        }
+    }
+    for action in cluster.intended_actions() {
+        problems.push(format!("would {action}"));
+    }
+
+    println!();
+    if problems.is_empty() {
+        println!("All resources are healthy.");
+    } else {
+        println!("Problems:");
+        for problem in problems.iter() {
+            println!("  {problem}");
+        }
+    }
+    problems.len()
+}
@@ -0,0 +1,775 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+//! Where resource groups should run, decided from a snapshot of the cluster.
+//!
+//! The decisions that Manage mode makes about whole resource groups, where to start a group which
+//! is not running, and when to move one to the other host of its pair, depend only on a few facts
+//! about the hosts and groups: which hosts are on standby, which group is running where, the
+//! placement weights, and the colocation and anti-affinity constraints. A Snapshot holds those
+//! facts, and decides from them alone, so that the same decisions can be made about a state which
+//! the cluster is not in, for `halo simulate`.
+//!
+//! The live cluster takes its Snapshot from Cluster::snapshot(). The management loops act on its
+//! decisions; a simulation instead applies them to the Snapshot, one at a time, until there is
+//! nothing left to do. Fencing and quorum are not modeled: a host which is down is assumed to be
+//! fenced, and every action is assumed to succeed.
+
+use std::{collections::BTreeMap, fmt};
+
+use crate::resource::Placement;
+
+/// The number of actions that a simulation takes, per resource group, before it gives up on
+/// reaching a state in which there is nothing left to do.
+const MAX_ACTIONS_PER_GROUP: usize = 4;
+
+/// What a Snapshot knows about a host.
+#[derive(Debug, Clone, Default)]
+pub struct HostState {
+    pub id: String,
+    pub standby: bool,
+
+    /// Whether the host is down, so that its resource groups must be failed over. The live
+    /// cluster treats a host whose agent is not connected as failed, so this is only set by
+    /// simulations.
+    pub down: bool,
+
+    pub attributes: BTreeMap<String, f64>,
+}
+
+/// What a Snapshot knows about a resource group.
+#[derive(Debug, Clone, Default)]
+pub struct GroupState {
+    pub id: String,
+
+    /// The IDs of the resources in the group, which the constraints refer to.
+    pub members: Vec<String>,
+
+    pub home: String,
+    pub away: Option<String>,
+    pub placement: Placement,
+    pub managed: bool,
+    pub migrating: bool,
+
+    /// The host that the group is running on, if it is running.
+    pub running_on: Option<String>,
+
+    /// Whether every resource in the group is known to be stopped. A group which is not running,
+    /// and not stopped either, is in a state that nothing can be concluded from yet.
+    pub stopped: bool,
+}
+
+/// Why Snapshot::place() chose where to start a resource group, when that is not where it would
+/// usually be started.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlacementReason {
+    /// The group must run on the same host as this one, which is running there, or is to be
+    /// started there.
+    Colocated(String),
+
+    /// The group must not run on the same host as this one, which is running on the other host.
+    AntiAffine(String),
+
+    /// The host has the highest placement score of those that the group may start on.
+    Score(f64),
+}
+
+/// PlacementReasons are shown in the history, e.g. "place on oss01 (score 10)".
+impl fmt::Display for PlacementReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlacementReason::Colocated(id) => write!(f, "with {id}"),
+            PlacementReason::AntiAffine(id) => write!(f, "apart from {id}"),
+            PlacementReason::Score(score) => write!(f, "score {score}"),
+        }
+    }
+}
+
+/// Why Snapshot::plan() moves a resource group to the other host of its pair.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MoveReason {
+    /// The host that the group is running on is down.
+    Down,
+
+    /// The host that the group is running on is on standby.
+    Standby,
+
+    /// The group prefers to run on its home node, which it is moved back to.
+    Home,
+
+    /// The group must not run on the same host as this one.
+    Apart(String),
+
+    /// The group must run on the same host as this one, which is on the other host.
+    Together(String),
+
+    /// The group must run with this one, which is being moved, so it is moved along with it.
+    With(String),
+}
+
+/// An action that Manage mode would take on a resource group.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Start a group which is not running on `host`.
+    Start {
+        group: String,
+        host: String,
+        reason: Option<PlacementReason>,
+    },
+
+    /// Move a running group from one host of its pair to the other.
+    Move {
+        group: String,
+        from: String,
+        to: String,
+        reason: MoveReason,
+    },
+
+    /// Give up on a group whose host is down, since it may not run anywhere else.
+    Stop { group: String, host: String },
+}
+
+impl Action {
+    /// The ID of the resource group that this action is on.
+    pub fn group(&self) -> &str {
+        match self {
+            Action::Start { group, .. }
+            | Action::Move { group, .. }
+            | Action::Stop { group, .. } => group,
+        }
+    }
+}
+
+/// Actions are shown as what Manage mode would do, e.g. "start resource group ost0 on oss01".
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Start {
+                group,
+                host,
+                reason,
+            } => {
+                write!(f, "start resource group {group} on {host}")?;
+                match reason {
+                    Some(reason) => write!(f, " ({reason})"),
+                    None => Ok(()),
+                }
+            }
+            Action::Move {
+                group,
+                from,
+                to,
+                reason,
+            } => {
+                write!(f, "move resource group {group} from {from} ")?;
+                match reason {
+                    MoveReason::Down => write!(f, "to {to}, since {from} is down"),
+                    MoveReason::Standby => write!(f, "to {to}, since {from} is on standby"),
+                    MoveReason::Home => write!(f, "back to {to}"),
+                    MoveReason::Apart(other) => write!(
+                        f,
+                        "to {to}, since it must not run on the same host as {other}"
+                    ),
+                    MoveReason::Together(other) => {
+                        write!(f, "to {to}, since it must run on the same host as {other}")
+                    }
+                    MoveReason::With(other) => write!(f, "to {to} along with {other}"),
+                }
+            }
+            Action::Stop { group, host } => write!(
+                f,
+                "leave resource group {group} stopped, since {host} is down and it may not run \
+                 anywhere else"
+            ),
+        }
+    }
+}
+
+/// The state of a cluster, as far as the placement of its resource groups is concerned.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub hosts: Vec<HostState>,
+    pub groups: Vec<GroupState>,
+    pub placement_weights: BTreeMap<String, f64>,
+    pub colocation: Vec<Vec<String>>,
+    pub anti_affinity: Vec<Vec<String>>,
+}
+
+impl Snapshot {
+    pub fn host(&self, id: &str) -> Option<&HostState> {
+        self.hosts.iter().find(|host| host.id == id)
+    }
+
+    pub fn group(&self, id: &str) -> Option<&GroupState> {
+        self.groups.iter().find(|group| group.id == id)
+    }
+
+    /// The group that the resource `id` is a member of.
+    pub fn group_of(&self, id: &str) -> Option<&GroupState> {
+        self.groups
+            .iter()
+            .find(|group| group.members.iter().any(|member| member == id))
+    }
+
+    /// Whether `group` may be started on `host`: it must be one of the group's nodes that its
+    /// placement allows, and it must be neither on standby nor down.
+    pub fn may_start_on(&self, group: &GroupState, host: &str) -> bool {
+        let allowed = group.home == host
+            || (group.placement.allow_away && group.away.as_deref() == Some(host));
+        allowed
+            && self
+                .host(host)
+                .is_some_and(|host| !host.standby && !host.down)
+    }
+
+    /// The hosts that `group` may be started on, home node first.
+    fn startable<'a>(&self, group: &'a GroupState) -> Vec<&'a str> {
+        std::iter::once(group.home.as_str())
+            .chain(group.away.as_deref())
+            .filter(|host| self.may_start_on(group, host))
+            .collect()
+    }
+
+    /// The node of `group`'s pair which is not `host`.
+    fn other_node<'a>(group: &'a GroupState, host: &str) -> Option<&'a str> {
+        if group.home == host {
+            group.away.as_deref()
+        } else {
+            Some(group.home.as_str())
+        }
+    }
+
+    /// Whether `group` is running on `host`.
+    fn runs_on(group: &GroupState, host: &str) -> bool {
+        group.running_on.as_deref() == Some(host)
+    }
+
+    /// The score of `host` for placing resource groups on it: the sum of each of its attributes
+    /// times the weight of that attribute. An attribute which the host does not have counts as 0.
+    pub fn score(&self, host: &HostState) -> f64 {
+        self.placement_weights
+            .iter()
+            .map(|(name, weight)| {
+                let value = if name == crate::config::RUNNING_GROUPS {
+                    self.groups
+                        .iter()
+                        .filter(|group| Self::runs_on(group, &host.id))
+                        .count() as f64
+                } else {
+                    host.attributes.get(name).copied().unwrap_or(0.0)
+                };
+                weight * value
+            })
+            .sum()
+    }
+
+    /// Where to start `group`, which is not running anywhere, and why, when that is decided by
+    /// more than which host is its home node:
+    ///
+    /// - A group which must run with another that is running is started where the other one is.
+    /// - Otherwise, a group which must run with others is started on a host that they may all run
+    ///   on, preferring the home node of the one whose ID comes first, so that they all end up on
+    ///   the same host.
+    /// - A host which is running a group that this one must not run with is avoided, unless there
+    ///   is no other host to run it on.
+    /// - With placement weights, the group is started on the host with the highest score, of those
+    ///   which are left. A tie goes to the host whose ID comes first.
+    ///
+    /// Returns None if none of these change where the group is started, or if it may not start
+    /// anywhere, so that it is started on its home node as usual.
+    pub fn place(&self, group: &GroupState) -> Option<(String, PlacementReason)> {
+        let mut candidates = self.startable(group);
+        let usual = *candidates.first()?;
+        let mut reason = None;
+
+        let colocated = self.colocated_with(group);
+        for other in colocated.iter() {
+            let Some(there) = other.running_on.as_deref() else {
+                continue;
+            };
+            if candidates.contains(&there) {
+                return Some((
+                    there.to_string(),
+                    PlacementReason::Colocated(other.id.clone()),
+                ));
+            }
+        }
+        if !colocated.is_empty() {
+            let together: Vec<&str> = candidates
+                .iter()
+                .copied()
+                .filter(|host| colocated.iter().all(|other| self.may_start_on(other, host)))
+                .collect();
+            if !together.is_empty() {
+                candidates = together;
+            }
+            let leader = colocated
+                .iter()
+                .copied()
+                .chain([group])
+                .min_by_key(|group| &group.id)
+                .unwrap();
+            if let Some(i) = candidates.iter().position(|host| *host == leader.home) {
+                let preferred = candidates.remove(i);
+                candidates.insert(0, preferred);
+            }
+            if candidates[0] != usual {
+                reason = Some(PlacementReason::Colocated(leader.id.clone()));
+            }
+        }
+
+        let anti_affine = self.anti_affine_with(group);
+        let running_apart = |host: &str| {
+            anti_affine
+                .iter()
+                .find(|other| Self::runs_on(other, host))
+                .copied()
+        };
+        let apart: Vec<&str> = candidates
+            .iter()
+            .copied()
+            .filter(|host| running_apart(host).is_none())
+            .collect();
+        if !apart.is_empty() && apart.len() < candidates.len() {
+            let avoided = candidates
+                .iter()
+                .find_map(|host| running_apart(host))
+                .unwrap();
+            if apart[0] != candidates[0] {
+                reason = Some(PlacementReason::AntiAffine(avoided.id.clone()));
+            }
+            candidates = apart;
+        }
+
+        if !self.placement_weights.is_empty() {
+            return candidates
+                .into_iter()
+                .filter_map(|id| self.host(id))
+                .map(|host| (host, self.score(host)))
+                .max_by(|(a_host, a), (b_host, b)| {
+                    a.total_cmp(b).then_with(|| b_host.id.cmp(&a_host.id))
+                })
+                .map(|(host, score)| (host.id.clone(), PlacementReason::Score(score)));
+        }
+        reason.map(|reason| (candidates[0].to_string(), reason))
+    }
+
+    /// The resource groups which `group` must run on the same host as, by the colocation
+    /// constraints, directly or through other groups.
+    pub fn colocated_with(&self, group: &GroupState) -> Vec<&GroupState> {
+        let mut found: Vec<&str> = vec![&group.id];
+        let mut i = 0;
+        while i < found.len() {
+            let Some(current) = self.group(found[i]) else {
+                i += 1;
+                continue;
+            };
+            for set in self.colocation.iter() {
+                if !set.iter().any(|id| current.members.contains(id)) {
+                    continue;
+                }
+                for other in set.iter().filter_map(|id| self.group_of(id)) {
+                    if !found.contains(&other.id.as_str()) {
+                        found.push(&other.id);
+                    }
+                }
+            }
+            i += 1;
+        }
+        found
+            .into_iter()
+            .skip(1)
+            .filter_map(|id| self.group(id))
+            .collect()
+    }
+
+    /// The resource groups which `group` must not run on the same host as, by the anti-affinity
+    /// constraints.
+    pub fn anti_affine_with(&self, group: &GroupState) -> Vec<&GroupState> {
+        let mut found: Vec<&GroupState> = Vec::new();
+        for set in self.anti_affinity.iter() {
+            if !set.iter().any(|id| group.members.contains(id)) {
+                continue;
+            }
+            for other in set.iter().filter_map(|id| self.group_of(id)) {
+                if other.id != group.id && !found.iter().any(|found| found.id == other.id) {
+                    found.push(other);
+                }
+            }
+        }
+        found
+    }
+
+    /// The resource groups which must run with `group`, and are running on the same host as it,
+    /// so that they are moved along with it.
+    pub fn moves_with(&self, group: &GroupState) -> Vec<&GroupState> {
+        let Some(here) = group.running_on.as_deref() else {
+            return Vec::new();
+        };
+        self.colocated_with(group)
+            .into_iter()
+            .filter(|other| Self::runs_on(other, here))
+            .collect()
+    }
+
+    /// Whether moving `group`, along with the groups that move with it, to `to` keeps to the
+    /// constraints: each of them may be started there, and none of them must not run with a group
+    /// which is running there.
+    pub fn constraints_allow_move(&self, group: &GroupState, to: &str) -> bool {
+        std::iter::once(group)
+            .chain(self.moves_with(group))
+            .all(|group| {
+                self.may_start_on(group, to)
+                    && !self
+                        .anti_affine_with(group)
+                        .iter()
+                        .any(|other| Self::runs_on(other, to))
+            })
+    }
+
+    /// The constraints which `group` breaks where it is running now, e.g. "must not run on the
+    /// same host as ost1". This happens when there was nowhere else for it to run, such as after a
+    /// failover, and is reported as a warning until it is resolved.
+    pub fn violations(&self, group: &GroupState) -> Vec<String> {
+        let Some(here) = group.running_on.as_deref() else {
+            return Vec::new();
+        };
+        let mut violations = Vec::new();
+        for other in self.anti_affine_with(group) {
+            if Self::runs_on(other, here) {
+                violations.push(format!("must not run on the same host as {}", other.id));
+            }
+        }
+        for other in self.colocated_with(group) {
+            if let Some(there) = other.running_on.as_deref().filter(|there| *there != here) {
+                violations.push(format!(
+                    "must run on the same host as {}, which is on {there}",
+                    other.id
+                ));
+            }
+        }
+        violations
+    }
+
+    /// If `group` breaks a constraint where it is running, and should be moved to `to`, which it
+    /// may run on, to resolve it, why. Of two groups which break a constraint together, the one
+    /// which is failed over is moved, or if neither or both are, the one whose ID comes last, so
+    /// that only one of them moves. Groups which are being migrated are left alone.
+    pub fn resolve_violation(&self, group: &GroupState, to: &str) -> Option<MoveReason> {
+        let here = group.running_on.as_deref()?;
+        if here == to || group.migrating || !self.constraints_allow_move(group, to) {
+            return None;
+        }
+        let failed_over = |group: &GroupState| !Self::runs_on(group, &group.home);
+        let moves = |other: &GroupState| {
+            !other.migrating
+                && match (failed_over(group), failed_over(other)) {
+                    (true, false) => true,
+                    (false, true) => false,
+                    _ => group.id > other.id,
+                }
+        };
+
+        if let Some(other) = self
+            .anti_affine_with(group)
+            .into_iter()
+            .find(|other| Self::runs_on(other, here) && moves(other))
+        {
+            return Some(MoveReason::Apart(other.id.clone()));
+        }
+        if let Some(other) = self
+            .colocated_with(group)
+            .into_iter()
+            .find(|other| Self::runs_on(other, to) && moves(other))
+        {
+            return Some(MoveReason::Together(other.id.clone()));
+        }
+        None
+    }
+
+    /// The actions that Manage mode would take on the managed resource groups which are not being
+    /// migrated, in the order of their IDs:
+    ///
+    /// - A group which is stopped is started where place() says, or else on the first host it may
+    ///   start on.
+    /// - A group whose host is down is failed over, if it may run on the other host.
+    /// - A group whose host is on standby is moved to the other host, if it may run there.
+    /// - A group which prefers its home node, and is failed over, is moved back, unless that
+    ///   would break its constraints.
+    /// - A group which breaks its constraints, and could keep to them on the other host, is moved
+    ///   there; see resolve_violation().
+    ///
+    /// Except for moves off of a host on standby or down, the groups which must run with a group
+    /// that is moved are moved along with it.
+    pub fn plan(&self) -> Vec<Action> {
+        let mut actions: Vec<Action> = Vec::new();
+        let planned = |actions: &[Action], id: &str| actions.iter().any(|a| a.group() == id);
+
+        for group in self.groups.iter().filter(|g| g.managed && !g.migrating) {
+            if planned(&actions, &group.id) {
+                continue;
+            }
+            let Some(here) = group.running_on.as_deref() else {
+                // Nothing can be concluded about a group whose status is not yet known:
+                if !group.stopped {
+                    continue;
+                }
+                let start = match self.place(group) {
+                    Some((host, reason)) => Some((host, Some(reason))),
+                    None => self
+                        .startable(group)
+                        .first()
+                        .map(|host| (host.to_string(), None)),
+                };
+                if let Some((host, reason)) = start {
+                    actions.push(Action::Start {
+                        group: group.id.clone(),
+                        host,
+                        reason,
+                    });
+                }
+                continue;
+            };
+
+            let there =
+                Self::other_node(group, here).filter(|there| self.may_start_on(group, there));
+            let here_host = self.host(here);
+            let to = |to: &str, reason| Action::Move {
+                group: group.id.clone(),
+                from: here.to_string(),
+                to: to.to_string(),
+                reason,
+            };
+
+            if here_host.is_some_and(|host| host.down) {
+                actions.push(match there {
+                    Some(there) => to(there, MoveReason::Down),
+                    None => Action::Stop {
+                        group: group.id.clone(),
+                        host: here.to_string(),
+                    },
+                });
+                continue;
+            }
+            if let (true, Some(there)) = (here_host.is_some_and(|host| host.standby), there) {
+                actions.push(to(there, MoveReason::Standby));
+                continue;
+            }
+
+            let moved = if group.placement.prefer_home
+                && here != group.home
+                && self.may_start_on(group, &group.home)
+                && self.constraints_allow_move(group, &group.home)
+            {
+                Some(to(&group.home, MoveReason::Home))
+            } else {
+                there.and_then(|there| {
+                    self.resolve_violation(group, there)
+                        .map(|reason| to(there, reason))
+                })
+            };
+            let Some(moved) = moved else {
+                continue;
+            };
+            let Action::Move { to: target, .. } = &moved else {
+                unreachable!()
+            };
+            let target = target.clone();
+            actions.push(moved);
+            for other in self.moves_with(group) {
+                if !planned(&actions, &other.id) && !other.migrating {
+                    actions.push(Action::Move {
+                        group: other.id.clone(),
+                        from: here.to_string(),
+                        to: target.clone(),
+                        reason: MoveReason::With(group.id.clone()),
+                    });
+                }
+            }
+        }
+        actions
+    }
+
+    /// Update this snapshot as though `action` was taken, and succeeded.
+    pub fn apply(&mut self, action: &Action) {
+        let Some(group) = self.groups.iter_mut().find(|g| g.id == action.group()) else {
+            return;
+        };
+        match action {
+            Action::Start { host, .. } | Action::Move { to: host, .. } => {
+                group.running_on = Some(host.clone());
+                group.stopped = false;
+            }
+            Action::Stop { .. } => {
+                group.running_on = None;
+                group.stopped = true;
+            }
+        }
+    }
+
+    /// Take the actions that Manage mode would, one at a time, each decided from the state that
+    /// the previous ones left, until there is nothing left to do. Returns the actions taken.
+    ///
+    /// A group whose constraints can not be kept may keep moving between its hosts, so the
+    /// simulation gives up after a few actions per group.
+    pub fn simulate(&mut self) -> Vec<Action> {
+        let mut taken = Vec::new();
+        let limit = MAX_ACTIONS_PER_GROUP * self.groups.len().max(1);
+        while taken.len() < limit {
+            let Some(action) = self.plan().into_iter().next() else {
+                break;
+            };
+            self.apply(&action);
+            taken.push(action);
+        }
+        taken
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host(id: &str) -> HostState {
+        HostState {
+            id: id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn group(id: &str, home: &str, away: &str) -> GroupState {
+        GroupState {
+            id: id.to_string(),
+            members: vec![id.to_string()],
+            home: home.to_string(),
+            away: Some(away.to_string()),
+            placement: Placement::default(),
+            managed: true,
+            stopped: true,
+            ..Default::default()
+        }
+    }
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            hosts: vec![host("oss00"), host("oss01")],
+            groups: vec![
+                group("ost0", "oss00", "oss01"),
+                group("ost1", "oss01", "oss00"),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_plan_starts_groups_at_home() {
+        let snapshot = snapshot();
+        let actions: Vec<String> = snapshot.plan().iter().map(|a| a.to_string()).collect();
+        assert_eq!(
+            actions,
+            [
+                "start resource group ost0 on oss00",
+                "start resource group ost1 on oss01"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_simulate_failed_host() {
+        let mut snapshot = snapshot();
+        snapshot.groups[0].running_on = Some("oss00".into());
+        snapshot.groups[1].running_on = Some("oss01".into());
+        snapshot.hosts[1].down = true;
+
+        let actions: Vec<String> = snapshot.simulate().iter().map(|a| a.to_string()).collect();
+        assert_eq!(
+            actions,
+            ["move resource group ost1 from oss01 to oss00, since oss01 is down"]
+        );
+        assert_eq!(snapshot.groups[1].running_on.as_deref(), Some("oss00"));
+
+        // With nowhere else to run, the group is given up on:
+        let mut snapshot = self::snapshot();
+        snapshot.groups[1].running_on = Some("oss01".into());
+        snapshot.groups[1].placement.allow_away = false;
+        snapshot.groups[0].running_on = Some("oss00".into());
+        snapshot.hosts[1].down = true;
+        let actions = snapshot.simulate();
+        assert_eq!(
+            actions,
+            [Action::Stop {
+                group: "ost1".into(),
+                host: "oss01".into()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_simulate_standby_and_prefer_home() {
+        let mut snapshot = snapshot();
+        snapshot.groups[0].running_on = Some("oss01".into());
+        snapshot.groups[0].placement.prefer_home = true;
+        snapshot.groups[1].running_on = Some("oss00".into());
+        snapshot.hosts[0].standby = true;
+
+        let actions: Vec<String> = snapshot.simulate().iter().map(|a| a.to_string()).collect();
+        assert_eq!(
+            actions,
+            ["move resource group ost1 from oss00 to oss01, since oss00 is on standby"]
+        );
+
+        snapshot.hosts[0].standby = false;
+        let actions: Vec<String> = snapshot.simulate().iter().map(|a| a.to_string()).collect();
+        assert_eq!(
+            actions,
+            ["move resource group ost0 from oss01 back to oss00"]
+        );
+    }
+
+    #[test]
+    fn test_simulate_constraints() {
+        // Two groups which must not run together end up apart once they may:
+        let mut snapshot = snapshot();
+        snapshot.anti_affinity = vec![vec!["ost0".into(), "ost1".into()]];
+        snapshot.groups[0].running_on = Some("oss00".into());
+        snapshot.groups[1].running_on = Some("oss00".into());
+        assert_eq!(
+            snapshot.violations(&snapshot.groups[1]),
+            ["must not run on the same host as ost0"]
+        );
+        let actions = snapshot.simulate();
+        assert_eq!(
+            actions,
+            [Action::Move {
+                group: "ost1".into(),
+                from: "oss00".into(),
+                to: "oss01".into(),
+                reason: MoveReason::Apart("ost0".into())
+            }]
+        );
+        assert!(snapshot.violations(&snapshot.groups[1]).is_empty());
+
+        // A group which must run with another is started with it, and moves along with it:
+        let mut snapshot = self::snapshot();
+        snapshot.colocation = vec![vec!["ost0".into(), "ost1".into()]];
+        let actions: Vec<String> = snapshot.simulate().iter().map(|a| a.to_string()).collect();
+        assert_eq!(
+            actions,
+            [
+                "start resource group ost0 on oss00",
+                "start resource group ost1 on oss00 (with ost0)"
+            ]
+        );
+        snapshot.groups[0].placement.prefer_home = true;
+        snapshot.groups[0].home = "oss01".into();
+        snapshot.groups[0].away = Some("oss00".into());
+        let actions: Vec<String> = snapshot.plan().iter().map(|a| a.to_string()).collect();
+        assert_eq!(
+            actions,
+            [
+                "move resource group ost0 from oss00 back to oss01",
+                "move resource group ost1 from oss00 to oss01 along with ost0"
+            ]
+        );
+    }
+}
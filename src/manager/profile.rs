@@ -0,0 +1,276 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+//! Timing of the management loops, for `--profile`.
+//!
+//! Each pass of a management or observation loop over a resource group is timed as a whole, and
+//! split into its monitor phase, in which the agents are asked whether each resource is running,
+//! and its action phase, in which resources are started or stopped. The time that the agent took
+//! to check on each resource is timed too. After each pass, a summary which names the slowest
+//! resources is logged, and the timings are added to histograms that are reported at `/metrics`.
+//!
+//! Without `--profile`, the cluster has no Profiler, and the loops time nothing.
+
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use log::warn;
+
+/// The upper bounds of the buckets of each histogram, in seconds. A start or stop can take as long
+/// as its timeout, which defaults to 300 seconds.
+const BUCKETS: [f64; 14] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0,
+];
+
+/// A histogram of durations, which can be added to from any task.
+#[derive(Debug, Default)]
+pub struct Histogram {
+    /// How many durations fell into each bucket, and not into an earlier one. The last count is
+    /// of the durations longer than every bucket.
+    counts: [AtomicU64; BUCKETS.len() + 1],
+
+    /// The sum of the durations, in microseconds.
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let bucket = BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(BUCKETS.len());
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Write the series of this histogram in the Prometheus text format, under `name`, with the
+    /// given labels, if any, e.g. `phase="monitor"`.
+    fn write(&self, out: &mut String, name: &str, labels: &str) {
+        let with = |le: &str| match labels {
+            "" => format!("le=\"{le}\""),
+            labels => format!("{labels},le=\"{le}\""),
+        };
+        let mut total = 0;
+        for (bound, count) in BUCKETS.iter().zip(&self.counts) {
+            total += count.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{name}_bucket{{{}}} {total}", with(&bound.to_string()));
+        }
+        total += self.counts[BUCKETS.len()].load(Ordering::Relaxed);
+        let _ = writeln!(out, "{name}_bucket{{{}}} {total}", with("+Inf"));
+
+        let labels = match labels {
+            "" => String::new(),
+            labels => format!("{{{labels}}}"),
+        };
+        let sum = self.sum.load(Ordering::Relaxed) as f64 / 1e6;
+        let _ = writeln!(out, "{name}_sum{labels} {sum}");
+        let _ = writeln!(out, "{name}_count{labels} {total}");
+    }
+}
+
+/// The timings of the management loops, which the cluster keeps when the manager is run with
+/// `--profile`.
+#[derive(Debug)]
+pub struct Profiler {
+    /// How many of the slowest resources to name in the summary of each pass.
+    slowest: usize,
+
+    passes: Histogram,
+    monitor_phases: Histogram,
+    action_phases: Histogram,
+    resource_monitors: Histogram,
+}
+
+impl Profiler {
+    pub fn new(slowest: usize) -> Self {
+        Self {
+            slowest,
+            passes: Histogram::default(),
+            monitor_phases: Histogram::default(),
+            action_phases: Histogram::default(),
+            resource_monitors: Histogram::default(),
+        }
+    }
+
+    /// Start timing pass number `pass` over the resource group `group`. The pass is recorded when
+    /// the returned PassProfile is dropped, however the pass ends.
+    pub fn begin_pass<'a>(&'a self, group: &'a str, pass: u64) -> PassProfile<'a> {
+        PassProfile {
+            profiler: self,
+            group,
+            pass,
+            started: Instant::now(),
+            monitored: None,
+            monitors: Vec::new(),
+        }
+    }
+
+    /// Write the histograms in the Prometheus text format, to be added to `/metrics`.
+    pub fn write_metrics(&self, out: &mut String) {
+        let _ = writeln!(
+            out,
+            "# HELP halo_pass_duration_seconds How long each pass over a resource group took, in all and in each phase."
+        );
+        let _ = writeln!(out, "# TYPE halo_pass_duration_seconds histogram");
+        for (phase, histogram) in [
+            ("total", &self.passes),
+            ("monitor", &self.monitor_phases),
+            ("actions", &self.action_phases),
+        ] {
+            histogram.write(
+                out,
+                "halo_pass_duration_seconds",
+                &format!("phase=\"{phase}\""),
+            );
+        }
+        let _ = writeln!(
+            out,
+            "# HELP halo_resource_monitor_duration_seconds How long the agents took to check whether each resource is running."
+        );
+        let _ = writeln!(
+            out,
+            "# TYPE halo_resource_monitor_duration_seconds histogram"
+        );
+        self.resource_monitors
+            .write(out, "halo_resource_monitor_duration_seconds", "");
+    }
+}
+
+/// The timings of one pass over a resource group, from Profiler::begin_pass().
+pub struct PassProfile<'a> {
+    profiler: &'a Profiler,
+    group: &'a str,
+    pass: u64,
+    started: Instant,
+
+    /// How long the monitor phase took, once it is over.
+    monitored: Option<Duration>,
+
+    /// How long the agent took to check on each resource, by the resource's ID.
+    monitors: Vec<(String, Duration)>,
+}
+
+impl PassProfile<'_> {
+    /// Record that the agent took `duration` to check on the resource `id`.
+    pub fn monitored_resource(&mut self, id: &str, duration: Duration) {
+        self.monitors.push((id.to_string(), duration));
+    }
+
+    /// Record that the monitor phase is over, so the rest of the pass is its action phase.
+    pub fn end_monitoring(&mut self) {
+        self.monitored = Some(self.started.elapsed());
+    }
+}
+
+impl Drop for PassProfile<'_> {
+    fn drop(&mut self) {
+        let total = self.started.elapsed();
+        let monitor = self.monitored.unwrap_or(total);
+        let actions = total.saturating_sub(monitor);
+
+        let profiler = self.profiler;
+        profiler.passes.observe(total);
+        profiler.monitor_phases.observe(monitor);
+        profiler.action_phases.observe(actions);
+        for (_, duration) in &self.monitors {
+            profiler.resource_monitors.observe(*duration);
+        }
+
+        warn!(
+            "{}",
+            summary(
+                self.group,
+                self.pass,
+                (total, monitor, actions),
+                &mut self.monitors,
+                profiler.slowest
+            )
+        );
+    }
+}
+
+/// The line which is logged after a pass, e.g. "Profile: resource group ost0, pass 12: 35.2ms
+/// (monitor 30.1ms, actions 5.1ms); slowest: ost0 30.1ms, zpool0 12.0ms".
+fn summary(
+    group: &str,
+    pass: u64,
+    (total, monitor, actions): (Duration, Duration, Duration),
+    monitors: &mut [(String, Duration)],
+    slowest: usize,
+) -> String {
+    let ms = |duration: Duration| format!("{:.1}ms", duration.as_secs_f64() * 1000.0);
+    let mut line = format!(
+        "Profile: resource group {group}, pass {pass}: {} (monitor {}, actions {})",
+        ms(total),
+        ms(monitor),
+        ms(actions)
+    );
+
+    monitors.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let slowest: Vec<String> = monitors
+        .iter()
+        .take(slowest)
+        .map(|(id, duration)| format!("{id} {}", ms(*duration)))
+        .collect();
+    if !slowest.is_empty() {
+        let _ = write!(line, "; slowest: {}", slowest.join(", "));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram() {
+        let histogram = Histogram::default();
+        histogram.observe(Duration::from_millis(3));
+        histogram.observe(Duration::from_millis(20));
+        histogram.observe(Duration::from_secs(1000));
+
+        let mut out = String::new();
+        histogram.write(&mut out, "h", "phase=\"monitor\"");
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "h_bucket{phase=\"monitor\",le=\"0.005\"} 1");
+        assert_eq!(lines[1], "h_bucket{phase=\"monitor\",le=\"0.01\"} 1");
+        assert_eq!(lines[2], "h_bucket{phase=\"monitor\",le=\"0.025\"} 2");
+        assert_eq!(lines[13], "h_bucket{phase=\"monitor\",le=\"300\"} 2");
+        assert_eq!(lines[14], "h_bucket{phase=\"monitor\",le=\"+Inf\"} 3");
+        assert_eq!(lines[15], "h_sum{phase=\"monitor\"} 1000.023");
+        assert_eq!(lines[16], "h_count{phase=\"monitor\"} 3");
+
+        let mut out = String::new();
+        Histogram::default().write(&mut out, "h", "");
+        assert!(out.starts_with("h_bucket{le=\"0.005\"} 0\n"));
+        assert!(out.ends_with("h_sum 0\nh_count 0\n"));
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut monitors = vec![
+            ("zpool0".to_string(), Duration::from_millis(12)),
+            ("ost0".to_string(), Duration::from_micros(30_100)),
+            ("ost1".to_string(), Duration::from_millis(2)),
+        ];
+        let times = (
+            Duration::from_micros(35_200),
+            Duration::from_micros(30_100),
+            Duration::from_micros(5_100),
+        );
+        assert_eq!(
+            summary("ost0", 12, times, &mut monitors, 2),
+            "Profile: resource group ost0, pass 12: 35.2ms (monitor 30.1ms, actions 5.1ms); \
+             slowest: ost0 30.1ms, zpool0 12.0ms"
+        );
+        assert_eq!(
+            summary("ost0", 12, times, &mut [], 2),
+            "Profile: resource group ost0, pass 12: 35.2ms (monitor 30.1ms, actions 5.1ms)"
+        );
+    }
+}
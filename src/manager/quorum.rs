@@ -0,0 +1,114 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+//! Whether the manager can reach enough of the cluster's hosts to act on it safely.
+//!
+//! A manager which has lost contact with most of the hosts may well be the one which is cut off,
+//! so without quorum it only observes: resources are not started, moved, or failed over until
+//! enough hosts are reachable again. Quorum is only checked when the config sets a policy for it.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// What to do when exactly half of the expected hosts are reachable.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Tiebreak {
+    /// Half is not a majority, so there is no quorum.
+    #[default]
+    Deny,
+
+    /// Half of the hosts are enough for quorum.
+    Allow,
+
+    /// Half of the hosts are enough for quorum if the first host listed in the config is one of
+    /// them, so that of two halves which can not reach each other, exactly one has quorum.
+    FirstHost,
+}
+
+impl fmt::Display for Tiebreak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tiebreak::Deny => write!(f, "deny"),
+            Tiebreak::Allow => write!(f, "allow"),
+            Tiebreak::FirstHost => write!(f, "first_host"),
+        }
+    }
+}
+
+/// The quorum policy of a cluster.
+#[derive(Debug, Clone)]
+pub struct Quorum {
+    /// How many hosts the cluster is expected to have.
+    pub expected: usize,
+
+    pub tiebreak: Tiebreak,
+
+    /// The ID of the first host listed in the config, for the FirstHost tiebreak.
+    pub first_host: Option<String>,
+}
+
+impl Quorum {
+    /// Whether `reachable` hosts are enough for quorum. `first_host_reachable` is whether the first
+    /// host listed in the config is one of them.
+    pub fn is_met(&self, reachable: usize, first_host_reachable: bool) -> bool {
+        match (reachable * 2).cmp(&self.expected) {
+            std::cmp::Ordering::Greater => true,
+            std::cmp::Ordering::Less => false,
+            std::cmp::Ordering::Equal => match self.tiebreak {
+                Tiebreak::Deny => false,
+                Tiebreak::Allow => true,
+                Tiebreak::FirstHost => first_host_reachable,
+            },
+        }
+    }
+
+    /// The fewest reachable hosts that can have quorum, counting a tie if the tiebreak may grant
+    /// it.
+    pub fn needed(&self) -> usize {
+        match self.tiebreak {
+            Tiebreak::Deny => self.expected / 2 + 1,
+            Tiebreak::Allow | Tiebreak::FirstHost => self.expected.div_ceil(2),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quorum(expected: usize, tiebreak: Tiebreak) -> Quorum {
+        Quorum {
+            expected,
+            tiebreak,
+            first_host: Some("mds00".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_majority() {
+        let q = quorum(5, Tiebreak::Deny);
+        assert!(q.is_met(5, false));
+        assert!(q.is_met(3, false));
+        assert!(!q.is_met(2, true));
+        assert!(!q.is_met(0, false));
+        assert_eq!(q.needed(), 3);
+    }
+
+    #[test]
+    fn test_tiebreak() {
+        assert!(!quorum(4, Tiebreak::Deny).is_met(2, true));
+        assert_eq!(quorum(4, Tiebreak::Deny).needed(), 3);
+
+        assert!(quorum(4, Tiebreak::Allow).is_met(2, false));
+        assert_eq!(quorum(4, Tiebreak::Allow).needed(), 2);
+
+        assert!(quorum(4, Tiebreak::FirstHost).is_met(2, true));
+        assert!(!quorum(4, Tiebreak::FirstHost).is_met(2, false));
+        assert!(!quorum(4, Tiebreak::FirstHost).is_met(1, true));
+
+        // With an odd number of hosts there can be no tie:
+        assert!(!quorum(3, Tiebreak::Allow).is_met(1, true));
+    }
+}
@@ -0,0 +1,98 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+//! Limits on how often each endpoint which changes something may be requested, for
+//! `--rate-limit`.
+//!
+//! Each endpoint, e.g. `PATCH /resources/{id}`, has a bucket of tokens which refills at the rate
+//! limit, up to the burst size. A request takes a token; a request which finds the bucket empty is
+//! rejected with how long until the next token, which the reply gives as its Retry-After.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// The rate limits of the endpoints of the manager.
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// How many tokens each bucket gains per second.
+    rate: f64,
+
+    /// How many tokens each bucket can hold.
+    burst: f64,
+
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    /// A limiter which lets each endpoint be requested `rate` times per second, and up to `burst`
+    /// times at once after it has been idle. The rate may not be 0; a burst of 0 is taken as 1.
+    pub fn new(rate: u32, burst: u32) -> Self {
+        Self {
+            rate: f64::from(rate),
+            burst: f64::from(burst.max(1)),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a token for a request to `endpoint` at `now`, or if there is none, return how long
+    /// until there is.
+    pub fn check(&self, endpoint: &str, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(endpoint.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let limiter = RateLimiter::new(2, 3);
+        let start = Instant::now();
+
+        // A burst is accepted, up to its size:
+        for _ in 0..3 {
+            assert_eq!(limiter.check("POST /mode", start), Ok(()));
+        }
+        assert_eq!(
+            limiter.check("POST /mode", start),
+            Err(Duration::from_millis(500))
+        );
+
+        // Each endpoint has its own limit:
+        assert_eq!(limiter.check("POST /maintenance", start), Ok(()));
+
+        // The bucket refills at the rate, but never beyond the burst size:
+        let later = start + Duration::from_millis(500);
+        assert_eq!(limiter.check("POST /mode", later), Ok(()));
+        assert!(limiter.check("POST /mode", later).is_err());
+        let much_later = later + Duration::from_secs(60);
+        for _ in 0..3 {
+            assert_eq!(limiter.check("POST /mode", much_later), Ok(()));
+        }
+        assert!(limiter.check("POST /mode", much_later).is_err());
+    }
+}
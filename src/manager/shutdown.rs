@@ -0,0 +1,76 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+//! Coordination of a graceful shutdown of the manager.
+//!
+//! When a shutdown is requested, resource management tasks finish the iteration they are in the
+//! middle of, so that an operation like starting a resource is never abandoned halfway. They then
+//! wait at the start of their next iteration until the manager exits.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use tokio::sync::Notify;
+
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+static SHUTDOWN: Notify = Notify::const_new();
+
+/// The number of resource management iterations currently in progress.
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+static IDLE: Notify = Notify::const_new();
+
+pub fn request_shutdown() {
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+    SHUTDOWN.notify_waiters();
+}
+
+pub fn is_shutting_down() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Wait until a shutdown has been requested.
+pub async fn shutdown_requested() {
+    loop {
+        let notified = SHUTDOWN.notified();
+        if is_shutting_down() {
+            return;
+        }
+        notified.await;
+    }
+}
+
+/// Wait until no resource management iteration is in progress. This should only be awaited after
+/// a shutdown has been requested, since otherwise new iterations keep beginning.
+pub async fn wait_for_idle() {
+    loop {
+        let notified = IDLE.notified();
+        if ACTIVE.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        notified.await;
+    }
+}
+
+/// Marks a resource management iteration as in progress for as long as it is held.
+pub struct Activity {}
+
+impl Drop for Activity {
+    fn drop(&mut self) {
+        if ACTIVE.fetch_sub(1, Ordering::SeqCst) == 1 {
+            IDLE.notify_waiters();
+        }
+    }
+}
+
+/// Begin a resource management iteration. If a shutdown has been requested, this never returns, so
+/// that no new iteration begins.
+pub async fn begin_activity() -> Activity {
+    // The count is incremented before checking for shutdown so that wait_for_idle() can not miss
+    // an iteration that is just beginning:
+    ACTIVE.fetch_add(1, Ordering::SeqCst);
+    let activity = Activity {};
+    if is_shutting_down() {
+        std::mem::drop(activity);
+        return std::future::pending().await;
+    }
+    activity
+}
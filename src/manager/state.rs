@@ -0,0 +1,229 @@
+// SPDX-License-Identifier: MIT
+// Copyright 2025. Triad National Security, LLC.
+
+//! The state file, for `--state-file`, which keeps the decisions that admins make at runtime
+//! across restarts of the manager.
+//!
+//! The config file defines the resources, and whether each starts out managed and running; the
+//! state file overlays what admins changed since: which resource groups are managed, which hosts
+//! are on standby, and the target state of each resource. The manager writes it whenever that
+//! changes, checking once per poll interval, and restores it when it starts. A state file which
+//! can not be read, or was written in another format than this manager's, is ignored, so that the
+//! manager starts with the config defaults rather than not at all.
+//!
+//! Maintenance mode is recorded too, but it is restored from the maintenance file, which is
+//! written as soon as maintenance mode changes, rather than on the next check.
+
+use std::{collections::BTreeMap, io, io::Write, sync::Arc};
+
+use {
+    log::{debug, error, info, warn},
+    serde::{Deserialize, Serialize},
+};
+
+use crate::{cluster::Cluster, manager::shutdown, resource::TargetState};
+
+/// The version of the format of the state file that this manager writes, and can read.
+pub const STATE_VERSION: u32 = 1;
+
+/// What the state file holds.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct State {
+    pub version: u32,
+
+    /// Whether each resource group is managed, by the group's ID.
+    pub managed: BTreeMap<String, bool>,
+
+    /// The IDs of the hosts which are on standby.
+    pub standby: Vec<String>,
+
+    /// The target state of each resource, by its ID.
+    pub target_states: BTreeMap<String, TargetState>,
+
+    pub maintenance: bool,
+}
+
+impl State {
+    /// The state of `cluster` now.
+    pub fn of(cluster: &Cluster) -> Self {
+        let mut standby: Vec<String> = cluster
+            .hosts()
+            .filter(|host| host.is_standby())
+            .map(|host| host.id())
+            .collect();
+        standby.sort();
+        Self {
+            version: STATE_VERSION,
+            managed: cluster
+                .resource_groups()
+                .map(|rg| (rg.id().to_string(), rg.get_managed()))
+                .collect(),
+            standby,
+            target_states: cluster
+                .resources()
+                .map(|res| (res.id.clone(), res.target_state()))
+                .collect(),
+            maintenance: cluster.maintenance().is_on(),
+        }
+    }
+
+    /// Read the state file at `path`. Returns None if there is none, and an error saying what is
+    /// wrong with it if it can not be used.
+    pub fn load(path: &str) -> Result<Option<Self>, String> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.to_string()),
+        };
+        #[derive(Deserialize)]
+        struct Versioned {
+            version: u32,
+        }
+        let versioned: Versioned = serde_json::from_str(&contents).map_err(|e| e.to_string())?;
+        if versioned.version != STATE_VERSION {
+            return Err(format!(
+                "it is in version {} of the format, but this manager uses version {STATE_VERSION}",
+                versioned.version
+            ));
+        }
+        serde_json::from_str(&contents)
+            .map(Some)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Write this state to the file at `path`, creating its directory if need be. It is written to
+    /// a temporary file next to it first, which is then renamed over it, so that the file is never
+    /// left half-written.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let path = std::path::Path::new(path);
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+
+        let mut file = std::fs::File::create(&temp)?;
+        file.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        file.write_all(b"\n")?;
+        file.sync_all()?;
+        std::fs::rename(&temp, path)
+    }
+
+    /// Apply this state to `cluster`, before it starts managing anything. Resource groups,
+    /// resources, and hosts which are no longer in the config are skipped. Returns how many
+    /// settings differed from the config, and were changed.
+    pub fn restore(&self, cluster: &Cluster) -> usize {
+        let mut changed = 0;
+        for (id, managed) in self.managed.iter() {
+            match cluster.find_resource_group(id) {
+                Some(rg) => changed += usize::from(rg.set_managed(*managed)),
+                None => debug!("Resource group {id} in the state file is no longer configured."),
+            }
+        }
+        for (id, target) in self.target_states.iter() {
+            match cluster.find_resource(id) {
+                Some((_, res)) => changed += usize::from(res.set_target_state(*target)),
+                None => debug!("Resource {id} in the state file is no longer configured."),
+            }
+        }
+        for id in self.standby.iter() {
+            match cluster.get_host(id) {
+                Some(host) if !host.is_standby() => {
+                    host.set_standby(true);
+                    changed += 1;
+                }
+                Some(_) => {}
+                None => debug!("Host {id} in the state file is no longer configured."),
+            }
+        }
+        changed
+    }
+}
+
+/// Restore the state of `cluster` from the state file given by `--state-file`, if any. If the
+/// file can not be used, this is logged, and the config defaults are kept.
+pub fn restore(cluster: &Cluster) {
+    let Some(path) = cluster.args.state_file.as_deref() else {
+        return;
+    };
+    match State::load(path) {
+        Ok(Some(state)) => {
+            let changed = state.restore(cluster);
+            info!(
+                "Restored the state of the cluster from '{path}', which changed {changed} \
+                 settings from the config."
+            );
+        }
+        Ok(None) => {
+            info!("There is no state file at '{path}' yet, so the config defaults are used.")
+        }
+        Err(e) => warn!(
+            "Could not read the state file '{path}', so the config defaults are used instead: {e}"
+        ),
+    }
+}
+
+/// Write the state of `cluster` to the state file given by `--state-file`, if any, whenever it
+/// changes, until a shutdown is requested; it is written once more then, in case it changed
+/// after the last check.
+pub async fn keep_saved(cluster: Arc<Cluster>) {
+    let Some(path) = cluster.args.state_file.clone() else {
+        return;
+    };
+    let mut saved = None;
+    let save = |saved: &mut Option<State>| {
+        let state = State::of(&cluster);
+        if saved.as_ref() == Some(&state) {
+            return;
+        }
+        match state.save(&path) {
+            Ok(()) => *saved = Some(state),
+            Err(e) => error!("Could not write the state file '{path}': {e}"),
+        }
+    };
+
+    tokio::select! {
+        _ = async {
+            loop {
+                save(&mut saved);
+                tokio::time::sleep(cluster.args.poll_delay()).await;
+            }
+        } => {}
+        _ = shutdown::shutdown_requested() => {}
+    }
+    save(&mut saved);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = std::env::temp_dir().join(format!("halo_state_{}", std::process::id()));
+        let path = dir.join("state.json");
+        let path = path.to_str().unwrap();
+
+        assert_eq!(State::load(path), Ok(None));
+
+        let state = State {
+            version: STATE_VERSION,
+            managed: BTreeMap::from([("ost0".to_string(), false)]),
+            standby: vec!["oss01".to_string()],
+            target_states: BTreeMap::from([("ost0".to_string(), TargetState::Stopped)]),
+            maintenance: false,
+        };
+        state.save(path).unwrap();
+        assert_eq!(State::load(path), Ok(Some(state.clone())));
+        assert!(!std::path::Path::new(&format!("{path}.tmp")).exists());
+
+        // A corrupt file, or one in another version of the format, can not be used:
+        std::fs::write(path, "{\"version\": 1, \"managed\": ").unwrap();
+        assert!(State::load(path).is_err());
+        std::fs::write(path, "{\"version\": 0}").unwrap();
+        let e = State::load(path).unwrap_err();
+        assert!(e.contains("version 0"), "{e}");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
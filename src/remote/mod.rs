@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: MIT
 // Copyright 2025. Triad National Security, LLC.
 
-use std::{error::Error, net::Ipv4Addr, str::FromStr};
+use std::{collections::HashMap, error::Error, net::Ipv4Addr, str::FromStr, sync::Arc};
 
 use {
     capnp::capability::Promise,
@@ -10,6 +10,7 @@ use {
     futures::AsyncReadExt,
     log::{info, trace},
     nix::ifaddrs,
+    tracing::Instrument,
 };
 
 use crate::{halo_capnp::ocf_resource_agent, tls::get_acceptor};
@@ -17,7 +18,14 @@ use crate::{halo_capnp::ocf_resource_agent, tls::get_acceptor};
 pub mod ocf;
 
 struct OcfResourceAgentImpl {
-    cli: Cli,
+    /// Shared with the blocking tasks that run the resource agent scripts.
+    cli: Arc<Cli>,
+
+    /// A lock for each resource which has an operation in progress, so that the operations on one
+    /// resource run one at a time, while those on different resources run in parallel. The
+    /// request does not say which resource it is for by ID, so resources are told apart by their
+    /// kind and parameters, which are all that the script sees of them either way.
+    locks: HashMap<String, Arc<tokio::sync::Mutex<()>>>,
 }
 
 #[derive(Parser)]
@@ -34,6 +42,14 @@ pub struct Cli {
     #[arg(short, long)]
     pub verbose: bool,
 
+    /// The format of log output.
+    #[arg(long, value_enum, default_value_t)]
+    pub log_format: crate::logging::LogFormat,
+
+    /// Append log output to this file instead of writing it to stderr.
+    #[arg(long)]
+    pub log_file: Option<String>,
+
     /// For the test environment, a remote agent can be given an ID to assist with identifying
     /// multiple agents running on the same system.
     #[arg(long)]
@@ -68,6 +84,13 @@ pub fn agent_main(args: Cli) -> Result<(), Box<dyn Error>> {
 
     let addr = format!("{addr}:{}", port);
 
+    if args.mtls {
+        if let Err(e) = crate::tls::init_acceptor(&crate::tls::TlsPaths::new(None)) {
+            eprintln!("--mtls is set, but the TLS certificates could not be loaded: {e}");
+            return Err(Box::new(e));
+        }
+    }
+
     let rt = tokio::runtime::Runtime::new().expect("Failed to launch runtime.");
     rt.block_on(async { __agent_main(args, &addr).await })?;
 
@@ -102,7 +125,10 @@ async fn __agent_main(args: Cli, addr: &str) -> Result<(), Box<dyn Error>> {
             info!("Listening on {addr}");
 
             let agent_client: ocf_resource_agent::Client =
-                capnp_rpc::new_client(OcfResourceAgentImpl { cli: args });
+                capnp_rpc::new_client(OcfResourceAgentImpl {
+                    cli: Arc::new(args),
+                    locks: HashMap::new(),
+                });
 
             loop {
                 let (stream, _) = listener.accept().await?;
@@ -148,7 +174,7 @@ impl ocf_resource_agent::Server for OcfResourceAgentImpl {
     fn operation(
         &mut self,
         params: ocf_resource_agent::OperationParams,
-        mut results: ocf_resource_agent::OperationResults,
+        results: ocf_resource_agent::OperationResults,
     ) -> Promise<(), ::capnp::Error> {
         let params = pry!(params.get());
         let resource = pry!(params.get_resource());
@@ -172,11 +198,57 @@ impl ocf_resource_agent::Server for OcfResourceAgentImpl {
 
         log_operation(&op, &ocf_args);
 
+        let lock = self.lock(resource, &ocf_args);
         let ocf_args = ocf::Arguments::from(&ocf_args);
+        let span = tracing::trace_span!("operation", resource, ?op);
+        let resource = resource.to_string();
+        let cli = Arc::clone(&self.cli);
+
+        // The script can take a long time to run, e.g. to start a Lustre target, so it is run on a
+        // blocking thread: RPCs are all served on the same thread, so running it here would hold
+        // up every other request until it finished, even a monitor of an unrelated resource.
+        //
+        // Resource agents are not safe against running two operations on the same resource at
+        // once, e.g. a stop while a start which timed out is still running, so each operation
+        // first waits for the one before it on the same resource to finish:
+        Promise::from_future(
+            async move {
+                let _guard = lock.lock_owned().await;
+                let outcome = tokio::task::spawn_blocking(move || {
+                    ocf::do_operation(&resource, op, &ocf_args, &cli)
+                })
+                .await
+                .map_err(|e| capnp::Error::failed(format!("resource agent task failed: {e}")))?;
+                Self::set_results(outcome, results)
+            }
+            .instrument(span),
+        )
+    }
+}
+
+impl OcfResourceAgentImpl {
+    /// The lock for the resource of kind `resource` with the given `args`. The locks of resources
+    /// which have no operation in progress are dropped.
+    fn lock(&mut self, resource: &str, args: &[(&str, &str)]) -> Arc<tokio::sync::Mutex<()>> {
+        let mut args = args.to_vec();
+        args.sort();
+        let mut key = resource.to_string();
+        for (k, v) in args {
+            key.push_str(&format!("\0{k}={v}"));
+        }
+
+        self.locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+        Arc::clone(self.locks.entry(key).or_default())
+    }
 
-        match ocf::do_operation(resource, op, &ocf_args, &self.cli) {
+    /// Fill in `results` with the `outcome` of running a resource agent script.
+    fn set_results(
+        outcome: Result<(i32, String), String>,
+        mut results: ocf_resource_agent::OperationResults,
+    ) -> Result<(), capnp::Error> {
+        match outcome {
             Ok((exit_code, error_output)) => {
-                let mut ok_result = pry!(results.get().get_result());
+                let mut ok_result = results.get().get_result()?;
                 let mut inner_result = ::capnp::message::Builder::new_default();
                 let mut inner_result =
                     inner_result.init_root::<ocf_resource_agent::inner_result::Builder>();
@@ -189,16 +261,16 @@ impl ocf_resource_agent::Server for OcfResourceAgentImpl {
                         ocf_error.init_root::<ocf_resource_agent::ocf_error::Builder>();
                     ocf_error.set_code(exit_code);
                     ocf_error.set_message(error_output);
-                    pry!(inner_result.set_inner_err(ocf_error.into_reader()));
+                    inner_result.set_inner_err(ocf_error.into_reader())?;
                 }
-                pry!(ok_result.set_ok(inner_result.into_reader()));
+                ok_result.set_ok(inner_result.into_reader())?;
             }
             Err(e) => {
-                pry!(results.get().get_result()).set_err(e);
+                results.get().get_result()?.set_err(e);
             }
         };
 
-        Promise::ok(())
+        Ok(())
     }
 }
 
@@ -3,15 +3,29 @@
 
 use std::{
     collections::{HashMap, VecDeque},
-    sync::{Arc, Mutex},
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
 use {
     futures::future,
-    log::{error, warn},
+    log::{debug, error, warn},
+    serde::{Deserialize, Serialize},
+    tracing::Instrument,
 };
 
-use crate::{halo_capnp::*, host::*, manager, remote::ocf};
+use crate::{
+    agent::ResourceAgent,
+    cluster::Cluster,
+    halo_capnp::*,
+    host::*,
+    manager::{self, events::Event, http::ResourceAction, profile::PassProfile, shutdown},
+    remote::ocf,
+};
 
 #[derive(Debug)]
 pub enum ManagementError {
@@ -58,16 +72,47 @@ fn get_worst_error(
 
 /// Resource Group contains a zpool resource together with all of the Lustre resources that depend
 /// on it.
+///
+/// Resource groups are shared between the management tasks and the HTTP handlers, so each piece
+/// of state which can change (the managed flag, the statuses, the parameters, and any migration in
+/// progress) is behind a lock of its own. A lock is only held long enough to read or update its
+/// value, never while taking another lock or across an `.await`, so the locks can not deadlock
+/// regardless of the order in which they are taken.
 #[derive(Debug)]
 pub struct ResourceGroup {
     pub root: Resource,
     overall_status: Mutex<ResourceStatus>,
     managed: Mutex<bool>,
     args: manager::Cli,
+
+    /// The number of passes that the management or observation loop has made over this group.
+    iterations: AtomicU64,
+
+    /// When the management or observation loop last finished a pass over this group.
+    last_pass: Mutex<Option<Instant>>,
+
+    /// The location that this group is being migrated to, while a migration is in progress.
+    migration: Mutex<Option<Location>>,
+
+    /// Why the last migration of this group failed, until the group is migrated successfully or
+    /// the failure is cleared.
+    migration_failure: Mutex<Option<String>>,
+
+    /// Where this group may run, from the `home_node` and `allowed_nodes` of its root resource.
+    placement: Placement,
+
+    /// Whether this group was removed from the cluster while the manager runs. A removed group is
+    /// kept, since management tasks may still refer to it, but they stop at their next pass.
+    removed: AtomicBool,
+
+    /// Whether a management task holds this group: in HA mode, whether its ResourceToken exists,
+    /// and otherwise, whether its task is running. A removed group is only added back once it is
+    /// no longer held, so that two tasks never manage it at once.
+    held: AtomicBool,
 }
 
 impl ResourceGroup {
-    pub fn new(root: Resource, args: manager::Cli) -> Self {
+    pub fn new(root: Resource, args: manager::Cli, placement: Placement) -> Self {
         assert!(root.kind == "heartbeat/ZFS");
         Self {
             root,
@@ -76,6 +121,13 @@ impl ResourceGroup {
             )),
             managed: Mutex::new(true),
             args,
+            iterations: AtomicU64::new(0),
+            last_pass: Mutex::new(None),
+            migration: Mutex::new(None),
+            migration_failure: Mutex::new(None),
+            placement,
+            removed: AtomicBool::new(false),
+            held: AtomicBool::new(false),
         }
     }
 
@@ -87,38 +139,191 @@ impl ResourceGroup {
         &self.root.home_node
     }
 
+    pub fn placement(&self) -> Placement {
+        self.placement
+    }
+
+    /// Whether this group was removed from the cluster, by Cluster::remove_resource_group().
+    pub fn is_removed(&self) -> bool {
+        self.removed.load(Ordering::Acquire)
+    }
+
+    /// Mark this group as removed from the cluster. Only Cluster::remove_resource_group() should
+    /// call this, so that the cluster's resource counts stay up to date.
+    pub fn set_removed(&self) {
+        self.removed.store(true, Ordering::Release);
+    }
+
+    /// Add this group back to the cluster after it was removed, with the settings of `rg`: the
+    /// same group, as it was just built from the config. What the manager last saw of the
+    /// resources is kept until they are checked again. Only Cluster::add_resource_group() should
+    /// call this, once it made sure that `rg` has the same resources, running on the same hosts.
+    pub fn revive(&self, rg: &ResourceGroup) {
+        self.set_managed(rg.get_managed());
+        for (res, new_res) in self.resources().zip(rg.resources()) {
+            res.set_parameters(new_res.parameters());
+            res.set_depends_on(new_res.depends_on());
+            res.set_timeouts(new_res.timeouts());
+            res.set_target_state(new_res.target_state());
+            res.clear_failures();
+        }
+        self.set_migration_result(Ok(()));
+        self.removed.store(false, Ordering::Release);
+    }
+
+    /// Whether `rg` has the same resources as this group, of the same kinds and depending on each
+    /// other in the same way, with the same hosts and placement, so that it can be added back in
+    /// place of this group by revive().
+    pub fn same_layout(&self, rg: &ResourceGroup) -> bool {
+        let same_host = |a: Option<&Arc<Host>>, b: Option<&Arc<Host>>| match (a, b) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        let dependents = |res: &Resource| -> Vec<String> {
+            res.dependents.iter().map(|dep| dep.id.clone()).collect()
+        };
+        self.placement == rg.placement
+            && self.resources().count() == rg.resources().count()
+            && self.resources().zip(rg.resources()).all(|(a, b)| {
+                a.id == b.id
+                    && a.kind == b.kind
+                    && Arc::ptr_eq(&a.home_node, &b.home_node)
+                    && same_host(a.failover_node.as_ref(), b.failover_node.as_ref())
+                    && dependents(a) == dependents(b)
+            })
+    }
+
+    /// Mark this group as held by a management task. Returns false if it already was, in which
+    /// case the caller must not manage it as well.
+    pub fn hold(&self) -> bool {
+        !self.held.swap(true, Ordering::AcqRel)
+    }
+
+    /// Mark this group as no longer held by a management task, once the task is done with it.
+    pub fn release(&self) {
+        self.held.store(false, Ordering::Release);
+    }
+
+    pub fn is_held(&self) -> bool {
+        self.held.load(Ordering::Acquire)
+    }
+
+    /// Whether this group may be started on the given location: it must be allowed to run there,
+    /// and the host there must not be on standby.
+    pub fn may_start_on(&self, loc: Location) -> bool {
+        let allowed = match loc {
+            Location::Home => true,
+            Location::Away => self.placement.allow_away,
+        };
+        allowed && self.root.node(loc).is_some_and(|host| !host.is_standby())
+    }
+
+    /// Whether this group may be started on `host`, as by may_start_on(), if it is one of the
+    /// group's nodes.
+    pub fn may_start_on_host(&self, host: &Host) -> bool {
+        [Location::Home, Location::Away].into_iter().any(|loc| {
+            self.root
+                .node(loc)
+                .is_some_and(|node| node.id() == host.id())
+                && self.may_start_on(loc)
+        })
+    }
+
+    /// The host that this group is running on, if it is running.
+    pub fn running_on(&self) -> Option<&Arc<Host>> {
+        self.root.location().and_then(|loc| self.root.node(loc))
+    }
+
+    /// The hosts that this group is allowed to run on by its placement, home node first. This
+    /// includes hosts on standby.
+    pub fn allowed_nodes(&self) -> impl Iterator<Item = &Arc<Host>> {
+        [Location::Home, Location::Away]
+            .into_iter()
+            .filter(|loc| *loc == Location::Home || self.placement.allow_away)
+            .filter_map(|loc| self.root.node(loc))
+    }
+
     /// The host-driven resource management loop manages resources on a given location until
     /// either:
     ///
     ///   - An error is observed: it returns back to the host management code so that the host can
     ///     take the appropriate action, whether that be fencing or trying again;
     ///
-    ///   - The root resource is discovered to be stopped, and the resource group is unmanaged: it
-    ///     returns back to the host management code so that the host can begin checing the
-    ///     failover partner to see if the resource was started there (manual failover).
+    ///   - The root resource is discovered to be stopped, and the resource group is unmanaged, or
+    ///     may not be started on this location: it returns back to the host management code so
+    ///     that the host can begin checing the failover partner to see if the resource was started
+    ///     there (manual failover).
+    ///
+    /// A resource is only started once the resources that it depends on in other groups are
+    /// running; until then, it is Blocked. A resource whose target state is Stopped is never
+    /// started, and if it is running, it is stopped, along with the resources which depend on it.
     pub async fn manage_loop(
         &self,
-        client: &ocf_resource_agent::Client,
+        cluster: &Cluster,
+        agent: &dyn ResourceAgent,
         loc: Location,
     ) -> Result<(), ManagementError> {
         loop {
-            self.update_resources(client, loc).await?;
-            match self.get_overall_status() {
-                ResourceStatus::Stopped => {
-                    if self.get_managed() {
-                        self.start_resources(client, loc).await?;
-                    } else if !self.root.is_running() {
-                        return Ok(());
+            // A group which was removed from the cluster is left as it is:
+            if self.is_removed() {
+                return Ok(());
+            }
+            let activity = shutdown::begin_activity().await;
+            let pass = self.iterations.fetch_add(1, Ordering::Relaxed) + 1;
+            let span = tracing::trace_span!("manage_pass", group = %self.id(), pass);
+            let mut profile = cluster
+                .profiler()
+                .map(|profiler| profiler.begin_pass(self.id(), pass));
+            if !self
+                .manage_pass(cluster, agent, loc, profile.as_mut())
+                .instrument(span)
+                .await?
+            {
+                return Ok(());
+            }
+            std::mem::drop(profile);
+            std::mem::drop(activity);
+            self.wait_for_next_pass().await;
+        }
+    }
+
+    /// One pass of the management loop. Returns whether to go on managing the resource group,
+    /// which is not the case once it is stopped and no longer meant to be started here.
+    async fn manage_pass(
+        &self,
+        cluster: &Cluster,
+        agent: &dyn ResourceAgent,
+        loc: Location,
+        profile: Option<&mut PassProfile<'_>>,
+    ) -> Result<bool, ManagementError> {
+        self.update_resources(agent, loc, profile).await?;
+        if self.get_managed() && cluster.may_act() {
+            self.stop_disabled_resources(cluster, agent).await?;
+        }
+        match self.get_overall_status() {
+            ResourceStatus::Stopped | ResourceStatus::Blocked(_) => {
+                if !cluster.update_quorum() || !cluster.may_act() {
+                    // Without quorum, in Observe mode, or in maintenance mode, the group is only
+                    // observed until the manager may act on it again.
+                } else if self.get_managed() && self.may_start_on(loc) && !self.is_removed() {
+                    self.start_resources(cluster, agent, loc).await?;
+                } else {
+                    // Nothing is waiting to be started here any more:
+                    for res in self.resources() {
+                        res.unblock();
+                    }
+                    if !self.root.is_running() {
+                        return Ok(false);
                     }
                 }
-                ResourceStatus::RunningOnHome | ResourceStatus::RunningOnAway => {}
-                other => {
-                    warn!("resource status was unexpected: {other:?}");
-                    return Err(ManagementError::Configuration);
-                }
-            };
-            tokio::time::sleep(tokio::time::Duration::from_millis(self.args.sleep_time)).await;
-        }
+            }
+            ResourceStatus::RunningOnHome | ResourceStatus::RunningOnAway => {}
+            other => {
+                warn!("resource status was unexpected: {other:?}");
+                return Err(ManagementError::Configuration);
+            }
+        };
+        Ok(true)
     }
 
     /// Observe some resources.
@@ -127,20 +332,46 @@ impl ResourceGroup {
     /// will exit with Ok(()) if the entire resource group has stopped.
     pub async fn observe_loop(
         &self,
-        client: &ocf_resource_agent::Client,
+        cluster: &Cluster,
+        agent: &dyn ResourceAgent,
         exit_if_resource_stopped: bool,
         loc: Location,
     ) -> Result<(), ManagementError> {
         loop {
-            self.update_resources(client, loc).await?;
+            if self.is_removed() {
+                return Ok(());
+            }
+            let activity = shutdown::begin_activity().await;
+            let pass = self.iterations.fetch_add(1, Ordering::Relaxed) + 1;
+            let span = tracing::trace_span!("observe_pass", group = %self.id(), pass);
+            let mut profile = cluster
+                .profiler()
+                .map(|profiler| profiler.begin_pass(self.id(), pass));
+            self.update_resources(agent, loc, profile.as_mut())
+                .instrument(span)
+                .await?;
             if exit_if_resource_stopped && !self.resources().any(|res| res.is_running()) {
                 return Ok(());
             }
+            std::mem::drop(profile);
+            std::mem::drop(activity);
 
-            tokio::time::sleep(tokio::time::Duration::from_millis(self.args.sleep_time)).await;
+            self.wait_for_next_pass().await;
         }
     }
 
+    /// Wait out the poll interval, and any jitter, between passes of a management loop.
+    async fn wait_for_next_pass(&self) {
+        *self.last_pass.lock().unwrap() = Some(Instant::now());
+        let delay = self.args.poll_delay();
+        debug!(
+            "Resource group {}: pass {} done, next in {delay:?}",
+            self.id(),
+            self.iterations.load(Ordering::Relaxed)
+        );
+        tokio::time::sleep(delay).await;
+    }
+
     /// Check the statuses of each of the resources in this resource group.
     ///
     /// This function updates the status of each resource (zpool and target) in the resource
@@ -155,19 +386,32 @@ impl ResourceGroup {
     /// - When communication with the remote agent succesfully occurred, but the remote agent
     ///   returned an error status, this function returns an Ok() variant and sets the resource
     ///   status to the appropriate value to indicate the kind of error returned.
+    ///
+    /// With `profile`, the time that the agent takes to check on each resource is recorded in it,
+    /// as is the end of the monitor phase of the pass.
     async fn update_resources(
         &self,
-        client: &ocf_resource_agent::Client,
+        agent: &dyn ResourceAgent,
         loc: Location,
+        profile: Option<&mut PassProfile<'_>>,
     ) -> Result<(), ManagementError> {
-        let futures = self
-            .resources()
-            .map(|r| r.is_running_here(client, loc, true));
+        let timed = profile.is_some();
+        let futures = self.resources().map(|r| async move {
+            let started = timed.then(Instant::now);
+            let result = r.is_running_here(agent, loc, true).await;
+            (r, started.map(|started| started.elapsed()), result)
+        });
 
         let statuses = future::join_all(futures).await;
         let mut res = Ok(());
 
-        for result in statuses {
+        if let Some(profile) = profile {
+            for (r, duration, _) in &statuses {
+                profile.monitored_resource(&r.id, duration.unwrap_or_default());
+            }
+            profile.end_monitoring();
+        }
+        for (_, _, result) in statuses {
             match result {
                 Ok(_) => {}
                 Err(e) => res = Err(e),
@@ -180,18 +424,67 @@ impl ResourceGroup {
     /// Attempt to start the resources in this resource group on the given location.
     async fn start_resources(
         &self,
-        client: &ocf_resource_agent::Client,
+        cluster: &Cluster,
+        agent: &dyn ResourceAgent,
         loc: Location,
     ) -> Result<(), ManagementError> {
-        self.root.start_if_needed_recursive(client, loc).await
+        self.root
+            .start_if_needed_recursive(cluster, agent, loc)
+            .await
+    }
+
+    /// Stop each running resource in this group whose target state is Stopped, after the resources
+    /// which depend on it, in this group or any other.
+    ///
+    /// A failure to stop a resource is recorded in its status, and retried on a later pass, so
+    /// only a failure to reach a remote agent is returned.
+    async fn stop_disabled_resources(
+        &self,
+        cluster: &Cluster,
+        agent: &dyn ResourceAgent,
+    ) -> Result<(), ManagementError> {
+        let mut stopped_any = false;
+        for res in self.resources() {
+            if res.target_state() == TargetState::Started || !res.is_running() {
+                continue;
+            }
+            stopped_any = true;
+
+            let dependents = cluster.dependents_of(res);
+            let mut ids: Vec<String> = dependents.iter().map(|res| res.id.clone()).collect();
+            ids.push(res.id.clone());
+            let _stopping = cluster.begin_stopping(ids);
+
+            warn!("Resource {}: stopping, since it is disabled.", res.id);
+            for dependent in dependents.iter().filter(|dependent| dependent.is_running()) {
+                warn!(
+                    "Resource {}: stopping, since it depends on {}.",
+                    dependent.id, res.id
+                );
+                let loc = dependent.location().unwrap_or(Location::Home);
+                if let Err(reason) = dependent.stop_and_wait(loc).await {
+                    warn!("Resource {}: could not stop: {reason}", dependent.id);
+                }
+            }
+            match res.stop_recursive(cluster, agent).await {
+                Err(ManagementError::Connection) => return Err(ManagementError::Connection),
+                Ok(()) | Err(ManagementError::Configuration) => {}
+            }
+        }
+
+        if stopped_any {
+            self.update_overall_status();
+        }
+        Ok(())
     }
 
     /// Attempt to stop the resources in this resource group.
     pub async fn stop_resources(
         &self,
-        client: &ocf_resource_agent::Client,
+        cluster: &Cluster,
+        agent: &dyn ResourceAgent,
     ) -> Result<(), ManagementError> {
-        self.root.stop_recursive(client).await
+        self.root.stop_recursive(cluster, agent).await
     }
 
     fn get_overall_status(&self) -> ResourceStatus {
@@ -215,9 +508,16 @@ impl ResourceGroup {
     }
 
     pub fn resources(&self) -> ResourceIterator<'_> {
-        ResourceIterator {
-            queue: VecDeque::from([&self.root]),
-        }
+        self.root.subtree()
+    }
+
+    pub fn iterations(&self) -> u64 {
+        self.iterations.load(Ordering::Relaxed)
+    }
+
+    /// When the management or observation loop last finished a pass over this group, if it has.
+    pub fn last_pass(&self) -> Option<Instant> {
+        *self.last_pass.lock().unwrap()
     }
 
     /// Get management status of resource group, to be used in status
@@ -226,25 +526,112 @@ impl ResourceGroup {
         *managed_status
     }
 
-    /// Sets resources group's managed status
-    pub fn set_managed(&self, managed: bool) {
+    /// Sets resources group's managed status. Returns false if it already had that status.
+    ///
+    /// The status is checked and set under one lock, so when several requests race to change it,
+    /// exactly one of them sees the change.
+    pub fn set_managed(&self, managed: bool) -> bool {
         let mut managed_status = self.managed.lock().unwrap();
+        let changed = *managed_status != managed;
         *managed_status = managed;
+        std::mem::drop(managed_status);
+        if changed {
+            self.root
+                .home_node
+                .events()
+                .publish(Event::ResourceManaged {
+                    id: self.id().to_string(),
+                    managed,
+                });
+        }
+        changed
+    }
+
+    /// Mark this resource group as being migrated to `to`. Returns None if a migration is already
+    /// in progress; otherwise, the migration lasts until the returned guard is dropped.
+    pub fn begin_migration(&self, to: Location) -> Option<MigrationGuard<'_>> {
+        let mut migration = self.migration.lock().unwrap();
+        if migration.is_some() {
+            return None;
+        }
+        *migration = Some(to);
+        Some(MigrationGuard { rg: self })
+    }
+
+    pub fn is_migrating(&self) -> bool {
+        self.migration.lock().unwrap().is_some()
+    }
+
+    /// Record how the last migration of this group turned out: `Err` with the reason that it
+    /// failed, or `Ok` once it has succeeded, or its failure has been cleared.
+    pub fn set_migration_result(&self, result: Result<(), &str>) {
+        let failure = result.err().map(str::to_string);
+        let old = std::mem::replace(
+            &mut *self.migration_failure.lock().unwrap(),
+            failure.clone(),
+        );
+        if old == failure {
+            return;
+        }
+
+        for res in self.resources() {
+            res.home_node.events().publish(Event::ResourceStatus {
+                id: res.id.clone(),
+                status: self.reported_status(res),
+            });
+        }
+    }
+
+    /// Get the status of a member of this group as it should be reported to the admin: while the
+    /// group is being migrated, every member is reported as Migrating, and after a migration
+    /// fails, as FailedMigrate.
+    pub fn reported_status(&self, res: &Resource) -> ResourceStatus {
+        if self.is_migrating() {
+            ResourceStatus::Migrating
+        } else if let Some(failed) = res.failed_status() {
+            failed
+        } else if let Some(reason) = self.migration_failure.lock().unwrap().clone() {
+            ResourceStatus::FailedMigrate(reason)
+        } else {
+            res.get_status()
+        }
+    }
+
+    /// Move this resource group from `from` to `to`, by stopping each of its resources, dependents
+    /// first, and then starting them again in dependency order.
+    ///
+    /// This drives the remote agents directly, so it should only be used when no management task is
+    /// responsible for the group.
+    pub async fn move_resources(&self, from: Location, to: Location) -> Result<(), String> {
+        let resources: Vec<&Resource> = self.resources().collect();
+        for res in resources.iter().rev() {
+            res.stop_and_wait(from).await?;
+        }
+        // A resource which should be stopped is not started again, nor are its dependents:
+        let mut skipped: Vec<&str> = Vec::new();
+        for res in resources {
+            if res.target_state() == TargetState::Stopped || skipped.contains(&res.id.as_str()) {
+                skipped.extend(res.subtree().map(|res| res.id.as_str()));
+                continue;
+            }
+            res.start_and_wait(to).await?;
+        }
+        Ok(())
     }
 
-    /// Check if the resource group is running on the system connected via the given Client.
+    /// Check if the resource group is running on the host that `agent` acts on.
     ///
     /// This checks each resource individually for the purpose of updating the status, but it uses
     /// the result of the root resource to determine the "overall" status.
     pub async fn is_running_here(
         &self,
-        client: &ocf_resource_agent::Client,
+        agent: &dyn ResourceAgent,
         loc: Location,
         update_status_if_stopped: bool,
     ) -> Result<bool, ManagementError> {
         let futures = self
             .resources()
-            .map(|r| r.is_running_here(client, loc, update_status_if_stopped));
+            .map(|r| r.is_running_here(agent, loc, update_status_if_stopped));
 
         for result in future::join_all(futures).await {
             match result {
@@ -261,6 +648,37 @@ impl ResourceGroup {
     }
 }
 
+/// The constraints on where a resource group may run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    /// Whether the group should be moved back to its home node once that node is healthy again
+    /// after a failover.
+    pub prefer_home: bool,
+
+    /// Whether the group may be started on its failover node.
+    pub allow_away: bool,
+}
+
+impl Default for Placement {
+    fn default() -> Self {
+        Self {
+            prefer_home: false,
+            allow_away: true,
+        }
+    }
+}
+
+/// While a MigrationGuard exists, its resource group is reported as Migrating.
+pub struct MigrationGuard<'a> {
+    rg: &'a ResourceGroup,
+}
+
+impl Drop for MigrationGuard<'_> {
+    fn drop(&mut self) {
+        *self.rg.migration.lock().unwrap() = None;
+    }
+}
+
 /// This iterator visits all of the Resources in a dependency tree in breadth-first order.
 pub struct ResourceIterator<'a> {
     queue: VecDeque<&'a Resource>,
@@ -290,7 +708,9 @@ pub struct Resource {
     /// The parameters of the resource as key-value pairs. For example, for Lustre, this would
     /// be something like:
     ///     [("mountpoint": "/mnt/ost1"), ("target": "ost1")]
-    pub parameters: HashMap<String, String>,
+    ///
+    /// These can be changed while the manager is running, so they are behind a lock.
+    parameters: Mutex<HashMap<String, String>>,
 
     /// The resources which depend on this resource.
     /// For example, Lustre targets depend on their containing zpool, so the Zpool resource's
@@ -300,14 +720,69 @@ pub struct Resource {
     /// Unique identifier for the resource.
     pub id: String,
 
+    /// The IDs of resources, possibly in other resource groups, which must be running before this
+    /// one is started. These can be changed by reloading the config, so they are behind a lock.
+    depends_on: Mutex<Vec<String>>,
+
     // TODO: better privacy here
     pub status: Mutex<ResourceStatus>,
     pub home_node: Arc<Host>,
     pub failover_node: Option<Arc<Host>>,
 
+    /// The start or stop of this resource which last failed, if it has not succeeded since.
+    retry: Mutex<Option<Retry>>,
+
+    /// Whether the manager should keep this resource running or stopped, while it is managed.
+    target_state: Mutex<TargetState>,
+
+    /// How long the resource agent may take to start or stop this resource. These can be changed
+    /// by reloading the config, so they are behind a lock.
+    timeouts: Mutex<Timeouts>,
+
     pub args: manager::Cli,
 }
 
+/// How many seconds a resource agent may take to start a resource, unless the config says
+/// otherwise.
+pub const DEFAULT_START_TIMEOUT: u64 = 300;
+
+/// How many seconds a resource agent may take to stop a resource, unless the config says
+/// otherwise.
+pub const DEFAULT_STOP_TIMEOUT: u64 = 300;
+
+/// How long the manager waits for a resource agent to start or stop a resource before giving up
+/// on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    pub start: Duration,
+    pub stop: Duration,
+}
+
+impl Timeouts {
+    pub fn from_config(res: &crate::config::Resource) -> Self {
+        Self {
+            start: Duration::from_secs(res.start_timeout.unwrap_or(DEFAULT_START_TIMEOUT)),
+            stop: Duration::from_secs(res.stop_timeout.unwrap_or(DEFAULT_STOP_TIMEOUT)),
+        }
+    }
+}
+
+/// A start or stop of a resource which failed, and when the manager may try it again.
+#[derive(Debug, Clone)]
+pub struct Retry {
+    pub action: ResourceAction,
+
+    /// How many times in a row the action failed.
+    pub failures: u32,
+
+    /// Why the action failed the last time.
+    pub reason: String,
+
+    /// When the action may be tried again. Once it has failed too many times in a row, this is None:
+    /// it is not tried again until an admin clears the failure.
+    pub next_attempt: Option<SystemTime>,
+}
+
 impl Resource {
     pub fn from_config(
         res: crate::config::Resource,
@@ -317,35 +792,169 @@ impl Resource {
         id: String,
         args: manager::Cli,
     ) -> Self {
+        let timeouts = Timeouts::from_config(&res);
         Resource {
             kind: res.kind,
-            parameters: res.parameters,
+            parameters: Mutex::new(res.parameters),
+            depends_on: Mutex::new(res.depends_on),
             dependents,
             status: Mutex::new(ResourceStatus::Unknown(
                 "Manager is starting up".to_string(),
             )),
             home_node,
             failover_node,
+            retry: Mutex::new(None),
+            target_state: Mutex::new(res.target_state),
+            timeouts: Mutex::new(timeouts),
             id,
             args,
         }
     }
 
-    /// This method checks if the resource is running on the system connected via the given Client.
+    pub fn timeouts(&self) -> Timeouts {
+        *self.timeouts.lock().unwrap()
+    }
+
+    /// Change how long the resource agent may take to start or stop this resource. A start or stop
+    /// which is already running keeps the timeout that it began with.
+    pub fn set_timeouts(&self, timeouts: Timeouts) {
+        *self.timeouts.lock().unwrap() = timeouts;
+    }
+
+    pub fn target_state(&self) -> TargetState {
+        *self.target_state.lock().unwrap()
+    }
+
+    /// Set whether the manager should keep this resource running or stopped. Returns false if it
+    /// already had that target state.
+    pub fn set_target_state(&self, target: TargetState) -> bool {
+        let mut target_state = self.target_state.lock().unwrap();
+        let changed = *target_state != target;
+        *target_state = target;
+        std::mem::drop(target_state);
+        if changed {
+            self.home_node.events().publish(Event::ResourceTargetState {
+                id: self.id.clone(),
+                target,
+            });
+        }
+        changed
+    }
+
+    /// The failed start or stop of this resource that is waiting to be tried again, if any.
+    pub fn retry(&self) -> Option<Retry> {
+        self.retry.lock().unwrap().clone()
+    }
+
+    /// Record that `action` on this resource failed for `reason`. Returns how long to wait before
+    /// trying it again, or None if it failed too many times in a row to be tried again. Without
+    /// `may_retry`, the manager gives up on it right away.
+    fn record_failure(
+        &self,
+        action: ResourceAction,
+        reason: String,
+        may_retry: bool,
+    ) -> Option<Duration> {
+        let mut retry = self.retry.lock().unwrap();
+        let failures = match &*retry {
+            Some(retry) if retry.action == action => retry.failures + 1,
+            _ => 1,
+        };
+        let delay = (may_retry && failures < self.args.retry_attempts())
+            .then(|| self.args.retry_delay(failures));
+        *retry = Some(Retry {
+            action,
+            failures,
+            reason: reason.clone(),
+            next_attempt: delay.map(|delay| SystemTime::now() + delay),
+        });
+        std::mem::drop(retry);
+
+        match delay {
+            Some(delay) => warn!(
+                "Resource {}: {action} failed ({failures} of {} attempts): {reason}. Trying again in \
+                 {delay:?}.",
+                self.id,
+                self.args.retry_attempts()
+            ),
+            None => {
+                if may_retry {
+                    error!(
+                        "Resource {}: {action} failed {failures} times in a row, so it will not be \
+                         tried again until the failure is cleared: {reason}",
+                        self.id
+                    );
+                } else {
+                    error!(
+                        "Resource {}: {action} will not be tried again until the failure is \
+                         cleared: {reason}",
+                        self.id
+                    );
+                }
+                if let Some(status) = self.failed_status() {
+                    self.home_node.events().publish(Event::ResourceStatus {
+                        id: self.id.clone(),
+                        status,
+                    });
+                }
+            }
+        }
+        delay
+    }
+
+    /// Whether the manager may try `action` on this resource now: not while it waits to retry a
+    /// failure, nor once it has given up.
+    fn may_attempt(&self, action: ResourceAction) -> bool {
+        match &*self.retry.lock().unwrap() {
+            Some(retry) if retry.action == action => retry
+                .next_attempt
+                .is_some_and(|next| SystemTime::now() >= next),
+            _ => true,
+        }
+    }
+
+    /// Forget about any failed start or stop of this resource, so that the manager tries it again
+    /// right away.
+    pub fn clear_failures(&self) {
+        let cleared = self.retry.lock().unwrap().take();
+        if cleared.is_some_and(|retry| retry.next_attempt.is_none()) {
+            warn!("Resource {}: failure cleared.", self.id);
+            self.home_node.events().publish(Event::ResourceStatus {
+                id: self.id.clone(),
+                status: self.get_status(),
+            });
+        }
+    }
+
+    /// FailedStart or FailedStop, once the manager has given up on starting or stopping this
+    /// resource.
+    pub fn failed_status(&self) -> Option<ResourceStatus> {
+        let retry = self.retry()?;
+        if retry.next_attempt.is_some() {
+            return None;
+        }
+        Some(match retry.action {
+            ResourceAction::Start => ResourceStatus::FailedStart(retry.reason),
+            ResourceAction::Stop => ResourceStatus::FailedStop(retry.reason),
+        })
+    }
+
+    /// This method checks if the resource is running on the host that `agent` acts on.
     pub async fn is_running_here(
         &self,
-        client: &ocf_resource_agent::Client,
+        agent: &dyn ResourceAgent,
         loc: Location,
         update_status_if_stopped: bool,
     ) -> Result<bool, ManagementError> {
-        match self.monitor_client(client).await {
+        match agent.monitor(self).await {
             Ok(AgentReply::Success(ocf::Status::Success)) => {
                 self.set_running_on_loc(loc);
                 Ok(true)
             }
             Ok(AgentReply::Success(ocf::Status::Error(kind, reason))) => match kind {
                 ocf::OcfError::ErrNotRunning => {
-                    if update_status_if_stopped {
+                    // A blocked resource is also stopped; it stays blocked until it is started:
+                    if update_status_if_stopped && !self.is_blocked() {
                         self.set_status(ResourceStatus::Stopped);
                     }
                     Ok(false)
@@ -368,13 +977,31 @@ impl Resource {
 
     /// Recursively start a resource as well as all of its dependents.
     /// Updates the status of each resource based on the outcome of the start attempt.
+    ///
+    /// A resource whose dependencies are not all running is marked Blocked instead, and neither it
+    /// nor its dependents are started.
     async fn start_if_needed_recursive(
         &self,
-        client: &ocf_resource_agent::Client,
+        cluster: &Cluster,
+        agent: &dyn ResourceAgent,
         loc: Location,
     ) -> Result<(), ManagementError> {
-        // If this resource is already running, don't bother doing anything:
+        // If this resource is already running, don't bother doing anything. A resource which should
+        // be stopped is left alone, and so are its dependents, which can not run without it:
         if !self.is_running() {
+            if self.target_state() == TargetState::Stopped {
+                return Ok(());
+            }
+            if let Some(dep) = cluster.unmet_dependency(self) {
+                self.set_status(ResourceStatus::Blocked(dep));
+                return Ok(());
+            }
+            // A resource which failed to start is not tried again until its backoff is over, and
+            // its dependents wait along with it:
+            if !self.may_attempt(ResourceAction::Start) {
+                return Ok(());
+            }
+
             warn!(
                 "Attempting to start resource {} on {}.",
                 self.id,
@@ -383,24 +1010,36 @@ impl Resource {
                     Location::Away => "its failover node",
                 }
             );
-            match self.start_client(client).await {
+            // A start which takes too long is not retried, since the agent may well hang again:
+            let Some(reply) = self
+                .run_with_timeout(cluster, agent, ResourceAction::Start)
+                .await
+            else {
+                return Ok(());
+            };
+            match reply {
                 // Agent replies that the resource was started succesfully.
-                Ok(AgentReply::Success(ocf::Status::Success)) => self.set_running_on_loc(loc),
-                // Agent replies that it could not start the resource. This is likely due to a
-                // misconfiguration or other issue that requires admin intervention, so return an
-                // error.
+                Ok(AgentReply::Success(ocf::Status::Success)) => {
+                    self.clear_failures();
+                    self.set_running_on_loc(loc);
+                }
+                // Agent replies that it could not start the resource. The failure may be
+                // transient, so it is tried again after a backoff, up to a limit; past that, the
+                // resource is left alone until an admin intervenes.
                 Ok(AgentReply::Success(ocf::Status::Error(_, reason))) => {
-                    self.set_status(ResourceStatus::Error(reason));
-                    return Err(ManagementError::Configuration);
+                    self.set_status(ResourceStatus::Error(reason.clone()));
+                    self.record_failure(ResourceAction::Start, reason, true);
+                    return Ok(());
                 }
                 // Agent replies that it could not run the resource management script. This is
-                // likely due to a misconfiguration like the script not being installed, so return
-                // an error.
+                // likely due to a misconfiguration like the script not being installed, but it is
+                // retried the same way.
                 Ok(AgentReply::Error(reason)) => {
                     error!("Warning: Remote agent returned error {reason} when attempting to start resource {}.",
                         self.id);
-                    self.set_status(ResourceStatus::Error(reason));
-                    return Err(ManagementError::Configuration);
+                    self.set_status(ResourceStatus::Error(reason.clone()));
+                    self.record_failure(ResourceAction::Start, reason, true);
+                    return Ok(());
                 }
                 // An RPC error occurred, for example, because the connection timed out or was
                 // reset. Management cannot proceed in a such a case, so return an error.
@@ -419,76 +1058,121 @@ impl Resource {
         let futures = self
             .dependents
             .iter()
-            .map(|r| r.start_if_needed_recursive(client, loc));
+            .map(|r| r.start_if_needed_recursive(cluster, agent, loc));
 
         get_worst_error(future::join_all(futures).await.into_iter())
     }
 
     async fn stop_recursive(
         &self,
-        client: &ocf_resource_agent::Client,
+        cluster: &Cluster,
+        agent: &dyn ResourceAgent,
     ) -> Result<(), ManagementError> {
-        let results = self.dependents.iter().map(|r| r.stop_recursive(client));
+        let results = self
+            .dependents
+            .iter()
+            .map(|r| r.stop_recursive(cluster, agent));
 
         get_worst_error(future::join_all(results).await.into_iter())?;
 
-        match self.stop_client(client).await {
-            Ok(AgentReply::Success(ocf::Status::Success)) => {
-                self.set_status(ResourceStatus::Stopped);
-                Ok(())
-            }
-            // Agent replies that it could not stop the resource. This is likely due to a
-            // misconfiguration or other issue that requires admin intervention, so return an
-            // error.
-            Ok(AgentReply::Success(ocf::Status::Error(_, reason))) => {
-                self.set_status(ResourceStatus::Error(reason));
-                Err(ManagementError::Configuration)
-            }
-            // Agent replies that it could not run the resource management script. This is
-            // likely due to a misconfiguration like the script not being installed, so return
-            // an error.
-            Ok(AgentReply::Error(reason)) => {
-                error!("Warning: Remote agent returned error {reason} when attempting to stop resource {}.",
-                    self.id);
-                self.set_status(ResourceStatus::Error(reason));
-                Err(ManagementError::Configuration)
-            }
-            // An RPC error occurred, for example, because the connection timed out or was
-            // reset. Management cannot proceed in a such a case, so return an error.
-            Err(e) => {
-                error!(
-                    "Error: '{e:?}' when attempting to start resource '{}'.",
-                    self.id
-                );
-                self.set_status(ResourceStatus::Unknown(format!("{e}")));
-                Err(e.into())
-            }
+        // A resource which the manager gave up on stopping is left alone:
+        if self.failed_status().is_some() {
+            return Err(ManagementError::Configuration);
         }
-    }
 
-    /// Perform a monitor RPC for this resource given a client.
-    pub async fn monitor_client(
-        &self,
-        client: &ocf_resource_agent::Client,
-    ) -> Result<AgentReply, capnp::Error> {
-        remote_ocf_operation_given_client(self, client, ocf_resource_agent::Operation::Monitor)
-            .await
+        // A failed stop is tried again after a backoff, up to a limit, after which the resource is
+        // left alone until an admin intervenes:
+        loop {
+            let Some(reply) = self
+                .run_with_timeout(cluster, agent, ResourceAction::Stop)
+                .await
+            else {
+                return Err(ManagementError::Configuration);
+            };
+            let reason = match reply {
+                Ok(AgentReply::Success(ocf::Status::Success)) => {
+                    self.clear_failures();
+                    self.set_status(ResourceStatus::Stopped);
+                    return Ok(());
+                }
+                // Agent replies that it could not stop the resource.
+                Ok(AgentReply::Success(ocf::Status::Error(_, reason))) => reason,
+                // Agent replies that it could not run the resource management script. This is
+                // likely due to a misconfiguration like the script not being installed.
+                Ok(AgentReply::Error(reason)) => {
+                    error!("Warning: Remote agent returned error {reason} when attempting to stop resource {}.",
+                        self.id);
+                    reason
+                }
+                // An RPC error occurred, for example, because the connection timed out or was
+                // reset. Management cannot proceed in a such a case, so return an error.
+                Err(e) => {
+                    error!(
+                        "Error: '{e:?}' when attempting to start resource '{}'.",
+                        self.id
+                    );
+                    self.set_status(ResourceStatus::Unknown(format!("{e}")));
+                    return Err(e.into());
+                }
+            };
+            self.set_status(ResourceStatus::Error(reason.clone()));
+            match self.record_failure(ResourceAction::Stop, reason, true) {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => return Err(ManagementError::Configuration),
+            }
+        }
     }
 
-    /// Perform a start RPC for this resource given a client.
-    pub async fn start_client(
+    /// Start or stop this resource through `agent`, once the cluster allows another action to
+    /// run, giving up once it takes longer than its timeout, or once an admin cancels it with `halo
+    /// cancel`. Returns None if it was given up on, after recording that the manager did.
+    async fn run_with_timeout(
         &self,
-        client: &ocf_resource_agent::Client,
-    ) -> Result<AgentReply, capnp::Error> {
-        remote_ocf_operation_given_client(self, client, ocf_resource_agent::Operation::Start).await
-    }
+        cluster: &Cluster,
+        agent: &dyn ResourceAgent,
+        action: ResourceAction,
+    ) -> Option<Result<AgentReply, capnp::Error>> {
+        let running = cluster.begin_action(vec![self.id.clone()]);
+        let timeouts = self.timeouts();
+        let timeout = match action {
+            ResourceAction::Start => timeouts.start,
+            ResourceAction::Stop => timeouts.stop,
+        };
+        let run = async {
+            // The time spent waiting for a turn does not count against the timeout:
+            let _permit = cluster.action_permit().await;
+            let started = Instant::now();
+            let result = match action {
+                ResourceAction::Start => tokio::time::timeout(timeout, agent.start(self)).await,
+                ResourceAction::Stop => tokio::time::timeout(timeout, agent.stop(self)).await,
+            };
+            result.map_err(|_| started.elapsed())
+        };
 
-    /// Perform a stop RPC for this resource given a client.
-    pub async fn stop_client(
-        &self,
-        client: &ocf_resource_agent::Client,
-    ) -> Result<AgentReply, capnp::Error> {
-        remote_ocf_operation_given_client(self, client, ocf_resource_agent::Operation::Stop).await
+        let reason = tokio::select! {
+            result = run => match result {
+                Ok(reply) => return Some(reply),
+                Err(elapsed) => {
+                    error!(
+                        "Resource {}: {action} timed out after {elapsed:.1?}; the resource agent \
+                         may still be running.",
+                        self.id
+                    );
+                    format!("{action} timed out after {}s", timeout.as_secs())
+                }
+            },
+            _ = running.token().cancelled() => {
+                warn!(
+                    "Resource {}: {action} canceled with 'halo cancel'; the resource agent may \
+                     still be running.",
+                    self.id
+                );
+                format!("{action} canceled with 'halo cancel'")
+            }
+        };
+        self.set_status(ResourceStatus::Error(reason.clone()));
+        self.record_failure(action, reason, false);
+        None
     }
 
     /// Perform a monitor RPC for this resource.
@@ -511,14 +1195,78 @@ impl Resource {
 
     /// Perform a stop RPC for this resource.
     pub async fn stop(&self) -> Result<AgentReply, AgentError> {
+        self.stop_on(Location::Home).await
+    }
+
+    /// Perform a stop RPC for this resource on the given location.
+    pub async fn stop_on(&self, loc: Location) -> Result<AgentReply, AgentError> {
         tokio::task::LocalSet::new()
             .run_until(async {
-                remote_ocf_operation(self, Location::Home, ocf_resource_agent::Operation::Stop)
-                    .await
+                remote_ocf_operation(self, loc, ocf_resource_agent::Operation::Stop).await
             })
             .await
     }
 
+    /// Start this resource on `loc`, then wait until the remote agent reports that it is running.
+    ///
+    /// The status of the resource is updated along the way. If it could not be started, the reason
+    /// is returned.
+    pub async fn start_and_wait(&self, loc: Location) -> Result<(), String> {
+        self.check_reply(self.start(loc).await)?;
+        self.wait_until_running(loc, true).await
+    }
+
+    /// Stop this resource on `loc`, then wait until the remote agent reports that it is stopped.
+    ///
+    /// The status of the resource is updated along the way. If it could not be stopped, the reason
+    /// is returned.
+    pub async fn stop_and_wait(&self, loc: Location) -> Result<(), String> {
+        self.check_reply(self.stop_on(loc).await)?;
+        self.wait_until_running(loc, false).await
+    }
+
+    /// Poll the remote agent until this resource is running (or stopped, if `running` is false) on
+    /// `loc`.
+    async fn wait_until_running(&self, loc: Location, running: bool) -> Result<(), String> {
+        loop {
+            match self.monitor(loc).await {
+                Ok(AgentReply::Success(ocf::Status::Success)) if running => {
+                    self.set_running_on_loc(loc);
+                    return Ok(());
+                }
+                Ok(AgentReply::Success(ocf::Status::Error(ocf::OcfError::ErrNotRunning, _)))
+                    if !running =>
+                {
+                    self.set_status(ResourceStatus::Stopped);
+                    return Ok(());
+                }
+                Ok(AgentReply::Success(ocf::Status::Success))
+                | Ok(AgentReply::Success(ocf::Status::Error(ocf::OcfError::ErrNotRunning, _))) => {}
+                other => return self.check_reply(other),
+            }
+
+            tokio::time::sleep(self.args.poll_delay()).await;
+        }
+    }
+
+    /// Given the reply to an operation on this resource, update the status of the resource if the
+    /// operation failed, and return the reason that it failed.
+    fn check_reply(&self, reply: Result<AgentReply, AgentError>) -> Result<(), String> {
+        match reply {
+            Ok(AgentReply::Success(ocf::Status::Success)) => Ok(()),
+            Ok(AgentReply::Success(ocf::Status::Error(_, reason)))
+            | Ok(AgentReply::Error(reason)) => {
+                self.set_status(ResourceStatus::Error(reason.clone()));
+                Err(reason)
+            }
+            Err(e) => {
+                let reason = format!("{e:?}");
+                self.set_status(ResourceStatus::Unknown(reason.clone()));
+                Err(reason)
+            }
+        }
+    }
+
     pub fn get_status(&self) -> ResourceStatus {
         self.status.lock().unwrap().clone()
     }
@@ -532,17 +1280,49 @@ impl Resource {
             warn!(
                 "Updating status of resource {} from {:?} to {:?}",
                 self.id, old_status_copy, status
-            )
+            );
+            self.home_node.events().publish(Event::ResourceStatus {
+                id: self.id.clone(),
+                status,
+            });
         }
     }
 
-    fn is_running(&self) -> bool {
+    pub fn is_blocked(&self) -> bool {
+        matches!(self.get_status(), ResourceStatus::Blocked(_))
+    }
+
+    /// A blocked resource which is no longer going to be started is just stopped.
+    pub fn unblock(&self) {
+        if self.is_blocked() {
+            self.set_status(ResourceStatus::Stopped);
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
         matches!(
             self.get_status(),
             ResourceStatus::RunningOnHome | ResourceStatus::RunningOnAway
         )
     }
 
+    /// The location that this resource is currently running on, if it is running.
+    pub fn location(&self) -> Option<Location> {
+        match self.get_status() {
+            ResourceStatus::RunningOnHome => Some(Location::Home),
+            ResourceStatus::RunningOnAway => Some(Location::Away),
+            _ => None,
+        }
+    }
+
+    /// The host for the given location of this resource, if it has one.
+    pub fn node(&self, loc: Location) -> Option<&Arc<Host>> {
+        match loc {
+            Location::Home => Some(&self.home_node),
+            Location::Away => self.failover_node.as_ref(),
+        }
+    }
+
     pub fn set_running_on_loc(&self, loc: Location) {
         match loc {
             Location::Home => self.set_status(ResourceStatus::RunningOnHome),
@@ -550,9 +1330,62 @@ impl Resource {
         };
     }
 
+    /// A copy of this resource's parameters.
+    pub fn parameters(&self) -> HashMap<String, String> {
+        self.parameters.lock().unwrap().clone()
+    }
+
+    pub fn parameter(&self, key: &str) -> Option<String> {
+        self.parameters.lock().unwrap().get(key).cloned()
+    }
+
+    /// Replace all of this resource's parameters. Like update_parameters(), a running resource
+    /// keeps running with its old parameters until it is restarted.
+    pub fn set_parameters(&self, parameters: HashMap<String, String>) {
+        *self.parameters.lock().unwrap() = parameters;
+    }
+
+    /// The IDs of the resources which must be running before this one is started.
+    pub fn depends_on(&self) -> Vec<String> {
+        self.depends_on.lock().unwrap().clone()
+    }
+
+    pub fn set_depends_on(&self, depends_on: Vec<String>) {
+        *self.depends_on.lock().unwrap() = depends_on;
+    }
+
+    /// Set each of the parameters in `updates`, keeping the others, and return the resulting
+    /// parameters. A running resource keeps running with its old parameters until it is restarted.
+    pub fn update_parameters(&self, updates: HashMap<String, String>) -> HashMap<String, String> {
+        let mut parameters = self.parameters.lock().unwrap();
+        parameters.extend(updates);
+        parameters.clone()
+    }
+
+    /// This resource, followed by the resources that depend on it, in dependency order.
+    pub fn subtree(&self) -> ResourceIterator<'_> {
+        ResourceIterator {
+            queue: VecDeque::from([self]),
+        }
+    }
+
+    /// Restart this resource where it is running on `loc`, along with the resources that depend
+    /// on it: they are stopped, dependents first, and then started again in dependency order.
+    pub async fn restart(&self, loc: Location) -> Result<(), String> {
+        let resources: Vec<&Resource> = self.subtree().collect();
+        for res in resources.iter().rev() {
+            res.stop_and_wait(loc).await?;
+        }
+        for res in resources {
+            res.start_and_wait(loc).await?;
+        }
+        Ok(())
+    }
+
     /// Return a string representation of this resource's parameters in a predictable way.
     pub fn params_string(&self) -> String {
-        let mut params: Vec<(&String, &String)> = self.parameters.iter().collect();
+        let parameters = self.parameters();
+        let mut params: Vec<(&String, &String)> = parameters.iter().collect();
         params.sort();
         let mut output: String = String::from("{");
         params.iter().enumerate().for_each(|(i, (k, v))| {
@@ -592,9 +1425,31 @@ pub enum ResourceStatus {
     /// state that requires admin intervention.
     Error(String),
 
+    /// The manager gave up on starting the resource for the given reason, after it failed too many
+    /// times in a row. Like Migrating, this is only ever reported; it is not observed.
+    FailedStart(String),
+
+    /// The manager gave up on stopping the resource for the given reason, after it failed too many
+    /// times in a row. This is only ever reported, too.
+    FailedStop(String),
+
+    /// The last migration of the resource's group that an admin requested failed for the given
+    /// reason, so the group may be left partly on each node. This is only ever reported, until the
+    /// group is migrated successfully or the failure is cleared.
+    FailedMigrate(String),
+
+    /// The resource is not running anywhere, and will not be started until the resource with the
+    /// given ID, which it depends on, is running.
+    Blocked(String),
+
     /// The resource is not running anywhere.
     Stopped,
 
+    /// The resource group is being moved between its home and failover nodes at the request of the
+    /// admin. This is never observed from a remote agent; it is only reported while a migration is
+    /// in progress.
+    Migrating,
+
     /// The resource is running on its failover node.
     RunningOnAway,
 
@@ -602,7 +1457,105 @@ pub enum ResourceStatus {
     RunningOnHome,
 }
 
+/// ResourceStatuses are displayed by the names that users see, e.g. in `halo status`.
+impl fmt::Display for ResourceStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ResourceStatus::Unknown(_) => "Unknown",
+                ResourceStatus::Error(_) => "Error",
+                ResourceStatus::FailedStart(_) => "FailedStart",
+                ResourceStatus::FailedStop(_) => "FailedStop",
+                ResourceStatus::FailedMigrate(_) => "FailedMigrate",
+                ResourceStatus::Blocked(_) => "Blocked",
+                ResourceStatus::Stopped => "Stopped",
+                ResourceStatus::Migrating => "Migrating",
+                ResourceStatus::RunningOnAway => "Running (Failed Over)",
+                ResourceStatus::RunningOnHome => "Running",
+            }
+        )
+    }
+}
+
 impl ResourceStatus {
+    /// The name of each kind of status, as given by name(), from "worst" to "best".
+    pub const NAMES: [&'static str; 10] = [
+        "Unknown",
+        "Error",
+        "FailedStart",
+        "FailedStop",
+        "FailedMigrate",
+        "Blocked",
+        "Stopped",
+        "Migrating",
+        "RunningOnAway",
+        "RunningOnHome",
+    ];
+
+    /// The name of this kind of status, without its reason, e.g. "RunningOnAway". Unlike the
+    /// Display form, which is for people to read, this is meant for metrics and filters.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ResourceStatus::Unknown(_) => "Unknown",
+            ResourceStatus::Error(_) => "Error",
+            ResourceStatus::FailedStart(_) => "FailedStart",
+            ResourceStatus::FailedStop(_) => "FailedStop",
+            ResourceStatus::FailedMigrate(_) => "FailedMigrate",
+            ResourceStatus::Blocked(_) => "Blocked",
+            ResourceStatus::Stopped => "Stopped",
+            ResourceStatus::Migrating => "Migrating",
+            ResourceStatus::RunningOnAway => "RunningOnAway",
+            ResourceStatus::RunningOnHome => "RunningOnHome",
+        }
+    }
+
+    /// The status of the kind named `name`, as given by name(), with an empty reason if it has one.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "Unknown" => ResourceStatus::Unknown(String::new()),
+            "Error" => ResourceStatus::Error(String::new()),
+            "FailedStart" => ResourceStatus::FailedStart(String::new()),
+            "FailedStop" => ResourceStatus::FailedStop(String::new()),
+            "FailedMigrate" => ResourceStatus::FailedMigrate(String::new()),
+            "Blocked" => ResourceStatus::Blocked(String::new()),
+            "Stopped" => ResourceStatus::Stopped,
+            "Migrating" => ResourceStatus::Migrating,
+            "RunningOnAway" => ResourceStatus::RunningOnAway,
+            "RunningOnHome" => ResourceStatus::RunningOnHome,
+            _ => return None,
+        })
+    }
+
+    /// The comment that is shown to users along with a status which has a reason.
+    pub fn comment(&self) -> Option<String> {
+        match self {
+            ResourceStatus::Unknown(reason)
+            | ResourceStatus::Error(reason)
+            | ResourceStatus::FailedStart(reason)
+            | ResourceStatus::FailedStop(reason)
+            | ResourceStatus::FailedMigrate(reason) => Some(reason.clone()),
+            ResourceStatus::Blocked(dep) => Some(format!("waiting for {dep}")),
+            ResourceStatus::Stopped
+            | ResourceStatus::Migrating
+            | ResourceStatus::RunningOnAway
+            | ResourceStatus::RunningOnHome => None,
+        }
+    }
+
+    /// Whether the status is one of the failures that needs an admin to look into it.
+    pub fn is_failed(&self) -> bool {
+        matches!(
+            self,
+            ResourceStatus::Unknown(_)
+                | ResourceStatus::Error(_)
+                | ResourceStatus::FailedStart(_)
+                | ResourceStatus::FailedStop(_)
+                | ResourceStatus::FailedMigrate(_)
+        )
+    }
+
     /// Given an iterator over ResourceStatuses, determine the "worst" one. This is used to assign
     /// an overall status to a group of resources based on the worst member status.
     ///
@@ -615,6 +1568,32 @@ impl ResourceStatus {
     }
 }
 
+/// Whether a resource should be running. The manager only drives a resource towards its target
+/// state while the resource is managed, so a managed resource with a target state of Stopped is
+/// kept stopped, but still monitored.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetState {
+    #[default]
+    Started,
+    Stopped,
+}
+
+impl TargetState {
+    pub fn is_started(&self) -> bool {
+        *self == TargetState::Started
+    }
+}
+
+impl fmt::Display for TargetState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargetState::Started => write!(f, "started"),
+            TargetState::Stopped => write!(f, "stopped"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Location {
     Home,
@@ -623,7 +1602,121 @@ pub enum Location {
 
 #[cfg(test)]
 mod tests {
-    use super::ResourceStatus;
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::agent::AgentResult;
+
+    /// An agent which keeps track of which resources are running, without running anything.
+    #[derive(Default)]
+    struct MockAgent {
+        running: Mutex<HashSet<String>>,
+
+        /// A resource which fails to start.
+        broken: Option<&'static str>,
+
+        /// Whether the agent acts as if its host can not be reached.
+        disconnected: bool,
+    }
+
+    impl MockAgent {
+        fn reply(&self, reply: impl FnOnce() -> ocf::Status) -> AgentResult<'_> {
+            let result = match self.disconnected {
+                true => Err(capnp::Error::disconnected("connection reset".to_string())),
+                false => Ok(AgentReply::Success(reply())),
+            };
+            Box::pin(future::ready(result))
+        }
+    }
+
+    impl ResourceAgent for MockAgent {
+        fn monitor<'a>(&'a self, res: &'a Resource) -> AgentResult<'a> {
+            self.reply(|| match self.running.lock().unwrap().contains(&res.id) {
+                true => ocf::Status::Success,
+                false => ocf::Status::Error(ocf::OcfError::ErrNotRunning, "stopped".to_string()),
+            })
+        }
+
+        fn start<'a>(&'a self, res: &'a Resource) -> AgentResult<'a> {
+            self.reply(|| {
+                if self.broken == Some(res.id.as_str()) {
+                    return ocf::Status::Error(ocf::OcfError::ErrGeneric, "mount failed".into());
+                }
+                self.running.lock().unwrap().insert(res.id.clone());
+                ocf::Status::Success
+            })
+        }
+
+        fn stop<'a>(&'a self, res: &'a Resource) -> AgentResult<'a> {
+            self.reply(|| {
+                self.running.lock().unwrap().remove(&res.id);
+                ocf::Status::Success
+            })
+        }
+    }
+
+    #[test]
+    fn test_manage_pass() {
+        let args = manager::Cli {
+            config: vec![crate::test_env::test_path("simple.yaml")],
+            manage_resources: true,
+            retry_attempts: Some(1),
+            ..Default::default()
+        };
+        let cluster = Cluster::new(args).unwrap();
+        let rg = cluster.get_resource_group("test_zpool");
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let statuses = || -> std::collections::BTreeMap<String, &str> {
+            rg.resources()
+                .map(|res| {
+                    let status = res.failed_status().unwrap_or_else(|| res.get_status());
+                    (res.id.clone(), status.name())
+                })
+                .collect()
+        };
+
+        // Every resource is started, the zpool before the targets on it, except the one which
+        // fails, which the manager gives up on:
+        let agent = MockAgent {
+            broken: Some("test_ost"),
+            ..Default::default()
+        };
+        let pass = rg.manage_pass(&cluster, &agent, Location::Home, None);
+        assert!(runtime.block_on(pass).unwrap());
+        let expected = [
+            ("test_mdt", "RunningOnHome"),
+            ("test_mgt", "RunningOnHome"),
+            ("test_ost", "FailedStart"),
+            ("test_zpool", "RunningOnHome"),
+        ];
+        assert_eq!(
+            statuses().into_iter().collect::<Vec<_>>(),
+            expected.map(|(id, status)| (id.to_string(), status))
+        );
+
+        // The next pass finds them running, and leaves the failed one alone:
+        let pass = rg.manage_pass(&cluster, &agent, Location::Home, None);
+        assert!(runtime.block_on(pass).unwrap());
+        assert_eq!(statuses()["test_mdt"], "RunningOnHome");
+        assert_eq!(statuses()["test_ost"], "FailedStart");
+
+        // An agent that can not be reached leaves the statuses unknown, and fails the host:
+        let agent = MockAgent {
+            disconnected: true,
+            ..Default::default()
+        };
+        let pass = rg.manage_pass(&cluster, &agent, Location::Home, None);
+        assert!(matches!(
+            runtime.block_on(pass),
+            Err(ManagementError::Connection)
+        ));
+        assert!(rg
+            .resources()
+            .all(|res| matches!(res.get_status(), ResourceStatus::Unknown(_))));
+    }
 
     #[test]
     fn test_get_worst() {
@@ -650,4 +1743,59 @@ mod tests {
             ResourceStatus::RunningOnAway,
         );
     }
+
+    #[test]
+    fn test_names() {
+        let statuses: Vec<ResourceStatus> = ResourceStatus::NAMES
+            .iter()
+            .map(|name| ResourceStatus::from_name(name).unwrap())
+            .collect();
+        for (status, name) in statuses.iter().zip(ResourceStatus::NAMES) {
+            assert_eq!(status.name(), name);
+        }
+        // The names are listed from "worst" to "best", like the statuses themselves:
+        assert!(statuses.is_sorted());
+        assert_eq!(ResourceStatus::from_name("Running"), None);
+    }
+
+    #[test]
+    fn test_display() {
+        let reason = || "reason".to_string();
+        let cases = [
+            (ResourceStatus::Unknown(reason()), "Unknown", true),
+            (ResourceStatus::Error(reason()), "Error", true),
+            (ResourceStatus::FailedStart(reason()), "FailedStart", true),
+            (ResourceStatus::FailedStop(reason()), "FailedStop", true),
+            (
+                ResourceStatus::FailedMigrate(reason()),
+                "FailedMigrate",
+                true,
+            ),
+            (ResourceStatus::Blocked("zpool_0".into()), "Blocked", false),
+            (ResourceStatus::Stopped, "Stopped", false),
+            (ResourceStatus::Migrating, "Migrating", false),
+            (
+                ResourceStatus::RunningOnAway,
+                "Running (Failed Over)",
+                false,
+            ),
+            (ResourceStatus::RunningOnHome, "Running", false),
+        ];
+        for (status, name, failed) in cases {
+            assert_eq!(status.to_string(), name);
+            assert_eq!(status.is_failed(), failed, "{name}");
+        }
+
+        assert_eq!(
+            ResourceStatus::FailedMigrate(reason()).comment().as_deref(),
+            Some("reason")
+        );
+        assert_eq!(
+            ResourceStatus::Blocked("zpool_0".into())
+                .comment()
+                .as_deref(),
+            Some("waiting for zpool_0")
+        );
+        assert_eq!(ResourceStatus::RunningOnHome.comment(), None);
+    }
 }
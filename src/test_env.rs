@@ -48,6 +48,26 @@ pub struct ManagerHandle {
     socket_path: String,
 }
 
+impl ManagerHandle {
+    /// The process ID of the manager.
+    pub fn id(&self) -> u32 {
+        self.handle.id()
+    }
+
+    /// Ask the manager to reload its config file, as `systemctl reload` would.
+    pub fn reload(&self) {
+        let pid = nix::unistd::Pid::from_raw(self.handle.id() as i32);
+        nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGHUP).unwrap();
+    }
+
+    /// Ask the manager to shut down gracefully, as systemd would, and wait for it to exit.
+    pub fn terminate(&mut self) -> std::process::ExitStatus {
+        let pid = nix::unistd::Pid::from_raw(self.handle.id() as i32);
+        nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGTERM).unwrap();
+        self.handle.wait().unwrap()
+    }
+}
+
 impl Drop for ManagerHandle {
     fn drop(&mut self) {
         let _ = self.handle.kill();
@@ -58,6 +78,8 @@ impl Drop for ManagerHandle {
             match std::os::unix::net::UnixStream::connect(&self.socket_path) {
                 Ok(_) => {}
                 Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => return,
+                // A manager which shut down gracefully removes its socket:
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return,
                 Err(e) => panic!("Unexpected error wait for manager to stop: {e}."),
             }
 
@@ -138,12 +160,16 @@ impl TestEnvironment {
 
     /// Writes out the given config as a yaml file in the tests private directory.
     pub fn write_out_config(&self, config: &Config) {
-        let mut config_file =
-            std::fs::File::create(format!("{}/config.yaml", &self.private_dir_path)).unwrap();
+        let mut config_file = std::fs::File::create(self.config_path()).unwrap();
         let contents = serde_yaml::to_string(&config).unwrap();
         config_file.write_all(contents.as_bytes()).unwrap();
     }
 
+    /// The path of the config file written by `write_out_config()`.
+    pub fn config_path(&self) -> String {
+        format!("{}/config.yaml", &self.private_dir_path)
+    }
+
     pub fn socket_path(&self) -> String {
         format!("{}/test.socket", &self.private_dir_path)
     }
@@ -154,13 +180,39 @@ impl TestEnvironment {
         let config_path = test_path(&format!("{}.yaml", self.test_id));
         let socket_path = format!("{}/{}", self.private_dir_path, "test.socket");
         manager::Cli {
-            config: Some(config_path),
+            config: vec![config_path],
+            config_check: false,
             socket: Some(socket_path),
+            socket_mode: None,
+            socket_group: None,
+            no_cleanup_socket: false,
+            force: false,
+            listen: None,
             mtls: false,
+            auth_token: None,
+            read_only_token: None,
             verbose: false,
+            log_format: Default::default(),
+            log_file: None,
             manage_resources: true,
+            dry_run: false,
+            once: false,
             fence_on_connection_close: true,
-            sleep_time: 5000,
+            no_fence: false,
+            poll_interval: Some(5000),
+            poll_jitter: None,
+            history_size: 500,
+            retry_attempts: None,
+            retry_backoff: None,
+            retry_backoff_max: None,
+            fence_attempts: manager::DEFAULT_FENCE_ATTEMPTS,
+            action_concurrency: None,
+            maintenance_file: Some(format!("{}/maintenance", self.private_dir_path)),
+            state_file: None,
+            profile: false,
+            profile_slowest: 3,
+            rate_limit: 0,
+            rate_limit_burst: 5,
         }
     }
 
@@ -219,20 +271,64 @@ impl TestEnvironment {
 
     /// Starts the manager in a new process for
     pub fn start_manager(&self, manage_resources: bool) -> ManagerHandle {
+        self.start_manager_with_args(manage_resources, &[])
+    }
+
+    /// Like start_manager(), but passes `extra_args` on to the manager as well.
+    pub fn start_manager_with_args(
+        &self,
+        manage_resources: bool,
+        extra_args: &[&str],
+    ) -> ManagerHandle {
         let log_file = format!("{}/manager_log", &self.private_dir_path);
         let log_file = std::fs::File::create(log_file).unwrap();
 
-        let socket_path = format!("{}/test.socket", &self.private_dir_path);
-        let config_path = format!("{}/config.yaml", &self.private_dir_path);
+        let socket_path = self.manager_socket_path();
+        let handle = self
+            .manager_command(manage_resources, extra_args)
+            .stderr(std::process::Stdio::from(log_file))
+            .spawn()
+            .expect("could not launch manager process");
+
+        wait_for_service(&socket_path, false);
+
+        ManagerHandle {
+            handle,
+            socket_path,
+        }
+    }
+
+    /// Run a manager with the same arguments as start_manager_with_args(), and wait for it to exit,
+    /// e.g. to check that it refuses to start.
+    pub fn run_manager(&self, manage_resources: bool, extra_args: &[&str]) -> std::process::Output {
+        self.manager_command(manage_resources, extra_args)
+            .output()
+            .expect("could not launch manager process")
+    }
+
+    fn manager_socket_path(&self) -> String {
+        format!("{}/test.socket", &self.private_dir_path)
+    }
+
+    fn manager_command(
+        &self,
+        manage_resources: bool,
+        extra_args: &[&str],
+    ) -> std::process::Command {
+        let socket_path = self.manager_socket_path();
+        let config_path = self.config_path();
+        let maintenance_file = format!("{}/maintenance", self.private_dir_path);
 
         let mut args = vec![
+            "--maintenance-file",
+            &maintenance_file,
             "--verbose",
             "--fence-on-connection-close",
             "--config",
             &config_path,
             "--socket",
             &socket_path,
-            "--sleep-time",
+            "--poll-interval",
             "500",
         ];
 
@@ -240,18 +336,21 @@ impl TestEnvironment {
             args.push("--manage-resources");
         }
 
-        let handle = std::process::Command::new(&self.manager_binary_path)
-            .args(args)
-            .stderr(std::process::Stdio::from(log_file))
-            .spawn()
-            .expect("could not launch manager process");
+        args.extend_from_slice(extra_args);
 
-        wait_for_service(&socket_path, false);
+        let mut command = std::process::Command::new(&self.manager_binary_path);
+        command.args(args);
+        command
+    }
 
-        ManagerHandle {
-            handle,
-            socket_path,
-        }
+    /// Get everything that the manager has logged so far.
+    pub fn manager_log(&self) -> String {
+        std::fs::read_to_string(format!("{}/manager_log", &self.private_dir_path)).unwrap()
+    }
+
+    /// Get everything that the remote agents have logged so far, one resource operation per line.
+    pub fn agent_log(&self) -> String {
+        std::fs::read_to_string(&self.log_file_path).unwrap()
     }
 
     /// Reads a line from the shared file used for communication from the agent, and asserts that
@@ -275,6 +374,27 @@ impl TestEnvironment {
         std::fs::remove_file(&path).expect(&format!("failed to remove file '{}'", &path));
     }
 
+    /// Make the test OCF resource script fail to start or stop a resource, or stop it from failing.
+    pub fn set_resource_fails(&self, resource: &config::Resource, agent: usize, fails: bool) {
+        let path = format!("{}.fails", self.get_resource_path(resource, agent));
+        if fails {
+            std::fs::File::create(&path).expect(&format!("failed to create file '{}'", &path));
+        } else {
+            std::fs::remove_file(&path).expect(&format!("failed to remove file '{}'", &path));
+        }
+    }
+
+    /// Make the test OCF resource script take five seconds to start or stop a resource, or stop it
+    /// from hanging.
+    pub fn set_resource_hangs(&self, resource: &config::Resource, agent: usize, hangs: bool) {
+        let path = format!("{}.hangs", self.get_resource_path(resource, agent));
+        if hangs {
+            std::fs::File::create(&path).expect(&format!("failed to create file '{}'", &path));
+        } else {
+            std::fs::remove_file(&path).expect(&format!("failed to remove file '{}'", &path));
+        }
+    }
+
     /// Simulate a resource startin by creating the state file that the test OCF resource
     /// script checks to determine if the resource is running.
     pub fn start_resource(&self, resource: &config::Resource, agent: usize) {
@@ -327,7 +447,11 @@ fn wait_for_service(addr: &str, tcp: bool) {
         } else {
             match std::os::unix::net::UnixStream::connect(addr) {
                 Ok(_) => return,
-                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                // The socket file is created slightly before the manager starts listening on it,
+                // so a refused connection just means the manager is not ready yet:
+                Err(e)
+                    if e.kind() == io::ErrorKind::NotFound
+                        || e.kind() == io::ErrorKind::ConnectionRefused => {}
                 Err(e) => {
                     panic!("Unexpected error attempting to connect to manager at {addr}: {e}")
                 }
@@ -366,12 +490,12 @@ impl TestAgent {
 /// communication file for succesfully performing `op` on `res`.
 pub fn agent_expected_line(op: &str, res: &Resource) -> String {
     match res.kind.as_str() {
-        "heartbeat/ZFS" => format!("zfs {} pool={}", op, res.parameters.get("pool").unwrap()),
+        "heartbeat/ZFS" => format!("zfs {} pool={}", op, res.parameter("pool").unwrap()),
         "lustre/Lustre" => format!(
             "lustre {} mountpoint={} target={}",
             op,
-            res.parameters.get("mountpoint").unwrap(),
-            res.parameters.get("target").unwrap(),
+            res.parameter("mountpoint").unwrap(),
+            res.parameter("target").unwrap(),
         ),
         _ => unreachable!(),
     }
@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: MIT
 // Copyright 2025. Triad National Security, LLC.
 
-use std::{fs::File, io::BufReader, path::PathBuf, sync::Arc};
+use std::{fmt, fs::File, io::BufReader, sync::Arc, sync::OnceLock};
 
 use {
     rustls::{
@@ -13,65 +13,154 @@ use {
     tokio_rustls::{TlsAcceptor, TlsConnector},
 };
 
-fn load_private_key(path: PathBuf) -> Result<PrivateKeyDer<'static>, Box<dyn std::error::Error>> {
-    let key_file = File::open(path)?;
-    let mut reader = BufReader::new(key_file);
-    private_key(&mut reader)?.ok_or_else(|| "No private key found".into())
+/// An error encountered while loading the certificates and keys needed for mTLS.
+#[derive(Debug)]
+pub struct TlsError(String);
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
 }
 
-fn load_cert(path: PathBuf) -> Vec<CertificateDer<'static>> {
-    let cert_file = &mut BufReader::new(File::open(path).unwrap());
-    let certs: Vec<CertificateDer<'static>> = certs(cert_file)
-        .collect::<Result<_, _>>()
-        .expect("Issue getting certs");
-    certs
+impl std::error::Error for TlsError {}
+
+/// The locations of the certificate and key files used for mTLS.
+///
+/// Each path is taken from the cluster config file if it is set there, otherwise from the
+/// corresponding environment variable (e.g., `HALO_CA_CERT`), otherwise from a default location
+/// in /etc/halo/.
+#[derive(Debug, Clone)]
+pub struct TlsPaths {
+    pub ca_cert: String,
+    pub server_cert: String,
+    pub server_key: String,
+    pub client_cert: String,
+    pub client_key: String,
 }
 
-pub fn get_acceptor() -> TlsAcceptor {
-    // Load server certificate and private key
-    let server_cert = load_cert(PathBuf::from(crate::default_server_cert()));
-    let server_key = load_private_key(PathBuf::from(crate::default_server_key()));
+impl TlsPaths {
+    pub fn new(config: Option<&crate::config::TlsConfig>) -> Self {
+        let pick = |configured: Option<&Option<String>>, default: fn() -> String| {
+            configured
+                .and_then(|path| path.clone())
+                .unwrap_or_else(default)
+        };
+
+        Self {
+            ca_cert: pick(config.map(|c| &c.ca_cert), crate::default_ca_cert),
+            server_cert: pick(config.map(|c| &c.server_cert), crate::default_server_cert),
+            server_key: pick(config.map(|c| &c.server_key), crate::default_server_key),
+            client_cert: pick(config.map(|c| &c.client_cert), crate::default_client_cert),
+            client_key: pick(config.map(|c| &c.client_key), crate::default_client_key),
+        }
+    }
+}
 
-    // Load CA root certificate
-    let ca_cert = load_cert(PathBuf::from(crate::default_ca_cert()));
+fn load_private_key(path: &str) -> Result<PrivateKeyDer<'static>, TlsError> {
+    let key_file =
+        File::open(path).map_err(|e| TlsError(format!("could not open key '{path}': {e}")))?;
+    let mut reader = BufReader::new(key_file);
+    private_key(&mut reader)
+        .map_err(|e| TlsError(format!("could not read key '{path}': {e}")))?
+        .ok_or_else(|| TlsError(format!("no private key found in '{path}'")))
+}
 
-    // Load CA cert into root store, I.E. trust it
+fn load_cert(path: &str) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let cert_file = File::open(path)
+        .map_err(|e| TlsError(format!("could not open certificate '{path}': {e}")))?;
+    let certs: Vec<CertificateDer<'static>> = certs(&mut BufReader::new(cert_file))
+        .collect::<Result<_, _>>()
+        .map_err(|e| TlsError(format!("could not read certificate '{path}': {e}")))?;
+    if certs.is_empty() {
+        return Err(TlsError(format!("no certificates found in '{path}'")));
+    }
+    Ok(certs)
+}
+
+fn load_root_store(path: &str) -> Result<RootCertStore, TlsError> {
     let mut root_store = RootCertStore::empty();
-    root_store.add_parsable_certificates(ca_cert);
+    root_store.add_parsable_certificates(load_cert(path)?);
+    Ok(root_store)
+}
+
+/// Build a TLS acceptor, which verifies that clients present a certificate signed by the CA.
+pub fn build_acceptor(paths: &TlsPaths) -> Result<TlsAcceptor, TlsError> {
+    let server_cert = load_cert(&paths.server_cert)?;
+    let server_key = load_private_key(&paths.server_key)?;
+    let root_store = load_root_store(&paths.ca_cert)?;
 
     // Create a client certificiate verifier, mTLS part of the code
     let client_verifier = WebPkiClientVerifier::builder(Arc::new(root_store))
         .build()
-        .expect("Failure to build client verifier");
+        .map_err(|e| TlsError(format!("could not build client verifier: {e}")))?;
 
-    // Build server config
     let config = ServerConfig::builder()
         .with_client_cert_verifier(client_verifier)
-        .with_single_cert(server_cert, server_key.unwrap())
-        .unwrap();
+        .with_single_cert(server_cert, server_key)
+        .map_err(|e| TlsError(format!("invalid server certificate or key: {e}")))?;
 
-    // return TLS acceptor
-    TlsAcceptor::from(Arc::new(config))
+    Ok(TlsAcceptor::from(Arc::new(config)))
 }
 
-pub fn get_connector() -> TlsConnector {
-    // Load cient certificate adn private key
-    let client_cert = load_cert(PathBuf::from(crate::default_client_cert()));
-    let client_key = load_private_key(PathBuf::from(crate::default_client_key()));
+/// Build the rustls client config, which presents the client certificate and verifies the server
+/// against the CA.
+pub fn build_client_config(paths: &TlsPaths) -> Result<ClientConfig, TlsError> {
+    let client_cert = load_cert(&paths.client_cert)?;
+    let client_key = load_private_key(&paths.client_key)?;
+    let root_store = load_root_store(&paths.ca_cert)?;
 
-    // Load CA root certificate
-    let ca_cert = load_cert(PathBuf::from(crate::default_ca_cert()));
+    ClientConfig::builder()
+        .with_root_certificates(root_store)
+        .with_client_auth_cert(client_cert, client_key)
+        .map_err(|e| TlsError(format!("invalid client certificate or key: {e}")))
+}
 
-    // Load the CA cert into the root store, I.E. trust it
-    let mut root_store = RootCertStore::empty();
-    root_store.add_parsable_certificates(ca_cert);
+pub fn build_connector(paths: &TlsPaths) -> Result<TlsConnector, TlsError> {
+    Ok(TlsConnector::from(Arc::new(build_client_config(paths)?)))
+}
 
-    // Build client config
-    let config = ClientConfig::builder()
-        .with_root_certificates(root_store)
-        .with_client_auth_cert(client_cert, client_key.unwrap())
-        .unwrap();
+static ACCEPTOR: OnceLock<TlsAcceptor> = OnceLock::new();
+static CONNECTOR: OnceLock<TlsConnector> = OnceLock::new();
+
+/// Load the server certificates from `paths` so that later calls to get_acceptor() use them.
+///
+/// This should be called at startup so that missing or invalid certificates are reported right
+/// away, rather than on the first connection.
+pub fn init_acceptor(paths: &TlsPaths) -> Result<(), TlsError> {
+    let acceptor = build_acceptor(paths)?;
+    let _ = ACCEPTOR.set(acceptor);
+    Ok(())
+}
+
+/// Load the client certificates from `paths` so that later calls to get_connector() use them.
+///
+/// This should be called at startup so that missing or invalid certificates are reported right
+/// away, rather than on the first connection.
+pub fn init_connector(paths: &TlsPaths) -> Result<(), TlsError> {
+    let connector = build_connector(paths)?;
+    let _ = CONNECTOR.set(connector);
+    Ok(())
+}
 
-    // Return TLS connector
-    TlsConnector::from(Arc::new(config))
+/// Get the TLS acceptor. If init_acceptor() was not called, the certificates are loaded from
+/// their default locations, and this panics if they can not be loaded.
+pub fn get_acceptor() -> TlsAcceptor {
+    ACCEPTOR
+        .get_or_init(|| {
+            build_acceptor(&TlsPaths::new(None))
+                .unwrap_or_else(|e| panic!("Could not set up mTLS: {e}"))
+        })
+        .clone()
+}
+
+/// Get the TLS connector. If init_connector() was not called, the certificates are loaded from
+/// their default locations, and this panics if they can not be loaded.
+pub fn get_connector() -> TlsConnector {
+    CONNECTOR
+        .get_or_init(|| {
+            build_connector(&TlsPaths::new(None))
+                .unwrap_or_else(|e| panic!("Could not set up mTLS: {e}"))
+        })
+        .clone()
 }
@@ -3,11 +3,18 @@
 
 #[cfg(test)]
 mod tests {
-    use std::{collections::HashMap, sync::Mutex};
+    use std::{
+        collections::{BTreeMap, HashMap},
+        sync::Mutex,
+    };
 
     use halo_lib::{
         commands::{self, status::get_status},
         config::{self, Config},
+        halo_capnp::AgentReply,
+        manager::{self, http},
+        remote::ocf,
+        resource::{Location, TargetState},
         test_env::*,
     };
 
@@ -93,6 +100,50 @@ mod tests {
         fn failback(&self, onto: usize) {
             commands::failback::do_failback(&self.socket_path(), &self.agent_id(onto)).unwrap();
         }
+
+        /// Make the test fence agent fail to power off the given agent, or stop it from failing.
+        fn set_fence_fails(&self, which_one: usize, fails: bool) {
+            let config_path = self.env.config_path();
+            let dir = std::path::Path::new(&config_path).parent().unwrap();
+            let marker = dir.join(format!("{}.fence_fails", self.agent_id(which_one)));
+            if fails {
+                std::fs::File::create(marker).unwrap();
+            } else {
+                std::fs::remove_file(marker).unwrap();
+            }
+        }
+
+        /// Run the halo CLI against this environment's manager.
+        fn run_cli(&self, args: &[&str]) -> std::process::Output {
+            std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+                .arg("--socket")
+                .arg(self.socket_path())
+                .args(args)
+                .output()
+                .unwrap()
+        }
+
+        /// Run the halo CLI against this environment's manager, with `input` on its stdin.
+        fn run_cli_with_stdin(&self, args: &[&str], input: &str) -> std::process::Output {
+            use std::io::Write;
+
+            let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+                .arg("--socket")
+                .arg(self.socket_path())
+                .args(args)
+                .stdin(std::process::Stdio::piped())
+                .stdout(std::process::Stdio::piped())
+                .stderr(std::process::Stdio::piped())
+                .spawn()
+                .unwrap();
+            child
+                .stdin
+                .take()
+                .unwrap()
+                .write_all(input.as_bytes())
+                .unwrap();
+            child.wait_with_output().unwrap()
+        }
     }
 
     impl Drop for HaEnvironment {
@@ -128,85 +179,2945 @@ mod tests {
                 format!("127.0.0.1:{}", ports[0]),
                 format!("127.0.0.1:{}", ports[1]),
             ]]),
+            ..Default::default()
         };
 
-        for i in 0..2 {
+        for (i, port) in ports.iter().enumerate() {
             let zpool_name = || -> String { format!("zpool_{i}") };
             let lustre_name = || -> String { format!("mdt_{i}") };
 
-            let root_resource = config::Resource {
-                kind: "heartbeat/ZFS".to_string(),
-                parameters: HashMap::from([("pool".to_string(), zpool_name())]),
-                requires: None,
-            };
+            let root_resource = config::Resource {
+                kind: "heartbeat/ZFS".to_string(),
+                parameters: HashMap::from([("pool".to_string(), zpool_name())]),
+                ..Default::default()
+            };
+
+            let child_resource = config::Resource {
+                kind: "lustre/Lustre".to_string(),
+                parameters: HashMap::from([
+                    ("mountpoint".to_string(), format!("/mnt/{}", lustre_name())),
+                    ("target".to_string(), lustre_name()),
+                    ("kind".to_string(), "mdt".to_string()),
+                ]),
+                requires: Some(zpool_name()),
+                ..Default::default()
+            };
+
+            let host = config::Host {
+                hostname: format!("127.0.0.1:{port}"),
+                resources: HashMap::from([
+                    (zpool_name(), root_resource),
+                    (lustre_name(), child_resource),
+                ]),
+                fence_agent: Some("fence_test".to_string()),
+                fence_parameters: Some(HashMap::from([
+                    ("target".to_string(), format!("{test_id}_{i}")),
+                    ("test_id".to_string(), test_id.clone()),
+                ])),
+                attributes: Default::default(),
+            };
+
+            config.hosts.push(host);
+        }
+
+        config
+    }
+
+    /// Make sure that the test environment correctly detects a test that results in a
+    /// "double-started" resource. This should panic the test, normally causing it to fail, but in
+    /// this test we want to make sure the panic happens.
+    #[test]
+    #[should_panic]
+    fn double_started_resource() {
+        let env = HaEnvironment::new("double_started_resource");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        // Sleep for a second to give the manager enough time to start resources...
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        // Manually start the zpool on the failover node, even though it's already started on the
+        // home node:
+        env.start_resource("zpool_0", 1);
+    }
+
+    /// Startup, both agents running, all resources stopped.
+    /// Agents should start resources on their home nodes.
+    #[test]
+    fn startup1() {
+        let env = HaEnvironment::new("startup1");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        // Sleep for a second to give the manager enough time to start resources...
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        // Then run the status command to be sure the resources started
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        eprintln!("{cluster_status:?}");
+
+        for res in cluster_status.resources {
+            assert_eq!(res.status, "Running");
+        }
+    }
+
+    /// `status --json` should print only a JSON array describing every resource.
+    #[test]
+    fn status_json() {
+        let env = HaEnvironment::new("status_json");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let output = env.run_cli(&["status", "--json"]);
+        assert!(output.status.success());
+
+        let resources: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let resources = resources.as_array().unwrap();
+        assert_eq!(resources.len(), 4);
+        for res in resources {
+            assert!(res["id"].is_string());
+            assert_eq!(res["status"], "Running");
+            assert_eq!(res["managed"], true);
+            assert!(res["parameters"].is_object());
+        }
+    }
+
+    /// The status can be narrowed down to the resources that match some filters, a page at a time.
+    #[test]
+    fn status_filters() {
+        let env = HaEnvironment::new("status_filters");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        assert!(env.run_cli(&["unmanage", "zpool_1"]).status.success());
+
+        let ids = |args: &[&str]| -> Vec<String> {
+            let output = env.run_cli(&[&["status", "--json"], args].concat());
+            assert!(output.status.success(), "{output:?}");
+            let resources: Vec<http::ResourceJson> =
+                serde_json::from_slice(&output.stdout).unwrap();
+            resources.into_iter().map(|res| res.id).collect()
+        };
+
+        let all = ids(&[]);
+        assert_eq!(all.len(), 4);
+        assert!(ids(&["--prefix", "zpool"])
+            .iter()
+            .all(|id| id.starts_with("zpool")));
+        assert_eq!(ids(&["--prefix", "zpool"]).len(), 2);
+        assert_eq!(ids(&["--unmanaged"]).len(), 2);
+        assert!(ids(&["--unmanaged"]).contains(&"zpool_1".to_string()));
+        assert_eq!(ids(&["--managed", "--status", "runningonhome"]).len(), 2);
+        assert!(ids(&["--status", "Stopped"]).is_empty());
+        assert_eq!(
+            ids(&["--limit", "1", "--offset", "1"]),
+            vec![all[1].clone()]
+        );
+
+        let client =
+            commands::HaloClient::new(commands::ManagerAddress::new(&env.socket_path())).unwrap();
+        let page = client
+            .status_matching(&http::StatusQuery {
+                limit: Some(1),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(page.resources.len(), 1);
+        assert_eq!(page.total, 4);
+        let output = env.run_cli(&["status", "--limit", "1"]);
+        assert!(
+            String::from_utf8_lossy(&output.stdout).contains("Showing 1 of 4 matching resources.")
+        );
+
+        // An unknown status is rejected, by the CLI and by the manager:
+        assert_eq!(
+            env.run_cli(&["status", "--status", "Bogus"]).status.code(),
+            Some(2)
+        );
+        let err = client
+            .status_matching(&http::StatusQuery {
+                status: Some("Bogus".to_string()),
+                ..Default::default()
+            })
+            .unwrap_err();
+        assert!(
+            matches!(err, commands::CommandError::Validation(_)),
+            "{err:?}"
+        );
+    }
+
+    /// `halo resources` lists just the IDs of the resources, in the same order as the status.
+    #[test]
+    fn resources_list() {
+        let env = HaEnvironment::new("resources_list");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        assert!(env.run_cli(&["unmanage", "zpool_1"]).status.success());
+
+        let lines = |args: &[&str]| -> Vec<String> {
+            let output = env.run_cli(&[&["resources"], args].concat());
+            assert!(output.status.success(), "{output:?}");
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(String::from)
+                .collect()
+        };
+        let status_ids = |args: &[&str]| -> Vec<String> {
+            let output = env.run_cli(&[&["status", "--json"], args].concat());
+            let resources: Vec<http::ResourceJson> =
+                serde_json::from_slice(&output.stdout).unwrap();
+            resources.into_iter().map(|res| res.id).collect()
+        };
+
+        assert_eq!(lines(&[]).len(), 4);
+        assert_eq!(lines(&[]), status_ids(&[]));
+        let mut unmanaged = lines(&["--unmanaged"]);
+        assert_eq!(unmanaged, status_ids(&["--unmanaged"]));
+        unmanaged.sort();
+        assert_eq!(unmanaged, vec!["mdt_1", "zpool_1"]);
+        assert_eq!(lines(&["--managed", "--prefix", "mdt"]), vec!["mdt_0"]);
+        assert!(lines(&["--status", "stopped"]).is_empty());
+
+        let output = env.run_cli(&["resources", "--json", "--prefix", "zpool", "--unmanaged"]);
+        let ids: Vec<String> = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(ids, vec!["zpool_1"]);
+
+        assert_eq!(
+            env.run_cli(&["resources", "--status", "Bogus"])
+                .status
+                .code(),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn ping() {
+        let env = HaEnvironment::new("ping");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        let output = env.run_cli(&["ping"]);
+        assert!(output.status.success());
+        let out = String::from_utf8(output.stdout).unwrap();
+        assert!(out.contains("is alive"));
+    }
+
+    /// The metrics endpoint should be reachable over both the unix socket and a TCP address.
+    #[test]
+    fn metrics() {
+        let env = HaEnvironment::new("metrics");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let tcp_addr = format!("127.0.0.1:{}", get_ports()[0]);
+        let _m = env
+            .env
+            .start_manager_with_args(true, &["--listen", &tcp_addr]);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        for addr in [env.socket_path(), tcp_addr] {
+            let addr = commands::ManagerAddress::new(&addr);
+            let metrics = addr
+                .client()
+                .unwrap()
+                .get(addr.url("/metrics"))
+                .send()
+                .unwrap()
+                .text()
+                .unwrap();
+
+            assert!(metrics.contains("halo_resources{status=\"RunningOnHome\"} 4"));
+            assert!(metrics.contains("halo_resources{status=\"Stopped\"} 0"));
+            assert!(metrics.contains("halo_resources_managed{managed=\"true\"} 4"));
+            assert!(metrics.contains("halo_resources_managed{managed=\"false\"} 0"));
+            assert!(metrics.contains("halo_quorum 1"));
+            assert!(metrics.contains(&format!(
+                "halo_host_reachable{{host=\"{}\"}} 1",
+                env.agent_id(0)
+            )));
+            let iterations: u64 = metrics
+                .lines()
+                .find_map(|line| line.strip_prefix("halo_main_loop_iterations_total "))
+                .unwrap()
+                .parse()
+                .unwrap();
+            assert!(iterations > 0);
+            assert!(metrics.contains("halo_actions_in_flight 0"));
+            assert!(!metrics.contains("halo_pass_duration_seconds"));
+        }
+    }
+
+    /// With --rate-limit, requests which change something should be rejected with a 429 once an
+    /// endpoint is over its limit, and `halo --retry` should wait and try again.
+    #[test]
+    fn rate_limit() {
+        let env = HaEnvironment::new("rate_limit");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env
+            .env
+            .start_manager_with_args(true, &["--rate-limit", "1", "--rate-limit-burst", "2"]);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        assert!(env.run_cli(&["unmanage", "zpool_0"]).status.success());
+        assert!(env.run_cli(&["manage", "zpool_0"]).status.success());
+        let output = env.run_cli(&["unmanage", "zpool_0"]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("pass --retry"), "{stderr}");
+
+        // Each endpoint is limited separately, and reads are not limited at all:
+        let addr = commands::ManagerAddress::new(&env.socket_path());
+        let client = addr.client().unwrap();
+        for _ in 0..5 {
+            let response = client.get(addr.url("/status")).send().unwrap();
+            assert_eq!(response.status().as_u16(), 200);
+        }
+        let patch = |managed| {
+            client
+                .patch(addr.url("/resources/zpool_1"))
+                .json(&halo_lib::manager::http::SetManagedArgs {
+                    managed,
+                    dry_run: false,
+                    force: false,
+                })
+                .send()
+                .unwrap()
+        };
+        assert_eq!(patch(false).status().as_u16(), 200);
+        assert_eq!(patch(true).status().as_u16(), 200);
+        let response = patch(false);
+        assert_eq!(response.status().as_u16(), 429);
+        assert_eq!(response.headers()["retry-after"], "1");
+
+        let output = env.run_cli(&["--retry", "unmanage", "zpool_0"]);
+        assert!(output.status.success(), "{output:?}");
+        let status = get_status(&env.socket_path()).unwrap();
+        assert!(status
+            .resources
+            .iter()
+            .any(|res| res.id == "zpool_0" && !res.managed));
+    }
+
+    /// With --state-file, which resource groups are managed, which hosts are on standby, and the
+    /// target state of each resource are kept across restarts of the manager.
+    #[test]
+    fn state_file() {
+        let env = HaEnvironment::new("state_file");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let path = format!("{}.state.json", env.env.config_path());
+        let args = ["--state-file", path.as_str()];
+        let manager = env.env.start_manager_with_args(true, &args);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let host = env.agent_id(1);
+        assert!(env.run_cli(&["unmanage", "zpool_1"]).status.success());
+        assert!(env.run_cli(&["standby", &host]).status.success());
+        assert!(env.run_cli(&["disable", "mdt_0"]).status.success());
+
+        std::thread::sleep(std::time::Duration::from_millis(1500));
+
+        let state: halo_lib::manager::state::State =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(!state.managed["zpool_1"]);
+        assert!(state.managed["zpool_0"]);
+        assert_eq!(state.standby, vec![host.clone()]);
+        assert_eq!(state.target_states["mdt_0"], TargetState::Stopped);
+
+        drop(manager);
+        let _m = env.env.start_manager_with_args(true, &args);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        assert!(env
+            .env
+            .manager_log()
+            .contains("Restored the state of the cluster"));
+        let status = get_status(&env.socket_path()).unwrap();
+        let resource = |id: &str| status.resources.iter().find(|res| res.id == id).unwrap();
+        assert!(!resource("zpool_1").managed);
+        assert!(resource("zpool_0").managed);
+        assert_eq!(resource("mdt_0").target_state, TargetState::Stopped);
+        assert!(!env
+            .env
+            .resource_is_started(env.get_resource_by_id("mdt_0"), 0));
+        assert!(status.hosts.iter().any(|h| h.id == host && h.standby));
+    }
+
+    /// A state file which can not be read is ignored, and the config defaults are used.
+    #[test]
+    fn state_file_corrupt() {
+        let env = HaEnvironment::new("state_file_corrupt");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let path = format!("{}.state.json", env.env.config_path());
+        std::fs::write(&path, "{\"version\": 1, \"managed\": ").unwrap();
+        let _m = env
+            .env
+            .start_manager_with_args(true, &["--state-file", &path]);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        assert!(env
+            .env
+            .manager_log()
+            .contains("Could not read the state file"));
+        let status = get_status(&env.socket_path()).unwrap();
+        assert!(status.resources.iter().all(|res| res.managed));
+        assert!(env
+            .env
+            .resource_is_started(env.get_resource_by_id("zpool_0"), 0));
+    }
+
+    /// With --once, the manager checks on every resource group, reports what it found and did,
+    /// and exits with a status saying whether the cluster is healthy, without opening its socket.
+    #[test]
+    fn once() {
+        let env = HaEnvironment::new("once");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+
+        // Nothing is running yet, which Manage mode would fix:
+        let output = env.env.run_manager(false, &["--once"]);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(output.status.code(), Some(3), "{stdout}");
+        assert!(stdout.contains("zpool_0: Stopped\n"), "{stdout}");
+        assert!(
+            stdout.contains(&format!(
+                "would start resource group zpool_0 on {}",
+                env.agent_id(0)
+            )),
+            "{stdout}"
+        );
+        assert!(!std::path::Path::new(&env.socket_path()).exists());
+        for id in ["zpool_0", "mdt_0"] {
+            assert!(!env.env.resource_is_started(env.get_resource_by_id(id), 0));
+        }
+
+        let output = env.env.run_manager(true, &["--once"]);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(output.status.code(), Some(0), "{stdout}");
+        let started = format!("zpool_0: Stopped -> Running on {}\n", env.agent_id(0));
+        assert!(stdout.contains(&started), "{stdout}");
+        assert!(stdout.contains("All resources are healthy."), "{stdout}");
+        assert!(env
+            .env
+            .resource_is_started(env.get_resource_by_id("mdt_1"), 1));
+
+        let output = env.env.run_manager(false, &["--once"]);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(output.status.code(), Some(0), "{stdout}");
+        let running = format!("zpool_0: Running on {}\n", env.agent_id(0));
+        assert!(stdout.contains(&running), "{stdout}");
+    }
+
+    /// With --profile, passes of the management loops are timed, summarized in the log, and
+    /// reported as histograms at /metrics.
+    #[test]
+    fn profile() {
+        let env = HaEnvironment::new("profile");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env
+            .env
+            .start_manager_with_args(true, &["--profile", "--profile-slowest", "1"]);
+
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let addr = commands::ManagerAddress::new(&env.socket_path());
+        let metrics = addr
+            .client()
+            .unwrap()
+            .get(addr.url("/metrics"))
+            .send()
+            .unwrap()
+            .text()
+            .unwrap();
+        let count = |series: &str| -> u64 {
+            metrics
+                .lines()
+                .find_map(|line| line.strip_prefix(&format!("{series} ")))
+                .unwrap_or_else(|| panic!("no {series} in {metrics}"))
+                .parse()
+                .unwrap()
+        };
+        let passes = count("halo_pass_duration_seconds_count{phase=\"total\"}");
+        assert!(passes > 0);
+        assert_eq!(
+            count("halo_pass_duration_seconds_count{phase=\"monitor\"}"),
+            passes
+        );
+        assert!(
+            count("halo_pass_duration_seconds_bucket{phase=\"actions\",le=\"+Inf\"}") >= passes
+        );
+        assert!(count("halo_resource_monitor_duration_seconds_count") >= passes * 2);
+
+        // Each summary names only the slowest resource of its group:
+        let log = env.env.manager_log();
+        let summary = log
+            .lines()
+            .find(|line| line.contains("Profile: resource group"))
+            .unwrap_or_else(|| panic!("no summary in {log}"));
+        assert!(summary.contains("(monitor "), "{summary}");
+        assert_eq!(summary.matches("ms").count(), 4, "{summary}");
+    }
+
+    /// With --log-format json, every line of the manager's log should be a JSON object.
+    #[test]
+    fn json_logs() {
+        let env = HaEnvironment::new("json_logs");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env
+            .env
+            .start_manager_with_args(true, &["--log-format", "json"]);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let log = env.env.manager_log();
+        assert!(log.contains("Manager starting in Manage mode."));
+        for line in log.lines() {
+            let record: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert!(record["timestamp"].is_string());
+            assert!(record["level"].is_string());
+            assert!(record["message"].is_string());
+        }
+    }
+
+    /// With --log-file, the manager's log should be appended to that file, in a directory that is
+    /// created if needed.
+    #[test]
+    fn log_file() {
+        let env = HaEnvironment::new("log_file");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let socket_path = env.socket_path();
+        let log_dir = std::path::Path::new(&socket_path).parent().unwrap();
+        let log_file = log_dir.join("logs/manager.log");
+        let log_file = log_file.to_str().unwrap();
+        let _m = env
+            .env
+            .start_manager_with_args(true, &["--log-file", log_file]);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let log = std::fs::read_to_string(log_file).unwrap();
+        assert!(log.contains("Manager starting in Manage mode."));
+        assert!(!env.env.manager_log().contains("Manager starting"));
+    }
+
+    /// On SIGTERM, the manager should exit successfully and remove its socket.
+    #[test]
+    fn graceful_shutdown() {
+        let env = HaEnvironment::new("graceful_shutdown");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let mut m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let status = m.terminate();
+        assert!(status.success());
+        assert!(!std::path::Path::new(&env.socket_path()).exists());
+    }
+
+    /// The CLI should be able to reach a manager listening on a TCP address.
+    #[test]
+    fn status_over_tcp() {
+        let env = HaEnvironment::new("status_over_tcp");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let addr = format!("127.0.0.1:{}", get_ports()[0]);
+        let _m = env.env.start_manager_with_args(true, &["--listen", &addr]);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+            .args(["--socket", &addr, "status", "--json"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+
+        let resources: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(resources.as_array().unwrap().len(), 4);
+
+        // The unix socket should keep working alongside the TCP address:
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        assert_eq!(cluster_status.resources.len(), 4);
+    }
+
+    /// The manager should listen on an IPv6 address or a hostname, and refuse to start with an
+    /// address that could be read more than one way.
+    #[test]
+    fn listen_ipv6_and_hostname() {
+        let env = HaEnvironment::new("listen_ipv6_and_hostname");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+
+        let output = env.env.run_manager(true, &["--listen", "::1:8080"]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("put an IPv6 address in brackets"),
+            "{stderr}"
+        );
+
+        let [port, _] = get_ports();
+        for addr in [format!("[::1]:{port}"), format!("localhost:{port}")] {
+            let _m = env.env.start_manager_with_args(true, &["--listen", &addr]);
+            std::thread::sleep(std::time::Duration::from_secs(1));
+
+            let output = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+                .args(["--socket", &addr, "status", "--json"])
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{addr}");
+            let resources: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+            assert_eq!(resources.as_array().unwrap().len(), 4);
+        }
+        assert!(env.env.manager_log().contains(&format!(
+            "listening on address 'localhost:{port}', resolved to"
+        )));
+    }
+
+    /// Unmanaging several resources at once should update the ones that exist, even when some of
+    /// the requested resources do not exist.
+    #[test]
+    fn unmanage_multiple() {
+        let env = HaEnvironment::new("unmanage_multiple");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let output = env.run_cli(&["unmanage", "zpool_[0-1]", "no_such_resource"]);
+        assert!(!output.status.success());
+        let err_message = String::from_utf8(output.stderr).unwrap();
+        assert!(err_message.contains("no_such_resource"));
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            assert!(!res.managed);
+        }
+    }
+
+    /// Every request should be logged with its ID, which is sent back in the reply, and which a
+    /// client may choose.
+    #[test]
+    fn request_logging() {
+        let env = HaEnvironment::new("request_logging");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let header = halo_lib::manager::http::REQUEST_ID_HEADER;
+        let addr = commands::ManagerAddress::new(&env.socket_path());
+        let client = addr.client().unwrap();
+        let response = client
+            .get(addr.url("/status"))
+            .header(header, "test-123")
+            .send()
+            .unwrap();
+        assert_eq!(response.headers()[header], "test-123");
+
+        let response = client
+            .get(addr.url("/resources/no_such_resource"))
+            .send()
+            .unwrap();
+        let id = response.headers()[header].to_str().unwrap().to_string();
+        assert!(id.parse::<u64>().is_ok());
+
+        let log = env.env.manager_log();
+        assert!(log.contains("HTTP request test-123: GET /status -> 200 OK in "));
+        assert!(log.contains(&format!(
+            "HTTP request {id}: GET /resources/no_such_resource -> 404 Not Found in "
+        )));
+    }
+
+    /// On SIGHUP, the manager should apply the changes to its config file that it can while it
+    /// runs, and keep the config that it has if the new one is invalid.
+    #[test]
+    fn reload_config() {
+        let mut env = HaEnvironment::new("reload_config");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        assert_eq!(get_status(&env.socket_path()).unwrap().config_generation, 1);
+
+        let zpool_0 = env.config.hosts[0].resources.get_mut("zpool_0").unwrap();
+        zpool_0
+            .parameters
+            .insert("reload_test".to_string(), "yes".to_string());
+        env.config.hosts[1]
+            .resources
+            .get_mut("zpool_1")
+            .unwrap()
+            .depends_on = vec!["mdt_0".to_string()];
+        env.config
+            .groups
+            .insert("zpools".to_string(), vec!["zpool_0".to_string()]);
+        env.env.write_out_config(&env.config);
+        m.reload();
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let status = get_status(&env.socket_path()).unwrap();
+        assert_eq!(status.config_generation, 2);
+        let resource = |id: &str| status.resources.iter().find(|res| res.id == id).unwrap();
+        assert_eq!(
+            resource("zpool_0")
+                .parameters
+                .get("reload_test")
+                .map(|s| s.as_str()),
+            Some("yes")
+        );
+        assert_eq!(resource("zpool_1").depends_on, vec!["mdt_0".to_string()]);
+        assert_eq!(status.groups.len(), 1);
+        assert!(env
+            .env
+            .manager_log()
+            .contains("Config generation 2 is active."));
+
+        // An invalid config is not applied:
+        env.config.hosts[0]
+            .resources
+            .get_mut("mdt_0")
+            .unwrap()
+            .depends_on = vec!["no_such_resource".to_string()];
+        env.env.write_out_config(&env.config);
+        m.reload();
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let status = get_status(&env.socket_path()).unwrap();
+        assert_eq!(status.config_generation, 2);
+        let mdt_0 = status
+            .resources
+            .iter()
+            .find(|res| res.id == "mdt_0")
+            .unwrap();
+        assert!(mdt_0.depends_on.is_empty());
+        assert!(env
+            .env
+            .manager_log()
+            .contains("Could not reload config; config generation 2 is still active."));
+
+        // A resource group which is added is started on its home host:
+        env.config.hosts[0]
+            .resources
+            .get_mut("mdt_0")
+            .unwrap()
+            .depends_on = Vec::new();
+        let zpool_2 = config::Resource {
+            kind: "heartbeat/ZFS".to_string(),
+            parameters: HashMap::from([("pool".to_string(), "zpool_2".to_string())]),
+            ..Default::default()
+        };
+        env.config.hosts[0]
+            .resources
+            .insert("zpool_2".to_string(), zpool_2.clone());
+        env.env.write_out_config(&env.config);
+        m.reload();
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        assert!(env.env.resource_is_started(&zpool_2, 0));
+        let status = get_status(&env.socket_path()).unwrap();
+        assert_eq!(status.config_generation, 3);
+        assert!(status.resources.iter().any(|res| res.id == "zpool_2"));
+        assert!(env
+            .env
+            .manager_log()
+            .contains("Reloaded config: changed resource group zpool_2 (added)."));
+
+        // It is only removed once it is stopped:
+        env.config.hosts[0].resources.remove("zpool_2");
+        env.env.write_out_config(&env.config);
+        m.reload();
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let status = get_status(&env.socket_path()).unwrap();
+        assert!(status.resources.iter().any(|res| res.id == "zpool_2"));
+        assert!(env
+            .env
+            .manager_log()
+            .contains("resource group zpool_2 was removed while it may still be running"));
+
+        assert!(env.run_cli(&["disable", "zpool_2"]).status.success());
+        std::thread::sleep(std::time::Duration::from_secs(2));
+        assert!(!env.env.resource_is_started(&zpool_2, 0));
+        m.reload();
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let status = get_status(&env.socket_path()).unwrap();
+        assert_eq!(status.config_generation, 5);
+        assert!(!status.resources.iter().any(|res| res.id == "zpool_2"));
+        assert!(env
+            .env
+            .manager_log()
+            .contains("Reloaded config: changed resource group zpool_2 (removed)."));
+
+        // Once its management task is gone, the same group can be added back, and is started
+        // again:
+        std::thread::sleep(std::time::Duration::from_secs(2));
+        env.config.hosts[0]
+            .resources
+            .insert("zpool_2".to_string(), zpool_2.clone());
+        env.env.write_out_config(&env.config);
+        m.reload();
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        assert!(env.env.resource_is_started(&zpool_2, 0));
+        let status = get_status(&env.socket_path()).unwrap();
+        assert_eq!(status.config_generation, 6);
+        assert!(status.resources.iter().any(|res| res.id == "zpool_2"));
+        assert!(env
+            .env
+            .manager_log()
+            .contains("Resource group zpool_2 was added back."));
+    }
+
+    /// A second manager started with the same socket should refuse to run, rather than taking the
+    /// socket from the first one.
+    #[test]
+    fn second_manager_is_refused() {
+        let env = HaEnvironment::new("second_manager_is_refused");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let m = env.start_manager(true);
+
+        let output = env.env.run_manager(true, &[]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains(&format!(
+                "another halo manager is already running (pid {})",
+                m.id()
+            )),
+            "{stderr}"
+        );
+
+        // The first manager still has its socket:
+        assert!(!get_status(&env.socket_path()).unwrap().resources.is_empty());
+    }
+
+    /// A manager should refuse to take a socket that another process is listening on, unless
+    /// forced, but replace a stale one, unless asked to leave the socket file alone.
+    #[test]
+    fn socket_in_use_or_stale() {
+        use std::os::unix::net::UnixListener;
+
+        let env = HaEnvironment::new("socket_in_use_or_stale");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let socket = env.socket_path();
+
+        let other = UnixListener::bind(&socket).unwrap();
+        let output = env.env.run_manager(true, &[]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("another process is listening on it"),
+            "{stderr}"
+        );
+        assert!(std::os::unix::net::UnixStream::connect(&socket).is_ok());
+
+        // Once the other process is gone its socket file is stale, but is still left alone with
+        // --no-cleanup-socket:
+        std::mem::drop(other);
+        let output = env.env.run_manager(true, &["--no-cleanup-socket"]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("is not removed because of --no-cleanup-socket"),
+            "{stderr}"
+        );
+
+        // With --force, the manager replaces the socket of a process which is still listening:
+        std::fs::remove_file(&socket).unwrap();
+        let _other = UnixListener::bind(&socket).unwrap();
+        let mut m = env.env.start_manager_with_args(true, &["--force"]);
+        assert!(!get_status(&socket).unwrap().resources.is_empty());
+        assert!(m.terminate().success());
+        assert!(!std::path::Path::new(&socket).exists());
+
+        // A manager started with --no-cleanup-socket leaves its own socket behind when it exits:
+        let mut m = env
+            .env
+            .start_manager_with_args(true, &["--no-cleanup-socket"]);
+        assert!(!get_status(&socket).unwrap().resources.is_empty());
+        assert!(m.terminate().success());
+        assert!(std::path::Path::new(&socket).exists());
+    }
+
+    /// The manager should give its socket the mode and group that it is asked to, and refuse to
+    /// start with a group that does not exist.
+    #[test]
+    fn socket_permissions() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let env = HaEnvironment::new("socket_permissions");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+
+        let output = env
+            .env
+            .run_manager(true, &["--socket-group", "halo_no_such_group"]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("invalid socket group 'halo_no_such_group': no such group"),
+            "{stderr}"
+        );
+
+        let gid = nix::unistd::getgid();
+        let group = nix::unistd::Group::from_gid(gid).unwrap().unwrap();
+        let _m = env.env.start_manager_with_args(
+            true,
+            &["--socket-mode", "0600", "--socket-group", &group.name],
+        );
+
+        let metadata = std::fs::metadata(env.socket_path()).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        assert_eq!(metadata.gid(), gid.as_raw());
+        assert!(!get_status(&env.socket_path()).unwrap().resources.is_empty());
+    }
+
+    /// With an auth token, the manager should only serve requests that carry it, apart from its
+    /// health checks, and the CLI should send the token from the environment.
+    #[test]
+    fn auth_token() {
+        let env = HaEnvironment::new("auth_token");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env
+            .env
+            .start_manager_with_args(true, &["--auth-token", "secret"]);
+
+        let addr = commands::ManagerAddress::new(&env.socket_path());
+        let client = addr.client().unwrap();
+        assert_eq!(client.get(addr.url("/")).send().unwrap().status(), 200);
+        assert_eq!(
+            client.get(addr.url("/status")).send().unwrap().status(),
+            401
+        );
+        let response = client
+            .get(addr.url("/status"))
+            .bearer_auth("wrong")
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), 401);
+
+        let client = commands::HaloClient::new(addr.with_auth_token("secret")).unwrap();
+        assert!(!client.status().unwrap().resources.is_empty());
+
+        let run_cli = |token: Option<&str>| {
+            let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_halo"));
+            command.args(["--socket", &env.socket_path(), "status", "--json"]);
+            match token {
+                Some(token) => command.env("HALO_AUTH_TOKEN", token),
+                None => command.env_remove("HALO_AUTH_TOKEN"),
+            };
+            command.output().unwrap()
+        };
+        assert!(run_cli(Some("secret")).status.success());
+        let output = run_cli(None);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("requires an auth token"), "{stderr}");
+    }
+
+    /// The read-only token should be enough to read from the manager, but not to change anything.
+    #[test]
+    fn read_only_token() {
+        let env = HaEnvironment::new("read_only_token");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.env.start_manager_with_args(
+            true,
+            &["--auth-token", "secret", "--read-only-token", "reader"],
+        );
+
+        let addr = commands::ManagerAddress::new(&env.socket_path());
+        let client = addr.client().unwrap();
+        let send = |request: reqwest::blocking::RequestBuilder, token: &str| {
+            request.bearer_auth(token).send().unwrap().status()
+        };
+        assert_eq!(send(client.get(addr.url("/status")), "reader"), 200);
+        assert_eq!(send(client.get(addr.url("/metrics")), "reader"), 200);
+        assert_eq!(send(client.get(addr.url("/status")), "wrong"), 401);
+
+        let unmanage = || {
+            client.patch(addr.url("/resources/zpool_0")).json(
+                &halo_lib::manager::http::SetManagedArgs {
+                    managed: false,
+                    dry_run: false,
+                    force: false,
+                },
+            )
+        };
+        assert_eq!(send(unmanage(), "reader"), 403);
+        let status = commands::HaloClient::new(addr.clone().with_auth_token("reader"))
+            .unwrap()
+            .status()
+            .unwrap();
+        assert!(status.resources.iter().all(|res| res.managed));
+
+        // Nor should it be enough to change anything over a WebSocket, which is opened with GET:
+        let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+            .args(["--socket", &env.socket_path(), "shell"])
+            .env("HALO_AUTH_TOKEN", "reader")
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        std::io::Write::write_all(
+            &mut child.stdin.take().unwrap(),
+            b"unmanage zpool_0\nstatus --prefix zpool_0 --json\n",
+        )
+        .unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains("the read-only token can not be used to change anything"),
+            "{stderr}"
+        );
+        let resources: Vec<halo_lib::manager::http::ResourceJson> =
+            serde_json::from_slice(&output.stdout).unwrap();
+        assert!(resources[0].managed);
+
+        assert_eq!(send(unmanage(), "secret"), 200);
+    }
+
+    /// With placement weights, a resource group which is not running anywhere should be started
+    /// on the host with the higher score, even when that is not its home node.
+    #[test]
+    fn placement_score() {
+        let mut env = HaEnvironment::new("placement_score");
+        env.config.placement_weights = BTreeMap::from([("memory_gb".to_string(), 2.0)]);
+        env.config.hosts[1].attributes = BTreeMap::from([("memory_gb".to_string(), 5.0)]);
+        env.env.write_out_config(&env.config);
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let status = get_status(&env.socket_path()).unwrap();
+        for res in status.resources {
+            match res.id.as_str() {
+                "zpool_0" | "mdt_0" => assert_eq!(res.status, "Running (Failed Over)"),
+                _ => assert_eq!(res.status, "Running"),
+            }
+        }
+        assert!(env
+            .env
+            .resource_is_started(env.get_resource_by_id("zpool_0"), 1));
+
+        let host = env.agent_id(1);
+        assert!(env.env.manager_log().contains(&format!(
+            "Resource group zpool_0 is not running, and will be started on {host}, whose \
+             placement score of 10 is the highest."
+        )));
+        let output = env.run_cli(&["history"]);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(
+            stdout.contains(&format!("place on {host} (score 10)")),
+            "{stdout}"
+        );
+    }
+
+    /// Resource groups which must not run together should be started apart, only put together on
+    /// one host when there is nowhere else for them to run, with a warning, and moved apart again
+    /// once they can be.
+    #[test]
+    fn anti_affinity() {
+        let mut env = HaEnvironment::new("anti_affinity");
+        let home = env.config.hosts[0].hostname.clone();
+        env.config.hosts[1]
+            .resources
+            .get_mut("zpool_1")
+            .unwrap()
+            .home_node = Some(home);
+        env.config.anti_affinity = vec![vec!["zpool_0".to_string(), "zpool_1".to_string()]];
+        env.env.write_out_config(&env.config);
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(3));
+
+        let placed = |zpool_1_on: usize| {
+            for (id, on) in [("zpool_0", 0), ("zpool_1", zpool_1_on)] {
+                assert!(env.env.resource_is_started(env.get_resource_by_id(id), on));
+                assert!(!env
+                    .env
+                    .resource_is_started(env.get_resource_by_id(id), 1 - on));
+            }
+        };
+        placed(1);
+        let status = get_status(&env.socket_path()).unwrap();
+        assert!(status.resources.iter().all(|res| res.warnings.is_empty()));
+
+        // With the other host on standby, they have to run together:
+        assert!(env.run_cli(&["standby", &env.agent_id(1)]).status.success());
+        std::thread::sleep(std::time::Duration::from_secs(2));
+        placed(0);
+        let status = get_status(&env.socket_path()).unwrap();
+        for res in status.resources.iter() {
+            let other = if res.id.ends_with('0') {
+                "zpool_1"
+            } else {
+                "zpool_0"
+            };
+            assert_eq!(
+                res.warnings,
+                [format!("must not run on the same host as {other}")],
+                "{}",
+                res.id
+            );
+        }
+        let output = env.run_cli(&["status"]);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(
+            stdout.contains("zpool_0\t (Warning: must not run on the same host as zpool_1)"),
+            "{stdout}"
+        );
+
+        assert!(env
+            .run_cli(&["unstandby", &env.agent_id(1)])
+            .status
+            .success());
+        std::thread::sleep(std::time::Duration::from_secs(3));
+        placed(1);
+        let status = get_status(&env.socket_path()).unwrap();
+        assert!(status.resources.iter().all(|res| res.warnings.is_empty()));
+
+        let output = env.run_cli(&["migrate", "zpool_1", "--to", &env.agent_id(0)]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains("must not run on the same host as 'zpool_0'"),
+            "{stderr}"
+        );
+    }
+
+    /// Resource groups which must run together should be started on the same host, and migrated
+    /// together.
+    #[test]
+    fn colocation() {
+        let mut env = HaEnvironment::new("colocation");
+        env.config.colocation = vec![vec!["zpool_0".to_string(), "zpool_1".to_string()]];
+        env.env.write_out_config(&env.config);
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let placed = |on: usize| {
+            for id in ["zpool_0", "mdt_0", "zpool_1", "mdt_1"] {
+                assert!(env.env.resource_is_started(env.get_resource_by_id(id), on));
+                assert!(!env
+                    .env
+                    .resource_is_started(env.get_resource_by_id(id), 1 - on));
+            }
+        };
+        placed(0);
+        assert!(env.env.manager_log().contains(&format!(
+            "Resource group zpool_1 is not running, and will be started on {}, since it must run \
+             with zpool_0.",
+            env.agent_id(0)
+        )));
+
+        let output = env.run_cli(&["migrate", "zpool_0", "--to", &env.agent_id(1)]);
+        assert!(output.status.success(), "{output:?}");
+        placed(1);
+        let status = get_status(&env.socket_path()).unwrap();
+        for res in status.resources {
+            match res.id.as_str() {
+                "zpool_0" | "mdt_0" => assert_eq!(res.status, "Running (Failed Over)"),
+                _ => assert_eq!(res.status, "Running"),
+            }
+            assert!(res.warnings.is_empty());
+        }
+    }
+
+    /// `halo simulate` should show where the resource groups would go in a hypothetical state,
+    /// without changing anything.
+    #[test]
+    fn simulate() {
+        let env = HaEnvironment::new("simulate");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let (h0, h1) = (env.agent_id(0), env.agent_id(1));
+        let output = env.run_cli(&["simulate", "--fail-node", &h1]);
+        assert!(output.status.success(), "{output:?}");
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(
+            stdout.contains(&format!(
+                "Would move resource group zpool_1 from {h1} to {h0}, since {h1} is down.\n"
+            )),
+            "{stdout}"
+        );
+        assert!(stdout.contains(&format!("zpool_0: {h0}\n")), "{stdout}");
+        assert!(
+            stdout.contains(&format!("zpool_1: {h1} -> {h0}\n")),
+            "{stdout}"
+        );
+
+        // Nothing was actually moved:
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        for (id, on) in [("zpool_0", 0), ("zpool_1", 1)] {
+            assert!(env.env.resource_is_started(env.get_resource_by_id(id), on));
+        }
+
+        let output = env.run_cli(&[
+            "simulate",
+            "--json",
+            "--standby",
+            &h0,
+            "--add-resource",
+            &format!("ost9@{h0}"),
+        ]);
+        assert!(output.status.success(), "{output:?}");
+        let reply: halo_lib::manager::http::SimulateReply =
+            serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(
+            reply.actions,
+            [
+                format!("start resource group ost9 on {h1}"),
+                format!("move resource group zpool_0 from {h0} to {h1}, since {h0} is on standby"),
+            ]
+        );
+        assert!(reply
+            .resource_groups
+            .iter()
+            .all(|group| group.to.as_deref() == Some(h1.as_str())));
+
+        let output = env.run_cli(&["simulate", "--fail-node", "no_such_host"]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains("'no_such_host' is not a host in this cluster"),
+            "{stderr}"
+        );
+    }
+
+    /// A resource group which the config says is not managed should start out unmanaged.
+    #[test]
+    fn unmanaged_in_config() {
+        let mut env = HaEnvironment::new("unmanaged_in_config");
+        env.config.hosts[0]
+            .resources
+            .get_mut("zpool_0")
+            .unwrap()
+            .managed = Some(false);
+        env.env.write_out_config(&env.config);
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        let status = get_status(&env.socket_path()).unwrap();
+        for res in status.resources.iter() {
+            assert_eq!(
+                res.managed,
+                !["zpool_0", "mdt_0"].contains(&res.id.as_str()),
+                "{}",
+                res.id
+            );
+        }
+    }
+
+    /// Statuses should only be colored when asked for, or on a terminal, and never in JSON.
+    #[test]
+    fn color_option() {
+        let env = HaEnvironment::new("color_option");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        // In observe mode, the resources stay stopped, which is colored:
+        let _m = env.start_manager(false);
+
+        let has_color = |args: &[&str]| {
+            let output = env.run_cli(args);
+            assert!(output.status.success(), "{output:?}");
+            String::from_utf8(output.stdout).unwrap().contains('\x1b')
+        };
+        assert!(has_color(&["--color", "always", "status"]));
+        assert!(has_color(&["status", "--color", "always"]));
+        assert!(has_color(&["--color", "always", "describe", "zpool_0"]));
+        assert!(has_color(&["--color", "always", "top"]));
+        assert!(!has_color(&["status"]));
+        assert!(!has_color(&["--color", "never", "status"]));
+        assert!(!has_color(&["--color", "always", "status", "--json"]));
+        assert!(!has_color(&[
+            "--color", "always", "describe", "--json", "zpool_0"
+        ]));
+        assert!(!has_color(&["--color", "always", "top", "--json"]));
+    }
+
+    /// The manager should be alive as soon as it answers requests, but only ready once its
+    /// management loops have finished a pass.
+    #[test]
+    fn readiness() {
+        let env = HaEnvironment::new("readiness");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        let addr = commands::ManagerAddress::new(&env.socket_path());
+        let client = addr.client().unwrap();
+        let mut ready = None;
+        for _ in 0..50 {
+            if let Ok(response) = client.get(addr.url("/")).send() {
+                assert_eq!(response.status(), 200);
+                let response = client.get(addr.url("/ready")).send().unwrap();
+                if response.status() == 200 {
+                    ready = Some(response.text().unwrap());
+                    break;
+                }
+                assert_eq!(response.status(), 503);
+                assert!(response.text().unwrap().contains("not finished a pass"));
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+        assert_eq!(ready.as_deref(), Some("ready\n"));
+    }
+
+    /// `halo top` should summarize the cluster, and print a single summary when its output is not
+    /// a terminal.
+    #[test]
+    fn top_summary() {
+        let env = HaEnvironment::new("top_summary");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        let mut summary = None;
+        for _ in 0..50 {
+            let output = env.run_cli(&["top", "--json"]);
+            if output.status.success() {
+                let s: http::SummaryJson = serde_json::from_slice(&output.stdout).unwrap();
+                let running = s
+                    .statuses
+                    .iter()
+                    .find(|entry| entry.status == "RunningOnHome")
+                    .map(|entry| entry.count);
+                if running == Some(4) {
+                    summary = Some(s);
+                    break;
+                }
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+        let summary = summary.expect("resources never all started");
+        assert_eq!(summary.managed, 4);
+        assert_eq!(summary.unmanaged, 0);
+        assert_eq!(summary.hosts, 2);
+        assert!(summary.standby.is_empty());
+        assert!(summary.main_loop_iterations > 0);
+
+        let output = env.run_cli(&["top"]);
+        assert!(output.status.success(), "{output:?}");
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.starts_with("Resources: 4 (4 managed, 0 unmanaged)\n"));
+        assert!(stdout.contains("Hosts: 2 (2 reachable)\n"));
+    }
+
+    /// Changing the managed state of a single resource group should reply with a status code that
+    /// says whether it worked.
+    #[test]
+    fn set_managed_status_codes() {
+        let env = HaEnvironment::new("set_managed_status_codes");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let addr = commands::ManagerAddress::new(&env.socket_path());
+        let client = addr.client().unwrap();
+        let set_managed_with = |resource: &str, managed: bool, force: bool| {
+            let response = client
+                .patch(addr.url(&format!("/resources/{resource}")))
+                .json(&halo_lib::manager::http::SetManagedArgs {
+                    managed,
+                    dry_run: false,
+                    force,
+                })
+                .send()
+                .unwrap();
+            let status = response.status().as_u16();
+            let reply: halo_lib::manager::http::SetManagedReply = response.json().unwrap();
+            assert_eq!(reply.error, status != 200);
+            (status, reply)
+        };
+        let set_managed =
+            |resource: &str, managed: bool| set_managed_with(resource, managed, false).0;
+
+        assert_eq!(set_managed("zpool_0", false), 200);
+        assert_eq!(set_managed("zpool_0", false), 409);
+        assert_eq!(set_managed("zpool_0", true), 200);
+        assert_eq!(set_managed("zpool_0", true), 409);
+        assert_eq!(set_managed("no_such_resource", true), 404);
+
+        // The reply also says why a request failed, without a code when it did not:
+        use halo_lib::manager::http::ErrorCode;
+        let (_, reply) = set_managed_with("zpool_0", true, false);
+        assert_eq!(reply.code, Some(ErrorCode::AlreadyInState));
+        let (_, reply) = set_managed_with("no_such_resource", true, false);
+        assert_eq!(reply.code, Some(ErrorCode::NotFound));
+        let (_, reply) = set_managed_with("zpool_0", true, true);
+        assert_eq!(reply.code, None);
+
+        // With force, a resource group that is already in the requested state is a success:
+        let (status, reply) = set_managed_with("zpool_0", true, true);
+        assert_eq!(status, 200);
+        assert!(!reply.changed);
+        assert!(reply.message.contains("no change"));
+        let (status, reply) = set_managed_with("zpool_0", false, true);
+        assert_eq!(status, 200);
+        assert!(reply.changed);
+        assert_eq!(set_managed_with("no_such_resource", true, true).0, 404);
+
+        // The client treats a resource group that is already managed as a success:
+        env.manage_resource("zpool_0");
+    }
+
+    /// Concurrent requests to manage and unmanage resource groups, while the management loops
+    /// are running, should leave each group in a consistent state.
+    #[test]
+    fn set_managed_stress() {
+        let env = HaEnvironment::new("set_managed_stress");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let addr = commands::ManagerAddress::new(&env.socket_path());
+        let changes: Vec<(String, bool)> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..8)
+                .map(|worker| {
+                    let addr = addr.clone();
+                    scope.spawn(move || {
+                        let client = addr.client().unwrap();
+                        let mut changes = Vec::new();
+                        for i in 0..50 {
+                            let resource = format!("zpool_{}", (worker + i) % 2);
+                            let managed = (worker + i / 2) % 2 == 0;
+                            let response = client
+                                .patch(addr.url(&format!("/resources/{resource}")))
+                                .json(&halo_lib::manager::http::SetManagedArgs {
+                                    managed,
+                                    dry_run: false,
+                                    force: false,
+                                })
+                                .send()
+                                .unwrap();
+                            match response.status().as_u16() {
+                                200 => changes.push((resource, managed)),
+                                409 => {}
+                                other => panic!("unexpected status {other}"),
+                            }
+                        }
+                        changes
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+
+        // Every change that succeeded flipped the managed flag, so the changes to each group must
+        // alternate between unmanaging and managing it:
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for id in ["zpool_0", "zpool_1"] {
+            let unmanaged = changes.iter().filter(|c| c.0 == id && !c.1).count();
+            let managed = changes.iter().filter(|c| c.0 == id && c.1).count();
+            let res = cluster_status
+                .resources
+                .iter()
+                .find(|r| r.id == id)
+                .unwrap();
+            assert_eq!(unmanaged - managed, if res.managed { 0 } else { 1 });
+        }
+
+        env.manage_resource("zpool_0");
+        env.manage_resource("zpool_1");
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            assert!(res.managed);
+            assert_eq!(res.status, "Running");
+        }
+    }
+
+    /// Unmanaging by pattern should update every matching resource group, and an invalid regex
+    /// should be rejected without updating anything.
+    #[test]
+    fn unmanage_pattern() {
+        let env = HaEnvironment::new("unmanage_pattern");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let output = env.run_cli(&["unmanage", "--regex", "zpool_("]);
+        assert!(!output.status.success());
+
+        let output = env.run_cli(&["unmanage", "--pattern", "nothing*"]);
+        assert!(!output.status.success());
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            assert!(res.managed);
+        }
+
+        let output = env.run_cli(&["unmanage", "--pattern", "zpool_*"]);
+        assert!(output.status.success());
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            assert!(!res.managed);
+        }
+    }
+
+    /// Starting and stopping a single resource should drive it to the requested status, and an
+    /// unmanaged resource should not be started.
+    #[test]
+    fn start_stop() {
+        let env = HaEnvironment::new("start_stop");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(false);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let output = env.run_cli(&["start", "zpool_0"]);
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "zpool_0: Running\n"
+        );
+        assert!(env
+            .env
+            .resource_is_started(env.get_resource_by_id("zpool_0"), 0));
+
+        let output = env.run_cli(&["stop", "zpool_0"]);
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "zpool_0: Stopped\n"
+        );
+        assert!(!env
+            .env
+            .resource_is_started(env.get_resource_by_id("zpool_0"), 0));
+
+        let output = env.run_cli(&["start", "no_such_resource"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("resource not found"));
+
+        env.unmanage_resource("zpool_1");
+        let output = env.run_cli(&["start", "zpool_1"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("Run 'halo manage zpool_1' first"));
+        assert!(!env
+            .env
+            .resource_is_started(env.get_resource_by_id("zpool_1"), 0));
+    }
+
+    /// Canceling a start which hangs should make it give up right away, leaving the resource with
+    /// the status it had before, and canceling when nothing is in progress should do nothing.
+    #[test]
+    fn cancel_action() {
+        let env = HaEnvironment::new("cancel_action");
+        env.env
+            .set_resource_hangs(env.get_resource_by_id("zpool_0"), 0, true);
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(false);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let output = env.run_cli(&["cancel", "zpool_0"]);
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "zpool_0: no action in progress\n"
+        );
+
+        let started = std::time::Instant::now();
+        std::thread::scope(|scope| {
+            let start = scope.spawn(|| env.run_cli(&["start", "zpool_0"]));
+            std::thread::sleep(std::time::Duration::from_millis(500));
+
+            let output = env.run_cli(&["--dry-run", "cancel", "zpool_0"]);
+            assert_eq!(
+                String::from_utf8(output.stdout).unwrap(),
+                "dry run: would cancel 1 action on 'zpool_0'\n"
+            );
+            let output = env.run_cli(&["cancel", "zpool_0"]);
+            assert!(output.status.success());
+            assert_eq!(
+                String::from_utf8(output.stdout).unwrap(),
+                "zpool_0: canceled 1 action\n"
+            );
+
+            let output = start.join().unwrap();
+            assert!(!output.status.success());
+            let stderr = String::from_utf8(output.stderr).unwrap();
+            assert!(stderr.contains("canceled with 'halo cancel'"), "{stderr}");
+        });
+        // The agent hangs for 5 seconds, which the start did not wait for:
+        assert!(started.elapsed() < std::time::Duration::from_secs(4));
+
+        let status = get_status(&env.socket_path()).unwrap();
+        let zpool_0 = status.resources.iter().find(|res| res.id == "zpool_0");
+        assert_eq!(zpool_0.unwrap().status, "Stopped");
+
+        let output = env.run_cli(&["cancel", "no_such_resource"]);
+        assert_eq!(output.status.code(), Some(4));
+    }
+
+    /// Canceling a start by the management loops should make them give up on it right away, and
+    /// not try it again until the failure is cleared.
+    #[test]
+    fn cancel_managed_start() {
+        let env = HaEnvironment::new("cancel_managed_start");
+        env.env
+            .set_resource_hangs(env.get_resource_by_id("zpool_0"), 0, true);
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        // Wait for the manager to begin starting the resource:
+        let began = std::time::Instant::now();
+        while !String::from_utf8(env.run_cli(&["--dry-run", "cancel", "zpool_0"]).stdout)
+            .unwrap()
+            .contains("would cancel 1 action")
+        {
+            assert!(began.elapsed() < std::time::Duration::from_secs(4));
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+
+        let output = env.run_cli(&["cancel", "zpool_0"]);
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "zpool_0: canceled 1 action\n"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(200));
+
+        // The agent hangs for 5 seconds, which the manager did not wait for:
+        assert!(began.elapsed() < std::time::Duration::from_secs(5));
+        let status = get_status(&env.socket_path()).unwrap();
+        let zpool_0 = status.resources.iter().find(|res| res.id == "zpool_0");
+        let zpool_0 = zpool_0.unwrap();
+        assert_eq!(zpool_0.status, "FailedStart");
+        assert!(zpool_0
+            .comment
+            .as_ref()
+            .unwrap()
+            .contains("start canceled with 'halo cancel'"));
+        assert!(env
+            .env
+            .manager_log()
+            .contains("Resource zpool_0: start canceled with 'halo cancel'"));
+    }
+
+    /// Migrating a resource group in Manage mode should hand it over to the partner host, and bad
+    /// requests should be rejected without moving anything.
+    #[test]
+    fn migrate_manage() {
+        let env = HaEnvironment::new("migrate_manage");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let output = env.run_cli(&["migrate", "zpool_0", "--to", "no_such_host"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("is not a host in this cluster"));
+
+        let target = env.agent_id(1);
+        let output = env.run_cli(&["migrate", "mdt_0", "--to", &target]);
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            format!("zpool_0: moved from {} to {target}\n", env.agent_id(0))
+        );
+
+        for id in ["zpool_0", "mdt_0"] {
+            assert!(!env.env.resource_is_started(env.get_resource_by_id(id), 0));
+            assert!(env.env.resource_is_started(env.get_resource_by_id(id), 1));
+        }
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            if res.id.contains("0") {
+                assert_eq!(res.status, "Running (Failed Over)");
+            } else {
+                assert_eq!(res.status, "Running");
+            }
+        }
+
+        env.unmanage_resource("zpool_1");
+        let output = env.run_cli(&["migrate", "zpool_1", "--to", &env.agent_id(0)]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("Run 'halo manage zpool_1' first"));
+        assert!(env
+            .env
+            .resource_is_started(env.get_resource_by_id("zpool_1"), 1));
+    }
+
+    /// Migrating a resource group in Observe mode should move it directly.
+    #[test]
+    fn migrate_observe() {
+        let env = HaEnvironment::new("migrate_observe");
+        env.start_resource("zpool_0", 0);
+        env.start_resource("mdt_0", 0);
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(false);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let output = env.run_cli(&["migrate", "zpool_0", "--to", &env.agent_id(1)]);
+        assert!(output.status.success());
+
+        for id in ["zpool_0", "mdt_0"] {
+            assert!(!env.env.resource_is_started(env.get_resource_by_id(id), 0));
+            assert!(env.env.resource_is_started(env.get_resource_by_id(id), 1));
+        }
+
+        // A resource group that is not running anywhere can not be migrated:
+        let output = env.run_cli(&["migrate", "zpool_1", "--to", &env.agent_id(0)]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("is not running"));
+    }
+
+    /// A resource group whose migration failed should be reported as FailedMigrate, even with
+    /// --exclude-normal, until the failure is cleared.
+    #[test]
+    fn migrate_failure_is_reported() {
+        let env = HaEnvironment::new("migrate_failure_is_reported");
+        env.start_resource("zpool_0", 0);
+        env.start_resource("mdt_0", 0);
+        env.env
+            .set_resource_fails(env.get_resource_by_id("zpool_0"), 1, true);
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(false);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let output = env.run_cli(&["migrate", "zpool_0", "--to", &env.agent_id(1)]);
+        assert!(!output.status.success());
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            if res.id.contains("0") {
+                assert_eq!(res.status, "FailedMigrate");
+                assert!(res.comment.is_some());
+            }
+        }
+
+        let output = env.run_cli(&["status", "--exclude-normal"]);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("FailedMigrate: heartbeat/ZFS\tzpool_0"));
+        assert!(stdout.contains("FailedMigrate: lustre/Lustre\tmdt_0"));
+
+        env.env
+            .set_resource_fails(env.get_resource_by_id("zpool_0"), 1, false);
+        let output = env.run_cli(&["start", "--clear-failed", "zpool_0"]);
+        assert!(output.status.success());
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            assert_ne!(res.status, "FailedMigrate");
+        }
+    }
+
+    /// A resource group which may only run on its home node should not be failed over, and should
+    /// be started again once its home node is back.
+    #[test]
+    fn placement_allowed_nodes() {
+        let mut env = HaEnvironment::new("placement_allowed_nodes");
+        let home = format!("127.0.0.1:{}", env.ports[1]);
+        env.config.hosts[1]
+            .resources
+            .get_mut("zpool_1")
+            .unwrap()
+            .allowed_nodes = Some(vec![home]);
+        env.env.write_out_config(&env.config);
+
+        let _a = env.start_agent(0);
+        let b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let output = env.run_cli(&["-v", "status"]);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let line = stdout.lines().find(|l| l.contains("zpool_1")).unwrap();
+        assert!(line.contains(&format!("allowed: {})", env.agent_id(1))));
+
+        drop(b);
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        for id in ["zpool_1", "mdt_1"] {
+            assert!(!env.env.resource_is_started(env.get_resource_by_id(id), 0));
+        }
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            if res.id.contains("1") {
+                assert_ne!(res.status, "Running (Failed Over)");
+            }
+        }
+
+        let _b = env.start_agent(1);
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            assert_eq!(res.status, "Running");
+        }
+    }
+
+    /// A resource group with an explicit home node should be moved back there once the home node
+    /// is healthy again after a failover.
+    #[test]
+    fn placement_prefer_home() {
+        let mut env = HaEnvironment::new("placement_prefer_home");
+        let home = format!("127.0.0.1:{}", env.ports[1]);
+        env.config.hosts[1]
+            .resources
+            .get_mut("zpool_1")
+            .unwrap()
+            .home_node = Some(home);
+        env.env.write_out_config(&env.config);
+
+        let _a = env.start_agent(0);
+        let b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        drop(b);
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            if res.id.contains("1") {
+                assert_eq!(res.status, "Running (Failed Over)");
+            }
+        }
+
+        let _b = env.start_agent(1);
+        std::thread::sleep(std::time::Duration::from_secs(3));
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            assert_eq!(res.status, "Running");
+        }
+        for id in ["zpool_1", "mdt_1"] {
+            assert!(!env.env.resource_is_started(env.get_resource_by_id(id), 0));
+            assert!(env.env.resource_is_started(env.get_resource_by_id(id), 1));
+        }
+    }
+
+    /// When stdout is not a terminal, `status --watch` should print successive snapshots.
+    #[test]
+    fn status_watch() {
+        let env = HaEnvironment::new("status_watch");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let mut watch = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+            .arg("--socket")
+            .arg(env.socket_path())
+            .args(["status", "--watch", "--interval", "1"])
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(2500));
+        watch.kill().unwrap();
+        let output = watch.wait_with_output().unwrap();
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(!stdout.contains('\x1b'));
+        let snapshots: Vec<&str> = stdout.split("\n\n").collect();
+        assert!(snapshots.len() >= 2);
+        assert!(snapshots[0].contains("zpool_0"));
+        assert!(snapshots[1].contains("zpool_0"));
+    }
+
+    /// `halo events` should print changes to resources and hosts as they happen.
+    #[test]
+    fn events() {
+        let env = HaEnvironment::new("events");
+        env.start_resource("zpool_0", 0);
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(false);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let mut events = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+            .arg("--socket")
+            .arg(env.socket_path())
+            .arg("events")
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(500));
+
+        env.unmanage_resource("zpool_1");
+        assert!(env.run_cli(&["standby", &env.agent_id(0)]).status.success());
+        env.stop_resource("zpool_0", 0);
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        events.kill().unwrap();
+        let output = events.wait_with_output().unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("zpool_1: unmanaged\n"));
+        assert!(stdout.contains(&format!("{}: on standby\n", env.agent_id(0))));
+        assert!(stdout.contains("zpool_0: Stopped\n"));
+    }
+
+    /// Actions on resources should be recorded in the history, which keeps only the most recent
+    /// ones.
+    #[test]
+    fn history() {
+        let env = HaEnvironment::new("history");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env
+            .env
+            .start_manager_with_args(true, &["--history-size", "2"]);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        env.unmanage_resource("zpool_0");
+        env.unmanage_resource("zpool_0");
+        let output = env.run_cli(&["migrate", "zpool_1", "--to", "no_such_host"]);
+        assert!(!output.status.success());
+
+        let output = env.run_cli(&["history", "--json"]);
+        assert!(output.status.success());
+        let entries: Vec<halo_lib::manager::history::Entry> =
+            serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].resource, "zpool_0");
+        assert_eq!(entries[0].action, "unmanage");
+        assert!(entries[0].result.contains("already unmanaged"));
+        assert_eq!(entries[1].resource, "zpool_1");
+        assert_eq!(entries[1].action, "migrate to no_such_host");
+        assert!(entries[1].result.contains("is not a host in this cluster"));
+
+        let output = env.run_cli(&["history"]);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(stdout.lines().count(), 2);
+        assert!(stdout.contains("\tzpool_0\tunmanage\t"));
+
+        let output = env.run_cli(&["history", "--since", "1h"]);
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 2);
+
+        let output = env.run_cli(&["history", "--since", "2000-01-01T00:00:00Z"]);
+        assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 2);
+
+        let output = env.run_cli(&["history", "--since", "2999-01-01T00:00:00Z", "--json"]);
+        assert!(output.status.success());
+        let entries: Vec<halo_lib::manager::history::Entry> =
+            serde_json::from_slice(&output.stdout).unwrap();
+        assert!(entries.is_empty());
+
+        let output = env.run_cli(&["history", "--since", "yesterday"]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("invalid duration 'yesterday'"), "{stderr}");
+    }
+
+    /// A resource which keeps failing to start should be retried with backoff, then left alone
+    /// until its failure is cleared.
+    #[test]
+    fn start_retries_then_gives_up() {
+        let env = HaEnvironment::new("start_retries_then_gives_up");
+        env.env
+            .set_resource_fails(env.get_resource_by_id("zpool_0"), 0, true);
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env
+            .env
+            .start_manager_with_args(true, &["--retry-attempts", "3", "--retry-backoff", "100"]);
+
+        std::thread::sleep(std::time::Duration::from_secs(3));
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        let zpool = cluster_status
+            .resources
+            .iter()
+            .find(|res| res.id == "zpool_0")
+            .unwrap();
+        assert_eq!(zpool.status, "FailedStart");
+        assert_eq!(zpool.retries, 3);
+        assert!(zpool.next_retry.is_none());
+        assert!(env.env.manager_log().contains("will not be tried again"));
+
+        // The manager has given up, so it leaves the resource alone even once it could start:
+        env.env
+            .set_resource_fails(env.get_resource_by_id("zpool_0"), 0, false);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        assert!(!env
+            .env
+            .resource_is_started(env.get_resource_by_id("zpool_0"), 0));
+
+        let out = env.run_cli(&["start", "zpool_0"]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("--clear-failed"));
+
+        let out = env.run_cli(&["start", "--clear-failed", "zpool_0"]);
+        assert!(out.status.success());
+
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            assert_eq!(res.status, "Running");
+            assert_eq!(res.retries, 0);
+        }
+    }
+
+    /// A resource whose agent hangs while starting it should be given up on once its start timeout
+    /// passes, without holding up the other resources.
+    #[test]
+    fn start_timeout() {
+        let mut env = HaEnvironment::new("start_timeout");
+        env.config.hosts[0]
+            .resources
+            .get_mut("zpool_0")
+            .unwrap()
+            .start_timeout = Some(1);
+        env.env.write_out_config(&env.config);
+        env.env
+            .set_resource_hangs(env.get_resource_by_id("zpool_0"), 0, true);
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(3));
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            match res.id.as_str() {
+                "zpool_0" => {
+                    assert_eq!(res.status, "FailedStart");
+                    assert!(res.comment.unwrap().contains("start timed out after 1s"));
+                    assert_eq!(res.start_timeout, 1);
+                }
+                "mdt_0" => assert_ne!(res.status, "Running"),
+                _ => assert_eq!(res.status, "Running"),
+            }
+        }
+        assert!(env
+            .env
+            .manager_log()
+            .contains("Resource zpool_0: start timed out after"));
+
+        let out = env.run_cli(&["describe", "zpool_0"]);
+        let stdout = String::from_utf8(out.stdout).unwrap();
+        assert!(stdout.contains("start timeout: 1s\n"));
+        assert!(stdout.contains("stop timeout: 300s\n"));
+    }
+
+    /// `halo mode observe` should stop the manager from restarting resources until `halo mode
+    /// manage`, and a manager started in Observe mode should not switch to Manage mode.
+    #[test]
+    fn mode() {
+        let env = HaEnvironment::new("mode");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let out = env.run_cli(&["mode"]);
+        assert_eq!(String::from_utf8(out.stdout).unwrap(), "manage\n");
+
+        let out = env.run_cli(&["mode", "observe"]);
+        assert!(out.status.success());
+        assert_eq!(
+            String::from_utf8(out.stdout).unwrap(),
+            "Switched to observe mode.\n"
+        );
+        assert!(env
+            .env
+            .manager_log()
+            .contains("Manager switching from Manage to Observe mode."));
+        let out = env.run_cli(&["mode", "observe"]);
+        assert_eq!(
+            String::from_utf8(out.stdout).unwrap(),
+            "Already in observe mode.\n"
+        );
+
+        env.stop_resource("mdt_0", 0);
+        std::thread::sleep(std::time::Duration::from_secs(2));
+        assert!(!env
+            .env
+            .resource_is_started(env.get_resource_by_id("mdt_0"), 0));
+
+        let out = env.run_cli(&["mode", "manage"]);
+        assert!(out.status.success());
+        std::thread::sleep(std::time::Duration::from_secs(2));
+        assert!(env
+            .env
+            .resource_is_started(env.get_resource_by_id("mdt_0"), 0));
+
+        drop(m);
+        let _m = env.start_manager(false);
+        let out = env.run_cli(&["mode", "manage"]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8(out.stderr)
+            .unwrap()
+            .contains("started in Observe mode"));
+        let out = env.run_cli(&["mode"]);
+        assert_eq!(String::from_utf8(out.stdout).unwrap(), "observe\n");
+    }
+
+    /// `halo maintenance on` should stop the manager from acting on any resource, even after it
+    /// restarts, until `halo maintenance off`.
+    #[test]
+    fn maintenance() {
+        let env = HaEnvironment::new("maintenance");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let out = env.run_cli(&["maintenance"]);
+        assert_eq!(String::from_utf8(out.stdout).unwrap(), "off\n");
+
+        let out = env.run_cli(&["maintenance", "on"]);
+        assert!(out.status.success());
+        assert!(String::from_utf8(out.stdout)
+            .unwrap()
+            .starts_with("Maintenance mode is on:"));
+        assert!(env
+            .env
+            .manager_log()
+            .contains("Maintenance mode switched on"));
+        let out = env.run_cli(&["maintenance", "on"]);
+        assert_eq!(
+            String::from_utf8(out.stdout).unwrap(),
+            "Maintenance mode is already on.\n"
+        );
+        let out = env.run_cli(&["status"]);
+        assert!(String::from_utf8(out.stdout)
+            .unwrap()
+            .starts_with("MAINTENANCE MODE since "));
+
+        env.stop_resource("mdt_0", 0);
+        std::thread::sleep(std::time::Duration::from_secs(2));
+        assert!(!env
+            .env
+            .resource_is_started(env.get_resource_by_id("mdt_0"), 0));
+
+        // It stays on when the manager restarts:
+        drop(m);
+        let _m = env.start_manager(true);
+        std::thread::sleep(std::time::Duration::from_secs(2));
+        assert!(!env
+            .env
+            .resource_is_started(env.get_resource_by_id("mdt_0"), 0));
+        let out = env.run_cli(&["maintenance"]);
+        assert!(String::from_utf8(out.stdout)
+            .unwrap()
+            .starts_with("on since "));
+        assert!(env
+            .env
+            .manager_log()
+            .contains("Maintenance mode has been on since"));
+
+        let out = env.run_cli(&["maintenance", "off"]);
+        assert_eq!(
+            String::from_utf8(out.stdout).unwrap(),
+            "Maintenance mode is off.\n"
+        );
+        std::thread::sleep(std::time::Duration::from_secs(2));
+        assert!(env
+            .env
+            .resource_is_started(env.get_resource_by_id("mdt_0"), 0));
+    }
+
+    /// `halo config dump` should print the settings that the manager resolved, from its command
+    /// line and config files, without giving away its auth token.
+    #[test]
+    fn config_dump() {
+        let env = HaEnvironment::new("config_dump");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let site_config = format!("{}.site.yaml", env.env.config_path());
+        std::fs::write(&site_config, "action_concurrency: 3\n").unwrap();
+        let _m = env.env.start_manager_with_args(
+            true,
+            &[
+                "--auth-token",
+                "secret",
+                "--poll-jitter",
+                "250",
+                "--config",
+                &site_config,
+            ],
+        );
+
+        let run_cli = |args: &[&str]| {
+            std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+                .args(["--socket", &env.socket_path(), "config", "dump"])
+                .args(args)
+                .env("HALO_AUTH_TOKEN", "secret")
+                .output()
+                .unwrap()
+        };
+
+        let output = run_cli(&["--json"]);
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(!stdout.contains("secret"), "{stdout}");
+        let config: http::ConfigJson = serde_json::from_str(&stdout).unwrap();
+        assert_eq!(config.auth_token.as_deref(), Some(http::REDACTED));
+        assert_eq!(config.socket, env.socket_path());
+        assert_eq!(config.mode, http::Mode::Manage);
+        assert_eq!(config.poll_jitter, 250);
+        assert_eq!(config.retry_attempts, 5);
+        assert_eq!(config.action_concurrency, 3);
+        assert_eq!(config.config_files, [env.env.config_path(), site_config]);
+        assert_eq!(config.hosts.len(), 2);
+        assert_eq!(config.resources.len(), 4);
+        let zpool_0 = config
+            .resources
+            .iter()
+            .find(|res| res.id == "zpool_0")
+            .unwrap();
+        assert_eq!(zpool_0.kind, "heartbeat/ZFS");
+        assert_eq!(zpool_0.start_timeout, 300);
+        assert_eq!(zpool_0.source, Some(env.env.config_path()));
+
+        let output = run_cli(&[]);
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(!stdout.contains("secret"), "{stdout}");
+        assert!(stdout.contains("poll_jitter: 250\n"), "{stdout}");
+        assert!(stdout.contains("mode: manage\n"), "{stdout}");
+    }
+
+    /// With --action-concurrency 1, a start should wait for the one before it to finish.
+    #[test]
+    fn action_concurrency() {
+        let env = HaEnvironment::new("action_concurrency");
+        for (id, agent) in [("zpool_0", 0), ("zpool_1", 1)] {
+            env.env
+                .set_resource_hangs(env.get_resource_by_id(id), agent, true);
+        }
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env
+            .env
+            .start_manager_with_args(true, &["--action-concurrency", "1"]);
+
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let starts = |log: &str| log.matches("zfs start").count();
+        assert_eq!(starts(&env.env.agent_log()), 1);
+
+        let addr = commands::ManagerAddress::new(&env.socket_path());
+        let metrics = addr
+            .client()
+            .unwrap()
+            .get(addr.url("/metrics"))
+            .send()
+            .unwrap()
+            .text()
+            .unwrap();
+        assert!(metrics.contains("halo_actions_in_flight 1"));
+
+        std::thread::sleep(std::time::Duration::from_secs(9));
+
+        assert_eq!(starts(&env.env.agent_log()), 2);
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            assert_eq!(res.status, "Running", "{}", res.id);
+        }
+    }
+
+    /// The remote agent, and the manager, should keep answering requests while a resource agent
+    /// script takes a long time to start a resource, but the operations on that resource should
+    /// run one at a time.
+    #[test]
+    fn agent_serves_during_slow_start() {
+        let env = HaEnvironment::new("agent_serves_during_slow_start");
+        env.env
+            .set_resource_hangs(env.get_resource_by_id("zpool_0"), 0, true);
+        let _a = env.start_agent(0);
+
+        let args = manager::Cli {
+            config: vec![env.env.config_path()],
+            ..env.env.manager_args()
+        };
+        let cluster = env.env.cluster(Some(args));
+        let find = |id: &str| cluster.resources().find(|res| res.id == id).unwrap();
+        let (zpool, mdt) = (find("zpool_0"), find("mdt_0"));
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let monitor = async {
+                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+                let began = std::time::Instant::now();
+                let reply = mdt.monitor(Location::Home).await;
+                (reply, began.elapsed())
+            };
+            // A monitor of the resource being started waits for the start to finish, so it finds
+            // the resource running:
+            let monitor_same = async {
+                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+                zpool.monitor(Location::Home).await
+            };
+            let (started, (monitored, elapsed), monitored_same) =
+                tokio::join!(zpool.start(Location::Home), monitor, monitor_same);
+
+            assert!(matches!(
+                started,
+                Ok(AgentReply::Success(ocf::Status::Success))
+            ));
+            assert!(matches!(
+                monitored,
+                Ok(AgentReply::Success(ocf::Status::Error(
+                    ocf::OcfError::ErrNotRunning,
+                    _
+                )))
+            ));
+            assert!(
+                elapsed < std::time::Duration::from_secs(2),
+                "monitor took {elapsed:?}"
+            );
+            assert!(matches!(
+                monitored_same,
+                Ok(AgentReply::Success(ocf::Status::Success))
+            ));
+        });
+
+        // The manager answers for the status of the cluster while an admin's start is running:
+        env.env.stop_resource(env.get_resource_by_id("zpool_0"), 0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(false);
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        std::thread::scope(|scope| {
+            let start = scope.spawn(|| env.run_cli(&["start", "zpool_0"]));
+            std::thread::sleep(std::time::Duration::from_millis(500));
+
+            let began = std::time::Instant::now();
+            let status = get_status(&env.socket_path()).unwrap();
+            let elapsed = began.elapsed();
+            assert!(status.resources.iter().any(|res| res.id == "zpool_0"));
+            assert!(
+                elapsed < std::time::Duration::from_secs(2),
+                "status took {elapsed:?}"
+            );
+
+            let output = start.join().unwrap();
+            assert!(output.status.success(), "{output:?}");
+        });
+    }
+
+    /// `halo describe` should print the detail of a single resource, and fail for one which does
+    /// not exist.
+    #[test]
+    fn describe() {
+        let env = HaEnvironment::new("describe");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let out = env.run_cli(&["describe", "--json", "mdt_0"]);
+        assert!(out.status.success());
+        let res: halo_lib::manager::http::ResourceJson =
+            serde_json::from_slice(&out.stdout).unwrap();
+        assert_eq!(res.id, "mdt_0");
+        assert_eq!(res.status, "Running");
+        assert!(res.managed);
+        assert_eq!(res.location.as_deref(), Some(env.agent_id(0).as_str()));
+        assert_eq!(res.home_node, env.agent_id(0));
+        assert_eq!(res.retries, 0);
+
+        let out = env.run_cli(&["describe", "mdt_0"]);
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        assert!(stdout.starts_with("id: mdt_0\n"));
+        assert!(stdout.contains("\nstatus: Running\n"));
+        assert!(stdout.contains(&format!("\nlocation: {}\n", env.agent_id(0))));
+
+        let out = env.run_cli(&["describe", "no_such_resource"]);
+        assert_eq!(out.status.code(), Some(4));
+        assert!(String::from_utf8_lossy(&out.stderr).contains("resource not found"));
+    }
+
+    /// A disabled resource should be stopped and kept stopped while it is still managed and
+    /// monitored, along with its dependents, until it is enabled again.
+    #[test]
+    fn disable_keeps_resource_stopped() {
+        let env = HaEnvironment::new("disable_keeps_resource_stopped");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let out = env.run_cli(&["disable", "zpool_0"]);
+        assert!(out.status.success());
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "zpool_0: disabled\n");
+
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        for id in ["zpool_0", "mdt_0"] {
+            assert!(!env.env.resource_is_started(env.get_resource_by_id(id), 0));
+            assert!(!env.env.resource_is_started(env.get_resource_by_id(id), 1));
+        }
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        let zpool = cluster_status
+            .resources
+            .iter()
+            .find(|res| res.id == "zpool_0")
+            .unwrap();
+        assert_eq!(zpool.status, "Stopped");
+        assert!(zpool.managed);
+        assert_eq!(zpool.target_state, TargetState::Stopped);
+
+        let out = env.run_cli(&["status"]);
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        assert!(stdout.contains("zpool_0\t (Disabled)"));
+
+        // It is not started, even when asked to:
+        let out = env.run_cli(&["start", "zpool_0"]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("Run 'halo enable zpool_0' first"));
+
+        let out = env.run_cli(&["disable", "zpool_0"]);
+        assert_eq!(
+            String::from_utf8_lossy(&out.stdout),
+            "zpool_0: already disabled\n"
+        );
+
+        let out = env.run_cli(&["enable", "zpool_0"]);
+        assert!(out.status.success());
+
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            assert_eq!(res.status, "Running");
+            assert_eq!(res.target_state, TargetState::Started);
+        }
+    }
+
+    /// A resource should only be started once the resources that it depends on are running, and
+    /// stopping one of those should stop it first, leaving it blocked until the dependency is
+    /// running again.
+    #[test]
+    fn depends_on() {
+        let mut env = HaEnvironment::new("depends_on");
+        env.config.hosts[1]
+            .resources
+            .get_mut("zpool_1")
+            .unwrap()
+            .depends_on = vec!["mdt_0".to_string()];
+        env.env.write_out_config(&env.config);
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        for (id, agent) in [("zpool_0", 0), ("mdt_0", 0), ("zpool_1", 1), ("mdt_1", 1)] {
+            assert!(env
+                .env
+                .resource_is_started(env.get_resource_by_id(id), agent));
+        }
+        let log = env.env.manager_log();
+        let started = |id: &str| {
+            log.find(&format!("Attempting to start resource {id} "))
+                .unwrap()
+        };
+        assert!(started("mdt_0") < started("zpool_1"));
+
+        env.unmanage_resource("zpool_0");
+        let output = env.run_cli(&["stop", "mdt_0"]);
+        assert!(output.status.success());
+        assert!(!env
+            .env
+            .resource_is_started(env.get_resource_by_id("mdt_0"), 0));
+        assert!(!env
+            .env
+            .resource_is_started(env.get_resource_by_id("zpool_1"), 1));
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        for agent in [0, 1] {
+            assert!(!env
+                .env
+                .resource_is_started(env.get_resource_by_id("zpool_1"), agent));
+        }
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        let zpool_1 = cluster_status
+            .resources
+            .iter()
+            .find(|res| res.id == "zpool_1")
+            .unwrap();
+        assert_eq!(zpool_1.status, "Blocked");
+        assert_eq!(zpool_1.comment.as_deref(), Some("waiting for mdt_0"));
+
+        let output = env.run_cli(&["start", "zpool_1"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("'zpool_1' depends on 'mdt_0', which is not running"));
+
+        env.manage_resource("zpool_0");
+        std::thread::sleep(std::time::Duration::from_secs(2));
+        assert!(env
+            .env
+            .resource_is_started(env.get_resource_by_id("mdt_0"), 0));
+        assert!(env
+            .env
+            .resource_is_started(env.get_resource_by_id("zpool_1"), 1));
+    }
+
+    /// A group from the config should be managed and migrated as a unit, and shown together in the
+    /// status.
+    #[test]
+    fn groups() {
+        let mut env = HaEnvironment::new("groups");
+        env.config.groups = std::collections::BTreeMap::from([
+            (
+                "pools".to_string(),
+                vec!["mdt_0".to_string(), "zpool_1".to_string()],
+            ),
+            ("first".to_string(), vec!["zpool_0".to_string()]),
+        ]);
+        env.env.write_out_config(&env.config);
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let output = env.run_cli(&["status"]);
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[0].contains("mdt_1"));
+        assert_eq!(lines[1], "Group @first:");
+        assert!(lines[2].contains("zpool_0"));
+        assert_eq!(lines[3], "Group @pools:");
+        assert!(lines[4].contains("mdt_0"));
+        assert!(lines[5].contains("zpool_1"));
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        assert_eq!(cluster_status.groups.len(), 2);
+        let mdt_0 = cluster_status
+            .resources
+            .iter()
+            .find(|res| res.id == "mdt_0")
+            .unwrap();
+        assert_eq!(mdt_0.groups, vec!["pools".to_string()]);
+
+        let output = env.run_cli(&["unmanage", "@pools"]);
+        assert!(output.status.success());
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            assert!(!res.managed, "{} should be unmanaged", res.id);
+        }
+        let output = env.run_cli(&["manage", "@pools", "@nope"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("Could not update '@nope': group not found."));
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            assert!(res.managed, "{} should be managed", res.id);
+        }
+
+        let target = env.agent_id(1);
+        let output = env.run_cli(&["migrate", "@pools", "--to", &target]);
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            format!(
+                "zpool_0: moved from {} to {target}\nzpool_1 is already running on {target}\n",
+                env.agent_id(0)
+            )
+        );
+        for id in ["zpool_0", "mdt_0", "zpool_1", "mdt_1"] {
+            assert!(env.env.resource_is_started(env.get_resource_by_id(id), 1));
+        }
+
+        // Every resource group is checked before any is moved:
+        env.unmanage_resource("zpool_1");
+        let output = env.run_cli(&["migrate", "@pools", "--to", &env.agent_id(0)]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("Run 'halo manage zpool_1' first"));
+        for id in ["zpool_0", "mdt_0"] {
+            assert!(env.env.resource_is_started(env.get_resource_by_id(id), 1));
+        }
+    }
 
-            let child_resource = config::Resource {
-                kind: "lustre/Lustre".to_string(),
-                parameters: HashMap::from([
-                    ("mountpoint".to_string(), lustre_name()),
-                    ("target".to_string(), lustre_name()),
-                    ("kind".to_string(), "mdt".to_string()),
-                ]),
-                requires: Some(zpool_name()),
-            };
+    /// The CLI's exit status should tell a resource that does not exist apart from a request that
+    /// is invalid.
+    #[test]
+    fn exit_codes() {
+        let env = HaEnvironment::new("exit_codes");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
 
-            let host = config::Host {
-                hostname: format!("127.0.0.1:{}", ports[i]),
-                resources: HashMap::from([
-                    (zpool_name(), root_resource),
-                    (lustre_name(), child_resource),
-                ]),
-                fence_agent: Some("fence_test".to_string()),
-                fence_parameters: Some(HashMap::from([
-                    ("target".to_string(), format!("{test_id}_{i}")),
-                    ("test_id".to_string(), test_id.clone()),
-                ])),
-            };
+        std::thread::sleep(std::time::Duration::from_secs(1));
 
-            config.hosts.push(host);
+        for args in [
+            vec!["start", "no_such_resource"],
+            vec!["unmanage", "no_such_resource"],
+            vec!["migrate", "no_such_resource", "--to", "no_such_host"],
+            vec!["set-param", "no_such_resource", "key=value"],
+        ] {
+            let output = env.run_cli(&args);
+            assert_eq!(output.status.code(), Some(4), "halo {}", args.join(" "));
         }
 
-        config
+        for args in [
+            vec!["migrate", "zpool_0", "--to", "no_such_host"],
+            vec!["set-param", "zpool_0", "no_value"],
+            vec!["--dry-run", "failback", "no_such_host"],
+        ] {
+            let output = env.run_cli(&args);
+            assert_eq!(output.status.code(), Some(2), "halo {}", args.join(" "));
+        }
+
+        assert_eq!(env.run_cli(&["ping"]).status.code(), Some(0));
     }
 
-    /// Make sure that the test environment correctly detects a test that results in a
-    /// "double-started" resource. This should panic the test, normally causing it to fail, but in
-    /// this test we want to make sure the panic happens.
+    /// With --dry-run, the CLI should report what the manager would do, and nothing should change.
     #[test]
-    #[should_panic]
-    fn double_started_resource() {
-        let env = HaEnvironment::new("double_started_resource");
+    fn dry_run() {
+        let env = HaEnvironment::new("dry_run");
         let _a = env.start_agent(0);
         let _b = env.start_agent(1);
         let _m = env.start_manager(true);
 
-        // Sleep for a second to give the manager enough time to start resources...
         std::thread::sleep(std::time::Duration::from_secs(1));
 
-        // Manually start the zpool on the failover node, even though it's already started on the
-        // home node:
-        env.start_resource("zpool_0", 1);
+        let output = env.run_cli(&["--dry-run", "unmanage", "zpool_0"]);
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "dry run: would set resource group 'zpool_0' to unmanaged\n"
+        );
+
+        let output = env.run_cli(&["--dry-run", "stop", "mdt_0"]);
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            format!("dry run: would stop 'mdt_0' on {}\n", env.agent_id(0))
+        );
+
+        let output = env.run_cli(&["--dry-run", "migrate", "zpool_1", "--to", &env.agent_id(0)]);
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            format!(
+                "dry run: would move resource group 'zpool_1' from {} to {}\n",
+                env.agent_id(1),
+                env.agent_id(0)
+            )
+        );
+
+        // A dry run is still checked like the real thing:
+        let output = env.run_cli(&["--dry-run", "migrate", "zpool_1", "--to", "no_such_host"]);
+        assert!(!output.status.success());
+
+        let output = env.run_cli(&["--dry-run", "standby", &env.agent_id(0)]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("--dry-run is not supported by 'halo standby'"));
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        assert!(env
+            .env
+            .resource_is_started(env.get_resource_by_id("mdt_0"), 0));
+        assert!(env
+            .env
+            .resource_is_started(env.get_resource_by_id("zpool_1"), 1));
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        assert!(cluster_status.resources.iter().all(|res| res.managed));
+        assert!(cluster_status.hosts.iter().all(|host| !host.standby));
+
+        let output = env.run_cli(&["history", "--json"]);
+        let entries: Vec<halo_lib::manager::history::Entry> =
+            serde_json::from_slice(&output.stdout).unwrap();
+        assert!(entries.is_empty());
     }
 
-    /// Startup, both agents running, all resources stopped.
-    /// Agents should start resources on their home nodes.
+    /// A manager started with --dry-run should log the resources that it would start, but leave
+    /// them stopped, even when asked to start them.
     #[test]
-    fn startup1() {
-        let env = HaEnvironment::new("startup1");
+    fn dry_run_manager() {
+        let env = HaEnvironment::new("dry_run_manager");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.env.start_manager_with_args(true, &["--dry-run"]);
+
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let log = env.env.manager_log();
+        assert!(log.contains("Manager starting in Dry run mode."));
+        assert!(log.contains(&format!(
+            "Dry run: would start resource group zpool_0 on {}.",
+            env.agent_id(0)
+        )));
+        assert!(log.contains(&format!(
+            "Dry run: would start resource group zpool_1 on {}.",
+            env.agent_id(1)
+        )));
+        // Each intended action is only logged once:
+        assert_eq!(log.matches("would start resource group zpool_0").count(), 1);
+
+        let output = env.run_cli(&["start", "zpool_0"]);
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            format!("dry run: would start 'zpool_0' on {}\n", env.agent_id(0))
+        );
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        for id in ["zpool_0", "mdt_0", "zpool_1"] {
+            for agent in [0, 1] {
+                assert!(!env
+                    .env
+                    .resource_is_started(env.get_resource_by_id(id), agent));
+            }
+        }
+    }
+
+    /// Changing the parameters of a resource should be reflected in its status, and with
+    /// --restart, the resource should be restarted where it is running.
+    #[test]
+    fn set_param() {
+        let env = HaEnvironment::new("set_param");
         let _a = env.start_agent(0);
         let _b = env.start_agent(1);
         let _m = env.start_manager(true);
 
-        // Sleep for a second to give the manager enough time to start resources...
         std::thread::sleep(std::time::Duration::from_secs(1));
 
-        // Then run the status command to be sure the resources started
+        let output = env.run_cli(&["set-param", "zpool_0", "extra=1", "--restart"]);
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("extra: 1"));
+        assert!(stdout.contains("zpool_0: restarted\n"));
+        for id in ["zpool_0", "mdt_0"] {
+            assert!(env.env.resource_is_started(env.get_resource_by_id(id), 0));
+        }
+
         let cluster_status = get_status(&env.socket_path()).unwrap();
-        eprintln!("{cluster_status:?}");
+        let zpool = cluster_status
+            .resources
+            .iter()
+            .find(|res| res.id == "zpool_0")
+            .unwrap();
+        assert_eq!(zpool.parameters.get("extra").unwrap(), "1");
+        assert_eq!(zpool.status, "Running");
+
+        let output = env.run_cli(&["set-param", "zpool_0", "=1"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("parameter names must not be empty"));
+
+        let output = env.run_cli(&["set-param", "zpool_0", "extra"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("expected key=value"));
+
+        // Parameters are checked against the schema for the resource's kind:
+        let output = env.run_cli(&["set-param", "mdt_0", "mountpoint=mnt/mdt_0"]);
+        assert_eq!(output.status.code(), Some(2));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains("mountpoint: expected an absolute path, but got \"mnt/mdt_0\""),
+            "{stderr}"
+        );
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        let mdt = cluster_status
+            .resources
+            .iter()
+            .find(|res| res.id == "mdt_0")
+            .unwrap();
+        assert_ne!(mdt.parameters["mountpoint"], "mnt/mdt_0");
+
+        let output = env.run_cli(&["set-param", "no_such_resource", "extra=1"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("resource not found"));
+    }
 
-        for res in cluster_status.resources {
-            assert_eq!(res.status, "Running");
+    /// Putting a host on standby in Manage mode should move its resources to its partner, and
+    /// keep them from being started there.
+    #[test]
+    fn standby_manage() {
+        let env = HaEnvironment::new("standby_manage");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let tcp_addr = format!("127.0.0.1:{}", get_ports()[0]);
+        let _m = env
+            .env
+            .start_manager_with_args(true, &["--listen", &tcp_addr]);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let host = env.agent_id(0);
+        let output = env.run_cli(&["standby", &host]);
+        assert!(output.status.success());
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        for id in ["zpool_0", "mdt_0"] {
+            assert!(!env.env.resource_is_started(env.get_resource_by_id(id), 0));
+            assert!(env.env.resource_is_started(env.get_resource_by_id(id), 1));
+        }
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in &cluster_status.resources {
+            if res.id.contains("0") {
+                assert_eq!(res.status, "Running (Failed Over)");
+            }
         }
+        assert!(cluster_status
+            .hosts
+            .iter()
+            .any(|h| h.id == host && h.standby));
+
+        let output = env.run_cli(&["status"]);
+        assert!(String::from_utf8(output.stdout)
+            .unwrap()
+            .contains(&format!("Hosts on standby: {host}")));
+
+        let addr = commands::ManagerAddress::new(&tcp_addr);
+        let metrics = addr
+            .client()
+            .unwrap()
+            .get(addr.url("/metrics"))
+            .send()
+            .unwrap()
+            .text()
+            .unwrap();
+        assert!(metrics.contains(&format!("halo_host_standby{{host=\"{host}\"}} 1")));
+
+        let output = env.run_cli(&["unstandby", &host]);
+        assert!(output.status.success());
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        assert!(cluster_status.hosts.iter().all(|h| !h.standby));
+
+        let output = env.run_cli(&["standby", "no_such_host"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("host not found"));
+    }
+
+    /// Managing a resource group whose hosts are all on standby should still work, but warn that
+    /// it will not be started.
+    #[test]
+    fn manage_warns_on_standby() {
+        let env = HaEnvironment::new("manage_warns_on_standby");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        assert!(env.run_cli(&["unmanage", "zpool_0"]).status.success());
+        let output = env.run_cli(&["standby", &env.agent_id(0)]);
+        assert!(output.status.success());
+
+        // Its partner can still run it:
+        let output = env.run_cli(&["manage", "zpool_0"]);
+        assert!(output.status.success());
+        assert!(!String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("Warning"));
+
+        assert!(env.run_cli(&["unmanage", "zpool_0"]).status.success());
+        let output = env.run_cli(&["standby", &env.agent_id(1)]);
+        assert!(output.status.success());
+
+        let output = env.run_cli(&["manage", "zpool_0"]);
+        assert!(output.status.success());
+        assert!(String::from_utf8(output.stderr).unwrap().contains(
+            "Warning: every host that resource group 'zpool_0' may run on is on standby"
+        ));
+
+        let output = env.run_cli(&["manage", "--json", "zpool_0"]);
+        assert!(output.status.success());
+        let results: Vec<halo_lib::manager::http::ManageResult> =
+            serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(results[0].message, "already managed");
+        assert_eq!(results[0].warnings.len(), 1);
     }
 
     /// Startup, one agent stopped, all resources stopped.
@@ -382,6 +3293,195 @@ mod tests {
         }
     }
 
+    /// Failover - resources are not started on the partner until their home host is fenced.
+    #[test]
+    fn failover_waits_for_fence() {
+        let env = HaEnvironment::new("failover_waits_for_fence");
+        let _a = env.start_agent(0);
+        let b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        env.set_fence_fails(1, true);
+        drop(b);
+
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            if res.id.contains("1") {
+                assert_ne!(res.status, "Running (Failed Over)");
+                assert!(!env
+                    .env
+                    .resource_is_started(env.get_resource_by_id(&res.id), 0));
+            }
+        }
+        assert!(env
+            .env
+            .manager_log()
+            .contains("CRITICAL: could not fence host"));
+
+        // Once fencing works, the resources are failed over:
+        env.set_fence_fails(1, false);
+
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            if res.id.contains("1") {
+                assert_eq!(res.status, "Running (Failed Over)");
+            }
+        }
+    }
+
+    /// Failover - once fencing a host has failed too many times in a row, the manager stops trying
+    /// until the breaker is reset, and the host's resources stay stopped.
+    #[test]
+    fn fence_breaker() {
+        let env = HaEnvironment::new("fence_breaker");
+        let _a = env.start_agent(0);
+        let b = env.start_agent(1);
+        let _m = env
+            .env
+            .start_manager_with_args(true, &["--fence-attempts", "2"]);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        env.set_fence_fails(1, true);
+        drop(b);
+
+        std::thread::sleep(std::time::Duration::from_secs(3));
+
+        let out = env.run_cli(&["status"]);
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        assert!(
+            stdout.contains(&format!("Hosts whose fencing failed: {}", env.agent_id(1))),
+            "{stdout}"
+        );
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        let host = cluster_status
+            .hosts
+            .iter()
+            .find(|host| host.id == env.agent_id(1))
+            .unwrap();
+        assert!(host.fence_failed);
+        assert_eq!(host.fence_failures, 2);
+        assert!(env
+            .env
+            .manager_log()
+            .contains("Fencing failed 2 times in a row"));
+
+        // Even once fencing would work, it is not tried until the breaker is reset:
+        env.set_fence_fails(1, false);
+        std::thread::sleep(std::time::Duration::from_secs(2));
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status
+            .resources
+            .iter()
+            .filter(|res| res.id.contains("1"))
+        {
+            assert_ne!(res.status, "Running (Failed Over)");
+        }
+
+        let out = env.run_cli(&["power", "--reset-breaker", &env.agent_id(1)]);
+        assert!(out.status.success(), "{out:?}");
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status
+            .resources
+            .iter()
+            .filter(|res| res.id.contains("1"))
+        {
+            assert_eq!(res.status, "Running (Failed Over)");
+        }
+        assert!(!cluster_status.hosts.iter().any(|host| host.fence_failed));
+
+        let out = env.run_cli(&["power", "--reset-breaker", "no_such_host"]);
+        assert_eq!(out.status.code(), Some(4), "{out:?}");
+    }
+
+    /// Failover - resources are not failed over while the cluster does not have quorum.
+    #[test]
+    fn failover_needs_quorum() {
+        let mut env = HaEnvironment::new("failover_needs_quorum");
+        env.config.quorum = Some(config::QuorumConfig::default());
+        env.env.write_out_config(&env.config);
+        let _a = env.start_agent(0);
+        let b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let out = env.run_cli(&["status"]);
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        assert!(stdout.contains("Quorum: yes (2 of 2 hosts reachable, 2 needed, tiebreak deny)"));
+
+        // With one of two hosts left, the default tiebreak denies quorum:
+        drop(b);
+
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let out = env.run_cli(&["status"]);
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        assert!(stdout.contains("Quorum: LOST (1 of 2 hosts reachable"));
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            if res.id.contains("1") {
+                assert_ne!(res.status, "Running (Failed Over)");
+                assert!(!env
+                    .env
+                    .resource_is_started(env.get_resource_by_id(&res.id), 0));
+            }
+        }
+        assert!(env.env.manager_log().contains("Quorum lost"));
+
+        let out = env.run_cli(&["migrate", "zpool_0", "--to", &env.agent_id(1)]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("does not have quorum"));
+
+        // Once the host is back, so is quorum, and its resources are managed there again:
+        let _b = env.start_agent(1);
+
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        assert!(cluster_status.quorum.unwrap().has_quorum);
+        for res in cluster_status.resources {
+            assert_eq!(res.status, "Running");
+        }
+    }
+
+    /// Failover - with the first_host tiebreak, half of the hosts have quorum if they include the
+    /// first host in the config.
+    #[test]
+    fn failover_quorum_tiebreak() {
+        let mut env = HaEnvironment::new("failover_quorum_tiebreak");
+        env.config.quorum = Some(config::QuorumConfig {
+            expected_hosts: None,
+            tiebreak: halo_lib::manager::quorum::Tiebreak::FirstHost,
+        });
+        env.env.write_out_config(&env.config);
+        let _a = env.start_agent(0);
+        let b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        drop(b);
+
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        assert!(cluster_status.quorum.unwrap().has_quorum);
+        for res in cluster_status.resources {
+            if res.id.contains("1") {
+                assert_eq!(res.status, "Running (Failed Over)");
+            }
+        }
+    }
+
     /// Failover - both resource groups running on same node, both get failed over.
     #[test]
     fn failover2() {
@@ -532,6 +3632,26 @@ mod tests {
         }
     }
 
+    /// Observe mode - test that the manager never starts or stops anything, only monitors.
+    #[test]
+    fn observe_no_actions() {
+        let env = HaEnvironment::new("observe_no_actions");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(false);
+
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let log = env.env.agent_log();
+        assert!(log.lines().any(|line| line.contains(" monitor ")));
+        for line in log.lines() {
+            assert!(
+                !line.contains(" start ") && !line.contains(" stop "),
+                "unexpected action in observe mode: {line}"
+            );
+        }
+    }
+
     /// Observe mode - test that a resource already started shows up as started, and failed over if
     /// appropriate.
     #[test]
@@ -791,4 +3911,260 @@ mod tests {
             }
         }
     }
+
+    /// Resources read from stdin should each be updated, with one line of output per resource and
+    /// a failure exit code if any of them was not found.
+    #[test]
+    fn manage_from_stdin() {
+        let env = HaEnvironment::new("manage_from_stdin");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let output = env.run_cli_with_stdin(
+            &["unmanage", "--from-stdin"],
+            "# pools\nzpool_0\n\nzpool_1\nno_such_resource\n",
+        );
+        assert_eq!(output.status.code(), Some(4));
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "zpool_0: unmanaged\nzpool_1: unmanaged\nno_such_resource: not found\n"
+        );
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("2 succeeded, 1 failed."));
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            assert!(!res.managed);
+        }
+
+        let output = env.run_cli_with_stdin(
+            &["manage", "--from-stdin"],
+            r#"[{"resource": "zpool_0"}, {"resource": "zpool_1", "manage": false}]"#,
+        );
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "zpool_0: managed\nzpool_1: unmanaged\n"
+        );
+
+        let cluster_status = get_status(&env.socket_path()).unwrap();
+        for res in cluster_status.resources {
+            assert_eq!(res.managed, res.id.ends_with('0'), "{}", res.id);
+        }
+
+        let output = env.run_cli_with_stdin(&["manage", "--from-stdin"], "");
+        assert!(output.status.success());
+        assert!(output.stdout.is_empty());
+
+        let output = env.run_cli_with_stdin(&["manage", "--from-stdin"], "[{\"resource\": 0}]");
+        assert_eq!(output.status.code(), Some(2));
+    }
+
+    /// When the manager does not find a resource, the CLI should suggest the closest one in the
+    /// local config.
+    #[test]
+    fn suggest_resource() {
+        let env = HaEnvironment::new("suggest_resource");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let config_path = env.env.config_path();
+        for args in [
+            vec!["start", "zpool_O"],
+            vec!["unmanage", "zpool_O"],
+            vec!["migrate", "zpool_O", "--to", "no_such_host"],
+        ] {
+            let mut full_args = vec!["--config", &config_path];
+            full_args.extend(&args);
+            let output = env.run_cli(&full_args);
+            assert_eq!(output.status.code(), Some(4), "halo {}", args.join(" "));
+            let stderr = String::from_utf8(output.stderr).unwrap();
+            assert!(stderr.contains("Did you mean 'zpool_0'?"), "{stderr}");
+        }
+
+        // Nothing is suggested for an ID which is not close to any resource:
+        let output = env.run_cli(&["--config", &config_path, "start", "no_such_resource"]);
+        assert!(!String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("Did you mean"));
+
+        // A caller of the commands can tell that what it named was not found:
+        match commands::failback::do_failback(&env.socket_path(), "no_such_host") {
+            Err(commands::CommandError::NotFound(message)) => {
+                assert!(message.contains("host not found"), "{message}")
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+        match commands::manage::send_command(&Some(env.socket_path()), "zpool_O", false) {
+            Err(commands::CommandError::NotFound(message)) => {
+                assert!(message.contains("resource group not found"), "{message}")
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    /// A batch request should report the outcome for each resource group, with a status that
+    /// says whether all, some, or none of the entries were found.
+    #[test]
+    fn manage_batch_results() {
+        use halo_lib::manager::http;
+
+        let env = HaEnvironment::new("manage_batch_results");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let addr = commands::ManagerAddress::new(&env.socket_path());
+        let client = addr.client().unwrap();
+        let unmanage = |resources: &[&str]| {
+            let response = client
+                .patch(addr.url("/resources"))
+                .json(&http::SetManagedBatchArgs {
+                    resources: resources.iter().map(|id| id.to_string()).collect(),
+                    managed: false,
+                    matching: http::Matching::Exact,
+                    dry_run: false,
+                    groups: Vec::new(),
+                    force: false,
+                })
+                .send()
+                .unwrap();
+            let status = response.status().as_u16();
+            let reply: http::SetManagedBatchReply = response.json().unwrap();
+            (status, reply.results)
+        };
+
+        let (status, results) = unmanage(&["zpool_0"]);
+        assert_eq!(status, 200);
+        assert_eq!(
+            results,
+            vec![http::ManageResult {
+                resource: "zpool_0".to_string(),
+                ok: true,
+                message: "now unmanaged".to_string(),
+                code: None,
+                warnings: Vec::new(),
+            }]
+        );
+
+        // A resource group which is already unmanaged fails as it does on its own:
+        let (status, results) = unmanage(&["zpool_0"]);
+        assert_eq!(status, 409);
+        assert!(!results[0].ok);
+        assert_eq!(results[0].message, "already unmanaged");
+        assert_eq!(results[0].code, Some(http::ErrorCode::AlreadyInState));
+
+        let output = env.run_cli(&["history", "--json"]);
+        let entries: Vec<halo_lib::manager::history::Entry> =
+            serde_json::from_slice(&output.stdout).unwrap();
+        let results: Vec<&str> = entries
+            .iter()
+            .filter(|entry| entry.resource == "zpool_0" && entry.action == "unmanage")
+            .map(|entry| entry.result.as_str())
+            .collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0], "ok");
+        assert!(results[1].contains("already unmanaged"), "{}", results[1]);
+
+        let (status, results) = unmanage(&["zpool_0", "no_such_resource"]);
+        assert_eq!(status, 207);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].code, Some(http::ErrorCode::AlreadyInState));
+        assert!(!results[1].ok);
+        assert_eq!(results[1].resource, "no_such_resource");
+        assert_eq!(results[1].code, Some(http::ErrorCode::NotFound));
+
+        let (status, results) = unmanage(&["no_such_resource"]);
+        assert_eq!(status, 404);
+        assert_eq!(results[0].message, "resource group not found");
+
+        let output = env.run_cli(&["manage", "--json", "zpool_[0-1]", "no_such_resource"]);
+        assert_eq!(output.status.code(), Some(4));
+        let results: Vec<http::ManageResult> = serde_json::from_slice(&output.stdout).unwrap();
+        let outcomes: Vec<(&str, bool)> = results
+            .iter()
+            .map(|result| (result.resource.as_str(), result.ok))
+            .collect();
+        // zpool_1 was already managed:
+        assert_eq!(
+            outcomes,
+            vec![
+                ("zpool_0", true),
+                ("zpool_1", false),
+                ("no_such_resource", false)
+            ]
+        );
+        assert_eq!(results[1].code, Some(http::ErrorCode::AlreadyInState));
+
+        let output = env.run_cli(&["-v", "unmanage", "zpool_1"]);
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "zpool_1  ok      now unmanaged\n"
+        );
+
+        let output = env.run_cli(&["-v", "unmanage", "zpool_1"]);
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "zpool_1  no-op   already unmanaged\n"
+        );
+    }
+
+    /// A shell session should send each command over a single WebSocket, report the replies as
+    /// the commands themselves do, and print the events it subscribed to.
+    #[test]
+    fn shell_session() {
+        use halo_lib::manager::http;
+
+        let env = HaEnvironment::new("shell_session");
+        let _a = env.start_agent(0);
+        let _b = env.start_agent(1);
+        let _m = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let output = env.run_cli_with_stdin(
+            &["shell"],
+            "# comment\nsubscribe\nunmanage zpool_0\nunmanage zpool_0\nstatus --prefix zpool_0 \
+             --json\nquit\nstatus\n",
+        );
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        // The second unmanage finds nothing to do, as `halo unmanage` would:
+        assert!(output.status.success(), "{stderr}");
+        assert!(
+            stderr.contains("Note: resource group 'zpool_0' is already unmanaged."),
+            "{stderr}"
+        );
+        assert!(stdout.contains("zpool_0: unmanaged\n"), "{stdout}");
+        let json = &stdout[stdout.find("[\n").unwrap()..];
+        let resources: Vec<http::ResourceJson> = serde_json::from_str(json).unwrap();
+        assert_eq!(resources.len(), 1);
+        assert!(!resources[0].managed);
+
+        // With --json, each message is printed as it was sent:
+        let output = env.run_cli_with_stdin(&["shell", "--json"], "manage zpool_0 no_such\n");
+        assert_eq!(output.status.code(), Some(4));
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let reply: http::WsReply = serde_json::from_str(stdout.trim()).unwrap();
+        let http::WsReply::Manage { id, status, reply } = reply else {
+            panic!("unexpected reply: {stdout}");
+        };
+        assert_eq!((id, status), (1, 207));
+        assert_eq!(reply.updated, vec!["zpool_0".to_string()]);
+        assert_eq!(reply.not_found, vec!["no_such".to_string()]);
+
+        let output = env.run_cli_with_stdin(&["shell"], "status --bogus\n");
+        assert_eq!(output.status.code(), Some(2));
+    }
 }
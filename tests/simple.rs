@@ -128,12 +128,35 @@ mod tests {
         assert!(powered_on);
     }
 
+    /// Without failover pairs, the manager should still start resources in Manage mode.
+    #[test]
+    fn manage_non_ha() {
+        let env = test_env_helper("manage_non_ha");
+
+        let config_str = std::fs::read_to_string(test_path("simple.yaml")).unwrap();
+        let mut config: halo_lib::config::Config = serde_yaml::from_str(&config_str).unwrap();
+        config.hosts[0].hostname = "127.0.0.1:8007".to_string();
+        env.write_out_config(&config);
+
+        let _agent = env.start_remote_agents(vec![TestAgent::new(8007, None)]);
+        let _manager = env.start_manager(true);
+
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let cluster_status = halo_lib::commands::status::get_status(&env.socket_path()).unwrap();
+        assert_eq!(cluster_status.resources.len(), 4);
+        for res in cluster_status.resources {
+            assert_eq!(res.status, "Running");
+        }
+        assert!(env.agent_log().contains("zfs start pool=test_zpool"));
+    }
+
     #[test]
     fn failover_partners() {
         let config_path = halo_lib::test_env::test_path("failover.yaml");
         let config_str = std::fs::read_to_string(std::path::Path::new(&config_path)).unwrap();
         let config: halo_lib::config::Config = serde_yaml::from_str(&config_str).unwrap();
-        let cluster = halo_lib::cluster::Cluster::from_config(Some(config_path)).unwrap();
+        let cluster = halo_lib::cluster::Cluster::from_config(vec![config_path]).unwrap();
         let failover_pairs = config.failover_pairs.unwrap();
         cluster.hosts().for_each(|h| {
             let host_str = h.address();
@@ -149,7 +172,7 @@ mod tests {
         let partner_set_res = first_host.set_failover_partner(Some(first_host_partner));
         assert_eq!(
             partner_set_res,
-            halo_lib::commands::HandledResult::Err(halo_lib::commands::HandledError {})
+            halo_lib::commands::HandledResult::Err(halo_lib::commands::HandledError::default())
         );
     }
 }
@@ -1,11 +1,11 @@
 // SPDX-License-Identifier: MIT
 // Copyright 2025. Triad National Security, LLC.
 
-/// Usability tests
-///
-/// The purpose of these tests is to ensure that running the binaries with invalid arguments or
-/// missing files (i.e., missing configuration files) results in a failing exit status and a useful
-/// error message.
+//! Usability tests
+//!
+//! The purpose of these tests is to ensure that running the binaries with invalid arguments or
+//! missing files (i.e., missing configuration files) results in a failing exit status and a useful
+//! error message.
 
 #[cfg(test)]
 mod tests {
@@ -35,6 +35,226 @@ mod tests {
         assert!(err_message.contains(invalid_config));
     }
 
+    #[test]
+    fn manager_default_config_missing() {
+        let missing_config = "/this/path/does/not/exist.conf";
+        let result = std::process::Command::new(env!("CARGO_BIN_EXE_halo_manager"))
+            .env("HALO_CONFIG", missing_config)
+            .output()
+            .unwrap();
+
+        assert!(!result.status.success());
+        let err_message = String::from_utf8(result.stderr).unwrap();
+        assert!(err_message.contains("No config file found at the default path"));
+        assert!(err_message.contains(missing_config));
+    }
+
+    #[test]
+    fn manager_config_malformed() {
+        let config_path =
+            std::env::temp_dir().join(format!("halo_malformed_{}.yaml", std::process::id()));
+        std::fs::write(&config_path, "hosts:\n- resources: {}\n").unwrap();
+        let result = std::process::Command::new(env!("CARGO_BIN_EXE_halo_manager"))
+            .arg("--config")
+            .arg(&config_path)
+            .output()
+            .unwrap();
+        let _ = std::fs::remove_file(&config_path);
+
+        assert!(!result.status.success());
+        let err_message = String::from_utf8(result.stderr).unwrap();
+        assert!(err_message.contains("missing field `hostname`"));
+        assert!(err_message.contains("line 2"));
+    }
+
+    #[test]
+    fn validate_reports_all_problems() {
+        let config_path =
+            std::env::temp_dir().join(format!("halo_validate_{}.yaml", std::process::id()));
+        let config = "hosts:
+- hostname: oss00
+  resources:
+    ost0:
+      kind: lustre/Lustre
+      parameters: {}
+      requires: zpool9
+  fence_agent: no_such_agent
+  fence_parameters: null
+failover_pairs: null
+";
+        std::fs::write(&config_path, config).unwrap();
+        let result = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+            .arg("validate")
+            .arg("--config")
+            .arg(&config_path)
+            .output()
+            .unwrap();
+        let _ = std::fs::remove_file(&config_path);
+
+        assert_eq!(result.status.code(), Some(2));
+        let err_message = String::from_utf8(result.stderr).unwrap();
+        assert!(err_message.contains("hosts[0].fence_agent"));
+        assert!(err_message.contains("hosts[0].resources.ost0.requires"));
+        assert!(err_message.contains("hosts[0].resources.ost0.parameters.target"));
+        assert!(err_message.contains("hosts[0].resources.ost0.parameters.mountpoint"));
+        assert!(err_message.contains("Found 4 problem(s)"));
+    }
+
+    /// Environment variables are substituted into the config before it is validated, so a
+    /// variable which is not set is reported too.
+    #[test]
+    fn validate_substitutes_env() {
+        let config_path =
+            std::env::temp_dir().join(format!("halo_validate_env_{}.yaml", std::process::id()));
+        let config = "hosts:
+- hostname: oss00
+  resources:
+    zpool0: {kind: heartbeat/ZFS, parameters: {pool: '${HALO_TEST_POOL}'}}
+    zpool1: {kind: heartbeat/ZFS, parameters: {pool: '${HALO_TEST_UNSET:-pool1}'}}
+  fence_agent: null
+  fence_parameters: null
+failover_pairs: null
+";
+        std::fs::write(&config_path, config).unwrap();
+        let validate = |pool: Option<&str>| {
+            let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_halo"));
+            command
+                .args(["validate", "--config"])
+                .arg(&config_path)
+                .env_remove("HALO_TEST_UNSET");
+            match pool {
+                Some(pool) => command.env("HALO_TEST_POOL", pool),
+                None => command.env_remove("HALO_TEST_POOL"),
+            };
+            command.output().unwrap()
+        };
+
+        let unset = validate(None);
+        let set = validate(Some("pool0"));
+        let _ = std::fs::remove_file(&config_path);
+
+        assert_eq!(unset.status.code(), Some(2));
+        let err_message = String::from_utf8(unset.stderr).unwrap();
+        assert!(
+            err_message.contains(
+                "hosts[0].resources.zpool0.parameters.pool: environment variable HALO_TEST_POOL \
+                is not set"
+            ),
+            "{err_message}"
+        );
+        assert!(set.status.success(), "{set:?}");
+    }
+
+    /// Several config files, or a directory of them, should be merged in order, and a resource
+    /// should only be defined in one of them.
+    #[test]
+    fn validate_merges_config_files() {
+        let dir = std::env::temp_dir().join(format!("halo_merge_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let base = dir.join("10-base.yaml");
+        let site = dir.join("20-site.yaml");
+        std::fs::write(
+            &base,
+            "hosts:
+- hostname: oss00
+  resources:
+    zpool0: {kind: heartbeat/ZFS, parameters: {pool: pool0}}
+  fence_agent: null
+  fence_parameters: null
+failover_pairs: null
+",
+        )
+        .unwrap();
+        std::fs::write(
+            &site,
+            "hosts:
+- hostname: oss00
+  resources:
+    zpool1: {kind: heartbeat/ZFS, parameters: {pool: pool1}}
+",
+        )
+        .unwrap();
+        std::fs::write(dir.join("README"), "not a config file").unwrap();
+
+        let validate = |args: &[&std::path::Path]| {
+            let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_halo"));
+            command.args(["validate", "--verbose"]);
+            for arg in args {
+                command.arg("--config").arg(arg);
+            }
+            command.output().unwrap()
+        };
+
+        let result = validate(&[&dir]);
+        let out = String::from_utf8(result.stdout).unwrap();
+        assert!(result.status.success(), "{out}");
+        assert!(
+            out.contains(&format!(
+                "Resource zpool0 is defined in \"{}\".",
+                base.display()
+            )),
+            "{out}"
+        );
+        assert!(
+            out.contains(&format!(
+                "Resource zpool1 is defined in \"{}\".",
+                site.display()
+            )),
+            "{out}"
+        );
+
+        let result = validate(&[&base, &base]);
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(result.status.code(), Some(2));
+        let err_message = String::from_utf8(result.stderr).unwrap();
+        assert!(
+            err_message.contains("resource \"zpool0\" is already defined"),
+            "{err_message}"
+        );
+    }
+
+    #[test]
+    fn manager_config_check() {
+        let good_config_path = format!(
+            "{}/{}",
+            std::env::var("CARGO_MANIFEST_DIR").unwrap(),
+            "tests/simple.yaml"
+        );
+        let result = std::process::Command::new(env!("CARGO_BIN_EXE_halo_manager"))
+            .args(["--config", &good_config_path, "--config-check"])
+            .output()
+            .unwrap();
+        assert!(result.status.success());
+        let out = String::from_utf8(result.stdout).unwrap();
+        assert!(out.contains("is valid"), "{out}");
+
+        let config_path =
+            std::env::temp_dir().join(format!("halo_config_check_{}.yaml", std::process::id()));
+        let config = "hosts:
+- hostname: oss00
+  resources:
+    ost0:
+      kind: lustre/Lustre
+      parameters: {}
+      requires: zpool9
+  fence_agent: null
+  fence_parameters: null
+failover_pairs: null
+";
+        std::fs::write(&config_path, config).unwrap();
+        let result = std::process::Command::new(env!("CARGO_BIN_EXE_halo_manager"))
+            .arg("--config")
+            .arg(&config_path)
+            .arg("--config-check")
+            .output()
+            .unwrap();
+        let _ = std::fs::remove_file(&config_path);
+
+        assert_eq!(result.status.code(), Some(2));
+        let err_message = String::from_utf8(result.stderr).unwrap();
+        assert!(err_message.contains("hosts[0].resources.ost0.requires"));
+    }
+
     #[test]
     fn manager_socket() {
         let good_config_path = format!(
@@ -58,6 +278,23 @@ mod tests {
         assert!(err_message.contains(invalid_socket));
     }
 
+    #[test]
+    fn manager_poll_interval_zero() {
+        let good_config_path = format!(
+            "{}/{}",
+            std::env::var("CARGO_MANIFEST_DIR").unwrap(),
+            "tests/simple.yaml"
+        );
+        let result = std::process::Command::new(env!("CARGO_BIN_EXE_halo_manager"))
+            .args(["--config", &good_config_path, "--poll-interval", "0"])
+            .output()
+            .unwrap();
+
+        assert!(!result.status.success());
+        let err_message = String::from_utf8(result.stderr).unwrap();
+        assert!(err_message.contains("Invalid poll interval"));
+    }
+
     #[test]
     fn discover_nodeset() {
         let invalid_nodesets = ["node[", "node[0-2", "node[a]", "host0[-1]"];
@@ -67,9 +304,651 @@ mod tests {
                 .output()
                 .unwrap();
 
-            assert!(!result.status.success());
+            assert_eq!(result.status.code(), Some(2));
+            let err_message = String::from_utf8(result.stderr).unwrap();
+            assert!(err_message.contains(&format!(
+                "Error: could not parse nodeset '{invalid_nodeset}': unable to parse"
+            )));
+            assert!(err_message.contains("e.g. 'node[00-05]'"));
+        }
+    }
+
+    /// Every command that takes nodesets reports one that can not be parsed the same way, before
+    /// it tries to reach the manager.
+    #[test]
+    fn nodeset_errors() {
+        let commands: [&[&str]; 5] = [
+            &["discover", "oss[00-01]", "--exclude", "oss[3-1]"],
+            &["standby", "oss[3-1]"],
+            &["unstandby", "oss[3-1]"],
+            &["manage", "oss[3-1]"],
+            &["power", "status", "oss[3-1]"],
+        ];
+        for args in commands {
+            let result = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+                .args(args)
+                .env("HALO_SOCKET", "/nonexistent/halo.socket")
+                .output()
+                .unwrap();
+
+            assert_eq!(result.status.code(), Some(2), "{args:?}");
+            let err_message = String::from_utf8(result.stderr).unwrap();
+            assert!(
+                err_message.starts_with(
+                    "Error: could not parse nodeset 'oss[3-1]': invalid range: start id is \
+                     greater than end id\n"
+                ),
+                "{args:?}: {err_message}"
+            );
+            assert!(err_message.contains("e.g. 'node[00-05]'"));
+        }
+    }
+
+    #[test]
+    fn discover_unknown_agent_type() {
+        let result = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+            .args(["discover", "--agent-type", "zpool,systemd", "oss00"])
+            .output()
+            .unwrap();
+
+        assert_eq!(result.status.code(), Some(2));
+        let err_message = String::from_utf8(result.stderr).unwrap();
+        assert!(err_message.contains("unknown agent type 'systemd': expected one of zpool, lustre"));
+    }
+
+    #[test]
+    fn discover_exclude_everything() {
+        let result = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+            .args([
+                "discover",
+                "oss[00-01]",
+                "--exclude",
+                "oss00",
+                "--exclude",
+                "oss01",
+            ])
+            .output()
+            .unwrap();
+
+        assert_eq!(result.status.code(), Some(2));
+        let err_message = String::from_utf8(result.stderr).unwrap();
+        assert!(err_message.contains("no hosts remain after exclusions"));
+    }
+
+    #[test]
+    fn discover_unreachable_host() {
+        // A stand-in for ssh, which can not reach oss01, and finds one zpool on each other host:
+        let bin_dir = std::env::temp_dir().join(format!("halo_discover_{}", std::process::id()));
+        std::fs::create_dir_all(&bin_dir).unwrap();
+        let ssh = bin_dir.join("ssh");
+        std::fs::write(
+            &ssh,
+            "#!/bin/sh
+if [ \"$1\" = oss01 ]; then
+    echo \"ssh: connect to host $1 port 22: Connection refused\" >&2
+    exit 255
+fi
+if [ \"$2\" = zpool ]; then
+    echo \"$1e0\"
+fi
+",
+        )
+        .unwrap();
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&ssh, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let path = format!("{}:{}", bin_dir.display(), std::env::var("PATH").unwrap());
+        let discover = |output: &str| {
+            std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+                .args(["discover", "--concurrency", "2", "--output", output])
+                .arg("oss[00-03]")
+                .env("PATH", &path)
+                .output()
+                .unwrap()
+        };
+        let result = discover("text");
+        let json = discover("json");
+        let csv = discover("csv");
+        let wide = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+            .args(["discover", "--format", "wide", "oss[00-03]"])
+            .env("PATH", &path)
+            .output()
+            .unwrap();
+        let lustre_only = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+            .args(["discover", "--agent-type", "lustre", "--output", "json"])
+            .arg("oss[00-03]")
+            .env("PATH", &path)
+            .output()
+            .unwrap();
+
+        // A starter config file is only replaced with --force:
+        let config_path = bin_dir.join("halo.yaml");
+        let config_path = config_path.to_str().unwrap();
+        let emit = |force: bool| {
+            let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_halo"));
+            command
+                .args(["discover", "--emit-config", config_path, "oss[00-03]"])
+                .env("PATH", &path);
+            if force {
+                command.arg("--force");
+            }
+            command.output().unwrap()
+        };
+        let emitted = emit(false);
+        let starter_yaml = std::fs::read_to_string(config_path).unwrap();
+        let refused = emit(false);
+        let forced = emit(true);
+        let validated = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+            .args(["validate", "--config", config_path])
+            .output()
+            .unwrap();
+
+        // Comparing with the config: first the one that was just written, then one which has
+        // drifted from what is found:
+        let diff = |output: &str| {
+            std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+                .args(["--config", config_path, "discover", "--diff"])
+                .args(["--exclude", "oss01", "--output", output, "oss[00-03]"])
+                .env("PATH", &path)
+                .output()
+                .unwrap()
+        };
+        let unchanged = diff("text");
+        let mut drifted: halo_lib::config::Config = serde_yaml::from_str(&starter_yaml).unwrap();
+        drifted.hosts[0].resources.clear();
+        drifted.hosts[1]
+            .resources
+            .get_mut("oss02e0")
+            .unwrap()
+            .parameters
+            .insert("pool".to_string(), "oss02e9".to_string());
+        std::fs::write(config_path, serde_yaml::to_string(&drifted).unwrap()).unwrap();
+        let changed = diff("text");
+        let changed_json = diff("json");
+        let _ = std::fs::remove_dir_all(&bin_dir);
+
+        assert_eq!(unchanged.status.code(), Some(0), "{unchanged:?}");
+        assert_eq!(
+            String::from_utf8_lossy(&unchanged.stdout),
+            "The resources found match the config.\n"
+        );
+        assert_eq!(changed.status.code(), Some(1));
+        assert_eq!(
+            String::from_utf8_lossy(&changed.stdout),
+            "+ oss00e0 (heartbeat/ZFS on oss00)
+~ oss02e0 parameters.pool: \"oss02e9\" -> \"oss02e0\"
+"
+        );
+        assert_eq!(changed_json.status.code(), Some(1));
+        let drift: serde_json::Value = serde_json::from_slice(&changed_json.stdout).unwrap();
+        assert_eq!(drift["added"][0]["id"], "oss00e0");
+        assert_eq!(drift["changed"][0]["field"], "parameters.pool");
+        assert!(drift["removed"].as_array().unwrap().is_empty());
+
+        assert_eq!(emitted.status.code(), Some(1));
+        assert!(emitted.stdout.is_empty());
+        let starter: halo_lib::config::Config = serde_yaml::from_str(&starter_yaml).unwrap();
+        assert_eq!(starter.hosts.len(), 3);
+        let pool = &starter.hosts[2].resources["oss03e0"];
+        assert_eq!(pool.managed, Some(false));
+        assert_eq!(pool.home_node.as_deref(), Some("oss03"));
+        assert_eq!(refused.status.code(), Some(2));
+        assert!(String::from_utf8_lossy(&refused.stderr).contains("pass --force to replace it"));
+        assert_eq!(forced.status.code(), Some(1));
+        assert!(validated.status.success(), "{validated:?}");
+
+        assert_eq!(result.status.code(), Some(1));
+        let config: halo_lib::config::Config = serde_yaml::from_slice(&result.stdout).unwrap();
+        let hostnames: Vec<&str> = config
+            .hosts
+            .iter()
+            .map(|host| host.hostname.as_str())
+            .collect();
+        assert_eq!(hostnames, vec!["oss00", "oss02", "oss03"]);
+        assert!(config.hosts[0].resources.contains_key("oss00e0"));
+
+        let err_message = String::from_utf8(result.stderr).unwrap();
+        assert!(err_message.contains("Could not discover 1 host(s):"));
+        assert!(err_message.contains("oss01: 'zpool list -H -o name' failed"));
+        assert!(err_message.contains("Connection refused"));
+
+        assert_eq!(json.status.code(), Some(1));
+        let reports: serde_json::Value = serde_json::from_slice(&json.stdout).unwrap();
+        let reports = reports.as_array().unwrap();
+        assert_eq!(reports.len(), 4);
+        assert_eq!(reports[1]["hostname"], "oss01");
+        assert_eq!(reports[1]["reachable"], false);
+        assert_eq!(reports[2]["reachable"], true);
+        assert_eq!(reports[2]["resources"]["oss02e0"]["kind"], "heartbeat/ZFS");
+        assert_eq!(reports[2]["resources"]["oss02e0"]["agent"], "zpool");
+
+        // Without the zpool agent, oss01 is not contacted with zpool, and no zpools are found:
+        let err_message = String::from_utf8(lustre_only.stderr).unwrap();
+        assert!(err_message.contains("oss01: 'mount -t lustre' failed"));
+        let reports: serde_json::Value = serde_json::from_slice(&lustre_only.stdout).unwrap();
+        assert!(reports[0]["resources"].as_object().unwrap().is_empty());
+
+        assert_eq!(csv.status.code(), Some(1));
+        let csv = String::from_utf8(csv.stdout).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            rows[0],
+            "hostname,reachable,resource,kind,requires,parameters,error,agent"
+        );
+        assert_eq!(
+            rows[1],
+            "oss00,true,oss00e0,heartbeat/ZFS,,pool=oss00e0,,zpool"
+        );
+        assert!(rows[2].starts_with("oss01,false,,,,,"));
+        assert_eq!(rows.len(), 5);
+
+        assert_eq!(wide.status.code(), Some(1));
+        let wide = String::from_utf8(wide.stdout).unwrap();
+        let rows: Vec<&str> = wide.lines().collect();
+        assert_eq!(rows.len(), 5);
+        assert_eq!(
+            rows[0],
+            "hostname  reachable  resources  zpools  targets  error"
+        );
+        assert_eq!(
+            rows[1],
+            "oss00     yes        1          1       0        -"
+        );
+        assert!(rows[2].starts_with("oss01     no         -          -       -        'zpool list"));
+        assert!(rows.iter().all(|row| row.chars().count() <= 132));
+    }
+
+    #[test]
+    fn discover_where_select() {
+        // A stand-in for ssh, which can not reach oss01, and finds two zpools on oss02 and one on
+        // each other host:
+        let bin_dir = std::env::temp_dir().join(format!("halo_query_{}", std::process::id()));
+        std::fs::create_dir_all(&bin_dir).unwrap();
+        let ssh = bin_dir.join("ssh");
+        std::fs::write(
+            &ssh,
+            "#!/bin/sh
+if [ \"$1\" = oss01 ]; then
+    echo \"ssh: connect to host $1 port 22: Connection refused\" >&2
+    exit 255
+fi
+if [ \"$2\" = zpool ]; then
+    echo \"$1e0\"
+    [ \"$1\" = oss02 ] && echo \"$1e1\"
+fi
+exit 0
+",
+        )
+        .unwrap();
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&ssh, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let path = format!("{}:{}", bin_dir.display(), std::env::var("PATH").unwrap());
+        let discover = |args: &[&str]| {
+            std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+                .arg("discover")
+                .args(args)
+                .arg("oss[00-03]")
+                .env("PATH", &path)
+                .output()
+                .unwrap()
+        };
+        let text = discover(&[
+            "--where",
+            "zpools>=2 || reachable==false",
+            "--select",
+            "hostname,zpools,reachable",
+        ]);
+        let csv = discover(&[
+            "--exclude",
+            "oss01",
+            "--where",
+            "resources>=1 && hostname!=oss02",
+            "--select",
+            "hostname",
+            "--output",
+            "csv",
+        ]);
+        let json = discover(&[
+            "--where",
+            "hostname=='oss03'",
+            "--select",
+            "hostname,resources",
+            "--output",
+            "json",
+        ]);
+        let unknown = discover(&["--where", "cpu_count>=16"]);
+        let not_a_number = discover(&["--where", "zpools>=lots"]);
+        let bad_select = discover(&["--select", "hostname,cpu_count"]);
+        let _ = std::fs::remove_dir_all(&bin_dir);
+
+        // oss01 could not be discovered, so that is still an error:
+        assert_eq!(text.status.code(), Some(1));
+        assert_eq!(
+            String::from_utf8_lossy(&text.stdout),
+            "hostname  zpools  reachable\noss01     0       false\noss02     2       true\n"
+        );
+        assert!(String::from_utf8_lossy(&text.stderr).contains("Could not discover 1 host(s):"));
+
+        assert_eq!(csv.status.code(), Some(0), "{csv:?}");
+        assert_eq!(
+            String::from_utf8_lossy(&csv.stdout),
+            "hostname\noss00\noss03\n"
+        );
+
+        let hosts: serde_json::Value = serde_json::from_slice(&json.stdout).unwrap();
+        assert_eq!(
+            hosts,
+            serde_json::json!([{"hostname": "oss03", "resources": 1}])
+        );
+
+        for (output, message) in [
+            (
+                unknown,
+                "unknown attribute 'cpu_count': expected one of hostname",
+            ),
+            (not_a_number, "'zpools' is a number, not 'lots'"),
+            (bad_select, "unknown attribute 'cpu_count'"),
+        ] {
+            assert_eq!(output.status.code(), Some(2));
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert!(stderr.contains(message), "{stderr}");
+        }
+    }
+
+    #[test]
+    fn completions_without_manager() {
+        for shell in ["bash", "zsh", "fish", "powershell"] {
+            let result = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+                .args(["--socket", "/this/path/does/not/exist.socket"])
+                .args(["completions", shell])
+                .output()
+                .unwrap();
+
+            assert!(result.status.success(), "{shell}");
+            let script = String::from_utf8(result.stdout).unwrap();
+            assert!(script.contains("set-param"), "{shell}");
+        }
+
+        let result = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+            .args(["completions", "no_such_shell"])
+            .output()
+            .unwrap();
+        assert_eq!(result.status.code(), Some(2));
+    }
+
+    #[test]
+    fn describe_schema_without_manager() {
+        let describe = |args: &[&str]| {
+            std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+                .args(["--socket", "/this/path/does/not/exist.socket", "describe"])
+                .args(args)
+                .output()
+                .unwrap()
+        };
+
+        let result = describe(&["--schema", "lustre/Lustre"]);
+        assert!(result.status.success());
+        let stdout = String::from_utf8(result.stdout).unwrap();
+        assert!(stdout.starts_with("kind: lustre/Lustre\n"), "{stdout}");
+        assert!(
+            stdout.contains("  mountpoint (required): an absolute path."),
+            "{stdout}"
+        );
+
+        let result = describe(&["--schema", "heartbeat/ZFS", "--json"]);
+        assert!(result.status.success());
+        let schema: serde_json::Value = serde_json::from_slice(&result.stdout).unwrap();
+        assert_eq!(schema["params"][0]["name"], "pool");
+        assert_eq!(schema["params"][0]["type"], "string");
+        assert_eq!(schema["params"][0]["required"], true);
+
+        let result = describe(&["--schema", "heartbeat/IPaddr2"]);
+        assert_eq!(result.status.code(), Some(4));
+        let stderr = String::from_utf8(result.stderr).unwrap();
+        assert!(
+            stderr.contains("Known kinds: heartbeat/ZFS, lustre/Lustre."),
+            "{stderr}"
+        );
+
+        // Either a resource or a schema must be given, but not both:
+        assert_eq!(describe(&[]).status.code(), Some(2));
+        assert_eq!(
+            describe(&["mdt0", "--schema", "lustre/Lustre"])
+                .status
+                .code(),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn version_build_info() {
+        let version = |args: &[&str]| {
+            let result = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(result.status.success(), "halo {}", args.join(" "));
+            String::from_utf8(result.stdout).unwrap()
+        };
+
+        let short = format!("halo {}\n", env!("CARGO_PKG_VERSION"));
+        assert_eq!(version(&["--version"]), short);
+        assert_eq!(version(&["version"]), short);
+
+        let verbose = version(&["-v", "version"]);
+        assert!(verbose.starts_with(&short));
+        assert!(verbose.contains("\ncommit:   "));
+        assert!(verbose.contains("\nrustc:    rustc "));
+
+        let info: halo_lib::commands::version::BuildInfo =
+            serde_json::from_str(&version(&["version", "--json"])).unwrap();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(info.built.ends_with('Z'));
+    }
+
+    /// With --output-fd, the results of a command should go to that file descriptor, so that a
+    /// script can read them apart from the logs; an fd which can not be written to is an error.
+    #[test]
+    fn output_fd() {
+        let run = |redirect: &str| {
+            std::process::Command::new("sh")
+                .arg("-c")
+                .arg(format!(
+                    "exec \"$0\" --output-fd 3 version --json {redirect}"
+                ))
+                .arg(env!("CARGO_BIN_EXE_halo"))
+                .output()
+                .unwrap()
+        };
+
+        let result = run("3>&2 2>/dev/null");
+        assert!(result.status.success());
+        assert!(result.stdout.is_empty());
+        let info: halo_lib::commands::version::BuildInfo =
+            serde_json::from_slice(&result.stderr).unwrap();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+
+        for (redirect, problem) in [
+            ("3>&-", "--output-fd 3 is not open"),
+            ("3</dev/null", "--output-fd 3 is not open for writing"),
+        ] {
+            let result = run(redirect);
+            assert_eq!(result.status.code(), Some(2), "{redirect}");
+            assert!(result.stdout.is_empty(), "{redirect}");
+            let stderr = String::from_utf8_lossy(&result.stderr);
+            assert_eq!(stderr, format!("Error: {problem}.\n"), "{redirect}");
+        }
+    }
+
+    #[test]
+    fn power_unknown_host() {
+        let config_path = format!(
+            "{}/{}",
+            std::env::var("CARGO_MANIFEST_DIR").unwrap(),
+            "tests/fencing.yaml"
+        );
+        let unknown_host = "no_such_host";
+        let result = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+            .args(vec!["--config", &config_path, "power", "off", unknown_host])
+            .output()
+            .unwrap();
+
+        assert_eq!(result.status.code(), Some(4));
+        let err_message = String::from_utf8(result.stderr).unwrap();
+        assert!(err_message.contains(unknown_host));
+    }
+
+    #[test]
+    fn cli_no_manager() {
+        let missing_socket = "/this/path/does/not/exist.socket";
+        for args in [vec!["status"], vec!["manage", "zpool_0"], vec!["ping"]] {
+            let result = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+                .args(["--socket", missing_socket])
+                .args(args)
+                .output()
+                .unwrap();
+
+            assert_eq!(result.status.code(), Some(3));
+            let err_message = String::from_utf8(result.stderr).unwrap();
+            assert!(err_message.contains(&format!(
+                "Error: could not connect to manager at {missing_socket}"
+            )));
+        }
+    }
+
+    #[test]
+    fn cli_invalid_resource_id() {
+        for args in [
+            vec!["start", ""],
+            vec!["manage", "ost0", "ost 1"],
+            vec!["migrate", "ost\t0", "--to", "oss01"],
+        ] {
+            let result = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+                .args(["--socket", "/this/path/does/not/exist.socket"])
+                .args(&args)
+                .output()
+                .unwrap();
+
+            // The ID is rejected before the CLI tries to reach the manager:
+            assert_eq!(result.status.code(), Some(2), "halo {}", args.join(" "));
             let err_message = String::from_utf8(result.stderr).unwrap();
-            assert!(err_message.contains("nodeset syntax error: unable to parse"));
+            assert!(err_message.contains("resource IDs can not"));
         }
     }
+
+    #[test]
+    fn cli_socket_sources() {
+        let dir = std::env::temp_dir().join(format!("halo_socket_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("halo.conf");
+        std::fs::write(
+            &config_path,
+            "hosts: []\nfailover_pairs: null\nsocket: /config.socket\n",
+        )
+        .unwrap();
+        let not_a_socket = dir.join("not_a_socket");
+        std::fs::write(&not_a_socket, "").unwrap();
+
+        let run = |env: Option<&str>, args: &[&str]| {
+            let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_halo"));
+            command.arg("--config").arg(&config_path).args(args);
+            match env {
+                Some(socket) => command.env("HALO_SOCKET", socket),
+                None => command.env_remove("HALO_SOCKET"),
+            };
+            let output = command.output().unwrap();
+            (
+                output.status.code(),
+                String::from_utf8(output.stderr).unwrap(),
+            )
+        };
+
+        let (_, stderr) = run(None, &["-v", "ping"]);
+        assert!(stderr.contains("Using manager at /config.socket (from the config file)."));
+        let (_, stderr) = run(Some("/env.socket"), &["-v", "ping"]);
+        assert!(stderr.contains("Using manager at /env.socket (from HALO_SOCKET)."));
+        let (_, stderr) = run(
+            Some("/env.socket"),
+            &["-v", "--socket", "/cli.socket", "ping"],
+        );
+        assert!(stderr.contains("Using manager at /cli.socket (from --socket)."));
+
+        let (code, stderr) = run(Some(not_a_socket.to_str().unwrap()), &["status"]);
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(code, Some(3));
+        assert!(stderr.contains("is not a socket"));
+    }
+
+    #[test]
+    fn status_watch_max_retries() {
+        let missing_socket = "/this/path/does/not/exist.socket";
+        let result = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+            .args(["--socket", missing_socket])
+            .args(["status", "--watch", "--interval", "1", "--max-retries", "1"])
+            .output()
+            .unwrap();
+
+        assert_eq!(result.status.code(), Some(3));
+        let err_message = String::from_utf8(result.stderr).unwrap();
+        assert!(err_message.contains(&format!(
+            "Warning: could not connect to manager at {missing_socket}; retrying in 1s."
+        )));
+        assert!(err_message.contains("giving up after 2 attempts"));
+    }
+
+    #[test]
+    fn cli_manager_timeout() {
+        // A listener which never accepts connections stands in for a wedged manager:
+        let socket_path = std::env::temp_dir().join(format!("halo_timeout_{}", std::process::id()));
+        let _ = std::fs::remove_file(&socket_path);
+        let _listener = std::os::unix::net::UnixListener::bind(&socket_path).unwrap();
+
+        let result = std::process::Command::new(env!("CARGO_BIN_EXE_halo"))
+            .args(["--timeout", "1", "status", "--socket"])
+            .arg(&socket_path)
+            .output()
+            .unwrap();
+        let _ = std::fs::remove_file(&socket_path);
+
+        assert_eq!(result.status.code(), Some(3));
+        let err_message = String::from_utf8(result.stderr).unwrap();
+        assert!(err_message.contains("Error: manager did not respond within 1s"));
+    }
+
+    #[test]
+    fn manager_log_file_unwritable() {
+        // The log file's directory can not be created, since its parent is a regular file:
+        let log_file = format!(
+            "{}/Cargo.toml/halo.log",
+            std::env::var("CARGO_MANIFEST_DIR").unwrap()
+        );
+        let result = std::process::Command::new(env!("CARGO_BIN_EXE_halo_manager"))
+            .args(["--log-file", &log_file])
+            .output()
+            .unwrap();
+
+        assert!(!result.status.success());
+        let err_message = String::from_utf8(result.stderr).unwrap();
+        assert!(err_message.contains(&log_file));
+    }
+
+    #[test]
+    fn manager_mtls_missing_certs() {
+        let good_config_path = format!(
+            "{}/{}",
+            std::env::var("CARGO_MANIFEST_DIR").unwrap(),
+            "tests/simple.yaml"
+        );
+        let missing_cert = "this_cert_does_not_exist.crt";
+        let result = std::process::Command::new(env!("CARGO_BIN_EXE_halo_manager"))
+            .args(vec!["--config", &good_config_path, "--mtls"])
+            .env("HALO_CLIENT_CERT", missing_cert)
+            .output()
+            .unwrap();
+
+        assert!(!result.status.success());
+        let err_message = String::from_utf8(result.stderr).unwrap();
+        assert!(err_message.contains(missing_cert));
+    }
 }